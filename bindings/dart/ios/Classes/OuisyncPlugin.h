@@ -83,6 +83,10 @@ enum ErrorCode {
    * Entry has been changed and no longer matches the expected value
    */
   EntryChanged = 16,
+  /**
+   * The file is not a repository or is corrupted
+   */
+  NotARepository = 17,
   VfsInvalidMountPoint = 2048,
   VfsDriverInstall = (2048 + 1),
   VfsBackend = (2048 + 2),
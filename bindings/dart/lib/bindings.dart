@@ -17,6 +17,8 @@ typedef _session_create_c = SessionCreateResult Function(
   Pointer<Char>,
   Pointer<Char>,
   Pointer<Char>,
+  Uint16,
+  Pointer<Char>,
   Pointer<NativeFunction<PostCObject>>,
   Int64,
 );
@@ -25,10 +27,15 @@ typedef session_create_dart = SessionCreateResult Function(
   Pointer<Char>,
   Pointer<Char>,
   Pointer<Char>,
+  int,
+  Pointer<Char>,
   Pointer<NativeFunction<PostCObject>>,
   int,
 );
 
+typedef _session_set_trace_path_c = Uint16 Function(Uint64, Pointer<Char>);
+typedef session_set_trace_path_dart = int Function(int, Pointer<Char>);
+
 typedef _session_channel_send_c = Void Function(Uint64, Pointer<Uint8>, Uint64);
 typedef session_channel_send_dart = void Function(int, Pointer<Uint8>, int);
 
@@ -40,9 +47,17 @@ typedef session_close_dart = void Function(
 typedef _session_close_blocking_c = Void Function(Uint64);
 typedef session_close_blocking_dart = void Function(int);
 
-typedef _file_copy_to_raw_fd_c = Void Function(
+typedef _file_copy_to_raw_fd_c = Uint64 Function(
+    Uint64, Uint64, Int, Pointer<NativeFunction<PostCObject>>, Int64);
+typedef file_copy_to_raw_fd_dart = int Function(
+    int, int, int, Pointer<NativeFunction<PostCObject>>, int);
+
+typedef _file_cancel_copy_c = Void Function(Uint64, Uint64);
+typedef file_cancel_copy_dart = void Function(int, int);
+
+typedef _repository_archive_raw_fd_c = Void Function(
     Uint64, Uint64, Int, Pointer<NativeFunction<PostCObject>>, Int64);
-typedef file_copy_to_raw_fd_dart = void Function(
+typedef repository_archive_raw_fd_dart = void Function(
     int, int, int, Pointer<NativeFunction<PostCObject>>, int);
 
 typedef _log_print_c = Void Function(Uint8, Pointer<Char>, Pointer<Char>);
@@ -66,6 +81,10 @@ class Bindings {
       : session_create = library
             .lookup<NativeFunction<_session_create_c>>('session_create_dart')
             .asFunction(),
+        session_set_trace_path = library
+            .lookup<NativeFunction<_session_set_trace_path_c>>(
+                'session_set_trace_path')
+            .asFunction(),
         session_channel_send = library
             .lookup<NativeFunction<_session_channel_send_c>>(
                 'session_channel_send')
@@ -81,6 +100,17 @@ class Bindings {
             .lookup<NativeFunction<_file_copy_to_raw_fd_c>>(
                 'file_copy_to_raw_fd_dart')
             .asFunction(),
+        file_cancel_copy = library
+            .lookup<NativeFunction<_file_cancel_copy_c>>('file_cancel_copy')
+            .asFunction(),
+        repository_export_archive_to_raw_fd = library
+            .lookup<NativeFunction<_repository_archive_raw_fd_c>>(
+                'repository_export_archive_to_raw_fd_dart')
+            .asFunction(),
+        repository_import_archive_from_raw_fd = library
+            .lookup<NativeFunction<_repository_archive_raw_fd_c>>(
+                'repository_import_archive_from_raw_fd_dart')
+            .asFunction(),
         log_print = library
             .lookup<NativeFunction<_log_print_c>>('log_print')
             .asFunction(),
@@ -89,10 +119,14 @@ class Bindings {
             .asFunction();
 
   final session_create_dart session_create;
+  final session_set_trace_path_dart session_set_trace_path;
   final session_channel_send_dart session_channel_send;
   final session_close_dart session_close;
   final session_close_blocking_dart session_close_blocking;
   final file_copy_to_raw_fd_dart file_copy_to_raw_fd;
+  final file_cancel_copy_dart file_cancel_copy;
+  final repository_archive_raw_fd_dart repository_export_archive_to_raw_fd;
+  final repository_archive_raw_fd_dart repository_import_archive_from_raw_fd;
   final log_print_dart log_print;
   final free_string_dart free_string;
 }
@@ -23,15 +23,42 @@ enum AccessMode {
 
 }
 
+enum CollisionPolicy {
+  skip,
+  overwrite,
+  rename,
+  ;
+
+  static CollisionPolicy decode(int n) {
+    switch (n) {
+      case 0: return CollisionPolicy.skip;
+      case 1: return CollisionPolicy.overwrite;
+      case 2: return CollisionPolicy.rename;
+      default: throw ArgumentError('invalid value: $n');
+    }
+  }
+
+  int encode() {
+    switch (this) {
+      case CollisionPolicy.skip: return 0;
+      case CollisionPolicy.overwrite: return 1;
+      case CollisionPolicy.rename: return 2;
+    }
+  }
+
+}
+
 enum EntryType {
   file,
   directory,
+  link,
   ;
 
   static EntryType decode(int n) {
     switch (n) {
       case 1: return EntryType.file;
       case 2: return EntryType.directory;
+      case 3: return EntryType.link;
       default: throw ArgumentError('invalid value: $n');
     }
   }
@@ -40,6 +67,7 @@ enum EntryType {
     switch (this) {
       case EntryType.file: return 1;
       case EntryType.directory: return 2;
+      case EntryType.link: return 3;
     }
   }
 
@@ -62,6 +90,14 @@ enum ErrorCode {
   connectionLost,
   invalidHandle,
   entryChanged,
+  notARepository,
+  quotaExceeded,
+  tokenExpired,
+  wrongPassword,
+  storeLocked,
+  networkUnavailable,
+  cancelled,
+  archiveVersionMismatch,
   vfsInvalidMountPoint,
   vfsDriverInstall,
   vfsBackend,
@@ -86,6 +122,14 @@ enum ErrorCode {
       case 14: return ErrorCode.connectionLost;
       case 15: return ErrorCode.invalidHandle;
       case 16: return ErrorCode.entryChanged;
+      case 17: return ErrorCode.notARepository;
+      case 18: return ErrorCode.quotaExceeded;
+      case 19: return ErrorCode.tokenExpired;
+      case 20: return ErrorCode.wrongPassword;
+      case 21: return ErrorCode.storeLocked;
+      case 22: return ErrorCode.networkUnavailable;
+      case 23: return ErrorCode.cancelled;
+      case 24: return ErrorCode.archiveVersionMismatch;
       case 2048: return ErrorCode.vfsInvalidMountPoint;
       case 2049: return ErrorCode.vfsDriverInstall;
       case 2050: return ErrorCode.vfsBackend;
@@ -112,6 +156,14 @@ enum ErrorCode {
       case ErrorCode.connectionLost: return 14;
       case ErrorCode.invalidHandle: return 15;
       case ErrorCode.entryChanged: return 16;
+      case ErrorCode.notARepository: return 17;
+      case ErrorCode.quotaExceeded: return 18;
+      case ErrorCode.tokenExpired: return 19;
+      case ErrorCode.wrongPassword: return 20;
+      case ErrorCode.storeLocked: return 21;
+      case ErrorCode.networkUnavailable: return 22;
+      case ErrorCode.cancelled: return 23;
+      case ErrorCode.archiveVersionMismatch: return 24;
       case ErrorCode.vfsInvalidMountPoint: return 2048;
       case ErrorCode.vfsDriverInstall: return 2049;
       case ErrorCode.vfsBackend: return 2050;
@@ -121,6 +173,37 @@ enum ErrorCode {
 
 }
 
+enum LockReason {
+  unlocked,
+  needsReadPassword,
+  needsWritePassword,
+  wrongPassword,
+  noReadSecrets,
+  ;
+
+  static LockReason decode(int n) {
+    switch (n) {
+      case 0: return LockReason.unlocked;
+      case 1: return LockReason.needsReadPassword;
+      case 2: return LockReason.needsWritePassword;
+      case 3: return LockReason.wrongPassword;
+      case 4: return LockReason.noReadSecrets;
+      default: throw ArgumentError('invalid value: $n');
+    }
+  }
+
+  int encode() {
+    switch (this) {
+      case LockReason.unlocked: return 0;
+      case LockReason.needsReadPassword: return 1;
+      case LockReason.needsWritePassword: return 2;
+      case LockReason.wrongPassword: return 3;
+      case LockReason.noReadSecrets: return 4;
+    }
+  }
+
+}
+
 enum LogLevel {
   error,
   warn,
@@ -152,15 +235,39 @@ enum LogLevel {
 
 }
 
+enum MergeStrategy {
+  unionEntries,
+  lastWriterWins,
+  ;
+
+  static MergeStrategy decode(int n) {
+    switch (n) {
+      case 0: return MergeStrategy.unionEntries;
+      case 1: return MergeStrategy.lastWriterWins;
+      default: throw ArgumentError('invalid value: $n');
+    }
+  }
+
+  int encode() {
+    switch (this) {
+      case MergeStrategy.unionEntries: return 0;
+      case MergeStrategy.lastWriterWins: return 1;
+    }
+  }
+
+}
+
 enum NetworkEvent {
   protocolVersionMismatch,
   peerSetChange,
+  externalAddrChange,
   ;
 
   static NetworkEvent decode(int n) {
     switch (n) {
       case 0: return NetworkEvent.protocolVersionMismatch;
       case 1: return NetworkEvent.peerSetChange;
+      case 2: return NetworkEvent.externalAddrChange;
       default: throw ArgumentError('invalid value: $n');
     }
   }
@@ -169,6 +276,7 @@ enum NetworkEvent {
     switch (this) {
       case NetworkEvent.protocolVersionMismatch: return 0;
       case NetworkEvent.peerSetChange: return 1;
+      case NetworkEvent.externalAddrChange: return 2;
     }
   }
 
@@ -210,6 +318,7 @@ enum PeerStateKind {
   connecting,
   handshaking,
   active,
+  dead,
   ;
 
   static PeerStateKind decode(int n) {
@@ -218,6 +327,7 @@ enum PeerStateKind {
       case 1: return PeerStateKind.connecting;
       case 2: return PeerStateKind.handshaking;
       case 3: return PeerStateKind.active;
+      case 4: return PeerStateKind.dead;
       default: throw ArgumentError('invalid value: $n');
     }
   }
@@ -228,6 +338,7 @@ enum PeerStateKind {
       case PeerStateKind.connecting: return 1;
       case PeerStateKind.handshaking: return 2;
       case PeerStateKind.active: return 3;
+      case PeerStateKind.dead: return 4;
     }
   }
 
@@ -255,3 +366,28 @@ enum SessionKind {
 
 }
 
+enum TransportPreference {
+  either,
+  quic,
+  tcp,
+  ;
+
+  static TransportPreference decode(int n) {
+    switch (n) {
+      case 0: return TransportPreference.either;
+      case 1: return TransportPreference.quic;
+      case 2: return TransportPreference.tcp;
+      default: throw ArgumentError('invalid value: $n');
+    }
+  }
+
+  int encode() {
+    switch (this) {
+      case TransportPreference.either: return 0;
+      case TransportPreference.quic: return 1;
+      case TransportPreference.tcp: return 2;
+    }
+  }
+
+}
+
@@ -16,13 +16,17 @@ import 'state_monitor.dart';
 export 'bindings.dart'
     show
         AccessMode,
+        CollisionPolicy,
         EntryType,
         ErrorCode,
+        LockReason,
         LogLevel,
+        MergeStrategy,
         NetworkEvent,
         PeerSource,
         PeerStateKind,
-        SessionKind;
+        SessionKind,
+        TransportPreference;
 
 part 'local_secret.dart';
 
@@ -47,22 +51,41 @@ class Session {
   /// [configPath] is a path to a directory where configuration files shall be stored. If it
   /// doesn't exists, it will be created.
   /// [logPath] is a path to the log file. If null, logs will be printed to standard output.
+  /// [workerThreads] is the number of threads of the async runtime. If null, one thread per CPU
+  /// core is used. It only takes effect if this call actually creates a new session, that is, not
+  /// when a shared session already exists.
+  /// [dhtContactsPath] is a path to a directory where the DHT contacts are stored so that the DHT
+  /// doesn't have to bootstrap from scratch after a restart. If null, [configPath] is used. Like
+  /// [workerThreads], it only takes effect if this call actually creates a new session.
   static Session create({
     SessionKind kind = SessionKind.shared,
     required String configPath,
     String? logPath,
     String logTag = defaultLogTag,
+    int? workerThreads,
+    String? dhtContactsPath,
   }) {
     if (debugTrace) {
       print("Session.open $configPath");
     }
 
+    // Checked here because the native function takes a 16-bit integer.
+    if (workerThreads != null &&
+        (workerThreads < 0 || workerThreads > 0xffff)) {
+      throw Error(ErrorCode.invalidArgument,
+          'invalid number of worker threads: $workerThreads');
+    }
+
     final recvPort = ReceivePort();
     final result = _withPoolSync((pool) => bindings.session_create(
           kind.encode(),
           pool.toNativeUtf8(configPath),
           logPath != null ? pool.toNativeUtf8(logPath) : nullptr,
           pool.toNativeUtf8(logTag),
+          workerThreads ?? 0,
+          dhtContactsPath != null
+              ? pool.toNativeUtf8(dhtContactsPath)
+              : nullptr,
           NativeApi.postCObject,
           recvPort.sendPort.nativePort,
         ));
@@ -85,6 +108,24 @@ class Session {
 
   String? get mountPoint => _mountPoint;
 
+  /// Number of worker threads of the async runtime of this session.
+  Future<int> get workerThreads =>
+      _client.invoke<int>('session_worker_threads');
+
+  /// Starts recording all the messages exchanged with the native library into the file at
+  /// [path] (appending to it if it exists), or stops recording if [path] is null. Useful to
+  /// capture a reproducible trace of a bug report. Messages carrying secrets (passwords, share
+  /// tokens, ...) are redacted, everything else is recorded in full.
+  void setTracePath(String? path) {
+    final errorCode = ErrorCode.decode(_withPoolSync((pool) =>
+        bindings.session_set_trace_path(
+            _client.handle, path != null ? pool.toNativeUtf8(path) : nullptr)));
+
+    if (errorCode != ErrorCode.ok) {
+      throw Error(errorCode, 'failed to set trace path: $path');
+    }
+  }
+
   // Mount all repositories that are open now or in future in read or
   // read/write mode into the `mountPoint`. The `mountPoint` may point to an
   // empty directory or may be a drive letter.
@@ -119,8 +160,31 @@ class Session {
     });
   }
 
+  /// Restricts the network to the network interface with the given [name] (e.g. `wg0`), or lifts
+  /// the restriction if `null`. Unspecified bind addresses are resolved to the current addresses
+  /// of the interface and re-resolved when they change. Binding the sockets to the interface
+  /// itself is supported only on Linux and Android, elsewhere they are bound only to its
+  /// addresses. Local discovery is restricted to the interface as well.
+  Future<void> setBindInterface(String? name) =>
+      _client.invoke<void>('network_set_bind_interface', name);
+
+  /// Network interface set with [setBindInterface].
+  Future<String?> get bindInterface =>
+      _client.invoke<String?>('network_bind_interface');
+
+  /// Stream of network events. `NetworkEvent.protocolVersionMismatch` is sent together with the
+  /// details of the mismatch which are available through [protocolMismatches].
   Stream<NetworkEvent> get networkEvents =>
-      _networkSubscription.stream.map((raw) => NetworkEvent.decode(raw as int));
+      _networkSubscription.stream.map((raw) => raw is List<Object?>
+          ? NetworkEvent.protocolVersionMismatch
+          : NetworkEvent.decode(raw as int));
+
+  /// Stream of encountered peers with a higher protocol version than ours, carrying the details
+  /// of the mismatch. Unlike [networkEvents] this can be used to tell the user exactly which
+  /// protocol version is required.
+  Stream<ProtocolMismatch> get protocolMismatches => _networkSubscription.stream
+      .where((raw) => raw is List<Object?>)
+      .map((raw) => ProtocolMismatch.decode(raw as List<Object?>));
 
   Future<void> addUserProvidedPeer(String addr) =>
       _client.invoke<void>('network_add_user_provided_peer', addr);
@@ -128,10 +192,32 @@ class Session {
   Future<void> removeUserProvidedPeer(String addr) =>
       _client.invoke<void>('network_remove_user_provided_peer', addr);
 
+  /// Adds a TCP peer given by a host name and port (e.g. `peer.example.com:1234`). The host is
+  /// resolved with DNS and periodically re-resolved so peers with a dynamic address stay
+  /// reachable.
+  Future<void> addUserProvidedTcpPeer(String host) =>
+      _client.invoke<void>('network_add_user_provided_tcp_peer', host);
+
+  /// Like [addUserProvidedTcpPeer] but for QUIC.
+  Future<void> addUserProvidedQuicPeer(String host) =>
+      _client.invoke<void>('network_add_user_provided_quic_peer', host);
+
+  Future<void> removeUserProvidedTcpPeer(String host) =>
+      _client.invoke<void>('network_remove_user_provided_tcp_peer', host);
+
+  Future<void> removeUserProvidedQuicPeer(String host) =>
+      _client.invoke<void>('network_remove_user_provided_quic_peer', host);
+
   Future<List<String>> get userProvidedPeers => _client
       .invoke<List<Object?>>('network_user_provided_peers')
       .then((list) => list.cast<String>());
 
+  /// Addresses detected as belonging to this device (connecting to them resulted in a connection
+  /// to self). Useful for diagnosing why a peer can't be connected to. Cleared on rebind.
+  Future<List<String>> get selfAddresses => _client
+      .invoke<List<Object?>>('network_self_addresses')
+      .then((list) => list.cast<String>());
+
   Future<String?> get tcpListenerLocalAddressV4 =>
       _client.invoke<String?>('network_tcp_listener_local_addr_v4');
 
@@ -157,6 +243,84 @@ class Session {
       .invoke<List<Object?>>('network_stats')
       .then((list) => NetworkStats.decode(list));
 
+  /// Number of sync protocol messages of each kind sent and received, aggregated across all
+  /// repositories and peers. Useful for diagnosing sync problems.
+  Future<MessageStats> get messageStats => _client
+      .invoke<List<Object?>>('network_message_stats')
+      .then((list) => MessageStats.decode(list));
+
+  /// Sets the max upload and download bandwidth (in bytes per second) a single peer can consume
+  /// when syncing, across all the repositories shared with it. `null` means unlimited.
+  Future<void> setBandwidthLimit({int? upload, int? download}) =>
+      _client.invoke<void>('network_set_bandwidth_limit', {
+        'upload': upload,
+        'download': download,
+      });
+
+  /// Current bandwidth limit of a single peer.
+  Future<BandwidthLimit> get bandwidthLimit => _client
+      .invoke<List<Object?>>('network_bandwidth_limit')
+      .then((list) => BandwidthLimit.decode(list));
+
+  /// Sets the nodes (in the "ip:port" format) to bootstrap the DHT against. Empty list means
+  /// use the default DHT routers. The setting is persisted across restarts.
+  Future<void> setDhtBootstrapNodes(List<String> nodes) =>
+      _client.invoke<void>('network_set_dht_bootstrap_nodes', nodes);
+
+  /// Sets the STUN servers (in the "ip:port" format) to use instead of the built-in ones. Empty
+  /// list disables STUN, in which case the external addresses and the NAT behavior are unknown.
+  /// The setting is persisted across restarts.
+  Future<void> setStunServers(List<String> servers) =>
+      _client.invoke<void>('network_set_stun_servers', servers);
+
+  /// Restores the built-in STUN servers after [setStunServers].
+  Future<void> resetStunServers() =>
+      _client.invoke<void>('network_reset_stun_servers');
+
+  /// Sets the max number of simultaneous peer connections. `null` means unlimited. Once the limit
+  /// is reached, new connections are refused until some of the existing ones close.
+  Future<void> setMaxConnections(int? max) =>
+      _client.invoke<void>('network_set_max_connections', max);
+
+  /// Max number of simultaneous peer connections or `null` if unlimited.
+  Future<int?> get maxConnections =>
+      _client.invoke<int?>('network_max_connections');
+
+  /// Sets which transport to prefer when a peer is reachable over both QUIC and TCP. A
+  /// connection over the preferred transport replaces the connections over the other one. The
+  /// peers agree on one preference during the handshake: if they conflict, the one of the peer
+  /// with the lower runtime id applies.
+  Future<void> setTransportPreference(TransportPreference preference) =>
+      _client.invoke<void>(
+          'network_set_transport_preference', preference.encode());
+
+  /// Transport preference set with [setTransportPreference].
+  Future<TransportPreference> get transportPreference => _client
+      .invoke<int>('network_transport_preference')
+      .then((n) => TransportPreference.decode(n));
+
+  /// Sets the human readable name of this device shown to the peers. Control characters are
+  /// removed and the name is truncated to 64 bytes. An empty name means no name. Applies to
+  /// connections established after this call.
+  Future<void> setDeviceName(String name) =>
+      _client.invoke<void>('network_set_device_name', name);
+
+  /// Device name set with [setDeviceName].
+  Future<String> get deviceName =>
+      _client.invoke<String>('network_device_name');
+
+  /// Sets how often to ping the peers and how long to wait for any message from a peer before
+  /// dropping the connection. [recvTimeout] must be greater than [sendInterval]. Applies to
+  /// connections established after this call.
+  Future<void> setKeepAliveConfig({
+    required Duration sendInterval,
+    required Duration recvTimeout,
+  }) =>
+      _client.invoke<void>('network_set_keep_alive_config', {
+        'send_interval': sendInterval.inMilliseconds,
+        'recv_timeout': recvTimeout.inMilliseconds,
+      });
+
   Future<List<PeerInfo>> get peers => _client
       .invoke<List<Object?>>('network_known_peers')
       .then(PeerInfo.decodeAll);
@@ -169,6 +333,13 @@ class Session {
   Future<int> get highestSeenProtocolVersion =>
       _client.invoke<int>('network_highest_seen_protocol_version');
 
+  /// Details of the most recent protocol version mismatch or `null` if no peer with a higher
+  /// protocol version has been seen yet. Useful to get the details after receiving
+  /// `NetworkEvent.protocolVersionMismatch`.
+  Future<ProtocolMismatch?> get lastProtocolMismatch => _client
+      .invoke<List<Object?>?>('network_last_protocol_mismatch')
+      .then((raw) => raw != null ? ProtocolMismatch.decode(raw) : null);
+
   /// Is port forwarding (UPnP) enabled?
   Future<bool> get isPortForwardingEnabled =>
       _client.invoke<bool>('network_is_port_forwarding_enabled');
@@ -188,6 +359,15 @@ class Session {
   Future<String> get thisRuntimeId =>
       _client.invoke<String>('network_this_runtime_id');
 
+  /// Disconnects the peer with the given runtime id (hex string) and rejects its further
+  /// connections.
+  Future<void> blockRuntimeId(String runtimeId) =>
+      _client.invoke<void>('network_block_runtime_id', runtimeId);
+
+  /// Unblocks a peer previously blocked with [blockRuntimeId].
+  Future<void> unblockRuntimeId(String runtimeId) =>
+      _client.invoke<void>('network_unblock_runtime_id', runtimeId);
+
   // Utility functions to generate password salts and to derive LocalSecretKey from LocalPasswords.
 
   Future<PasswordSalt> generateSaltForPasswordHash() => _client
@@ -246,12 +426,25 @@ class PeerInfo {
   final String? runtimeId;
   final NetworkStats stats;
 
+  /// When the connection became active or, if it's not active yet, when the connection attempt
+  /// started.
+  final DateTime? connectedSince;
+
+  /// Round-trip time to the peer. Null if not measured yet.
+  final Duration? rtt;
+
+  /// Name the peer advertised for itself. Not authenticated, for display only.
+  final String? displayName;
+
   PeerInfo({
     required this.addr,
     required this.source,
     required this.state,
     this.runtimeId,
     this.stats = const NetworkStats(),
+    this.connectedSince,
+    this.rtt,
+    this.displayName,
   });
 
   static PeerInfo decode(Object? raw) {
@@ -275,12 +468,23 @@ class PeerInfo {
 
     final stats = NetworkStats.decode(list[3] as List<Object?>);
 
+    // Older versions don't send the following fields.
+    final connectedSince = list.length > 4
+        ? DateTime.fromMillisecondsSinceEpoch(list[4] as int)
+        : null;
+    final rawRtt = list.length > 5 ? list[5] as int? : null;
+    final rtt = rawRtt != null ? Duration(microseconds: rawRtt) : null;
+    final displayName = list.length > 6 ? list[6] as String? : null;
+
     return PeerInfo(
       addr: addr,
       source: source,
       state: state,
       runtimeId: runtimeId,
       stats: stats,
+      connectedSince: connectedSince,
+      rtt: rtt,
+      displayName: displayName,
     );
   }
 
@@ -289,7 +493,7 @@ class PeerInfo {
 
   @override
   String toString() =>
-      '$runtimeType(addr: $addr, source: $source, state: $state, runtimeId: $runtimeId)';
+      '$runtimeType(addr: $addr, source: $source, state: $state, runtimeId: $runtimeId, displayName: $displayName)';
 }
 
 class NetworkStats {
@@ -317,6 +521,472 @@ class NetworkStats {
       '$runtimeType(bytesTx: $bytesTx, bytesRx: $bytesRx, throughputTx: $throughputTx, throughputRx: $throughputRx)';
 }
 
+/// Bandwidth limit in bytes per second. `null` means unlimited.
+class BandwidthLimit {
+  final int? upload;
+  final int? download;
+
+  const BandwidthLimit({this.upload, this.download});
+
+  static BandwidthLimit decode(List<Object?> raw) => BandwidthLimit(
+        upload: raw[0] as int?,
+        download: raw[1] as int?,
+      );
+
+  @override
+  String toString() => '$runtimeType(upload: $upload, download: $download)';
+}
+
+/// Breakdown of the repository size.
+class SizeBreakdown {
+  final int logicalBytes;
+  final int physicalBytes;
+  final int blockSize;
+  final int blockCount;
+  final int overheadBytes;
+
+  const SizeBreakdown({
+    this.logicalBytes = 0,
+    this.physicalBytes = 0,
+    this.blockSize = 0,
+    this.blockCount = 0,
+    this.overheadBytes = 0,
+  });
+
+  static SizeBreakdown decode(List<Object?> raw) => SizeBreakdown(
+        logicalBytes: raw[0] as int,
+        physicalBytes: raw[1] as int,
+        blockSize: raw[2] as int,
+        blockCount: raw[3] as int,
+        overheadBytes: raw[4] as int,
+      );
+
+  @override
+  String toString() =>
+      '$runtimeType(logicalBytes: $logicalBytes, physicalBytes: $physicalBytes, blockSize: $blockSize, blockCount: $blockCount, overheadBytes: $overheadBytes)';
+}
+
+/// Number of present and missing blocks of a repository and the size of its storage.
+class StorageStats {
+  final int presentBlocks;
+  final int missingBlocks;
+  final int presentBytes;
+  final int indexBytes;
+
+  const StorageStats({
+    this.presentBlocks = 0,
+    this.missingBlocks = 0,
+    this.presentBytes = 0,
+    this.indexBytes = 0,
+  });
+
+  static StorageStats decode(List<Object?> raw) => StorageStats(
+        presentBlocks: raw[0] as int,
+        missingBlocks: raw[1] as int,
+        presentBytes: raw[2] as int,
+        indexBytes: raw[3] as int,
+      );
+
+  @override
+  String toString() =>
+      '$runtimeType(presentBlocks: $presentBlocks, missingBlocks: $missingBlocks, presentBytes: $presentBytes, indexBytes: $indexBytes)';
+}
+
+/// What was recovered and what was lost by [Repository.rebuildIndex].
+class RebuildIndexReport {
+  final int recoveredBlobs;
+  final int incompleteBlobs;
+  final int lostBlobs;
+  final int recoveredBlocks;
+  final int unassignedBlocks;
+
+  /// Blobs with several versions stored, left out of the rebuilt index.
+  final int ambiguousBlobs;
+
+  /// Whether the rebuilt index replaced the current one or was merged into it.
+  final bool replaced;
+
+  const RebuildIndexReport({
+    this.recoveredBlobs = 0,
+    this.incompleteBlobs = 0,
+    this.lostBlobs = 0,
+    this.recoveredBlocks = 0,
+    this.unassignedBlocks = 0,
+    this.ambiguousBlobs = 0,
+    this.replaced = false,
+  });
+
+  static RebuildIndexReport decode(List<Object?> raw) => RebuildIndexReport(
+        recoveredBlobs: raw[0] as int,
+        incompleteBlobs: raw[1] as int,
+        lostBlobs: raw[2] as int,
+        recoveredBlocks: raw[3] as int,
+        unassignedBlocks: raw[4] as int,
+        ambiguousBlobs: raw[5] as int,
+        replaced: raw[6] as bool,
+      );
+
+  @override
+  String toString() =>
+      '$runtimeType(recoveredBlobs: $recoveredBlobs, incompleteBlobs: $incompleteBlobs, lostBlobs: $lostBlobs, recoveredBlocks: $recoveredBlocks, unassignedBlocks: $unassignedBlocks, ambiguousBlobs: $ambiguousBlobs, replaced: $replaced)';
+}
+
+/// Event emitted by [Repository.rebuildIndex]. Exactly one of [progress] and [report] is set. The
+/// event with the [report] is the last one.
+class RebuildIndexEvent {
+  final Progress? progress;
+  final RebuildIndexReport? report;
+
+  const RebuildIndexEvent._({this.progress, this.report});
+}
+
+/// Sync status of a single branch (writer) of a repository, as returned by
+/// [Repository.branches].
+class BranchStatus {
+  /// Id of the branch (the writer's public key), hex encoded.
+  final String branchId;
+
+  /// Version vector of the latest snapshot of the branch, mapping hex encoded branch ids to
+  /// versions.
+  final Map<String, int> versionVector;
+
+  /// Whether the index of the latest snapshot has been completely downloaded.
+  final bool isComplete;
+
+  /// Number of blocks of the latest snapshot that are not downloaded yet.
+  final int missingBlocks;
+
+  const BranchStatus(
+    this.branchId,
+    this.versionVector,
+    this.isComplete,
+    this.missingBlocks,
+  );
+
+  static BranchStatus decode(List<Object?> raw) => BranchStatus(
+        HEX.encode(raw[0] as Uint8List),
+        (raw[1] as Map<Object?, Object?>).map(
+          (key, value) => MapEntry(HEX.encode(key as Uint8List), value as int),
+        ),
+        raw[2] as bool,
+        raw[3] as int,
+      );
+
+  @override
+  String toString() =>
+      '$runtimeType(branchId: $branchId, versionVector: $versionVector, isComplete: $isComplete, missingBlocks: $missingBlocks)';
+}
+
+enum LoggedEventKind {
+  snapshotApproved,
+  snapshotRejected,
+  blockReceived,
+  maintenanceCompleted,
+  transferStateChanged,
+}
+
+/// Event recorded in the repository event log, as returned by [Repository.eventLog].
+class LoggedEvent {
+  /// When the event was emitted.
+  final DateTime timestamp;
+
+  final LoggedEventKind kind;
+
+  /// Id of the affected branch (for the snapshot events) or block (for
+  /// [LoggedEventKind.blockReceived]), hex encoded. `null` for the other events.
+  final String? id;
+
+  const LoggedEvent(this.timestamp, this.kind, this.id);
+
+  static LoggedEvent decode(List<Object?> raw) {
+    final timestamp = DateTime.fromMillisecondsSinceEpoch(raw[0] as int);
+    final payload = raw[1];
+
+    if (payload is Map && payload.containsKey('snapshot_approved')) {
+      return LoggedEvent(timestamp, LoggedEventKind.snapshotApproved,
+          HEX.encode(payload['snapshot_approved'] as Uint8List));
+    } else if (payload is Map && payload.containsKey('snapshot_rejected')) {
+      return LoggedEvent(timestamp, LoggedEventKind.snapshotRejected,
+          HEX.encode(payload['snapshot_rejected'] as Uint8List));
+    } else if (payload is Map && payload.containsKey('block_received')) {
+      return LoggedEvent(timestamp, LoggedEventKind.blockReceived,
+          HEX.encode(payload['block_received'] as Uint8List));
+    } else if (payload == 'maintenance_completed') {
+      return LoggedEvent(
+          timestamp, LoggedEventKind.maintenanceCompleted, null);
+    } else {
+      return LoggedEvent(
+          timestamp, LoggedEventKind.transferStateChanged, null);
+    }
+  }
+
+  @override
+  String toString() => '$runtimeType(timestamp: $timestamp, kind: $kind, id: $id)';
+}
+
+enum DirectoryEventKind { created, modified, removed }
+
+/// Change of an entry in a watched directory.
+class DirectoryEvent {
+  final DirectoryEventKind kind;
+
+  /// Name of the changed entry.
+  final String name;
+
+  const DirectoryEvent(this.kind, this.name);
+
+  @override
+  String toString() => '$runtimeType(kind: $kind, name: $name)';
+}
+
+enum IntegrityProblemKind { nodeHashMismatch, blockMissing, blockCorrupted }
+
+/// Problem found by [Repository.verifyIntegrity].
+class IntegrityProblem {
+  final IntegrityProblemKind kind;
+
+  /// Hash of the affected node or id of the affected block.
+  final String id;
+
+  const IntegrityProblem(this.kind, this.id);
+
+  @override
+  String toString() => '$runtimeType(kind: $kind, id: $id)';
+}
+
+enum TransferEventKind { copied, skipped, failed, done }
+
+/// Progress of a bulk import or export.
+class TransferEvent {
+  final TransferEventKind kind;
+
+  /// Path of the entry relative to the root of the transfer. Empty for [TransferEventKind.done].
+  final String path;
+
+  /// Number of bytes copied so far and the total number of bytes to copy. Only meaningful for
+  /// [TransferEventKind.copied].
+  final int bytes;
+  final int total;
+
+  /// Reason why the entry was skipped or the error why it failed.
+  final String? message;
+
+  /// Number of copied, skipped and failed entries. Only meaningful for [TransferEventKind.done].
+  final int copied;
+  final int skipped;
+  final int failed;
+
+  const TransferEvent._(
+    this.kind, {
+    this.path = '',
+    this.bytes = 0,
+    this.total = 0,
+    this.message,
+    this.copied = 0,
+    this.skipped = 0,
+    this.failed = 0,
+  });
+
+  static TransferEvent decode(Map<Object?, Object?> raw) {
+    if (raw['copied'] case List<Object?> value) {
+      return TransferEvent._(
+        TransferEventKind.copied,
+        path: value[0] as String,
+        bytes: value[1] as int,
+        total: value[2] as int,
+      );
+    } else if (raw['skipped'] case List<Object?> value) {
+      return TransferEvent._(
+        TransferEventKind.skipped,
+        path: value[0] as String,
+        message: value[1] as String,
+      );
+    } else if (raw['failed'] case List<Object?> value) {
+      return TransferEvent._(
+        TransferEventKind.failed,
+        path: value[0] as String,
+        message: value[1] as String,
+      );
+    } else if (raw['done'] case List<Object?> value) {
+      return TransferEvent._(
+        TransferEventKind.done,
+        copied: value[0] as int,
+        skipped: value[1] as int,
+        failed: value[2] as int,
+      );
+    } else if (raw['aborted'] case String error) {
+      throw Exception(error);
+    } else {
+      throw Exception('invalid transfer event');
+    }
+  }
+
+  @override
+  String toString() =>
+      '$runtimeType(kind: $kind, path: $path, bytes: $bytes, total: $total, message: $message, copied: $copied, skipped: $skipped, failed: $failed)';
+}
+
+/// Encountered peer that uses a higher protocol version than this library.
+class ProtocolMismatch {
+  /// Protocol version of this library.
+  final int ourVersion;
+
+  /// Protocol version of the peer.
+  final int theirVersion;
+
+  /// Address of the peer.
+  final String peerAddr;
+
+  /// Runtime id of the peer (hex encoded). Null if the peer disconnected before
+  /// revealing it.
+  final String? runtimeId;
+
+  const ProtocolMismatch(
+    this.ourVersion,
+    this.theirVersion,
+    this.peerAddr,
+    this.runtimeId,
+  );
+
+  static ProtocolMismatch decode(List<Object?> raw) => ProtocolMismatch(
+        raw[0] as int,
+        raw[1] as int,
+        raw[2] as String,
+        raw[3] as String?,
+      );
+
+  @override
+  String toString() =>
+      '$runtimeType(ourVersion: $ourVersion, theirVersion: $theirVersion, peerAddr: $peerAddr, runtimeId: $runtimeId)';
+}
+
+/// File with two or more concurrent versions.
+class Conflict {
+  /// Path of the conflicting file.
+  final String path;
+
+  /// Paths under which the individual versions of the file can be opened.
+  final List<String> versions;
+
+  const Conflict(this.path, this.versions);
+
+  static Conflict decode(List<Object?> raw) => Conflict(
+        raw[0] as String,
+        (raw[1] as List<Object?>).cast<String>(),
+      );
+
+  @override
+  String toString() => '$runtimeType(path: $path, versions: $versions)';
+}
+
+/// File with concurrent versions, as returned by [Repository.detectConflict].
+class ConflictInfo {
+  /// Path of the conflicting file.
+  final String path;
+
+  /// The concurrent versions of the file.
+  final List<ConflictVersion> versions;
+
+  const ConflictInfo(this.path, this.versions);
+
+  static ConflictInfo decode(List<Object?> raw) => ConflictInfo(
+        raw[0] as String,
+        (raw[1] as List<Object?>)
+            .map((version) => ConflictVersion.decode(version as List<Object?>))
+            .toList(),
+      );
+
+  @override
+  String toString() => '$runtimeType(path: $path, versions: $versions)';
+}
+
+/// Single version of a conflicting file.
+class ConflictVersion {
+  /// Path under which this version can be opened.
+  final String path;
+
+  /// Id of the branch (author) of this version, hex encoded.
+  final String branchId;
+
+  /// Version vector of this version, mapping hex encoded branch ids to versions.
+  final Map<String, int> versionVector;
+
+  const ConflictVersion(this.path, this.branchId, this.versionVector);
+
+  static ConflictVersion decode(List<Object?> raw) => ConflictVersion(
+        raw[0] as String,
+        HEX.encode(raw[1] as Uint8List),
+        (raw[2] as Map<Object?, Object?>).map(
+          (key, value) => MapEntry(HEX.encode(key as Uint8List), value as int),
+        ),
+      );
+
+  @override
+  String toString() =>
+      '$runtimeType(path: $path, branchId: $branchId, versionVector: $versionVector)';
+}
+
+/// Number of sync protocol messages of each kind.
+class MessageCounts {
+  final int rootNode;
+  final int childNodes;
+  final int innerNodes;
+  final int leafNodes;
+  final int block;
+  final int blockOffer;
+  final int error;
+
+  const MessageCounts({
+    this.rootNode = 0,
+    this.childNodes = 0,
+    this.innerNodes = 0,
+    this.leafNodes = 0,
+    this.block = 0,
+    this.blockOffer = 0,
+    this.error = 0,
+  });
+
+  static MessageCounts decode(List<Object?> raw) => MessageCounts(
+        rootNode: raw[0] as int,
+        childNodes: raw[1] as int,
+        innerNodes: raw[2] as int,
+        leafNodes: raw[3] as int,
+        block: raw[4] as int,
+        blockOffer: raw[5] as int,
+        error: raw[6] as int,
+      );
+
+  @override
+  String toString() =>
+      '$runtimeType(rootNode: $rootNode, childNodes: $childNodes, innerNodes: $innerNodes, leafNodes: $leafNodes, block: $block, blockOffer: $blockOffer, error: $error)';
+}
+
+/// Number of sync protocol messages sent and received, broken down by their kind.
+class MessageStats {
+  final MessageCounts requestsSent;
+  final MessageCounts requestsReceived;
+  final MessageCounts responsesSent;
+  final MessageCounts responsesReceived;
+
+  const MessageStats({
+    this.requestsSent = const MessageCounts(),
+    this.requestsReceived = const MessageCounts(),
+    this.responsesSent = const MessageCounts(),
+    this.responsesReceived = const MessageCounts(),
+  });
+
+  static MessageStats decode(List<Object?> raw) => MessageStats(
+        requestsSent: MessageCounts.decode(raw[0] as List<Object?>),
+        requestsReceived: MessageCounts.decode(raw[1] as List<Object?>),
+        responsesSent: MessageCounts.decode(raw[2] as List<Object?>),
+        responsesReceived: MessageCounts.decode(raw[3] as List<Object?>),
+      );
+
+  @override
+  String toString() =>
+      '$runtimeType(requestsSent: $requestsSent, requestsReceived: $requestsReceived, responsesSent: $responsesSent, responsesReceived: $responsesReceived)';
+}
+
 /// A handle to a Ouisync repository.
 class Repository {
   final Client _client;
@@ -338,12 +1008,16 @@ class Repository {
   /// any             |  null            |  write         |  read (only!) with secret
   /// null            |  any             |  write         |  read without secret, require secret for writing
   /// any             |  any             |  write         |  read with one secret, write with (possibly same) one
+  ///
+  /// If [discoveryEnabled] is true, DHT and PEX are enabled as soon as syncing is enabled.
+  /// Otherwise the repository stays undiscoverable until they are explicitly enabled.
   static Future<Repository> create(
     Session session, {
     required String store,
     required SetLocalSecret? readSecret,
     required SetLocalSecret? writeSecret,
     ShareToken? shareToken,
+    bool discoveryEnabled = false,
   }) async {
     if (debugTrace) {
       print("Repository.create $store");
@@ -355,7 +1029,8 @@ class Repository {
         'path': store,
         'read_secret': readSecret?.encode(),
         'write_secret': writeSecret?.encode(),
-        'share_token': shareToken?.toString()
+        'share_token': shareToken?.toString(),
+        'discovery_enabled': discoveryEnabled,
       },
     );
 
@@ -383,6 +1058,31 @@ class Repository {
     return Repository._(session._client, handle, store);
   }
 
+  /// Opens an existing repository trying each of [secrets] in order and using the one that
+  /// unlocks the highest access mode. Returns the repository, the achieved access mode and the
+  /// index of the secret that was used (`null` if none of them unlocked anything).
+  static Future<(Repository, AccessMode, int?)> openWithSecrets(
+    Session session, {
+    required String store,
+    required List<LocalSecret> secrets,
+  }) async {
+    if (debugTrace) {
+      print("Repository.openWithSecrets $store");
+    }
+
+    final raw =
+        await session._client.invoke<List<Object?>>('repository_open_with_secrets', {
+      'path': store,
+      'secrets': secrets.map((secret) => secret.encode()).toList(),
+    });
+
+    return (
+      Repository._(session._client, raw[0] as int, store),
+      AccessMode.decode(raw[1] as int),
+      raw[2] as int?,
+    );
+  }
+
   /// Closes the repository. All outstanding handles become invalid. Invoking any operation on a
   /// repository after it's been closed results in an error being thrown.
   Future<void> close() async {
@@ -390,6 +1090,13 @@ class Repository {
     await _client.invoke('repository_close', _handle);
   }
 
+  /// Moves the database file of this repository to [dst] while keeping the repository open. Fails
+  /// if [dst] already exists.
+  Future<void> rename(String dst) => _client.invoke<void>('repository_rename', {
+        'repository': _handle,
+        'dst': dst,
+      });
+
   /// Checks whether syncing with other replicas is enabled.
   Future<bool> get isSyncEnabled =>
       _client.invoke<bool>('repository_is_sync_enabled', _handle);
@@ -438,6 +1145,58 @@ class Repository {
         'secret': secret?.encode(),
       });
 
+  /// Switches this repository to blind mode while keeping the stored secrets, so it can be later
+  /// unlocked again with [unlock]. Open files start failing until then.
+  Future<void> lock() => _client.invoke<void>('repository_lock', _handle);
+
+  /// Unlocks this repository previously locked with [lock]. Throws
+  /// [ErrorCode.wrongPassword] if [secret] doesn't raise the access mode.
+  Future<void> unlock(LocalSecret secret) =>
+      _client.invoke<void>('repository_unlock', {
+        'repository': _handle,
+        'secret': secret.encode(),
+      });
+
+  /// Why is this repository not in a higher access mode. [LockReason.wrongPassword] means the
+  /// last secret passed to [open] or [setAccessMode] didn't unlock anything.
+  Future<LockReason> get lockReason => _client
+      .invoke<int>('repository_lock_reason', _handle)
+      .then((n) => LockReason.decode(n));
+
+  /// Returns the access mode the given secret would unlock ([AccessMode.blind] if it unlocks
+  /// nothing) without changing the current access mode of this repository. Useful to check the
+  /// old password before changing it.
+  Future<AccessMode> verifyPassword(LocalSecret secret) => _client
+      .invoke<int>('repository_verify_password', {
+        'repository': _handle,
+        'secret': secret.encode(),
+      })
+      .then((n) => AccessMode.decode(n));
+
+  /// Exports the secrets unlocked by [secret] as a recovery string the owner can keep in a safe
+  /// place (e.g. a password manager) to restore the access on another device with
+  /// [importAccessSecrets].
+  ///
+  /// WARNING: unlike a share token, the recovery string contains the raw secrets (including the
+  /// write key), never expires and can't be revoked. Anyone who gets it has the same access as the
+  /// owner. Never share it with anyone.
+  ///
+  /// Throws [ErrorCode.wrongPassword] if [secret] doesn't unlock at least read access.
+  Future<String> exportAccessSecrets(LocalSecret secret) =>
+      _client.invoke<String>('repository_export_access_secrets', {
+        'repository': _handle,
+        'secret': secret.encode(),
+      });
+
+  /// Restores the access to this repository from a recovery string produced by
+  /// [exportAccessSecrets]. The access lasts until the repository is closed; call [setAccess]
+  /// afterwards to persist it.
+  Future<void> importAccessSecrets(String recovery) =>
+      _client.invoke<void>('repository_import_access_secrets', {
+        'repository': _handle,
+        'recovery': recovery,
+      });
+
   /// Returns the type (file, directory, ..) of the entry at [path]. Returns `null` if the entry
   /// doesn't exists.
   Future<EntryType?> type(String path) async {
@@ -462,19 +1221,66 @@ class Repository {
     return await type(path) != null;
   }
 
-  /// Move/rename the file/directory from [src] to [dst].
-  Future<void> move(String src, String dst) async {
+  /// Returns whether modifying the entry (file or directory) at [path] would create a conflict
+  /// because another replica has a version of it that is newer than or concurrent with the local
+  /// one.
+  Future<bool> wouldConflict(String path) =>
+      _client.invoke<bool>('repository_would_conflict', {
+        'repository': _handle,
+        'path': path,
+      });
+
+  /// Pins the file at [path] so it stays available offline: its missing blocks are downloaded
+  /// first and its blocks never expire. Directories can't be pinned.
+  Future<void> pin(String path) => _client.invoke<void>('repository_pin', {
+        'repository': _handle,
+        'path': path,
+      });
+
+  /// Removes the pin of the file at [path].
+  Future<void> unpin(String path) => _client.invoke<void>('repository_unpin', {
+        'repository': _handle,
+        'path': path,
+      });
+
+  /// Returns whether the file at [path] is pinned.
+  Future<bool> isPinned(String path) =>
+      _client.invoke<bool>('repository_is_pinned', {
+        'repository': _handle,
+        'path': path,
+      });
+
+  /// Move/rename the file/directory from [src] to [dst]. By default the version history of the
+  /// entry is preserved so other replicas see this as a move, not as a removal followed by a
+  /// creation. If [preserveHistory] is false, the entry at [dst] starts a new version history
+  /// instead.
+  Future<void> move(
+    String src,
+    String dst, {
+    bool preserveHistory = true,
+  }) async {
     if (debugTrace) {
       print("Repository.move $src -> $dst");
     }
 
-    await _client.invoke<void>('repository_move_entry', {
+    await _client.invoke<void>('repository_move_entry_preserving_history', {
       'repository': _handle,
       'src': src,
       'dst': dst,
+      'preserve_history': preserveHistory,
     });
   }
 
+  /// Copies the file at [src] to [dst] without copying its content. The copy shares the
+  /// underlying blocks with the original so it's fast and doesn't take additional space. Copying
+  /// directories is not supported.
+  Future<void> copy(String src, String dst) =>
+      _client.invoke<void>('repository_copy_entry', {
+        'repository': _handle,
+        'src': src,
+        'dst': dst,
+      });
+
   Stream<void> get events => _subscription.stream.cast<void>();
 
   Future<bool> get isDhtEnabled async {
@@ -496,6 +1302,344 @@ class Repository {
     });
   }
 
+  /// Finds groups of files with identical content. Each group (list of paths) is emitted as soon
+  /// as it's found. Cancelling the stream subscription cancels the search. Empty files and files
+  /// that are not fully downloaded are skipped.
+  ///
+  /// If [onProgress] is given, it's called with the progress of the search (files hashed / files
+  /// to hash).
+  Stream<List<String>> findDuplicates(
+      {void Function(Progress progress)? onProgress}) async* {
+    final subscription =
+        Subscription(_client, 'repository_find_duplicates', _handle);
+
+    try {
+      await for (final event in subscription.stream) {
+        if (event is Map && event.containsKey('progress')) {
+          onProgress
+              ?.call(Progress.decode(event['progress'] as List<Object?>));
+        } else if (event is Map && event.containsKey('group')) {
+          yield (event['group'] as List<Object?>).cast<String>();
+        } else if (event is Map && event.containsKey('failed')) {
+          throw Exception(event['failed']);
+        } else {
+          // done
+          break;
+        }
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Checks the stored index nodes and blocks against their hashes. Each problem is emitted as
+  /// soon as it's found. Cancelling the stream subscription cancels the check.
+  Stream<IntegrityProblem> verifyIntegrity() async* {
+    final subscription =
+        Subscription(_client, 'repository_verify_integrity', _handle);
+
+    try {
+      await for (final event in subscription.stream) {
+        if (event is Map && event.containsKey('node_hash_mismatch')) {
+          yield IntegrityProblem(IntegrityProblemKind.nodeHashMismatch,
+              event['node_hash_mismatch']);
+        } else if (event is Map && event.containsKey('block_missing')) {
+          yield IntegrityProblem(
+              IntegrityProblemKind.blockMissing, event['block_missing']);
+        } else if (event is Map && event.containsKey('block_corrupted')) {
+          yield IntegrityProblem(
+              IntegrityProblemKind.blockCorrupted, event['block_corrupted']);
+        } else if (event is Map && event.containsKey('failed')) {
+          throw Exception(event['failed']);
+        } else {
+          // done
+          break;
+        }
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Rotates the keys of this repository: re-encrypts its whole content under newly generated
+  /// secrets into a new repository at [dst] and emits the progress as it goes. Cancelling the
+  /// stream subscription interrupts the rotation, calling this again with the same [dst] resumes
+  /// it.
+  ///
+  /// The rotated repository has a different id so the previously issued share tokens don't work
+  /// for it. All the other replicas need a new token created from the rotated repository. It's
+  /// protected by [readSecret] and [writeSecret] which should normally be the same as the local
+  /// secrets of this repository.
+  Stream<Progress> rotateKeys(
+    String dst, {
+    required SetLocalSecret? readSecret,
+    required SetLocalSecret? writeSecret,
+  }) async* {
+    final subscription = Subscription(_client, 'repository_rotate_keys', {
+      'repository': _handle,
+      'dst': dst,
+      'read_secret': readSecret?.encode(),
+      'write_secret': writeSecret?.encode(),
+    });
+
+    try {
+      await for (final event in subscription.stream) {
+        if (event is Map && event.containsKey('progress')) {
+          yield Progress.decode(event['progress'] as List<Object?>);
+        } else if (event is Map && event.containsKey('failed')) {
+          throw Exception(event['failed']);
+        } else {
+          // done
+          break;
+        }
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Rebuilds the index of the local branch from the locally stored blocks, to recover from a
+  /// corrupted index. Requires write access. Emits the progress (blocks matched / blocks stored)
+  /// followed by a final report of what was recovered and what was lost. The repository should
+  /// not be synced while the index is being rebuilt.
+  Stream<RebuildIndexEvent> rebuildIndex() async* {
+    final subscription =
+        Subscription(_client, 'repository_rebuild_index', _handle);
+
+    try {
+      await for (final event in subscription.stream) {
+        if (event is Map && event.containsKey('progress')) {
+          yield RebuildIndexEvent._(
+              progress: Progress.decode(event['progress'] as List<Object?>));
+        } else if (event is Map && event.containsKey('done')) {
+          yield RebuildIndexEvent._(
+              report:
+                  RebuildIndexReport.decode(event['done'] as List<Object?>));
+          break;
+        } else if (event is Map && event.containsKey('failed')) {
+          throw Exception(event['failed']);
+        } else {
+          break;
+        }
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Watches the directory at [path] for created, modified and removed entries. The stream ends
+  /// when the directory is removed or moved away. Cancelling the stream subscription stops the
+  /// watch.
+  Stream<DirectoryEvent> watchDirectory(String path) async* {
+    final subscription = Subscription(_client, 'repository_watch_directory', {
+      'repository': _handle,
+      'path': path,
+    });
+
+    try {
+      await for (final event in subscription.stream) {
+        if (event is Map && event.containsKey('created')) {
+          yield DirectoryEvent(DirectoryEventKind.created, event['created']);
+        } else if (event is Map && event.containsKey('modified')) {
+          yield DirectoryEvent(DirectoryEventKind.modified, event['modified']);
+        } else if (event is Map && event.containsKey('removed')) {
+          yield DirectoryEvent(DirectoryEventKind.removed, event['removed']);
+        } else if (event is Map && event.containsKey('failed')) {
+          throw Exception(event['failed']);
+        } else {
+          // directory_removed
+          break;
+        }
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Watches the file or directory at [path] and emits its new version vector (encoded the same
+  /// way as [currentVersion]) whenever it or any of its descendants changes. Equal consecutive
+  /// values can be used to skip duplicate refreshes. The stream ends when the entry is removed or
+  /// moved away. Cancelling the stream subscription stops the watch.
+  Stream<Uint8List> subscribePath(String path) async* {
+    final subscription = Subscription(_client, 'repository_path', {
+      'repository': _handle,
+      'path': path,
+    });
+
+    try {
+      await for (final event in subscription.stream) {
+        if (event is Map && event.containsKey('changed')) {
+          yield Uint8List.fromList((event['changed'] as List).cast<int>());
+        } else if (event is Map && event.containsKey('failed')) {
+          throw Exception(event['failed']);
+        } else {
+          // removed
+          break;
+        }
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Recursively imports the file or directory at [hostPath] on the local filesystem into this
+  /// repository at [path], overwriting existing files. Emits progress of every entry followed by
+  /// a final [TransferEventKind.done] summary. Entries that can't be read are reported as failed
+  /// and skipped. Symlinks to files are followed, other symlinks are skipped. Cancelling the
+  /// stream subscription cancels the import. Not available on the web.
+  Stream<TransferEvent> importPath(String hostPath, String path) async* {
+    final subscription = Subscription(_client, 'repository_import_path', {
+      'repository': _handle,
+      'src': hostPath,
+      'dst': path,
+    });
+
+    try {
+      await for (final event in subscription.stream) {
+        final decoded = TransferEvent.decode(event as Map<Object?, Object?>);
+        yield decoded;
+
+        if (decoded.kind == TransferEventKind.done) {
+          break;
+        }
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Recursively exports the file or directory at [path] in this repository to [hostPath] on the
+  /// local filesystem, creating directories as needed. Collisions with existing entries are
+  /// resolved according to [policy]. Emits progress of every entry followed by a final
+  /// [TransferEventKind.done] summary. Files that are not fully synced yet are reported as failed
+  /// instead of being exported truncated. Cancelling the stream subscription cancels the export.
+  /// Not available on the web.
+  Stream<TransferEvent> exportPath(
+    String path,
+    String hostPath, {
+    CollisionPolicy policy = CollisionPolicy.skip,
+  }) async* {
+    final subscription = Subscription(_client, 'repository_export_path', {
+      'repository': _handle,
+      'src': path,
+      'dst': hostPath,
+      'policy': policy.encode(),
+    });
+
+    try {
+      await for (final event in subscription.stream) {
+        final decoded = TransferEvent.decode(event as Map<Object?, Object?>);
+        yield decoded;
+
+        if (decoded.kind == TransferEventKind.done) {
+          break;
+        }
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Registers a handler which is called for every file with concurrent versions (initially for
+  /// all the existing ones and then for every new or changed conflict). If the handler returns a
+  /// content, the conflict is resolved by replacing the file with it. If it returns `null`, all
+  /// the versions are kept (the default behaviour). Cancel the returned subscription to unregister
+  /// the handler.
+  StreamSubscription<Object?> setConflictHandler(
+    Future<List<int>?> Function(Conflict) handler,
+  ) {
+    final subscription =
+        Subscription(_client, 'repository_conflicts', _handle);
+
+    return subscription.stream.listen(
+      (event) async {
+        final conflict = Conflict.decode(event as List<Object?>);
+        final content = await handler(conflict);
+
+        if (content != null) {
+          await resolveConflict(conflict.path, content);
+        }
+      },
+      onDone: () => subscription.close(),
+    );
+  }
+
+  /// Checks whether the file at [path] has concurrent versions without modifying anything. Use it
+  /// to find out whether writing to the file would fork it into a conflict. Returns the competing
+  /// versions or `null` if there is no conflict.
+  Future<ConflictInfo?> detectConflict(String path) => _client
+          .invoke<List<Object?>?>('repository_detect_conflict', {
+        'repository': _handle,
+        'path': path,
+      }).then((raw) => raw != null ? ConflictInfo.decode(raw) : null);
+
+  /// Resolves the conflict of the file at [path] by replacing it with [content]. The result
+  /// supersedes all the currently known versions of the file.
+  Future<void> resolveConflict(String path, List<int> content) =>
+      _client.invoke<void>('repository_resolve_conflict', {
+        'repository': _handle,
+        'path': path,
+        'content': Uint8List.fromList(content),
+      });
+
+  /// Sets the number of database connections to pre-open in the background every time this
+  /// repository is opened, which makes the first operations after opening faster. Zero disables
+  /// it.
+  Future<void> setPoolWarmup(int count) =>
+      _client.invoke<void>('repository_set_pool_warmup', {
+        'repository': _handle,
+        'count': count,
+      });
+
+  /// Sets the max number of complete snapshots kept per branch. Older snapshots are pruned
+  /// automatically, even those still serving as fallback for the newer ones. Zero means
+  /// unlimited, which is the default.
+  Future<void> setSnapshotRetention(int count) =>
+      _client.invoke<void>('repository_set_snapshot_retention', {
+        'repository': _handle,
+        'count': count,
+      });
+
+  /// Gets the max number of complete snapshots kept per branch. Zero means unlimited.
+  Future<int> get snapshotRetention =>
+      _client.invoke<int>('repository_snapshot_retention', _handle);
+
+  /// Sets the storage quota of this repository in bytes. `null` means no quota. Writes that would
+  /// grow the repository beyond the quota fail with [ErrorCode.quotaExceeded] and snapshots from
+  /// other replicas that would exceed it are rejected.
+  Future<void> setQuota(int? quota) =>
+      _client.invoke<void>('repository_set_quota', {
+        'repository': _handle,
+        'quota': quota,
+      });
+
+  /// Gets the storage quota of this repository in bytes or `null` if there is none.
+  Future<int?> get quota => _client.invoke<int?>('repository_quota', _handle);
+
+  /// Gets the size (in bytes) counted against the quota: the blocks referenced from the latest
+  /// snapshots of all the branches, including those not downloaded yet.
+  Future<int> get quotaUsage =>
+      _client.invoke<int>('repository_quota_usage', _handle);
+
+  /// Sets the strategy used to merge concurrent versions of directories. The strategy is a local
+  /// setting, replicas using different strategies can temporarily show different content.
+  Future<void> setDirectoryMergeStrategy(MergeStrategy strategy) =>
+      _client.invoke<void>('repository_set_directory_merge_strategy', {
+        'repository': _handle,
+        'strategy': strategy.encode(),
+      });
+
+  /// Gets the strategy used to merge concurrent versions of directories.
+  Future<MergeStrategy> get directoryMergeStrategy => _client
+      .invoke<int>('repository_directory_merge_strategy', _handle)
+      .then((n) => MergeStrategy.decode(n));
+
+  /// Announces this repository on the DHT immediately instead of waiting for the next periodic
+  /// announce. Returns whether the announce was triggered, which is not the case when DHT is
+  /// disabled for this repository or when called again too soon.
+  Future<bool> announceNow() =>
+      _client.invoke<bool>('repository_announce_now', _handle);
+
   Future<bool> get isPexEnabled =>
       _client.invoke<bool>('repository_is_pex_enabled', _handle);
 
@@ -505,12 +1649,50 @@ class Repository {
         'enabled': enabled,
       });
 
+  Future<bool> get isUploadEnabled =>
+      _client.invoke<bool>('repository_is_upload_enabled', _handle);
+
+  /// Enables/disables uploading of this repository's data to peers. When disabled, the repository
+  /// is "receive-only": it keeps downloading from peers but declines to serve them its blocks.
+  /// Note that a swarm of receive-only replicas can't make progress without at least one replica
+  /// that has upload enabled.
+  Future<void> setUploadEnabled(bool enabled) =>
+      _client.invoke<void>('repository_set_upload_enabled', {
+        'repository': _handle,
+        'enabled': enabled,
+      });
+
+  Future<bool> get isBlockStorageEnabled =>
+      _client.invoke<bool>('repository_is_block_storage_enabled', _handle);
+
+  /// Enables/disables storing of this repository's blocks. When disabled, the repository still
+  /// syncs and relays the index (acting as a coordination node) but never downloads, stores or
+  /// serves block content.
+  Future<void> setBlockStorageEnabled(bool enabled) =>
+      _client.invoke<void>('repository_set_block_storage_enabled', {
+        'repository': _handle,
+        'enabled': enabled,
+      });
+
+  /// Returns the access mode the peer with the given [runtimeId] (as reported in [PeerInfo]) has
+  /// proven to have to this repository, or `null` if we are not currently connected to the peer.
+  /// Note a reader is reported as blind if this replica is itself blind.
+  Future<AccessMode?> peerAccess(String runtimeId) =>
+      _client.invoke<int?>('repository_peer_access', {
+        'repository': _handle,
+        'runtime_id': Uint8List.fromList(HEX.decode(runtimeId)),
+      }).then((n) => n != null ? AccessMode.decode(n) : null);
+
   /// Create a share token providing access to this repository with the given mode. Can optionally
   /// specify repository name which will be included in the token and suggested to the recipient.
+  /// If [notAfter] is given, honest clients refuse the token after that time. This is only
+  /// advisory: a modified client can ignore it. Fails with [ErrorCode.wrongPassword] if [secret]
+  /// is given but doesn't unlock [accessMode].
   Future<ShareToken> createShareToken({
     required AccessMode accessMode,
     LocalSecret? secret,
     String? name,
+    DateTime? notAfter,
   }) {
     if (debugTrace) {
       print("Repository.createShareToken");
@@ -521,6 +1703,7 @@ class Repository {
       'secret': secret?.encode(),
       'access_mode': accessMode.encode(),
       'name': name,
+      'not_after': notAfter?.millisecondsSinceEpoch,
     }).then((token) => ShareToken._(_client, token));
   }
 
@@ -528,6 +1711,53 @@ class Repository {
       .invoke<List<Object?>>('repository_sync_progress', _handle)
       .then(Progress.decode);
 
+  /// Stream of [syncProgress] updates. Emits the current progress first and then every time it
+  /// changes, at most a few times per second.
+  Stream<Progress> get syncProgressStream async* {
+    final subscription =
+        Subscription(_client, 'repository_sync_progress', _handle);
+
+    try {
+      await for (final event in subscription.stream) {
+        yield Progress.decode(event as List<Object?>);
+      }
+    } finally {
+      await subscription.close();
+    }
+  }
+
+  /// Progress of downloading the index (directory structure and file listings). This advances
+  /// during the initial sync even before [syncProgress] (which counts blocks) does.
+  Future<Progress> get indexProgress => _client
+      .invoke<List<Object?>>('repository_index_progress', _handle)
+      .then(Progress.decode);
+
+  /// Whether any blocks of this repository are currently being transferred to or from other
+  /// replicas. Changes of this state are notified via [events].
+  Future<bool> get isTransferring =>
+      _client.invoke<bool>('repository_is_transferring', _handle);
+
+  /// Whether this repository is fully synced, that is, all the blocks referenced from the latest
+  /// snapshots of all its complete branches are downloaded. Prefer this over comparing
+  /// [syncProgress] values which are subject to rounding.
+  Future<bool> get isComplete =>
+      _client.invoke<bool>('repository_is_complete', _handle);
+
+  /// Completes once this repository becomes fully synced (see [isComplete]). Completes right
+  /// away if it already is. The repository can become incomplete again later.
+  Future<void> get onComplete =>
+      _client.invoke<void>('repository_on_complete', _handle);
+
+  /// Checks which of the entries at [paths] are fully available locally (can be read without
+  /// being connected to any other replica). Returns a list parallel to [paths]. Entries that
+  /// don't exist are reported as not available.
+  Future<List<bool>> availability(List<String> paths) => _client
+      .invoke<List<Object?>>('repository_availability', {
+        'repository': _handle,
+        'paths': paths,
+      })
+      .then((list) => list.cast<bool>());
+
   StateMonitor? get stateMonitor {
     final store = _store;
     return store != null
@@ -540,6 +1770,23 @@ class Repository {
   Future<String> get infoHash =>
       _client.invoke<String>("repository_info_hash", _handle);
 
+  /// Short human readable "safety number" derived from the repository secrets. Users can compare
+  /// it out-of-band (in person, over a call, ...) to verify they share the same secrets and the
+  /// share token wasn't tampered with. Only fingerprints of repositories with the same access
+  /// mode can match:
+  ///
+  /// - write: both have the same write key.
+  /// - read: both have the same repository id and read key.
+  /// - blind: both have the same repository id, which is public and proves nothing more.
+  ///
+  /// To compare with a user of lower access, pass their [accessMode]. It's clamped to the access
+  /// mode of this repository.
+  Future<String> securityFingerprint([AccessMode? accessMode]) =>
+      _client.invoke<String>("repository_security_fingerprint", {
+        'repository': _handle,
+        'access_mode': accessMode?.encode(),
+      });
+
   Future<String> hexDatabaseId() async {
     final bytes =
         await _client.invoke<Uint8List>("repository_database_id", _handle);
@@ -605,6 +1852,113 @@ class Repository {
   Future<NetworkStats> get networkStats => _client
       .invoke<List<Object?>>('repository_stats', _handle)
       .then((list) => NetworkStats.decode(list));
+
+  /// Breakdown of the repository size into the total length of the files and the actual storage
+  /// used, including the overhead (block padding, index, metadata, ...).
+  Future<SizeBreakdown> get sizeBreakdown => _client
+      .invoke<List<Object?>>('repository_size_breakdown', _handle)
+      .then((list) => SizeBreakdown.decode(list));
+
+  /// Number of locally present and missing blocks and the size of the stored blocks and of the
+  /// index. Unlike [syncProgress], these are absolute numbers.
+  Future<StorageStats> get storageStats => _client
+      .invoke<List<Object?>>('repository_storage_stats', _handle)
+      .then((list) => StorageStats.decode(list));
+
+  /// Sync status of every branch known to this repository. Useful for diagnosing why the
+  /// replicas haven't converged yet.
+  Future<List<BranchStatus>> get branches => _client
+      .invoke<List<Object?>>('repository_branches', _handle)
+      .then((list) => list
+          .map((raw) => BranchStatus.decode(raw as List<Object?>))
+          .toList());
+
+  /// When each remote branch (writer) was last synced, that is, when we last received a complete
+  /// snapshot of it. Keys are the hex encoded branch ids. Branches never synced are not included.
+  Future<Map<String, DateTime>> get branchSyncTimes => _client
+      .invoke<Map<Object?, Object?>>('repository_branch_sync_times', _handle)
+      .then((map) => map.map((key, value) => MapEntry(
+            HEX.encode(key as Uint8List),
+            DateTime.fromMillisecondsSinceEpoch(value as int),
+          )));
+
+  /// Ids (hex encoded) of the blocks that are currently being downloaded.
+  Future<List<String>> get downloading => _client
+      .invoke<List<Object?>>('repository_downloading', _handle)
+      .then((list) => list.cast<String>());
+
+  /// Stops downloading the missing blocks of the file at [path]. The blocks are not requested
+  /// again until the repository is reopened.
+  Future<void> cancelDownload(String path) =>
+      _client.invoke<void>('repository_cancel_download', {
+        'repository': _handle,
+        'path': path,
+      });
+
+  /// Enables the in-memory event log which keeps the last [capacity] events of this repository
+  /// for diagnostics. Zero disables the log and clears it.
+  Future<void> setEventLogCapacity(int capacity) =>
+      _client.invoke<void>('repository_set_event_log_capacity', {
+        'repository': _handle,
+        'capacity': capacity,
+      });
+
+  /// Events recorded in the event log, oldest first. See [setEventLogCapacity].
+  Future<List<LoggedEvent>> get eventLog => _client
+      .invoke<List<Object?>>('repository_event_log', _handle)
+      .then((list) => list
+          .map((raw) => LoggedEvent.decode(raw as List<Object?>))
+          .toList());
+
+  /// Current version of the repository as an opaque token. Persist it and pass it back later to
+  /// track changes incrementally.
+  Future<Uint8List> get currentVersion =>
+      _client.invoke<Uint8List>('repository_current_version', _handle);
+
+  /// Writes the index and all locally present blocks of this repository into an archive file at
+  /// [path] so they can be transferred to another device without a network connection (e.g., on a
+  /// USB stick) and merged there with [importArchive]. The data is stored encrypted.
+  Future<void> exportArchive(String path) =>
+      _client.invoke<void>('repository_export_archive', {
+        'repository': _handle,
+        'path': path,
+      });
+
+  /// Merges an archive created with [exportArchive] into this repository.
+  Future<void> importArchive(String path) =>
+      _client.invoke<void>('repository_import_archive', {
+        'repository': _handle,
+        'path': path,
+      });
+
+  /// Like [exportArchive] but writes into the provided raw file descriptor. Takes ownership of
+  /// the descriptor and closes it when done.
+  Future<void> exportArchiveToRawFd(int fd) => _invoke(
+        (port) => bindings.repository_export_archive_to_raw_fd(
+          _client.handle,
+          _handle,
+          fd,
+          NativeApi.postCObject,
+          port,
+        ),
+      );
+
+  /// Like [importArchive] but reads from the provided raw file descriptor. Takes ownership of the
+  /// descriptor and closes it when done.
+  Future<void> importArchiveFromRawFd(int fd) => _invoke(
+        (port) => bindings.repository_import_archive_from_raw_fd(
+          _client.handle,
+          _handle,
+          fd,
+          NativeApi.postCObject,
+          port,
+        ),
+      );
+
+  /// Fetch the per-repository sync protocol message statistics.
+  Future<MessageStats> get messageStats => _client
+      .invoke<List<Object?>>('repository_message_stats', _handle)
+      .then((list) => MessageStats.decode(list));
 }
 
 sealed class AccessChange {
@@ -636,6 +1990,25 @@ class ShareToken {
           .invoke<String>('share_token_normalize', s)
           .then((s) => ShareToken._(session._client, s));
 
+  /// Decodes a share token in the compact format (see [toCompact]). Note that [fromString]
+  /// accepts both the regular and the compact format.
+  static Future<ShareToken> fromCompact(Session session, String s) =>
+      session._client
+          .invoke<String>('share_token_from_compact', s)
+          .then((s) => ShareToken._(session._client, s));
+
+  /// Converts a recovery string (see [Repository.exportAccessSecrets]) into a share token. Pass
+  /// it to [Repository.create] to create a new replica of the repository.
+  static Future<ShareToken> fromRecovery(Session session, String recovery) =>
+      session._client
+          .invoke<String>('share_token_from_recovery', recovery)
+          .then((s) => ShareToken._(session._client, s));
+
+  /// Encodes the share token into a compact format suitable for QR codes (it uses only the
+  /// characters of the QR alphanumeric mode).
+  Future<String> toCompact() =>
+      _client.invoke<String>('share_token_to_compact', _token);
+
   /// Get the suggested repository name from the share token.
   Future<String> get suggestedName =>
       _client.invoke<String>('share_token_suggested_name', _token);
@@ -849,6 +2222,20 @@ class File {
     return _client.invoke<void>('file_flush', _handle);
   }
 
+  /// Enables write-behind flushing of this file: writes are committed automatically at most once
+  /// per [interval] instead of on every [flush]. Pass `null` to disable it. Writes done since the
+  /// last automatic flush are lost on crash, but are always committed on [flush] or [close].
+  Future<void> setAutoFlush(Duration? interval) {
+    if (debugTrace) {
+      print("File.setAutoFlush $interval");
+    }
+
+    return _client.invoke<void>('file_set_auto_flush', {
+      'file': _handle,
+      'interval': interval?.inMilliseconds,
+    });
+  }
+
   /// Read [size] bytes from this file, starting at [offset].
   ///
   /// To read the whole file at once:
@@ -920,21 +2307,126 @@ class File {
 
   Future<int> get progress => _client.invoke<int>('file_progress', _handle);
 
+  /// Returns whether the [len] bytes starting at [offset] are available locally, that is, whether
+  /// reading them would complete without waiting for any download.
+  Future<bool> isRangeAvailable(int offset, int len) =>
+      _client.invoke<bool>('file_is_range_available', {
+        'file': _handle,
+        'offset': offset,
+        'len': len,
+      });
+
   /// Copy the contents of the file into the provided raw file descriptor.
-  Future<void> copyToRawFd(int fd) {
+  ///
+  /// If [cancel] completes before the copy finishes, the copy is stopped, the descriptor closed
+  /// and the returned future fails with [ErrorCode.cancelled].
+  Future<void> copyToRawFd(int fd, {Future<void>? cancel}) {
     if (debugTrace) {
       print("File.copyToRawFd");
     }
 
-    return _invoke(
-      (port) => bindings.file_copy_to_raw_fd(
+    return _invoke((port) {
+      final copy = bindings.file_copy_to_raw_fd(
         _client.handle,
         _handle,
         fd,
         NativeApi.postCObject,
         port,
-      ),
-    );
+      );
+
+      cancel?.then((_) => bindings.file_cancel_copy(_client.handle, copy));
+    });
+  }
+}
+
+/// Set of file modifications committed atomically: peers observe either all of them or none.
+///
+/// Files are added with [openFile] or [createFile] which return the index of the file within the
+/// batch. Nothing is visible until [commit] is called. Closing the batch without committing it
+/// discards the modifications.
+class Batch {
+  final Client _client;
+  final int _handle;
+
+  Batch._(this._client, this._handle);
+
+  /// Starts a new batch in [repo].
+  static Future<Batch> create(Repository repo) async {
+    if (debugTrace) {
+      print("Batch.create");
+    }
+
+    return Batch._(repo._client,
+        await repo._client.invoke<int>('repository_batch', repo._handle));
+  }
+
+  /// Adds the existing file at [path] to this batch. Returns its index.
+  Future<int> openFile(String path) {
+    if (debugTrace) {
+      print("Batch.openFile $path");
+    }
+
+    return _client.invoke<int>('batch_open_file', {
+      'batch': _handle,
+      'path': path,
+    });
+  }
+
+  /// Creates a new file at [path] as part of this batch. Returns its index.
+  Future<int> createFile(String path) {
+    if (debugTrace) {
+      print("Batch.createFile $path");
+    }
+
+    return _client.invoke<int>('batch_create_file', {
+      'batch': _handle,
+      'path': path,
+    });
+  }
+
+  /// Writes [data] to the file at index [file], starting at [offset].
+  Future<void> write(int file, int offset, List<int> data) {
+    if (debugTrace) {
+      print("Batch.write");
+    }
+
+    return _client.invoke<void>('batch_write', {
+      'batch': _handle,
+      'file': file,
+      'offset': offset,
+      'data': Uint8List.fromList(data),
+    });
+  }
+
+  /// Truncates the file at index [file] to [size] bytes.
+  Future<void> truncate(int file, int size) {
+    if (debugTrace) {
+      print("Batch.truncate");
+    }
+
+    return _client.invoke<void>('batch_truncate', {
+      'batch': _handle,
+      'file': file,
+      'len': size,
+    });
+  }
+
+  /// Atomically commits all the modifications and closes this batch.
+  Future<void> commit() {
+    if (debugTrace) {
+      print("Batch.commit");
+    }
+
+    return _client.invoke<void>('batch_commit', _handle);
+  }
+
+  /// Closes this batch discarding all the modifications.
+  Future<void> close() {
+    if (debugTrace) {
+      print("Batch.close");
+    }
+
+    return _client.invoke<void>('batch_close', _handle);
   }
 }
 
@@ -19,6 +19,8 @@ internal interface Bindings : Library {
         configs_path: String,
         log_path: String?,
         log_tag: String,
+        worker_threads: Short,
+        dht_contacts_dir: String?,
         context: Pointer?,
         callback: Callback,
     ): SessionCreateResult
@@ -54,6 +54,9 @@ class Repository private constructor(internal val handle: Long, internal val cli
          *                    devices. See also [createShareToken]. This also determines the
          *                    maximal access mode the repo can be opened in. If null, it's *write*
          *                    mode.
+         * @param discoveryEnabled  whether DHT and PEX get enabled as soon as syncing is enabled.
+         *                    If false, the repo can't be discovered by other peers until they are
+         *                    explicitly enabled.
          */
         suspend fun create(
             session: Session,
@@ -61,6 +64,7 @@ class Repository private constructor(internal val handle: Long, internal val cli
             readSecret: SetLocalSecret?,
             writeSecret: SetLocalSecret?,
             shareToken: ShareToken? = null,
+            discoveryEnabled: Boolean = false,
         ): Repository {
             val client = session.client
             val handle = client.invoke(
@@ -69,6 +73,7 @@ class Repository private constructor(internal val handle: Long, internal val cli
                     readSecret,
                     writeSecret,
                     shareToken?.toString(),
+                    discoveryEnabled,
                 ),
             ) as Long
 
@@ -101,6 +106,13 @@ class Repository private constructor(internal val handle: Long, internal val cli
      */
     suspend fun close() = client.invoke(RepositoryClose(handle))
 
+    /**
+     * Moves the database file of the repository to [dst] while keeping the repository open.
+     *
+     * Fails if [dst] already exists.
+     */
+    suspend fun rename(dst: String) = client.invoke(RepositoryRename(handle, dst))
+
     /**
      * Subscribe to repository events.
      *
@@ -151,13 +163,19 @@ class Repository private constructor(internal val handle: Long, internal val cli
      *                   of what the repo is opened in.
      * @param name       optional human-readable name of the repo that the share token will be
      *                   labeled with. Useful to help organize the share tokens.
+     * @param notAfter   optional expiry of the token in milliseconds since the unix epoch. Honest
+     *                   clients refuse the token after that time. This is only advisory - a
+     *                   modified client can ignore it.
      */
     suspend fun createShareToken(
         secret: LocalSecret? = null,
         accessMode: AccessMode = AccessMode.WRITE,
         name: String? = null,
+        notAfter: Long? = null,
     ): ShareToken {
-        val raw = client.invoke(RepositoryCreateShareToken(handle, secret, accessMode, name)) as String
+        val raw = client.invoke(
+            RepositoryCreateShareToken(handle, secret, accessMode, name, notAfter),
+        ) as String
         return ShareToken(raw, client)
     }
 
@@ -292,9 +310,11 @@ class Repository private constructor(internal val handle: Long, internal val cli
      *
      * @param src path to move the entry from.
      * @param dst path to move the entry to.
+     * @param preserveHistory whether the entry keeps its version history so other replicas see
+     *        this as a move, not as a removal followed by a creation.
      */
-    suspend fun moveEntry(src: String, dst: String) =
-        client.invoke(RepositoryMoveEntry(handle, src, dst))
+    suspend fun moveEntry(src: String, dst: String, preserveHistory: Boolean = true) =
+        client.invoke(RepositoryMoveEntryPreservingHistory(handle, src, dst, preserveHistory))
 }
 
 /**
@@ -55,6 +55,7 @@ internal class RepositoryCreate(
     val readSecret: SetLocalSecret?,
     val writeSecret: SetLocalSecret?,
     val shareToken: String?,
+    val discoveryEnabled: Boolean,
 ) : Request() {
     override fun packContent(packer: MessagePacker) {
         packer.packMap(
@@ -63,6 +64,7 @@ internal class RepositoryCreate(
                 "read_secret" to readSecret,
                 "write_secret" to writeSecret,
                 "shareToken" to shareToken,
+                "discovery_enabled" to discoveryEnabled,
             ),
         )
     }
@@ -78,6 +80,16 @@ internal class RepositoryClose : ValueRequest<Long> {
     constructor(value: Long) : super(value)
 }
 
+internal class RepositoryRename(val repository: Long, val dst: String) : Request() {
+    override fun packContent(packer: MessagePacker) =
+        packer.packMap(
+            mapOf(
+                "repository" to repository,
+                "dst" to dst,
+            ),
+        )
+}
+
 internal class RepositorySubscribe : ValueRequest<Long> {
     constructor(value: Long) : super(value)
 }
@@ -196,6 +208,23 @@ internal class RepositoryMoveEntry(val repository: Long, val src: String, val ds
         )
 }
 
+internal class RepositoryMoveEntryPreservingHistory(
+    val repository: Long,
+    val src: String,
+    val dst: String,
+    val preserveHistory: Boolean,
+) : Request() {
+    override fun packContent(packer: MessagePacker) =
+        packer.packMap(
+            mapOf(
+                "repository" to repository,
+                "src" to src,
+                "dst" to dst,
+                "preserve_history" to preserveHistory,
+            ),
+        )
+}
+
 internal class RepositoryIsDhtEnabled : ValueRequest<Long> {
     constructor(value: Long) : super(value)
 }
@@ -219,6 +248,7 @@ internal class RepositoryCreateShareToken(
     val secret: LocalSecret?,
     val accessMode: AccessMode,
     val name: String?,
+    val notAfter: Long?,
 ) : Request() {
     override fun packContent(packer: MessagePacker) =
         packer.packMap(
@@ -227,6 +257,7 @@ internal class RepositoryCreateShareToken(
                 "secret" to secret,
                 "access_mode" to accessMode.encode(),
                 "name" to name,
+                "not_after" to notAfter,
             ),
         )
 }
@@ -285,6 +316,18 @@ internal class ShareTokenNormalize : ValueRequest<String> {
     constructor(value: String) : super(value)
 }
 
+internal class ShareTokenToCompact : ValueRequest<String> {
+    constructor(value: String) : super(value)
+}
+
+internal class ShareTokenFromCompact : ValueRequest<String> {
+    constructor(value: String) : super(value)
+}
+
+internal class ShareTokenFromRecovery : ValueRequest<String> {
+    constructor(value: String) : super(value)
+}
+
 internal class DirectoryCreate(val repository: Long, val path: String) : Request() {
     override fun packContent(packer: MessagePacker) =
         packer.packMap(
@@ -439,6 +482,8 @@ internal class NetworkAddCacheServer : ValueRequest<String> {
 
 internal class NetworkShutdown : EmptyRequest()
 
+internal class SessionWorkerThreads : EmptyRequest()
+
 internal class Unsubscribe : ValueRequest<Long> {
     constructor(value: Long) : super(value)
 }
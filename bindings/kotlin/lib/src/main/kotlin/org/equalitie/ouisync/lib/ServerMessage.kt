@@ -203,7 +203,15 @@ internal class Notification(val content: Any) : ServerMessage {
 
         private fun unpackValue(name: String, unpacker: MessageUnpacker): Any {
             when (name) {
-                "network" -> return NetworkEvent.decode(unpacker.unpackByte())
+                "network" -> {
+                    // Protocol version mismatch is sent together with its details (as an array).
+                    if (unpacker.getNextFormat().getValueType() == ValueType.ARRAY) {
+                        unpacker.skipValue()
+                        return NetworkEvent.PROTOCOL_VERSION_MISMATCH
+                    }
+
+                    return NetworkEvent.decode(unpacker.unpackByte())
+                }
                 else -> throw InvalidNotification()
             }
         }
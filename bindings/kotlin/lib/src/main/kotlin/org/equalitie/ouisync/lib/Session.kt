@@ -41,6 +41,13 @@ class Session private constructor(
          * @param kind        whether to create shared or unique session. `SHARED` should be used
          *                    by default. `UNIQUE` is useful mostly for tests, to ensure test
          *                    isolation and/or to simulate multiple replicas in a single test.
+         * @param workerThreads number of threads of the async runtime. Zero means one thread per
+         *                    CPU core. It only takes effect if this call actually creates a new
+         *                    session, that is, not when a shared session already exists.
+         * @param dhtContactsDir path to the directory where the DHT contacts are stored so that
+         *                    the DHT doesn't have to bootstrap from scratch after a restart. If
+         *                    null, [configsPath] is used. Like [workerThreads], it only takes
+         *                    effect if this call actually creates a new session.
          * @throws Error
          */
         fun create(
@@ -48,7 +55,17 @@ class Session private constructor(
             logPath: String? = null,
             logTag: String = "ouisync",
             kind: SessionKind = SessionKind.SHARED,
+            workerThreads: Int = 0,
+            dhtContactsDir: String? = null,
         ): Session {
+            // Checked here because the native function takes a 16-bit integer.
+            if (workerThreads !in 0..UShort.MAX_VALUE.toInt()) {
+                throw Error(
+                    ErrorCode.INVALID_ARGUMENT,
+                    "invalid number of worker threads: $workerThreads",
+                )
+            }
+
             val client = Client()
 
             val callback = object : Callback {
@@ -66,6 +83,8 @@ class Session private constructor(
                 configsPath,
                 logPath,
                 logTag,
+                workerThreads.toShort(),
+                dhtContactsDir,
                 null,
                 callback,
             )
@@ -259,4 +278,9 @@ class Session private constructor(
      * every start.
      */
     suspend fun thisRuntimeId(): String = client.invoke(NetworkThisRuntimeId()) as String
+
+    /**
+     * Returns the number of worker threads of the async runtime of this session.
+     */
+    suspend fun workerThreads(): Int = client.invoke(SessionWorkerThreads()) as Int
 }
@@ -19,8 +19,36 @@ class ShareToken internal constructor(private val value: String, private val cli
 
             return ShareToken(value, client)
         }
+
+        /**
+         * Creates share token from a String in the compact format (see [toCompact]). Note that
+         * [fromString] accepts both the regular and the compact format.
+         */
+        suspend fun fromCompact(session: Session, string: String): ShareToken {
+            val client = session.client
+            val value = client.invoke(ShareTokenFromCompact(string)) as String
+
+            return ShareToken(value, client)
+        }
+
+        /**
+         * Creates share token from a recovery string exported from another replica. Pass it to
+         * [Repository.create] to create a new replica of the repository.
+         */
+        suspend fun fromRecovery(session: Session, recovery: String): ShareToken {
+            val client = session.client
+            val value = client.invoke(ShareTokenFromRecovery(recovery)) as String
+
+            return ShareToken(value, client)
+        }
     }
 
+    /**
+     * Encodes this token into a compact format suitable for QR codes (it uses only the characters
+     * of the QR alphanumeric mode).
+     */
+    suspend fun toCompact() = client.invoke(ShareTokenToCompact(value)) as String
+
     /**
      * Returns the access mode this share token grants.
      */
@@ -37,6 +37,12 @@ public enum OuisyncErrorCode: UInt16 {
     case InvalidHandle = 15
     /// Entry has been changed and no longer matches the expected value
     case EntryChanged = 16
+    /// The file is not a repository or is corrupted
+    case NotARepository = 17
+    /// The operation would exceed the storage quota of the repository
+    case QuotaExceeded = 18
+    /// The share token has expired
+    case TokenExpired = 19
 
     // These can't happen and apple devices
     // case VfsInvalidMountPoint = 2048
@@ -75,6 +81,9 @@ public class OuisyncError : Error, CustomDebugStringConvertible {
         case .ConnectionLost: codeStr = "Connection lost"
         case .InvalidHandle: codeStr = "Invalid handle to a resource (e.g., Repository, File, ...)"
         case .EntryChanged: codeStr = "Entry has been changed and no longer matches the expected value"
+        case .NotARepository: codeStr = "The file is not a repository or is corrupted"
+        case .QuotaExceeded: codeStr = "The operation would exceed the storage quota of the repository"
+        case .TokenExpired: codeStr = "The share token has expired"
 
         case .Other: codeStr = "Unspecified error"
         }
@@ -45,6 +45,12 @@ impl ConfigStore {
         }
     }
 
+    /// Stores the DHT contacts in `dir` instead of in the config directory.
+    pub fn with_dht_contacts_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.dht_contacts_store = Arc::new(dht_contacts::Store::new(dir));
+        self
+    }
+
     pub fn dht_contacts_store(&self) -> Arc<dht_contacts::Store> {
         self.dht_contacts_store.clone()
     }
@@ -100,6 +100,10 @@ where
             return Ok(());
         }
 
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+
         let mut file = fs::OpenOptions::new()
             .write(true)
             .create(true)
@@ -6,6 +6,11 @@ use std::net::SocketAddr;
 const BIND_KEY: ConfigKey<Vec<PeerAddr>> =
     ConfigKey::new("bind", "Addresses to bind the network listeners to");
 
+const BIND_INTERFACE_KEY: ConfigKey<String> = ConfigKey::new(
+    "bind_interface",
+    "Name of the network interface to restrict the network listeners and connections to",
+);
+
 const PORT_FORWARDING_ENABLED_KEY: ConfigKey<bool> =
     ConfigKey::new("port_forwarding_enabled", "Enable port forwarding / UPnP");
 
@@ -45,6 +50,16 @@ const LAST_USED_UDP_PORT_COMMENT: &str =
 
 const PEX_KEY: ConfigKey<PexConfig> = ConfigKey::new("pex", "Peer exchange configuration");
 
+const DHT_BOOTSTRAP_NODES_KEY: ConfigKey<Vec<SocketAddr>> = ConfigKey::new(
+    "dht_bootstrap_nodes",
+    "List of nodes to bootstrap the DHT against. If empty, the default DHT routers are used",
+);
+
+const STUN_SERVERS_KEY: ConfigKey<Vec<SocketAddr>> = ConfigKey::new(
+    "stun_servers",
+    "List of STUN servers to use instead of the built-in ones. If empty, STUN is disabled",
+);
+
 #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct NetworkDefaults {
     pub port_forwarding_enabled: bool,
@@ -53,6 +68,9 @@ pub struct NetworkDefaults {
 
 /// Initialize the network according to the config.
 pub async fn init(network: &Network, config: &ConfigStore, defaults: NetworkDefaults) {
+    let interface = config.entry(BIND_INTERFACE_KEY).get().await.ok();
+    network.set_bind_interface(interface).await;
+
     let bind_addrs = config.entry(BIND_KEY).get().await.unwrap_or_default();
     bind_with_reuse_ports(network, config, &bind_addrs).await;
 
@@ -78,6 +96,19 @@ pub async fn init(network: &Network, config: &ConfigStore, defaults: NetworkDefa
     let PexConfig { send, recv } = config.entry(PEX_KEY).get().await.unwrap_or_default();
     network.set_pex_send_enabled(send);
     network.set_pex_recv_enabled(recv);
+
+    let nodes = config
+        .entry(DHT_BOOTSTRAP_NODES_KEY)
+        .get()
+        .await
+        .unwrap_or_default();
+    if !nodes.is_empty() {
+        network.set_dht_bootstrap_nodes(nodes);
+    }
+
+    if let Ok(servers) = config.entry(STUN_SERVERS_KEY).get().await {
+        network.set_stun_servers(servers);
+    }
 }
 
 /// Binds the network to the specified addresses.
@@ -89,6 +120,23 @@ pub async fn bind(network: &Network, config: &ConfigStore, addrs: &[PeerAddr]) {
     bind_with_reuse_ports(network, config, addrs).await;
 }
 
+/// Restricts the network to the given network interface or lifts the restriction if `None`. See
+/// [`Network::set_bind_interface`] for details.
+pub async fn set_bind_interface(
+    network: &Network,
+    config: &ConfigStore,
+    interface: Option<String>,
+) {
+    let entry = config.entry(BIND_INTERFACE_KEY);
+
+    match &interface {
+        Some(interface) => entry.set(interface).await.ok(),
+        None => entry.remove().await.ok(),
+    };
+
+    network.set_bind_interface(interface).await;
+}
+
 async fn bind_with_reuse_ports(network: &Network, config: &ConfigStore, addrs: &[PeerAddr]) {
     let mut last_used_ports = LastUsedPorts::load(config).await;
     let addrs: Vec<_> = addrs
@@ -190,6 +238,37 @@ pub async fn set_pex_recv_enabled(network: &Network, config: &ConfigStore, enabl
     network.set_pex_recv_enabled(enabled);
 }
 
+/// Sets the nodes to bootstrap the DHT against. Empty `nodes` means use the default DHT routers.
+pub async fn set_dht_bootstrap_nodes(
+    network: &Network,
+    config: &ConfigStore,
+    nodes: Vec<SocketAddr>,
+) {
+    config.entry(DHT_BOOTSTRAP_NODES_KEY).set(&nodes).await.ok();
+    network.set_dht_bootstrap_nodes(nodes);
+}
+
+/// Sets the STUN servers to use instead of the built-in ones. Empty `servers` disables STUN, `None`
+/// restores the built-in servers.
+pub async fn set_stun_servers(
+    network: &Network,
+    config: &ConfigStore,
+    servers: Option<Vec<SocketAddr>>,
+) {
+    let entry = config.entry(STUN_SERVERS_KEY);
+
+    match servers {
+        Some(servers) => {
+            entry.set(&servers).await.ok();
+            network.set_stun_servers(servers);
+        }
+        None => {
+            entry.remove().await.ok();
+            network.reset_stun_servers();
+        }
+    }
+}
+
 /// Utility to help reuse bind ports across network restarts.
 struct LastUsedPorts {
     quic_v4: u16,
@@ -1,6 +1,7 @@
 pub mod remote;
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use ouisync_lib::{DirEvent, IntegrityReport, PathEvent, Progress, RebuildIndexReport};
 use serde::{Deserialize, Deserializer, Serialize};
 
 pub trait DeserializeVersioned<'de>: Sized {
@@ -34,10 +35,207 @@ impl<T, E> ServerMessage<T, E> {
 #[serde(rename_all = "snake_case")]
 pub enum Notification {
     Repository,
-    Network(NetworkEvent),
+    Network(NetworkNotification),
     StateMonitor,
     /// The list of repositories in a session has changed.
     RepositoryListChanged,
+    /// Result of a duplicate content search.
+    Duplicates(DuplicatesEvent),
+    /// A file has concurrent versions that need to be resolved.
+    Conflict(ConflictEvent),
+    /// An entry in a watched directory has changed.
+    DirectoryChanged(DirectoryEvent),
+    /// A watched entry or any of its descendants has changed.
+    PathChanged(PathChangeEvent),
+    /// Progress of a bulk import or export.
+    Transfer(TransferEvent),
+    /// Result of a repository integrity check.
+    Integrity(IntegrityEvent),
+    /// Syncing progress of a repository has changed.
+    SyncProgress(Progress),
+    /// Progress of a repository key rotation.
+    KeyRotation(KeyRotationEvent),
+    /// Progress of a repository index rebuild.
+    IndexRebuild(IndexRebuildEvent),
+}
+
+/// Duplicate content search notification event.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatesEvent {
+    /// Number of files hashed so far and the total number of files to hash.
+    Progress(Progress),
+    /// Group of paths of files with identical content.
+    Group(Vec<String>),
+    /// The search failed with the given error message. No more events follow.
+    Failed(String),
+    /// The search completed. No more events follow.
+    Done,
+}
+
+/// Repository integrity check notification event. Hashes and block ids are hex encoded.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityEvent {
+    /// The children of the index node with the given hash don't hash to it.
+    NodeHashMismatch(String),
+    /// The block with the given id is marked as present in the index but it's not stored.
+    BlockMissing(String),
+    /// The content of the block with the given id doesn't hash to the id.
+    BlockCorrupted(String),
+    /// The check failed with the given error message. No more events follow.
+    Failed(String),
+    /// The check completed. No more events follow.
+    Done,
+}
+
+impl From<IntegrityReport> for IntegrityEvent {
+    fn from(report: IntegrityReport) -> Self {
+        match report {
+            IntegrityReport::NodeHashMismatch(hash) => Self::NodeHashMismatch(hash.to_string()),
+            IntegrityReport::BlockMissing(block_id) => Self::BlockMissing(block_id.to_string()),
+            IntegrityReport::BlockCorrupted(block_id) => Self::BlockCorrupted(block_id.to_string()),
+        }
+    }
+}
+
+/// Key rotation notification event.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyRotationEvent {
+    /// Number of bytes re-encrypted so far and the total number of bytes to re-encrypt.
+    Progress(Progress),
+    /// The rotation failed with the given error message. No more events follow.
+    Failed(String),
+    /// The rotation completed. No more events follow.
+    Done,
+}
+
+/// Index rebuild notification event.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexRebuildEvent {
+    /// Number of blocks matched so far and the total number of stored blocks.
+    Progress(Progress),
+    /// The rebuild failed with the given error message. No more events follow.
+    Failed(String),
+    /// The rebuild completed with the given report. No more events follow.
+    Done(RebuildIndexReport),
+}
+
+/// Conflict notification event.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ConflictEvent {
+    /// Path of the conflicting file.
+    pub path: String,
+    /// Unique paths of the individual versions of the file.
+    pub versions: Vec<String>,
+}
+
+/// Details of a protocol version mismatch.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ProtocolMismatchEvent {
+    /// Protocol version of this library.
+    pub our_version: u32,
+    /// Protocol version of the peer.
+    pub their_version: u32,
+    /// Address of the peer.
+    pub peer_addr: String,
+    /// Runtime id of the peer formatted as a hex string. `None` if the peer disconnected before
+    /// revealing it.
+    pub runtime_id: Option<String>,
+}
+
+/// Directory watch notification event.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirectoryEvent {
+    /// Entry with the given name was created.
+    Created(String),
+    /// Entry with the given name was modified.
+    Modified(String),
+    /// Entry with the given name was removed.
+    Removed(String),
+    /// The watched directory was removed or moved away. No more events follow.
+    DirectoryRemoved,
+    /// Watching failed with the given error message. No more events follow.
+    Failed(String),
+}
+
+impl From<DirEvent> for DirectoryEvent {
+    fn from(event: DirEvent) -> Self {
+        match event {
+            DirEvent::Created(name) => Self::Created(name),
+            DirEvent::Modified(name) => Self::Modified(name),
+            DirEvent::Removed(name) => Self::Removed(name),
+            DirEvent::DirectoryRemoved => Self::DirectoryRemoved,
+        }
+    }
+}
+
+/// Path watch notification event.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathChangeEvent {
+    /// The entry or any of its descendants changed. Carries the new version vector of the entry,
+    /// encoded with msgpack (same as the repository current version).
+    Changed(Vec<u8>),
+    /// The entry was removed or moved away. No more events follow.
+    Removed,
+    /// Watching failed with the given error message. No more events follow.
+    Failed(String),
+}
+
+impl From<PathEvent> for PathChangeEvent {
+    fn from(event: PathEvent) -> Self {
+        match event {
+            // unwrap is OK because serializing a version vector into a `Vec` can't fail.
+            PathEvent::Changed(vv) => Self::Changed(rmp_serde::to_vec(&vv).unwrap()),
+            PathEvent::Removed => Self::Removed,
+        }
+    }
+}
+
+/// Bulk import/export notification event. Paths are relative to the root of the transfer.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferEvent {
+    /// Entry was copied. `bytes` is the number of bytes copied so far and `total` the number of
+    /// bytes to copy in total.
+    Copied {
+        path: String,
+        bytes: u64,
+        total: u64,
+    },
+    /// Entry was skipped because it can't be transferred.
+    Skipped { path: String, reason: String },
+    /// Transfer of the entry failed. The transfer continues with the next entry.
+    Failed { path: String, error: String },
+    /// The transfer completed. Carries the number of copied, skipped and failed entries. No more
+    /// events follow.
+    Done {
+        copied: u64,
+        skipped: u64,
+        failed: u64,
+    },
+    /// The whole transfer failed with the given error message. No more events follow.
+    Aborted(String),
+}
+
+/// How to resolve collisions with existing entries when exporting to the host filesystem.
+/// Directories are always merged with existing directories.
+#[derive(
+    Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, TryFromPrimitive, IntoPrimitive,
+)]
+#[repr(u8)]
+#[serde(into = "u8", try_from = "u8")]
+pub enum CollisionPolicy {
+    /// Keep the existing entry and skip the exported one.
+    Skip = 0,
+    /// Replace the existing file. Directories are never replaced.
+    Overwrite = 1,
+    /// Export the entry under a different name ("name (1).ext", "name (2).ext", ...).
+    Rename = 2,
 }
 
 /// Network notification event.
@@ -49,9 +247,35 @@ pub enum Notification {
 pub enum NetworkEvent {
     /// A peer has appeared with higher protocol version than us. Probably means we are using
     /// outdated library. This event can be used to notify the user that they should update the app.
+    /// Sent together with the details of the mismatch (see [`NetworkNotification`]).
     ProtocolVersionMismatch = 0,
     /// The set of known peers has changed (e.g., a new peer has been discovered)
     PeerSetChange = 1,
+    /// Our external address or the behavior of the NAT we are behind has changed.
+    ExternalAddrChange = 2,
+}
+
+/// Payload of the network notification.
+// NOTE: using untagged so events without details are still sent as the bare `NetworkEvent` code.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NetworkNotification {
+    /// Event without details.
+    Event(NetworkEvent),
+    /// `NetworkEvent::ProtocolVersionMismatch` together with the details of the mismatch.
+    ProtocolVersionMismatch(ProtocolMismatchEvent),
+}
+
+impl From<NetworkEvent> for NetworkNotification {
+    fn from(event: NetworkEvent) -> Self {
+        Self::Event(event)
+    }
+}
+
+impl From<ProtocolMismatchEvent> for NetworkNotification {
+    fn from(event: ProtocolMismatchEvent) -> Self {
+        Self::ProtocolVersionMismatch(event)
+    }
 }
 
 /// Opaque, non-sensitive value unique to a particular client session and accessible to both the
@@ -116,4 +340,33 @@ mod tests {
             assert_eq!(decoded, orig);
         }
     }
+
+    #[test]
+    fn network_notification_serialize_deserialize() {
+        let origs = [
+            Notification::Network(NetworkEvent::PeerSetChange.into()),
+            Notification::Network(NetworkEvent::ExternalAddrChange.into()),
+            Notification::Network(
+                ProtocolMismatchEvent {
+                    our_version: 15,
+                    their_version: 16,
+                    peer_addr: "quic/192.168.1.204:45678".to_owned(),
+                    runtime_id: Some("ab".repeat(32)),
+                }
+                .into(),
+            ),
+        ];
+
+        for orig in origs {
+            let encoded = rmp_serde::to_vec(&orig).unwrap();
+            let decoded: Notification = rmp_serde::from_slice(&encoded).unwrap();
+            assert_eq!(decoded, orig);
+        }
+
+        // Events without details are encoded as the bare event code.
+        assert_eq!(
+            rmp_serde::to_vec(&NetworkNotification::Event(NetworkEvent::PeerSetChange)).unwrap(),
+            rmp_serde::to_vec(&NetworkEvent::PeerSetChange).unwrap(),
+        );
+    }
 }
@@ -9,7 +9,12 @@ use ouisync_lib::{
     RepositoryId, RepositoryParams, SetLocalSecret, ShareToken, StorageSize, WriteSecrets,
 };
 use state_monitor::StateMonitor;
-use std::{io, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 use thiserror::Error;
 use tokio_rustls::rustls;
 use tracing::instrument;
@@ -62,7 +67,7 @@ pub async fn create(
         .with_parent_monitor(repos_monitor.clone());
 
     let access_secrets = if let Some(share_token) = share_token {
-        share_token.into_secrets()
+        share_token.into_secrets()?
     } else {
         AccessSecrets::random_write()
     };
@@ -96,16 +101,41 @@ pub async fn open(
     Ok(repository)
 }
 
+/// Opens an existing repository using whichever of the given local secrets unlocks the highest
+/// access mode. Returns also the index of the secret that was used, if any.
+pub async fn open_with_secrets(
+    store: PathBuf,
+    local_secrets: &[LocalSecret],
+    config: &ConfigStore,
+    repos_monitor: &StateMonitor,
+) -> Result<(Repository, Option<usize>), OpenError> {
+    let params = RepositoryParams::new(store)
+        .with_device_id(device_id::get_or_create(config).await?)
+        .with_parent_monitor(repos_monitor.clone());
+
+    let result = Repository::open_with_secrets(&params, local_secrets, AccessMode::Write).await?;
+
+    Ok(result)
+}
+
 /// The `key` parameter is optional, if `None` the current access level of the opened
 /// repository is used. If provided, the highest access level that the key can unlock is used.
+/// Fails with `WrongPassword` if the key doesn't unlock the requested `access_mode`.
 pub async fn create_share_token(
     repository: &Repository,
     local_secret: Option<LocalSecret>,
     access_mode: AccessMode,
     name: Option<String>,
+    not_after: Option<SystemTime>,
 ) -> Result<String, ouisync_lib::Error> {
     let access_secrets = if let Some(local_secret) = local_secret {
-        repository.unlock_secrets(local_secret).await?
+        let access_secrets = repository.unlock_secrets(local_secret).await?;
+
+        if access_secrets.access_mode() < access_mode {
+            return Err(ouisync_lib::Error::WrongPassword);
+        }
+
+        access_secrets
     } else {
         repository.secrets()
     };
@@ -116,6 +146,11 @@ pub async fn create_share_token(
     } else {
         share_token
     };
+    let share_token = if let Some(not_after) = not_after {
+        share_token.with_expiry(not_after)
+    } else {
+        share_token
+    };
 
     Ok(share_token.to_string())
 }
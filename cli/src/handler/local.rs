@@ -262,6 +262,7 @@ impl ouisync_bridge::transport::Handler for LocalHandler {
                     password.map(Password::from).map(LocalSecret::Password),
                     mode,
                     Some(name),
+                    None,
                 )
                 .await?;
 
@@ -326,7 +327,9 @@ impl ouisync_bridge::transport::Handler for LocalHandler {
                     .collect();
                 Ok(names.into())
             }
-            Request::Bind { addrs } => {
+            Request::Bind { addrs, interface } => {
+                network::set_bind_interface(&self.state.network, &self.state.config, interface)
+                    .await;
                 network::bind(&self.state.network, &self.state.config, &addrs).await;
                 Ok(().into())
             }
@@ -498,7 +501,7 @@ impl ouisync_bridge::transport::Handler for LocalHandler {
 
                 let holder = self.state.repositories.find(&name)?;
                 let token = ShareToken::from_str(&token)?;
-                let new_credentials = Credentials::with_random_writer_id(token.into_secrets());
+                let new_credentials = Credentials::with_random_writer_id(token.into_secrets()?);
                 holder.repository.set_credentials(new_credentials).await?;
                 Ok(().into())
             }
@@ -465,6 +465,7 @@ mod tests {
             config_dir: temp_dir.path().join("config"),
             store_dir: temp_dir.path().join("store"),
             mount_dir: temp_dir.path().join("mount"),
+            dht_contacts_dir: None,
         };
 
         let certs = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
@@ -50,6 +50,12 @@ pub(crate) struct Dirs {
     /// Can be also specified with env variable OUISYNC_MOUNT_DIR.
     #[arg(long, default_value_os_t = default_mount_dir(), value_name = "PATH")]
     pub mount_dir: PathBuf,
+
+    /// Directory to store the DHT contacts in, to speed up the DHT bootstrap after a restart
+    ///
+    /// Defaults to the config directory.
+    #[arg(long, value_name = "PATH")]
+    pub dht_contacts_dir: Option<PathBuf>,
 }
 
 /// Path to the config directory.
@@ -172,6 +172,14 @@ pub(crate) enum Request {
         /// Examples: quic/0.0.0.0:0, quic/[::]:0, tcp/192.168.0.100:55555
         #[arg(value_name = "PROTO/IP:PORT")]
         addrs: Vec<PeerAddr>,
+
+        /// Restrict the network to the network interface with this name (e.g. wg0). Unspecified
+        /// IPs (0.0.0.0 or [::]) are resolved to the current addresses of the interface and
+        /// re-resolved when they change. On Linux and Android the sockets are also bound to the
+        /// interface itself (SO_BINDTODEVICE), elsewhere only to its addresses. If omitted, the
+        /// network is not restricted to any interface.
+        #[arg(short, long, value_name = "NAME")]
+        interface: Option<String>,
     },
     /// List addresses and ports we are listening on
     ListBinds,
@@ -572,6 +580,7 @@ impl fmt::Display for PeerInfoDisplay<'_> {
                 PeerState::Connecting => "connecting",
                 PeerState::Handshaking => "handshaking",
                 PeerState::Active { .. } => "active",
+                PeerState::Dead => "dead",
             },
         )?;
 
@@ -584,6 +593,14 @@ impl fmt::Display for PeerInfoDisplay<'_> {
                 self.0.stats.bytes_tx,
                 self.0.stats.bytes_rx,
             )?;
+
+            if let Some(rtt) = self.0.rtt {
+                write!(f, " {}ms", rtt.as_millis())?;
+            }
+
+            if let Some(display_name) = &self.0.display_name {
+                write!(f, " {:?}", display_name)?;
+            }
         }
 
         Ok(())
@@ -614,6 +631,9 @@ mod tests {
                 source: PeerSource::Dht,
                 state: PeerState::Connecting,
                 stats: Stats::default(),
+                connected_since: SystemTime::UNIX_EPOCH,
+                rtt: None,
+                display_name: None,
             })
             .to_string(),
             "127.0.0.1 1248 quic dht connecting"
@@ -635,6 +655,11 @@ mod tests {
                     throughput_tx: 0,
                     throughput_rx: 0,
                 },
+                connected_since: DateTime::parse_from_rfc3339("2024-06-12T02:30:00Z")
+                    .unwrap()
+                    .into(),
+                rtt: Some(Duration::from_millis(42)),
+                display_name: Some("my phone".to_owned()),
             })
             .to_string(),
             "127.0.0.1 \
@@ -645,7 +670,9 @@ mod tests {
              ee1aa49a4459dfe813a3cf6eb882041230c7b2558469de81f87c9bf23bf10a03 \
              2024-06-12T02:30:00Z \
              1024 \
-             4096"
+             4096 \
+             42ms \
+             \"my phone\""
         );
     }
 }
@@ -36,6 +36,10 @@ pub(crate) struct State {
 impl State {
     pub async fn init(dirs: &Dirs, monitor: StateMonitor) -> Result<Arc<Self>, Error> {
         let config = ConfigStore::new(&dirs.config_dir);
+        let config = match &dirs.dht_contacts_dir {
+            Some(dir) => config.with_dht_contacts_dir(dir),
+            None => config,
+        };
 
         let network = Network::new(
             monitor.make_child("Network"),
@@ -0,0 +1,123 @@
+use crate::{
+    error::Error,
+    registry::{Handle, InvalidHandle},
+    repository::{RepositoryHandle, RepositoryHolder},
+    state::State,
+};
+use camino::Utf8PathBuf;
+use deadlock::AsyncMutex;
+use ouisync_lib::{Batch, File};
+use std::{io::SeekFrom, sync::Arc};
+
+pub struct BatchHolder {
+    // `None` after the batch has been committed.
+    batch: AsyncMutex<Option<Batch>>,
+    repository: Arc<RepositoryHolder>,
+}
+
+pub(crate) type BatchHandle = Handle<Arc<BatchHolder>>;
+
+/// Starts a batch of file modifications to be committed atomically.
+pub(crate) fn create(state: &State, repo: RepositoryHandle) -> Result<BatchHandle, Error> {
+    let repository = state.repositories.get(repo)?;
+    let batch = repository.repository.batch()?;
+
+    let holder = BatchHolder {
+        batch: AsyncMutex::new(Some(batch)),
+        repository,
+    };
+
+    Ok(state.batches.insert(Arc::new(holder)))
+}
+
+/// Adds the existing file at the given path to the batch. Returns the index of the file within the
+/// batch.
+pub(crate) async fn open_file(
+    state: &State,
+    handle: BatchHandle,
+    path: Utf8PathBuf,
+) -> Result<u64, Error> {
+    let holder = state.batches.get(handle)?;
+    let mut batch = holder.batch.lock().await;
+    let batch = batch.as_mut().ok_or(InvalidHandle)?;
+
+    if let Some(index) = batch.position(&path) {
+        return Ok(index as u64);
+    }
+
+    let file = holder.repository.repository.open_file(&path).await?;
+    batch.add_file(&path, file).await?;
+
+    Ok(batch.len() as u64 - 1)
+}
+
+/// Creates a new file at the given path as part of the batch. Returns the index of the file within
+/// the batch.
+pub(crate) async fn create_file(
+    state: &State,
+    handle: BatchHandle,
+    path: Utf8PathBuf,
+) -> Result<u64, Error> {
+    let holder = state.batches.get(handle)?;
+    let mut batch = holder.batch.lock().await;
+    let batch = batch.as_mut().ok_or(InvalidHandle)?;
+
+    batch.create_file(&path).await?;
+
+    Ok(batch.len() as u64 - 1)
+}
+
+pub(crate) async fn write(
+    state: &State,
+    handle: BatchHandle,
+    file: u64,
+    offset: u64,
+    buffer: Vec<u8>,
+) -> Result<(), Error> {
+    let holder = state.batches.get(handle)?;
+    let mut batch = holder.batch.lock().await;
+    let file = get_file(&mut batch, file)?;
+
+    file.seek(SeekFrom::Start(offset));
+    file.write_all(&buffer).await?;
+
+    Ok(())
+}
+
+/// Truncate the file to `len` bytes.
+pub(crate) async fn truncate(
+    state: &State,
+    handle: BatchHandle,
+    file: u64,
+    len: u64,
+) -> Result<(), Error> {
+    let holder = state.batches.get(handle)?;
+    let mut batch = holder.batch.lock().await;
+    let file = get_file(&mut batch, file)?;
+
+    file.truncate(len)?;
+
+    Ok(())
+}
+
+/// Atomically commits all the modifications done in the batch and closes it.
+pub(crate) async fn commit(state: &State, handle: BatchHandle) -> Result<(), Error> {
+    let holder = state.batches.remove(handle).ok_or(InvalidHandle)?;
+    let batch = holder.batch.lock().await.take().ok_or(InvalidHandle)?;
+
+    batch.commit().await?;
+
+    Ok(())
+}
+
+/// Closes the batch discarding all the modifications done in it.
+pub(crate) fn close(state: &State, handle: BatchHandle) {
+    state.batches.remove(handle);
+}
+
+fn get_file(batch: &mut Option<Batch>, index: u64) -> Result<&mut File, InvalidHandle> {
+    batch
+        .as_mut()
+        .and_then(|batch| batch.file_mut(usize::try_from(index).ok()?))
+        .ok_or(InvalidHandle)
+}
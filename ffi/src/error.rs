@@ -1,4 +1,5 @@
 use crate::{
+    file::CopyCancelled,
     registry::InvalidHandle,
     repository::{EntryChanged, RegistrationRequired},
     session::SessionError,
@@ -60,6 +61,23 @@ pub enum ErrorCode {
     InvalidHandle = 15,
     /// Entry has been changed and no longer matches the expected value
     EntryChanged = 16,
+    /// The file is not a repository or is corrupted
+    NotARepository = 17,
+    /// The operation would exceed the storage quota of the repository
+    QuotaExceeded = 18,
+    /// The share token has expired
+    TokenExpired = 19,
+    /// The password didn't unlock the requested access
+    WrongPassword = 20,
+    /// The repository database is locked by another connection or process. Retrying later might
+    /// succeed.
+    StoreLocked = 21,
+    /// Failed to reach the remote host
+    NetworkUnavailable = 22,
+    /// The operation has been cancelled
+    Cancelled = 23,
+    /// The archive was created with an incompatible version of the archive format
+    ArchiveVersionMismatch = 24,
 
     VfsInvalidMountPoint = 2048,
     VfsDriverInstall = 2048 + 1,
@@ -76,8 +94,10 @@ pub(crate) trait ToErrorCode {
 impl ToErrorCode for SessionError {
     fn to_error_code(&self) -> ErrorCode {
         match self {
-            Self::InitializeLogger(_) | Self::InitializeRuntime(_) => ErrorCode::Other,
-            Self::InvalidUtf8(_) => ErrorCode::InvalidArgument,
+            Self::InitializeLogger(_) | Self::InitializeRuntime(_) | Self::Trace(_) => {
+                ErrorCode::Other
+            }
+            Self::InvalidUtf8(_) | Self::InvalidWorkerThreads(_) => ErrorCode::InvalidArgument,
             Self::NoActiveSession => ErrorCode::InvalidHandle,
         }
     }
@@ -87,7 +107,7 @@ impl ToErrorCode for RemoteError {
     fn to_error_code(&self) -> ErrorCode {
         match self {
             Self::PermissionDenied => ErrorCode::PermissionDenied,
-            Self::Connect(error) => error.to_error_code(),
+            Self::Connect(_) => ErrorCode::NetworkUnavailable,
             Self::Server(error) => error.to_error_code(),
         }
     }
@@ -108,9 +128,19 @@ impl ToErrorCode for ouisync_lib::Error {
                 ErrorCode::InvalidArgument
             }
             Self::StorageVersionMismatch => ErrorCode::StorageVersionMismatch,
-            Self::EntryIsFile | Self::EntryIsDirectory | Self::Writer(_) | Self::Locked => {
-                ErrorCode::Other
-            }
+            Self::ArchiveVersionMismatch => ErrorCode::ArchiveVersionMismatch,
+            Self::NotARepository => ErrorCode::NotARepository,
+            Self::QuotaExceeded => ErrorCode::QuotaExceeded,
+            Self::TokenExpired => ErrorCode::TokenExpired,
+            Self::WrongPassword => ErrorCode::WrongPassword,
+            Self::StoreLocked(_) => ErrorCode::StoreLocked,
+            Self::EntryIsFile
+            | Self::EntryIsDirectory
+            | Self::EntryIsLink
+            | Self::TooManyLinks
+            | Self::Writer(_)
+            | Self::Reader(_)
+            | Self::Locked => ErrorCode::Other,
         }
     }
 }
@@ -186,6 +216,12 @@ impl ToErrorCode for EntryChanged {
     }
 }
 
+impl ToErrorCode for CopyCancelled {
+    fn to_error_code(&self) -> ErrorCode {
+        ErrorCode::Cancelled
+    }
+}
+
 impl ToErrorCode for io::Error {
     fn to_error_code(&self) -> ErrorCode {
         ErrorCode::Other
@@ -230,4 +266,21 @@ mod tests {
 
         assert_eq!(error.to_string(), "repository error: permission denied");
     }
+
+    #[test]
+    fn error_code() {
+        let error = Error::from(ouisync_lib::Error::WrongPassword);
+        assert_eq!(error.code, ErrorCode::WrongPassword);
+        assert_eq!(u16::from(error.code), 20);
+
+        let error = Error::from(ouisync_lib::Error::from(ouisync_lib::db::Error::Busy));
+        assert_eq!(error.code, ErrorCode::StoreLocked);
+        assert_eq!(u16::from(error.code), 21);
+
+        let error = Error::from(RemoteError::Connect(
+            io::ErrorKind::ConnectionRefused.into(),
+        ));
+        assert_eq!(error.code, ErrorCode::NetworkUnavailable);
+        assert_eq!(u16::from(error.code), 22);
+    }
 }
@@ -2,7 +2,9 @@ use crate::{error::Error, registry::Handle, repository::RepositoryHandle, state:
 use camino::Utf8PathBuf;
 use deadlock::AsyncMutex;
 use ouisync_lib::{Branch, File};
-use std::{io::SeekFrom, sync::Arc};
+use std::{io::SeekFrom, sync::Arc, time::Duration};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 
 pub struct FileHolder {
     pub(crate) file: AsyncMutex<File>,
@@ -11,6 +13,14 @@ pub struct FileHolder {
 
 pub(crate) type FileHandle = Handle<Arc<FileHolder>>;
 
+/// Handle to a running copy of a file into a raw file descriptor. Cancelling the token stops the
+/// copy.
+pub(crate) type CopyHandle = Handle<CancellationToken>;
+
+#[derive(Debug, Error)]
+#[error("copy cancelled")]
+pub(crate) struct CopyCancelled;
+
 pub(crate) async fn open(
     state: &State,
     repo: RepositoryHandle,
@@ -108,6 +118,22 @@ pub(crate) async fn flush(state: &State, handle: FileHandle) -> Result<(), Error
     Ok(())
 }
 
+/// Enable or disable write-behind flushing of the file. See `File::set_auto_flush` for details.
+pub(crate) async fn set_auto_flush(
+    state: &State,
+    handle: FileHandle,
+    interval: Option<Duration>,
+) -> Result<(), Error> {
+    state
+        .files
+        .get(handle)?
+        .file
+        .lock()
+        .await
+        .set_auto_flush(interval);
+    Ok(())
+}
+
 /// Read at most `len` bytes from the file and returns them. The returned buffer can be shorter
 /// than `len` and empty in case of EOF.
 pub(crate) async fn read(
@@ -179,6 +205,27 @@ pub(crate) async fn len(state: &State, handle: FileHandle) -> Result<u64, Error>
     Ok(state.files.get(handle)?.file.lock().await.len())
 }
 
+/// Returns whether the given byte range of the file is available locally without downloading
+/// anything.
+pub(crate) async fn is_range_available(
+    state: &State,
+    handle: FileHandle,
+    offset: u64,
+    len: u64,
+) -> Result<bool, Error> {
+    // Don't keep the file locked while the check is being awaited.
+    let available = state
+        .files
+        .get(handle)?
+        .file
+        .lock()
+        .await
+        .is_range_available(offset, len);
+    let available = available.await?;
+
+    Ok(available)
+}
+
 /// Retrieve the sync progress of the file.
 pub(crate) async fn progress(state: &State, handle: FileHandle) -> Result<u64, Error> {
     // Don't keep the file locked while progress is being awaited.
@@ -187,3 +234,99 @@ pub(crate) async fn progress(state: &State, handle: FileHandle) -> Result<u64, E
 
     Ok(progress)
 }
+
+/// Copies the whole content of the file into `dst`, keeping the file locked for the duration of
+/// the copy. Cancelling `token` stops the copy with `CopyCancelled`, which drops (and thus closes)
+/// `dst` and releases the file lock right away.
+#[cfg(unix)]
+pub(crate) async fn copy_to_writer<W>(
+    holder: &FileHolder,
+    mut dst: W,
+    token: CancellationToken,
+) -> Result<(), Error>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    // The source file is only being read from so cancelling leaves it intact.
+    tokio::select! {
+        result = async move {
+            let mut file = holder.file.lock().await;
+            file.seek(SeekFrom::Start(0));
+            file.copy_to_writer(&mut dst).await
+        } => result.map_err(Into::into),
+        _ = token.cancelled() => Err(CopyCancelled.into()),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use assert_matches::assert_matches;
+    use ouisync_bridge::{config::ConfigStore, repository};
+    use state_monitor::StateMonitor;
+    use tempfile::TempDir;
+    use tokio::{io::AsyncReadExt, net::UnixStream, time};
+
+    #[tokio::test]
+    async fn cancel_copy() {
+        let dir = TempDir::new().unwrap();
+        let config = ConfigStore::new(dir.path().join("config"));
+        let repo = repository::create(
+            dir.path().join("repo.ouisyncdb"),
+            None,
+            None,
+            None,
+            &config,
+            &StateMonitor::make_root(),
+        )
+        .await
+        .unwrap();
+
+        // Much larger than the socket buffer so the copy stalls until the other end reads.
+        let len = 4 * 1024 * 1024;
+        let mut file = repo.create_file("test.dat").await.unwrap();
+        file.write_all(&vec![1; len]).await.unwrap();
+        file.flush().await.unwrap();
+
+        let holder = Arc::new(FileHolder {
+            file: AsyncMutex::new(file),
+            local_branch: None,
+        });
+
+        let (mut reader, writer) = UnixStream::pair().unwrap();
+        let token = CancellationToken::new();
+
+        let copy = tokio::spawn({
+            let holder = holder.clone();
+            let token = token.clone();
+            async move { copy_to_writer(&holder, writer, token).await }
+        });
+
+        // Once the first byte arrives the copy is running and holds the file lock.
+        let mut content = vec![0; 1];
+        reader.read_exact(&mut content).await.unwrap();
+
+        token.cancel();
+
+        assert_matches!(
+            copy.await.unwrap(),
+            Err(Error {
+                code: ErrorCode::Cancelled,
+                ..
+            })
+        );
+
+        // The lock is released...
+        time::timeout(Duration::from_secs(5), holder.file.lock())
+            .await
+            .unwrap();
+
+        // ...and the descriptor closed, so reading ends before the whole file has been received.
+        time::timeout(Duration::from_secs(5), reader.read_to_end(&mut content))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(content.len() < len);
+    }
+}
@@ -1,16 +1,16 @@
 use crate::{
-    directory,
+    batch, directory,
     error::Error,
     file, network,
     protocol::{Request, Response},
     repository, session, share_token,
     state::State,
-    state_monitor,
+    state_monitor, transfer,
 };
 use async_trait::async_trait;
 use ouisync_bridge::transport::SessionContext;
-use ouisync_lib::{crypto::cipher::SecretKey, PeerAddr};
-use std::{net::SocketAddr, sync::Arc};
+use ouisync_lib::{crypto::cipher::SecretKey, PeerAddr, PeerHost, StorageSize};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 #[derive(Clone)]
 pub(crate) struct Handler {
@@ -42,12 +42,14 @@ impl ouisync_bridge::transport::Handler for Handler {
                 read_secret,
                 write_secret,
                 share_token,
+                discovery_enabled,
             } => repository::create(
                 &self.state,
                 path.into_std_path_buf(),
                 read_secret,
                 write_secret,
                 share_token,
+                discovery_enabled,
             )
             .await?
             .into(),
@@ -56,12 +58,103 @@ impl ouisync_bridge::transport::Handler for Handler {
                     .await?
                     .into()
             }
+            Request::RepositoryOpenWithSecrets { path, secrets } => {
+                repository::open_with_secrets(&self.state, path.into_std_path_buf(), secrets)
+                    .await?
+                    .into()
+            }
             Request::RepositoryClose(handle) => {
                 repository::close(&self.state, handle).await?.into()
             }
+            Request::RepositoryRename { repository, dst } => {
+                repository::rename(&self.state, repository, dst)
+                    .await?
+                    .into()
+            }
             Request::RepositorySubscribe(handle) => {
                 repository::subscribe(&self.state, &context.notification_tx, handle)?.into()
             }
+            Request::RepositoryFindDuplicatesSubscribe(handle) => {
+                repository::find_duplicates(&self.state, &context.notification_tx, handle)?.into()
+            }
+            Request::RepositoryVerifyIntegritySubscribe(handle) => {
+                repository::verify_integrity(&self.state, &context.notification_tx, handle)?.into()
+            }
+            Request::RepositoryRebuildIndexSubscribe(handle) => {
+                repository::rebuild_index(&self.state, &context.notification_tx, handle)?.into()
+            }
+            Request::RepositoryRotateKeysSubscribe {
+                repository,
+                dst,
+                read_secret,
+                write_secret,
+            } => repository::rotate_keys(
+                &self.state,
+                &context.notification_tx,
+                repository,
+                dst.into_std_path_buf(),
+                read_secret,
+                write_secret,
+            )
+            .await?
+            .into(),
+            Request::RepositorySyncProgressSubscribe(handle) => {
+                repository::subscribe_to_sync_progress(
+                    &self.state,
+                    &context.notification_tx,
+                    handle,
+                )?
+                .into()
+            }
+            Request::RepositoryConflictsSubscribe(handle) => {
+                repository::subscribe_to_conflicts(&self.state, &context.notification_tx, handle)?
+                    .into()
+            }
+            Request::RepositoryImportPathSubscribe {
+                repository,
+                src,
+                dst,
+            } => transfer::import(&self.state, &context.notification_tx, repository, src, dst)?
+                .into(),
+            Request::RepositoryExportPathSubscribe {
+                repository,
+                src,
+                dst,
+                policy,
+            } => transfer::export(
+                &self.state,
+                &context.notification_tx,
+                repository,
+                src,
+                dst,
+                policy,
+            )?
+            .into(),
+            Request::RepositoryWatchDirectorySubscribe { repository, path } => {
+                repository::watch_directory(
+                    &self.state,
+                    &context.notification_tx,
+                    repository,
+                    path,
+                )?
+                .into()
+            }
+            Request::RepositoryPathSubscribe { repository, path } => {
+                repository::subscribe_path(&self.state, &context.notification_tx, repository, path)?
+                    .into()
+            }
+            Request::RepositoryDetectConflict { repository, path } => {
+                repository::detect_conflict(&self.state, repository, path)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryResolveConflict {
+                repository,
+                path,
+                content,
+            } => repository::resolve_conflict(&self.state, repository, path, content.into())
+                .await?
+                .into(),
             Request::ListRepositories => {
                 // TODO: We could collect only once
                 let handles = self
@@ -117,6 +210,36 @@ impl ouisync_bridge::transport::Handler for Handler {
                 repository::set_access_mode(&self.state, repository, access_mode, secret).await?;
                 ().into()
             }
+            Request::RepositoryLock(repository) => {
+                repository::lock(&self.state, repository).await?;
+                ().into()
+            }
+            Request::RepositoryUnlock { repository, secret } => {
+                repository::unlock(&self.state, repository, secret).await?;
+                ().into()
+            }
+            Request::RepositoryLockReason(repository) => {
+                repository::lock_reason(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryVerifyPassword { repository, secret } => {
+                repository::verify_password(&self.state, repository, secret)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryExportAccessSecrets { repository, secret } => {
+                repository::export_access_secrets(&self.state, repository, secret)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryImportAccessSecrets {
+                repository,
+                recovery,
+            } => {
+                repository::import_access_secrets(&self.state, repository, &recovery).await?;
+                ().into()
+            }
             Request::RepositoryRequiresLocalSecretForReading(handle) => self
                 .state
                 .repositories
@@ -136,6 +259,10 @@ impl ouisync_bridge::transport::Handler for Handler {
             Request::RepositoryInfoHash(handle) => {
                 repository::info_hash(&self.state, handle)?.into()
             }
+            Request::RepositorySecurityFingerprint {
+                repository,
+                access_mode,
+            } => repository::security_fingerprint(&self.state, repository, access_mode)?.into(),
             Request::RepositoryDatabaseId(handle) => {
                 repository::database_id(&self.state, handle).await?.into()
             }
@@ -153,11 +280,63 @@ impl ouisync_bridge::transport::Handler for Handler {
                     .await?
                     .into()
             }
+            Request::RepositoryWouldConflict { repository, path } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .would_conflict(path)
+                .await?
+                .into(),
+            Request::RepositoryPin { repository, path } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .pin(path)
+                .await?
+                .into(),
+            Request::RepositoryUnpin { repository, path } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .unpin(path)
+                .await?
+                .into(),
+            Request::RepositoryIsPinned { repository, path } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .is_pinned(path)
+                .await?
+                .into(),
             Request::RepositoryMoveEntry {
                 repository,
                 src,
                 dst,
-            } => repository::move_entry(&self.state, repository, src, dst)
+            } => repository::move_entry(&self.state, repository, src, dst, true)
+                .await?
+                .into(),
+            Request::RepositoryMoveEntryPreservingHistory {
+                repository,
+                src,
+                dst,
+                preserve_history,
+            } => repository::move_entry(&self.state, repository, src, dst, preserve_history)
+                .await?
+                .into(),
+            Request::RepositoryCopyEntry {
+                repository,
+                src,
+                dst,
+            } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .copy_entry(src, dst)
                 .await?
                 .into(),
             Request::RepositoryIsDhtEnabled(repository) => {
@@ -172,6 +351,107 @@ impl ouisync_bridge::transport::Handler for Handler {
                 repository::set_dht_enabled(&self.state, repository, enabled).await?;
                 ().into()
             }
+            Request::RepositorySetPoolWarmup { repository, count } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .set_pool_warmup(count)
+                .await?
+                .into(),
+            Request::RepositorySetSnapshotRetention { repository, count } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .set_snapshot_retention(count)
+                .await?
+                .into(),
+            Request::RepositorySnapshotRetention(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .snapshot_retention()
+                .await?
+                .into(),
+            Request::RepositorySetQuota { repository, quota } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .set_quota(quota.map(StorageSize::from_bytes))
+                .await?
+                .into(),
+            Request::RepositoryQuota(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .quota()
+                .await?
+                .map(|quota| quota.to_bytes())
+                .into(),
+            Request::RepositoryQuotaUsage(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .quota_usage()
+                .await?
+                .to_bytes()
+                .into(),
+            Request::RepositorySetDirectoryMergeStrategy {
+                repository,
+                strategy,
+            } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .set_directory_merge_strategy(strategy)
+                .await?
+                .into(),
+            Request::RepositoryDirectoryMergeStrategy(repository) => {
+                repository::directory_merge_strategy(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryAnnounceNow(repository) => {
+                repository::announce_now(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryIsUploadEnabled(repository) => {
+                repository::is_upload_enabled(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositorySetUploadEnabled {
+                repository,
+                enabled,
+            } => {
+                repository::set_upload_enabled(&self.state, repository, enabled).await?;
+                ().into()
+            }
+            Request::RepositoryIsBlockStorageEnabled(repository) => {
+                repository::is_block_storage_enabled(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositorySetBlockStorageEnabled {
+                repository,
+                enabled,
+            } => {
+                repository::set_block_storage_enabled(&self.state, repository, enabled).await?;
+                ().into()
+            }
+            Request::RepositoryPeerAccess {
+                repository,
+                runtime_id,
+            } => repository::peer_access(&self.state, repository, runtime_id)
+                .await?
+                .into(),
             Request::RepositoryIsPexEnabled(repository) => {
                 repository::is_pex_enabled(&self.state, repository)
                     .await?
@@ -189,9 +469,17 @@ impl ouisync_bridge::transport::Handler for Handler {
                 secret,
                 access_mode,
                 name,
-            } => repository::create_share_token(&self.state, repository, secret, access_mode, name)
-                .await?
-                .into(),
+                not_after,
+            } => repository::create_share_token(
+                &self.state,
+                repository,
+                secret,
+                access_mode,
+                name,
+                not_after,
+            )
+            .await?
+            .into(),
             Request::RepositoryCreateMirror { repository, host } => {
                 repository::create_mirror(&self.state, repository, &host)
                     .await?
@@ -217,6 +505,9 @@ impl ouisync_bridge::transport::Handler for Handler {
             Request::ShareTokenInfoHash(token) => share_token::info_hash(token).into(),
             Request::ShareTokenSuggestedName(token) => share_token::suggested_name(token).into(),
             Request::ShareTokenNormalize(token) => token.to_string().into(),
+            Request::ShareTokenToCompact(token) => token.to_compact().into(),
+            Request::ShareTokenFromCompact(input) => share_token::from_compact(&input)?.into(),
+            Request::ShareTokenFromRecovery(input) => share_token::from_recovery(&input)?.into(),
             Request::ShareTokenMirrorExists { share_token, host } => {
                 share_token::mirror_exists(&self.state, share_token, &host)
                     .await?
@@ -230,6 +521,33 @@ impl ouisync_bridge::transport::Handler for Handler {
                     .await?
                     .into()
             }
+            Request::RepositoryIndexProgress(repository) => {
+                repository::index_progress(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryIsTransferring(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .is_transferring()
+                .into(),
+            Request::RepositoryIsComplete(repository) => {
+                repository::is_complete(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryOnComplete(repository) => {
+                repository::on_complete(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryAvailability { repository, paths } => {
+                repository::availability(&self.state, repository, paths)
+                    .await?
+                    .into()
+            }
             Request::RepositoryMountAll(mount_point) => {
                 repository::mount_root(&self.state, mount_point)
                     .await?
@@ -248,6 +566,91 @@ impl ouisync_bridge::transport::Handler for Handler {
             Request::RepositoryStats(repository) => {
                 repository::stats(&self.state, repository).await?.into()
             }
+            Request::RepositorySizeBreakdown(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .size_breakdown()
+                .await?
+                .into(),
+            Request::RepositoryStorageStats(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .storage_stats()
+                .await?
+                .into(),
+            Request::RepositoryBranches(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .branches()
+                .await?
+                .into(),
+            Request::RepositoryBranchSyncTimes(repository) => {
+                repository::branch_sync_times(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryCurrentVersion(repository) => {
+                repository::current_version(&self.state, repository)
+                    .await?
+                    .into()
+            }
+            Request::RepositorySetEventLogCapacity {
+                repository,
+                capacity,
+            } => {
+                self.state
+                    .repositories
+                    .get(repository)?
+                    .repository
+                    .set_event_log_capacity(capacity as usize);
+                ().into()
+            }
+            Request::RepositoryEventLog(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .event_log()
+                .into(),
+            Request::RepositoryDownloading(repository) => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .downloading()
+                .into_iter()
+                .map(|block_id| block_id.to_string())
+                .collect::<Vec<_>>()
+                .into(),
+            Request::RepositoryCancelDownload { repository, path } => self
+                .state
+                .repositories
+                .get(repository)?
+                .repository
+                .cancel_download(path)
+                .await?
+                .into(),
+            Request::RepositoryExportArchive { repository, path } => {
+                repository::export_archive(&self.state, repository, path)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryImportArchive { repository, path } => {
+                repository::import_archive(&self.state, repository, path)
+                    .await?
+                    .into()
+            }
+            Request::RepositoryMessageStats(repository) => {
+                repository::message_stats(&self.state, repository)
+                    .await?
+                    .into()
+            }
             Request::DirectoryCreate { repository, path } => {
                 directory::create(&self.state, repository, path)
                     .await?
@@ -293,16 +696,58 @@ impl ouisync_bridge::transport::Handler for Handler {
             }
             Request::FileLen(file) => file::len(&self.state, file).await?.into(),
             Request::FileProgress(file) => file::progress(&self.state, file).await?.into(),
+            Request::FileIsRangeAvailable { file, offset, len } => {
+                file::is_range_available(&self.state, file, offset, len)
+                    .await?
+                    .into()
+            }
             Request::FileFlush(file) => file::flush(&self.state, file).await?.into(),
+            Request::FileSetAutoFlush { file, interval } => {
+                file::set_auto_flush(&self.state, file, interval.map(Duration::from_millis))
+                    .await?
+                    .into()
+            }
             Request::FileClose(file) => file::close(&self.state, file).await?.into(),
+            Request::RepositoryBatch(repository) => batch::create(&self.state, repository)?.into(),
+            Request::BatchOpenFile { batch, path } => {
+                batch::open_file(&self.state, batch, path).await?.into()
+            }
+            Request::BatchCreateFile { batch, path } => {
+                batch::create_file(&self.state, batch, path).await?.into()
+            }
+            Request::BatchWrite {
+                batch,
+                file,
+                offset,
+                data,
+            } => batch::write(&self.state, batch, file, offset, data.into())
+                .await?
+                .into(),
+            Request::BatchTruncate { batch, file, len } => {
+                batch::truncate(&self.state, batch, file, len).await?.into()
+            }
+            Request::BatchCommit(batch) => batch::commit(&self.state, batch).await?.into(),
+            Request::BatchClose(batch) => {
+                batch::close(&self.state, batch);
+                ().into()
+            }
             Request::NetworkInit(defaults) => {
                 ouisync_bridge::network::init(&self.state.network, &self.state.config, defaults)
                     .await;
+
+                // The config could have re-enabled some of the features disabled in test mode.
+                if self.state.test_mode {
+                    self.state.isolate_network();
+                }
+
                 ().into()
             }
             Request::NetworkSubscribe => {
                 network::subscribe(&self.state, &context.notification_tx).into()
             }
+            Request::NetworkLastProtocolMismatch => {
+                network::last_protocol_mismatch(&self.state).into()
+            }
             Request::NetworkBind {
                 quic_v4,
                 quic_v6,
@@ -325,6 +770,16 @@ impl ouisync_bridge::transport::Handler for Handler {
                 .await;
                 ().into()
             }
+            Request::NetworkSetBindInterface(interface) => {
+                ouisync_bridge::network::set_bind_interface(
+                    &self.state.network,
+                    &self.state.config,
+                    interface,
+                )
+                .await;
+                ().into()
+            }
+            Request::NetworkBindInterface => self.state.network.bind_interface().into(),
             Request::NetworkTcpListenerLocalAddrV4 => self
                 .state
                 .network
@@ -380,8 +835,31 @@ impl ouisync_bridge::transport::Handler for Handler {
                     .await
                     .into()
             }
+            Request::NetworkAddUserProvidedTcpPeer(host) => {
+                network::add_user_provided_host(&self.state, PeerHost::Tcp(host))?;
+                ().into()
+            }
+            Request::NetworkAddUserProvidedQuicPeer(host) => {
+                network::add_user_provided_host(&self.state, PeerHost::Quic(host))?;
+                ().into()
+            }
+            Request::NetworkRemoveUserProvidedTcpPeer(host) => {
+                network::remove_user_provided_host(&self.state, PeerHost::Tcp(host));
+                ().into()
+            }
+            Request::NetworkRemoveUserProvidedQuicPeer(host) => {
+                network::remove_user_provided_host(&self.state, PeerHost::Quic(host));
+                ().into()
+            }
             Request::NetworkKnownPeers => self.state.network.peer_info_collector().collect().into(),
+            Request::NetworkSelfAddresses => self.state.network.self_addresses().into(),
             Request::NetworkThisRuntimeId => network::this_runtime_id(&self.state).into(),
+            Request::NetworkBlockRuntimeId(runtime_id) => {
+                network::block_runtime_id(&self.state, &runtime_id)?.into()
+            }
+            Request::NetworkUnblockRuntimeId(runtime_id) => {
+                network::unblock_runtime_id(&self.state, &runtime_id)?.into()
+            }
             Request::NetworkCurrentProtocolVersion => {
                 self.state.network.current_protocol_version().into()
             }
@@ -416,10 +894,74 @@ impl ouisync_bridge::transport::Handler for Handler {
             Request::NetworkExternalAddrV6 => self.state.network.external_addr_v6().await.into(),
             Request::NetworkNatBehavior => self.state.network.nat_behavior().await.into(),
             Request::NetworkStats => self.state.network.stats().into(),
+            Request::NetworkMessageStats => self.state.network.message_stats().into(),
+            Request::NetworkSetBandwidthLimit { upload, download } => {
+                network::set_bandwidth_limit(&self.state, upload, download);
+                ().into()
+            }
+            Request::NetworkBandwidthLimit => network::bandwidth_limit(&self.state).into(),
+            Request::NetworkSetDhtBootstrapNodes(nodes) => {
+                ouisync_bridge::network::set_dht_bootstrap_nodes(
+                    &self.state.network,
+                    &self.state.config,
+                    nodes,
+                )
+                .await;
+                ().into()
+            }
+            Request::NetworkSetStunServers(servers) => {
+                ouisync_bridge::network::set_stun_servers(
+                    &self.state.network,
+                    &self.state.config,
+                    Some(servers),
+                )
+                .await;
+                ().into()
+            }
+            Request::NetworkResetStunServers => {
+                ouisync_bridge::network::set_stun_servers(
+                    &self.state.network,
+                    &self.state.config,
+                    None,
+                )
+                .await;
+                ().into()
+            }
+            Request::NetworkSetMaxConnections(max) => {
+                network::set_max_connections(&self.state, max);
+                ().into()
+            }
+            Request::NetworkMaxConnections => network::max_connections(&self.state).into(),
+            Request::NetworkSetTransportPreference(preference) => {
+                self.state.network.set_transport_preference(preference);
+                ().into()
+            }
+            Request::NetworkTransportPreference => {
+                u8::from(self.state.network.transport_preference()).into()
+            }
+            Request::NetworkSetDeviceName(name) => {
+                self.state.network.set_device_name(name);
+                ().into()
+            }
+            Request::NetworkDeviceName => self.state.network.device_name().into(),
+            Request::NetworkSetKeepAliveConfig {
+                send_interval,
+                recv_timeout,
+            } => {
+                network::set_keep_alive_config(
+                    &self.state,
+                    Duration::from_millis(send_interval),
+                    Duration::from_millis(recv_timeout),
+                )?;
+                ().into()
+            }
             Request::NetworkShutdown => {
                 self.state.network.shutdown().await;
                 ().into()
             }
+            Request::SessionWorkerThreads => u32::try_from(self.state.worker_threads)
+                .unwrap_or(u32::MAX)
+                .into(),
             Request::StateMonitorGet(path) => state_monitor::get(&self.state, path)?.into(),
             Request::StateMonitorSubscribe(path) => {
                 state_monitor::subscribe(&self.state, &context.notification_tx, path)?.into()
@@ -2,6 +2,7 @@
 
 #[macro_use]
 mod utils;
+mod batch;
 mod c;
 mod dart;
 mod directory;
@@ -19,14 +20,17 @@ mod session;
 mod share_token;
 mod state;
 mod state_monitor;
+mod trace;
+mod transfer;
 mod transport;
 
 use crate::{
     c::{Callback, CallbackSender},
     dart::{Port, PortSender, PostDartCObjectFn},
-    error::Error,
-    file::FileHandle,
+    error::{Error, ErrorCode, ToErrorCode},
+    file::{CopyHandle, FileHandle},
     log::LogLevel,
+    repository::RepositoryHandle,
     sender::Sender,
     session::{SessionCreateResult, SessionHandle},
 };
@@ -39,10 +43,22 @@ use std::{
 
 /// Creates a ouisync session (common C-like API)
 ///
+/// `worker_threads` is the number of worker threads of the session runtime. Zero means one per CPU
+/// core. Fewer threads save battery on low-core devices, more can speed up syncing many
+/// repositories in parallel. It only takes effect if this call actually creates a new runtime,
+/// that is, not when it returns an already existing shared session, as a running runtime can't
+/// be resized. Fails with `ErrorCode::InvalidArgument` if the number is too large.
+///
+/// `dht_contacts_dir` is the directory where the session stores the DHT contacts (the addresses of
+/// the DHT nodes seen recently), which are used to speed up the bootstrap after a restart. Null
+/// means the config directory. The directory is created if it doesn't exist. Like
+/// `worker_threads`, it only takes effect if this call actually creates a new session.
+///
 /// # Safety
 ///
 /// - `configs_path`, `log_path` and `log_tag` must be pointers to nul-terminated utf-8 encoded
 ///   strings.
+/// - `dht_contacts_dir` must be either null or a pointer to a nul-terminated utf-8 encoded string.
 /// - `context` must be a valid pointer to a value that outlives the `Session` and that is safe
 ///   to be sent to other threads or null.
 /// - `callback` must be a valid function pointer which does not leak the passed `msg_ptr`.
@@ -52,19 +68,33 @@ pub unsafe extern "C" fn session_create(
     configs_path: *const c_char,
     log_path: *const c_char,
     log_tag: *const c_char,
+    worker_threads: u16,
+    dht_contacts_dir: *const c_char,
     context: *mut (),
     callback: Callback,
 ) -> SessionCreateResult {
     let sender = CallbackSender::new(context, callback);
-    session::create(kind, configs_path, log_path, log_tag, sender).into()
+    session::create(
+        kind,
+        configs_path,
+        log_path,
+        log_tag,
+        worker_threads,
+        dht_contacts_dir,
+        sender,
+    )
+    .into()
 }
 
 /// Creates a ouisync session (dart-specific API)
 ///
+/// See `session_create` for the meaning of `worker_threads` and `dht_contacts_dir`.
+///
 /// # Safety
 ///
 /// - `configs_path`, `log_path` and `log_tag` must be pointers to nul-terminated utf-8 encoded
 ///   strings.
+/// - `dht_contacts_dir` must be either null or a pointer to a nul-terminated utf-8 encoded string.
 /// - `post_c_object_fn` must be a pointer to the dart's `NativeApi.postCObject` function
 #[no_mangle]
 pub unsafe extern "C" fn session_create_dart(
@@ -72,11 +102,52 @@ pub unsafe extern "C" fn session_create_dart(
     configs_path: *const c_char,
     log_path: *const c_char,
     log_tag: *const c_char,
+    worker_threads: u16,
+    dht_contacts_dir: *const c_char,
     post_c_object_fn: PostDartCObjectFn,
     port: Port,
 ) -> SessionCreateResult {
     let sender = PortSender::new(post_c_object_fn, port);
-    session::create(kind, configs_path, log_path, log_tag, sender).into()
+    session::create(
+        kind,
+        configs_path,
+        log_path,
+        log_tag,
+        worker_threads,
+        dht_contacts_dir,
+        sender,
+    )
+    .into()
+}
+
+/// Creates a ouisync session for integration tests (common C-like API).
+///
+/// The session is always unique (as with `SessionKind::Unique`), its runtime id is derived from
+/// `seed` (so it's the same in every run) and local discovery and port forwarding are disabled.
+/// To connect two such sessions, bind them to loopback addresses and add each other as user
+/// provided peers. Note the repositories are still stored in files under `configs_path` (which
+/// should be a temporary directory) as there is no in-memory store.
+///
+/// Available only with the `test-hooks` feature.
+///
+/// # Safety
+///
+/// - `configs_path` and `log_path` must be pointers to nul-terminated utf-8 encoded strings.
+///   `log_path` can be null.
+/// - `context` must be a valid pointer to a value that outlives the `Session` and that is safe
+///   to be sent to other threads or null.
+/// - `callback` must be a valid function pointer which does not leak the passed `msg_ptr`.
+#[cfg(feature = "test-hooks")]
+#[no_mangle]
+pub unsafe extern "C" fn session_create_for_test(
+    configs_path: *const c_char,
+    log_path: *const c_char,
+    seed: u64,
+    context: *mut (),
+    callback: Callback,
+) -> SessionCreateResult {
+    let sender = CallbackSender::new(context, callback);
+    session::create_for_test(configs_path, log_path, seed, sender).into()
 }
 
 /// Get an existing session if one was created and not yet destroyed, otherwise returns result with
@@ -162,7 +233,53 @@ pub unsafe extern "C" fn session_channel_send(
     let payload = slice::from_raw_parts(payload_ptr, payload_len as usize);
     let payload = payload.into();
 
-    session.get().client_tx.send(payload).ok();
+    session.get().send(payload);
+}
+
+/// Starts recording every message sent to and from the session (with timestamps) into the file at
+/// `path`, appending to it if it already exists. Pass null `path` to stop recording. Useful to
+/// capture the exact sequence of requests that leads to a bug so it can be reproduced later with
+/// `session_replay`.
+///
+/// The recording never blocks the session: messages are written in the background and those that
+/// can't be queued fast enough are dropped. The file is capped in size, once it reaches the limit
+/// no more messages are recorded. Messages carrying secrets (passwords, keys, share tokens, recovery
+/// strings, ...) and the responses to them are redacted, but other content such as file data is
+/// recorded in full.
+///
+/// # Safety
+///
+/// - `session` must be a valid session handle.
+/// - `path` must be either null or a pointer to a nul-terminated utf-8 encoded string.
+#[no_mangle]
+pub unsafe extern "C" fn session_set_trace_path(
+    session: SessionHandle,
+    path: *const c_char,
+) -> ErrorCode {
+    match session::set_trace_path(session.get(), path) {
+        Ok(()) => ErrorCode::Ok,
+        Err(error) => error.to_error_code(),
+    }
+}
+
+/// Sends all the requests recorded in the trace file at `path` (see `session_set_trace_path`) to
+/// the session, in the recorded order. The responses are delivered the same way as responses to
+/// regular requests. Note the requests refer to the handles that were valid in the traced session,
+/// so replaying generally works only against a fresh session with the same configuration.
+///
+/// Available only with the `test-hooks` feature.
+///
+/// # Safety
+///
+/// - `session` must be a valid session handle.
+/// - `path` must be a pointer to a nul-terminated utf-8 encoded string.
+#[cfg(feature = "test-hooks")]
+#[no_mangle]
+pub unsafe extern "C" fn session_replay(session: SessionHandle, path: *const c_char) -> ErrorCode {
+    match session::replay(session.get(), path) {
+        Ok(()) => ErrorCode::Ok,
+        Err(error) => error.to_error_code(),
+    }
 }
 
 /// Copy the file contents into the provided raw file descriptor (dart-specific API).
@@ -171,6 +288,10 @@ pub unsafe extern "C" fn session_channel_send(
 /// caller needs to access the descriptor afterwards (or while the function is running), he/she
 /// needs to `dup` it before passing it into this function.
 ///
+/// Returns a handle which can be passed to `file_cancel_copy` to cancel the copy. In that case
+/// the copy fails with the `Cancelled` error and the file descriptor is closed. Returns the null
+/// handle (0) if the copy failed to start.
+///
 /// # Safety
 ///
 /// - `session` must be a valid session handle
@@ -186,10 +307,11 @@ pub unsafe extern "C" fn file_copy_to_raw_fd_dart(
     fd: c_int,
     post_c_object_fn: PostDartCObjectFn,
     port: Port,
-) {
+) -> CopyHandle {
     use bytes::Bytes;
-    use std::{io::SeekFrom, os::fd::FromRawFd};
+    use std::os::fd::FromRawFd;
     use tokio::fs;
+    use tokio_util::sync::CancellationToken;
 
     let session = session.get();
     let sender = PortSender::new(post_c_object_fn, port);
@@ -198,22 +320,28 @@ pub unsafe extern "C" fn file_copy_to_raw_fd_dart(
         Ok(file) => file,
         Err(error) => {
             sender.send(encode_error(&error.into()));
-            return;
+            return CopyHandle::from_id(0);
         }
     };
 
-    let mut dst = fs::File::from_raw_fd(fd);
+    let dst = fs::File::from_raw_fd(fd);
+
+    let state = session.shared.state.clone();
+    let token = CancellationToken::new();
+    let copy_handle = state.copies.insert(token.clone());
 
     session.shared.runtime.spawn(async move {
-        let mut src = src.file.lock().await;
-        src.seek(SeekFrom::Start(0));
-        let result = src.copy_to_writer(&mut dst).await;
+        let result = file::copy_to_writer(&src, dst, token).await;
+
+        state.copies.remove(copy_handle);
 
         match result {
             Ok(()) => sender.send(Bytes::new()),
-            Err(error) => sender.send(encode_error(&error.into())),
+            Err(error) => sender.send(encode_error(&error)),
         }
     });
+
+    copy_handle
 }
 
 /// Always returns `OperationNotSupported` error. Defined to avoid lookup errors on non-unix
@@ -232,6 +360,163 @@ pub unsafe extern "C" fn file_copy_to_raw_fd_dart(
     _fd: c_int,
     post_c_object_fn: PostDartCObjectFn,
     port: Port,
+) -> CopyHandle {
+    let sender = PortSender::new(post_c_object_fn, port);
+    sender.send(encode_error(
+        &ouisync_lib::Error::OperationNotSupported.into(),
+    ));
+
+    CopyHandle::from_id(0)
+}
+
+/// Cancel a copy started with `file_copy_to_raw_fd_dart`. Does nothing if the copy has already
+/// finished.
+///
+/// # Safety
+///
+/// `session` must be a valid session handle.
+#[no_mangle]
+pub unsafe extern "C" fn file_cancel_copy(session: SessionHandle, copy_handle: CopyHandle) {
+    session.get().shared.state.cancel_copy(copy_handle);
+}
+
+/// Export the repository archive (see `Repository::export_archive`) into the provided raw file
+/// descriptor (dart-specific API).
+///
+/// This function takes ownership of the file descriptor and closes it when it finishes. If the
+/// caller needs to access the descriptor afterwards (or while the function is running), he/she
+/// needs to `dup` it before passing it into this function.
+///
+/// # Safety
+///
+/// - `session` must be a valid session handle
+/// - `handle` must be a valid repository holder handle
+/// - `fd` must be a valid and open file descriptor
+/// - `post_c_object_fn` must be a pointer to the dart's `NativeApi.postCObject` function
+/// - `port` must be a valid dart native port
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn repository_export_archive_to_raw_fd_dart(
+    session: SessionHandle,
+    handle: RepositoryHandle,
+    fd: c_int,
+    post_c_object_fn: PostDartCObjectFn,
+    port: Port,
+) {
+    use bytes::Bytes;
+    use std::os::fd::FromRawFd;
+    use tokio::{fs, io::BufWriter};
+
+    // Take ownership first so the descriptor is closed on every path.
+    let mut dst = BufWriter::new(fs::File::from_raw_fd(fd));
+
+    let session = session.get();
+    let sender = PortSender::new(post_c_object_fn, port);
+
+    let holder = match session.shared.state.repositories.get(handle) {
+        Ok(holder) => holder,
+        Err(error) => {
+            sender.send(encode_error(&error.into()));
+            return;
+        }
+    };
+
+    session.shared.runtime.spawn(async move {
+        match holder.repository.export_archive(&mut dst).await {
+            Ok(()) => sender.send(Bytes::new()),
+            Err(error) => sender.send(encode_error(&error.into())),
+        }
+    });
+}
+
+/// Always returns `OperationNotSupported` error. Defined to avoid lookup errors on non-unix
+/// platforms. Do not use.
+///
+/// # Safety
+///
+/// - `post_c_object_fn` must be a pointer to the dart's `NativeApi.postCObject` function
+/// - `port` must be a valid dart native port.
+/// - `session`, `handle` and `fd` are not actually used and so have no safety requirements.
+#[cfg(not(unix))]
+#[no_mangle]
+pub unsafe extern "C" fn repository_export_archive_to_raw_fd_dart(
+    _session: SessionHandle,
+    _handle: RepositoryHandle,
+    _fd: c_int,
+    post_c_object_fn: PostDartCObjectFn,
+    port: Port,
+) {
+    let sender = PortSender::new(post_c_object_fn, port);
+    sender.send(encode_error(
+        &ouisync_lib::Error::OperationNotSupported.into(),
+    ))
+}
+
+/// Import a repository archive (see `Repository::import_archive`) from the provided raw file
+/// descriptor (dart-specific API).
+///
+/// This function takes ownership of the file descriptor and closes it when it finishes. If the
+/// caller needs to access the descriptor afterwards (or while the function is running), he/she
+/// needs to `dup` it before passing it into this function.
+///
+/// # Safety
+///
+/// - `session` must be a valid session handle
+/// - `handle` must be a valid repository holder handle
+/// - `fd` must be a valid and open file descriptor
+/// - `post_c_object_fn` must be a pointer to the dart's `NativeApi.postCObject` function
+/// - `port` must be a valid dart native port
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn repository_import_archive_from_raw_fd_dart(
+    session: SessionHandle,
+    handle: RepositoryHandle,
+    fd: c_int,
+    post_c_object_fn: PostDartCObjectFn,
+    port: Port,
+) {
+    use bytes::Bytes;
+    use std::os::fd::FromRawFd;
+    use tokio::{fs, io::BufReader};
+
+    // Take ownership first so the descriptor is closed on every path.
+    let mut src = BufReader::new(fs::File::from_raw_fd(fd));
+
+    let session = session.get();
+    let sender = PortSender::new(post_c_object_fn, port);
+
+    let holder = match session.shared.state.repositories.get(handle) {
+        Ok(holder) => holder,
+        Err(error) => {
+            sender.send(encode_error(&error.into()));
+            return;
+        }
+    };
+
+    session.shared.runtime.spawn(async move {
+        match holder.repository.import_archive(&mut src).await {
+            Ok(()) => sender.send(Bytes::new()),
+            Err(error) => sender.send(encode_error(&error.into())),
+        }
+    });
+}
+
+/// Always returns `OperationNotSupported` error. Defined to avoid lookup errors on non-unix
+/// platforms. Do not use.
+///
+/// # Safety
+///
+/// - `post_c_object_fn` must be a pointer to the dart's `NativeApi.postCObject` function
+/// - `port` must be a valid dart native port.
+/// - `session`, `handle` and `fd` are not actually used and so have no safety requirements.
+#[cfg(not(unix))]
+#[no_mangle]
+pub unsafe extern "C" fn repository_import_archive_from_raw_fd_dart(
+    _session: SessionHandle,
+    _handle: RepositoryHandle,
+    _fd: c_int,
+    post_c_object_fn: PostDartCObjectFn,
+    port: Port,
 ) {
     let sender = PortSender::new(post_c_object_fn, port);
     sender.send(encode_error(
@@ -62,6 +62,10 @@ impl Mounter {
         result
     }
 
+    pub fn is_mounted(&self, store_path: &Path) -> bool {
+        self.inner.lock().unwrap().repos.contains_key(store_path)
+    }
+
     pub fn unmount(&self, store_path: &Path) -> Result<(), Error> {
         let mut inner = self.inner.lock().unwrap();
 
@@ -1,30 +1,41 @@
 use crate::state::{State, TaskHandle};
 use ouisync_bridge::{
-    protocol::{NetworkEvent, Notification},
+    protocol::{NetworkEvent, NetworkNotification, Notification, ProtocolMismatchEvent},
     transport::NotificationSender,
 };
+use ouisync_lib::{
+    crypto::sign::PublicKey, BandwidthLimit, PeerHost, ProtocolMismatch, PublicRuntimeId,
+};
+use std::time::Duration;
 use tokio::select;
 
 /// Subscribe to network event notifications.
 pub(crate) fn subscribe(state: &State, notification_tx: &NotificationSender) -> TaskHandle {
     let mut on_protocol_mismatch = state.network.on_protocol_mismatch();
     let mut on_peer_set_change = state.network.on_peer_set_change();
+    let mut on_external_addr_change = state.network.on_external_addr_change();
     let notification_tx = notification_tx.clone();
 
     state.spawn_task(|id| async move {
         // TODO: This loop exits when the first of the watched channels closes. It might be less
         // error prone to keep the loop until all of the channels are closed.
         loop {
-            let event = select! {
+            let event: NetworkNotification = select! {
                 e = on_protocol_mismatch.changed() => {
                     match e {
-                        Ok(()) => NetworkEvent::ProtocolVersionMismatch,
+                        Ok(mismatch) => to_event(mismatch).into(),
                         Err(_) => return,
                     }
                 },
                 e = on_peer_set_change.changed() => {
                     match e {
-                        Ok(()) => NetworkEvent::PeerSetChange,
+                        Ok(()) => NetworkEvent::PeerSetChange.into(),
+                        Err(_) => return,
+                    }
+                },
+                e = on_external_addr_change.changed() => {
+                    match e {
+                        Ok(_) => NetworkEvent::ExternalAddrChange.into(),
                         Err(_) => return,
                     }
                 }
@@ -38,7 +49,108 @@ pub(crate) fn subscribe(state: &State, notification_tx: &NotificationSender) ->
     })
 }
 
+/// Returns the details of the most recent protocol version mismatch, if any.
+pub(crate) fn last_protocol_mismatch(state: &State) -> Option<ProtocolMismatchEvent> {
+    state.network.last_protocol_mismatch().map(to_event)
+}
+
+fn to_event(mismatch: ProtocolMismatch) -> ProtocolMismatchEvent {
+    ProtocolMismatchEvent {
+        our_version: mismatch.our_version,
+        their_version: mismatch.their_version,
+        peer_addr: mismatch.peer_addr.to_string(),
+        runtime_id: mismatch
+            .runtime_id
+            .map(|runtime_id| hex::encode(runtime_id.as_ref())),
+    }
+}
+
 /// Returns our runtime id formatted as a hex string.
 pub(crate) fn this_runtime_id(state: &State) -> String {
     hex::encode(state.network.this_runtime_id().as_ref())
 }
+
+/// Blocks the peer with the given runtime id (formatted as a hex string). The peer is disconnected
+/// and its further connections are rejected.
+pub(crate) fn block_runtime_id(state: &State, runtime_id: &str) -> Result<(), ouisync_lib::Error> {
+    state
+        .network
+        .block_runtime_id(parse_runtime_id(runtime_id)?);
+    Ok(())
+}
+
+/// Unblocks a peer previously blocked with `block_runtime_id`.
+pub(crate) fn unblock_runtime_id(
+    state: &State,
+    runtime_id: &str,
+) -> Result<(), ouisync_lib::Error> {
+    state
+        .network
+        .unblock_runtime_id(&parse_runtime_id(runtime_id)?);
+    Ok(())
+}
+
+/// Sets the max upload and download bandwidth (in bytes per second) of a single peer. `None` means
+/// unlimited.
+pub(crate) fn set_bandwidth_limit(state: &State, upload: Option<u64>, download: Option<u64>) {
+    state.network.set_bandwidth_limit(upload, download)
+}
+
+/// Returns the current bandwidth limit of a single peer.
+pub(crate) fn bandwidth_limit(state: &State) -> BandwidthLimit {
+    state.network.bandwidth_limit()
+}
+
+/// Adds a peer given by a host name and port. The host is resolved with DNS and periodically
+/// re-resolved. Fails with `InvalidArgument` if the host isn't in the `host:port` format.
+pub(crate) fn add_user_provided_host(
+    state: &State,
+    host: PeerHost,
+) -> Result<(), ouisync_lib::Error> {
+    // Round-trip through the string form to validate it.
+    let host: PeerHost = host
+        .to_string()
+        .parse()
+        .map_err(|_| ouisync_lib::Error::InvalidArgument)?;
+
+    state.network.add_user_provided_host(host);
+
+    Ok(())
+}
+
+/// Removes a peer previously added with `add_user_provided_host`.
+pub(crate) fn remove_user_provided_host(state: &State, host: PeerHost) {
+    state.network.remove_user_provided_host(&host)
+}
+
+/// Sets the max number of simultaneous peer connections. `None` means unlimited.
+pub(crate) fn set_max_connections(state: &State, max: Option<u32>) {
+    state
+        .network
+        .set_max_connections(max.map(|max| max as usize))
+}
+
+/// Returns the max number of simultaneous peer connections.
+pub(crate) fn max_connections(state: &State) -> Option<u32> {
+    state
+        .network
+        .max_connections()
+        .map(|max| max.try_into().unwrap_or(u32::MAX))
+}
+
+/// Sets how often to ping the peers and how long to wait for any message from a peer before
+/// considering the connection dead.
+pub(crate) fn set_keep_alive_config(
+    state: &State,
+    send_interval: Duration,
+    recv_timeout: Duration,
+) -> Result<(), ouisync_lib::Error> {
+    state
+        .network
+        .set_keep_alive_config(send_interval, recv_timeout)
+}
+
+fn parse_runtime_id(s: &str) -> Result<PublicRuntimeId, ouisync_lib::Error> {
+    let public_key: PublicKey = s.parse().map_err(|_| ouisync_lib::Error::InvalidArgument)?;
+    Ok(public_key.into())
+}
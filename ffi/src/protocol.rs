@@ -1,19 +1,26 @@
 use crate::{
+    batch::BatchHandle,
     directory::Directory,
     file::FileHandle,
     registry::Handle,
-    repository::{MetadataEdit, RepositoryHandle},
+    repository::{ConflictInfo, MetadataEdit, OpenedRepository, RepositoryHandle},
     state::TaskHandle,
 };
 use camino::Utf8PathBuf;
-use ouisync_bridge::network::NetworkDefaults;
+use ouisync_bridge::{
+    network::NetworkDefaults,
+    protocol::{CollisionPolicy, ProtocolMismatchEvent},
+};
 use ouisync_lib::{
-    crypto::PasswordSalt, AccessChange, AccessMode, LocalSecret, NatBehavior, PeerAddr, PeerInfo,
-    Progress, SetLocalSecret, ShareToken, Stats,
+    crypto::{sign::PublicKey, PasswordSalt},
+    AccessChange, AccessMode, BandwidthLimit, BranchStatus, LocalSecret, LoggedEvent,
+    MergeStrategy, MessageStats, NatBehavior, PeerAddr, PeerInfo, Progress, PublicRuntimeId,
+    SetLocalSecret, ShareToken, SizeBreakdown, Stats, StorageStats, TransportPreference,
 };
 use serde::{Deserialize, Serialize};
 use state_monitor::{MonitorId, StateMonitor};
 use std::{
+    collections::BTreeMap,
     fmt,
     net::{SocketAddr, SocketAddrV4, SocketAddrV6},
     path::PathBuf,
@@ -29,12 +36,24 @@ pub(crate) enum Request {
         read_secret: Option<SetLocalSecret>,
         write_secret: Option<SetLocalSecret>,
         share_token: Option<ShareToken>,
+        // Missing in requests from older clients, which never enabled discovery on create.
+        #[serde(default)]
+        discovery_enabled: bool,
     },
     RepositoryOpen {
         path: Utf8PathBuf,
         secret: Option<LocalSecret>,
     },
+    RepositoryOpenWithSecrets {
+        path: Utf8PathBuf,
+        secrets: Vec<LocalSecret>,
+    },
+    RepositoryLockReason(RepositoryHandle),
     RepositoryClose(RepositoryHandle),
+    RepositoryRename {
+        repository: RepositoryHandle,
+        dst: PathBuf,
+    },
     RepositorySubscribe(RepositoryHandle),
     ListRepositories,
     ListRepositoriesSubscribe,
@@ -61,8 +80,29 @@ pub(crate) enum Request {
         access_mode: AccessMode,
         secret: Option<LocalSecret>,
     },
+    RepositoryLock(RepositoryHandle),
+    RepositoryUnlock {
+        repository: RepositoryHandle,
+        secret: LocalSecret,
+    },
+    RepositoryVerifyPassword {
+        repository: RepositoryHandle,
+        secret: LocalSecret,
+    },
+    RepositoryExportAccessSecrets {
+        repository: RepositoryHandle,
+        secret: LocalSecret,
+    },
+    RepositoryImportAccessSecrets {
+        repository: RepositoryHandle,
+        recovery: String,
+    },
     RepositoryName(RepositoryHandle),
     RepositoryInfoHash(RepositoryHandle),
+    RepositorySecurityFingerprint {
+        repository: RepositoryHandle,
+        access_mode: Option<AccessMode>,
+    },
     RepositoryDatabaseId(RepositoryHandle),
     RepositoryEntryType {
         repository: RepositoryHandle,
@@ -72,28 +112,142 @@ pub(crate) enum Request {
         repository: RepositoryHandle,
         path: Utf8PathBuf,
     },
+    RepositoryWouldConflict {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+    },
+    RepositoryPin {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+    },
+    RepositoryUnpin {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+    },
+    RepositoryIsPinned {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+    },
     RepositoryMoveEntry {
         repository: RepositoryHandle,
         src: Utf8PathBuf,
         dst: Utf8PathBuf,
     },
+    RepositoryMoveEntryPreservingHistory {
+        repository: RepositoryHandle,
+        src: Utf8PathBuf,
+        dst: Utf8PathBuf,
+        preserve_history: bool,
+    },
+    RepositoryCopyEntry {
+        repository: RepositoryHandle,
+        src: Utf8PathBuf,
+        dst: Utf8PathBuf,
+    },
     RepositoryIsDhtEnabled(RepositoryHandle),
     RepositorySetDhtEnabled {
         repository: RepositoryHandle,
         enabled: bool,
     },
+    RepositoryAnnounceNow(RepositoryHandle),
+    RepositoryFindDuplicatesSubscribe(RepositoryHandle),
+    RepositoryVerifyIntegritySubscribe(RepositoryHandle),
+    RepositoryRebuildIndexSubscribe(RepositoryHandle),
+    RepositoryRotateKeysSubscribe {
+        repository: RepositoryHandle,
+        /// Path of the store of the rotated repository.
+        dst: Utf8PathBuf,
+        read_secret: Option<SetLocalSecret>,
+        write_secret: Option<SetLocalSecret>,
+    },
+    RepositoryConflictsSubscribe(RepositoryHandle),
+    RepositoryWatchDirectorySubscribe {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+    },
+    RepositoryPathSubscribe {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+    },
+    RepositoryImportPathSubscribe {
+        repository: RepositoryHandle,
+        src: PathBuf,
+        dst: Utf8PathBuf,
+    },
+    RepositoryExportPathSubscribe {
+        repository: RepositoryHandle,
+        src: Utf8PathBuf,
+        dst: PathBuf,
+        policy: CollisionPolicy,
+    },
+    RepositoryDetectConflict {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+    },
+    RepositoryResolveConflict {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+        content: Bytes,
+    },
+    RepositorySetPoolWarmup {
+        repository: RepositoryHandle,
+        count: u32,
+    },
+    RepositorySetSnapshotRetention {
+        repository: RepositoryHandle,
+        count: u32,
+    },
+    RepositorySnapshotRetention(RepositoryHandle),
+    RepositorySetQuota {
+        repository: RepositoryHandle,
+        quota: Option<u64>,
+    },
+    RepositoryQuota(RepositoryHandle),
+    RepositoryQuotaUsage(RepositoryHandle),
+    RepositorySetDirectoryMergeStrategy {
+        repository: RepositoryHandle,
+        strategy: MergeStrategy,
+    },
+    RepositoryDirectoryMergeStrategy(RepositoryHandle),
     RepositoryIsPexEnabled(RepositoryHandle),
     RepositorySetPexEnabled {
         repository: RepositoryHandle,
         enabled: bool,
     },
+    RepositoryIsUploadEnabled(RepositoryHandle),
+    RepositorySetUploadEnabled {
+        repository: RepositoryHandle,
+        enabled: bool,
+    },
+    RepositoryIsBlockStorageEnabled(RepositoryHandle),
+    RepositorySetBlockStorageEnabled {
+        repository: RepositoryHandle,
+        enabled: bool,
+    },
+    RepositoryPeerAccess {
+        repository: RepositoryHandle,
+        runtime_id: PublicRuntimeId,
+    },
     RepositoryCreateShareToken {
         repository: RepositoryHandle,
         secret: Option<LocalSecret>,
         access_mode: AccessMode,
         name: Option<String>,
+        /// Expiry of the token in milliseconds since the unix epoch, if any. See
+        /// `ShareToken::with_expiry` for details.
+        not_after: Option<u64>,
     },
     RepositorySyncProgress(RepositoryHandle),
+    RepositorySyncProgressSubscribe(RepositoryHandle),
+    RepositoryIndexProgress(RepositoryHandle),
+    RepositoryIsTransferring(RepositoryHandle),
+    RepositoryIsComplete(RepositoryHandle),
+    /// Resolves once the repository becomes fully synced. See `Repository::on_complete`.
+    RepositoryOnComplete(RepositoryHandle),
+    RepositoryAvailability {
+        repository: RepositoryHandle,
+        paths: Vec<Utf8PathBuf>,
+    },
     RepositoryCreateMirror {
         repository: RepositoryHandle,
         host: String,
@@ -118,10 +272,37 @@ pub(crate) enum Request {
     RepositoryMount(RepositoryHandle),
     RepositoryUnmount(RepositoryHandle),
     RepositoryStats(RepositoryHandle),
+    RepositoryMessageStats(RepositoryHandle),
+    RepositorySizeBreakdown(RepositoryHandle),
+    RepositoryStorageStats(RepositoryHandle),
+    RepositoryBranches(RepositoryHandle),
+    RepositoryBranchSyncTimes(RepositoryHandle),
+    RepositoryCurrentVersion(RepositoryHandle),
+    RepositorySetEventLogCapacity {
+        repository: RepositoryHandle,
+        capacity: u64,
+    },
+    RepositoryEventLog(RepositoryHandle),
+    RepositoryDownloading(RepositoryHandle),
+    RepositoryCancelDownload {
+        repository: RepositoryHandle,
+        path: Utf8PathBuf,
+    },
+    RepositoryExportArchive {
+        repository: RepositoryHandle,
+        path: PathBuf,
+    },
+    RepositoryImportArchive {
+        repository: RepositoryHandle,
+        path: PathBuf,
+    },
     ShareTokenMode(#[serde(with = "as_str")] ShareToken),
     ShareTokenInfoHash(#[serde(with = "as_str")] ShareToken),
     ShareTokenSuggestedName(#[serde(with = "as_str")] ShareToken),
     ShareTokenNormalize(#[serde(with = "as_str")] ShareToken),
+    ShareTokenToCompact(#[serde(with = "as_str")] ShareToken),
+    ShareTokenFromCompact(String),
+    ShareTokenFromRecovery(String),
     ShareTokenMirrorExists {
         #[serde(with = "as_str")]
         share_token: ShareToken,
@@ -176,10 +357,45 @@ pub(crate) enum Request {
     },
     FileLen(FileHandle),
     FileProgress(FileHandle),
+    FileIsRangeAvailable {
+        file: FileHandle,
+        offset: u64,
+        len: u64,
+    },
     FileFlush(FileHandle),
+    FileSetAutoFlush {
+        file: FileHandle,
+        /// Auto flush interval in milliseconds or `None` to disable auto flushing.
+        interval: Option<u64>,
+    },
     FileClose(FileHandle),
+    RepositoryBatch(RepositoryHandle),
+    BatchOpenFile {
+        batch: BatchHandle,
+        path: Utf8PathBuf,
+    },
+    BatchCreateFile {
+        batch: BatchHandle,
+        path: Utf8PathBuf,
+    },
+    BatchWrite {
+        batch: BatchHandle,
+        /// Index of the file within the batch.
+        file: u64,
+        offset: u64,
+        data: Bytes,
+    },
+    BatchTruncate {
+        batch: BatchHandle,
+        /// Index of the file within the batch.
+        file: u64,
+        len: u64,
+    },
+    BatchCommit(BatchHandle),
+    BatchClose(BatchHandle),
     NetworkInit(NetworkDefaults),
     NetworkSubscribe,
+    NetworkLastProtocolMismatch,
     NetworkBind {
         #[serde(with = "as_option_str", default)]
         quic_v4: Option<SocketAddrV4>,
@@ -190,6 +406,8 @@ pub(crate) enum Request {
         #[serde(with = "as_option_str", default)]
         tcp_v6: Option<SocketAddrV6>,
     },
+    NetworkSetBindInterface(Option<String>),
+    NetworkBindInterface,
     NetworkTcpListenerLocalAddrV4,
     NetworkTcpListenerLocalAddrV6,
     NetworkQuicListenerLocalAddrV4,
@@ -197,8 +415,15 @@ pub(crate) enum Request {
     NetworkAddUserProvidedPeer(#[serde(with = "as_str")] PeerAddr),
     NetworkRemoveUserProvidedPeer(#[serde(with = "as_str")] PeerAddr),
     NetworkUserProvidedPeers,
+    NetworkAddUserProvidedTcpPeer(String),
+    NetworkAddUserProvidedQuicPeer(String),
+    NetworkRemoveUserProvidedTcpPeer(String),
+    NetworkRemoveUserProvidedQuicPeer(String),
     NetworkKnownPeers,
+    NetworkSelfAddresses,
     NetworkThisRuntimeId,
+    NetworkBlockRuntimeId(String),
+    NetworkUnblockRuntimeId(String),
     NetworkCurrentProtocolVersion,
     NetworkHighestSeenProtocolVersion,
     NetworkIsPortForwardingEnabled,
@@ -209,7 +434,29 @@ pub(crate) enum Request {
     NetworkExternalAddrV6,
     NetworkNatBehavior,
     NetworkStats,
+    NetworkMessageStats,
+    NetworkSetBandwidthLimit {
+        upload: Option<u64>,
+        download: Option<u64>,
+    },
+    NetworkBandwidthLimit,
+    NetworkSetDhtBootstrapNodes(#[serde(with = "as_vec_str")] Vec<SocketAddr>),
+    NetworkSetStunServers(#[serde(with = "as_vec_str")] Vec<SocketAddr>),
+    NetworkResetStunServers,
+    NetworkSetMaxConnections(Option<u32>),
+    NetworkMaxConnections,
+    NetworkSetTransportPreference(TransportPreference),
+    NetworkTransportPreference,
+    NetworkSetDeviceName(String),
+    NetworkDeviceName,
+    NetworkSetKeepAliveConfig {
+        /// In milliseconds
+        send_interval: u64,
+        /// In milliseconds
+        recv_timeout: u64,
+    },
     NetworkShutdown,
+    SessionWorkerThreads,
     StateMonitorGet(Vec<MonitorId>),
     StateMonitorSubscribe(Vec<MonitorId>),
     Unsubscribe(TaskHandle),
@@ -232,14 +479,26 @@ pub(crate) enum Response {
     U64(u64),
     Bytes(Bytes),
     String(String),
+    Strings(Vec<String>),
     Handle(u64),
     Handles(Vec<u64>),
+    Bools(Vec<bool>),
     Directory(Directory),
     StateMonitor(StateMonitor),
     Progress(Progress),
     PeerInfos(Vec<PeerInfo>),
     PeerAddrs(#[serde(with = "as_vec_str")] Vec<PeerAddr>),
     NetworkStats(Stats),
+    MessageStats(MessageStats),
+    BandwidthLimit(BandwidthLimit),
+    SizeBreakdown(SizeBreakdown),
+    StorageStats(StorageStats),
+    BranchStatuses(Vec<BranchStatus>),
+    BranchSyncTimes(BTreeMap<PublicKey, u64>),
+    EventLog(Vec<LoggedEvent>),
+    Conflict(ConflictInfo),
+    OpenedRepository(OpenedRepository),
+    ProtocolMismatch(ProtocolMismatchEvent),
 }
 
 impl<T> From<Option<T>> for Response
@@ -319,6 +578,23 @@ impl TryFrom<Response> for String {
     }
 }
 
+impl From<Vec<String>> for Response {
+    fn from(value: Vec<String>) -> Self {
+        Self::Strings(value)
+    }
+}
+
+impl TryFrom<Response> for Vec<String> {
+    type Error = UnexpectedResponse;
+
+    fn try_from(response: Response) -> Result<Self, Self::Error> {
+        match response {
+            Response::Strings(value) => Ok(value),
+            _ => Err(UnexpectedResponse),
+        }
+    }
+}
+
 impl From<StateMonitor> for Response {
     fn from(value: StateMonitor) -> Self {
         Self::StateMonitor(value)
@@ -389,6 +665,23 @@ impl From<Progress> for Response {
     }
 }
 
+impl From<Vec<bool>> for Response {
+    fn from(value: Vec<bool>) -> Self {
+        Self::Bools(value)
+    }
+}
+
+impl TryFrom<Response> for Vec<bool> {
+    type Error = UnexpectedResponse;
+
+    fn try_from(response: Response) -> Result<Self, Self::Error> {
+        match response {
+            Response::Bools(value) => Ok(value),
+            _ => Err(UnexpectedResponse),
+        }
+    }
+}
+
 impl From<Vec<PeerInfo>> for Response {
     fn from(value: Vec<PeerInfo>) -> Self {
         Self::PeerInfos(value)
@@ -442,6 +735,66 @@ impl From<Stats> for Response {
     }
 }
 
+impl From<MessageStats> for Response {
+    fn from(value: MessageStats) -> Self {
+        Self::MessageStats(value)
+    }
+}
+
+impl From<BandwidthLimit> for Response {
+    fn from(value: BandwidthLimit) -> Self {
+        Self::BandwidthLimit(value)
+    }
+}
+
+impl From<Vec<BranchStatus>> for Response {
+    fn from(value: Vec<BranchStatus>) -> Self {
+        Self::BranchStatuses(value)
+    }
+}
+
+impl From<BTreeMap<PublicKey, u64>> for Response {
+    fn from(value: BTreeMap<PublicKey, u64>) -> Self {
+        Self::BranchSyncTimes(value)
+    }
+}
+
+impl From<Vec<LoggedEvent>> for Response {
+    fn from(value: Vec<LoggedEvent>) -> Self {
+        Self::EventLog(value)
+    }
+}
+
+impl From<SizeBreakdown> for Response {
+    fn from(value: SizeBreakdown) -> Self {
+        Self::SizeBreakdown(value)
+    }
+}
+
+impl From<StorageStats> for Response {
+    fn from(value: StorageStats) -> Self {
+        Self::StorageStats(value)
+    }
+}
+
+impl From<OpenedRepository> for Response {
+    fn from(value: OpenedRepository) -> Self {
+        Self::OpenedRepository(value)
+    }
+}
+
+impl From<ConflictInfo> for Response {
+    fn from(value: ConflictInfo) -> Self {
+        Self::Conflict(value)
+    }
+}
+
+impl From<ProtocolMismatchEvent> for Response {
+    fn from(value: ProtocolMismatchEvent) -> Self {
+        Self::ProtocolMismatch(value)
+    }
+}
+
 impl fmt::Debug for Response {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -452,8 +805,10 @@ impl fmt::Debug for Response {
             Self::U64(value) => f.debug_tuple("U64").field(value).finish(),
             Self::Bytes(_) => write!(f, "Bytes(_)"),
             Self::String(value) => f.debug_tuple("String").field(value).finish(),
+            Self::Strings(value) => f.debug_tuple("Strings").field(value).finish(),
             Self::Handle(value) => f.debug_tuple("Handle").field(value).finish(),
             Self::Handles(value) => f.debug_tuple("Handles").field(value).finish(),
+            Self::Bools(value) => f.debug_tuple("Bools").field(value).finish(),
             Self::Directory(_) => write!(f, "Directory(_)"),
             Self::StateMonitor(_) => write!(f, "StateMonitor(_)"),
             Self::Progress(value) => f.debug_tuple("Progress").field(value).finish(),
@@ -463,6 +818,29 @@ impl fmt::Debug for Response {
                 .finish(),
             Self::PeerAddrs(value) => f.debug_tuple("PeerAddrs").field(value).finish(),
             Self::NetworkStats(value) => f.debug_tuple("NetworkStats").field(value).finish(),
+            Self::MessageStats(value) => f.debug_tuple("MessageStats").field(value).finish(),
+            Self::BandwidthLimit(value) => f.debug_tuple("BandwidthLimit").field(value).finish(),
+            Self::SizeBreakdown(value) => f.debug_tuple("SizeBreakdown").field(value).finish(),
+            Self::StorageStats(value) => f.debug_tuple("StorageStats").field(value).finish(),
+            Self::BranchStatuses(value) => f
+                .debug_struct("BranchStatuses")
+                .field("len", &value.len())
+                .finish(),
+            Self::BranchSyncTimes(value) => f
+                .debug_struct("BranchSyncTimes")
+                .field("len", &value.len())
+                .finish(),
+            Self::EventLog(value) => f
+                .debug_struct("EventLog")
+                .field("len", &value.len())
+                .finish(),
+            Self::Conflict(value) => f.debug_tuple("Conflict").field(value).finish(),
+            Self::OpenedRepository(value) => {
+                f.debug_tuple("OpenedRepository").field(value).finish()
+            }
+            Self::ProtocolMismatch(value) => {
+                f.debug_tuple("ProtocolMismatch").field(value).finish()
+            }
         }
     }
 }
@@ -601,7 +979,7 @@ impl fmt::Debug for Bytes {
 
 #[cfg(test)]
 mod tests {
-    use std::time::SystemTime;
+    use std::time::{Duration, SystemTime};
 
     use super::*;
     use ouisync_lib::{
@@ -618,6 +996,7 @@ mod tests {
                 read_secret: None,
                 write_secret: None,
                 share_token: None,
+                discovery_enabled: false,
             },
             Request::RepositoryClose(Handle::from_id(1)),
             Request::RepositorySetCredentials {
@@ -633,6 +1012,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn repository_create_without_discovery_enabled() {
+        #[derive(Serialize)]
+        #[serde(rename_all = "snake_case")]
+        enum OldRequest {
+            RepositoryCreate {
+                path: Utf8PathBuf,
+                read_secret: Option<SetLocalSecret>,
+                write_secret: Option<SetLocalSecret>,
+                share_token: Option<String>,
+            },
+        }
+
+        let encoded = rmp_serde::to_vec_named(&OldRequest::RepositoryCreate {
+            path: Utf8PathBuf::from("/tmp/repo.db"),
+            read_secret: None,
+            write_secret: None,
+            share_token: None,
+        })
+        .unwrap();
+        let decoded: Request = rmp_serde::from_slice(&encoded).unwrap();
+
+        assert_eq!(
+            decoded,
+            Request::RepositoryCreate {
+                path: Utf8PathBuf::from("/tmp/repo.db"),
+                read_secret: None,
+                write_secret: None,
+                share_token: None,
+                discovery_enabled: false,
+            }
+        );
+    }
+
     #[test]
     fn response_serialize_deserialize() {
         let origs = [
@@ -659,6 +1072,9 @@ mod tests {
                     source: PeerSource::LocalDiscovery,
                     state: PeerState::Connecting,
                     stats: Stats::default(),
+                    connected_since: SystemTime::UNIX_EPOCH,
+                    rtt: None,
+                    display_name: None,
                 },
                 PeerInfo {
                     addr: PeerAddr::Quic(
@@ -670,6 +1086,9 @@ mod tests {
                         since: SystemTime::UNIX_EPOCH,
                     },
                     stats: Stats::default(),
+                    connected_since: SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+                    rtt: Some(Duration::from_micros(1500)),
+                    display_name: Some("laptop".to_owned()),
                 },
             ]),
             Response::PeerAddrs(vec![PeerAddr::Tcp(([192, 168, 1, 234], 45678).into())]),
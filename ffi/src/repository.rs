@@ -1,31 +1,64 @@
 use crate::{
     error::{Error, ErrorCode},
     registry::{Handle, InvalidHandle, Registry},
+    sender,
     state::{State, TaskHandle},
 };
 use camino::Utf8PathBuf;
-use ouisync_bridge::{protocol::Notification, repository, transport::NotificationSender};
+use futures_util::{future, stream, StreamExt};
+use ouisync_bridge::{
+    protocol::{
+        ConflictEvent, DirectoryEvent, DuplicatesEvent, IndexRebuildEvent, IntegrityEvent,
+        KeyRotationEvent, Notification, PathChangeEvent,
+    },
+    repository,
+    transport::NotificationSender,
+};
 use ouisync_lib::{
-    self, crypto::Hashable, path, AccessMode, Credentials, Event, LocalSecret, Progress,
-    Registration, Repository, SetLocalSecret, ShareToken, Stats,
+    self,
+    crypto::{sign::PublicKey, Hashable},
+    path, AccessMode, Credentials, DuplicatesSearchEvent, Event, LocalSecret, MessageStats,
+    Progress, PublicRuntimeId, RebuildIndexEvent, Registration, Repository, RepositoryParams,
+    SetLocalSecret, ShareToken, Stats, VersionVector,
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, BTreeMap, HashMap},
     ffi::OsString,
     mem,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    pin::pin,
     sync::{Arc, RwLock as BlockingRwLock},
+    time::{Duration, SystemTime},
 };
 use thiserror::Error;
-use tokio::sync::{broadcast::error::RecvError, watch, RwLock as AsyncRwLock};
+use tokio::{
+    fs,
+    io::{BufReader, BufWriter},
+    sync::{broadcast::error::RecvError, watch, RwLock as AsyncRwLock},
+    time,
+};
 
 pub(crate) struct RepositoryHolder {
-    pub store_path: PathBuf,
+    store_path: BlockingRwLock<PathBuf>,
     pub repository: Arc<Repository>,
     pub registration: AsyncRwLock<Option<Registration>>,
 }
 
+impl RepositoryHolder {
+    pub fn new(store_path: PathBuf, repository: Repository) -> Self {
+        Self {
+            store_path: BlockingRwLock::new(store_path),
+            repository: Arc::new(repository),
+            registration: AsyncRwLock::new(None),
+        }
+    }
+
+    pub fn store_path(&self) -> PathBuf {
+        self.store_path.read().unwrap().clone()
+    }
+}
+
 pub(crate) type RepositoryHandle = Handle<Arc<RepositoryHolder>>;
 
 #[derive(Debug, Error)]
@@ -36,12 +69,16 @@ pub(crate) struct RegistrationRequired;
 #[error("entry has been changed")]
 pub(crate) struct EntryChanged;
 
+/// Creates a new repository. If `discovery_enabled` is true, DHT and PEX get enabled as soon as
+/// the repository starts syncing. Otherwise the repository can't be discovered by other peers until
+/// they are explicitly enabled.
 pub(crate) async fn create(
     state: &State,
     store_path: PathBuf,
     local_read_secret: Option<SetLocalSecret>,
     local_write_secret: Option<SetLocalSecret>,
     share_token: Option<ShareToken>,
+    discovery_enabled: bool,
 ) -> Result<RepositoryHandle, Error> {
     let entry = ensure_vacant_entry(state, store_path.clone()).await?;
 
@@ -55,12 +92,12 @@ pub(crate) async fn create(
     )
     .await?;
 
-    let holder = RepositoryHolder {
-        store_path,
-        repository: Arc::new(repository),
-        registration: AsyncRwLock::new(None),
-    };
-    let handle = entry.insert(holder);
+    // Do this before the handle is returned to the app so the repository is never registered
+    // with the network with a different discovery setting.
+    ouisync_lib::set_discovery_enabled(&repository.handle(), discovery_enabled, discovery_enabled)
+        .await?;
+
+    let handle = entry.insert(RepositoryHolder::new(store_path, repository));
 
     Ok(handle)
 }
@@ -95,16 +132,76 @@ pub(crate) async fn open(
     )
     .await?;
 
-    let holder = RepositoryHolder {
-        store_path,
-        repository: Arc::new(repository),
-        registration: AsyncRwLock::new(None),
-    };
-    let handle = entry.insert(holder);
+    let handle = entry.insert(RepositoryHolder::new(store_path, repository));
 
     Ok(handle)
 }
 
+/// Opens an existing repository trying each of the given local secrets and using the one that
+/// unlocks the highest access mode. If the repository is already open, its access mode is raised
+/// if any of the secrets allows it.
+pub(crate) async fn open_with_secrets(
+    state: &State,
+    store_path: PathBuf,
+    local_secrets: Vec<LocalSecret>,
+) -> Result<OpenedRepository, Error> {
+    let entry = match state.repositories.entry(store_path.clone()).await {
+        RepositoryEntry::Occupied(handle) => {
+            let holder = state.repositories.get(handle)?;
+            let mut secret_index = None;
+            let mut failed = 0;
+            let count = local_secrets.len();
+
+            for (index, local_secret) in local_secrets.into_iter().enumerate() {
+                let prev_mode = holder.repository.access_mode();
+
+                match holder
+                    .repository
+                    .set_access_mode(AccessMode::Write, Some(local_secret))
+                    .await
+                {
+                    Ok(()) => (),
+                    Err(ouisync_lib::Error::WrongPassword) => failed += 1,
+                    Err(error) => return Err(error.into()),
+                }
+
+                if holder.repository.access_mode() > prev_mode {
+                    secret_index = Some(index as u32);
+                }
+            }
+
+            // Same as when opening the repository: fail only if none of the secrets worked.
+            if count > 0 && failed == count {
+                return Err(ouisync_lib::Error::WrongPassword.into());
+            }
+
+            return Ok(OpenedRepository {
+                repository: handle,
+                access_mode: holder.repository.access_mode(),
+                secret_index,
+            });
+        }
+        RepositoryEntry::Vacant(entry) => entry,
+    };
+
+    let (repository, secret_index) = repository::open_with_secrets(
+        store_path.clone(),
+        &local_secrets,
+        &state.config,
+        &state.repos_monitor,
+    )
+    .await?;
+
+    let access_mode = repository.access_mode();
+    let handle = entry.insert(RepositoryHolder::new(store_path, repository));
+
+    Ok(OpenedRepository {
+        repository: handle,
+        access_mode,
+        secret_index: secret_index.map(|index| index as u32),
+    })
+}
+
 async fn ensure_vacant_entry(
     state: &State,
     store_path: PathBuf,
@@ -125,12 +222,48 @@ async fn ensure_vacant_entry(
 pub(crate) async fn close(state: &State, handle: RepositoryHandle) -> Result<(), Error> {
     if let Some(holder) = state.repositories.remove(handle) {
         holder.repository.close().await?;
-        state.mounter.unmount(&holder.store_path)?;
+        state.mounter.unmount(&holder.store_path())?;
     }
 
     Ok(())
 }
 
+/// Moves the database file of the repository to `dst` while keeping it open. The handle remains
+/// valid and, if the repository is mounted, it's remounted under the new name.
+pub(crate) async fn rename(
+    state: &State,
+    handle: RepositoryHandle,
+    dst: PathBuf,
+) -> Result<(), Error> {
+    let holder = state.repositories.get(handle)?;
+
+    let entry = match state.repositories.entry(dst.clone()).await {
+        RepositoryEntry::Occupied(_) => return Err(ouisync_lib::Error::EntryExists.into()),
+        RepositoryEntry::Vacant(entry) => entry,
+    };
+
+    let src = holder.store_path();
+    let mounted = state.mounter.is_mounted(&src);
+
+    if mounted {
+        state.mounter.unmount(&src)?;
+    }
+
+    let result = holder.repository.rename_store(&dst).await;
+
+    if result.is_ok() {
+        entry.relocate(handle, &holder, &src);
+    }
+
+    if mounted {
+        state
+            .mounter
+            .mount(&holder.store_path(), &holder.repository)?;
+    }
+
+    Ok(result?)
+}
+
 /// Called when the session is closed and the user has not closed some or all the open
 /// repositories.
 pub async fn close_all_repositories(state: &State) {
@@ -139,13 +272,13 @@ pub async fn close_all_repositories(state: &State) {
         if let Err(error) = holder.repository.close().await {
             tracing::warn!(
                 "Failed to close repository \"{:?}\": {error:?}",
-                holder.store_path
+                holder.store_path()
             );
         }
-        if let Err(error) = state.mounter.unmount(&holder.store_path) {
+        if let Err(error) = state.mounter.unmount(&holder.store_path()) {
             tracing::warn!(
                 "Failed to unmount repository \"{:?}\": {error:?}",
-                holder.store_path
+                holder.store_path()
             );
         }
     }
@@ -161,23 +294,27 @@ pub(crate) async fn is_sync_enabled(
         .registration
         .read()
         .await
-        .is_some())
+        .as_ref()
+        .map(|registration| registration.is_sync_enabled())
+        .unwrap_or(false))
 }
 
+/// Disabling sync only pauses it (see [Registration::set_sync_enabled]) so the repository stays
+/// registered with the network and keeps being announced on the DHT.
 pub(crate) async fn set_sync_enabled(
     state: &State,
     handle: RepositoryHandle,
     enabled: bool,
 ) -> Result<(), Error> {
     let holder = state.repositories.get(handle)?;
+    let mut registration = holder.registration.write().await;
 
-    if enabled {
-        let mut registration = holder.registration.write().await;
-        if registration.is_none() {
+    match registration.as_ref() {
+        Some(registration) => registration.set_sync_enabled(enabled),
+        None if enabled => {
             *registration = Some(state.network.register(holder.repository.handle()).await);
         }
-    } else {
-        holder.registration.write().await.take();
+        None => (),
     }
 
     Ok(())
@@ -216,6 +353,75 @@ pub(crate) fn access_mode(state: &State, handle: RepositoryHandle) -> Result<u8,
         .into())
 }
 
+pub(crate) async fn lock_reason(state: &State, handle: RepositoryHandle) -> Result<u8, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .repository
+        .lock_reason()
+        .await?
+        .into())
+}
+
+pub(crate) async fn directory_merge_strategy(
+    state: &State,
+    handle: RepositoryHandle,
+) -> Result<u8, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .repository
+        .directory_merge_strategy()
+        .await?
+        .into())
+}
+
+/// Returns the access mode the given local secret would unlock (`Blind` if it unlocks nothing)
+/// without changing the current access mode of the repository.
+pub(crate) async fn verify_password(
+    state: &State,
+    handle: RepositoryHandle,
+    secret: LocalSecret,
+) -> Result<u8, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .repository
+        .verify_password(secret)
+        .await?
+        .into())
+}
+
+/// Exports the secrets unlocked by `secret` as a recovery string. See
+/// `Repository::export_access_secrets` for the security implications.
+pub(crate) async fn export_access_secrets(
+    state: &State,
+    handle: RepositoryHandle,
+    secret: LocalSecret,
+) -> Result<String, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .repository
+        .export_access_secrets(secret)
+        .await?)
+}
+
+/// Restores the access to the repository from a recovery string.
+pub(crate) async fn import_access_secrets(
+    state: &State,
+    handle: RepositoryHandle,
+    recovery: &str,
+) -> Result<(), Error> {
+    state
+        .repositories
+        .get(handle)?
+        .repository
+        .import_access_secrets(recovery)
+        .await?;
+    Ok(())
+}
+
 pub(crate) async fn set_access_mode(
     state: &State,
     handle: RepositoryHandle,
@@ -232,6 +438,28 @@ pub(crate) async fn set_access_mode(
     Ok(())
 }
 
+/// Switches the repository to blind mode without removing the stored secrets.
+pub(crate) async fn lock(state: &State, handle: RepositoryHandle) -> Result<(), Error> {
+    state.repositories.get(handle)?.repository.lock().await?;
+    Ok(())
+}
+
+/// Unlocks the repository previously locked with `lock` using the given local secret.
+pub(crate) async fn unlock(
+    state: &State,
+    handle: RepositoryHandle,
+    local_secret: LocalSecret,
+) -> Result<(), Error> {
+    state
+        .repositories
+        .get(handle)?
+        .repository
+        .unlock(local_secret)
+        .await?;
+
+    Ok(())
+}
+
 /// Return the info-hash of the repository formatted as hex string. This can be used as a globally
 /// unique, non-secret identifier of the repository.
 /// User is responsible for deallocating the returned string.
@@ -242,6 +470,18 @@ pub(crate) fn info_hash(state: &State, handle: RepositoryHandle) -> Result<Strin
     Ok(hex::encode(info_hash))
 }
 
+/// Returns a short human readable fingerprint of the repository secrets which two users can
+/// compare out-of-band to verify they share the same secrets. If `access_mode` is given (clamped to
+/// the current one), returns the fingerprint for that mode instead.
+pub(crate) fn security_fingerprint(
+    state: &State,
+    handle: RepositoryHandle,
+    access_mode: Option<AccessMode>,
+) -> Result<String, Error> {
+    let holder = state.repositories.get(handle)?;
+    Ok(holder.repository.security_fingerprint(access_mode))
+}
+
 /// Returns an ID that is randomly generated once per repository. Can be used to store local user
 /// data per repository (e.g. passwords behind biometric storage).
 pub(crate) async fn database_id(state: &State, handle: RepositoryHandle) -> Result<Vec<u8>, Error> {
@@ -255,7 +495,7 @@ pub(crate) async fn database_id(state: &State, handle: RepositoryHandle) -> Resu
 pub(crate) fn get_name(state: &State, handle: RepositoryHandle) -> Result<OsString, Error> {
     let holder = state.repositories.get(handle)?;
 
-    let store_path = &holder.store_path;
+    let store_path = holder.store_path();
 
     match store_path.with_extension("").file_name() {
         Some(store_path) => Ok(store_path.to_os_string()),
@@ -268,12 +508,14 @@ pub(crate) fn get_name(state: &State, handle: RepositoryHandle) -> Result<OsStri
 
 pub(crate) fn mount(state: &State, handle: RepositoryHandle) -> Result<(), Error> {
     let holder = state.repositories.get(handle)?;
-    state.mounter.mount(&holder.store_path, &holder.repository)
+    state
+        .mounter
+        .mount(&holder.store_path(), &holder.repository)
 }
 
 pub(crate) fn unmount(state: &State, handle: RepositoryHandle) -> Result<(), Error> {
     let holder = state.repositories.get(handle)?;
-    state.mounter.unmount(&holder.store_path)
+    state.mounter.unmount(&holder.store_path())
 }
 
 /// Returns the type of repository entry (file, directory, ...) or `None` if the entry doesn't
@@ -324,12 +566,14 @@ pub(crate) async fn entry_version_hash(
     Ok(hash.as_ref().into())
 }
 
-/// Move/rename entry from src to dst.
+/// Moves (renames) the entry at `src` to `dst`. See [ouisync_lib::Repository::move_entry_with] for
+/// the meaning of `preserve_history`.
 pub(crate) async fn move_entry(
     state: &State,
     handle: RepositoryHandle,
     src: Utf8PathBuf,
     dst: Utf8PathBuf,
+    preserve_history: bool,
 ) -> Result<(), Error> {
     let holder = state.repositories.get(handle)?;
     let (src_dir, src_name) = path::decompose(&src).ok_or(ouisync_lib::Error::EntryNotFound)?;
@@ -337,7 +581,7 @@ pub(crate) async fn move_entry(
 
     holder
         .repository
-        .move_entry(src_dir, src_name, dst_dir, dst_name)
+        .move_entry_with(src_dir, src_name, dst_dir, dst_name, preserve_history)
         .await?;
 
     Ok(())
@@ -351,18 +595,215 @@ pub(crate) fn subscribe(
 ) -> Result<TaskHandle, Error> {
     let holder = state.repositories.get(repository_handle)?;
 
-    let mut notification_rx = holder.repository.subscribe();
+    let events = stream::unfold(holder.repository.subscribe(), |mut rx| async move {
+        match rx.recv().await {
+            Ok(Event { .. }) | Err(RecvError::Lagged(_)) => Some(((), rx)),
+            Err(RecvError::Closed) => None,
+        }
+    });
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| {
+        sender::send_coalesced(events, id, || Notification::Repository, notification_tx)
+    });
+
+    Ok(handle)
+}
+
+/// Starts searching for files with identical content. Each found group is sent as a
+/// `DuplicatesEvent::Group` notification, interleaved with `DuplicatesEvent::Progress`
+/// notifications and followed by either `Done` or `Failed`. Unsubscribing cancels the search.
+pub(crate) fn find_duplicates(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
     let notification_tx = notification_tx.clone();
 
     let handle = state.spawn_task(|id| async move {
-        loop {
-            match notification_rx.recv().await {
-                Ok(Event { .. }) | Err(RecvError::Lagged(_)) => (),
-                Err(RecvError::Closed) => break,
-            }
+        let mut events = pin!(repository.duplicates_with_progress());
+
+        let last = loop {
+            let event = match events.next().await {
+                Some(Ok(DuplicatesSearchEvent::Progress(progress))) => {
+                    DuplicatesEvent::Progress(progress)
+                }
+                Some(Ok(DuplicatesSearchEvent::Group(group))) => {
+                    DuplicatesEvent::Group(group.into_iter().map(String::from).collect())
+                }
+                Some(Err(error)) => break DuplicatesEvent::Failed(error.to_string()),
+                None => break DuplicatesEvent::Done,
+            };
+
+            notification_tx
+                .send((id, Notification::Duplicates(event)))
+                .await
+                .ok();
+        };
+
+        notification_tx
+            .send((id, Notification::Duplicates(last)))
+            .await
+            .ok();
+    });
+
+    Ok(handle)
+}
+
+/// Starts verifying the repository integrity. Each found problem is sent as an `IntegrityEvent`
+/// notification, followed by either `Done` or `Failed`. Unsubscribing cancels the check.
+pub(crate) fn verify_integrity(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let mut reports = pin!(repository.verify_integrity());
+
+        let last = loop {
+            let event = match reports.next().await {
+                Some(Ok(report)) => IntegrityEvent::from(report),
+                Some(Err(error)) => break IntegrityEvent::Failed(error.to_string()),
+                None => break IntegrityEvent::Done,
+            };
+
+            notification_tx
+                .send((id, Notification::Integrity(event)))
+                .await
+                .ok();
+        };
+
+        notification_tx
+            .send((id, Notification::Integrity(last)))
+            .await
+            .ok();
+    });
+
+    Ok(handle)
+}
+
+/// Rotates the keys of the repository into a new repository at `dst`. The progress is reported
+/// as `KeyRotation` notifications ending with either `Done` or `Failed`. Cancelling the
+/// subscription interrupts the rotation, running it again with the same `dst` resumes it. See
+/// `Repository::rotate_keys` for details.
+pub(crate) async fn rotate_keys(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+    dst: PathBuf,
+    read_secret: Option<SetLocalSecret>,
+    write_secret: Option<SetLocalSecret>,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let params = RepositoryParams::new(dst)
+        .with_device_id(ouisync_bridge::device_id::get_or_create(&state.config).await?)
+        .with_parent_monitor(state.repos_monitor.clone());
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let mut progresses = pin!(repository.rotate_keys(&params, read_secret, write_secret));
 
+        let last = loop {
+            let event = match progresses.next().await {
+                Some(Ok(progress)) => KeyRotationEvent::Progress(progress),
+                Some(Err(error)) => break KeyRotationEvent::Failed(error.to_string()),
+                None => break KeyRotationEvent::Done,
+            };
+
+            notification_tx
+                .send((id, Notification::KeyRotation(event)))
+                .await
+                .ok();
+        };
+
+        notification_tx
+            .send((id, Notification::KeyRotation(last)))
+            .await
+            .ok();
+    });
+
+    Ok(handle)
+}
+
+/// Rebuilds the index of the local branch from the stored blocks. The progress is reported as
+/// `IndexRebuild` notifications ending with either `Done` (carrying the report) or `Failed`. See
+/// `Repository::rebuild_index` for details.
+pub(crate) fn rebuild_index(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let mut events = pin!(repository.rebuild_index());
+
+        let last = loop {
+            let event = match events.next().await {
+                Some(Ok(RebuildIndexEvent::Progress(progress))) => {
+                    IndexRebuildEvent::Progress(progress)
+                }
+                Some(Ok(RebuildIndexEvent::Done(report))) => break IndexRebuildEvent::Done(report),
+                Some(Err(error)) => break IndexRebuildEvent::Failed(error.to_string()),
+                None => break IndexRebuildEvent::Failed("interrupted".to_owned()),
+            };
+
+            notification_tx
+                .send((id, Notification::IndexRebuild(event)))
+                .await
+                .ok();
+        };
+
+        notification_tx
+            .send((id, Notification::IndexRebuild(last)))
+            .await
+            .ok();
+    });
+
+    Ok(handle)
+}
+
+/// Subscribe to sync progress notifications. A `SyncProgress` notification carrying the current
+/// progress is sent first and then another one every time the progress changes (throttled).
+pub(crate) fn subscribe_to_sync_progress(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let mut progresses = pin!(repository.subscribe_sync_progress());
+
+        while let Some(progress) = progresses.next().await {
             notification_tx
-                .send((id, Notification::Repository))
+                .send((id, Notification::SyncProgress(progress)))
                 .await
                 .ok();
         }
@@ -371,6 +812,280 @@ pub(crate) fn subscribe(
     Ok(handle)
 }
 
+/// Subscribe to conflict notifications. A `ConflictEvent` is sent for every file that has
+/// concurrent versions, initially for all the existing conflicts and then whenever a new conflict
+/// appears or an existing one changes. Resolve the conflicts with `resolve_conflict`. Conflicts
+/// that are not resolved keep all their versions.
+pub(crate) fn subscribe_to_conflicts(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let mut event_rx = repository.subscribe();
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let mut known = HashMap::new();
+
+        loop {
+            match repository.conflicts().await {
+                Ok(conflicts) => {
+                    // Forget the resolved conflicts so they are reported again if they reappear.
+                    known.retain(|path, _| conflicts.iter().any(|c| &c.path == path));
+
+                    for conflict in conflicts {
+                        if known.get(&conflict.path) == Some(&conflict) {
+                            continue;
+                        }
+
+                        let event = ConflictEvent {
+                            path: conflict.path.to_string(),
+                            versions: conflict
+                                .versions
+                                .iter()
+                                .map(|version| version.unique_path.to_string())
+                                .collect(),
+                        };
+
+                        known.insert(conflict.path.clone(), conflict);
+
+                        notification_tx
+                            .send((id, Notification::Conflict(event)))
+                            .await
+                            .ok();
+                    }
+                }
+                Err(error) => {
+                    tracing::warn!(?error, "failed to find conflicts");
+                }
+            }
+
+            match event_rx.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => (),
+                Err(RecvError::Closed) => break,
+            }
+
+            // Wait for the burst of events to settle down and drain them to avoid rescanning the
+            // repository on every single change.
+            time::sleep(Duration::from_secs(1)).await;
+            event_rx = event_rx.resubscribe();
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Watches the directory at `path` for changes of its entries. Each change is sent as a
+/// `DirectoryEvent` notification. The watch ends with either `DirectoryRemoved` or `Failed`.
+pub(crate) fn watch_directory(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+    path: Utf8PathBuf,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let last = match repository.watch_directory(path).await {
+            Ok(events) => {
+                let mut events = pin!(events);
+
+                loop {
+                    let event = match events.next().await {
+                        Some(Ok(event)) => DirectoryEvent::from(event),
+                        Some(Err(error)) => break DirectoryEvent::Failed(error.to_string()),
+                        None => return,
+                    };
+
+                    notification_tx
+                        .send((id, Notification::DirectoryChanged(event)))
+                        .await
+                        .ok();
+                }
+            }
+            Err(error) => DirectoryEvent::Failed(error.to_string()),
+        };
+
+        notification_tx
+            .send((id, Notification::DirectoryChanged(last)))
+            .await
+            .ok();
+    });
+
+    Ok(handle)
+}
+
+/// Watches the file or directory at `path` for changes of itself or its descendants. Each change is
+/// sent as a `PathChangeEvent` notification. The watch ends with either `Removed` or `Failed`.
+pub(crate) fn subscribe_path(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+    path: Utf8PathBuf,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let last = match repository.subscribe_path(path).await {
+            Ok(events) => {
+                let mut events = pin!(events);
+
+                loop {
+                    let event = match events.next().await {
+                        Some(Ok(event)) => PathChangeEvent::from(event),
+                        Some(Err(error)) => break PathChangeEvent::Failed(error.to_string()),
+                        None => return,
+                    };
+
+                    notification_tx
+                        .send((id, Notification::PathChanged(event)))
+                        .await
+                        .ok();
+                }
+            }
+            Err(error) => PathChangeEvent::Failed(error.to_string()),
+        };
+
+        notification_tx
+            .send((id, Notification::PathChanged(last)))
+            .await
+            .ok();
+    });
+
+    Ok(handle)
+}
+
+/// Resolve conflict of the file at `path` by replacing it with `content`.
+pub(crate) async fn resolve_conflict(
+    state: &State,
+    handle: RepositoryHandle,
+    path: Utf8PathBuf,
+    content: Vec<u8>,
+) -> Result<(), Error> {
+    state
+        .repositories
+        .get(handle)?
+        .repository
+        .resolve_conflict(path, &content)
+        .await?;
+
+    Ok(())
+}
+
+/// Checks whether the file at `path` has concurrent versions without modifying anything. Returns
+/// the competing versions or `None` if there is no conflict.
+pub(crate) async fn detect_conflict(
+    state: &State,
+    handle: RepositoryHandle,
+    path: Utf8PathBuf,
+) -> Result<Option<ConflictInfo>, Error> {
+    let conflict = state
+        .repositories
+        .get(handle)?
+        .repository
+        .detect_conflict(path)
+        .await?;
+
+    Ok(conflict.map(|conflict| ConflictInfo {
+        path: conflict.path.into_string(),
+        versions: conflict
+            .versions
+            .into_iter()
+            .map(|version| ConflictVersionInfo {
+                path: version.unique_path.into_string(),
+                branch_id: version.branch_id,
+                version_vector: version.version_vector,
+            })
+            .collect(),
+    }))
+}
+
+/// Returns the current version vector of the repository, encoded with msgpack. The app can persist
+/// it as an opaque checkpoint token and pass it back to the library later.
+pub(crate) async fn current_version(
+    state: &State,
+    handle: RepositoryHandle,
+) -> Result<Vec<u8>, Error> {
+    let version = state
+        .repositories
+        .get(handle)?
+        .repository
+        .current_version()
+        .await?;
+
+    // unwrap is OK because serializing a version vector into a `Vec` can't fail.
+    Ok(rmp_serde::to_vec(&version).unwrap())
+}
+
+/// Returns when each remote branch was last synced, in milliseconds since the unix epoch.
+pub(crate) async fn branch_sync_times(
+    state: &State,
+    handle: RepositoryHandle,
+) -> Result<BTreeMap<PublicKey, u64>, Error> {
+    let times = state
+        .repositories
+        .get(handle)?
+        .repository
+        .branch_sync_times()
+        .await?;
+
+    Ok(times
+        .into_iter()
+        .map(|(branch_id, time)| {
+            let millis = time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+
+            (branch_id, u64::try_from(millis).unwrap_or(u64::MAX))
+        })
+        .collect())
+}
+
+/// Writes the repository index and blocks into an archive file at `path` for offline transfer.
+pub(crate) async fn export_archive(
+    state: &State,
+    handle: RepositoryHandle,
+    path: PathBuf,
+) -> Result<(), Error> {
+    let holder = state.repositories.get(handle)?;
+    let mut dst = BufWriter::new(fs::File::create(path).await?);
+
+    holder.repository.export_archive(&mut dst).await?;
+
+    Ok(())
+}
+
+/// Merges the archive file at `path` (previously created with `export_archive`) into the
+/// repository.
+pub(crate) async fn import_archive(
+    state: &State,
+    handle: RepositoryHandle,
+    path: PathBuf,
+) -> Result<(), Error> {
+    let holder = state.repositories.get(handle)?;
+    let mut src = BufReader::new(fs::File::open(path).await?);
+
+    holder.repository.import_archive(&mut src).await?;
+
+    Ok(())
+}
+
 pub(crate) async fn is_dht_enabled(state: &State, handle: RepositoryHandle) -> Result<bool, Error> {
     Ok(state
         .repositories
@@ -401,6 +1116,20 @@ pub(crate) async fn set_dht_enabled(
     Ok(())
 }
 
+/// Triggers an immediate DHT announce of the repository. Returns whether the announce was
+/// actually triggered (it's not if DHT is disabled or if it's being rate-limited).
+pub(crate) async fn announce_now(state: &State, handle: RepositoryHandle) -> Result<bool, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .registration
+        .read()
+        .await
+        .as_ref()
+        .ok_or(RegistrationRequired)?
+        .announce_now())
+}
+
 pub(crate) async fn is_pex_enabled(state: &State, handle: RepositoryHandle) -> Result<bool, Error> {
     Ok(state
         .repositories
@@ -431,6 +1160,95 @@ pub(crate) async fn set_pex_enabled(
     Ok(())
 }
 
+pub(crate) async fn is_upload_enabled(
+    state: &State,
+    handle: RepositoryHandle,
+) -> Result<bool, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .registration
+        .read()
+        .await
+        .as_ref()
+        .ok_or(RegistrationRequired)?
+        .is_upload_enabled())
+}
+
+/// Enables/disables serving blocks of the repository to peers. See
+/// `Registration::set_upload_enabled` for details.
+pub(crate) async fn set_upload_enabled(
+    state: &State,
+    handle: RepositoryHandle,
+    enabled: bool,
+) -> Result<(), Error> {
+    state
+        .repositories
+        .get(handle)?
+        .registration
+        .read()
+        .await
+        .as_ref()
+        .ok_or(RegistrationRequired)?
+        .set_upload_enabled(enabled)
+        .await;
+    Ok(())
+}
+
+pub(crate) async fn is_block_storage_enabled(
+    state: &State,
+    handle: RepositoryHandle,
+) -> Result<bool, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .registration
+        .read()
+        .await
+        .as_ref()
+        .ok_or(RegistrationRequired)?
+        .is_block_storage_enabled())
+}
+
+/// Enables/disables storing blocks of the repository. See
+/// `Registration::set_block_storage_enabled` for details.
+pub(crate) async fn set_block_storage_enabled(
+    state: &State,
+    handle: RepositoryHandle,
+    enabled: bool,
+) -> Result<(), Error> {
+    state
+        .repositories
+        .get(handle)?
+        .registration
+        .read()
+        .await
+        .as_ref()
+        .ok_or(RegistrationRequired)?
+        .set_block_storage_enabled(enabled)
+        .await;
+    Ok(())
+}
+
+/// Returns the access mode the given peer has proven to have to the repository, or `None` if
+/// there is currently no link with the peer.
+pub(crate) async fn peer_access(
+    state: &State,
+    handle: RepositoryHandle,
+    runtime_id: PublicRuntimeId,
+) -> Result<Option<u8>, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .registration
+        .read()
+        .await
+        .as_ref()
+        .ok_or(RegistrationRequired)?
+        .peer_access(&runtime_id)
+        .map(Into::into))
+}
+
 /// The `local_secret` parameter is optional, if `None` the current access level of the opened
 /// repository is used. If provided, the highest access level that the local_secret can unlock is
 /// used.
@@ -440,13 +1258,59 @@ pub(crate) async fn create_share_token(
     local_secret: Option<LocalSecret>,
     access_mode: AccessMode,
     name: Option<String>,
+    not_after: Option<u64>,
 ) -> Result<String, Error> {
     let holder = state.repositories.get(repository)?;
-    let token =
-        repository::create_share_token(&holder.repository, local_secret, access_mode, name).await?;
+    let not_after = not_after.map(|ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms));
+    let token = repository::create_share_token(
+        &holder.repository,
+        local_secret,
+        access_mode,
+        name,
+        not_after,
+    )
+    .await?;
     Ok(token)
 }
 
+/// Returns the index syncing progress, that is, how much of the metadata (directory structure and
+/// file listings) has been received. Unlike the syncing progress it advances even before any
+/// blocks are downloaded.
+pub(crate) async fn index_progress(
+    state: &State,
+    handle: RepositoryHandle,
+) -> Result<Progress, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .repository
+        .index_progress()
+        .await?)
+}
+
+/// Checks offline availability of multiple entries at once. Returns a list of booleans parallel
+/// to `paths`. Entries that don't exist are reported as unavailable.
+pub(crate) async fn availability(
+    state: &State,
+    handle: RepositoryHandle,
+    paths: Vec<Utf8PathBuf>,
+) -> Result<Vec<bool>, Error> {
+    let holder = state.repositories.get(handle)?;
+
+    future::try_join_all(paths.into_iter().map(|path| {
+        let repository = &holder.repository;
+
+        async move {
+            match repository.is_available_offline(path).await {
+                Ok(available) => Ok(available),
+                Err(ouisync_lib::Error::EntryNotFound) => Ok(false),
+                Err(error) => Err(error.into()),
+            }
+        }
+    }))
+    .await
+}
+
 /// Returns the syncing progress.
 pub(crate) async fn sync_progress(
     state: &State,
@@ -460,6 +1324,25 @@ pub(crate) async fn sync_progress(
         .await?)
 }
 
+pub(crate) async fn is_complete(state: &State, handle: RepositoryHandle) -> Result<bool, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .repository
+        .is_complete()
+        .await?)
+}
+
+/// Waits until the repository is fully synced.
+pub(crate) async fn on_complete(state: &State, handle: RepositoryHandle) -> Result<(), Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .repository
+        .on_complete()
+        .await?)
+}
+
 /// Create mirrored repository on the given server
 pub(crate) async fn create_mirror(
     state: &State,
@@ -569,6 +1452,21 @@ pub(crate) async fn stats(state: &State, handle: RepositoryHandle) -> Result<Sta
         .stats())
 }
 
+pub(crate) async fn message_stats(
+    state: &State,
+    handle: RepositoryHandle,
+) -> Result<MessageStats, Error> {
+    Ok(state
+        .repositories
+        .get(handle)?
+        .registration
+        .read()
+        .await
+        .as_ref()
+        .ok_or(RegistrationRequired)?
+        .message_stats())
+}
+
 /// Edit of a single metadata entry.
 #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub(crate) struct MetadataEdit {
@@ -584,6 +1482,36 @@ pub(crate) struct MetadataEdit {
     pub new: Option<String>,
 }
 
+/// Repository opened with `open_with_secrets`.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub(crate) struct OpenedRepository {
+    /// Handle of the opened repository.
+    pub repository: RepositoryHandle,
+    /// The access mode the repository was opened in.
+    pub access_mode: AccessMode,
+    /// Index of the secret that unlocked the access mode or `None` if none of them did.
+    pub secret_index: Option<u32>,
+}
+
+/// File with concurrent versions, as returned by `detect_conflict`.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub(crate) struct ConflictInfo {
+    /// Path of the file.
+    pub path: String,
+    /// The concurrent versions of the file.
+    pub versions: Vec<ConflictVersionInfo>,
+}
+
+/// Single version of a conflicting file.
+#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub(crate) struct ConflictVersionInfo {
+    /// Path under which this version can be opened.
+    pub path: String,
+    /// Id of the branch (author) of this version.
+    pub branch_id: PublicKey,
+    pub version_vector: VersionVector,
+}
+
 /// Registry of opened repositories.
 pub(crate) struct Repositories {
     inner: BlockingRwLock<Inner>,
@@ -638,7 +1566,7 @@ impl Repositories {
         let mut inner = self.inner.write().unwrap();
 
         let holder = inner.registry.remove(handle)?;
-        inner.index.remove(&holder.store_path);
+        inner.index.remove(&*holder.store_path.read().unwrap());
 
         self.change_tx.send(()).ok();
 
@@ -702,6 +1630,20 @@ impl RepositoryVacantEntry<'_> {
 
         handle
     }
+
+    /// Moves an existing repository from `src` to the path of this entry.
+    pub fn relocate(mut self, handle: RepositoryHandle, holder: &RepositoryHolder, src: &Path) {
+        let mut inner = self.inner.write().unwrap();
+
+        inner.index.remove(src);
+        inner
+            .index
+            .insert(self.store_path.clone(), IndexEntry::Existing(handle));
+        *holder.store_path.write().unwrap() = self.store_path.clone();
+
+        self.inserted = true;
+        self.change_tx.send(()).unwrap_or(());
+    }
 }
 
 impl Drop for RepositoryVacantEntry<'_> {
@@ -1,6 +1,117 @@
+use crate::trace::{Direction, Tracer};
 use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use ouisync_bridge::{protocol::Notification, transport::NotificationSender};
+use std::{pin::pin, sync::Arc};
+use tokio::select;
 
 /// Trait for asynchronously sending responses to the guest language.
 pub(crate) trait Sender: Unpin + Send + 'static {
     fn send(&self, msg: Bytes);
 }
+
+/// Sender that records every sent message with the given `Tracer` before forwarding it.
+pub(crate) struct TracingSender<T> {
+    inner: T,
+    tracer: Arc<Tracer>,
+}
+
+impl<T> TracingSender<T> {
+    pub fn new(inner: T, tracer: Arc<Tracer>) -> Self {
+        Self { inner, tracer }
+    }
+}
+
+impl<T> Sender for TracingSender<T>
+where
+    T: Sender,
+{
+    fn send(&self, msg: Bytes) {
+        self.tracer.record(Direction::Outbound, &msg);
+        self.inner.send(msg);
+    }
+}
+
+/// Forwards a notification to the client for every item of `events`, coalescing them with a
+/// latest-wins dirty flag: while a previously sent notification hasn't been drained by the client
+/// yet, further events only mark the subscription dirty and a single notification is sent once
+/// the client catches up. Intermediate notifications can be dropped this way but the last event is
+/// always followed by a notification.
+pub(crate) async fn send_coalesced<S, F>(
+    events: S,
+    id: u64,
+    make_notification: F,
+    notification_tx: NotificationSender,
+) where
+    S: Stream<Item = ()>,
+    F: Fn() -> Notification,
+{
+    let mut events = pin!(events);
+    let mut dirty = false;
+
+    loop {
+        if !dirty {
+            match events.next().await {
+                Some(()) => dirty = true,
+                None => break,
+            }
+
+            continue;
+        }
+
+        select! {
+            permit = notification_tx.reserve() => {
+                let Ok(permit) = permit else {
+                    break;
+                };
+
+                permit.send((id, make_notification()));
+                dirty = false;
+            }
+            event = events.next() => {
+                if event.is_none() {
+                    notification_tx.send((id, make_notification())).await.ok();
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn send_coalesced_notifications() {
+        let num_events = 10_000;
+        let state = Arc::new(AtomicU64::new(0));
+        let events = stream::iter(1..=num_events).map({
+            let state = state.clone();
+            move |value| state.store(value, Ordering::Relaxed)
+        });
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let task = tokio::spawn(send_coalesced(events, 1, || Notification::Repository, tx));
+
+        let mut received = 0;
+        let mut last_seen = 0;
+
+        while let Some((id, notification)) = rx.recv().await {
+            assert_eq!(id, 1);
+            assert_eq!(notification, Notification::Repository);
+
+            received += 1;
+            last_seen = state.load(Ordering::Relaxed);
+        }
+
+        task.await.unwrap();
+
+        assert!(received > 0);
+        assert!(received < num_events / 100, "received = {received}");
+        assert_eq!(last_seen, num_events);
+    }
+}
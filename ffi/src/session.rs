@@ -2,24 +2,27 @@ use crate::{
     error::{ErrorCode, ToErrorCode},
     handler::Handler,
     repository,
-    sender::Sender,
+    sender::{Sender, TracingSender},
     state::{State, TaskHandle},
+    trace::{Direction, Tracer},
     transport::{ClientSender, Server},
     utils,
 };
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use ouisync_bridge::{
     logger::{LogColor, LogFormat, Logger},
     protocol::Notification,
     transport::NotificationSender,
 };
+use ouisync_lib::SecretRuntimeId;
 use scoped_task::ScopedAbortHandle;
-use state_monitor::StateMonitor;
+use state_monitor::{MonitoredValue, StateMonitor};
 use std::{
     ffi::c_char,
     io,
     marker::PhantomData,
-    path::Path,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
     ptr,
     str::Utf8Error,
     sync::{Arc, Mutex, Weak},
@@ -32,22 +35,54 @@ use tokio::{runtime, time};
 pub struct Session {
     pub(crate) shared: Arc<Shared>,
     pub(crate) client_tx: ClientSender,
+    tracer: Arc<Tracer>,
     _server_abort_handle: ScopedAbortHandle,
 }
 
+impl Session {
+    fn start(shared: Arc<Shared>, sender: impl Sender) -> Self {
+        let tracer = Arc::new(Tracer::default());
+        let (server, client_tx) = Server::new(TracingSender::new(sender, tracer.clone()));
+
+        let _server_abort_handle = shared
+            .runtime
+            .spawn(server.run(Handler::new(shared.state.clone())))
+            .abort_handle()
+            .into();
+
+        Self {
+            shared,
+            client_tx,
+            tracer,
+            _server_abort_handle,
+        }
+    }
+
+    /// Sends a raw message from the client to this session.
+    pub(crate) fn send(&self, payload: BytesMut) {
+        self.tracer.record(Direction::Inbound, &payload);
+        self.client_tx.send(payload).ok();
+    }
+}
+
 /// State shared between multiple instances of the same session.
 pub(crate) struct Shared {
     pub(crate) runtime: runtime::Runtime,
     pub(crate) state: Arc<State>,
     _logger: Logger,
+    _worker_threads: MonitoredValue<usize>,
 }
 
 impl Shared {
-    fn new(
-        configs_path: &Path,
-        log_path: Option<&Path>,
+    unsafe fn new(
+        configs_path: *const c_char,
+        log_path: *const c_char,
         log_tag: String,
+        options: Options,
     ) -> Result<Arc<Self>, SessionError> {
+        let configs_path = Path::new(utils::ptr_to_str(configs_path)?);
+        let log_path = utils::ptr_to_maybe_str(log_path)?.map(Path::new);
+
         let root_monitor = StateMonitor::make_root();
 
         // Init logger
@@ -61,22 +96,56 @@ impl Shared {
         .map_err(SessionError::InitializeLogger)?;
 
         // Create runtime
+        let worker_threads = match options.worker_threads {
+            0 => thread::available_parallelism()
+                .map(NonZeroUsize::get)
+                .unwrap_or(1),
+            count => count.into(),
+        };
+
         let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(worker_threads)
             .enable_all()
             .build()
             .map_err(SessionError::InitializeRuntime)?;
         let _enter = runtime.enter(); // runtime context is needed for some of the following calls
 
-        let state = Arc::new(State::new(configs_path.to_owned(), root_monitor));
+        let worker_threads_value = root_monitor.make_value("worker_threads", worker_threads);
+        let state = Arc::new(State::new(
+            configs_path.to_owned(),
+            options.dht_contacts_dir,
+            root_monitor,
+            worker_threads,
+            options.runtime_id,
+            options.test_mode,
+        ));
+
+        if options.test_mode {
+            state.isolate_network();
+        }
 
         Ok(Arc::new(Self {
             runtime,
             state,
             _logger: logger,
+            _worker_threads: worker_threads_value,
         }))
     }
 }
 
+/// Options of a newly created session.
+#[derive(Default)]
+struct Options {
+    /// Runtime id of the session. Random if `None`.
+    runtime_id: Option<SecretRuntimeId>,
+    /// Whether the session connects only to explicitly added peers. See [State::isolate_network].
+    test_mode: bool,
+    /// Number of worker threads of the session runtime. Zero means one per CPU core.
+    worker_threads: u16,
+    /// Directory to store the DHT contacts in. `None` means the config directory.
+    dht_contacts_dir: Option<PathBuf>,
+}
+
 /// What type of session to create.
 ///
 /// `Shared` should be used by default. `Unique` is useful mostly for tests, to ensure test
@@ -123,6 +192,10 @@ pub enum SessionError {
     InvalidUtf8(#[from] Utf8Error),
     #[error("session has not yet been created or it's been already destroyed")]
     NoActiveSession,
+    #[error("invalid number of worker threads: {0} (max is {MAX_WORKER_THREADS})")]
+    InvalidWorkerThreads(u16),
+    #[error("failed to access trace file")]
+    Trace(#[source] io::Error),
 }
 
 #[repr(C)]
@@ -151,45 +224,74 @@ impl From<Result<Session, SessionError>> for SessionCreateResult {
 
 static SHARED: Mutex<Weak<Shared>> = Mutex::new(Weak::new());
 
+/// Max number of runtime worker threads that can be requested.
+const MAX_WORKER_THREADS: u16 = 256;
+
 pub(crate) unsafe fn create(
     kind: SessionKind,
     configs_path: *const c_char,
     log_path: *const c_char,
     log_tag: *const c_char,
+    worker_threads: u16,
+    dht_contacts_dir: *const c_char,
     sender: impl Sender,
 ) -> Result<Session, SessionError> {
-    let configs_path = Path::new(utils::ptr_to_str(configs_path)?);
-    let log_path = utils::ptr_to_maybe_str(log_path)?.map(Path::new);
     let log_tag = utils::ptr_to_str(log_tag)?.to_owned();
+    let dht_contacts_dir = utils::ptr_to_maybe_str(dht_contacts_dir)?.map(PathBuf::from);
+
+    if worker_threads > MAX_WORKER_THREADS {
+        return Err(SessionError::InvalidWorkerThreads(worker_threads));
+    }
+
+    let options = Options {
+        worker_threads,
+        dht_contacts_dir,
+        ..Options::default()
+    };
 
     let shared = match kind {
-        SessionKind::Unique => Shared::new(configs_path, log_path, log_tag)?,
+        SessionKind::Unique => Shared::new(configs_path, log_path, log_tag, options)?,
         SessionKind::Shared => {
             let mut guard = SHARED.lock().unwrap();
 
             if let Some(shared) = guard.upgrade() {
                 shared
             } else {
-                let shared = Shared::new(configs_path, log_path, log_tag)?;
+                let shared = Shared::new(configs_path, log_path, log_tag, options)?;
                 *guard = Arc::downgrade(&shared);
                 shared
             }
         }
     };
 
-    let (server, client_tx) = Server::new(sender);
-
-    let _server_abort_handle = shared
-        .runtime
-        .spawn(server.run(Handler::new(shared.state.clone())))
-        .abort_handle()
-        .into();
+    Ok(Session::start(shared, sender))
+}
 
-    Ok(Session {
-        shared,
-        client_tx,
-        _server_abort_handle,
-    })
+/// Creates a unique session for integration tests. The runtime id is derived from `seed` so it's
+/// the same in every run and the network is isolated (see [State::isolate_network]) so the
+/// session connects only to explicitly added peers.
+#[cfg(feature = "test-hooks")]
+pub(crate) unsafe fn create_for_test(
+    configs_path: *const c_char,
+    log_path: *const c_char,
+    seed: u64,
+    sender: impl Sender,
+) -> Result<Session, SessionError> {
+    use ouisync_lib::crypto::{sign::Keypair, Hashable};
+
+    let keypair = Keypair::from(&<[u8; 32]>::from(seed.hash()));
+    let shared = Shared::new(
+        configs_path,
+        log_path,
+        "ouisync-test".to_owned(),
+        Options {
+            runtime_id: Some(keypair.into()),
+            test_mode: true,
+            ..Options::default()
+        },
+    )?;
+
+    Ok(Session::start(shared, sender))
 }
 
 pub(crate) fn grab_shared(sender: impl Sender) -> Result<Session, SessionError> {
@@ -203,25 +305,14 @@ pub(crate) fn grab_shared(sender: impl Sender) -> Result<Session, SessionError>
         }
     };
 
-    let (server, client_tx) = Server::new(sender);
-
-    let _server_abort_handle = shared
-        .runtime
-        .spawn(server.run(Handler::new(shared.state.clone())))
-        .abort_handle()
-        .into();
-
-    Ok(Session {
-        shared,
-        client_tx,
-        _server_abort_handle,
-    })
+    Ok(Session::start(shared, sender))
 }
 
 pub(crate) fn close(session: Session, sender: impl Sender) {
     let Session {
         shared,
         client_tx: _,
+        tracer: _,
         _server_abort_handle,
     } = session;
 
@@ -245,6 +336,7 @@ pub(crate) fn close_blocking(session: Session) {
     let Session {
         shared,
         client_tx: _,
+        tracer: _,
         _server_abort_handle,
     } = session;
 
@@ -265,6 +357,41 @@ pub(crate) fn close_blocking(session: Session) {
         .ok();
 }
 
+/// Starts or stops (if `path` is null) tracing the messages exchanged between the client and
+/// `session` into the file at `path`. See the `trace` module for the file format.
+pub(crate) unsafe fn set_trace_path(
+    session: &Session,
+    path: *const c_char,
+) -> Result<(), SessionError> {
+    let path = utils::ptr_to_maybe_str(path)?.map(Path::new);
+
+    session
+        .tracer
+        .set_path(session.shared.runtime.handle(), path)
+        .map_err(SessionError::Trace)
+}
+
+/// Sends all the requests recorded in the trace file at `path` to `session`, in the order they
+/// were recorded. The responses are delivered to the client the same way as for regular
+/// requests.
+#[cfg(feature = "test-hooks")]
+pub(crate) unsafe fn replay(session: &Session, path: *const c_char) -> Result<(), SessionError> {
+    let path = Path::new(utils::ptr_to_str(path)?);
+    let frames = session
+        .shared
+        .runtime
+        .block_on(crate::trace::read(path))
+        .map_err(SessionError::Trace)?;
+
+    for frame in frames {
+        if frame.direction == Direction::Inbound {
+            session.send(BytesMut::from(&frame.payload[..]));
+        }
+    }
+
+    Ok(())
+}
+
 /// Subscribe to changes in repository list
 pub(crate) fn subscribe(state: &State, notification_tx: &NotificationSender) -> TaskHandle {
     let mut on_repository_list_changed = state.repositories.subscribe();
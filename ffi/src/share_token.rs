@@ -1,5 +1,5 @@
 use crate::{error::Error, state::State};
-use ouisync_lib::{self, ShareToken};
+use ouisync_lib::{self, AccessSecrets, ShareToken};
 
 /// Returns the access mode of the given share token.
 pub(crate) fn mode(token: ShareToken) -> u8 {
@@ -16,6 +16,19 @@ pub(crate) fn suggested_name(token: ShareToken) -> String {
     token.suggested_name().to_owned()
 }
 
+/// Decodes a token in the compact format and returns it in the regular format.
+pub(crate) fn from_compact(input: &str) -> Result<String, Error> {
+    let token = ShareToken::from_compact(input).map_err(ouisync_lib::Error::from)?;
+    Ok(token.to_string())
+}
+
+/// Converts a recovery string (see `repository_export_access_secrets`) into a share token which
+/// can then be passed to `repository_create` to create a new replica of the repository.
+pub(crate) fn from_recovery(input: &str) -> Result<String, Error> {
+    let secrets = AccessSecrets::from_recovery_string(input).map_err(ouisync_lib::Error::from)?;
+    Ok(ShareToken::from(secrets).to_string())
+}
+
 /// Check if the repository is mirrored on the given server.
 pub(crate) async fn mirror_exists(
     state: &State,
@@ -25,3 +38,52 @@ pub(crate) async fn mirror_exists(
     let config = state.get_remote_client_config().await?;
     Ok(ouisync_bridge::repository::mirror_exists(token.id(), config, host).await?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ouisync_bridge::{config::ConfigStore, repository};
+    use ouisync_lib::{AccessMode, SetLocalSecret};
+    use state_monitor::StateMonitor;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn create_replica_from_recovery_string() {
+        let dir = TempDir::new().unwrap();
+        let config = ConfigStore::new(dir.path().join("config"));
+        let monitor = StateMonitor::make_root();
+
+        let local_secret = SetLocalSecret::Password("mellon".to_string().into());
+        let origin = repository::create(
+            dir.path().join("origin.ouisyncdb"),
+            None,
+            Some(local_secret.clone()),
+            None,
+            &config,
+            &monitor,
+        )
+        .await
+        .unwrap();
+        let recovery = origin
+            .export_access_secrets(local_secret.into())
+            .await
+            .unwrap();
+
+        let token: ShareToken = from_recovery(&recovery).unwrap().parse().unwrap();
+        let replica = repository::create(
+            dir.path().join("replica.ouisyncdb"),
+            None,
+            None,
+            Some(token),
+            &config,
+            &monitor,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(replica.access_mode(), AccessMode::Write);
+        assert_eq!(replica.secrets().id(), origin.secrets().id());
+
+        assert!(from_recovery("not a recovery string").is_err());
+    }
+}
@@ -1,11 +1,12 @@
 use crate::{
-    file::FileHolder,
+    batch::BatchHolder,
+    file::{CopyHandle, FileHolder},
     mounter::Mounter,
     registry::{Handle, SharedRegistry},
     repository::Repositories,
 };
 use ouisync_bridge::{config::ConfigStore, transport};
-use ouisync_lib::Network;
+use ouisync_lib::{Network, SecretRuntimeId};
 use scoped_task::ScopedJoinHandle;
 use state_monitor::StateMonitor;
 use std::{
@@ -15,9 +16,13 @@ use std::{
     sync::Arc,
 };
 use tokio::sync::{oneshot, OnceCell};
+use tokio_util::sync::CancellationToken;
 
 pub(crate) struct State {
+    pub batches: SharedRegistry<Arc<BatchHolder>>,
     pub config: ConfigStore,
+    /// Cancellation tokens of the running copies into raw file descriptors.
+    pub copies: SharedRegistry<CancellationToken>,
     pub files: SharedRegistry<Arc<FileHolder>>,
     pub mounter: Mounter,
     pub network: Network,
@@ -25,23 +30,40 @@ pub(crate) struct State {
     pub repositories: Repositories,
     pub repos_monitor: StateMonitor,
     pub root_monitor: StateMonitor,
+    /// Number of worker threads of the runtime this state runs on.
+    pub worker_threads: usize,
+    /// Whether this is a test session whose network is isolated. See [Self::isolate_network].
+    pub test_mode: bool,
     tasks: SharedRegistry<ScopedJoinHandle<()>>,
 }
 
 impl State {
-    pub fn new(configs_path: PathBuf, root_monitor: StateMonitor) -> Self {
+    pub fn new(
+        configs_path: PathBuf,
+        dht_contacts_dir: Option<PathBuf>,
+        root_monitor: StateMonitor,
+        worker_threads: usize,
+        runtime_id: Option<SecretRuntimeId>,
+        test_mode: bool,
+    ) -> Self {
         let config = ConfigStore::new(configs_path);
+        let config = match dht_contacts_dir {
+            Some(dir) => config.with_dht_contacts_dir(dir),
+            None => config,
+        };
 
         let network = Network::new(
             root_monitor.make_child("Network"),
             Some(config.dht_contacts_store()),
-            None,
+            runtime_id,
         );
 
         let repos_monitor = root_monitor.make_child("Repositories");
 
         Self {
+            batches: SharedRegistry::new(),
             config,
+            copies: SharedRegistry::new(),
             files: SharedRegistry::new(),
             mounter: Mounter::new(),
             network,
@@ -49,10 +71,22 @@ impl State {
             repositories: Repositories::new(),
             repos_monitor,
             root_monitor,
+            worker_threads,
+            test_mode,
             tasks: SharedRegistry::new(),
         }
     }
 
+    /// Disables everything that makes the network connect to peers other than the explicitly
+    /// added ones (local discovery, port forwarding, DHT and STUN), so test sessions are isolated
+    /// from each other and from the outside world.
+    pub fn isolate_network(&self) {
+        self.network.set_local_discovery_enabled(false);
+        self.network.set_port_forwarding_enabled(false);
+        self.network.set_dht_enabled(false);
+        self.network.set_stun_servers(Vec::new());
+    }
+
     pub async fn get_remote_client_config(&self) -> io::Result<Arc<rustls::ClientConfig>> {
         self.remote_client_config
             .get_or_try_init(|| make_remote_client_config(self.config.dir()))
@@ -87,6 +121,13 @@ impl State {
     pub fn remove_task(&self, handle: TaskHandle) {
         self.tasks.remove(handle);
     }
+
+    /// Cancel a running copy. Does nothing if the copy has already finished.
+    pub fn cancel_copy(&self, handle: CopyHandle) {
+        if let Some(token) = self.copies.remove(handle) {
+            token.cancel();
+        }
+    }
 }
 
 pub(crate) type TaskHandle = Handle<ScopedJoinHandle<()>>;
@@ -0,0 +1,388 @@
+//! Capturing of the raw messages exchanged between the client and a session, for debugging.
+//!
+//! The trace file is a sequence of frames. Each frame consists of the direction (one byte, see
+//! `Direction`), the timestamp (u64, microseconds since the unix epoch), the payload length (u32)
+//! and the payload itself (the raw msgpack message). Integers are big-endian.
+//!
+//! Messages carrying secrets (local secrets, share tokens, recovery strings, credentials, ...) as
+//! well as the responses to them are redacted: their body is replaced with a string naming the
+//! request, only the message id is kept intact. Such frames can't be replayed.
+
+use crate::protocol::Request;
+use bytes::{BufMut, Bytes, BytesMut};
+use deadlock::BlockingMutex;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use std::{
+    collections::HashSet,
+    fs::OpenOptions,
+    io,
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{fs, io::AsyncWriteExt, runtime, sync::mpsc};
+
+/// Maximum number of frames waiting to be written. Frames recorded while the queue is full are
+/// dropped so that tracing never blocks the caller.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Maximum size of the trace file. Once reached, no more frames are written to it.
+const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+const HEADER_SIZE: usize = 1 + 8 + 4;
+
+/// Size of the message id which precedes the message body.
+const ID_SIZE: usize = 8;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub(crate) enum Direction {
+    /// Message sent by the client to the session (request).
+    Inbound = 0,
+    /// Message sent by the session to the client (response or notification).
+    Outbound = 1,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub(crate) struct Frame {
+    pub direction: Direction,
+    /// Microseconds since the unix epoch.
+    pub timestamp: u64,
+    pub payload: Bytes,
+}
+
+impl Frame {
+    fn new(direction: Direction, payload: Bytes) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_micros() as u64)
+            .unwrap_or(0);
+
+        Self {
+            direction,
+            timestamp,
+            payload,
+        }
+    }
+
+    fn encode(&self, dst: &mut BytesMut) {
+        dst.reserve(HEADER_SIZE + self.payload.len());
+        dst.put_u8(self.direction.into());
+        dst.put_u64(self.timestamp);
+        dst.put_u32(self.payload.len() as u32);
+        dst.put_slice(&self.payload);
+    }
+
+    /// Decodes one frame from the front of `src`. Returns `None` if `src` is empty and
+    /// `InvalidData` if it doesn't start with a complete, valid frame.
+    #[cfg(any(test, feature = "test-hooks"))]
+    fn decode(src: &mut Bytes) -> io::Result<Option<Self>> {
+        use bytes::Buf;
+
+        if src.is_empty() {
+            return Ok(None);
+        }
+
+        if src.len() < HEADER_SIZE {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let direction = Direction::try_from(src.get_u8())
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+        let timestamp = src.get_u64();
+        let len = src.get_u32() as usize;
+
+        if src.len() < len {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        Ok(Some(Self {
+            direction,
+            timestamp,
+            payload: src.split_to(len),
+        }))
+    }
+}
+
+/// Records the messages of a session into a trace file. Disabled by default.
+#[derive(Default)]
+pub(crate) struct Tracer {
+    // Checked before taking the lock so that a disabled tracer costs only an atomic load per
+    // message.
+    enabled: AtomicBool,
+    state: BlockingMutex<State>,
+    dropped: AtomicU64,
+}
+
+#[derive(Default)]
+struct State {
+    tx: Option<mpsc::Sender<Frame>>,
+    redactor: Redactor,
+}
+
+impl Tracer {
+    /// Starts appending the frames to the file at `path` (creating it if it doesn't exist) or
+    /// stops tracing if `path` is `None`.
+    pub fn set_path(&self, runtime: &runtime::Handle, path: Option<&Path>) -> io::Result<()> {
+        let Some(path) = path else {
+            self.enabled.store(false, Ordering::Release);
+            *self.state.lock().unwrap() = State::default();
+            return Ok(());
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        let file = fs::File::from_std(file);
+
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        runtime.spawn(write(file, size, rx));
+
+        *self.state.lock().unwrap() = State {
+            tx: Some(tx),
+            redactor: Redactor::default(),
+        };
+        self.dropped.store(0, Ordering::Relaxed);
+        self.enabled.store(true, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Records a message, redacting it if it carries secrets. Never blocks. Does nothing if
+    /// tracing is disabled.
+    pub fn record(&self, direction: Direction, payload: &[u8]) {
+        if !self.enabled.load(Ordering::Acquire) {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let State { tx, redactor } = &mut *state;
+
+        let Some(tx) = tx.as_ref() else {
+            return;
+        };
+
+        let payload = redactor.redact(direction, payload);
+
+        if tx.try_send(Frame::new(direction, payload)).is_err()
+            && self.dropped.fetch_add(1, Ordering::Relaxed) == 0
+        {
+            tracing::warn!("trace queue full or closed, dropping frames");
+        }
+    }
+}
+
+/// Strips secrets from the traced messages.
+#[derive(Default)]
+struct Redactor {
+    // Ids of the sensitive requests whose responses haven't been recorded yet.
+    pending: HashSet<u64>,
+}
+
+impl Redactor {
+    fn redact(&mut self, direction: Direction, payload: &[u8]) -> Bytes {
+        let Some((id, body)) = split_id(payload) else {
+            return Bytes::copy_from_slice(payload);
+        };
+
+        let name = match direction {
+            Direction::Inbound => {
+                let Some(name) = rmp_serde::from_slice(body)
+                    .ok()
+                    .as_ref()
+                    .and_then(sensitive_request_name)
+                else {
+                    return Bytes::copy_from_slice(payload);
+                };
+
+                self.pending.insert(id);
+                name
+            }
+            Direction::Outbound => {
+                // Only the first message with the given id is the response, any following ones
+                // are subscription notifications which don't carry secrets.
+                if !self.pending.remove(&id) {
+                    return Bytes::copy_from_slice(payload);
+                }
+
+                "response"
+            }
+        };
+
+        let mut buffer = id.to_be_bytes().to_vec();
+        // unwrap is OK because encoding a string into a vec can't fail.
+        rmp_serde::encode::write(&mut buffer, &format!("<redacted {name}>")).unwrap();
+
+        buffer.into()
+    }
+}
+
+fn split_id(payload: &[u8]) -> Option<(u64, &[u8])> {
+    let id = payload.get(..ID_SIZE)?;
+    let id = u64::from_be_bytes(id.try_into().unwrap());
+
+    Some((id, &payload[ID_SIZE..]))
+}
+
+/// Returns the name of the request if it or its response contains secrets, `None` otherwise.
+fn sensitive_request_name(request: &Request) -> Option<&'static str> {
+    let name = match request {
+        Request::RepositoryCreate { .. } => "repository_create",
+        Request::RepositoryOpen { .. } => "repository_open",
+        Request::RepositoryOpenWithSecrets { .. } => "repository_open_with_secrets",
+        Request::RepositorySetAccess { .. } => "repository_set_access",
+        Request::RepositoryCredentials(_) => "repository_credentials",
+        Request::RepositorySetCredentials { .. } => "repository_set_credentials",
+        Request::RepositorySetAccessMode { .. } => "repository_set_access_mode",
+        Request::RepositoryUnlock { .. } => "repository_unlock",
+        Request::RepositoryVerifyPassword { .. } => "repository_verify_password",
+        Request::RepositoryExportAccessSecrets { .. } => "repository_export_access_secrets",
+        Request::RepositoryImportAccessSecrets { .. } => "repository_import_access_secrets",
+        Request::RepositoryRotateKeysSubscribe { .. } => "repository_rotate_keys_subscribe",
+        Request::RepositoryCreateShareToken { .. } => "repository_create_share_token",
+        Request::ShareTokenMode(_) => "share_token_mode",
+        Request::ShareTokenInfoHash(_) => "share_token_info_hash",
+        Request::ShareTokenSuggestedName(_) => "share_token_suggested_name",
+        Request::ShareTokenNormalize(_) => "share_token_normalize",
+        Request::ShareTokenToCompact(_) => "share_token_to_compact",
+        Request::ShareTokenFromCompact(_) => "share_token_from_compact",
+        Request::ShareTokenFromRecovery(_) => "share_token_from_recovery",
+        Request::ShareTokenMirrorExists { .. } => "share_token_mirror_exists",
+        Request::DeriveSecretKey { .. } => "derive_secret_key",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+async fn write(mut file: fs::File, mut size: u64, mut rx: mpsc::Receiver<Frame>) {
+    let mut buffer = BytesMut::new();
+
+    while let Some(frame) = rx.recv().await {
+        buffer.clear();
+        frame.encode(&mut buffer);
+
+        if size + buffer.len() as u64 > MAX_FILE_SIZE {
+            tracing::warn!("trace file size limit reached, tracing stopped");
+            break;
+        }
+
+        if let Err(error) = file.write_all(&buffer).await {
+            tracing::error!(?error, "failed to write trace file, tracing stopped");
+            break;
+        }
+
+        size += buffer.len() as u64;
+    }
+
+    file.flush().await.ok();
+}
+
+/// Reads all frames from the trace file at `path`.
+#[cfg(feature = "test-hooks")]
+pub(crate) async fn read(path: &Path) -> io::Result<Vec<Frame>> {
+    let mut content = Bytes::from(fs::read(path).await?);
+    let mut frames = Vec::new();
+
+    while let Some(frame) = Frame::decode(&mut content)? {
+        frames.push(frame);
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::Handle;
+    use ouisync_lib::{crypto::Password, LocalSecret};
+
+    #[test]
+    fn encode_decode() {
+        let frames = [
+            Frame::new(Direction::Inbound, Bytes::from_static(b"request")),
+            Frame::new(Direction::Outbound, Bytes::new()),
+            Frame::new(Direction::Outbound, Bytes::from_static(b"response")),
+        ];
+
+        let mut buffer = BytesMut::new();
+        for frame in &frames {
+            frame.encode(&mut buffer);
+        }
+
+        let mut buffer = buffer.freeze();
+        for frame in &frames {
+            assert_eq!(Frame::decode(&mut buffer).unwrap().as_ref(), Some(frame));
+        }
+
+        assert_eq!(Frame::decode(&mut buffer).unwrap(), None);
+    }
+
+    #[test]
+    fn decode_truncated() {
+        let mut buffer = BytesMut::new();
+        Frame::new(Direction::Inbound, Bytes::from_static(b"request")).encode(&mut buffer);
+        buffer.truncate(buffer.len() - 1);
+
+        assert!(Frame::decode(&mut buffer.freeze()).is_err());
+    }
+
+    #[test]
+    fn redact_sensitive_request_and_response() {
+        let mut redactor = Redactor::default();
+
+        let request = Request::RepositoryUnlock {
+            repository: Handle::from_id(1),
+            secret: LocalSecret::Password(Password::from("supersecret".to_owned())),
+        };
+        let payload = encode_message(7, &request);
+
+        let redacted = redactor.redact(Direction::Inbound, &payload);
+        assert_eq!(
+            redacted,
+            encode_message(7, &"<redacted repository_unlock>".to_owned())
+        );
+        assert!(!contains(&redacted, b"supersecret"));
+
+        // Response to the sensitive request is redacted too.
+        let response = encode_message(7, &"secret response".to_owned());
+        let redacted = redactor.redact(Direction::Outbound, &response);
+        assert_eq!(
+            redacted,
+            encode_message(7, &"<redacted response>".to_owned())
+        );
+
+        // ...but only the first one, the subsequent messages are notifications.
+        let notification = encode_message(7, &"notification".to_owned());
+        assert_eq!(
+            redactor.redact(Direction::Outbound, &notification),
+            notification
+        );
+    }
+
+    #[test]
+    fn redact_keeps_other_messages() {
+        let mut redactor = Redactor::default();
+
+        let request = encode_message(1, &Request::RepositoryClose(Handle::from_id(1)));
+        assert_eq!(redactor.redact(Direction::Inbound, &request), request);
+
+        let response = encode_message(1, &"response".to_owned());
+        assert_eq!(redactor.redact(Direction::Outbound, &response), response);
+
+        // Malformed messages are recorded as is.
+        let malformed = Bytes::from_static(b"abc");
+        assert_eq!(redactor.redact(Direction::Inbound, &malformed), malformed);
+    }
+
+    fn encode_message<T: serde::Serialize>(id: u64, body: &T) -> Bytes {
+        let mut buffer = id.to_be_bytes().to_vec();
+        rmp_serde::encode::write(&mut buffer, body).unwrap();
+        buffer.into()
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+}
@@ -0,0 +1,829 @@
+//! Bulk copying of whole directory trees between the host filesystem and a repository.
+
+use crate::{
+    error::{Error, ErrorCode},
+    repository::RepositoryHandle,
+    state::{State, TaskHandle},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use ouisync_bridge::{
+    protocol::{CollisionPolicy, Notification, TransferEvent},
+    transport::NotificationSender,
+};
+use ouisync_lib::{EntryType, File, Repository, StoreError, BLOCK_SIZE};
+use std::{
+    collections::HashMap,
+    fs::FileType,
+    io,
+    path::{Component, Path, PathBuf},
+};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+/// Recursively imports the file or directory at `src` on the host filesystem into the repository
+/// at `dst`. Existing files are overwritten. The progress is reported as `TransferEvent`
+/// notifications ending with either `Done` or `Aborted`. Entries that can't be read are reported
+/// as failed and skipped. Symlinks to files are followed, other symlinks are skipped because they
+/// can't be represented in a repository. Cancelling the subscription cancels the import (entries
+/// imported so far are kept).
+pub(crate) fn import(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+    src: PathBuf,
+    dst: Utf8PathBuf,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let mut reporter = Reporter::new(id, notification_tx);
+
+        let last = match import_tree(&repository, &src, &dst, &mut reporter).await {
+            Ok(()) => reporter.done(),
+            Err(error) => TransferEvent::Aborted(error.to_string()),
+        };
+
+        reporter.send(last).await;
+    });
+
+    Ok(handle)
+}
+
+async fn import_tree(
+    repo: &Repository,
+    src: &Path,
+    dst: &Utf8Path,
+    reporter: &mut Reporter,
+) -> Result<(), Error> {
+    // The root itself is followed even if it's a symlink.
+    let metadata = fs::metadata(src).await?;
+
+    if !metadata.is_dir() {
+        reporter.total = metadata.len();
+
+        let len = import_file(repo, src, dst).await?;
+        let path = dst.file_name().unwrap_or_default().to_owned();
+        reporter.copied(path, len).await;
+
+        return Ok(());
+    }
+
+    let entries = scan_host(src, reporter).await?;
+
+    reporter.total = entries
+        .iter()
+        .map(|entry| match entry.kind {
+            EntryKind::File(len) => len,
+            EntryKind::Directory => 0,
+        })
+        .sum();
+
+    repo.create_directory(dst).await?;
+
+    for entry in entries {
+        let dst = dst.join(&entry.path);
+        let result = match entry.kind {
+            EntryKind::Directory => repo
+                .create_directory(&dst)
+                .await
+                .map(|_| 0)
+                .map_err(Into::into),
+            EntryKind::File(_) => import_file(repo, &entry.src, &dst).await,
+        };
+
+        match result {
+            Ok(len) => reporter.copied(entry.path.into_string(), len).await,
+            Err(error) => reporter.failed(entry.path.into_string(), error).await,
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies a single file from the host filesystem into the repository. Returns the number of bytes
+/// copied.
+async fn import_file(repo: &Repository, src: &Path, dst: &Utf8Path) -> Result<u64, Error> {
+    let mut src = fs::File::open(src).await?;
+    let mut dst = create_or_truncate(repo, dst).await?;
+    let mut buffer = vec![0; BLOCK_SIZE];
+    let mut len = 0;
+
+    loop {
+        let n = src.read(&mut buffer).await?;
+
+        if n == 0 {
+            break;
+        }
+
+        dst.write_all(&buffer[..n]).await?;
+        len += n as u64;
+    }
+
+    dst.flush().await?;
+
+    Ok(len)
+}
+
+async fn create_or_truncate(repo: &Repository, path: &Utf8Path) -> Result<File, Error> {
+    match repo.create_file(path).await {
+        Ok(file) => Ok(file),
+        Err(ouisync_lib::Error::EntryExists) => {
+            let mut file = repo.open_file(path).await?;
+            file.fork(repo.local_branch()?).await?;
+            file.truncate(0)?;
+            Ok(file)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Entry on the host filesystem to be imported.
+struct HostEntry {
+    /// Path on the host filesystem.
+    src: PathBuf,
+    /// Path relative to the import root.
+    path: Utf8PathBuf,
+    kind: EntryKind,
+}
+
+enum EntryKind {
+    File(u64),
+    Directory,
+}
+
+/// Collects all the entries in the host directory `root` to import, each directory preceding its
+/// content. Entries that can't be imported are reported and omitted.
+async fn scan_host(root: &Path, reporter: &mut Reporter) -> Result<Vec<HostEntry>, Error> {
+    let mut entries = Vec::new();
+    let mut stack = vec![(root.to_owned(), Utf8PathBuf::new())];
+
+    while let Some((src_dir, dir_path)) = stack.pop() {
+        let mut read_dir = match fs::read_dir(&src_dir).await {
+            Ok(read_dir) => read_dir,
+            Err(error) if dir_path.as_str().is_empty() => return Err(error.into()),
+            Err(error) => {
+                reporter.failed(dir_path.into_string(), error.into()).await;
+                continue;
+            }
+        };
+
+        loop {
+            let host_entry = match read_dir.next_entry().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(error) => {
+                    reporter.failed(dir_path.to_string(), error.into()).await;
+                    break;
+                }
+            };
+
+            let src = host_entry.path();
+            let path = match host_entry.file_name().into_string() {
+                Ok(name) => dir_path.join(name),
+                Err(name) => {
+                    let path = dir_path.join(name.to_string_lossy().as_ref());
+                    reporter
+                        .skipped(path.into_string(), "file name is not valid UTF-8")
+                        .await;
+                    continue;
+                }
+            };
+
+            let file_type = match host_entry.file_type().await {
+                Ok(file_type) => file_type,
+                Err(error) => {
+                    reporter.failed(path.into_string(), error.into()).await;
+                    continue;
+                }
+            };
+
+            let kind = match classify(&src, file_type).await {
+                Ok(Ok(kind)) => kind,
+                Ok(Err(reason)) => {
+                    reporter.skipped(path.into_string(), reason).await;
+                    continue;
+                }
+                Err(error) => {
+                    reporter.failed(path.into_string(), error).await;
+                    continue;
+                }
+            };
+
+            if let EntryKind::Directory = kind {
+                stack.push((src.clone(), path.clone()));
+            }
+
+            entries.push(HostEntry { src, path, kind });
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Determines how to import the host entry at `path`. Returns the reason if it's to be skipped.
+async fn classify(
+    path: &Path,
+    file_type: FileType,
+) -> Result<Result<EntryKind, &'static str>, Error> {
+    if file_type.is_dir() {
+        return Ok(Ok(EntryKind::Directory));
+    }
+
+    if file_type.is_symlink() {
+        // Follow the symlink but only to a file. Following symlinks to directories could lead to
+        // infinite recursion.
+        let metadata = fs::metadata(path).await?;
+
+        return if metadata.is_file() {
+            Ok(Ok(EntryKind::File(metadata.len())))
+        } else {
+            Ok(Err("symlinks to directories are not supported"))
+        };
+    }
+
+    if file_type.is_file() {
+        return Ok(Ok(EntryKind::File(fs::metadata(path).await?.len())));
+    }
+
+    Ok(Err("unsupported file type"))
+}
+
+/// Recursively exports the file or directory at `src` in the repository to `dst` on the host
+/// filesystem, creating directories as needed. Collisions with existing host entries are resolved
+/// according to `policy`. The progress is reported the same way as in `import`. Files with missing
+/// blocks (not synced yet) are reported as failed instead of being written out truncated.
+/// Cancelling the subscription cancels the export (entries exported so far are kept).
+pub(crate) fn export(
+    state: &State,
+    notification_tx: &NotificationSender,
+    repository_handle: RepositoryHandle,
+    src: Utf8PathBuf,
+    dst: PathBuf,
+    policy: CollisionPolicy,
+) -> Result<TaskHandle, Error> {
+    let repository = state
+        .repositories
+        .get(repository_handle)?
+        .repository
+        .clone();
+    let notification_tx = notification_tx.clone();
+
+    let handle = state.spawn_task(|id| async move {
+        let mut reporter = Reporter::new(id, notification_tx);
+
+        let last = match export_tree(&repository, &src, &dst, policy, &mut reporter).await {
+            Ok(()) => reporter.done(),
+            Err(error) => TransferEvent::Aborted(error.to_string()),
+        };
+
+        reporter.send(last).await;
+    });
+
+    Ok(handle)
+}
+
+const ALREADY_EXISTS: &str = "entry with the same name already exists";
+
+async fn export_tree(
+    repo: &Repository,
+    src: &Utf8Path,
+    dst: &Path,
+    policy: CollisionPolicy,
+    reporter: &mut Reporter,
+) -> Result<(), Error> {
+    if repo.lookup_type(src).await? == EntryType::File {
+        let file = repo.open_file(src).await?;
+        let path = src.file_name().unwrap_or_default().to_owned();
+
+        reporter.total = file.len();
+
+        match resolve_collision(dst.to_owned(), false, policy).await? {
+            Some(dst) => {
+                let len = export_file(file, &dst).await?;
+                reporter.copied(path, len).await;
+            }
+            None => reporter.skipped(path, ALREADY_EXISTS).await,
+        }
+
+        return Ok(());
+    }
+
+    let entries = scan_repo(repo, src, reporter).await?;
+
+    reporter.total = entries
+        .iter()
+        .map(|entry| match entry.kind {
+            EntryKind::File(len) => len,
+            EntryKind::Directory => 0,
+        })
+        .sum();
+
+    fs::create_dir_all(dst).await?;
+
+    // Host directories the repository directories were exported to, or `None` for those that
+    // weren't exported.
+    let mut dirs = HashMap::from([(Utf8PathBuf::new(), Some(dst.to_owned()))]);
+
+    for entry in entries {
+        let is_dir = matches!(entry.kind, EntryKind::Directory);
+
+        let result = match entry
+            .path
+            .parent()
+            .and_then(|parent| dirs.get(parent))
+            .and_then(Option::as_ref)
+        {
+            Some(host_parent) => match host_target(host_parent, &entry.path) {
+                Ok(target) => {
+                    export_entry(repo, &src.join(&entry.path), target, is_dir, policy).await
+                }
+                Err(error) => Err(error),
+            },
+            None => Err(Error {
+                code: ErrorCode::Other,
+                message: "parent directory was not exported".to_owned(),
+            }),
+        };
+
+        let path = entry.path.to_string();
+
+        let host_path = match result {
+            Ok(Some((host_path, len))) => {
+                reporter.copied(path, len).await;
+                Some(host_path)
+            }
+            Ok(None) => {
+                reporter.skipped(path, ALREADY_EXISTS).await;
+                None
+            }
+            Err(error) => {
+                reporter.failed(path, error).await;
+                None
+            }
+        };
+
+        if is_dir {
+            dirs.insert(entry.path, host_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the host path to export the repository entry at `path` to, given the host directory
+/// its parent was exported to. The entry names can come from remote replicas so any path
+/// component other than a plain name (e.g. `..`, root or a drive prefix) is rejected to prevent
+/// writing outside of the export directory.
+fn host_target(host_parent: &Path, path: &Utf8Path) -> Result<PathBuf, Error> {
+    let mut name = None;
+
+    for component in Path::new(path.as_str()).components() {
+        match component {
+            Component::Normal(component) => name = Some(component),
+            Component::Prefix(_)
+            | Component::RootDir
+            | Component::CurDir
+            | Component::ParentDir => {
+                name = None;
+                break;
+            }
+        }
+    }
+
+    name.map(|name| host_parent.join(name))
+        .ok_or_else(|| Error {
+            code: ErrorCode::MalformedData,
+            message: format!("invalid entry name: {path:?}"),
+        })
+}
+
+/// Exports a single entry to `target`, or to a different path if required by the collision
+/// policy. Returns the host path the entry was exported to and the number of bytes written, or
+/// `None` if it was skipped.
+async fn export_entry(
+    repo: &Repository,
+    src: &Utf8Path,
+    target: PathBuf,
+    is_dir: bool,
+    policy: CollisionPolicy,
+) -> Result<Option<(PathBuf, u64)>, Error> {
+    let Some(target) = resolve_collision(target, is_dir, policy).await? else {
+        return Ok(None);
+    };
+
+    let len = if is_dir {
+        fs::create_dir_all(&target).await?;
+        0
+    } else {
+        export_file(repo.open_file(src).await?, &target).await?
+    };
+
+    Ok(Some((target, len)))
+}
+
+/// Writes the content of `file` to the host file at `dst`. Returns the number of bytes written.
+async fn export_file(mut file: File, dst: &Path) -> Result<u64, Error> {
+    // Write into a temporary file first so a failed export doesn't leave a truncated file behind
+    // nor destroys the file being overwritten.
+    let name = dst.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dst.with_file_name(format!(".{name}.ouisync-export"));
+    let len = file.len();
+
+    let result: Result<_, Error> = async {
+        let mut writer = fs::File::create(&tmp).await?;
+
+        file.copy_to_writer(&mut writer)
+            .await
+            .map_err(|error| match error {
+                ouisync_lib::Error::Store(StoreError::BlockNotFound) => Error {
+                    code: ErrorCode::Store,
+                    message: "file is incomplete (some of its blocks are not synced yet)"
+                        .to_owned(),
+                },
+                error => error.into(),
+            })?;
+
+        writer.flush().await?;
+        fs::rename(&tmp, dst).await?;
+
+        Ok(len)
+    }
+    .await;
+
+    if result.is_err() {
+        fs::remove_file(&tmp).await.ok();
+    }
+
+    result
+}
+
+/// Returns the path to export an entry to given that it would be exported to `target` if there
+/// was no collision, or `None` if it's to be skipped. Directories are always merged with existing
+/// directories.
+async fn resolve_collision(
+    target: PathBuf,
+    is_dir: bool,
+    policy: CollisionPolicy,
+) -> Result<Option<PathBuf>, Error> {
+    let metadata = match fs::symlink_metadata(&target).await {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Some(target)),
+        Err(error) => return Err(error.into()),
+    };
+
+    if is_dir && metadata.is_dir() {
+        return Ok(Some(target));
+    }
+
+    match policy {
+        CollisionPolicy::Skip => Ok(None),
+        CollisionPolicy::Overwrite => {
+            // Never replace a whole directory.
+            if metadata.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "directory with the same name already exists",
+                )
+                .into());
+            }
+
+            // Files are replaced in `export_file`, only a file in place of a directory needs to
+            // be removed.
+            if is_dir {
+                fs::remove_file(&target).await?;
+            }
+
+            Ok(Some(target))
+        }
+        CollisionPolicy::Rename => {
+            let stem = target.file_stem().unwrap_or_default().to_string_lossy();
+            let extension = target.extension().map(|ext| ext.to_string_lossy());
+
+            for n in 1.. {
+                let name = match &extension {
+                    Some(extension) => format!("{stem} ({n}).{extension}"),
+                    None => format!("{stem} ({n})"),
+                };
+                let candidate = target.with_file_name(name);
+
+                match fs::symlink_metadata(&candidate).await {
+                    Ok(_) => continue,
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                        return Ok(Some(candidate))
+                    }
+                    Err(error) => return Err(error.into()),
+                }
+            }
+
+            unreachable!()
+        }
+    }
+}
+
+/// Entry in the repository to be exported.
+struct RepoEntry {
+    /// Path relative to the export root.
+    path: Utf8PathBuf,
+    kind: EntryKind,
+}
+
+/// Collects all the entries in the repository directory `root` to export, each directory
+/// preceding its content. Conflicting files are exported under their unique names.
+async fn scan_repo(
+    repo: &Repository,
+    root: &Utf8Path,
+    reporter: &mut Reporter,
+) -> Result<Vec<RepoEntry>, Error> {
+    let mut entries = Vec::new();
+    let mut stack = vec![(root.to_owned(), Utf8PathBuf::new())];
+
+    while let Some((src_dir, dir_path)) = stack.pop() {
+        let dir = match repo.open_directory(&src_dir).await {
+            Ok(dir) => dir,
+            Err(error) if dir_path.as_str().is_empty() => return Err(error.into()),
+            Err(error) => {
+                reporter.failed(dir_path.into_string(), error.into()).await;
+                continue;
+            }
+        };
+
+        for entry in dir.entries() {
+            let name = entry.unique_name();
+            let path = dir_path.join(name.as_ref());
+
+            let kind = match entry.entry_type() {
+                EntryType::Directory => {
+                    stack.push((src_dir.join(name.as_ref()), path.clone()));
+                    EntryKind::Directory
+                }
+                EntryType::File => {
+                    // The length is used only for the progress. If the file can't be opened, the
+                    // error is reported when exporting it.
+                    let len = match entry.file() {
+                        Ok(file) => file.open().await.map(|file| file.len()).unwrap_or(0),
+                        Err(_) => 0,
+                    };
+
+                    EntryKind::File(len)
+                }
+                // Links are not exported.
+                EntryType::Link => continue,
+            };
+
+            entries.push(RepoEntry { path, kind });
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Sends transfer progress notifications and keeps the summary.
+struct Reporter {
+    id: u64,
+    tx: NotificationSender,
+    bytes: u64,
+    total: u64,
+    copied: u64,
+    skipped: u64,
+    failed: u64,
+}
+
+impl Reporter {
+    fn new(id: u64, tx: NotificationSender) -> Self {
+        Self {
+            id,
+            tx,
+            bytes: 0,
+            total: 0,
+            copied: 0,
+            skipped: 0,
+            failed: 0,
+        }
+    }
+
+    async fn copied(&mut self, path: String, len: u64) {
+        self.copied += 1;
+        self.bytes += len;
+
+        let event = TransferEvent::Copied {
+            path,
+            bytes: self.bytes,
+            total: self.total,
+        };
+
+        self.send(event).await;
+    }
+
+    async fn skipped(&mut self, path: String, reason: &str) {
+        self.skipped += 1;
+        self.send(TransferEvent::Skipped {
+            path,
+            reason: reason.to_owned(),
+        })
+        .await;
+    }
+
+    async fn failed(&mut self, path: String, error: Error) {
+        tracing::warn!(%path, ?error, "Failed to transfer entry");
+
+        self.failed += 1;
+        self.send(TransferEvent::Failed {
+            path,
+            error: error.message,
+        })
+        .await;
+    }
+
+    fn done(&self) -> TransferEvent {
+        TransferEvent::Done {
+            copied: self.copied,
+            skipped: self.skipped,
+            failed: self.failed,
+        }
+    }
+
+    async fn send(&self, event: TransferEvent) {
+        self.tx
+            .send((self.id, Notification::Transfer(event)))
+            .await
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use ouisync_bridge::{config::ConfigStore, repository};
+    use state_monitor::StateMonitor;
+    use tempfile::TempDir;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn import_directory() {
+        let (dir, repo) = setup().await;
+
+        let src = dir.path().join("src");
+        let large = vec![7; 2 * BLOCK_SIZE + 13];
+        fs::create_dir_all(src.join("sub/empty")).await.unwrap();
+        fs::write(src.join("a.txt"), b"alpha").await.unwrap();
+        fs::write(src.join("sub/b.dat"), &large).await.unwrap();
+
+        let (tx, mut rx) = mpsc::channel(32);
+        let mut reporter = Reporter::new(0, tx);
+
+        import_tree(&repo, &src, Utf8Path::new("dst"), &mut reporter)
+            .await
+            .unwrap();
+
+        assert_eq!(read_file(&repo, "dst/a.txt").await, b"alpha");
+        assert_eq!(read_file(&repo, "dst/sub/b.dat").await, large);
+        repo.open_directory("dst/sub/empty").await.unwrap();
+
+        assert_eq!(
+            reporter.done(),
+            TransferEvent::Done {
+                copied: 4,
+                skipped: 0,
+                failed: 0,
+            }
+        );
+
+        // The byte counts add up to the total.
+        let mut last = None;
+        while let Ok((_, Notification::Transfer(event))) = rx.try_recv() {
+            last = Some(event);
+        }
+
+        assert_matches!(
+            last,
+            Some(TransferEvent::Copied { bytes, total, .. })
+                if bytes == total && total == 5 + large.len() as u64
+        );
+    }
+
+    #[tokio::test]
+    async fn import_overwrites_existing_file() {
+        let (dir, repo) = setup().await;
+
+        let mut file = repo.create_file("test.txt").await.unwrap();
+        file.write_all(b"old and longer content").await.unwrap();
+        file.flush().await.unwrap();
+        drop(file);
+
+        let src = dir.path().join("test.txt");
+        fs::write(&src, b"new").await.unwrap();
+
+        let (tx, _rx) = mpsc::channel(32);
+        let mut reporter = Reporter::new(0, tx);
+
+        import_tree(&repo, &src, Utf8Path::new("test.txt"), &mut reporter)
+            .await
+            .unwrap();
+
+        assert_eq!(read_file(&repo, "test.txt").await, b"new");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn import_follows_symlinks_to_files_only() {
+        let (dir, repo) = setup().await;
+
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("sub")).await.unwrap();
+        fs::write(src.join("a.txt"), b"alpha").await.unwrap();
+        fs::symlink(src.join("a.txt"), src.join("file-link"))
+            .await
+            .unwrap();
+        fs::symlink(src.join("sub"), src.join("dir-link"))
+            .await
+            .unwrap();
+
+        let (tx, _rx) = mpsc::channel(32);
+        let mut reporter = Reporter::new(0, tx);
+
+        import_tree(&repo, &src, Utf8Path::new("dst"), &mut reporter)
+            .await
+            .unwrap();
+
+        assert_eq!(read_file(&repo, "dst/file-link").await, b"alpha");
+        assert_matches!(
+            repo.lookup_type("dst/dir-link").await,
+            Err(ouisync_lib::Error::EntryNotFound)
+        );
+
+        assert_eq!(
+            reporter.done(),
+            TransferEvent::Done {
+                copied: 3,
+                skipped: 1,
+                failed: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn import_missing_source() {
+        let (dir, repo) = setup().await;
+
+        let (tx, _rx) = mpsc::channel(32);
+        let mut reporter = Reporter::new(0, tx);
+
+        assert!(import_tree(
+            &repo,
+            &dir.path().join("missing"),
+            Utf8Path::new("dst"),
+            &mut reporter
+        )
+        .await
+        .is_err());
+    }
+
+    async fn setup() -> (TempDir, Repository) {
+        let dir = TempDir::new().unwrap();
+        let config = ConfigStore::new(dir.path().join("config"));
+
+        let repo = repository::create(
+            dir.path().join("repo.ouisyncdb"),
+            None,
+            None,
+            None,
+            &config,
+            &StateMonitor::make_root(),
+        )
+        .await
+        .unwrap();
+
+        (dir, repo)
+    }
+
+    async fn read_file(repo: &Repository, path: &str) -> Vec<u8> {
+        repo.open_file(path)
+            .await
+            .unwrap()
+            .read_to_end()
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn host_target_rejects_escaping_names() {
+        let parent = Path::new("export");
+
+        assert_eq!(
+            host_target(parent, Utf8Path::new("a/b")).unwrap(),
+            parent.join("b")
+        );
+
+        for path in ["", "..", "a/..", "/etc", "/", "."] {
+            assert!(host_target(parent, Utf8Path::new(path)).is_err(), "{path}");
+        }
+    }
+}
@@ -8,7 +8,14 @@ use tempfile::TempDir;
 use tokio::runtime::Runtime;
 use utils::Actor;
 
-criterion_group!(default, write_file, read_file, sync);
+criterion_group!(
+    default,
+    write_file,
+    read_file,
+    reread_file,
+    reread_small_file,
+    sync
+);
 criterion_main!(default);
 
 fn write_file(c: &mut Criterion) {
@@ -106,6 +113,127 @@ fn read_file(c: &mut Criterion) {
     group.finish();
 }
 
+/// Reads the same file a second time, with the block cache disabled and enabled.
+fn reread_file(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("reread_file");
+    group.sample_size(10);
+
+    let buffer_size = 4096;
+    let file_size = 8 * 1024 * 1024;
+
+    group.throughput(Throughput::Bytes(file_size));
+
+    for (label, cache_size) in [("cache disabled", 0), ("cache enabled", 2 * file_size)] {
+        group.bench_function(BenchmarkId::from_parameter(label), |b| {
+            let file_name = Utf8Path::new("file.dat");
+
+            b.iter_batched_ref(
+                || {
+                    let mut rng = StdRng::from_entropy();
+                    let base_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR")).unwrap();
+
+                    let repo = runtime.block_on(async {
+                        let repo = utils::create_repo(
+                            &mut rng,
+                            &base_dir.path().join("repo.db"),
+                            0,
+                            StateMonitor::make_root(),
+                        )
+                        .await;
+
+                        repo.set_block_cache_size(cache_size);
+
+                        utils::write_file(
+                            &mut rng,
+                            &repo,
+                            file_name,
+                            file_size as usize,
+                            buffer_size,
+                            false,
+                        )
+                        .await;
+
+                        // First read to warm up the cache.
+                        utils::read_file(&repo, file_name, buffer_size).await;
+
+                        repo
+                    });
+
+                    (base_dir, repo)
+                },
+                |(_base_dir, repo)| {
+                    let _len = runtime.block_on(utils::read_file(repo, file_name, buffer_size));
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// Reads the same small file many times, with the block cache disabled and enabled.
+fn reread_small_file(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("reread_small_file");
+    group.sample_size(10);
+
+    let file_size = 4096;
+    let read_count = 1000;
+
+    group.throughput(Throughput::Bytes(file_size * read_count));
+
+    for (label, cache_size) in [("cache disabled", 0), ("cache enabled", 1024 * 1024)] {
+        group.bench_function(BenchmarkId::from_parameter(label), |b| {
+            let file_name = Utf8Path::new("file.dat");
+
+            b.iter_batched_ref(
+                || {
+                    let mut rng = StdRng::from_entropy();
+                    let base_dir = TempDir::new_in(env!("CARGO_TARGET_TMPDIR")).unwrap();
+
+                    let repo = runtime.block_on(async {
+                        let repo = utils::create_repo(
+                            &mut rng,
+                            &base_dir.path().join("repo.db"),
+                            0,
+                            StateMonitor::make_root(),
+                        )
+                        .await;
+
+                        repo.set_block_cache_size(cache_size);
+
+                        utils::write_file(
+                            &mut rng,
+                            &repo,
+                            file_name,
+                            file_size as usize,
+                            file_size as usize,
+                            false,
+                        )
+                        .await;
+
+                        repo
+                    });
+
+                    (base_dir, repo)
+                },
+                |(_base_dir, repo)| {
+                    runtime.block_on(async {
+                        for _ in 0..read_count {
+                            utils::read_file(repo, file_name, file_size as usize).await;
+                        }
+                    })
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
 fn sync(c: &mut Criterion) {
     let runtime = Runtime::new().unwrap();
 
@@ -38,7 +38,6 @@ impl SetLocalSecret {
     }
 }
 
-#[cfg(test)]
 impl From<SetLocalSecret> for LocalSecret {
     fn from(local: SetLocalSecret) -> Self {
         match local {
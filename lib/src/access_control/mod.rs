@@ -1,5 +1,6 @@
 mod access_mode;
 mod local_secret;
+mod recovery;
 mod share_token;
 
 pub use self::{
@@ -9,7 +10,7 @@ pub use self::{
 };
 
 use crate::{
-    crypto::{cipher, sign},
+    crypto::{cipher, sign, Hashable},
     error::Error,
     protocol::RepositoryId,
     Result,
@@ -106,6 +107,44 @@ impl AccessSecrets {
         }
     }
 
+    /// Returns a short human readable "safety number" (six groups of five digits) derived from
+    /// these secrets. Two users can compare their fingerprints out-of-band (in person, over a
+    /// call, ...) to confirm they have the same secrets, that is, that the share token they
+    /// exchanged over an insecure channel wasn't replaced or tampered with.
+    ///
+    /// What a match proves depends on the access mode. Fingerprints of different modes never
+    /// match, so to compare with a user of lower access use `with_mode` first:
+    ///
+    /// - `Write`: both users have the same write key. They can both write to the same repository.
+    /// - `Read`: both users have the same repository id and the same read key. Because the read
+    ///   key is derived from the write key, a match with a user who has write access also proves
+    ///   the read key is the genuine one for the repository.
+    /// - `Blind`: both users have the same repository id. This proves nothing about access to the
+    ///   content because the id is public (it's derivable from any share token, blind included).
+    pub fn security_fingerprint(&self) -> String {
+        let hash = match self {
+            Self::Blind { id } => (b"ouisync fingerprint blind", id).hash(),
+            Self::Read { id, read_key } => {
+                (b"ouisync fingerprint read", id, read_key.as_array()).hash()
+            }
+            Self::Write(secrets) => {
+                (b"ouisync fingerprint write", secrets.write_keys.to_bytes()).hash()
+            }
+        };
+
+        hash.as_ref()
+            .chunks_exact(5)
+            .take(6)
+            .map(|chunk| {
+                let group = chunk
+                    .iter()
+                    .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+                format!("{:05}", group % 100_000)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     pub(crate) fn keys(&self) -> Option<AccessKeys> {
         match self {
             Self::Blind { .. } => None,
@@ -480,6 +519,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn security_fingerprint() {
+        let write = AccessSecrets::random_write();
+        let read = write.with_mode(AccessMode::Read);
+        let blind = write.with_mode(AccessMode::Blind);
+
+        let fingerprint = write.security_fingerprint();
+        assert_eq!(fingerprint.len(), 6 * 5 + 5);
+        assert!(fingerprint
+            .split(' ')
+            .all(|group| group.len() == 5 && group.bytes().all(|c| c.is_ascii_digit())));
+
+        // Deterministic
+        assert_eq!(write.clone().security_fingerprint(), fingerprint);
+
+        // Every mode has a different fingerprint
+        assert_ne!(read.security_fingerprint(), fingerprint);
+        assert_ne!(blind.security_fingerprint(), fingerprint);
+        assert_ne!(blind.security_fingerprint(), read.security_fingerprint());
+
+        // Same id but a different read key
+        let forged = AccessSecrets::Read {
+            id: *write.id(),
+            read_key: cipher::SecretKey::random(),
+        };
+        assert_ne!(forged.security_fingerprint(), read.security_fingerprint());
+        assert_eq!(
+            forged.with_mode(AccessMode::Blind).security_fingerprint(),
+            blind.security_fingerprint()
+        );
+
+        // Different repository
+        let other = AccessSecrets::random_write();
+        assert_ne!(other.security_fingerprint(), fingerprint);
+    }
+
     #[test]
     fn access_change_key_serialize_deserialize_msgpack() {
         let key = cipher::SecretKey::random();
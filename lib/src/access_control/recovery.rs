@@ -0,0 +1,83 @@
+//! Recovery strings: the full access secrets of a repository encoded for the owner to keep in a
+//! safe place (e.g. a password manager) so the access can be restored on a new device.
+//!
+//! Unlike a share token, a recovery string is not meant to be given to anyone: it carries no name
+//! nor expiration and with write access it contains the write key itself.
+
+use super::{
+    share_token::{base32, strip_prefix_ignore_case},
+    AccessSecrets, DecodeError,
+};
+use bincode::Options;
+use zeroize::Zeroizing;
+
+/// Prefix of the recovery strings. Distinct from the share token prefixes so the two can't be
+/// mistaken for each other.
+const RECOVERY_PREFIX: &str = "OUISYNC-RECOVERY:";
+
+const VERSION: u8 = 1;
+
+impl AccessSecrets {
+    /// Encodes these secrets into a recovery string. See [`Repository::export_access_secrets`]
+    /// for the security implications.
+    ///
+    /// [`Repository::export_access_secrets`]: crate::Repository::export_access_secrets
+    pub fn to_recovery_string(&self) -> String {
+        let mut buffer = Zeroizing::new(vec![VERSION]);
+
+        // unwrap is ok because serializing into a vec doesn't fail.
+        bincode::options()
+            .serialize_into(&mut *buffer, self)
+            .unwrap();
+
+        format!("{}{}", RECOVERY_PREFIX, base32::encode(&buffer))
+    }
+
+    /// Decodes the secrets from a recovery string produced by [Self::to_recovery_string].
+    pub fn from_recovery_string(input: &str) -> Result<Self, DecodeError> {
+        let input = input.trim();
+        let input = strip_prefix_ignore_case(input, RECOVERY_PREFIX).ok_or(DecodeError)?;
+        let input = Zeroizing::new(base32::decode(input)?);
+
+        match input.split_first() {
+            Some((&VERSION, rest)) => Ok(bincode::options().deserialize(rest)?),
+            _ => Err(DecodeError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{access_control::AccessMode, ShareToken};
+
+    #[test]
+    fn recovery_string_roundtrip() {
+        let secrets = AccessSecrets::random_write();
+
+        for mode in [AccessMode::Write, AccessMode::Read, AccessMode::Blind] {
+            let secrets = secrets.with_mode(mode);
+            let encoded = secrets.to_recovery_string();
+            assert!(encoded.starts_with(RECOVERY_PREFIX));
+
+            let decoded = AccessSecrets::from_recovery_string(&encoded).unwrap();
+            assert_eq!(decoded, secrets);
+            assert_eq!(
+                decoded.security_fingerprint(),
+                secrets.security_fingerprint()
+            );
+        }
+    }
+
+    #[test]
+    fn recovery_string_is_not_a_share_token() {
+        let secrets = AccessSecrets::random_write();
+
+        let recovery = secrets.to_recovery_string();
+        assert!(recovery.parse::<ShareToken>().is_err());
+
+        let token = ShareToken::from(secrets);
+        assert!(AccessSecrets::from_recovery_string(&token.to_string()).is_err());
+        assert!(AccessSecrets::from_recovery_string(&token.to_compact()).is_err());
+    }
+}
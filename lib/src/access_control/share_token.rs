@@ -1,14 +1,21 @@
 use super::{AccessMode, AccessSecrets, DecodeError};
-use crate::protocol::RepositoryId;
+use crate::{
+    error::{Error, Result},
+    protocol::RepositoryId,
+    time,
+};
 use bincode::Options;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     fmt,
     str::{self, FromStr},
+    time::SystemTime,
 };
 use zeroize::Zeroizing;
 
 pub const PREFIX: &str = "https://ouisync.net/r";
+/// Prefix of the compact token format (see [ShareToken::to_compact]).
+pub const COMPACT_PREFIX: &str = "OUISYNC:";
 pub const VERSION: u64 = 1;
 
 /// Token to share a repository which can be encoded as a URL-formatted string and transmitted to
@@ -17,6 +24,7 @@ pub const VERSION: u64 = 1;
 pub struct ShareToken {
     secrets: AccessSecrets,
     name: String,
+    not_after: Option<SystemTime>,
 }
 
 impl ShareToken {
@@ -28,6 +36,22 @@ impl ShareToken {
         }
     }
 
+    /// Attach an expiry time to the token. Once the time passes, `into_secrets` refuses the token.
+    ///
+    /// Note: the expiry is only advisory. It's not protected in any way so a modified client can
+    /// simply ignore it (or strip it from the token) and use the secrets anyway. It only prevents
+    /// honest clients from using a token after its deadline. To actually revoke access, the
+    /// repository secrets need to be changed.
+    ///
+    /// The expiry is encoded as milliseconds since the unix epoch, so times before the epoch are
+    /// clamped to it (the token is expired either way).
+    pub fn with_expiry(self, not_after: SystemTime) -> Self {
+        Self {
+            not_after: Some(not_after.max(SystemTime::UNIX_EPOCH)),
+            ..self
+        }
+    }
+
     /// Id of the repository to share.
     pub fn id(&self) -> &RepositoryId {
         self.secrets.id()
@@ -38,17 +62,96 @@ impl ShareToken {
         &self.name
     }
 
+    /// Time after which the token should no longer be accepted, if provided.
+    pub fn not_after(&self) -> Option<SystemTime> {
+        self.not_after
+    }
+
     pub fn secrets(&self) -> &AccessSecrets {
         &self.secrets
     }
 
-    pub fn into_secrets(self) -> AccessSecrets {
-        self.secrets
+    /// Returns the secrets contained in this token or `Error::TokenExpired` if the token has
+    /// expired (see [Self::with_expiry]).
+    pub fn into_secrets(self) -> Result<AccessSecrets> {
+        self.into_secrets_at(SystemTime::now())
+    }
+
+    fn into_secrets_at(self, now: SystemTime) -> Result<AccessSecrets> {
+        match self.not_after {
+            Some(not_after) if now > not_after => Err(Error::TokenExpired),
+            Some(_) | None => Ok(self.secrets),
+        }
     }
 
     pub fn access_mode(&self) -> AccessMode {
         self.secrets.access_mode()
     }
+
+    /// Encodes the token into a compact string suitable for QR codes. It consists only of
+    /// characters from the QR alphanumeric set (uppercase base32) which encodes into fewer modules
+    /// than the regular (URL) format. It starts with [COMPACT_PREFIX] so it can be told apart from
+    /// the regular format. Both formats are accepted by `from_str`.
+    pub fn to_compact(&self) -> String {
+        let mut buffer = Zeroizing::new(Vec::new());
+        encode_version(&mut buffer, VERSION);
+
+        // unwrap is ok because serializing into a vec doesn't fail.
+        bincode::options()
+            .serialize_into(
+                &mut *buffer,
+                &CompactRepr {
+                    secrets: &self.secrets,
+                    name: &self.name,
+                    not_after: self.not_after.map(encode_not_after),
+                },
+            )
+            .unwrap();
+
+        format!("{}{}", COMPACT_PREFIX, base32::encode(&buffer))
+    }
+
+    /// Decodes the token from the compact format produced by [Self::to_compact].
+    pub fn from_compact(input: &str) -> Result<Self, DecodeError> {
+        let input = input.trim();
+        let input = strip_prefix_ignore_case(input, COMPACT_PREFIX).ok_or(DecodeError)?;
+
+        let input = Zeroizing::new(base32::decode(input)?);
+        let input = decode_version(&input)?;
+
+        let repr: CompactReprOwned = bincode::options().deserialize(input)?;
+
+        Ok(Self {
+            secrets: repr.secrets,
+            name: repr.name,
+            not_after: repr.not_after.map(time::from_millis_since_epoch),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct CompactRepr<'a> {
+    secrets: &'a AccessSecrets,
+    name: &'a str,
+    not_after: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct CompactReprOwned {
+    secrets: AccessSecrets,
+    name: String,
+    not_after: Option<u64>,
+}
+
+pub(super) fn strip_prefix_ignore_case<'a>(input: &'a str, prefix: &str) -> Option<&'a str> {
+    if input.len() >= prefix.len()
+        && input.is_char_boundary(prefix.len())
+        && input[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&input[prefix.len()..])
+    } else {
+        None
+    }
 }
 
 impl From<AccessSecrets> for ShareToken {
@@ -56,6 +159,7 @@ impl From<AccessSecrets> for ShareToken {
         Self {
             secrets,
             name: String::new(),
+            not_after: None,
         }
     }
 }
@@ -67,6 +171,11 @@ impl FromStr for ShareToken {
         // Trim from the end as well because reading lines from a file includes the `\n` character.
         // Also the user may accidentally include white space if done from the app.
         let input = input.trim();
+
+        if strip_prefix_ignore_case(input, COMPACT_PREFIX).is_some() {
+            return Self::from_compact(input);
+        }
+
         let input = input.strip_prefix(PREFIX).ok_or(DecodeError)?;
 
         // The '/' before '#...' is optional.
@@ -84,8 +193,13 @@ impl FromStr for ShareToken {
 
         let secrets: AccessSecrets = bincode::options().deserialize(input)?;
         let name = parse_name(params)?;
+        let not_after = parse_not_after(params)?;
 
-        Ok(Self::from(secrets).with_name(name))
+        Ok(Self {
+            secrets,
+            name,
+            not_after,
+        })
     }
 }
 
@@ -98,6 +212,25 @@ fn parse_name(query: &str) -> Result<String, DecodeError> {
     Ok(urlencoding::decode(value)?.into_owned())
 }
 
+// `not_after` is never before the epoch (see `ShareToken::with_expiry`) so this fails only if it's
+// too far in the future to fit, in which case saturate.
+fn encode_not_after(not_after: SystemTime) -> u64 {
+    time::to_millis_since_epoch(not_after).unwrap_or(u64::MAX)
+}
+
+fn parse_not_after(query: &str) -> Result<Option<SystemTime>, DecodeError> {
+    query
+        .split('&')
+        .find_map(|param| param.strip_prefix("not_after="))
+        .map(|value| {
+            value
+                .parse()
+                .map(time::from_millis_since_epoch)
+                .map_err(|_| DecodeError)
+        })
+        .transpose()
+}
+
 fn encode_version(output: &mut Vec<u8>, version: u64) {
     let version = vint64::encode(version);
     output.extend_from_slice(version.as_ref());
@@ -112,6 +245,60 @@ fn decode_version(mut input: &[u8]) -> Result<&[u8], DecodeError> {
     }
 }
 
+/// Base32 (RFC 4648 alphabet, no padding). Decoding also accepts lowercase letters.
+pub(super) mod base32 {
+    use super::DecodeError;
+
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    pub fn encode(input: &[u8]) -> String {
+        let mut output = String::with_capacity((input.len() * 8 + 4) / 5);
+        let mut buffer: u16 = 0;
+        let mut bits = 0;
+
+        for byte in input {
+            buffer = (buffer << 8) | u16::from(*byte);
+            bits += 8;
+
+            while bits >= 5 {
+                bits -= 5;
+                output.push(ALPHABET[usize::from((buffer >> bits) & 0x1f)].into());
+            }
+        }
+
+        if bits > 0 {
+            output.push(ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)].into());
+        }
+
+        output
+    }
+
+    pub fn decode(input: &str) -> Result<Vec<u8>, DecodeError> {
+        let mut output = Vec::with_capacity(input.len() * 5 / 8);
+        let mut buffer: u16 = 0;
+        let mut bits = 0;
+
+        for c in input.bytes() {
+            let value = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a',
+                b'2'..=b'7' => c - b'2' + 26,
+                _ => return Err(DecodeError),
+            };
+
+            buffer = (buffer << 5) | u16::from(value);
+            bits += 5;
+
+            if bits >= 8 {
+                bits -= 8;
+                output.push((buffer >> bits) as u8);
+            }
+        }
+
+        Ok(output)
+    }
+}
+
 impl fmt::Display for ShareToken {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}#", PREFIX)?;
@@ -128,8 +315,15 @@ impl fmt::Display for ShareToken {
             base64::encode_config(buffer, base64::URL_SAFE_NO_PAD)
         )?;
 
+        let mut separator = '?';
+
         if !self.name.is_empty() {
-            write!(f, "?name={}", urlencoding::encode(&self.name))?
+            write!(f, "{separator}name={}", urlencoding::encode(&self.name))?;
+            separator = '&';
+        }
+
+        if let Some(not_after) = self.not_after {
+            write!(f, "{separator}not_after={}", encode_not_after(not_after))?;
         }
 
         Ok(())
@@ -161,6 +355,7 @@ mod tests {
     use super::*;
     use crate::crypto::{cipher, sign};
     use assert_matches::assert_matches;
+    use std::time::Duration;
 
     #[test]
     fn to_string_from_string_blind() {
@@ -224,4 +419,85 @@ mod tests {
             assert_eq!(access.id, token_id);
         });
     }
+
+    #[test]
+    fn to_compact_from_compact() {
+        let not_after =
+            time::from_millis_since_epoch(time::to_millis_since_epoch(SystemTime::now()).unwrap());
+
+        let tokens = [
+            ShareToken::from(AccessSecrets::Blind {
+                id: RepositoryId::random(),
+            }),
+            ShareToken::from(AccessSecrets::Read {
+                id: RepositoryId::random(),
+                read_key: cipher::SecretKey::random(),
+            })
+            .with_name("foo bar"),
+            ShareToken::from(AccessSecrets::random_write())
+                .with_name("baz")
+                .with_expiry(not_after),
+        ];
+
+        for token in tokens {
+            let compact = token.to_compact();
+
+            assert!(compact.starts_with(COMPACT_PREFIX));
+            assert!(compact
+                .bytes()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == b':'));
+            // QR alphanumeric mode takes 5.5 bits per character, byte mode takes 8.
+            assert!(compact.len() * 11 / 2 < token.to_string().len() * 8);
+
+            assert_eq!(ShareToken::from_compact(&compact).unwrap(), token);
+            assert_eq!(compact.parse::<ShareToken>().unwrap(), token);
+            assert_eq!(compact.to_lowercase().parse::<ShareToken>().unwrap(), token);
+        }
+
+        assert_matches!(ShareToken::from_compact("OUISYNC:!!!"), Err(DecodeError));
+        assert_matches!(
+            ShareToken::from_compact(&ShareToken::from(AccessSecrets::random_write()).to_string()),
+            Err(DecodeError)
+        );
+    }
+
+    #[test]
+    fn to_string_from_string_with_expiry() {
+        // Truncate to millis because that's the precision of the encoded expiry.
+        let not_after =
+            time::from_millis_since_epoch(time::to_millis_since_epoch(SystemTime::now()).unwrap())
+                + Duration::from_secs(60);
+        let token = ShareToken::from(AccessSecrets::random_write())
+            .with_name("foo")
+            .with_expiry(not_after);
+
+        let encoded = token.to_string();
+        let decoded: ShareToken = encoded.parse().unwrap();
+
+        assert_eq!(decoded, token);
+        assert_eq!(decoded.not_after(), Some(not_after));
+
+        // Normalization preserves the expiry.
+        assert_eq!(decoded.to_string(), encoded);
+
+        assert!(decoded.clone().into_secrets_at(not_after).is_ok());
+        assert_matches!(
+            decoded.into_secrets_at(not_after + Duration::from_secs(1)),
+            Err(Error::TokenExpired)
+        );
+    }
+
+    #[test]
+    fn expiry_before_epoch() {
+        let token = ShareToken::from(AccessSecrets::random_write())
+            .with_expiry(SystemTime::UNIX_EPOCH - Duration::from_secs(60));
+        assert_eq!(token.not_after(), Some(SystemTime::UNIX_EPOCH));
+
+        let decoded: ShareToken = token.to_string().parse().unwrap();
+        assert_eq!(decoded, token);
+        assert_matches!(decoded.into_secrets(), Err(Error::TokenExpired));
+
+        let decoded = ShareToken::from_compact(&token.to_compact()).unwrap();
+        assert_eq!(decoded, token);
+    }
 }
@@ -0,0 +1,154 @@
+//! In-memory cache of decrypted block contents.
+
+use crate::protocol::{BlockContent, BlockId, BLOCK_SIZE};
+use deadlock::BlockingMutex;
+use lru::LruCache;
+use std::sync::Arc;
+
+/// Bounded LRU cache of recently decrypted blocks, shared among all branches of a repository.
+///
+/// Blocks are content-addressed so a given `BlockId` always maps to the same plaintext and the
+/// entries never need to be updated, only evicted. The cache is disabled (has zero capacity) by
+/// default.
+#[derive(Clone)]
+pub(crate) struct BlockCache {
+    inner: Arc<BlockingMutex<Inner>>,
+}
+
+struct Inner {
+    entries: LruCache<BlockId, BlockContent>,
+    // Max number of cached blocks.
+    capacity: usize,
+}
+
+impl BlockCache {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(BlockingMutex::new(Inner {
+                entries: LruCache::unbounded(),
+                capacity: 0,
+            })),
+        }
+    }
+
+    /// Sets the max size of the cache in bytes. The size is rounded down to a whole number of
+    /// blocks. Zero disables the cache. If the cache currently holds more blocks than the new
+    /// size allows, the least recently used ones are evicted.
+    pub fn set_size(&self, size: u64) {
+        let capacity = usize::try_from(size / BLOCK_SIZE as u64).unwrap_or(usize::MAX);
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.capacity = capacity;
+        inner.shrink();
+    }
+
+    /// Returns the max size of the cache in bytes.
+    pub fn size(&self) -> u64 {
+        self.inner.lock().unwrap().capacity as u64 * BLOCK_SIZE as u64
+    }
+
+    /// Returns a copy of the cached content of the given block, if any. Marks the block as the
+    /// most recently used.
+    pub fn get(&self, id: &BlockId) -> Option<BlockContent> {
+        self.inner.lock().unwrap().entries.get(id).cloned()
+    }
+
+    /// Inserts the decrypted content of the given block into the cache, evicting the least
+    /// recently used block if the cache is full. Does nothing if the cache is disabled.
+    pub fn insert(&self, id: BlockId, content: &BlockContent) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.capacity == 0 {
+            return;
+        }
+
+        inner.entries.put(id, content.clone());
+        inner.shrink();
+    }
+
+    /// Evicts the given block from the cache.
+    pub fn remove(&self, id: &BlockId) {
+        self.inner.lock().unwrap().entries.pop(id);
+    }
+
+    /// Evicts all blocks from the cache. The capacity remains unchanged.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().entries.clear();
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+}
+
+impl Inner {
+    fn shrink(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.pop_lru();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn disabled_by_default() {
+        let cache = BlockCache::new();
+        let id = rand::random();
+
+        cache.insert(id, &rand::random());
+        assert!(cache.get(&id).is_none());
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut rng = rand::thread_rng();
+
+        let cache = BlockCache::new();
+        cache.set_size(2 * BLOCK_SIZE as u64);
+
+        let (id0, content0): (BlockId, BlockContent) = (rng.gen(), rng.gen());
+        let (id1, content1): (BlockId, BlockContent) = (rng.gen(), rng.gen());
+        let (id2, content2): (BlockId, BlockContent) = (rng.gen(), rng.gen());
+
+        cache.insert(id0, &content0);
+        cache.insert(id1, &content1);
+
+        // Touch `id0` so that `id1` becomes the least recently used.
+        assert_eq!(cache.get(&id0).as_deref(), Some(&content0[..]));
+
+        cache.insert(id2, &content2);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&id0).as_deref(), Some(&content0[..]));
+        assert!(cache.get(&id1).is_none());
+        assert_eq!(cache.get(&id2).as_deref(), Some(&content2[..]));
+    }
+
+    #[test]
+    fn shrink_on_resize() {
+        let mut rng = rand::thread_rng();
+
+        let cache = BlockCache::new();
+        cache.set_size(4 * BLOCK_SIZE as u64);
+
+        for _ in 0..4 {
+            cache.insert(rng.gen(), &rng.gen());
+        }
+
+        assert_eq!(cache.len(), 4);
+
+        // Sizes are rounded down to whole blocks.
+        cache.set_size(2 * BLOCK_SIZE as u64 + 1);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size(), 2 * BLOCK_SIZE as u64);
+
+        cache.set_size(0);
+        assert_eq!(cache.len(), 0);
+    }
+}
@@ -32,7 +32,7 @@ impl BlockIds {
         // stop iterating before we hit `LocatorNotFound` and we would end up processing also the
         // blocks that are past the end of the blob. This means that e.g., the garbage collector
         // would consider those blocks still reachable and would never remove them.
-        let upper_bound = match read_len(&mut tx, &root_node, blob_id, branch.keys().read()).await {
+        let upper_bound = match read_len(&mut tx, &root_node, blob_id, &branch).await {
             Ok(len) => Some(block_count(len)),
             Err(Error::Store(store::Error::BlockNotFound)) => None,
             Err(error) => return Err(error),
@@ -47,6 +47,11 @@ impl BlockIds {
         })
     }
 
+    /// Skips the next `count` blocks.
+    pub fn skip(&mut self, count: u32) {
+        self.locator = self.locator.nth(count);
+    }
+
     pub async fn try_next(&mut self) -> Result<Option<(BlockId, SingleBlockPresence)>> {
         if let Some(upper_bound) = self.upper_bound {
             if self.locator.number() >= upper_bound {
@@ -1,5 +1,6 @@
 pub(crate) mod lock;
 
+mod block_cache;
 mod block_ids;
 mod id;
 mod position;
@@ -7,7 +8,7 @@ mod position;
 #[cfg(test)]
 mod tests;
 
-pub(crate) use self::{block_ids::BlockIds, id::BlobId};
+pub(crate) use self::{block_cache::BlockCache, block_ids::BlockIds, id::BlobId};
 
 use self::position::Position;
 use crate::{
@@ -72,11 +73,17 @@ impl Blob {
     ) -> Result<Self> {
         assert_eq!(root_node.proof.writer_id, *branch.id());
 
-        let (_, buffer) =
-            read_block(tx, root_node, &Locator::head(id), branch.keys().read()).await?;
+        let (block_id, buffer) = read_block(
+            tx,
+            root_node,
+            &Locator::head(id),
+            branch.keys().read(),
+            branch.block_cache(),
+        )
+        .await?;
 
         let len = buffer.read_u64(0);
-        let cached_block = CachedBlock::from(buffer);
+        let cached_block = CachedBlock::loaded(block_id, buffer);
         let cache = iter::once((0, cached_block)).collect();
         let position = Position::ZERO;
 
@@ -355,9 +362,15 @@ impl Blob {
             Entry::Occupied(_) => (),
             Entry::Vacant(entry) => {
                 let locator = Locator::head(self.id).nth(self.position.block);
-                let (_, buffer) =
-                    read_block(tx, root_node, &locator, self.branch.keys().read()).await?;
-                entry.insert(CachedBlock::from(buffer));
+                let (block_id, buffer) = read_block(
+                    tx,
+                    root_node,
+                    &locator,
+                    self.branch.keys().read(),
+                    self.branch.block_cache(),
+                )
+                .await?;
+                entry.insert(CachedBlock::loaded(block_id, buffer));
             }
         }
 
@@ -446,8 +459,15 @@ impl Blob {
             let root_node = tx
                 .load_latest_approved_root_node(self.branch.id(), RootNodeFilter::Any)
                 .await?;
-            let (_, mut content) =
-                read_block(tx, &root_node, &locator, self.branch.keys().read()).await?;
+            let (block_id, mut content) = read_block(
+                tx,
+                &root_node,
+                &locator,
+                self.branch.keys().read(),
+                self.branch.block_cache(),
+            )
+            .await?;
+            self.branch.block_cache().remove(&block_id);
             content.write_u64(0, self.len_modified);
             write_block(changeset, &locator, content, self.branch.keys().read());
         }
@@ -465,6 +485,12 @@ impl Blob {
         self.cache = clean;
 
         for (number, block) in dirty {
+            // The block is being replaced so its previous version is no longer needed in the
+            // block cache.
+            if let Some(id) = block.id {
+                self.branch.block_cache().remove(&id);
+            }
+
             let locator = Locator::head(self.id).nth(number);
             write_block(
                 changeset,
@@ -494,6 +520,8 @@ impl Clone for Blob {
 struct CachedBlock {
     content: BlockContent,
     dirty: bool,
+    // Id of the block this was loaded from, if any.
+    id: Option<BlockId>,
 }
 
 impl CachedBlock {
@@ -501,6 +529,14 @@ impl CachedBlock {
         Self::default()
     }
 
+    fn loaded(id: BlockId, content: BlockContent) -> Self {
+        Self {
+            content,
+            dirty: false,
+            id: Some(id),
+        }
+    }
+
     fn with_dirty(self, dirty: bool) -> Self {
         Self { dirty, ..self }
     }
@@ -511,6 +547,7 @@ impl From<BlockContent> for CachedBlock {
         Self {
             content,
             dirty: false,
+            id: None,
         }
     }
 }
@@ -541,7 +578,7 @@ pub(crate) async fn fork(blob_id: BlobId, src_branch: &Branch, dst_branch: &Bran
         let root_node = tx
             .load_latest_approved_root_node(src_branch.id(), RootNodeFilter::Any)
             .await?;
-        load_block_count_hint(&mut tx, &root_node, blob_id, src_branch.keys().read()).await?
+        load_block_count_hint(&mut tx, &root_node, blob_id, src_branch).await?
     };
 
     struct Batch {
@@ -607,7 +644,48 @@ pub(crate) async fn fork(blob_id: BlobId, src_branch: &Branch, dst_branch: &Bran
     Ok(())
 }
 
-fn block_count(len: u64) -> u32 {
+/// Links all the blocks of the blob `src_id` in `src_branch` under the blob id `dst_id` without
+/// copying their content. Both blobs then share the same blocks which are removed from the store
+/// only after no blob references them anymore. The changeset is meant to be applied to the
+/// branch where the new blob should be created.
+pub(crate) async fn copy(
+    tx: &mut ReadTransaction,
+    changeset: &mut Changeset,
+    src_branch: &Branch,
+    src_id: BlobId,
+    dst_id: BlobId,
+) -> Result<()> {
+    let read_key = src_branch.keys().read();
+    let root_node = tx
+        .load_latest_approved_root_node(src_branch.id(), RootNodeFilter::Any)
+        .await?;
+    let end = load_block_count_hint(tx, &root_node, src_id, src_branch).await?;
+
+    let locators = Locator::head(src_id)
+        .sequence()
+        .zip(Locator::head(dst_id).sequence())
+        .take(end as usize);
+
+    for (src_locator, dst_locator) in locators {
+        let (block_id, block_presence) = match tx
+            .find_block_at(&root_node, &src_locator.encode(read_key))
+            .await
+        {
+            Ok(block_info) => block_info,
+            Err(store::Error::LocatorNotFound) => {
+                // end of the blob
+                break;
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        changeset.link_block(dst_locator.encode(read_key), block_id, block_presence);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn block_count(len: u64) -> u32 {
     // https://stackoverflow.com/questions/2745074/fast-ceiling-of-an-integer-division-in-c-c
     (1 + (len + HEADER_SIZE as u64 - 1) / BLOCK_SIZE as u64)
         .try_into()
@@ -618,9 +696,16 @@ async fn read_len(
     tx: &mut ReadTransaction,
     root_node: &RootNode,
     blob_id: BlobId,
-    read_key: &cipher::SecretKey,
+    branch: &Branch,
 ) -> Result<u64> {
-    let (_, buffer) = read_block(tx, root_node, &Locator::head(blob_id), read_key).await?;
+    let (_, buffer) = read_block(
+        tx,
+        root_node,
+        &Locator::head(blob_id),
+        branch.keys().read(),
+        branch.block_cache(),
+    )
+    .await?;
     Ok(buffer.read_u64(0))
 }
 
@@ -630,9 +715,9 @@ async fn load_block_count_hint(
     tx: &mut ReadTransaction,
     root_node: &RootNode,
     blob_id: BlobId,
-    read_key: &cipher::SecretKey,
+    branch: &Branch,
 ) -> Result<u32> {
-    match read_len(tx, root_node, blob_id, read_key).await {
+    match read_len(tx, root_node, blob_id, branch).await {
         Ok(len) => Ok(block_count(len)),
         Err(Error::Store(store::Error::BlockNotFound)) => Ok(u32::MAX),
         Err(error) => Err(error),
@@ -644,15 +729,21 @@ async fn read_block(
     root_node: &RootNode,
     locator: &Locator,
     read_key: &cipher::SecretKey,
+    cache: &BlockCache,
 ) -> Result<(BlockId, BlockContent)> {
     let (id, _) = tx
         .find_block_at(root_node, &locator.encode(read_key))
         .await?;
 
+    if let Some(content) = cache.get(&id) {
+        return Ok((id, content));
+    }
+
     let mut content = BlockContent::new();
     let nonce = tx.read_block(&id, &mut content).await?;
 
     decrypt_block(read_key, &nonce, &mut content);
+    cache.insert(id, &content);
 
     Ok((id, content))
 }
@@ -681,7 +772,11 @@ fn write_block(
     block_id
 }
 
-fn decrypt_block(blob_key: &cipher::SecretKey, block_nonce: &BlockNonce, content: &mut [u8]) {
+pub(crate) fn decrypt_block(
+    blob_key: &cipher::SecretKey,
+    block_nonce: &BlockNonce,
+    content: &mut [u8],
+) {
     let block_key = SecretKey::derive_from_key(blob_key.as_array(), block_nonce);
     block_key.decrypt_no_aead(&Nonce::default(), content);
 }
@@ -706,7 +801,7 @@ fn encrypt_block(blob_key: &cipher::SecretKey, block_nonce: &BlockNonce, content
 ///
 /// Note: `read_key` is used as an additional secret hashing material to prevent known plaintext
 /// attacks.
-fn make_block_nonce(
+pub(crate) fn make_block_nonce(
     locator: &Locator,
     plaintext_content: &[u8],
     read_key: &cipher::SecretKey,
@@ -1,6 +1,7 @@
 use super::*;
 use crate::{
     access_control::{AccessKeys, WriteSecrets},
+    block_tracker::BlockTracker,
     branch::BranchShared,
     crypto::sign::PublicKey,
     db,
@@ -860,6 +861,93 @@ async fn block_ids_of_identical_blocks_in_the_same_blob() {
     assert_ne!(block_ids[1], block_ids[2]);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn block_cache_reuses_decrypted_blocks() {
+    let (mut rng, _base_dir, store, [branch]) = setup(0).await;
+    branch.block_cache().set_size(4 * BLOCK_SIZE as u64);
+
+    let mut tx = store.begin_write().await.unwrap();
+    let mut changeset = Changeset::new();
+
+    let id = rng.gen();
+    let content = random_bytes(&mut rng, BLOCK_SIZE);
+
+    let mut blob = Blob::create(branch.clone(), id);
+    blob.write_all(&mut tx, &mut changeset, &content)
+        .await
+        .unwrap();
+    blob.flush(&mut tx, &mut changeset).await.unwrap();
+    changeset
+        .apply(&mut tx, branch.id(), branch.keys().write().unwrap())
+        .await
+        .unwrap();
+
+    // Writing doesn't populate the cache.
+    assert_eq!(branch.block_cache().len(), 0);
+
+    let mut blob = Blob::open(&mut tx, branch.clone(), id).await.unwrap();
+    assert_eq!(blob.read_to_end(&mut tx).await.unwrap(), content);
+
+    // The content spans two blocks (because of the header)
+    assert_eq!(branch.block_cache().len(), 2);
+
+    // Reading again is served from the cache.
+    let mut blob = Blob::open(&mut tx, branch.clone(), id).await.unwrap();
+    assert_eq!(blob.read_to_end(&mut tx).await.unwrap(), content);
+    assert_eq!(branch.block_cache().len(), 2);
+
+    drop(tx);
+    store.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn block_cache_evicts_stale_blocks_on_write() {
+    let (mut rng, _base_dir, store, [branch]) = setup(0).await;
+    branch.block_cache().set_size(4 * BLOCK_SIZE as u64);
+
+    let mut tx = store.begin_write().await.unwrap();
+    let mut changeset = Changeset::new();
+
+    let id = rng.gen();
+    let content_0 = random_bytes(&mut rng, BLOCK_SIZE);
+    let content_1 = random_bytes(&mut rng, BLOCK_SIZE);
+
+    let mut blob = Blob::create(branch.clone(), id);
+    blob.write_all(&mut tx, &mut changeset, &content_0)
+        .await
+        .unwrap();
+    blob.flush(&mut tx, &mut changeset).await.unwrap();
+    changeset
+        .apply(&mut tx, branch.id(), branch.keys().write().unwrap())
+        .await
+        .unwrap();
+
+    let mut blob = Blob::open(&mut tx, branch.clone(), id).await.unwrap();
+    assert_eq!(blob.read_to_end(&mut tx).await.unwrap(), content_0);
+    assert_eq!(branch.block_cache().len(), 2);
+
+    // Overwrite the whole content. The previous versions of the blocks get evicted.
+    let mut changeset = Changeset::new();
+    let mut blob = Blob::open(&mut tx, branch.clone(), id).await.unwrap();
+    blob.write_all(&mut tx, &mut changeset, &content_1)
+        .await
+        .unwrap();
+    blob.flush(&mut tx, &mut changeset).await.unwrap();
+    changeset
+        .apply(&mut tx, branch.id(), branch.keys().write().unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(branch.block_cache().len(), 0);
+
+    let mut blob = Blob::open(&mut tx, branch.clone(), id).await.unwrap();
+    assert_eq!(blob.read_to_end(&mut tx).await.unwrap(), content_1);
+    assert_eq!(branch.block_cache().len(), 2);
+
+    drop(tx);
+    store.close().await.unwrap();
+}
+
 async fn setup<const N: usize>(rng_seed: u64) -> (StdRng, TempDir, Store, [Branch; N]) {
     let mut rng = StdRng::seed_from_u64(rng_seed);
     let keys: AccessKeys = WriteSecrets::generate(&mut rng).into();
@@ -867,7 +955,7 @@ async fn setup<const N: usize>(rng_seed: u64) -> (StdRng, TempDir, Store, [Branc
     let store = Store::new(pool);
 
     let event_tx = EventSender::new(1);
-    let shared = BranchShared::new();
+    let shared = BranchShared::new(BlockTracker::new());
 
     let branches = [(); N].map(|_| {
         let id = PublicKey::random();
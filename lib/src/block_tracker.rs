@@ -6,6 +6,9 @@ use deadlock::BlockingMutex;
 use std::{collections::hash_map::Entry, sync::Arc};
 use tokio::sync::watch;
 
+/// Priority of blocks required with [BlockTracker::require].
+pub(crate) const DEFAULT_PRIORITY: u8 = 0;
+
 /// Helper for tracking required missing blocks.
 #[derive(Clone)]
 pub(crate) struct BlockTracker {
@@ -41,7 +44,20 @@ impl BlockTracker {
 
     /// Marks the block with the given id as required.
     pub fn require(&self, block_id: BlockId) {
-        if self.shared.inner.lock().unwrap().require(block_id) {
+        self.require_with_priority(block_id, DEFAULT_PRIORITY)
+    }
+
+    /// Marks the block with the given id as required with the given priority. Among the blocks
+    /// that are offered by the same peer, those with higher priority are requested first. If the
+    /// block is already required, its priority is raised but never lowered.
+    pub fn require_with_priority(&self, block_id: BlockId, priority: u8) {
+        if self
+            .shared
+            .inner
+            .lock()
+            .unwrap()
+            .require(block_id, priority)
+        {
             self.shared.notify()
         }
     }
@@ -77,6 +93,33 @@ impl BlockTracker {
         }
     }
 
+    /// Cancels the download of the block with the given id: it's no longer required and won't be
+    /// required again by subsequent calls to `require` (or by offers in the `Greedy` mode). If the
+    /// block has already been requested, the request is left to finish but if it fails the block
+    /// is not requested again.
+    pub fn cancel(&self, block_id: BlockId) {
+        self.shared.inner.lock().unwrap().cancel(block_id)
+    }
+
+    /// Returns the ids of the missing blocks that are currently being downloaded, that is, those
+    /// that are required or whose request is in flight.
+    pub fn pending(&self) -> Vec<BlockId> {
+        self.shared
+            .inner
+            .lock()
+            .unwrap()
+            .missing_blocks
+            .iter()
+            .filter(|(_, missing_block)| {
+                matches!(
+                    missing_block.state,
+                    State::Idle { required: true, .. } | State::Accepted(_)
+                )
+            })
+            .map(|(block_id, _)| *block_id)
+            .collect()
+    }
+
     pub fn client(&self) -> TrackerClient {
         let client_id = self.shared.inner.lock().unwrap().insert_client();
         let notify_rx = self.shared.notify_tx.subscribe();
@@ -110,7 +153,13 @@ pub(crate) struct RequireBatch<'a> {
 
 impl RequireBatch<'_> {
     pub fn add(&mut self, block_id: BlockId) {
-        if self.shared.inner.lock().unwrap().require(block_id) {
+        if self
+            .shared
+            .inner
+            .lock()
+            .unwrap()
+            .require(block_id, DEFAULT_PRIORITY)
+        {
             self.notify = true;
         }
     }
@@ -171,6 +220,8 @@ impl TrackerClient {
                     required: false,
                     approved: false,
                 },
+                priority: DEFAULT_PRIORITY,
+                cancelled: false,
             });
 
         missing_block
@@ -198,7 +249,7 @@ impl TrackerClient {
         match inner.request_mode {
             RequestMode::Lazy => (),
             RequestMode::Greedy => {
-                if inner.require(block_id) {
+                if inner.require(block_id, DEFAULT_PRIORITY) {
                     notify = true;
                 }
             }
@@ -395,7 +446,7 @@ impl Inner {
 
     /// Mark the block with the given id as required. Returns true if the block wasn't already
     /// required and if it has at least one offer. Otherwise returns false.
-    fn require(&mut self, block_id: BlockId) -> bool {
+    fn require(&mut self, block_id: BlockId, priority: u8) -> bool {
         let missing_block = self
             .missing_blocks
             .entry(block_id)
@@ -405,8 +456,16 @@ impl Inner {
                     required: false,
                     approved: false,
                 },
+                priority: DEFAULT_PRIORITY,
+                cancelled: false,
             });
 
+        if missing_block.cancelled {
+            return false;
+        }
+
+        missing_block.priority = missing_block.priority.max(priority);
+
         match &mut missing_block.state {
             State::Idle { required: true, .. } | State::Accepted(_) => false,
             State::Idle { required, .. } => {
@@ -416,6 +475,30 @@ impl Inner {
         }
     }
 
+    fn cancel(&mut self, block_id: BlockId) {
+        let missing_block = self
+            .missing_blocks
+            .entry(block_id)
+            .or_insert_with(|| MissingBlock {
+                offers: HashMap::default(),
+                state: State::Idle {
+                    required: false,
+                    approved: false,
+                },
+                priority: DEFAULT_PRIORITY,
+                cancelled: false,
+            });
+
+        missing_block.cancelled = true;
+
+        match &mut missing_block.state {
+            State::Idle { required, .. } => *required = false,
+            // The request is already in flight. If it fails, `unaccept_by` makes sure the block is
+            // not required again.
+            State::Accepted(_) => (),
+        }
+    }
+
     fn complete(&mut self, block_id: &BlockId) {
         let Some(missing_block) = self.missing_blocks.remove(block_id) else {
             return;
@@ -428,11 +511,15 @@ impl Inner {
         }
     }
 
+    /// Proposes the highest priority block that's required, approved and offered by the given
+    /// client.
     fn propose_offer(&mut self, client_id: ClientId) -> Option<BlockId> {
+        let mut candidate: Option<(BlockId, u8)> = None;
+
         // TODO: OPTIMIZE (but profile first) this linear lookup
         for block_id in self.clients.get(&client_id).into_iter().flatten() {
             // unwrap is ok because of the invariant in `Inner`
-            let missing_block = self.missing_blocks.get_mut(block_id).unwrap();
+            let missing_block = self.missing_blocks.get(block_id).unwrap();
 
             match missing_block.state {
                 State::Idle {
@@ -443,18 +530,28 @@ impl Inner {
             }
 
             // unwrap is ok because of the invariant.
-            let offer = missing_block.offers.get_mut(&client_id).unwrap();
-            match offer {
-                Offer::Available => {
-                    *offer = Offer::Proposed;
-                }
+            match missing_block.offers.get(&client_id).unwrap() {
+                Offer::Available => (),
                 Offer::Proposed | Offer::Accepted => continue,
             }
 
-            return Some(*block_id);
+            if candidate.map_or(true, |(_, priority)| missing_block.priority > priority) {
+                candidate = Some((*block_id, missing_block.priority));
+            }
         }
 
-        None
+        let (block_id, _) = candidate?;
+
+        // unwraps are ok because the block and its offer were found above.
+        *self
+            .missing_blocks
+            .get_mut(&block_id)
+            .unwrap()
+            .offers
+            .get_mut(&client_id)
+            .unwrap() = Offer::Proposed;
+
+        Some(block_id)
     }
 
     fn accept_offer(&mut self, block_id: &BlockId, client_id: ClientId) -> bool {
@@ -511,6 +608,9 @@ struct MissingBlock {
     // Clients that offered this block.
     offers: HashMap<ClientId, Offer>,
     state: State,
+    priority: u8,
+    // Whether the download of this block has been cancelled with `BlockTracker::cancel`.
+    cancelled: bool,
 }
 
 impl MissingBlock {
@@ -518,10 +618,10 @@ impl MissingBlock {
         match self.state {
             State::Accepted(other_client_id) if other_client_id == client_id => {
                 self.state = State::Idle {
-                    required: true,
+                    required: !self.cancelled,
                     approved: true,
                 };
-                true
+                !self.cancelled
             }
             State::Accepted(_) | State::Idle { .. } => false,
         }
@@ -782,6 +882,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn cancel_before_accept() {
+        let tracker = BlockTracker::new();
+        tracker.set_request_mode(RequestMode::Greedy);
+
+        let client = tracker.client();
+
+        let block: Block = rand::random();
+        client.register(block.id, OfferState::Approved);
+        assert_eq!(tracker.pending(), vec![block.id]);
+
+        tracker.cancel(block.id);
+        assert!(tracker.pending().is_empty());
+        assert!(client.offers().try_next().is_none());
+
+        // Requiring it again has no effect.
+        tracker.require(block.id);
+        assert!(tracker.pending().is_empty());
+        assert!(client.offers().try_next().is_none());
+    }
+
+    #[test]
+    fn cancel_after_accept() {
+        let tracker = BlockTracker::new();
+        tracker.set_request_mode(RequestMode::Lazy);
+
+        let client0 = tracker.client();
+        let client1 = tracker.client();
+
+        let block: Block = rand::random();
+
+        tracker.require(block.id);
+        client0.register(block.id, OfferState::Approved);
+        client1.register(block.id, OfferState::Approved);
+
+        let block_promise = client0.offers().try_next().and_then(BlockOffer::accept);
+        assert!(block_promise.is_some());
+
+        // The in-flight request is still pending...
+        tracker.cancel(block.id);
+        assert_eq!(tracker.pending(), vec![block.id]);
+
+        // ...but when it fails, the block is not offered to the other client.
+        drop(block_promise);
+        assert!(tracker.pending().is_empty());
+        assert!(client1.offers().try_next().is_none());
+    }
+
     #[test]
     fn approve() {
         let tracker = BlockTracker::new();
@@ -944,4 +1092,44 @@ mod tests {
             assert!(block_promise.contains(block_id));
         }
     }
+
+    #[proptest]
+    fn higher_priority_first(
+        #[strategy(1usize..100)] num_blocks: usize,
+        #[strategy(test_utils::rng_seed_strategy())] rng_seed: u64,
+    ) {
+        higher_priority_first_case(num_blocks, rng_seed)
+    }
+
+    fn higher_priority_first_case(num_blocks: usize, rng_seed: u64) {
+        let mut rng = StdRng::seed_from_u64(rng_seed);
+
+        let tracker = BlockTracker::new();
+        tracker.set_request_mode(RequestMode::Lazy);
+
+        let client = tracker.client();
+
+        let block_ids: Vec<BlockId> = (&mut rng).sample_iter(Standard).take(num_blocks).collect();
+        let blocks: HashMap<BlockId, u8> = block_ids
+            .into_iter()
+            .map(|block_id| (block_id, rng.gen()))
+            .collect();
+
+        for (block_id, priority) in &blocks {
+            client.register(*block_id, OfferState::Approved);
+            tracker.require_with_priority(*block_id, *priority);
+        }
+
+        let mut accepted = Vec::with_capacity(blocks.len());
+
+        while let Some(block_promise) = client.offers().try_next().and_then(BlockOffer::accept) {
+            accepted.push(blocks[block_promise.block_id()]);
+        }
+
+        assert_eq!(accepted.len(), blocks.len());
+
+        for (prev, next) in accepted.iter().zip(accepted.iter().skip(1)) {
+            assert!(prev >= next);
+        }
+    }
 }
@@ -1,6 +1,10 @@
 use crate::{
     access_control::AccessKeys,
-    blob::lock::{BranchLocker, Locker},
+    blob::{
+        lock::{BranchLocker, Locker},
+        BlockCache,
+    },
+    block_tracker::BlockTracker,
     crypto::sign::PublicKey,
     debug::DebugPrinter,
     directory::{Directory, DirectoryFallback, DirectoryLocking, EntryRef},
@@ -13,6 +17,10 @@ use crate::{
     version_vector::VersionVector,
 };
 use camino::{Utf8Component, Utf8Path};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 
 #[derive(Clone)]
 pub struct Branch {
@@ -106,6 +114,7 @@ impl Branch {
                             Some(entry.open(DirectoryFallback::Disabled).await?)
                         }
                         Ok(EntryRef::File(_)) => return Err(Error::EntryIsFile),
+                        Ok(EntryRef::Link(_)) => return Err(Error::EntryIsLink),
                         Ok(EntryRef::Tombstone(_)) | Err(Error::EntryNotFound) => None,
                         Err(error) => return Err(error),
                     };
@@ -155,6 +164,20 @@ impl Branch {
         self.shared.locker.branch(*self.id())
     }
 
+    /// Whether the repository this branch belongs to has been switched to blind mode after this
+    /// branch was created. The branch keys are still around in that case but must not be used.
+    pub(crate) fn is_locked(&self) -> bool {
+        self.shared.locked.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn block_cache(&self) -> &BlockCache {
+        &self.shared.block_cache
+    }
+
+    pub(crate) fn block_tracker(&self) -> &BlockTracker {
+        &self.shared.block_tracker
+    }
+
     pub(crate) fn notify(&self) -> BranchEventSender {
         BranchEventSender {
             event_tx: self.event_tx.clone(),
@@ -213,12 +236,18 @@ impl Branch {
 #[derive(Clone)]
 pub(crate) struct BranchShared {
     pub locker: Locker,
+    pub block_cache: BlockCache,
+    pub locked: Arc<AtomicBool>,
+    pub block_tracker: BlockTracker,
 }
 
 impl BranchShared {
-    pub fn new() -> Self {
+    pub fn new(block_tracker: BlockTracker) -> Self {
         Self {
             locker: Locker::new(),
+            block_cache: BlockCache::new(),
+            locked: Arc::new(AtomicBool::new(false)),
+            block_tracker,
         }
     }
 }
@@ -311,7 +340,7 @@ mod tests {
         let event_tx = EventSender::new(1);
 
         let store = Store::new(pool);
-        let shared = BranchShared::new();
+        let shared = BranchShared::new(BlockTracker::new());
         let branch = Branch::new(writer_id, store, secrets.into(), shared, event_tx);
 
         (base_dir, branch)
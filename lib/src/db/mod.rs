@@ -11,13 +11,14 @@ pub use migrations::SCHEMA_VERSION;
 use tracing::Span;
 
 use deadlock::ExpectShortLifetime;
+use futures_util::future;
 use ref_cast::RefCast;
 use sqlx::{
     sqlite::{
         Sqlite, SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous,
         SqliteTransactionManager,
     },
-    Row, SqlitePool, TransactionManager,
+    ConnectOptions, Connection as _, Row, SqlitePool, TransactionManager,
 };
 use std::{
     fmt,
@@ -25,30 +26,60 @@ use std::{
     io,
     ops::{Deref, DerefMut},
     panic::Location,
-    path::Path,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock as BlockingRwLock,
+    },
     time::Duration,
 };
 #[cfg(test)]
 use tempfile::TempDir;
 use thiserror::Error;
-use tokio::{fs, task};
+use tokio::{
+    fs,
+    io::AsyncReadExt,
+    sync::{watch, Mutex as AsyncMutex},
+    task, time,
+};
 
 const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+// How long a relocation waits for the connections currently in use to be released before giving
+// up. Kept short because a task holding a connection might be itself waiting for the relocation
+// to finish (to acquire another connection).
+const RELOCATE_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
 const WARN_AFTER_CONNECTION_LIFETIME: Duration = Duration::from_secs(30);
+const MAX_READ_CONNECTIONS: u32 = 8;
 
 pub use self::connection::Connection;
 
 /// Database connection pool.
 #[derive(Clone)]
 pub struct Pool {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    pools: BlockingRwLock<Pools>,
+    // Incremented every time `pools` are replaced (see `Pool::relocate`). Used to wake up tasks
+    // that tried to acquire a connection while the pools were being replaced.
+    generation: watch::Sender<u64>,
+    // Serializes relocations.
+    relocate_lock: AsyncMutex<()>,
+    closed: AtomicBool,
+}
+
+#[derive(Clone)]
+struct Pools {
     // Pool with multiple read-only connections
     reads: SqlitePool,
     // Pool with a single writable connection.
     write: SqlitePool,
+    conn_options: SqliteConnectOptions,
 }
 
-impl Pool {
+impl Pools {
     async fn create(conn_options: SqliteConnectOptions) -> Result<Self, sqlx::Error> {
         let conn_options = conn_options
             .journal_mode(SqliteJournalMode::Wal)
@@ -69,23 +100,54 @@ impl Pool {
             .await?;
 
         let reads = pool_options
-            .max_connections(8)
-            .connect_with(conn_options.read_only(true))
+            .max_connections(MAX_READ_CONNECTIONS)
+            .connect_with(conn_options.clone().read_only(true))
             .await?;
 
-        Ok(Self { reads, write })
+        Ok(Self {
+            reads,
+            write,
+            conn_options,
+        })
+    }
+
+    async fn close(&self) {
+        // Make sure to first close `reads` and only then `write`. That way when closing the write
+        // connection it is the last remaining connection and so it performs a WAL checkpoint and
+        // removes the auxiliary db files (*-wal and *-shm).
+        self.reads.close().await;
+        self.write.close().await;
+    }
+}
+
+impl Pool {
+    async fn create(conn_options: SqliteConnectOptions) -> Result<Self, sqlx::Error> {
+        let pools = Pools::create(conn_options).await?;
+
+        Ok(Self {
+            shared: Arc::new(Shared {
+                pools: BlockingRwLock::new(pools),
+                generation: watch::Sender::new(0),
+                relocate_lock: AsyncMutex::new(()),
+                closed: AtomicBool::new(false),
+            }),
+        })
     }
 
     /// Acquire a read-only database connection.
     #[track_caller]
     pub fn acquire(&self) -> impl Future<Output = Result<PoolConnection, sqlx::Error>> + '_ {
-        PoolConnection::acquire(&self.reads, Location::caller())
+        self.acquire_from(|pools| &pools.reads, Location::caller())
     }
 
     /// Begin a read-only transaction. See [`ReadTransaction`] for more details.
     #[track_caller]
     pub fn begin_read(&self) -> impl Future<Output = Result<ReadTransaction, sqlx::Error>> + '_ {
-        ReadTransaction::begin(&self.reads, Location::caller())
+        let location = Location::caller();
+
+        async move {
+            ReadTransaction::begin(self.acquire_from(|pools| &pools.reads, location).await?).await
+        }
     }
 
     /// Begin a write transaction. See [`WriteTransaction`] for more details.
@@ -94,21 +156,214 @@ impl Pool {
         let location = Location::caller();
 
         async move {
+            let conn = self.acquire_from(|pools| &pools.write, location).await?;
+
             Ok(WriteTransaction {
-                inner: ReadTransaction::begin(&self.write, location).await?,
+                inner: ReadTransaction::begin(conn).await?,
             })
         }
     }
 
+    /// Pre-opens up to `count` read-only connections (capped at the pool size) and runs a trivial
+    /// query on each so that subsequent operations don't have to pay the connection establishment
+    /// cost. The connections are returned to the pool afterwards and expire after being idle for
+    /// `IDLE_TIMEOUT`.
+    pub(crate) async fn warmup(&self, count: u32) -> Result<(), sqlx::Error> {
+        let count = count.min(MAX_READ_CONNECTIONS);
+        let reads = self.shared.pools.read().unwrap().reads.clone();
+
+        // Acquire all the connections first so they are actually distinct.
+        let mut conns = future::try_join_all((0..count).map(|_| reads.acquire())).await?;
+
+        for conn in &mut conns {
+            sqlx::query("SELECT 1").execute(&mut **conn).await?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) async fn close(&self) -> Result<(), sqlx::Error> {
-        // Make sure to first close `reads` and only then `write`. That way when closing the write
-        // connection it is the last remaining connection and so it performs a WAL checkpoint and
-        // removes the auxiliary db files (*-wal and *-shm).
-        self.reads.close().await;
-        self.write.close().await;
+        let _guard = self.shared.relocate_lock.lock().await;
+
+        self.shared.closed.store(true, Ordering::Release);
+        let pools = self.shared.pools.read().unwrap().clone();
+        pools.close().await;
+
+        // Wake up the tasks waiting for a relocation to finish so they see the pool is closed.
+        self.shared
+            .generation
+            .send_modify(|generation| *generation += 1);
 
         Ok(())
     }
+
+    /// Moves the database file (together with its auxiliary files) to `dst` while keeping this
+    /// pool (and all its clones) usable. The pool is closed first (waiting for the connections
+    /// currently in use to be released), then the files are moved and the pool reopened at the new
+    /// location. Attempts to acquire a connection in the meantime wait until the move is done. If
+    /// the destination is on a different filesystem, the files are copied and the originals
+    /// removed.
+    ///
+    /// Fails with `Error::Exists` if `dst` already exists (an existing file is never overwritten).
+    /// Fails with `Error::Busy` if some connections are not released in time. If the move fails,
+    /// the database is reopened at the original location.
+    pub(crate) async fn relocate(&self, dst: impl AsRef<Path>) -> Result<(), Error> {
+        let dst = dst.as_ref();
+        let _guard = self.shared.relocate_lock.lock().await;
+
+        if self.shared.closed.load(Ordering::Acquire) {
+            return Err(Error::Open(sqlx::Error::PoolClosed));
+        }
+
+        let old = self.shared.pools.read().unwrap().clone();
+        let src = old.conn_options.get_filename().to_owned();
+
+        // Fail early without closing the pool. Not relied upon though - the move itself fails if
+        // `dst` gets created in the meantime.
+        if fs::metadata(dst).await.is_ok() {
+            return Err(Error::Exists);
+        }
+
+        create_directory(dst).await?;
+
+        // Don't wait for the connections in use indefinitely: their holders might be waiting for
+        // this relocation to finish, which would stall both until `ACQUIRE_TIMEOUT`.
+        let result = match time::timeout(RELOCATE_CLOSE_TIMEOUT, old.close()).await {
+            Ok(()) => move_files(&src, dst).await,
+            Err(_) => Err(Error::Busy),
+        };
+
+        let path = if result.is_ok() { dst } else { &src };
+
+        let reopened = match Pools::create(old.conn_options.clone().filename(path)).await {
+            Ok(new) => {
+                *self.shared.pools.write().unwrap() = new;
+                Ok(())
+            }
+            Err(error) => {
+                // Can't reopen the database. Treat the pool as closed.
+                self.shared.closed.store(true, Ordering::Release);
+                Err(Error::Open(error))
+            }
+        };
+
+        // Wake up the tasks waiting for the relocation to finish.
+        self.shared
+            .generation
+            .send_modify(|generation| *generation += 1);
+
+        reopened?;
+        result
+    }
+
+    async fn acquire_from(
+        &self,
+        select: fn(&Pools) -> &SqlitePool,
+        location: &'static Location<'static>,
+    ) -> Result<PoolConnection, sqlx::Error> {
+        loop {
+            let (pool, mut generation_rx) = {
+                let pools = self.shared.pools.read().unwrap();
+                (select(&pools).clone(), self.shared.generation.subscribe())
+            };
+
+            match PoolConnection::acquire(&pool, location).await {
+                Err(sqlx::Error::PoolClosed) if !self.shared.closed.load(Ordering::Acquire) => {
+                    // The pool is being relocated. Wait for it to be reopened and try again. The
+                    // timeout prevents deadlock in case this task holds another connection which
+                    // the relocation is waiting for.
+                    time::timeout(ACQUIRE_TIMEOUT, generation_rx.changed())
+                        .await
+                        .map_err(|_| sqlx::Error::PoolTimedOut)?
+                        .ok();
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+// Moves the database file and its auxiliary files (if they exist). Either all of them are moved
+// or none.
+async fn move_files(src: &Path, dst: &Path) -> Result<(), Error> {
+    let mut moved = Vec::new();
+
+    for suffix in ["", "-wal", "-shm"] {
+        let src = append_to_path(src, suffix);
+        let dst = append_to_path(dst, suffix);
+
+        if !suffix.is_empty() && fs::metadata(&src).await.is_err() {
+            continue;
+        }
+
+        if let Err(error) = move_file(&src, &dst).await {
+            for (src, dst) in moved.into_iter().rev() {
+                move_file(&dst, &src).await.ok();
+            }
+
+            return Err(error);
+        }
+
+        moved.push((src, dst));
+    }
+
+    Ok(())
+}
+
+// Moves a single file without overwriting `dst` if it exists. `fs::rename` is not used because it
+// silently replaces an existing `dst` on unix. Creating a hard link instead fails atomically if
+// `dst` exists.
+async fn move_file(src: &Path, dst: &Path) -> Result<(), Error> {
+    match fs::hard_link(src, dst).await {
+        Ok(()) => (),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => return Err(Error::Exists),
+        // Linking fails when moving across filesystems (or on filesystems without hard links).
+        // Fall back to copying.
+        Err(_) => copy_file(src, dst).await?,
+    }
+
+    if let Err(error) = fs::remove_file(src).await {
+        // Don't leave two copies of the database behind.
+        fs::remove_file(dst).await.ok();
+        return Err(Error::Move(error));
+    }
+
+    Ok(())
+}
+
+// Copies `src` to `dst`, failing if `dst` already exists.
+async fn copy_file(src: &Path, dst: &Path) -> Result<(), Error> {
+    let mut reader = fs::File::open(src).await.map_err(Error::Move)?;
+    let mut writer = match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)
+        .await
+    {
+        Ok(writer) => writer,
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => return Err(Error::Exists),
+        Err(error) => return Err(Error::Move(error)),
+    };
+
+    let result = async {
+        tokio::io::copy(&mut reader, &mut writer).await?;
+        writer.sync_all().await
+    }
+    .await;
+
+    if let Err(error) = result {
+        drop(writer);
+        fs::remove_file(dst).await.ok();
+        return Err(Error::Move(error));
+    }
+
+    Ok(())
+}
+
+fn append_to_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
 }
 
 /// Database connection from pool
@@ -161,11 +416,7 @@ pub struct ReadTransaction {
 
 impl ReadTransaction {
     // Internal
-    async fn begin(
-        pool: &SqlitePool,
-        location: &'static Location<'static>,
-    ) -> Result<Self, sqlx::Error> {
-        let mut inner = PoolConnection::acquire(pool, location).await?;
+    async fn begin(mut inner: PoolConnection) -> Result<Self, sqlx::Error> {
         SqliteTransactionManager::begin(&mut inner.inner).await?;
 
         Ok(Self {
@@ -348,9 +599,19 @@ pub(crate) async fn create_temp() -> Result<(TempDir, Pool), Error> {
     Ok((temp_dir, pool))
 }
 
-/// Opens a connection to the specified database. Fails if the db doesn't exist.
+/// Opens a connection to the specified database. Fails if the db doesn't exist or if it's not a
+/// repository database.
 pub(crate) async fn open(path: impl AsRef<Path>) -> Result<Pool, Error> {
+    let path = path.as_ref();
+
+    check_header(path).await?;
+
     let connect_options = SqliteConnectOptions::new().filename(path);
+
+    // Check this before creating the pool (which switches the db to WAL mode) and running the
+    // migrations so we don't modify foreign databases.
+    check_schema(connect_options.clone()).await?;
+
     let pool = Pool::create(connect_options).await.map_err(Error::Open)?;
 
     migrations::run(&pool).await?;
@@ -366,6 +627,60 @@ pub async fn open_without_migrations(path: impl AsRef<Path>) -> Result<Pool, Err
     Ok(pool)
 }
 
+/// Checks that the file at `path` is a sqlite database. This is done by reading the file header
+/// directly (without opening it with sqlite) which allows to distinguish truncated or unrelated
+/// files from other errors. Missing or unreadable files are not checked here.
+async fn check_header(path: &Path) -> Result<(), Error> {
+    const MAGIC: &[u8] = b"SQLite format 3\0";
+    const HEADER_SIZE: usize = 100;
+
+    let mut file = match fs::File::open(path).await {
+        Ok(file) => file,
+        Err(_) => return Ok(()),
+    };
+
+    let mut header = [0; HEADER_SIZE];
+
+    match file.read_exact(&mut header).await {
+        Ok(_) => (),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            return Err(Error::NotARepository)
+        }
+        Err(_) => return Ok(()),
+    }
+
+    if header.starts_with(MAGIC) {
+        Ok(())
+    } else {
+        Err(Error::NotARepository)
+    }
+}
+
+/// Checks that the database is a repository database by looking for a table that every repository
+/// database has (it's created by the very first migration). Uses a separate read-only connection so
+/// the database is not modified in any way.
+async fn check_schema(connect_options: SqliteConnectOptions) -> Result<(), Error> {
+    let mut conn = connect_options
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(Error::Open)?;
+
+    let result = sqlx::query(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'metadata_public'",
+    )
+    .fetch_optional(&mut conn)
+    .await;
+
+    conn.close().await.ok();
+
+    if result?.is_some() {
+        Ok(())
+    } else {
+        Err(Error::NotARepository)
+    }
+}
+
 async fn create_directory(path: &Path) -> Result<(), Error> {
     if let Some(dir) = path.parent() {
         fs::create_dir_all(dir)
@@ -396,8 +711,14 @@ pub enum Error {
     Exists,
     #[error("failed to open database")]
     Open(#[source] sqlx::Error),
+    #[error("not a repository")]
+    NotARepository,
     #[error("failed to execute database query")]
     Query(#[from] sqlx::Error),
+    #[error("failed to move database")]
+    Move(#[source] io::Error),
+    #[error("database is busy")]
+    Busy,
 }
 
 async fn get_pragma(conn: &mut Connection, name: &str) -> Result<u32, Error> {
@@ -442,4 +763,24 @@ mod tests {
         assert_eq!(encode_u64(u64::MAX / 2 + 1), i64::MIN);
         assert_eq!(encode_u64(u64::MAX), -1);
     }
+
+    #[tokio::test]
+    async fn move_file_does_not_overwrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let src = temp_dir.path().join("src.db");
+        let dst = temp_dir.path().join("dst.db");
+
+        fs::write(&src, b"src").await.unwrap();
+        fs::write(&dst, b"dst").await.unwrap();
+
+        assert!(matches!(move_file(&src, &dst).await, Err(Error::Exists)));
+        assert!(matches!(copy_file(&src, &dst).await, Err(Error::Exists)));
+        assert_eq!(fs::read(&src).await.unwrap(), b"src");
+        assert_eq!(fs::read(&dst).await.unwrap(), b"dst");
+
+        fs::remove_file(&dst).await.unwrap();
+        move_file(&src, &dst).await.unwrap();
+        assert!(fs::metadata(&src).await.is_err());
+        assert_eq!(fs::read(&dst).await.unwrap(), b"src");
+    }
 }
@@ -1,6 +1,6 @@
 //! Directory content
 
-use super::entry_data::EntryData;
+use super::entry_data::{EntryData, FileMetadata};
 use crate::{
     blob::BlobId,
     error::{Error, Result},
@@ -34,7 +34,7 @@ impl Content {
     pub fn deserialize(mut input: &[u8]) -> Result<Self> {
         let version = vint64::decode(&mut input).map_err(|_| Error::MalformedDirectory)?;
         let entries = match version {
-            VERSION => deserialize_entries(input),
+            VERSION => deserialize_v2(input),
             1 => Ok(v2::from_v1(deserialize_entries(input)?)),
             0 => Ok(v2::from_v1(v1::from_v0(deserialize_entries(input)?))),
             _ => Err(Error::StorageVersionMismatch),
@@ -48,6 +48,27 @@ impl Content {
         output.extend_from_slice(vint64::encode(VERSION).as_ref());
         bincode::serialize_into(&mut output, &self.entries)
             .expect("failed to serialize directory content");
+
+        // The file metadata goes into a trailer after the entries. Older versions ignore trailing
+        // bytes so they can still read the directory, they just don't see the metadata (and drop
+        // it if they modify the directory).
+        let metadata: BTreeMap<_, _> = self
+            .entries
+            .iter()
+            .filter_map(|(name, data)| match data {
+                EntryData::File(data) if !data.metadata.is_empty() => Some((name, &data.metadata)),
+                EntryData::File(_)
+                | EntryData::Directory(_)
+                | EntryData::Tombstone(_)
+                | EntryData::Link(_) => None,
+            })
+            .collect();
+
+        if !metadata.is_empty() {
+            bincode::serialize_into(&mut output, &metadata)
+                .expect("failed to serialize directory content");
+        }
+
         output
     }
 
@@ -112,6 +133,19 @@ impl Content {
         ))
     }
 
+    /// Replaces the metadata of the file entry at `name`.
+    pub fn set_metadata(&mut self, name: &str, metadata: FileMetadata) -> Result<()> {
+        match self.entries.get_mut(name) {
+            Some(EntryData::File(data)) => {
+                data.metadata = metadata;
+                Ok(())
+            }
+            Some(EntryData::Directory(_)) => Err(Error::EntryIsDirectory),
+            Some(EntryData::Link(_)) => Err(Error::EntryIsLink),
+            Some(EntryData::Tombstone(_)) | None => Err(Error::EntryNotFound),
+        }
+    }
+
     /// Initial version vector for a new entry to be inserted.
     pub fn initial_version_vector(&self, name: &str) -> VersionVector {
         if let Some(EntryData::Tombstone(entry)) = self.entries.get(name) {
@@ -150,6 +184,27 @@ fn deserialize_entries<'a, T: Deserialize<'a>>(input: &'a [u8]) -> Result<T, Err
     bincode::deserialize(input).map_err(|_| Error::MalformedDirectory)
 }
 
+// Deserializes the entries followed by the optional file metadata trailer (see
+// `Content::serialize`).
+fn deserialize_v2(mut input: &[u8]) -> Result<v2::Entries, Error> {
+    let mut entries: v2::Entries =
+        bincode::deserialize_from(&mut input).map_err(|_| Error::MalformedDirectory)?;
+
+    if input.is_empty() {
+        return Ok(entries);
+    }
+
+    let metadata: BTreeMap<String, FileMetadata> = deserialize_entries(input)?;
+
+    for (name, metadata) in metadata {
+        if let Some(EntryData::File(data)) = entries.get_mut(&name) {
+            data.metadata = metadata;
+        }
+    }
+
+    Ok(entries)
+}
+
 fn check_replace(old: &EntryData, new: &EntryData) -> Result<Option<BlobId>, EntryExists> {
     // Replace entries only if the new version is more up to date than the old version.
 
@@ -174,10 +229,21 @@ fn check_replace(old: &EntryData, new: &EntryData) -> Result<Option<BlobId>, Ent
             EntryData::Tombstone(new),
             EntryData::Tombstone(old),
         ) if new.cause == old.cause => Err(EntryExists::Same),
+        (Some(Ordering::Equal | Ordering::Less), EntryData::Link(new), EntryData::Link(old))
+            if new.target == old.target =>
+        {
+            Err(EntryExists::Same)
+        }
         (
             Some(Ordering::Equal | Ordering::Less),
-            EntryData::File(_) | EntryData::Directory(_) | EntryData::Tombstone(_),
-            EntryData::File(_) | EntryData::Directory(_) | EntryData::Tombstone(_),
+            EntryData::File(_)
+            | EntryData::Directory(_)
+            | EntryData::Tombstone(_)
+            | EntryData::Link(_),
+            EntryData::File(_)
+            | EntryData::Directory(_)
+            | EntryData::Tombstone(_)
+            | EntryData::Link(_),
         ) => Err(EntryExists::Different),
         (None, _, _) => Err(EntryExists::Different),
     }
@@ -196,7 +262,10 @@ mod v2 {
         v1.into_iter()
             .map(|(name, data)| {
                 let data = match data {
-                    v1::EntryData::File(data) => EntryData::File(data),
+                    v1::EntryData::File(v1::EntryFileData {
+                        blob_id,
+                        version_vector,
+                    }) => EntryData::file(blob_id, version_vector),
                     v1::EntryData::Directory(data) => EntryData::Directory(data),
                     v1::EntryData::Tombstone(v1::EntryTombstoneData { version_vector }) => {
                         EntryData::Tombstone(EntryTombstoneData {
@@ -215,7 +284,7 @@ mod v2 {
 mod v1 {
     use super::v0;
     use std::collections::BTreeMap;
-    pub(super) use v0::{EntryData, EntryTombstoneData};
+    pub(super) use v0::{EntryData, EntryFileData, EntryTombstoneData};
 
     pub(super) type Entries = BTreeMap<String, v0::EntryData>;
 
@@ -244,8 +313,8 @@ mod v1 {
 }
 
 mod v0 {
-    use super::super::entry_data::{EntryDirectoryData, EntryFileData};
-    use crate::{crypto::sign::PublicKey, version_vector::VersionVector};
+    use super::super::entry_data::EntryDirectoryData;
+    use crate::{blob::BlobId, crypto::sign::PublicKey, version_vector::VersionVector};
     use serde::Deserialize;
     use std::collections::BTreeMap;
 
@@ -258,6 +327,12 @@ mod v0 {
         Tombstone(EntryTombstoneData),
     }
 
+    #[derive(Deserialize)]
+    pub(super) struct EntryFileData {
+        pub blob_id: BlobId,
+        pub version_vector: VersionVector,
+    }
+
     #[derive(Deserialize)]
     pub(super) struct EntryTombstoneData {
         pub version_vector: VersionVector,
@@ -1,6 +1,9 @@
 use super::{
     content::Content,
-    entry_data::{EntryData, EntryDirectoryData, EntryFileData, EntryTombstoneData},
+    entry_data::{
+        EntryData, EntryDirectoryData, EntryFileData, EntryLinkData, EntryTombstoneData,
+        FileMetadata,
+    },
     parent_context::ParentContext,
     Directory, DirectoryFallback, DirectoryLocking,
 };
@@ -23,6 +26,7 @@ pub enum EntryRef<'a> {
     File(FileRef<'a>),
     Directory(DirectoryRef<'a>),
     Tombstone(TombstoneRef<'a>),
+    Link(LinkRef<'a>),
 }
 
 impl<'a> EntryRef<'a> {
@@ -33,6 +37,7 @@ impl<'a> EntryRef<'a> {
             EntryData::File(entry_data) => Self::File(FileRef { entry_data, inner }),
             EntryData::Directory(entry_data) => Self::Directory(DirectoryRef { entry_data, inner }),
             EntryData::Tombstone(entry_data) => Self::Tombstone(TombstoneRef { entry_data, inner }),
+            EntryData::Link(entry_data) => Self::Link(LinkRef { entry_data, inner }),
         }
     }
 
@@ -41,6 +46,7 @@ impl<'a> EntryRef<'a> {
             Self::File(r) => r.name(),
             Self::Directory(r) => r.name(),
             Self::Tombstone(r) => r.name(),
+            Self::Link(r) => r.name(),
         }
     }
 
@@ -49,6 +55,7 @@ impl<'a> EntryRef<'a> {
             Self::File(f) => f.version_vector(),
             Self::Directory(d) => d.version_vector(),
             Self::Tombstone(t) => t.version_vector(),
+            Self::Link(l) => l.version_vector(),
         }
     }
 
@@ -57,6 +64,7 @@ impl<'a> EntryRef<'a> {
             Self::File(r) => Ok(r),
             Self::Directory(_) => Err(Error::EntryIsDirectory),
             Self::Tombstone(_) => Err(Error::EntryNotFound),
+            Self::Link(_) => Err(Error::EntryIsLink),
         }
     }
 
@@ -65,6 +73,16 @@ impl<'a> EntryRef<'a> {
             Self::File(_) => Err(Error::EntryIsFile),
             Self::Directory(r) => Ok(r),
             Self::Tombstone(_) => Err(Error::EntryNotFound),
+            Self::Link(_) => Err(Error::EntryIsLink),
+        }
+    }
+
+    pub fn link(self) -> Result<LinkRef<'a>> {
+        match self {
+            Self::File(_) => Err(Error::EntryIsFile),
+            Self::Directory(_) => Err(Error::EntryIsDirectory),
+            Self::Tombstone(_) => Err(Error::EntryNotFound),
+            Self::Link(r) => Ok(r),
         }
     }
 
@@ -76,6 +94,10 @@ impl<'a> EntryRef<'a> {
         matches!(self, Self::Directory(_))
     }
 
+    pub fn is_link(&self) -> bool {
+        matches!(self, Self::Link(_))
+    }
+
     pub fn is_tombstone(&self) -> bool {
         matches!(self, Self::Tombstone(_))
     }
@@ -93,6 +115,7 @@ impl<'a> EntryRef<'a> {
             Self::File(e) => EntryData::File(e.data().clone()),
             Self::Directory(e) => EntryData::Directory(e.data().clone()),
             Self::Tombstone(e) => EntryData::Tombstone(e.data().clone()),
+            Self::Link(e) => EntryData::Link(e.data().clone()),
         }
     }
 
@@ -101,6 +124,7 @@ impl<'a> EntryRef<'a> {
             Self::File(r) => &r.inner,
             Self::Directory(r) => &r.inner,
             Self::Tombstone(r) => &r.inner,
+            Self::Link(r) => &r.inner,
         }
     }
 }
@@ -140,6 +164,11 @@ impl<'a> FileRef<'a> {
         &self.entry_data.version_vector
     }
 
+    /// User defined metadata of this file. See [`File::set_metadata`].
+    pub fn metadata(&self) -> &'a FileMetadata {
+        &self.entry_data.metadata
+    }
+
     pub async fn open(&self) -> Result<File> {
         let parent_context = self.inner.parent_context();
         let branch = self.branch().clone();
@@ -163,6 +192,28 @@ impl<'a> FileRef<'a> {
         Ok(())
     }
 
+    /// Forks the file into `dst_branch` (if it's not there already) and merges `merge` into the
+    /// version vector of the resulting entry. This makes this version supersede all the versions
+    /// whose version vectors are included in `merge`.
+    pub(crate) async fn fork_merged(
+        &self,
+        dst_branch: &Branch,
+        merge: &VersionVector,
+    ) -> Result<()> {
+        let parent_context = self.inner.parent_context();
+
+        if self.branch().id() == dst_branch.id() {
+            parent_context
+                .merge(dst_branch.clone(), merge.clone())
+                .await
+        } else {
+            parent_context
+                .fork_merged(self.branch(), dst_branch, merge)
+                .await?;
+            Ok(())
+        }
+    }
+
     pub fn branch(&self) -> &Branch {
         self.inner.branch()
     }
@@ -220,6 +271,23 @@ impl<'a> DirectoryRef<'a> {
         .await
     }
 
+    /// Opens this directory within the given transaction, without locking.
+    pub(crate) async fn open_in(
+        &self,
+        tx: &mut ReadTransaction,
+        fallback: DirectoryFallback,
+    ) -> Result<Directory> {
+        Directory::open_in(
+            None,
+            tx,
+            self.branch().clone(),
+            *self.blob_id(),
+            Some(self.inner.parent_context()),
+            fallback,
+        )
+        .await
+    }
+
     pub(super) async fn open_snapshot(
         &self,
         tx: &mut ReadTransaction,
@@ -281,6 +349,72 @@ impl fmt::Debug for TombstoneRef<'_> {
     }
 }
 
+#[derive(Copy, Clone)]
+pub struct LinkRef<'a> {
+    entry_data: &'a EntryLinkData,
+    inner: RefInner<'a>,
+}
+
+impl<'a> LinkRef<'a> {
+    pub fn name(&self) -> &'a str {
+        self.inner.name
+    }
+
+    /// Path this link points to.
+    pub fn target(&self) -> &'a str {
+        &self.entry_data.target
+    }
+
+    pub fn version_vector(&self) -> &'a VersionVector {
+        &self.entry_data.version_vector
+    }
+
+    pub fn branch(&self) -> &'a Branch {
+        self.inner.branch()
+    }
+
+    pub fn parent(&self) -> &'a Directory {
+        self.inner.parent
+    }
+
+    pub(crate) fn data(&self) -> &EntryLinkData {
+        self.entry_data
+    }
+
+    /// Forks the link into `dst_branch` (if it's not there already) and merges `merge` into the
+    /// version vector of the resulting entry. Links have no blob so this only copies the entry
+    /// itself.
+    pub(crate) async fn fork_merged(
+        &self,
+        dst_branch: &Branch,
+        merge: &VersionVector,
+    ) -> Result<()> {
+        if self.branch().id() == dst_branch.id() {
+            return self
+                .inner
+                .parent_context()
+                .merge(dst_branch.clone(), merge.clone())
+                .await;
+        }
+
+        let mut data = self.entry_data.clone();
+        data.version_vector.merge(merge);
+
+        let mut directory = self.inner.parent.fork(dst_branch).await?;
+        directory.fork_link(self.name(), data).await
+    }
+}
+
+impl fmt::Debug for LinkRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LinkRef")
+            .field("name", &self.inner.name)
+            .field("vv", &self.entry_data.version_vector)
+            .field("target", &self.entry_data.target)
+            .finish()
+    }
+}
+
 #[derive(Copy, Clone)]
 struct RefInner<'a> {
     parent: &'a Directory,
@@ -1,13 +1,41 @@
-use crate::{blob::BlobId, version_vector::VersionVector};
-use serde::{Deserialize, Serialize};
+use crate::{
+    blob::BlobId,
+    error::{Error, Result},
+    version_vector::VersionVector,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+
+/// User defined metadata of a file (e.g., its content type), as key-value pairs.
+pub type FileMetadata = BTreeMap<String, String>;
+
+/// Maximum total size (sum of the lengths of all keys and values) of the metadata of a single
+/// file. The metadata is stored inline in the parent directory so it needs to be kept small.
+pub const MAX_FILE_METADATA_SIZE: usize = 4 * 1024;
+
+/// Returns `InvalidArgument` if `metadata` exceeds `MAX_FILE_METADATA_SIZE`.
+pub(crate) fn check_metadata_size(metadata: &FileMetadata) -> Result<()> {
+    let size: usize = metadata
+        .iter()
+        .map(|(key, value)| key.len() + value.len())
+        .sum();
+
+    if size <= MAX_FILE_METADATA_SIZE {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument)
+    }
+}
 
 //--------------------------------------------------------------------
 
-#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[serde(from = "Repr")]
 pub(crate) enum EntryData {
     File(EntryFileData),
     Directory(EntryDirectoryData),
     Tombstone(EntryTombstoneData),
+    Link(EntryLinkData),
 }
 
 impl EntryData {
@@ -15,6 +43,7 @@ impl EntryData {
         Self::File(EntryFileData {
             blob_id,
             version_vector,
+            metadata: FileMetadata::new(),
         })
     }
 
@@ -25,11 +54,19 @@ impl EntryData {
         })
     }
 
+    pub fn link(target: String, version_vector: VersionVector) -> Self {
+        Self::Link(EntryLinkData {
+            target,
+            version_vector,
+        })
+    }
+
     pub fn version_vector(&self) -> &VersionVector {
         match self {
             Self::File(f) => &f.version_vector,
             Self::Directory(d) => &d.version_vector,
             Self::Tombstone(t) => &t.version_vector,
+            Self::Link(l) => &l.version_vector,
         }
     }
 
@@ -38,6 +75,7 @@ impl EntryData {
             Self::File(f) => &mut f.version_vector,
             Self::Directory(d) => &mut d.version_vector,
             Self::Tombstone(t) => &mut t.version_vector,
+            Self::Link(l) => &mut l.version_vector,
         }
     }
 
@@ -45,17 +83,83 @@ impl EntryData {
         match self {
             Self::File(f) => Some(&f.blob_id),
             Self::Directory(d) => Some(&d.blob_id),
-            Self::Tombstone(_) => None,
+            Self::Tombstone(_) | Self::Link(_) => None,
+        }
+    }
+}
+
+// Files are always serialized the same way as before metadata was introduced, without the metadata.
+// The metadata is stored separately, after the entries (see `Content::serialize`), so that older
+// versions can still decode the directory.
+impl Serialize for EntryData {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        const NAME: &str = "EntryData";
+
+        match self {
+            Self::File(data) => serializer.serialize_newtype_variant(
+                NAME,
+                0,
+                "File",
+                &PlainFileDataRef {
+                    blob_id: &data.blob_id,
+                    version_vector: &data.version_vector,
+                },
+            ),
+            Self::Directory(data) => {
+                serializer.serialize_newtype_variant(NAME, 1, "Directory", data)
+            }
+            Self::Tombstone(data) => {
+                serializer.serialize_newtype_variant(NAME, 2, "Tombstone", data)
+            }
+            Self::Link(data) => serializer.serialize_newtype_variant(NAME, 3, "Link", data),
+        }
+    }
+}
+
+/// Serialized representation of `EntryData`.
+#[derive(Deserialize)]
+#[serde(rename = "EntryData")]
+enum Repr {
+    File(PlainFileData),
+    Directory(EntryDirectoryData),
+    Tombstone(EntryTombstoneData),
+    Link(EntryLinkData),
+    // Note: new variants must be added at the end to keep the serialized format compatible.
+}
+
+impl From<Repr> for EntryData {
+    fn from(repr: Repr) -> Self {
+        match repr {
+            Repr::File(data) => Self::file(data.blob_id, data.version_vector),
+            Repr::Directory(data) => Self::Directory(data),
+            Repr::Tombstone(data) => Self::Tombstone(data),
+            Repr::Link(data) => Self::Link(data),
         }
     }
 }
 
+#[derive(Deserialize)]
+struct PlainFileData {
+    blob_id: BlobId,
+    version_vector: VersionVector,
+}
+
+#[derive(Serialize)]
+struct PlainFileDataRef<'a> {
+    blob_id: &'a BlobId,
+    version_vector: &'a VersionVector,
+}
+
 //--------------------------------------------------------------------
 
 #[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct EntryFileData {
     pub blob_id: BlobId,
     pub version_vector: VersionVector,
+    pub metadata: FileMetadata,
 }
 
 impl Clone for EntryFileData {
@@ -63,13 +167,16 @@ impl Clone for EntryFileData {
         Self {
             blob_id: self.blob_id,
             version_vector: self.version_vector.clone(),
+            metadata: self.metadata.clone(),
         }
     }
 }
 
 impl PartialEq for EntryFileData {
     fn eq(&self, other: &Self) -> bool {
-        self.blob_id == other.blob_id && self.version_vector == other.version_vector
+        self.blob_id == other.blob_id
+            && self.version_vector == other.version_vector
+            && self.metadata == other.metadata
     }
 }
 
@@ -81,6 +188,13 @@ pub(crate) struct EntryDirectoryData {
     pub version_vector: VersionVector,
 }
 
+/// Entry that points to another entry by its path (similar to a symbolic link).
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+pub(crate) struct EntryLinkData {
+    pub target: String,
+    pub version_vector: VersionVector,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
 pub(crate) struct EntryTombstoneData {
     pub cause: TombstoneCause,
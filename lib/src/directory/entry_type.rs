@@ -20,4 +20,5 @@ use serde::{Deserialize, Serialize};
 pub enum EntryType {
     File = 1,
     Directory = 2,
+    Link = 3,
 }
@@ -8,17 +8,20 @@ mod tests;
 
 pub use self::{
     content::VERSION as DIRECTORY_VERSION,
-    entry::{DirectoryRef, EntryRef, FileRef},
+    entry::{DirectoryRef, EntryRef, FileRef, LinkRef},
+    entry_data::{FileMetadata, MAX_FILE_METADATA_SIZE},
     entry_type::EntryType,
 };
 pub(crate) use self::{
-    entry_data::{EntryData, EntryTombstoneData, TombstoneCause},
+    entry_data::{
+        check_metadata_size, EntryData, EntryLinkData, EntryTombstoneData, TombstoneCause,
+    },
     parent_context::ParentContext,
 };
 
-use self::content::Content;
+use self::content::{Content, EntryExists};
 use crate::{
-    blob::{lock::ReadLock, Blob, BlobId},
+    blob::{self, lock::ReadLock, Blob, BlobId},
     branch::Branch,
     crypto::sign::PublicKey,
     debug::DebugPrinter,
@@ -51,6 +54,15 @@ impl Directory {
         Self::open(branch, BlobId::ROOT, None, locking, fallback).await
     }
 
+    /// Opens the root directory within the given transaction, without locking.
+    pub(crate) async fn open_root_in(
+        tx: &mut ReadTransaction,
+        branch: Branch,
+        fallback: DirectoryFallback,
+    ) -> Result<Self> {
+        Self::open_in(None, tx, branch, BlobId::ROOT, None, fallback).await
+    }
+
     /// Opens the root directory or creates it if it doesn't exists.
     ///
     /// See [`Self::create_directory`] for info about the `merge` parameter.
@@ -153,6 +165,179 @@ impl Directory {
         Ok(file)
     }
 
+    /// Creates a new file in this directory which is a copy of the blob `src_blob_id` from
+    /// `src_branch`. The copy shares the blocks with the original so no content is re-encrypted or
+    /// duplicated in the store. Fails with `EntryExists` if an entry with the same name already
+    /// exists.
+    pub(crate) async fn copy_file(
+        &mut self,
+        name: String,
+        src_branch: &Branch,
+        src_blob_id: BlobId,
+    ) -> Result<()> {
+        let mut tx = self.branch().store().begin_write().await?;
+        let mut changeset = Changeset::new();
+
+        self.refresh_in(&mut tx).await?;
+
+        match self.lookup(&name) {
+            Ok(EntryRef::File(_) | EntryRef::Directory(_) | EntryRef::Link(_)) => {
+                return Err(Error::EntryExists)
+            }
+            Ok(EntryRef::Tombstone(_)) | Err(Error::EntryNotFound) => (),
+            Err(error) => return Err(error),
+        }
+
+        let blob_id = rand::random();
+        blob::copy(&mut tx, &mut changeset, src_branch, src_blob_id, blob_id).await?;
+
+        let version_vector = self
+            .content
+            .initial_version_vector(&name)
+            .incremented(*self.branch().id());
+        let data = EntryData::file(blob_id, version_vector);
+
+        let content = self
+            .begin_insert_entry(&mut tx, &mut changeset, name, data)
+            .await?;
+
+        self.commit(tx, changeset).await?;
+        self.finalize(content);
+
+        Ok(())
+    }
+
+    /// Prepares a new file in this directory without inserting it yet. The file gets inserted when
+    /// it's saved using `File::insert_in`. Used by `Batch`.
+    pub(crate) fn prepare_file(&self, name: String) -> Result<File> {
+        match self.lookup(&name) {
+            Ok(EntryRef::File(_) | EntryRef::Directory(_) | EntryRef::Link(_)) => {
+                return Err(Error::EntryExists)
+            }
+            Ok(EntryRef::Tombstone(_)) | Err(Error::EntryNotFound) => (),
+            Err(error) => return Err(error),
+        }
+
+        let blob_id = rand::random();
+        let parent = self.create_parent_context(name);
+
+        Ok(File::create(
+            self.branch().clone(),
+            Locator::head(blob_id),
+            parent,
+        ))
+    }
+
+    /// Prepares a new subdirectory of this directory without saving it. It's saved and inserted into
+    /// this directory later with [`Self::insert_in`]. Used by `Batch`.
+    pub(crate) fn prepare_directory(&self, name: String) -> Result<Self> {
+        match self.lookup(&name) {
+            Ok(EntryRef::File(_) | EntryRef::Directory(_) | EntryRef::Link(_)) => {
+                return Err(Error::EntryExists)
+            }
+            Ok(EntryRef::Tombstone(_)) | Err(Error::EntryNotFound) => (),
+            Err(error) => return Err(error),
+        }
+
+        let blob_id = rand::random();
+        let lock = self
+            .branch()
+            .locker()
+            .try_read(blob_id)
+            .map_err(|_| Error::EntryExists)?;
+        let parent = self.create_parent_context(name);
+
+        Ok(Self::create(
+            lock,
+            self.branch().clone(),
+            blob_id,
+            Some(parent),
+        ))
+    }
+
+    /// Saves this directory prepared with [`Self::prepare_directory`] and inserts its entry into
+    /// the parent directory within the given transaction which is not committed. Used by `Batch`.
+    pub(crate) async fn insert_in(&mut self, tx: &mut WriteTransaction) -> Result<()> {
+        let parent = self.parent.clone().ok_or(Error::OperationNotSupported)?;
+        let mut changeset = Changeset::new();
+
+        self.save(tx, &mut changeset, &Content::empty()).await?;
+        parent
+            .insert_directory(tx, &mut changeset, self.branch().clone(), *self.blob_id())
+            .await?;
+
+        changeset
+            .apply(
+                tx,
+                self.branch().id(),
+                self.branch()
+                    .keys()
+                    .write()
+                    .ok_or(Error::PermissionDenied)?,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Creates a new link inside this directory pointing to `target`. The target is stored as is
+    /// and is resolved only when the link is being followed, so it doesn't need to exist.
+    pub async fn create_link(&mut self, name: String, target: String) -> Result<()> {
+        let mut tx = self.branch().store().begin_write().await?;
+        let mut changeset = Changeset::new();
+
+        self.refresh_in(&mut tx).await?;
+
+        match self.lookup(&name) {
+            Ok(EntryRef::File(_) | EntryRef::Directory(_) | EntryRef::Link(_)) => {
+                return Err(Error::EntryExists)
+            }
+            Ok(EntryRef::Tombstone(_)) | Err(Error::EntryNotFound) => (),
+            Err(error) => return Err(error),
+        }
+
+        let version_vector = self
+            .content
+            .initial_version_vector(&name)
+            .incremented(*self.branch().id());
+        let data = EntryData::link(target, version_vector);
+
+        let content = self
+            .begin_insert_entry(&mut tx, &mut changeset, name, data)
+            .await?;
+
+        self.commit(tx, changeset).await?;
+        self.finalize(content);
+
+        Ok(())
+    }
+
+    /// Inserts a link forked from another branch into this directory. Inserting a link that's
+    /// already there (same target and the same or newer version) is a no-op.
+    pub(crate) async fn fork_link(&mut self, name: &str, data: EntryLinkData) -> Result<()> {
+        let mut tx = self.branch().store().begin_write().await?;
+        let mut changeset = Changeset::new();
+
+        self.refresh_in(&mut tx).await?;
+
+        let data = EntryData::Link(data);
+
+        match self.content.check_insert(name, &data) {
+            Ok(_) => (),
+            Err(EntryExists::Same) => return Ok(()),
+            Err(EntryExists::Different) => return Err(Error::EntryExists),
+        }
+
+        let content = self
+            .begin_insert_entry(&mut tx, &mut changeset, name.to_owned(), data)
+            .await?;
+
+        self.commit(tx, changeset).await?;
+        self.finalize(content);
+
+        Ok(())
+    }
+
     /// Creates a new subdirectory of this directory.
     ///
     /// `blob_id` is the blob id of the directory to be created. It must be unique. The easiest way
@@ -290,7 +475,8 @@ impl Directory {
         let mut changeset = Changeset::new();
 
         match self.lookup(name) {
-            Ok(EntryRef::File(_) | EntryRef::Directory(_)) | Err(Error::EntryNotFound) => (),
+            Ok(EntryRef::File(_) | EntryRef::Directory(_) | EntryRef::Link(_))
+            | Err(Error::EntryNotFound) => (),
             Ok(EntryRef::Tombstone(old_entry)) => {
                 // Attempt to replace a tombstone with another tombstone whose version vector is
                 // the same or lower is a no-op.
@@ -518,7 +704,8 @@ impl Directory {
     ) -> Result<(WriteTransaction, Option<ReadLock>, VersionVector)> {
         let mut old_blob_id = match self.lookup(name) {
             Ok(EntryRef::Directory(entry)) => Some(*entry.blob_id()),
-            Ok(EntryRef::File(_) | EntryRef::Tombstone(_)) | Err(Error::EntryNotFound) => None,
+            Ok(EntryRef::File(_) | EntryRef::Tombstone(_) | EntryRef::Link(_))
+            | Err(Error::EntryNotFound) => None,
             Err(error) => return Err(error),
         };
 
@@ -551,6 +738,7 @@ impl Directory {
                     continue;
                 }
                 (Ok(EntryRef::File(_)), _) => return Err(Error::EntryIsFile),
+                (Ok(EntryRef::Link(_)), _) => return Err(Error::EntryIsLink),
                 (Err(error), _) => return Err(error),
             }
         }
@@ -589,7 +777,9 @@ impl Directory {
 
         let entry = match self_content.get_mut(name) {
             Some(EntryData::Directory(entry)) => entry,
-            Some(EntryData::File(_) | EntryData::Tombstone(_)) | None => unreachable!(),
+            Some(EntryData::File(_) | EntryData::Tombstone(_) | EntryData::Link(_)) | None => {
+                unreachable!()
+            }
         };
 
         let bump = Bump::Merge(initial_vv);
@@ -762,7 +952,7 @@ impl Directory {
                         }
                     }
                 }
-                EntryData::Tombstone(_) => {}
+                EntryData::Tombstone(_) | EntryData::Link(_) => {}
             }
         }
     }
@@ -967,6 +1157,19 @@ pub(crate) async fn bump_root(branch: &Branch, merge: VersionVector) -> Result<(
     commit(tx, changeset, branch).await
 }
 
+/// Parses the serialized content of a directory blob and returns the blob ids of its file and
+/// subdirectory entries.
+pub(crate) fn parse_child_blobs(content: &[u8]) -> Result<Vec<(BlobId, EntryType)>> {
+    Ok(Content::deserialize(content)?
+        .iter()
+        .filter_map(|(_, data)| match data {
+            EntryData::File(data) => Some((data.blob_id, EntryType::File)),
+            EntryData::Directory(data) => Some((data.blob_id, EntryType::Directory)),
+            EntryData::Tombstone(_) | EntryData::Link(_) => None,
+        })
+        .collect())
+}
+
 // Load directory content. On missing block, fallback to previous snapshot (if any).
 async fn load(
     tx: &mut ReadTransaction,
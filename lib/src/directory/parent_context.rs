@@ -6,7 +6,7 @@ use crate::{
         lock::{LockKind, ReadLock},
     },
     branch::Branch,
-    directory::{content::EntryExists, Directory},
+    directory::{content::EntryExists, Directory, EntryData, EntryRef, FileMetadata},
     error::Result,
     protocol::Bump,
     store::{Changeset, ReadTransaction},
@@ -62,9 +62,119 @@ impl ParentContext {
         Ok(())
     }
 
+    /// Replaces the metadata of this entry (which must be a file) and updates the version vectors
+    /// of it and all its ancestors.
+    pub async fn set_metadata(
+        &self,
+        tx: &mut ReadTransaction,
+        changeset: &mut Changeset,
+        branch: Branch,
+        metadata: FileMetadata,
+        bump: Bump,
+    ) -> Result<()> {
+        let mut directory = self.open_in(tx, branch).await?;
+        let mut content = directory.content.clone();
+        content.set_metadata(&self.entry_name, metadata)?;
+        let diff = content.bump(&self.entry_name, bump)?;
+        directory.save(tx, changeset, &content).await?;
+        directory.bump(tx, changeset, Bump::Add(diff)).await?;
+
+        Ok(())
+    }
+
+    /// Returns the metadata of this entry.
+    pub async fn entry_metadata(&self, branch: Branch) -> Result<FileMetadata> {
+        match self.open(branch).await?.lookup(&self.entry_name)? {
+            EntryRef::File(file) => Ok(file.metadata().clone()),
+            EntryRef::Directory(_) => Err(Error::EntryIsDirectory),
+            EntryRef::Link(_) => Err(Error::EntryIsLink),
+            EntryRef::Tombstone(_) => Err(Error::EntryNotFound),
+        }
+    }
+
+    /// Inserts a new file entry pointing to `blob_id` under the name of this entry and updates the
+    /// version vectors of all the ancestors. Fails with `EntryExists` if such entry already exists.
+    pub async fn insert_file(
+        &self,
+        tx: &mut ReadTransaction,
+        changeset: &mut Changeset,
+        branch: Branch,
+        blob_id: BlobId,
+    ) -> Result<()> {
+        self.insert(tx, changeset, branch, |version_vector| {
+            EntryData::file(blob_id, version_vector)
+        })
+        .await
+    }
+
+    /// Inserts the entry of a new subdirectory with the given blob id into the parent directory and
+    /// updates the version vectors of all its ancestors.
+    pub async fn insert_directory(
+        &self,
+        tx: &mut ReadTransaction,
+        changeset: &mut Changeset,
+        branch: Branch,
+        blob_id: BlobId,
+    ) -> Result<()> {
+        self.insert(tx, changeset, branch, |version_vector| {
+            EntryData::directory(blob_id, version_vector)
+        })
+        .await
+    }
+
+    async fn insert(
+        &self,
+        tx: &mut ReadTransaction,
+        changeset: &mut Changeset,
+        branch: Branch,
+        data: impl FnOnce(VersionVector) -> EntryData,
+    ) -> Result<()> {
+        let mut directory = self.open_in(tx, branch).await?;
+        let mut content = directory.content.clone();
+        let version_vector = content
+            .initial_version_vector(&self.entry_name)
+            .incremented(*directory.branch().id());
+        let diff = content.insert(self.entry_name.clone(), data(version_vector))?;
+        directory.save(tx, changeset, &content).await?;
+        directory.bump(tx, changeset, Bump::Add(diff)).await?;
+
+        Ok(())
+    }
+
+    /// Merges `merge` into the version vector of this entry and updates the version vectors of
+    /// all its ancestors accordingly.
+    pub async fn merge(&self, branch: Branch, merge: VersionVector) -> Result<()> {
+        let mut tx = branch.store().begin_write().await?;
+        let mut changeset = Changeset::new();
+
+        let mut directory = self.open_in(&mut tx, branch).await?;
+        let mut content = directory.content.clone();
+        let diff = content.bump(&self.entry_name, Bump::Merge(merge))?;
+
+        if diff.is_empty() {
+            return Ok(());
+        }
+
+        directory.save(&mut tx, &mut changeset, &content).await?;
+        directory
+            .bump(&mut tx, &mut changeset, Bump::Add(diff))
+            .await?;
+        directory.commit(tx, changeset).await?;
+        directory.finalize(content);
+
+        Ok(())
+    }
+
     /// Atomically forks the blob of this entry into the local branch and returns the updated
     /// parent context.
     // TODO: move this function to the `file` mod.
+    pub async fn fork(&self, src_branch: &Branch, dst_branch: &Branch) -> Result<Self> {
+        self.fork_merged(src_branch, dst_branch, &VersionVector::new())
+            .await
+    }
+
+    /// Like [`Self::fork`] but additionally merges `merge` into the version vector of the forked
+    /// entry.
     #[instrument(
         skip_all,
         fields(
@@ -73,9 +183,15 @@ impl ParentContext {
         ),
         err(Debug)
     )]
-    pub async fn fork(&self, src_branch: &Branch, dst_branch: &Branch) -> Result<Self> {
+    pub async fn fork_merged(
+        &self,
+        src_branch: &Branch,
+        dst_branch: &Branch,
+        merge: &VersionVector,
+    ) -> Result<Self> {
         let directory = self.open(src_branch.clone()).await?;
-        let src_entry_data = directory.lookup(&self.entry_name)?.clone_data();
+        let mut src_entry_data = directory.lookup(&self.entry_name)?.clone_data();
+        src_entry_data.version_vector_mut().merge(merge);
         let new_blob_id = *src_entry_data.blob_id().ok_or(Error::EntryNotFound)?;
         Span::current().record("blob_id", field::debug(&new_blob_id));
 
@@ -1,6 +1,7 @@
 use super::*;
 use crate::{
     access_control::{AccessKeys, WriteSecrets},
+    block_tracker::BlockTracker,
     branch::BranchShared,
     db,
     event::EventSender,
@@ -8,7 +9,7 @@ use crate::{
     test_utils,
 };
 use assert_matches::assert_matches;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use tempfile::TempDir;
 use tracing::Instrument;
 
@@ -713,6 +714,60 @@ async fn create_tombstone_is_idempotent() {
     assert_eq!(proof2, proof1);
 }
 
+#[test]
+fn file_metadata_is_readable_by_older_versions() {
+    use super::entry_data::{EntryDirectoryData, EntryLinkData};
+    use serde::Deserialize;
+
+    // The directory entry format before file metadata was introduced.
+    #[derive(Deserialize)]
+    #[serde(rename = "EntryData")]
+    #[allow(dead_code)]
+    enum OldEntryData {
+        File {
+            blob_id: BlobId,
+            version_vector: VersionVector,
+        },
+        Directory(EntryDirectoryData),
+        Tombstone(EntryTombstoneData),
+        Link(EntryLinkData),
+    }
+
+    let mut content = Content::empty();
+    content
+        .insert(
+            "a.txt".into(),
+            EntryData::file(rand::random(), VersionVector::new()),
+        )
+        .unwrap();
+    content
+        .insert(
+            "b.txt".into(),
+            EntryData::file(rand::random(), VersionVector::new()),
+        )
+        .unwrap();
+
+    let metadata = FileMetadata::from([("content-type".to_owned(), "text/plain".to_owned())]);
+    content.set_metadata("a.txt", metadata.clone()).unwrap();
+
+    let buffer = content.serialize();
+
+    let mut input = &buffer[..];
+    assert_eq!(vint64::decode(&mut input).unwrap(), DIRECTORY_VERSION);
+    let old: BTreeMap<String, OldEntryData> = bincode::deserialize(input).unwrap();
+    assert_eq!(old.len(), 2);
+
+    let content = Content::deserialize(&buffer).unwrap();
+    assert_matches!(
+        content.get_key_value("a.txt"),
+        Some((_, EntryData::File(data))) if data.metadata == metadata
+    );
+    assert_matches!(
+        content.get_key_value("b.txt"),
+        Some((_, EntryData::File(data))) if data.metadata.is_empty()
+    );
+}
+
 async fn setup() -> (TempDir, Branch) {
     let (base_dir, [branch]) = setup_multiple().await;
     (base_dir, branch)
@@ -729,7 +784,7 @@ async fn setup_multiple<const N: usize>() -> (TempDir, [Branch; N]) {
 fn create_branch(pool: db::Pool, keys: AccessKeys) -> Branch {
     let store = Store::new(pool);
     let id = PublicKey::random();
-    let shared = BranchShared::new();
+    let shared = BranchShared::new(BlockTracker::new());
     let event_tx = EventSender::new(1);
     Branch::new(id, store, keys, shared, event_tx)
 }
@@ -9,9 +9,9 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub enum Error {
     // TODO: remove / merge with `Store`
     #[error("database error")]
-    Db(#[from] db::Error),
+    Db(#[source] db::Error),
     #[error("store error")]
-    Store(#[from] store::Error),
+    Store(#[source] store::Error),
     #[error("permission denied")]
     PermissionDenied,
     // TODO: remove
@@ -31,6 +31,10 @@ pub enum Error {
     EntryIsFile,
     #[error("entry is a directory")]
     EntryIsDirectory,
+    #[error("entry is a link")]
+    EntryIsLink,
+    #[error("too many levels of links")]
+    TooManyLinks,
     #[error("File name is not a valid UTF-8 string")]
     NonUtf8FileName,
     #[error("offset is out of range")]
@@ -41,10 +45,24 @@ pub enum Error {
     OperationNotSupported,
     #[error("failed to write into writer")]
     Writer(#[source] io::Error),
+    #[error("failed to read from reader")]
+    Reader(#[source] io::Error),
     #[error("storage version mismatch")]
     StorageVersionMismatch,
+    #[error("archive version mismatch")]
+    ArchiveVersionMismatch,
     #[error("file or directory is locked")]
     Locked,
+    #[error("not a repository")]
+    NotARepository,
+    #[error("storage quota exceeded")]
+    QuotaExceeded,
+    #[error("share token expired")]
+    TokenExpired,
+    #[error("wrong password")]
+    WrongPassword,
+    #[error("store is locked")]
+    StoreLocked(#[source] Option<sqlx::Error>),
 }
 
 impl Error {
@@ -55,6 +73,28 @@ impl Error {
     }
 }
 
+impl From<db::Error> for Error {
+    fn from(src: db::Error) -> Self {
+        match src {
+            db::Error::Open(error) | db::Error::Query(error) if is_locked(&error) => {
+                Self::StoreLocked(Some(error))
+            }
+            db::Error::Busy => Self::StoreLocked(None),
+            _ => Self::Db(src),
+        }
+    }
+}
+
+impl From<store::Error> for Error {
+    fn from(src: store::Error) -> Self {
+        match src {
+            store::Error::QuotaExceeded => Self::QuotaExceeded,
+            store::Error::Db(error) if is_locked(&error) => Self::StoreLocked(Some(error)),
+            _ => Self::Store(src),
+        }
+    }
+}
+
 impl From<TryFromSliceError> for Error {
     fn from(_: TryFromSliceError) -> Self {
         Self::MalformedData
@@ -63,10 +103,31 @@ impl From<TryFromSliceError> for Error {
 
 impl From<sqlx::Error> for Error {
     fn from(src: sqlx::Error) -> Self {
-        Self::Db(src.into())
+        if is_locked(&src) {
+            Self::StoreLocked(Some(src))
+        } else {
+            Self::Db(src.into())
+        }
     }
 }
 
+/// Whether the error means the database is locked by another connection or process
+/// (`SQLITE_BUSY` or `SQLITE_LOCKED`, including their extended codes).
+fn is_locked(error: &sqlx::Error) -> bool {
+    const SQLITE_BUSY: i32 = 5;
+    const SQLITE_LOCKED: i32 = 6;
+
+    let sqlx::Error::Database(error) = error else {
+        return false;
+    };
+
+    error
+        .code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .map(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED))
+        .unwrap_or(false)
+}
+
 pub struct Verbose<'a>(&'a Error);
 
 impl fmt::Display for Verbose<'_> {
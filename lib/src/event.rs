@@ -3,11 +3,21 @@
 
 use crate::{crypto::sign::PublicKey, protocol::BlockId};
 use core::fmt;
+use deadlock::BlockingMutex;
 use futures_util::{stream, Stream};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
 use tokio::sync::broadcast;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 #[non_exhaustive]
 pub enum Payload {
     /// A new snapshot was approved in the specified branch.
@@ -21,6 +31,9 @@ pub enum Payload {
     /// This event is useful mostly for diagnostics or testing and can be safely ignored in other
     /// contexts.
     MaintenanceCompleted,
+    /// The repository started or stopped transferring blocks to or from remote replicas. Use
+    /// `Repository::is_transferring` to find out which one it was.
+    TransferStateChanged,
 }
 
 /// Notification event
@@ -60,10 +73,21 @@ impl EventScope {
     }
 }
 
+/// Event recorded in the event log. See `Repository::set_event_log_capacity`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LoggedEvent {
+    /// When the event was emitted.
+    #[serde(with = "as_millis_since_epoch")]
+    pub timestamp: SystemTime,
+    /// Event payload.
+    pub payload: Payload,
+}
+
 #[derive(Clone)]
 pub(crate) struct EventSender {
     inner: broadcast::Sender<Event>,
     scope: EventScope,
+    log: Arc<EventLog>,
 }
 
 impl EventSender {
@@ -71,6 +95,7 @@ impl EventSender {
         Self {
             inner: broadcast::channel(capacity).0,
             scope: EventScope::DEFAULT,
+            log: Arc::new(EventLog::new()),
         }
     }
 
@@ -79,6 +104,7 @@ impl EventSender {
     }
 
     pub fn send(&self, payload: Payload) {
+        self.log.record(payload);
         self.inner
             .send(Event::new(payload).with_scope(self.scope))
             .unwrap_or(0);
@@ -87,6 +113,90 @@ impl EventSender {
     pub fn subscribe(&self) -> broadcast::Receiver<Event> {
         self.inner.subscribe()
     }
+
+    /// Sets the maximum number of events kept in the event log. Zero disables the log.
+    pub fn set_log_capacity(&self, capacity: usize) {
+        self.log.set_capacity(capacity)
+    }
+
+    /// Returns the events currently in the event log, oldest first.
+    pub fn log(&self) -> Vec<LoggedEvent> {
+        self.log.entries.lock().unwrap().iter().copied().collect()
+    }
+}
+
+// Ring buffer of the most recently sent events.
+struct EventLog {
+    // Checked before locking `entries` so that a disabled log costs just an atomic load per event.
+    capacity: AtomicUsize,
+    entries: BlockingMutex<VecDeque<LoggedEvent>>,
+}
+
+impl EventLog {
+    fn new() -> Self {
+        Self {
+            capacity: AtomicUsize::new(0),
+            entries: BlockingMutex::new(VecDeque::new()),
+        }
+    }
+
+    fn set_capacity(&self, capacity: usize) {
+        let mut entries = self.entries.lock().unwrap();
+
+        self.capacity.store(capacity, Ordering::Relaxed);
+
+        let excess = entries.len().saturating_sub(capacity);
+        entries.drain(..excess);
+
+        if capacity == 0 {
+            entries.shrink_to_fit();
+        }
+    }
+
+    fn record(&self, payload: Payload) {
+        if self.capacity.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+
+        // Re-check under the lock in case the log has been resized concurrently.
+        let capacity = self.capacity.load(Ordering::Relaxed);
+        if capacity == 0 {
+            return;
+        }
+
+        if entries.len() >= capacity {
+            entries.pop_front();
+        }
+
+        entries.push_back(LoggedEvent {
+            timestamp: SystemTime::now(),
+            payload,
+        });
+    }
+}
+
+mod as_millis_since_epoch {
+    use crate::time;
+    use serde::{ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S>(value: &SystemTime, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        time::to_millis_since_epoch(*value)
+            .map_err(S::Error::custom)?
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(time::from_millis_since_epoch(u64::deserialize(d)?))
+    }
 }
 
 #[derive(Debug)]
@@ -1,19 +1,34 @@
+mod reader;
+
+pub use self::reader::FileReader;
+
 use crate::{
-    blob::{lock::UpgradableLock, Blob, BlockIds, ReadWriteError},
+    blob::{lock::UpgradableLock, Blob, BlockIds, ReadWriteError, HEADER_SIZE},
     branch::Branch,
-    directory::{Directory, ParentContext},
+    crypto::{Digest, Hash},
+    directory::{check_metadata_size, Directory, FileMetadata, ParentContext},
     error::{Error, Result},
     protocol::{Bump, Locator, SingleBlockPresence, BLOCK_SIZE},
-    store::{Changeset, ReadTransaction},
+    store::{Changeset, ReadTransaction, WriteTransaction},
     version_vector::VersionVector,
 };
 use std::{fmt, future::Future, io::SeekFrom};
-use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    time::{Duration, Instant},
+};
+
+/// Priority of the blocks required by `File::prefetch_range`.
+const PREFETCH_PRIORITY: u8 = u8::MAX;
 
 pub struct File {
     blob: Blob,
     parent: ParentContext,
     lock: UpgradableLock,
+    auto_flush: Option<AutoFlush>,
+    // Whether this file is part of a `Batch`. Batched files are flushed only when the batch is
+    // committed.
+    batched: bool,
 }
 
 impl File {
@@ -32,6 +47,8 @@ impl File {
             blob: Blob::open(&mut tx, branch, *locator.blob_id()).await?,
             parent,
             lock,
+            auto_flush: None,
+            batched: false,
         })
     }
 
@@ -52,6 +69,8 @@ impl File {
             blob: Blob::create(branch, *locator.blob_id()),
             parent,
             lock,
+            auto_flush: None,
+            batched: false,
         }
     }
 
@@ -95,8 +114,129 @@ impl File {
         }
     }
 
+    /// Returns whether all blocks of this file are available locally. Stops at the first missing
+    /// block.
+    /// NOTE: Like `progress`, the returned future doesn't borrow from `self`.
+    pub fn is_available_offline(&self) -> impl Future<Output = Result<bool>> {
+        let branch = self.branch().clone();
+        let blob_id = *self.blob.id();
+
+        async move {
+            let mut block_ids = BlockIds::open(branch, blob_id).await?;
+
+            while let Some((_, block_presence)) = block_ids.try_next().await? {
+                match block_presence {
+                    SingleBlockPresence::Present => (),
+                    SingleBlockPresence::Missing | SingleBlockPresence::Expired => {
+                        return Ok(false)
+                    }
+                }
+            }
+
+            Ok(true)
+        }
+    }
+
+    /// Asks for the blocks covering the given byte range of this file to be downloaded before the
+    /// other missing blocks (of this or any other file). Useful for streaming, to fetch the data
+    /// near the current playback position first. Blocks that are already present are ignored.
+    ///
+    /// NOTE: Like `progress`, the returned future doesn't borrow from `self`.
+    pub fn prefetch_range(&self, offset: u64, len: u64) -> impl Future<Output = Result<()>> {
+        let branch = self.branch().clone();
+        let blob_id = *self.blob.id();
+        let (start, count) = block_range(offset, len);
+
+        async move {
+            if count == 0 {
+                return Ok(());
+            }
+
+            let mut block_ids = BlockIds::open(branch.clone(), blob_id).await?;
+            block_ids.skip(start);
+
+            for _ in 0..count {
+                let Some((block_id, block_presence)) = block_ids.try_next().await? else {
+                    break;
+                };
+
+                match block_presence {
+                    SingleBlockPresence::Present => (),
+                    SingleBlockPresence::Missing | SingleBlockPresence::Expired => branch
+                        .block_tracker()
+                        .require_with_priority(block_id, PREFETCH_PRIORITY),
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Cancels the download of the missing blocks of this file. They are not requested from the
+    /// peers anymore until the repository is reopened. Blocks whose requests are already in flight
+    /// may still be received.
+    ///
+    /// NOTE: Like `progress`, the returned future doesn't borrow from `self`.
+    pub fn cancel_download(&self) -> impl Future<Output = Result<()>> {
+        let branch = self.branch().clone();
+        let blob_id = *self.blob.id();
+
+        async move {
+            let mut block_ids = BlockIds::open(branch.clone(), blob_id).await?;
+
+            while let Some((block_id, block_presence)) = block_ids.try_next().await? {
+                match block_presence {
+                    SingleBlockPresence::Present => (),
+                    SingleBlockPresence::Missing | SingleBlockPresence::Expired => {
+                        branch.block_tracker().cancel(block_id)
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Returns whether all blocks covering the given byte range of this file are available
+    /// locally, that is, whether reading the range would complete without waiting for any
+    /// download. Doesn't request any missing blocks. The part of the range past the end of the file
+    /// is considered available.
+    ///
+    /// NOTE: Like `progress`, the returned future doesn't borrow from `self`.
+    pub fn is_range_available(&self, offset: u64, len: u64) -> impl Future<Output = Result<bool>> {
+        let branch = self.branch().clone();
+        let blob_id = *self.blob.id();
+        let (start, count) = block_range(offset, len);
+
+        async move {
+            if count == 0 {
+                return Ok(true);
+            }
+
+            let mut block_ids = BlockIds::open(branch, blob_id).await?;
+            block_ids.skip(start);
+
+            for _ in 0..count {
+                let Some((_, block_presence)) = block_ids.try_next().await? else {
+                    break;
+                };
+
+                match block_presence {
+                    SingleBlockPresence::Present => (),
+                    SingleBlockPresence::Missing | SingleBlockPresence::Expired => {
+                        return Ok(false)
+                    }
+                }
+            }
+
+            Ok(true)
+        }
+    }
+
     /// Reads data from this file. Returns the number of bytes actually read.
     pub async fn read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+        self.check_unlocked()?;
+
         loop {
             match self.blob.read(buffer) {
                 Ok(len) => return Ok(len),
@@ -104,6 +244,11 @@ impl File {
                     let mut tx = self.branch().store().begin_read().await?;
                     self.blob.warmup(&mut tx).await?;
                 }
+                Err(ReadWriteError::CacheFull) if self.batched => {
+                    // Flushing now would make the pending writes visible before the batch is
+                    // committed.
+                    return Err(Error::OperationNotSupported);
+                }
                 Err(ReadWriteError::CacheFull) => {
                     self.flush().await?;
                 }
@@ -139,15 +284,25 @@ impl File {
 
     /// Writes `buffer` into this file. Returns the number of bytes actually written.
     pub async fn write(&mut self, buffer: &[u8]) -> Result<usize> {
+        self.check_unlocked()?;
+
         self.acquire_write_lock()?;
 
         loop {
             match self.blob.write(buffer) {
-                Ok(len) => return Ok(len),
+                Ok(len) => {
+                    self.flush_if_due().await?;
+                    return Ok(len);
+                }
                 Err(ReadWriteError::CacheMiss) => {
                     let mut tx = self.branch().store().begin_read().await?;
                     self.blob.warmup(&mut tx).await?;
                 }
+                Err(ReadWriteError::CacheFull) if self.batched => {
+                    // Flushing now would make the pending writes visible before the batch is
+                    // committed.
+                    return Err(Error::OperationNotSupported);
+                }
                 Err(ReadWriteError::CacheFull) => {
                     self.flush().await?;
                 }
@@ -173,35 +328,97 @@ impl File {
         self.blob.seek(pos)
     }
 
+    /// Enables (`Some`) or disables (`None`) write-behind flushing of this file.
+    ///
+    /// When enabled, `write` automatically flushes the file if at least `interval` has elapsed
+    /// since the last flush, so buffered writes are committed at most once per interval instead
+    /// of once per explicit `flush` call. This greatly reduces the transaction overhead of
+    /// append-heavy workloads (e.g. logs) which then don't need to call `flush` after every write.
+    ///
+    /// Durability trade-off: writes that haven't been flushed yet live only in memory and are lost
+    /// on crash. Because the check happens on write, data written in the last interval stays
+    /// buffered until the next write, explicit `flush` or close. The file still needs to be
+    /// flushed explicitly before being dropped.
+    pub fn set_auto_flush(&mut self, interval: Option<Duration>) {
+        self.auto_flush = interval.map(|interval| AutoFlush {
+            interval,
+            last_flush: Instant::now(),
+        });
+    }
+
     /// Truncates the file to the given length.
     pub fn truncate(&mut self, len: u64) -> Result<()> {
+        self.check_unlocked()?;
         self.acquire_write_lock()?;
         self.blob.truncate(len)
     }
 
     /// Atomically saves any pending modifications and updates the version vectors of this file and
     /// all its ancestors.
+    ///
+    /// Fails with `OperationNotSupported` if this file is part of a [`Batch`](crate::Batch). Such
+    /// file is flushed only when the batch is committed.
+    ///
+    /// Once this returns `Ok`, the changes are visible to any subsequent read on the same
+    /// repository, e.g. `open_directory` or `open_file` from another task. None of the caches can
+    /// serve stale data after a flush because both the cached index snapshots and the cached
+    /// blocks are keyed by content hash: a flush creates new keys instead of modifying the cached
+    /// entries, and directories are always loaded from the current snapshot.
     pub async fn flush(&mut self) -> Result<()> {
+        self.check_unlocked()?;
+
+        if self.batched {
+            return Err(Error::OperationNotSupported);
+        }
+
         if !self.blob.is_dirty() {
             return Ok(());
         }
 
+        self.commit(Bump::increment(*self.branch().id())).await
+    }
+
+    /// Saves any pending modifications and updates the version vector of this file so that it's
+    /// greater than `merge`. Used to resolve conflicts: the resulting version supersedes all the
+    /// versions whose version vectors were merged into `merge`.
+    pub(crate) async fn flush_merged(&mut self, merge: VersionVector) -> Result<()> {
+        let merge = merge.incremented(*self.branch().id());
+        self.commit(Bump::Merge(merge)).await
+    }
+
+    async fn commit(&mut self, bump: Bump) -> Result<()> {
         let mut tx = self.branch().store().begin_write().await?;
+        self.commit_in(&mut tx, bump).await?;
+
+        let event_tx = self.branch().notify();
+        tx.commit_and_then(move || event_tx.send()).await?;
+
+        if let Some(auto_flush) = &mut self.auto_flush {
+            auto_flush.last_flush = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    // Saves any pending modifications and updates the version vectors of this file and all its
+    // ancestors within the given transaction, but doesn't commit it.
+    async fn commit_in(&mut self, tx: &mut WriteTransaction, bump: Bump) -> Result<()> {
+        self.check_unlocked()?;
+
         let mut changeset = Changeset::new();
 
-        self.blob.flush(&mut tx, &mut changeset).await?;
+        self.blob.flush(tx, &mut changeset).await?;
         self.parent
-            .bump(
-                &mut tx,
-                &mut changeset,
-                self.branch().clone(),
-                Bump::increment(*self.branch().id()),
-            )
+            .bump(tx, &mut changeset, self.branch().clone(), bump)
             .await?;
 
+        self.apply(tx, changeset).await
+    }
+
+    async fn apply(&self, tx: &mut WriteTransaction, changeset: Changeset) -> Result<()> {
         changeset
             .apply(
-                &mut tx,
+                tx,
                 self.branch().id(),
                 self.branch()
                     .keys()
@@ -210,12 +427,52 @@ impl File {
             )
             .await?;
 
-        let event_tx = self.branch().notify();
-        tx.commit_and_then(move || event_tx.send()).await?;
-
         Ok(())
     }
 
+    /// Marks this file as part of a batch. See [`Self::flush`].
+    pub(crate) fn set_batched(&mut self) {
+        self.batched = true;
+    }
+
+    /// Like `flush` but within the given transaction which is not committed. Used by `Batch`.
+    pub(crate) async fn flush_in(&mut self, tx: &mut WriteTransaction) -> Result<()> {
+        if !self.blob.is_dirty() {
+            return Ok(());
+        }
+
+        self.commit_in(tx, Bump::increment(*self.branch().id()))
+            .await
+    }
+
+    /// Saves this newly created file and inserts its entry into the parent directory within the
+    /// given transaction which is not committed. Used by `Batch` for files created by it.
+    pub(crate) async fn insert_in(&mut self, tx: &mut WriteTransaction) -> Result<()> {
+        self.check_unlocked()?;
+
+        let mut changeset = Changeset::new();
+
+        self.blob.flush(tx, &mut changeset).await?;
+        self.parent
+            .insert_file(tx, &mut changeset, self.branch().clone(), *self.blob.id())
+            .await?;
+
+        self.apply(tx, changeset).await
+    }
+
+    async fn flush_if_due(&mut self) -> Result<()> {
+        if self.batched {
+            return Ok(());
+        }
+
+        match &self.auto_flush {
+            Some(auto_flush) if auto_flush.last_flush.elapsed() >= auto_flush.interval => {
+                self.flush().await
+            }
+            Some(_) | None => Ok(()),
+        }
+    }
+
     /// Saves any pending modifications but does not update the version vectors. For internal use
     /// only.
     pub(crate) async fn save(
@@ -227,8 +484,32 @@ impl File {
         Ok(())
     }
 
+    /// Computes the hash of the whole (plaintext) content of this file. Files with identical
+    /// content have identical hashes regardless of their blob ids or branches. Moves the seek
+    /// position to the end of the file.
+    pub(crate) async fn content_hash(&mut self) -> Result<Hash> {
+        let mut hasher = blake3::Hasher::new();
+        let mut buffer = vec![0; BLOCK_SIZE];
+
+        self.seek(SeekFrom::Start(0));
+
+        loop {
+            match self.read(&mut buffer).await? {
+                0 => break,
+                len => {
+                    hasher.update(&buffer[..len]);
+                }
+            }
+        }
+
+        Ok(Digest::finalize(hasher).into())
+    }
+
     /// Copy the entire contents of this file into the provided writer (e.g. a file on a regular
     /// filesystem)
+    ///
+    /// The copy can be cancelled by dropping the returned future. This leaves the file intact (it
+    /// is only read from) but the writer might have received only part of the content.
     pub async fn copy_to_writer<W: AsyncWrite + Unpin>(&mut self, dst: &mut W) -> Result<()> {
         let mut buffer = vec![0; BLOCK_SIZE];
 
@@ -245,9 +526,17 @@ impl File {
         Ok(())
     }
 
+    /// Converts this file into a type implementing `AsyncRead` and `AsyncSeek`, for use with the
+    /// tokio io utilities. Reading starts at the current seek position.
+    pub fn into_async_read(self) -> FileReader {
+        FileReader::new(self)
+    }
+
     /// Forks this file into the given branch. Ensure all its ancestor directories exist and live
     /// in the branch as well. Should be called before any mutable operation.
     pub async fn fork(&mut self, dst_branch: Branch) -> Result<()> {
+        self.check_unlocked()?;
+
         if self.branch().id() == dst_branch.id() {
             // File already lives in the local branch. We assume the ancestor directories have been
             // already created as well so there is nothing else to do.
@@ -264,7 +553,13 @@ impl File {
             Blob::open(&mut tx, dst_branch, *self.blob.id()).await?
         };
 
-        *self = Self { blob, parent, lock };
+        *self = Self {
+            blob,
+            parent,
+            lock,
+            auto_flush: self.auto_flush.take(),
+            batched: self.batched,
+        };
 
         Ok(())
     }
@@ -275,17 +570,86 @@ impl File {
             .await
     }
 
+    /// Returns the user defined metadata of this file.
+    pub async fn metadata(&self) -> Result<FileMetadata> {
+        self.parent.entry_metadata(self.branch().clone()).await
+    }
+
+    /// Replaces the user defined metadata of this file (e.g., its content type). The metadata is
+    /// stored in the parent directory entry, so it's encrypted and synced together with it.
+    /// Also saves any pending modifications of the content. Fails with `InvalidArgument` if the
+    /// total size of the keys and values exceeds [`MAX_FILE_METADATA_SIZE`](crate::MAX_FILE_METADATA_SIZE).
+    pub async fn set_metadata(&mut self, metadata: FileMetadata) -> Result<()> {
+        self.check_unlocked()?;
+        check_metadata_size(&metadata)?;
+
+        let mut tx = self.branch().store().begin_write().await?;
+        let mut changeset = Changeset::new();
+
+        self.blob.flush(&mut tx, &mut changeset).await?;
+        self.parent
+            .set_metadata(
+                &mut tx,
+                &mut changeset,
+                self.branch().clone(),
+                metadata,
+                Bump::increment(*self.branch().id()),
+            )
+            .await?;
+        self.apply(&mut tx, changeset).await?;
+
+        let event_tx = self.branch().notify();
+        tx.commit_and_then(move || event_tx.send()).await?;
+
+        Ok(())
+    }
+
     /// BlobId of this file.
     #[cfg(test)]
     pub(crate) fn blob_id(&self) -> &crate::blob::BlobId {
         self.blob.id()
     }
 
+    // Any operation that needs the branch keys must fail once the repository has been locked, even
+    // though this file still holds them.
+    fn check_unlocked(&self) -> Result<()> {
+        if self.branch().is_locked() {
+            Err(Error::PermissionDenied)
+        } else {
+            Ok(())
+        }
+    }
+
     fn acquire_write_lock(&mut self) -> Result<()> {
         self.lock.upgrade().then_some(()).ok_or(Error::Locked)
     }
 }
 
+struct AutoFlush {
+    interval: Duration,
+    last_flush: Instant,
+}
+
+// Returns the index of the first block and the number of blocks covering the given byte range of
+// a file.
+fn block_range(offset: u64, len: u64) -> (u32, u32) {
+    // The first block also contains the blob header.
+    let start = offset.saturating_add(HEADER_SIZE as u64) / BLOCK_SIZE as u64;
+    let end = if len > 0 {
+        offset
+            .saturating_add(len)
+            .saturating_add(HEADER_SIZE as u64)
+            .div_ceil(BLOCK_SIZE as u64)
+    } else {
+        start
+    };
+
+    let start = u32::try_from(start).unwrap_or(u32::MAX);
+    let count = u32::try_from(end).unwrap_or(u32::MAX).saturating_sub(start);
+
+    (start, count)
+}
+
 impl fmt::Debug for File {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("File")
@@ -300,6 +664,7 @@ mod tests {
     use super::*;
     use crate::{
         access_control::{AccessKeys, WriteSecrets},
+        block_tracker::BlockTracker,
         branch::BranchShared,
         crypto::sign::PublicKey,
         db,
@@ -449,12 +814,94 @@ mod tests {
         assert_eq!(dst_content, src_content);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn into_async_read() {
+        use tokio::io::{self, AsyncReadExt, AsyncSeekExt};
+
+        let (_base_dir, [branch]) = setup().await;
+
+        // Spans multiple blocks and ends in the middle of one.
+        let content: Vec<u8> = (0..(2 * BLOCK_SIZE + BLOCK_SIZE / 2))
+            .map(|n| n as u8)
+            .collect();
+
+        let mut file = branch.ensure_file_exists("test.dat".into()).await.unwrap();
+        file.write_all(&content).await.unwrap();
+        file.flush().await.unwrap();
+
+        file.seek(SeekFrom::Start(0));
+        let expected = file.read_to_end().await.unwrap();
+
+        file.seek(SeekFrom::Start(0));
+        let mut reader = file.into_async_read();
+        let mut actual = Vec::new();
+        io::copy(&mut reader, &mut actual).await.unwrap();
+
+        assert_eq!(actual, expected);
+
+        // Seek relative to a position inside a buffered block.
+        reader.seek(SeekFrom::Start(10)).await.unwrap();
+        let mut buffer = [0; 5];
+        reader.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer, content[10..15]);
+
+        let position = reader
+            .seek(SeekFrom::Current(BLOCK_SIZE as i64))
+            .await
+            .unwrap();
+        assert_eq!(position, BLOCK_SIZE as u64 + 15);
+        reader.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(buffer, content[BLOCK_SIZE + 15..BLOCK_SIZE + 20]);
+
+        assert_eq!(
+            reader.stream_position().await.unwrap(),
+            BLOCK_SIZE as u64 + 20
+        );
+
+        reader.seek(SeekFrom::End(-3)).await.unwrap();
+        let mut tail = Vec::new();
+        reader.read_to_end(&mut tail).await.unwrap();
+        assert_eq!(tail, content[content.len() - 3..]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn auto_flush() {
+        let (_base_dir, [branch]) = setup().await;
+
+        let mut file = branch.ensure_file_exists("log.txt".into()).await.unwrap();
+        file.flush().await.unwrap();
+
+        let vv0 = file.version_vector().await.unwrap();
+
+        // Disabled by default
+        file.write_all(b"one").await.unwrap();
+        assert_eq!(file.version_vector().await.unwrap(), vv0);
+
+        // Flush on every write
+        file.set_auto_flush(Some(Duration::ZERO));
+        file.write_all(b"two").await.unwrap();
+
+        let vv1 = file.version_vector().await.unwrap();
+        assert!(vv1 > vv0);
+
+        // Interval not yet elapsed
+        file.set_auto_flush(Some(Duration::from_secs(3600)));
+        file.write_all(b"three").await.unwrap();
+        assert_eq!(file.version_vector().await.unwrap(), vv1);
+
+        file.flush().await.unwrap();
+        assert!(file.version_vector().await.unwrap() > vv1);
+
+        file.seek(SeekFrom::Start(0));
+        assert_eq!(file.read_to_end().await.unwrap(), b"onetwothree");
+    }
+
     async fn setup<const N: usize>() -> (TempDir, [Branch; N]) {
         let (base_dir, pool) = db::create_temp().await.unwrap();
         let store = Store::new(pool);
         let keys = AccessKeys::from(WriteSecrets::random());
         let event_tx = EventSender::new(1);
-        let shared = BranchShared::new();
+        let shared = BranchShared::new(BlockTracker::new());
 
         let branches = [(); N].map(|_| {
             create_branch(
@@ -0,0 +1,156 @@
+use super::File;
+use crate::{error::Result, protocol::BLOCK_SIZE};
+use futures_util::{future::BoxFuture, ready, FutureExt};
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+/// Adapter which implements `AsyncRead` and `AsyncSeek` for a `File`. Created with
+/// [File::into_async_read].
+///
+/// Reads the file in whole blocks and serves the individual `poll_read` calls from the last read
+/// block.
+pub struct FileReader {
+    // Exactly one of `file` and `read` is `Some` at any time.
+    file: Option<File>,
+    read: Option<BoxFuture<'static, (File, Result<Vec<u8>>)>>,
+    buffer: Vec<u8>,
+    offset: usize,
+    seek: Option<SeekFrom>,
+}
+
+impl FileReader {
+    pub(super) fn new(file: File) -> Self {
+        Self {
+            file: Some(file),
+            read: None,
+            buffer: Vec::new(),
+            offset: 0,
+            seek: None,
+        }
+    }
+
+    fn poll_read_block(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        if self.read.is_none() {
+            // unwrap is OK because of the invariant.
+            let mut file = self.file.take().unwrap();
+
+            self.read = Some(
+                async move {
+                    let mut buffer = vec![0; BLOCK_SIZE];
+                    let result = file.read(&mut buffer).await.map(|len| {
+                        buffer.truncate(len);
+                        buffer
+                    });
+
+                    (file, result)
+                }
+                .boxed(),
+            );
+        }
+
+        // unwrap is OK because we just ensured `read` is `Some`.
+        let (file, result) = ready!(self.read.as_mut().unwrap().poll_unpin(cx));
+
+        self.read = None;
+        self.file = Some(file);
+        self.offset = 0;
+
+        match result {
+            Ok(buffer) => {
+                self.buffer = buffer;
+                Poll::Ready(Ok(()))
+            }
+            Err(error) => {
+                self.buffer.clear();
+                Poll::Ready(Err(error))
+            }
+        }
+    }
+}
+
+impl AsyncRead for FileReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        if this.offset >= this.buffer.len() {
+            ready!(this.poll_read_block(cx)).map_err(into_io_error)?;
+        }
+
+        // Empty buffer here means end of file.
+        let len = buf.remaining().min(this.buffer.len() - this.offset);
+        buf.put_slice(&this.buffer[this.offset..this.offset + len]);
+        this.offset += len;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for FileReader {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+
+        if this.seek.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "other file operation is pending, call poll_complete before start_seek",
+            ));
+        }
+
+        this.seek = Some(position);
+
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+
+        // Finish any pending read first. Its error (if any) doesn't matter if we are seeking
+        // because the read data would be discarded anyway.
+        if this.read.is_some() {
+            let result = ready!(this.poll_read_block(cx));
+
+            if this.seek.is_none() {
+                result.map_err(into_io_error)?;
+            }
+        }
+
+        // unwrap is OK because of the invariant.
+        let file = this.file.as_mut().unwrap();
+
+        // The file position is past the end of the buffered data. Account for the part of the
+        // buffer that's not been consumed yet.
+        let unread = (this.buffer.len() - this.offset) as i64;
+
+        let position = match this.seek.take() {
+            Some(SeekFrom::Current(offset)) => {
+                this.buffer.clear();
+                this.offset = 0;
+                file.seek(SeekFrom::Current(offset - unread))
+            }
+            Some(position) => {
+                this.buffer.clear();
+                this.offset = 0;
+                file.seek(position)
+            }
+            None => file.seek(SeekFrom::Current(0)) - unread as u64,
+        };
+
+        Poll::Ready(Ok(position))
+    }
+}
+
+fn into_io_error(error: crate::error::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error)
+}
@@ -0,0 +1,53 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
+
+/// How concurrent versions of the same directory entry are combined when merging directories.
+///
+/// Entries with different names are always preserved, as are concurrent versions of
+/// subdirectories (which are merged recursively). The strategies differ only in how they treat
+/// concurrent versions of a file (including a file modified on one replica and removed or moved on
+/// another).
+#[derive(
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    IntoPrimitive,
+    TryFromPrimitive,
+)]
+#[repr(u8)]
+#[serde(into = "u8", try_from = "u8")]
+pub enum MergeStrategy {
+    /// Keep all concurrent versions. Concurrent file versions are kept side by side (as conflicts
+    /// to be resolved by the user) and a file modified concurrently with its removal is kept.
+    ///
+    /// The directory listing is the union of the entries of all the concurrent versions of the
+    /// directory, deduplicated by name. When concurrent versions of an entry with the same name
+    /// collide, each of them is listed under a unique name made of the original name followed by
+    /// `.v` and a short prefix of the id of the branch it comes from (e.g. `notes.txt.v1a2b3c4d`).
+    /// Concurrent versions of a directory don't collide with each other, they are listed once as a
+    /// single merged directory. They do collide with a file or link of the same name though, in
+    /// which case the merged directory gets the suffix as well. Only collisions between versions
+    /// of a file are reported as conflicts (see `Repository::conflicts`). Collisions involving a
+    /// directory or a link show up only as the suffixed names in the listing.
+    #[default]
+    UnionEntries = 0,
+    /// Keep only one of the concurrent versions: the one with the most edits, i.e. the highest sum
+    /// of the entries of its version vector, with ties broken by the branch id so every replica
+    /// picks the same one. Note that despite the name the winner is not picked by time: no wall
+    /// clock time is recorded, so the version edited most recently loses to a concurrent one that
+    /// was edited more times.
+    ///
+    /// The other versions are discarded (each discarded version is logged). They stay in the
+    /// branches they come from only until those branches merge the winner.
+    ///
+    /// The strategy is a local setting and is not synced. A replica that uses `UnionEntries`
+    /// shows the concurrent versions side by side until it receives the winner picked by a
+    /// replica that uses this strategy. The winner supersedes all of them, so from then on all
+    /// the replicas show the same entry.
+    LastWriterWins = 1,
+}
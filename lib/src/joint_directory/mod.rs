@@ -1,13 +1,16 @@
+mod merge_strategy;
 #[cfg(test)]
 mod tests;
 
+pub use self::merge_strategy::MergeStrategy;
+
 use crate::{
     branch::Branch,
     conflict,
     crypto::sign::PublicKey,
     directory::{
         self, Directory, DirectoryFallback, DirectoryRef, EntryRef, EntryTombstoneData, EntryType,
-        FileRef,
+        FileMetadata, FileRef, LinkRef,
     },
     error::{Error, Result},
     file::File,
@@ -21,6 +24,7 @@ use camino::{Utf8Component, Utf8Path};
 use either::Either;
 use std::{
     borrow::Cow,
+    cmp::Ordering,
     collections::{BTreeMap, VecDeque},
     fmt, iter, mem,
 };
@@ -150,6 +154,33 @@ impl JointDirectory {
             .and_then(|entry| entry.file())
     }
 
+    /// Returns whether any remote version of the entry with the given name is newer than or
+    /// concurrent with its local version. Returns `false` if there is no local version of the
+    /// entry. Tombstones are ignored (see [Self::has_remote_version_not_covered_by]).
+    pub(crate) fn has_unmerged_remote_version(&self, name: &str) -> bool {
+        self.local_version()
+            .and_then(|dir| dir.lookup(name).ok())
+            .map(|entry| self.has_remote_version_not_covered_by(name, entry.version_vector()))
+            .unwrap_or(false)
+    }
+
+    /// Returns whether any remote version of the entry with the given name is newer than or
+    /// concurrent with `vv`. Tombstones are ignored because a removal doesn't conflict with a
+    /// concurrent modification (the modified entry is kept).
+    pub(crate) fn has_remote_version_not_covered_by(&self, name: &str, vv: &VersionVector) -> bool {
+        let local_branch_id = self.local_branch.as_ref().map(|branch| branch.id());
+
+        self.entry_versions(name)
+            .filter(|entry| Some(entry.branch_id()) != local_branch_id)
+            .filter(|entry| !entry.is_tombstone())
+            .any(|entry| {
+                matches!(
+                    entry.version_vector().partial_cmp(vv),
+                    Some(Ordering::Greater) | None
+                )
+            })
+    }
+
     /// Length of the directory in bytes. If there are multiple versions, returns the sum of their
     /// lengths.
     #[allow(clippy::len_without_is_empty)]
@@ -212,6 +243,7 @@ impl JointDirectory {
                 let name = entry.name().to_owned();
                 let branch_id = match &entry {
                     JointEntryRef::File(entry) => *entry.branch().id(),
+                    JointEntryRef::Link(entry) => *entry.branch().id(),
                     JointEntryRef::Directory(_) => *local_branch.id(),
                 };
                 let vv = entry.version_vector().into_owned();
@@ -250,8 +282,15 @@ impl JointDirectory {
     /// In the presence of conflicts (multiple concurrent versions of the same file) this function
     /// still proceeds as far as it can, but the conflicting files remain unmerged. It signals this
     /// by returning `Error::AmbiguousEntry`.
-    #[async_recursion]
     pub async fn merge(&mut self) -> Result<Directory> {
+        self.merge_with(MergeStrategy::default()).await
+    }
+
+    /// Merge all versions of this `JointDirectory` into a single `Directory` using the given
+    /// strategy for combining concurrent versions of the same entry. See [`Self::merge`] for
+    /// details.
+    #[async_recursion]
+    pub(crate) async fn merge_with(&mut self, strategy: MergeStrategy) -> Result<Directory> {
         let old_version_vector = if let Some(local_version) = self.local_version() {
             local_version.version_vector().await?
         } else {
@@ -277,6 +316,34 @@ impl JointDirectory {
         let mut check_for_removal = Vec::new();
 
         for (name, merge) in self.merge_entries() {
+            if strategy == MergeStrategy::LastWriterWins {
+                if let Some(winner) = self.last_writer(name) {
+                    match winner {
+                        LastWriter::File(entry, merge) => {
+                            match entry.fork_merged(&local_branch, &merge).await {
+                                Ok(()) => (),
+                                // The local version changed in the meantime. Retry on the next
+                                // merge.
+                                Err(Error::EntryExists) => conflict = true,
+                                Err(error) => return Err(error),
+                            }
+                        }
+                        LastWriter::Link(entry, merge) => {
+                            match entry.fork_merged(&local_branch, &merge).await {
+                                Ok(()) => (),
+                                Err(Error::EntryExists) => conflict = true,
+                                Err(error) => return Err(error),
+                            }
+                        }
+                        LastWriter::Tombstone(tombstone) => {
+                            check_for_removal.push((name.to_owned(), tombstone))
+                        }
+                    }
+
+                    continue;
+                }
+            }
+
             match merge {
                 Merge::Existing(existing) => {
                     for entry in existing {
@@ -294,6 +361,12 @@ impl JointDirectory {
                                     Err(error) => return Err(error),
                                 }
                             }
+                            JointEntryRef::Link(entry) => match entry.fork(&local_branch).await {
+                                Ok(()) => {}
+                                // Concurrent links are in conflict the same way files are.
+                                Err(Error::EntryExists) => conflict = true,
+                                Err(error) => return Err(error),
+                            },
                             JointEntryRef::Directory(entry) => {
                                 let mut dir = entry
                                     .open_with(
@@ -302,7 +375,7 @@ impl JointDirectory {
                                     )
                                     .await?;
                                 match dir
-                                    .merge()
+                                    .merge_with(strategy)
                                     .instrument(tracing::info_span!("dir", message = name))
                                     .await
                                 {
@@ -349,6 +422,74 @@ impl JointDirectory {
         }
     }
 
+    // If there are multiple concurrent versions of the entry with the given name, at least one of
+    // them is a file or a link and none is a directory, returns the version that wins under
+    // `MergeStrategy::LastWriterWins`. Its version vector is merged with those of all the other
+    // versions so that when inserted into the local branch it supersedes them and every replica
+    // ends up with the same entry.
+    fn last_writer<'a>(&'a self, name: &'a str) -> Option<LastWriter<'a>> {
+        let entries = versioned::keep_maximal(
+            self.entry_versions(name),
+            PreferBranch(self.local_branch.as_ref().map(Branch::id)),
+        );
+
+        if entries.len() < 2
+            || entries.iter().any(|entry| entry.is_directory())
+            || entries.iter().all(|entry| entry.is_tombstone())
+        {
+            return None;
+        }
+
+        let merge = entries.iter().fold(VersionVector::new(), |vv, entry| {
+            vv.merged(entry.version_vector())
+        });
+
+        let winner = entries.iter().max_by(|lhs, rhs| {
+            lhs.version_vector()
+                .total()
+                .cmp(&rhs.version_vector().total())
+                .then_with(|| lhs.branch_id().cmp(rhs.branch_id()))
+        })?;
+
+        for entry in &entries {
+            if entry.branch_id() != winner.branch_id() {
+                tracing::info!(
+                    entry = name,
+                    branch_id = ?entry.branch_id(),
+                    vv = ?entry.version_vector(),
+                    winner_branch_id = ?winner.branch_id(),
+                    "Discarding concurrent version with fewer edits",
+                );
+            }
+        }
+
+        match winner {
+            EntryRef::File(file) => Some(LastWriter::File(*file, merge)),
+            EntryRef::Link(link) => Some(LastWriter::Link(*link, merge)),
+            EntryRef::Tombstone(_) => {
+                // Merge all the concurrent tombstones to prefer `Moved` over `Removed`, so we don't
+                // remove a blob that's still referenced from where it's been moved to.
+                let mut tombstone: Option<EntryTombstoneData> = None;
+
+                for entry in &entries {
+                    if let EntryRef::Tombstone(entry) = entry {
+                        if let Some(tombstone) = &mut tombstone {
+                            tombstone.merge(entry.data());
+                        } else {
+                            tombstone = Some(entry.data().clone());
+                        }
+                    }
+                }
+
+                let mut tombstone = tombstone?;
+                tombstone.version_vector = merge;
+
+                Some(LastWriter::Tombstone(tombstone))
+            }
+            EntryRef::Directory(_) => unreachable!(),
+        }
+    }
+
     // Merge the version vectors of all the versions in this joint directory.
     async fn merge_version_vectors(&self) -> Result<VersionVector> {
         let mut outcome = VersionVector::new();
@@ -403,6 +544,7 @@ impl fmt::Debug for JointDirectory {
 pub enum JointEntryRef<'a> {
     File(JointFileRef<'a>),
     Directory(JointDirectoryRef<'a>),
+    Link(JointLinkRef<'a>),
 }
 
 impl<'a> JointEntryRef<'a> {
@@ -410,6 +552,7 @@ impl<'a> JointEntryRef<'a> {
         match self {
             Self::File(r) => r.name(),
             Self::Directory(r) => r.name(),
+            Self::Link(r) => r.name(),
         }
     }
 
@@ -417,6 +560,7 @@ impl<'a> JointEntryRef<'a> {
         match self {
             Self::File(r) => r.unique_name(),
             Self::Directory(r) => r.unique_name(),
+            Self::Link(r) => r.unique_name(),
         }
     }
 
@@ -424,6 +568,7 @@ impl<'a> JointEntryRef<'a> {
         match self {
             Self::File(_) => EntryType::File,
             Self::Directory(_) => EntryType::Directory,
+            Self::Link(_) => EntryType::Link,
         }
     }
 
@@ -431,6 +576,7 @@ impl<'a> JointEntryRef<'a> {
         match self {
             Self::File(r) => Cow::Borrowed(r.version_vector()),
             Self::Directory(r) => Cow::Owned(r.version_vector()),
+            Self::Link(r) => Cow::Borrowed(r.version_vector()),
         }
     }
 
@@ -438,6 +584,7 @@ impl<'a> JointEntryRef<'a> {
         match self {
             Self::File(r) => Ok(r.file),
             Self::Directory(_) => Err(Error::EntryIsDirectory),
+            Self::Link(_) => Err(Error::EntryIsLink),
         }
     }
 
@@ -445,6 +592,15 @@ impl<'a> JointEntryRef<'a> {
         match self {
             Self::Directory(r) => Ok(r),
             Self::File(_) => Err(Error::EntryIsFile),
+            Self::Link(_) => Err(Error::EntryIsLink),
+        }
+    }
+
+    pub fn link(self) -> Result<JointLinkRef<'a>> {
+        match self {
+            Self::Link(r) => Ok(r),
+            Self::File(_) => Err(Error::EntryIsFile),
+            Self::Directory(_) => Err(Error::EntryIsDirectory),
         }
     }
 
@@ -452,6 +608,7 @@ impl<'a> JointEntryRef<'a> {
         match self {
             Self::File(r) => r.branch(),
             Self::Directory(r) => r.first_version().branch(),
+            Self::Link(r) => r.branch(),
         }
     }
 }
@@ -490,6 +647,10 @@ impl<'a> JointFileRef<'a> {
         self.file.version_vector()
     }
 
+    pub fn metadata(&self) -> &'a FileMetadata {
+        self.file.metadata()
+    }
+
     pub fn branch(&self) -> &Branch {
         self.file.branch()
     }
@@ -503,6 +664,52 @@ impl<'a> JointFileRef<'a> {
     }
 }
 
+#[derive(Debug)]
+pub struct JointLinkRef<'a> {
+    link: LinkRef<'a>,
+    needs_disambiguation: bool,
+}
+
+impl<'a> JointLinkRef<'a> {
+    pub fn name(&self) -> &'a str {
+        self.link.name()
+    }
+
+    pub fn unique_name(&self) -> Cow<'a, str> {
+        if self.needs_disambiguation {
+            Cow::from(conflict::create_unique_name(
+                self.name(),
+                self.link.branch().id(),
+            ))
+        } else {
+            Cow::from(self.name())
+        }
+    }
+
+    /// Path this link points to.
+    pub fn target(&self) -> &'a str {
+        self.link.target()
+    }
+
+    pub(crate) async fn fork(&self, dst_branch: &Branch) -> Result<()> {
+        self.link
+            .fork_merged(dst_branch, &VersionVector::new())
+            .await
+    }
+
+    pub fn version_vector(&self) -> &'a VersionVector {
+        self.link.version_vector()
+    }
+
+    pub fn branch(&self) -> &Branch {
+        self.link.branch()
+    }
+
+    pub fn inner(&self) -> LinkRef<'a> {
+        self.link
+    }
+}
+
 pub struct JointDirectoryRef<'a> {
     versions: Vec<DirectoryRef<'a>>,
     local_branch: Option<&'a Branch>,
@@ -633,6 +840,7 @@ struct Existing<'a> {
     // Thus it might make sense to have one place holder for the first file to avoid Vec allocation
     // when not needed.
     files: VecDeque<FileRef<'a>>,
+    links: VecDeque<LinkRef<'a>>,
     directories: Vec<DirectoryRef<'a>>,
     needs_disambiguation: bool,
     local_branch: Option<&'a Branch>,
@@ -650,8 +858,15 @@ impl<'a> Iterator for Existing<'a> {
             return Some(JointEntryRef::Directory(dir));
         }
 
-        Some(JointEntryRef::File(JointFileRef {
-            file: self.files.pop_front()?,
+        if let Some(file) = self.files.pop_front() {
+            return Some(JointEntryRef::File(JointFileRef {
+                file,
+                needs_disambiguation: self.needs_disambiguation,
+            }));
+        }
+
+        Some(JointEntryRef::Link(JointLinkRef {
+            link: self.links.pop_front()?,
             needs_disambiguation: self.needs_disambiguation,
         }))
     }
@@ -665,6 +880,7 @@ impl<'a> Merge<'a> {
         I: Iterator<Item = EntryRef<'a>>,
     {
         let mut files = VecDeque::new();
+        let mut links = VecDeque::new();
         let mut directories = vec![];
         let mut tombstone: Option<EntryTombstoneData> = None;
 
@@ -674,8 +890,13 @@ impl<'a> Merge<'a> {
         for entry in entries {
             match entry {
                 EntryRef::File(file) => files.push_back(file),
+                EntryRef::Link(link) => links.push_back(link),
                 EntryRef::Directory(dir) => directories.push(dir),
-                EntryRef::Tombstone(_) if !files.is_empty() || !directories.is_empty() => continue,
+                EntryRef::Tombstone(_)
+                    if !files.is_empty() || !links.is_empty() || !directories.is_empty() =>
+                {
+                    continue
+                }
                 EntryRef::Tombstone(new_tombstone) => {
                     let new_tombstone = if let Some(mut old_tombstone) = tombstone.take() {
                         old_tombstone.merge(new_tombstone.data());
@@ -689,14 +910,16 @@ impl<'a> Merge<'a> {
             }
         }
 
-        let needs_disambiguation = files.len() + if directories.is_empty() { 0 } else { 1 } > 1;
+        let needs_disambiguation =
+            files.len() + links.len() + if directories.is_empty() { 0 } else { 1 } > 1;
 
         match tombstone {
-            Some(tombstone) if files.is_empty() && directories.is_empty() => {
+            Some(tombstone) if files.is_empty() && links.is_empty() && directories.is_empty() => {
                 Self::Tombstone(tombstone)
             }
             Some(_) | None => Self::Existing(Existing {
                 files,
+                links,
                 directories,
                 needs_disambiguation,
                 local_branch,
@@ -712,6 +935,16 @@ impl<'a> Merge<'a> {
     }
 }
 
+// Outcome of resolving concurrent versions of an entry under `MergeStrategy::LastWriterWins`.
+enum LastWriter<'a> {
+    // The file version that won, and the version vector to merge into it.
+    File(FileRef<'a>, VersionVector),
+    // The link version that won, and the version vector to merge into it.
+    Link(LinkRef<'a>, VersionVector),
+    // A tombstone won.
+    Tombstone(EntryTombstoneData),
+}
+
 enum Pattern<'a> {
     // Fetch all entries
     All,
@@ -1,6 +1,7 @@
 use super::*;
 use crate::{
     access_control::WriteSecrets,
+    block_tracker::BlockTracker,
     branch::{Branch, BranchShared},
     crypto::{sign::PublicKey, Hash},
     db,
@@ -405,6 +406,49 @@ async fn merge_locally_non_existing_file() {
     );
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_locally_non_existing_link() {
+    // 0 - local, 1 - remote
+    let (_base_dir, [branch0, branch1]) = setup().await;
+
+    let mut local_root = branch0.open_or_create_root().await.unwrap();
+    let mut remote_root = branch1.open_or_create_root().await.unwrap();
+
+    remote_root
+        .create_link("link".into(), "target.txt".into())
+        .await
+        .unwrap();
+
+    let root = JointDirectory::new(
+        Some(branch0.clone()),
+        [local_root.clone(), remote_root.clone()],
+    );
+    let entry = root.lookup_unique("link").unwrap();
+    assert_eq!(entry.entry_type(), EntryType::Link);
+    assert_eq!(entry.link().unwrap().target(), "target.txt");
+
+    JointDirectory::new(
+        Some(branch0.clone()),
+        [local_root.clone(), remote_root.clone()],
+    )
+    .merge()
+    .await
+    .unwrap();
+
+    local_root.refresh().await.unwrap();
+
+    let entry = local_root.lookup("link").unwrap().link().unwrap();
+    assert_eq!(entry.target(), "target.txt");
+    assert_eq!(
+        entry.version_vector(),
+        remote_root.lookup("link").unwrap().version_vector()
+    );
+
+    assert!(
+        local_root.version_vector().await.unwrap() >= remote_root.version_vector().await.unwrap()
+    );
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn merge_locally_older_file() {
     let (_base_dir, [branch0, branch1]) = setup().await;
@@ -1121,6 +1165,217 @@ async fn merge_moved_file() {
     assert_eq!(file.read_to_end().await.unwrap(), file_content);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_concurrent_files_union_entries() {
+    let (_base_dir, [branch0, branch1]) = setup().await;
+
+    let mut root0 = branch0.open_or_create_root().await.unwrap();
+    create_file(&mut root0, "cat.jpg", b"v0").await;
+
+    let mut root1 = branch1.open_or_create_root().await.unwrap();
+    create_file(&mut root1, "cat.jpg", b"v1").await;
+
+    // Both versions are kept as conflicting.
+    assert_matches!(
+        merge_with(&[&branch0, &branch1], MergeStrategy::UnionEntries).await,
+        Err(Error::AmbiguousEntry)
+    );
+    assert_eq!(
+        open_joint_root(&branch0, &branch1).await.entries().count(),
+        2
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_concurrent_files_last_writer_wins() {
+    let (_base_dir, [branch0, branch1]) = setup().await;
+
+    let mut root0 = branch0.open_or_create_root().await.unwrap();
+    create_file(&mut root0, "cat.jpg", b"v0").await;
+
+    // Modify the remote version once more so it has more changes than the local one.
+    let mut root1 = branch1.open_or_create_root().await.unwrap();
+    create_file(&mut root1, "cat.jpg", b"v1").await;
+    root1.refresh().await.unwrap();
+    update_file(&root1, "cat.jpg", b"v2", &branch1).await;
+    root1.refresh().await.unwrap();
+
+    assert!(
+        read_version_vector(&root1, "cat.jpg").await.total()
+            > read_version_vector(&root0, "cat.jpg").await.total()
+    );
+
+    merge_with(&[&branch0, &branch1], MergeStrategy::LastWriterWins)
+        .await
+        .unwrap();
+    merge_with(&[&branch1, &branch0], MergeStrategy::LastWriterWins)
+        .await
+        .unwrap();
+
+    root0.refresh().await.unwrap();
+    root1.refresh().await.unwrap();
+
+    // Both branches converged to the remote version.
+    for root in [&root0, &root1] {
+        let content = open_file(root, "cat.jpg")
+            .await
+            .read_to_end()
+            .await
+            .unwrap();
+        assert_eq!(content, b"v2");
+    }
+
+    assert_eq!(
+        read_version_vector(&root0, "cat.jpg").await,
+        read_version_vector(&root1, "cat.jpg").await
+    );
+    assert_eq!(
+        open_joint_root(&branch0, &branch1).await.entries().count(),
+        1
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_concurrent_files_with_different_names_last_writer_wins() {
+    let (_base_dir, [branch0, branch1]) = setup().await;
+
+    let mut root0 = branch0.open_or_create_root().await.unwrap();
+    create_file(&mut root0, "cat.jpg", b"cat").await;
+
+    let mut root1 = branch1.open_or_create_root().await.unwrap();
+    create_file(&mut root1, "dog.jpg", b"dog").await;
+
+    merge_with(&[&branch0, &branch1], MergeStrategy::LastWriterWins)
+        .await
+        .unwrap();
+
+    // Independently added files are preserved.
+    root0.refresh().await.unwrap();
+    assert_eq!(root0.entries().count(), 2);
+    root0.lookup("cat.jpg").unwrap().file().unwrap();
+    root0.lookup("dog.jpg").unwrap().file().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_modified_and_removed_file_last_writer_wins() {
+    // (local modifications, remote modifications before the removal, file should be kept)
+    for (local_count, remote_count, kept) in [(1, 2, false), (3, 0, true)] {
+        let (_base_dir, [branch0, branch1]) = setup().await;
+
+        let mut root0 = branch0.open_or_create_root().await.unwrap();
+        let mut root1 = branch1.open_or_create_root().await.unwrap();
+
+        let mut file = create_file(&mut root0, "cat.jpg", b"v0").await;
+        file.fork(branch1.clone()).await.unwrap();
+        drop(file);
+
+        // Modify and then remove the file in the remote branch.
+        for i in 0..remote_count {
+            root1.refresh().await.unwrap();
+            update_file(
+                &root1,
+                "cat.jpg",
+                format!("remote {i}").as_bytes(),
+                &branch1,
+            )
+            .await;
+        }
+
+        root1.refresh().await.unwrap();
+        let vv = read_version_vector(&root1, "cat.jpg").await;
+        root1
+            .remove_entry("cat.jpg", branch1.id(), vv)
+            .await
+            .unwrap();
+
+        // Concurrently modify it in the local branch.
+        for i in 0..local_count {
+            root0.refresh().await.unwrap();
+            update_file(&root0, "cat.jpg", format!("local {i}").as_bytes(), &branch0).await;
+        }
+
+        merge_with(&[&branch0, &branch1], MergeStrategy::LastWriterWins)
+            .await
+            .unwrap();
+
+        root0.refresh().await.unwrap();
+
+        if kept {
+            let content = open_file(&root0, "cat.jpg")
+                .await
+                .read_to_end()
+                .await
+                .unwrap();
+            assert_eq!(content, format!("local {}", local_count - 1).as_bytes());
+        } else {
+            assert_matches!(root0.lookup("cat.jpg"), Ok(EntryRef::Tombstone(_)));
+        }
+
+        assert_eq!(
+            open_joint_root(&branch0, &branch1).await.entries().count(),
+            usize::from(kept)
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_moved_and_modified_file_last_writer_wins() {
+    let (_base_dir, [branch0, branch1]) = setup().await;
+
+    let mut root0 = branch0.open_or_create_root().await.unwrap();
+    let mut root1 = branch1.open_or_create_root().await.unwrap();
+
+    let mut file = create_file(&mut root0, "a", b"v0").await;
+    file.fork(branch1.clone()).await.unwrap();
+    drop(file);
+
+    // Modify the file twice in the remote branch and then move it into a subdirectory.
+    for content in [b"remote 0", b"remote 1"] {
+        root1.refresh().await.unwrap();
+        update_file(&root1, "a", content, &branch1).await;
+    }
+
+    root1.refresh().await.unwrap();
+    let mut dir1 = root1
+        .create_directory("dir".to_owned(), rand::random(), &VersionVector::new())
+        .await
+        .unwrap();
+    let entry_data = root1.lookup("a").unwrap().clone_data();
+    root1
+        .move_entry(
+            "a",
+            entry_data,
+            &mut dir1,
+            "a",
+            VersionVector::first(*branch1.id()),
+        )
+        .await
+        .unwrap();
+
+    // Concurrently modify it once in the local branch.
+    root0.refresh().await.unwrap();
+    update_file(&root0, "a", b"local 0", &branch0).await;
+
+    merge_with(&[&branch0, &branch1], MergeStrategy::LastWriterWins)
+        .await
+        .unwrap();
+
+    // The move wins because it has more changes.
+    root0.refresh().await.unwrap();
+    assert_matches!(root0.lookup("a"), Ok(EntryRef::Tombstone(_)));
+
+    let dir0 = root0
+        .lookup("dir")
+        .unwrap()
+        .directory()
+        .unwrap()
+        .open(DirectoryFallback::Disabled)
+        .await
+        .unwrap();
+    let content = open_file(&dir0, "a").await.read_to_end().await.unwrap();
+    assert_eq!(content, b"remote 1");
+}
+
 // TODO: merge directory with missing blocks
 
 #[tokio::test(flavor = "multi_thread")]
@@ -1166,6 +1421,73 @@ async fn remove_non_empty_subdirectory() {
     assert_matches!(local_root.lookup("dir1"), Ok(EntryRef::Directory(_)));
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn has_unmerged_remote_version_file() {
+    let (_base_dir, [branch0, branch1]) = setup().await;
+
+    generate(&branch0, &["file.txt"]).await.unwrap();
+    merge(&[&branch1, &branch0]).await.unwrap();
+
+    // Both versions are the same.
+    assert!(!open_joint_root(&branch0, &branch1)
+        .await
+        .has_unmerged_remote_version("file.txt"));
+
+    // Remote version is newer.
+    let root1 = branch1.open_or_create_root().await.unwrap();
+    update_file(&root1, "file.txt", b"remote", &branch1).await;
+
+    assert!(open_joint_root(&branch0, &branch1)
+        .await
+        .has_unmerged_remote_version("file.txt"));
+
+    // Remote version merged into local.
+    merge(&[&branch0, &branch1]).await.unwrap();
+
+    assert!(!open_joint_root(&branch0, &branch1)
+        .await
+        .has_unmerged_remote_version("file.txt"));
+
+    // Remote version is older.
+    let root0 = branch0.open_or_create_root().await.unwrap();
+    update_file(&root0, "file.txt", b"local", &branch0).await;
+
+    assert!(!open_joint_root(&branch0, &branch1)
+        .await
+        .has_unmerged_remote_version("file.txt"));
+
+    // Remote version is concurrent.
+    let root1 = branch1.open_or_create_root().await.unwrap();
+    update_file(&root1, "file.txt", b"remote again", &branch1).await;
+
+    assert!(open_joint_root(&branch0, &branch1)
+        .await
+        .has_unmerged_remote_version("file.txt"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn has_unmerged_remote_version_directory() {
+    let (_base_dir, [branch0, branch1]) = setup().await;
+
+    // No local version.
+    generate(&branch1, &["dir"]).await.unwrap();
+    assert!(!open_joint_root(&branch0, &branch1)
+        .await
+        .has_unmerged_remote_version("dir"));
+
+    // Concurrent versions.
+    generate(&branch0, &["dir"]).await.unwrap();
+    assert!(open_joint_root(&branch0, &branch1)
+        .await
+        .has_unmerged_remote_version("dir"));
+
+    // Merged.
+    merge(&[&branch0, &branch1]).await.unwrap();
+    assert!(!open_joint_root(&branch0, &branch1)
+        .await
+        .has_unmerged_remote_version("dir"));
+}
+
 async fn setup<const N: usize>() -> (TempDir, [Branch; N]) {
     setup_with_rng::<N>(StdRng::from_entropy()).await
 }
@@ -1176,7 +1498,7 @@ async fn setup_with_rng<const N: usize>(mut rng: StdRng) -> (TempDir, [Branch; N
     let store = Store::new(pool);
     let event_tx = EventSender::new(1);
     let secrets = WriteSecrets::generate(&mut rng);
-    let shared = BranchShared::new();
+    let shared = BranchShared::new(BlockTracker::new());
 
     let branches = [(); N].map(|_| {
         let id = PublicKey::generate(&mut rng);
@@ -1283,12 +1605,17 @@ async fn generate(branch: &Branch, content: &[&str]) -> Result<()> {
 
 /// Merge all branches into the first one.
 async fn merge(branches: &[&Branch]) -> Result<()> {
+    merge_with(branches, MergeStrategy::UnionEntries).await
+}
+
+/// Merge all branches into the first one using the given strategy.
+async fn merge_with(branches: &[&Branch], strategy: MergeStrategy) -> Result<()> {
     let roots = future::try_join_all(branches.iter().map(|branch| branch.open_or_create_root()))
         .await
         .unwrap();
 
     JointDirectory::new(Some(branches[0].clone()), roots)
-        .merge()
+        .merge_with(strategy)
         .await?;
 
     Ok(())
@@ -1350,3 +1677,10 @@ async fn dump_directory_into(
 
     Ok(())
 }
+
+async fn open_joint_root(local_branch: &Branch, remote_branch: &Branch) -> JointDirectory {
+    let local_root = local_branch.open_or_create_root().await.unwrap();
+    let remote_root = remote_branch.open_or_create_root().await.unwrap();
+
+    JointDirectory::new(Some(local_branch.clone()), [local_root, remote_root])
+}
@@ -34,6 +34,7 @@ mod sync;
 #[cfg(test)]
 mod test_utils;
 mod time;
+mod transfer_tracker;
 #[cfg_attr(test, macro_use)]
 mod version_vector;
 mod versioned;
@@ -48,23 +49,30 @@ pub use self::{
     db::SCHEMA_VERSION,
     debug::DebugPrinter,
     device_id::DeviceId,
-    directory::{Directory, EntryRef, EntryType, DIRECTORY_VERSION},
+    directory::{
+        Directory, EntryRef, EntryType, FileMetadata, DIRECTORY_VERSION, MAX_FILE_METADATA_SIZE,
+    },
     error::{Error, Result},
-    event::{Event, Payload},
-    file::File,
-    joint_directory::{JointDirectory, JointEntryRef},
+    event::{Event, LoggedEvent, Payload},
+    file::{File, FileReader},
+    joint_directory::{JointDirectory, JointEntryRef, MergeStrategy},
     joint_entry::JointEntry,
     network::{
-        repository_info_hash, DhtContactsStoreTrait, NatBehavior, Network, PeerAddr, PeerInfo,
-        PeerInfoCollector, PeerSource, PeerState, PublicRuntimeId, Registration, SecretRuntimeId,
-        Stats, DHT_ROUTERS,
+        repository_info_hash, set_discovery_enabled, BandwidthLimit, BandwidthLimitOverride,
+        BindError, CongestionKind, DhtContactsStoreTrait, ExternalAddrs, KeepAliveConfig,
+        MessageCounts, MessageStats, NatBehavior, Network, PeerAddr, PeerEvent, PeerEventKind,
+        PeerHost, PeerInfo, PeerInfoCollector, PeerSource, PeerState, ProtocolMismatch,
+        PublicRuntimeId, QuicTuning, ReconnectBackoff, Registration, SecretRuntimeId, Stats,
+        TransportPreference, DHT_ROUTERS, MAX_DEVICE_NAME_LEN,
     },
     progress::Progress,
     protocol::{RepositoryId, StorageSize, BLOCK_SIZE},
     repository::{
-        delete as delete_repository, Credentials, Metadata, Repository, RepositoryHandle,
-        RepositoryParams,
+        delete as delete_repository, Batch, BranchStatus, Conflict, ConflictVersion, Credentials,
+        DirEvent, DuplicatesSearchEvent, LockReason, Metadata, PathEvent, RebuildIndexEvent,
+        RebuildIndexReport, Repository, RepositoryHandle, RepositoryParams, SizeBreakdown,
+        StorageStats,
     },
-    store::{Error as StoreError, DATA_VERSION},
+    store::{Error as StoreError, IntegrityReport, DATA_VERSION},
     version_vector::VersionVector,
 };
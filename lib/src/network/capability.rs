@@ -0,0 +1,273 @@
+//! Proving to a peer what access mode we have to a repository and verifying the proofs received
+//! from peers.
+//!
+//! The proofs are exchanged right after the encrypted channel is established and are bound to it
+//! (via the handshake hash) and to the role of the prover. This prevents replaying them in other
+//! sessions or reflecting them back to their sender. A write proof is a signature made with the
+//! repository write key and can be verified by anyone. A read proof is a keyed hash made with the
+//! read key and so can be verified only by peers that have at least read access themselves - for
+//! everyone else such peer appears to be blind.
+
+use super::{crypto::Role, runtime_id::PublicRuntimeId};
+use crate::{
+    access_control::{AccessMode, AccessSecrets},
+    collections::HashMap,
+    crypto::{cipher::SecretKey, sign::Signature, Hashable},
+    repository::Credentials,
+};
+use deadlock::{BlockingMutex, BlockingRwLock};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Proof of the access mode a replica has to a repository.
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) enum Proof {
+    Blind,
+    Read([u8; SecretKey::SIZE]),
+    Write(Signature),
+}
+
+/// Capabilities of this replica and of its peers for a single repository.
+#[derive(Clone)]
+pub(super) struct Capabilities {
+    credentials: Arc<BlockingRwLock<Credentials>>,
+    peers: Arc<BlockingMutex<HashMap<PublicRuntimeId, PeerEntry>>>,
+}
+
+// Access mode of a peer together with the number of its links that verified it. A peer can be
+// connected over multiple links at the same time (e.g. TCP and QUIC).
+struct PeerEntry {
+    access_mode: AccessMode,
+    links: usize,
+}
+
+impl Capabilities {
+    pub fn new(credentials: Arc<BlockingRwLock<Credentials>>) -> Self {
+        Self {
+            credentials,
+            peers: Arc::default(),
+        }
+    }
+
+    /// Creates proof of our access mode for the session with the given handshake hash.
+    pub fn prove(&self, session: &[u8], role: Role) -> Proof {
+        prove(self.credentials.read().unwrap().secrets(), session, role)
+    }
+
+    /// Verifies the proof received from the peer with the given role and returns the access mode
+    /// it proves.
+    pub fn verify(&self, session: &[u8], role: Role, proof: &Proof) -> AccessMode {
+        verify(
+            self.credentials.read().unwrap().secrets(),
+            session,
+            role,
+            proof,
+        )
+    }
+
+    /// Records the access mode verified on a link to the given peer. The most recently verified
+    /// mode is the one reported. The peer is forgotten once the returned guards of all its links
+    /// are dropped.
+    pub fn insert_peer(&self, runtime_id: PublicRuntimeId, access_mode: AccessMode) -> PeerGuard {
+        self.peers
+            .lock()
+            .unwrap()
+            .entry(runtime_id)
+            .and_modify(|entry| {
+                entry.access_mode = access_mode;
+                entry.links += 1;
+            })
+            .or_insert(PeerEntry {
+                access_mode,
+                links: 1,
+            });
+
+        PeerGuard {
+            peers: self.peers.clone(),
+            runtime_id,
+        }
+    }
+
+    pub fn peer(&self, runtime_id: &PublicRuntimeId) -> Option<AccessMode> {
+        self.peers
+            .lock()
+            .unwrap()
+            .get(runtime_id)
+            .map(|entry| entry.access_mode)
+    }
+}
+
+/// Keeps the capabilities of a peer recorded while the link they were verified on is running.
+pub(super) struct PeerGuard {
+    peers: Arc<BlockingMutex<HashMap<PublicRuntimeId, PeerEntry>>>,
+    runtime_id: PublicRuntimeId,
+}
+
+impl Drop for PeerGuard {
+    fn drop(&mut self) {
+        let mut peers = self.peers.lock().unwrap();
+
+        let Some(entry) = peers.get_mut(&self.runtime_id) else {
+            return;
+        };
+
+        entry.links -= 1;
+
+        if entry.links == 0 {
+            peers.remove(&self.runtime_id);
+        }
+    }
+}
+
+fn prove(secrets: &AccessSecrets, session: &[u8], role: Role) -> Proof {
+    let message = message(secrets, session, role);
+
+    match secrets {
+        AccessSecrets::Blind { .. } => Proof::Blind,
+        AccessSecrets::Read { read_key, .. } => {
+            Proof::Read(*read_key_proof(read_key, &message).as_array())
+        }
+        AccessSecrets::Write(secrets) => Proof::Write(secrets.write_keys.sign(message.as_ref())),
+    }
+}
+
+fn verify(secrets: &AccessSecrets, session: &[u8], role: Role, proof: &Proof) -> AccessMode {
+    let message = message(secrets, session, role);
+
+    match proof {
+        Proof::Blind => AccessMode::Blind,
+        Proof::Read(proof) => match secrets.read_key() {
+            Some(read_key)
+                if SecretKey::try_from(&proof[..]).ok()
+                    == Some(read_key_proof(read_key, &message)) =>
+            {
+                AccessMode::Read
+            }
+            // We can't verify the proof without the read key (or the proof is invalid).
+            _ => AccessMode::Blind,
+        },
+        Proof::Write(signature) => {
+            if secrets
+                .id()
+                .write_public_key()
+                .verify(message.as_ref(), signature)
+            {
+                AccessMode::Write
+            } else {
+                AccessMode::Blind
+            }
+        }
+    }
+}
+
+fn message(secrets: &AccessSecrets, session: &[u8], role: Role) -> impl AsRef<[u8]> {
+    let role: u8 = match role {
+        Role::Initiator => 0,
+        Role::Responder => 1,
+    };
+
+    (secrets.id(), session, role, b"ouisync capability proof").hash()
+}
+
+fn read_key_proof(read_key: &SecretKey, message: &impl AsRef<[u8]>) -> SecretKey {
+    SecretKey::derive_from_key(read_key.as_array(), message.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{access_control::WriteSecrets, network::runtime_id::SecretRuntimeId};
+
+    #[test]
+    fn verify_proofs() {
+        let session: [u8; 32] = rand::random();
+        let write = AccessSecrets::Write(WriteSecrets::random());
+        let read = write.with_mode(AccessMode::Read);
+        let blind = write.with_mode(AccessMode::Blind);
+
+        for (prover, verifier, expected) in [
+            (&write, &write, AccessMode::Write),
+            (&write, &read, AccessMode::Write),
+            (&write, &blind, AccessMode::Write),
+            (&read, &write, AccessMode::Read),
+            (&read, &read, AccessMode::Read),
+            // Read proof can't be verified without the read key.
+            (&read, &blind, AccessMode::Blind),
+            (&blind, &write, AccessMode::Blind),
+        ] {
+            let proof = prove(prover, &session, Role::Initiator);
+            assert_eq!(
+                verify(verifier, &session, Role::Initiator, &proof),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn reject_proof_from_different_session() {
+        let write = AccessSecrets::Write(WriteSecrets::random());
+        let read = write.with_mode(AccessMode::Read);
+
+        let proof = prove(&write, b"session 1", Role::Initiator);
+        assert_eq!(
+            verify(&write, b"session 2", Role::Initiator, &proof),
+            AccessMode::Blind
+        );
+
+        let proof = prove(&read, b"session 1", Role::Initiator);
+        assert_eq!(
+            verify(&read, b"session 2", Role::Initiator, &proof),
+            AccessMode::Blind
+        );
+    }
+
+    #[test]
+    fn reject_reflected_proof() {
+        let write = AccessSecrets::Write(WriteSecrets::random());
+        let read = write.with_mode(AccessMode::Read);
+
+        // A blind peer sends our own proof back to us.
+        let proof = prove(&write, b"session", Role::Initiator);
+        assert_eq!(
+            verify(&write, b"session", Role::Responder, &proof),
+            AccessMode::Blind
+        );
+
+        let proof = prove(&read, b"session", Role::Initiator);
+        assert_eq!(
+            verify(&read, b"session", Role::Responder, &proof),
+            AccessMode::Blind
+        );
+    }
+
+    #[test]
+    fn reject_proof_for_different_repository() {
+        let session = b"session";
+        let this = AccessSecrets::Write(WriteSecrets::random());
+        let other = AccessSecrets::Write(WriteSecrets::random());
+
+        let proof = prove(&other, session, Role::Initiator);
+        assert_eq!(
+            verify(&this, session, Role::Initiator, &proof),
+            AccessMode::Blind
+        );
+    }
+
+    #[test]
+    fn peer_with_multiple_links() {
+        let capabilities = Capabilities::new(Arc::new(BlockingRwLock::new(
+            Credentials::with_random_writer_id(AccessSecrets::random_write()),
+        )));
+        let runtime_id = SecretRuntimeId::random().public();
+
+        let guard_tcp = capabilities.insert_peer(runtime_id, AccessMode::Write);
+        let guard_quic = capabilities.insert_peer(runtime_id, AccessMode::Write);
+
+        // Closing one link keeps the capabilities of the peer.
+        drop(guard_tcp);
+        assert_eq!(capabilities.peer(&runtime_id), Some(AccessMode::Write));
+
+        drop(guard_quic);
+        assert_eq!(capabilities.peer(&runtime_id), None);
+    }
+}
@@ -16,9 +16,15 @@ use crate::{
         UntrustedProof,
     },
     repository::Vault,
-    store::{ClientReader, ClientWriter},
+    store::{ClientReader, ClientWriter, RootNodeStatus},
+};
+use std::{
+    iter,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
-use std::iter;
 use tokio::{select, sync::mpsc};
 use tracing::{instrument, Level};
 
@@ -37,8 +43,10 @@ impl Client {
         vault: Vault,
         content_tx: mpsc::UnboundedSender<Content>,
         response_rx: mpsc::Receiver<Response>,
+        block_storage_enabled: Arc<AtomicBool>,
     ) -> Self {
-        let pending_requests = PendingRequests::new(vault.monitor.clone());
+        let pending_requests =
+            PendingRequests::new(vault.monitor.clone(), vault.transfer_tracker.clone());
         let block_tracker = vault.block_tracker.client();
 
         let inner = Inner {
@@ -46,6 +54,7 @@ impl Client {
             pending_requests,
             block_tracker,
             content_tx,
+            block_storage_enabled,
         };
 
         Self { inner, response_rx }
@@ -65,6 +74,8 @@ struct Inner {
     pending_requests: PendingRequests,
     block_tracker: TrackerClient,
     content_tx: mpsc::UnboundedSender<Content>,
+    // If false, no blocks are requested and any received ones are discarded.
+    block_storage_enabled: Arc<AtomicBool>,
 }
 
 impl Inner {
@@ -225,7 +236,13 @@ impl Inner {
 
         tracing::debug!("Received root node - {status}");
 
-        if status.request_children() {
+        // Without block storage we only need the index, not the block presence.
+        let request_children = match status {
+            RootNodeStatus::NewBlocks => self.block_storage_enabled.load(Ordering::Relaxed),
+            _ => status.request_children(),
+        };
+
+        if request_children {
             self.send_request(PendingRequest::ChildNodes(
                 hash,
                 ResponseDisambiguator::new(block_presence),
@@ -252,7 +269,13 @@ impl Inner {
             total
         );
 
-        for node in status.new_children {
+        let new_blocks_children = if self.block_storage_enabled.load(Ordering::Relaxed) {
+            status.new_blocks_children
+        } else {
+            Vec::new()
+        };
+
+        for node in status.new_children.into_iter().chain(new_blocks_children) {
             self.send_request(PendingRequest::ChildNodes(
                 node.hash,
                 ResponseDisambiguator::new(node.summary.block_presence),
@@ -279,6 +302,10 @@ impl Inner {
             total,
         );
 
+        if !self.block_storage_enabled.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
         for (block_id, state) in status.new_block_offers {
             self.block_tracker.register(block_id, state);
         }
@@ -293,6 +320,10 @@ impl Inner {
         block_id: BlockId,
         debug_payload: DebugResponse,
     ) -> Result<()> {
+        if !self.block_storage_enabled.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
         let Some(offer_state) = reader.load_block_offer_state(&block_id).await? else {
             return Ok(());
         };
@@ -312,6 +343,12 @@ impl Inner {
         block_promise: Option<BlockPromise>,
         debug_payload: DebugResponse,
     ) -> Result<()> {
+        // The block might have been requested before block storage got disabled.
+        if !self.block_storage_enabled.load(Ordering::Relaxed) {
+            tracing::trace!("Discarded block (block storage disabled)");
+            return Ok(());
+        }
+
         writer.save_block(&block, block_promise).await?;
 
         tracing::trace!("Received block");
@@ -551,7 +588,8 @@ mod tests {
 
         vault.block_tracker.set_request_mode(RequestMode::Lazy);
 
-        let pending_requests = PendingRequests::new(vault.monitor.clone());
+        let pending_requests =
+            PendingRequests::new(vault.monitor.clone(), vault.transfer_tracker.clone());
         let block_tracker = vault.block_tracker.client();
 
         let (content_tx, _content_rx) = mpsc::unbounded_channel();
@@ -561,6 +599,7 @@ mod tests {
             pending_requests,
             block_tracker,
             content_tx,
+            block_storage_enabled: Arc::new(AtomicBool::new(true)),
         };
 
         (base_dir, inner, secrets)
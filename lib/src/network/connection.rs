@@ -14,47 +14,101 @@ use serde::Serialize;
 use std::{
     fmt,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 use tokio::sync::watch;
 
+/// Max number of dead peers kept listed. When exceeded, the ones that were reserved the earliest
+/// are removed.
+const MAX_DEAD_PEERS: usize = 32;
+
 /// Container for known connections.
 pub(super) struct ConnectionSet {
     connections: watch::Sender<HashMap<Key, Data>>,
+    // Max number of connections (`usize::MAX` means unlimited).
+    max_connections: AtomicUsize,
 }
 
 impl ConnectionSet {
     pub fn new() -> Self {
         Self {
             connections: watch::Sender::new(HashMap::default()),
+            max_connections: AtomicUsize::new(usize::MAX),
         }
     }
 
+    /// Sets the max number of connections (`None` means unlimited). Existing connections are kept
+    /// even if there are more of them than the new limit, only new reservations are refused.
+    pub fn set_max_connections(&self, max: Option<usize>) {
+        let max = max.unwrap_or(usize::MAX);
+        let old = self.max_connections.swap(max, Ordering::Relaxed);
+
+        // Wake up anyone waiting for a free slot.
+        if max > old {
+            self.connections.send_modify(|_| ());
+        }
+    }
+
+    pub fn max_connections(&self) -> Option<usize> {
+        match self.max_connections.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            max => Some(max),
+        }
+    }
+
+    /// Waits until the number of connections drops below the max.
+    pub async fn wait_for_free_slot(&self) {
+        self.connections
+            .subscribe()
+            .wait_for(|connections| {
+                live_count(connections) < self.max_connections.load(Ordering::Relaxed)
+            })
+            .await
+            .ok();
+    }
+
     /// Attempt to reserve an connection to the given peer. If the connection hasn't been reserved
     /// yet, it returns a `ConnectionPermit` which keeps the connection reserved as long as it
     /// lives. Otherwise it returns `None`. To release a connection the permit needs to be dropped.
     /// Also returns a notification object that can be used to wait until the permit gets released.
+    /// Returns `ReserveResult::Full` if the max number of connections has been reached. Entries of
+    /// peers marked as dead are replaced.
     pub fn reserve(&self, addr: PeerAddr, source: PeerSource) -> ReserveResult {
         let key = Key {
             addr,
             dir: ConnectionDirection::from_source(source),
         };
-
-        self.connections
-            .send_if_modified_return(|connections| match connections.entry(key) {
-                Entry::Vacant(entry) => {
+        let max_connections = self.max_connections.load(Ordering::Relaxed);
+
+        self.connections.send_if_modified_return(|connections| {
+            let full = live_count(connections) >= max_connections;
+
+            match connections.get(&key) {
+                Some(data) if data.state != PeerState::Dead => (
+                    false,
+                    ReserveResult::Occupied(data.on_release.subscribe(), data.source, data.id),
+                ),
+                // Vacant or dead
+                _ if full => (false, ReserveResult::Full),
+                _ => {
                     let id = ConnectionId::next();
 
-                    entry.insert(Data {
-                        id,
-                        state: PeerState::Known,
-                        source,
-                        stats_tracker: StatsTracker::default(),
-                        on_release: DropAwaitable::new(),
-                    });
+                    connections.insert(
+                        key,
+                        Data {
+                            id,
+                            state: PeerState::Known,
+                            source,
+                            stats_tracker: StatsTracker::default(),
+                            connected_since: SystemTime::now(),
+                            rtt: None,
+                            display_name: None,
+                            on_release: DropAwaitable::new(),
+                        },
+                    );
 
                     (
                         true,
@@ -65,19 +119,8 @@ impl ConnectionSet {
                         }),
                     )
                 }
-                Entry::Occupied(entry) => {
-                    let peer_permit = entry.get();
-
-                    (
-                        false,
-                        ReserveResult::Occupied(
-                            peer_permit.on_release.subscribe(),
-                            peer_permit.source,
-                            peer_permit.id,
-                        ),
-                    )
-                }
-            })
+            }
+        })
     }
 
     pub fn peer_info_collector(&self) -> PeerInfoCollector {
@@ -101,6 +144,24 @@ impl ConnectionSet {
             .map(|data| data.peer_info(addr))
     }
 
+    /// Returns notifications of the release of all the active connections to the peer with the
+    /// given runtime id whose address matches the predicate.
+    pub fn active_released(
+        &self,
+        runtime_id: &PublicRuntimeId,
+        pred: impl Fn(&PeerAddr) -> bool,
+    ) -> Vec<AwaitDrop> {
+        self.connections
+            .borrow()
+            .iter()
+            .filter(|(key, data)| {
+                matches!(data.state, PeerState::Active { id, .. } if id == *runtime_id)
+                    && pred(&key.addr)
+            })
+            .map(|(_, data)| data.on_release.subscribe())
+            .collect()
+    }
+
     pub fn subscribe(&self) -> ConnectionSetSubscription {
         ConnectionSetSubscription(self.connections.subscribe())
     }
@@ -109,7 +170,7 @@ impl ConnectionSet {
 /// Unique identifier of a connection. Connections are mostly already identified by the peer address
 /// and direction (incoming / outgoing), but this type allows to distinguish even connections with
 /// the same address/direction but that were established in two separate occasions.
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
 #[repr(transparent)]
 pub(super) struct ConnectionId(u64);
 
@@ -130,6 +191,8 @@ pub(super) enum ReserveResult {
     Permit(ConnectionPermit),
     // Use the receiver to get notified when the existing permit is destroyed.
     Occupied(AwaitDrop, PeerSource, ConnectionId),
+    // The max number of connections has been reached.
+    Full,
 }
 
 #[derive(Clone)]
@@ -206,10 +269,42 @@ impl ConnectionPermit {
         self.set_state(PeerState::Handshaking);
     }
 
-    pub fn mark_as_active(&self, runtime_id: PublicRuntimeId) {
-        self.set_state(PeerState::Active {
-            id: runtime_id,
-            since: SystemTime::now(),
+    pub fn mark_as_active(&self, runtime_id: PublicRuntimeId, display_name: Option<String>) {
+        let since = SystemTime::now();
+
+        self.connections.send_modify(|connections| {
+            // unwrap is ok because if `self` exists then the entry should exists as well.
+            let peer = connections.get_mut(&self.key).unwrap();
+            peer.state = PeerState::Active {
+                id: runtime_id,
+                since,
+            };
+            peer.connected_since = since;
+            peer.display_name = display_name;
+        });
+    }
+
+    /// Releases the permit but keeps the peer listed (as `PeerState::Dead`) so that it's still
+    /// reported in the peer info. The entry doesn't count towards the max number of connections
+    /// and is replaced by the next reservation of the same peer. At most `MAX_DEAD_PEERS` dead
+    /// peers are kept, the oldest ones are removed first.
+    pub fn mark_as_dead(self) {
+        self.connections.send_if_modified(|connections| {
+            let Some(peer) = connections.get_mut(&self.key) else {
+                return false;
+            };
+
+            if peer.id != self.id {
+                return false;
+            }
+
+            peer.state = PeerState::Dead;
+            // Notify anyone waiting for this permit to be released.
+            peer.on_release = DropAwaitable::new();
+
+            prune_dead(connections);
+
+            true
         });
     }
 
@@ -220,6 +315,11 @@ impl ConnectionPermit {
 
             if peer.state != new_state {
                 peer.state = new_state;
+
+                if let PeerState::Active { since, .. } = new_state {
+                    peer.connected_since = since;
+                }
+
                 true
             } else {
                 false
@@ -227,6 +327,23 @@ impl ConnectionPermit {
         });
     }
 
+    /// Records the round-trip time measured on this connection.
+    pub fn set_rtt(&self, rtt: Duration) {
+        self.connections.send_if_modified(|connections| {
+            // The entry might not exist if this is a `ConnectionPermitHalf` whose other half has
+            // been dropped already.
+            if let Some(peer) = connections.get_mut(&self.key) {
+                if peer.id == self.id {
+                    peer.rtt = Some(rtt);
+                }
+            }
+
+            // Don't notify the subscribers. The RTT changes after every ping which would flood
+            // them. The new value is picked up the next time the peer info is collected.
+            false
+        });
+    }
+
     /// Returns a `AwaitDrop` that gets notified when this permit gets released.
     pub fn released(&self) -> AwaitDrop {
         // We can't use unwrap here because this method is used in `ConnectionPermitHalf` which can
@@ -268,6 +385,9 @@ impl ConnectionPermit {
             state: PeerState::Known,
             source: PeerSource::UserProvided,
             stats_tracker: StatsTracker::default(),
+            connected_since: SystemTime::now(),
+            rtt: None,
+            display_name: None,
             on_release: DropAwaitable::new(),
         };
 
@@ -302,7 +422,7 @@ impl Drop for ConnectionPermit {
                 return false;
             };
 
-            if entry.get().id != self.id {
+            if entry.get().id != self.id || entry.get().state == PeerState::Dead {
                 return false;
             }
 
@@ -321,6 +441,10 @@ impl ConnectionPermitHalf {
         self.0.id
     }
 
+    pub fn addr(&self) -> PeerAddr {
+        self.0.addr()
+    }
+
     pub fn byte_counters(&self) -> Arc<ByteCounters> {
         self.0.byte_counters()
     }
@@ -328,6 +452,10 @@ impl ConnectionPermitHalf {
     pub fn released(&self) -> AwaitDrop {
         self.0.released()
     }
+
+    pub fn set_rtt(&self, rtt: Duration) {
+        self.0.set_rtt(rtt)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -341,9 +469,39 @@ struct Data {
     state: PeerState,
     source: PeerSource,
     stats_tracker: StatsTracker,
+    connected_since: SystemTime,
+    rtt: Option<Duration>,
+    display_name: Option<String>,
     on_release: DropAwaitable,
 }
 
+/// Number of connections not counting the dead peers.
+fn live_count(connections: &HashMap<Key, Data>) -> usize {
+    connections
+        .values()
+        .filter(|data| data.state != PeerState::Dead)
+        .count()
+}
+
+/// Removes the oldest dead peers so that at most `MAX_DEAD_PEERS` of them remain.
+fn prune_dead(connections: &mut HashMap<Key, Data>) {
+    let mut dead: Vec<_> = connections
+        .iter()
+        .filter(|(_, data)| data.state == PeerState::Dead)
+        .map(|(key, data)| (data.id, *key))
+        .collect();
+
+    if dead.len() <= MAX_DEAD_PEERS {
+        return;
+    }
+
+    dead.sort_unstable_by_key(|(id, _)| *id);
+
+    for (_, key) in &dead[..dead.len() - MAX_DEAD_PEERS] {
+        connections.remove(key);
+    }
+}
+
 impl Data {
     fn peer_info(&self, addr: PeerAddr) -> PeerInfo {
         let stats = self.stats_tracker.read();
@@ -353,6 +511,123 @@ impl Data {
             source: self.source,
             state: self.state,
             stats,
+            connected_since: self.connected_since,
+            rtt: self.rtt,
+            display_name: self.display_name.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[tokio::test]
+    async fn max_connections() {
+        let connections = ConnectionSet::new();
+        connections.set_max_connections(Some(1));
+
+        let addr_a = PeerAddr::Tcp((Ipv4Addr::LOCALHOST, 1000).into());
+        let addr_b = PeerAddr::Tcp((Ipv4Addr::LOCALHOST, 1001).into());
+
+        let permit = match connections.reserve(addr_a, PeerSource::UserProvided) {
+            ReserveResult::Permit(permit) => permit,
+            _ => panic!("expected permit"),
+        };
+
+        assert!(matches!(
+            connections.reserve(addr_b, PeerSource::Listener),
+            ReserveResult::Full
+        ));
+
+        drop(permit);
+        connections.wait_for_free_slot().await;
+
+        let _permit = match connections.reserve(addr_b, PeerSource::Listener) {
+            ReserveResult::Permit(permit) => permit,
+            _ => panic!("expected permit"),
+        };
+
+        assert!(matches!(
+            connections.reserve(addr_a, PeerSource::UserProvided),
+            ReserveResult::Full
+        ));
+
+        // Removing the limit allows new reservations again.
+        connections.set_max_connections(None);
+        assert!(matches!(
+            connections.reserve(addr_a, PeerSource::UserProvided),
+            ReserveResult::Permit(_)
+        ));
+    }
+
+    #[test]
+    fn dead_peer() {
+        let connections = ConnectionSet::new();
+        connections.set_max_connections(Some(1));
+
+        let addr_a = PeerAddr::Tcp((Ipv4Addr::LOCALHOST, 1000).into());
+        let addr_b = PeerAddr::Tcp((Ipv4Addr::LOCALHOST, 1001).into());
+
+        let permit = match connections.reserve(addr_a, PeerSource::UserProvided) {
+            ReserveResult::Permit(permit) => permit,
+            _ => panic!("expected permit"),
+        };
+
+        permit.mark_as_dead();
+
+        // The dead peer is still reported...
+        assert_eq!(
+            connections.get_peer_info(addr_a).map(|info| info.state),
+            Some(PeerState::Dead)
+        );
+
+        // ...but doesn't count towards the limit.
+        let permit = match connections.reserve(addr_b, PeerSource::UserProvided) {
+            ReserveResult::Permit(permit) => permit,
+            _ => panic!("expected permit"),
+        };
+        drop(permit);
+
+        // Reserving the dead peer again replaces it.
+        let _permit = match connections.reserve(addr_a, PeerSource::UserProvided) {
+            ReserveResult::Permit(permit) => permit,
+            _ => panic!("expected permit"),
+        };
+        assert_eq!(
+            connections.get_peer_info(addr_a).map(|info| info.state),
+            Some(PeerState::Known)
+        );
+    }
+
+    #[test]
+    fn dead_peers_are_pruned() {
+        let connections = ConnectionSet::new();
+        let addrs: Vec<_> = (0..MAX_DEAD_PEERS as u16 + 2)
+            .map(|port| PeerAddr::Tcp((Ipv4Addr::LOCALHOST, 1000 + port).into()))
+            .collect();
+
+        for addr in &addrs {
+            match connections.reserve(*addr, PeerSource::Dht) {
+                ReserveResult::Permit(permit) => permit.mark_as_dead(),
+                _ => panic!("expected permit"),
+            }
         }
+
+        assert_eq!(
+            connections.peer_info_collector().collect().len(),
+            MAX_DEAD_PEERS
+        );
+
+        // The oldest ones are removed, the most recent ones are kept.
+        assert!(connections.get_peer_info(addrs[0]).is_none());
+        assert!(connections.get_peer_info(addrs[1]).is_none());
+        assert_eq!(
+            connections
+                .get_peer_info(*addrs.last().unwrap())
+                .map(|info| info.state),
+            Some(PeerState::Dead)
+        );
     }
 }
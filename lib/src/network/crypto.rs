@@ -32,6 +32,14 @@ pub(super) enum Role {
 }
 
 impl Role {
+    /// Role of the other replica.
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Initiator => Self::Responder,
+            Self::Responder => Self::Initiator,
+        }
+    }
+
     /// Determine the role this replica will have in the communication protocol for the given
     /// repository.
     ///
@@ -114,13 +122,13 @@ impl EncryptingSink<'_> {
 }
 
 /// Establish encrypted communication channel for the purpose of syncing the given
-/// repository.
+/// repository. Returns also the handshake hash which uniquely identifies the established session.
 pub(super) async fn establish_channel<'a>(
     role: Role,
     repo_id: &RepositoryId,
     stream: &'a mut Instrumented<ContentStream>,
     sink: &'a mut Instrumented<ContentSink>,
-) -> Result<(DecryptingStream<'a>, EncryptingSink<'a>), EstablishError> {
+) -> Result<(DecryptingStream<'a>, EncryptingSink<'a>, Vec<u8>), EstablishError> {
     let mut handshake_state = build_handshake_state(role, repo_id);
 
     let (recv_cipher, send_cipher) = match role {
@@ -143,6 +151,8 @@ pub(super) async fn establish_channel<'a>(
         }
     };
 
+    let session_hash = handshake_state.get_hash().to_vec();
+
     let stream = DecryptingStream {
         inner: stream,
         cipher: recv_cipher,
@@ -155,7 +165,7 @@ pub(super) async fn establish_channel<'a>(
         buffer: vec![],
     };
 
-    Ok((stream, sink))
+    Ok((stream, sink, session_hash))
 }
 
 #[derive(Debug, Error)]
@@ -0,0 +1,64 @@
+//! Human readable name of a replica, advertised to peers during the handshake so they can show
+//! something friendlier than the runtime id. The name is not authenticated in any way - any peer
+//! can claim any name - so it must be treated as purely cosmetic.
+
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Max length of a device name in bytes. Longer names are truncated.
+pub const MAX_DEVICE_NAME_LEN: usize = 64;
+
+/// Removes control characters, surrounding whitespace and truncates the name to
+/// [`MAX_DEVICE_NAME_LEN`] bytes (on a char boundary).
+pub(super) fn sanitize(name: &str) -> String {
+    let mut output = String::new();
+
+    for c in name.trim().chars().filter(|c| !c.is_control()) {
+        if output.len() + c.len_utf8() > MAX_DEVICE_NAME_LEN {
+            break;
+        }
+
+        output.push(c);
+    }
+
+    output.trim_end().to_owned()
+}
+
+/// Sends our (already sanitized) name to the peer and receives theirs. Returns `None` if the peer
+/// didn't advertise any name.
+pub(super) async fn exchange<IO>(our_name: &str, io: &mut IO) -> io::Result<Option<String>>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    debug_assert!(our_name.len() <= MAX_DEVICE_NAME_LEN);
+
+    io.write_all(&[our_name.len() as u8]).await?;
+    io.write_all(our_name.as_bytes()).await?;
+
+    let mut len = [0; 1];
+    io.read_exact(&mut len).await?;
+
+    let mut buffer = vec![0; len[0] as usize];
+    io.read_exact(&mut buffer).await?;
+
+    let their_name = sanitize(&String::from_utf8_lossy(&buffer));
+
+    Ok(Some(their_name).filter(|name| !name.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_name() {
+        assert_eq!(sanitize("  my phone \n"), "my phone");
+        assert_eq!(sanitize("a\u{1b}[31mb\0c"), "a[31mbc");
+        assert_eq!(sanitize("\t\r\n"), "");
+
+        let long = "ž".repeat(MAX_DEVICE_NAME_LEN);
+        let sanitized = sanitize(&long);
+        assert_eq!(sanitized.len(), MAX_DEVICE_NAME_LEN);
+        assert!(long.starts_with(&sanitized));
+    }
+}
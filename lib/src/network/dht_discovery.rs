@@ -10,7 +10,7 @@ use futures_util::{stream, StreamExt};
 use net::{quic, udp::DatagramSocket};
 use rand::Rng;
 use scoped_task::ScopedJoinHandle;
-use state_monitor::StateMonitor;
+use state_monitor::{MonitoredValue, StateMonitor};
 use std::{
     collections::{hash_map, HashMap, HashSet},
     future::pending,
@@ -25,12 +25,12 @@ use std::{
 use tokio::{
     select,
     sync::{mpsc, watch},
-    time::{self, timeout, Duration},
+    time::{self, timeout, Duration, Instant},
 };
 use tracing::{instrument::Instrument, Span};
 
-// Hardcoded DHT routers to bootstrap the DHT against.
-// TODO: add this to `NetworkOptions` so it can be overriden by the user.
+// Default DHT routers to bootstrap the DHT against. Can be overriden with
+// `Network::set_dht_bootstrap_nodes`.
 pub const DHT_ROUTERS: &[&str] = &[
     "dht.ouisync.net:6881",
     "router.bittorrent.com:6881",
@@ -45,6 +45,10 @@ pub const DHT_ROUTERS: &[&str] = &[
 const MIN_DHT_ANNOUNCE_DELAY: Duration = Duration::from_secs(3 * 60);
 const MAX_DHT_ANNOUNCE_DELAY: Duration = Duration::from_secs(6 * 60);
 
+// Minimal interval between two out-of-cycle announces (see `LookupRequest::announce_now`) of the
+// same repository, so that repeated requests don't spam the DHT.
+const MIN_FORCED_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(30);
+
 #[async_trait]
 pub trait DhtContactsStoreTrait: Sync + Send + 'static {
     async fn load_v4(&self) -> io::Result<HashSet<SocketAddrV4>>;
@@ -70,8 +74,18 @@ impl DhtDiscovery {
         contacts_store: Option<Arc<dyn DhtContactsStoreTrait>>,
         monitor: StateMonitor,
     ) -> Self {
-        let v4 = BlockingMutex::new(RestartableDht::new(socket_maker_v4, contacts_store.clone()));
-        let v6 = BlockingMutex::new(RestartableDht::new(socket_maker_v6, contacts_store));
+        let routers = default_routers();
+
+        let v4 = BlockingMutex::new(RestartableDht::new(
+            socket_maker_v4,
+            routers.clone(),
+            contacts_store.clone(),
+        ));
+        let v6 = BlockingMutex::new(RestartableDht::new(
+            socket_maker_v6,
+            routers,
+            contacts_store,
+        ));
 
         let lookups = Arc::new(BlockingMutex::new(HashMap::default()));
 
@@ -102,6 +116,65 @@ impl DhtDiscovery {
         v4.rebind(socket_maker_v4);
         v6.rebind(socket_maker_v6);
 
+        self.restart_lookups(&mut v4, &mut v6);
+    }
+
+    // Replace the routers used to bootstrap the DHTs. Empty `routers` means use the default ones
+    // (`DHT_ROUTERS`). Like in `rebind`, any running DHTs are restarted so the change takes effect
+    // immediately.
+    pub fn set_routers(&self, routers: Vec<String>) {
+        let routers = if routers.is_empty() {
+            default_routers()
+        } else {
+            routers
+        };
+
+        let mut v4 = self.v4.lock().unwrap();
+        let mut v6 = self.v6.lock().unwrap();
+
+        v4.set_routers(routers.clone());
+        v6.set_routers(routers);
+
+        self.restart_lookups(&mut v4, &mut v6);
+    }
+
+    // Enable or disable the DHTs. While disabled, no DHT instance is running and the lookups don't
+    // find any peers. The lookups are kept though and resume once the DHTs are enabled again.
+    pub fn set_enabled(&self, enabled: bool) {
+        let mut v4 = self.v4.lock().unwrap();
+        let mut v6 = self.v6.lock().unwrap();
+
+        if v4.enabled == enabled && v6.enabled == enabled {
+            return;
+        }
+
+        v4.set_enabled(enabled);
+        v6.set_enabled(enabled);
+
+        self.restart_lookups(&mut v4, &mut v6);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.v4.lock().unwrap().enabled
+    }
+
+    // Write the current contacts of the running DHTs (if any) into the contacts store right away
+    // instead of waiting for the next periodic save. Used on shutdown so the next start has the
+    // most recent contacts.
+    pub async fn save_contacts(&self) {
+        let dhts = [
+            self.v4.lock().unwrap().current(),
+            self.v6.lock().unwrap().current(),
+        ];
+
+        for dht in dhts.iter().flatten() {
+            if let Some(dht) = &**dht {
+                dht.result().await.save_contacts().await;
+            }
+        }
+    }
+
+    fn restart_lookups(&self, v4: &mut RestartableDht, v6: &mut RestartableDht) {
         let mut lookups = self.lookups.lock().unwrap();
 
         if lookups.is_empty() {
@@ -168,9 +241,18 @@ impl DhtDiscovery {
     }
 }
 
+fn default_routers() -> Vec<String> {
+    DHT_ROUTERS
+        .iter()
+        .map(|router| router.to_string())
+        .collect()
+}
+
 // Wrapper for a DHT instance that can be stopped and restarted at any point.
 struct RestartableDht {
     socket_maker: Option<quic::SideChannelMaker>,
+    routers: Vec<String>,
+    enabled: bool,
     dht: Weak<Option<TaskOrResult<MonitoredDht>>>,
     contacts_store: Option<Arc<dyn DhtContactsStoreTrait>>,
 }
@@ -178,10 +260,13 @@ struct RestartableDht {
 impl RestartableDht {
     fn new(
         socket_maker: Option<quic::SideChannelMaker>,
+        routers: Vec<String>,
         contacts_store: Option<Arc<dyn DhtContactsStoreTrait>>,
     ) -> Self {
         Self {
             socket_maker,
+            routers,
+            enabled: true,
             dht: Weak::new(),
             contacts_store,
         }
@@ -196,9 +281,15 @@ impl RestartableDht {
     ) -> Arc<Option<TaskOrResult<MonitoredDht>>> {
         if let Some(dht) = self.dht.upgrade() {
             dht
-        } else if let Some(maker) = &self.socket_maker {
+        } else if let Some(maker) = self.socket_maker.as_ref().filter(|_| self.enabled) {
             let socket = maker.make();
-            let dht = MonitoredDht::start(socket, monitor, span, self.contacts_store.clone());
+            let dht = MonitoredDht::start(
+                socket,
+                self.routers.clone(),
+                monitor,
+                span,
+                self.contacts_store.clone(),
+            );
 
             let dht = Arc::new(Some(dht));
 
@@ -210,15 +301,33 @@ impl RestartableDht {
         }
     }
 
+    // Retrieve a shared pointer to the running DHT instance, if there is one. Unlike `fetch`, never
+    // starts a new one.
+    fn current(&self) -> Option<Arc<Option<TaskOrResult<MonitoredDht>>>> {
+        self.dht.upgrade()
+    }
+
     fn rebind(&mut self, socket_maker: Option<quic::SideChannelMaker>) {
         self.socket_maker = socket_maker;
         self.dht = Weak::new();
     }
+
+    fn set_routers(&mut self, routers: Vec<String>) {
+        self.routers = routers;
+        self.dht = Weak::new();
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.dht = Weak::new();
+    }
 }
 
 // Wrapper for a DHT instance that periodically outputs it's state to the provided StateMonitor.
 struct MonitoredDht {
     dht: MainlineDht,
+    is_v4: bool,
+    contacts_store: Option<Arc<dyn DhtContactsStoreTrait>>,
     _monitoring_task: ScopedJoinHandle<()>,
     _periodic_dht_node_load_task: Option<ScopedJoinHandle<()>>,
 }
@@ -226,6 +335,7 @@ struct MonitoredDht {
 impl MonitoredDht {
     fn start(
         socket: quic::SideChannel,
+        routers: Vec<String>,
         parent_monitor: &StateMonitor,
         span: &Span,
         contacts_store: Option<Arc<dyn DhtContactsStoreTrait>>,
@@ -243,6 +353,7 @@ impl MonitoredDht {
         TaskOrResult::new(scoped_task::spawn(MonitoredDht::create(
             is_v4,
             socket,
+            routers,
             monitor,
             span,
             contacts_store,
@@ -252,13 +363,14 @@ impl MonitoredDht {
     async fn create(
         is_v4: bool,
         socket: quic::SideChannel,
+        routers: Vec<String>,
         monitor: StateMonitor,
         span: Span,
         contacts_store: Option<Arc<dyn DhtContactsStoreTrait>>,
     ) -> Self {
         // TODO: load the DHT state from a previous save if it exists.
         let mut builder = MainlineDht::builder()
-            .add_routers(DHT_ROUTERS.iter().copied())
+            .add_routers(routers)
             .set_read_only(false);
 
         if let Some(contacts_store) = &contacts_store {
@@ -326,7 +438,7 @@ impl MonitoredDht {
         let monitoring_task = monitoring_task.instrument(span.clone());
         let monitoring_task = scoped_task::spawn(monitoring_task);
 
-        let _periodic_dht_node_load_task = contacts_store.map(|contacts_store| {
+        let _periodic_dht_node_load_task = contacts_store.clone().map(|contacts_store| {
             scoped_task::spawn(
                 Self::keep_reading_contacts(is_v4, dht.clone(), contacts_store).instrument(span),
             )
@@ -334,6 +446,8 @@ impl MonitoredDht {
 
         Self {
             dht,
+            is_v4,
+            contacts_store,
             _monitoring_task: monitoring_task,
             _periodic_dht_node_load_task,
         }
@@ -359,37 +473,12 @@ impl MonitoredDht {
                 }
             };
 
-            // TODO: Make use of the information which is good and which questionable.
-            let mix = good.union(&questionable);
-
-            if is_v4 {
-                let mix = mix.filter_map(|addr| match addr {
-                    SocketAddr::V4(addr) => Some(*addr),
-                    SocketAddr::V6(_) => None,
-                });
-
-                match contacts_store.store_v4(mix.collect()).await {
-                    Ok(()) => reported_failure = false,
-                    Err(error) => {
-                        if !reported_failure {
-                            reported_failure = true;
-                            tracing::error!("DhtDiscovery failed to write contacts {error:?}");
-                        }
-                    }
-                }
-            } else {
-                let mix = mix.filter_map(|addr| match addr {
-                    SocketAddr::V4(_) => None,
-                    SocketAddr::V6(addr) => Some(*addr),
-                });
-
-                match contacts_store.store_v6(mix.collect()).await {
-                    Ok(()) => reported_failure = false,
-                    Err(error) => {
-                        if !reported_failure {
-                            reported_failure = true;
-                            tracing::error!("DhtDiscovery failed to write contacts {error:?}");
-                        }
+            match Self::store_contacts(is_v4, good.union(&questionable), &*contacts_store).await {
+                Ok(()) => reported_failure = false,
+                Err(error) => {
+                    if !reported_failure {
+                        reported_failure = true;
+                        tracing::error!("DhtDiscovery failed to write contacts {error:?}");
                     }
                 }
             }
@@ -398,6 +487,49 @@ impl MonitoredDht {
         }
     }
 
+    async fn save_contacts(&self) {
+        let Some(contacts_store) = &self.contacts_store else {
+            return;
+        };
+
+        let (good, questionable) = match self.dht.load_contacts().await {
+            Ok(contacts) => contacts,
+            Err(error) => {
+                tracing::warn!("DhtDiscovery failed to read contacts: {error:?}");
+                return;
+            }
+        };
+
+        if let Err(error) =
+            Self::store_contacts(self.is_v4, good.union(&questionable), &**contacts_store).await
+        {
+            tracing::error!("DhtDiscovery failed to write contacts {error:?}");
+        }
+    }
+
+    async fn store_contacts(
+        is_v4: bool,
+        contacts: impl Iterator<Item = &SocketAddr>,
+        contacts_store: &(impl DhtContactsStoreTrait + ?Sized),
+    ) -> io::Result<()> {
+        // TODO: Make use of the information which is good and which questionable.
+        if is_v4 {
+            let contacts = contacts.filter_map(|addr| match addr {
+                SocketAddr::V4(addr) => Some(*addr),
+                SocketAddr::V6(_) => None,
+            });
+
+            contacts_store.store_v4(contacts.collect()).await
+        } else {
+            let contacts = contacts.filter_map(|addr| match addr {
+                SocketAddr::V4(_) => None,
+                SocketAddr::V6(addr) => Some(*addr),
+            });
+
+            contacts_store.store_v6(contacts.collect()).await
+        }
+    }
+
     async fn load_initial_contacts(
         is_v4: bool,
         contacts_store: &(impl DhtContactsStoreTrait + ?Sized),
@@ -432,6 +564,26 @@ pub struct LookupRequest {
     lookups: Weak<BlockingMutex<Lookups>>,
 }
 
+impl LookupRequest {
+    /// Triggers an immediate (out-of-cycle) search and announce of this lookup's info-hash. Does
+    /// nothing if the previous such announce happened less than `MIN_FORCED_ANNOUNCE_INTERVAL`
+    /// ago or if there is no DHT running (e.g., the network is disabled). Returns whether the
+    /// announce was triggered.
+    pub fn announce_now(&self) -> bool {
+        let Some(lookups) = self.lookups.upgrade() else {
+            return false;
+        };
+
+        let mut lookups = lookups.lock().unwrap();
+
+        if let Some(lookup) = lookups.get_mut(&self.info_hash) {
+            lookup.announce_now()
+        } else {
+            false
+        }
+    }
+}
+
 impl Drop for LookupRequest {
     fn drop(&mut self) {
         if let Some(lookups) = self.lookups.upgrade() {
@@ -457,6 +609,8 @@ struct Lookup {
     requests: Arc<BlockingMutex<HashMap<RequestId, mpsc::UnboundedSender<SeenPeer>>>>,
     wake_up_tx: watch::Sender<()>,
     task: Option<ScopedJoinHandle<()>>,
+    last_forced_announce: Option<Instant>,
+    forced_announces: MonitoredValue<u64>,
 }
 
 impl Lookup {
@@ -475,6 +629,10 @@ impl Lookup {
         let seen_peers = Arc::new(SeenPeers::new());
         let requests = Arc::new(BlockingMutex::new(HashMap::default()));
 
+        let forced_announces = monitor
+            .make_child(format!("{info_hash:?}"))
+            .make_value("forced_announces", 0);
+
         let task = if dht_v4.is_some() || dht_v6.is_some() {
             Some(Self::start_task(
                 dht_v4,
@@ -495,6 +653,8 @@ impl Lookup {
             requests,
             wake_up_tx,
             task,
+            last_forced_announce: None,
+            forced_announces,
         }
     }
 
@@ -538,6 +698,30 @@ impl Lookup {
         self.wake_up_tx.send(()).unwrap_or(());
     }
 
+    fn announce_now(&mut self) -> bool {
+        if self.task.is_none() {
+            return false;
+        }
+
+        let now = Instant::now();
+
+        if self
+            .last_forced_announce
+            .is_some_and(|last| now.duration_since(last) < MIN_FORCED_ANNOUNCE_INTERVAL)
+        {
+            return false;
+        }
+
+        self.last_forced_announce = Some(now);
+        *self.forced_announces.get() += 1;
+
+        // Wakes the lookup task up if it's sleeping. If it's currently searching, another search
+        // starts immediately after the current one finishes.
+        self.wake_up_tx.send(()).unwrap_or(());
+
+        true
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn start_task(
         dht_v4: Arc<Option<TaskOrResult<MonitoredDht>>>,
@@ -675,3 +859,91 @@ impl<T> TaskOrResult<T> {
         self.result.get().unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tokio::net::UdpSocket;
+
+    #[tokio::test]
+    async fn bootstrap_against_custom_router() {
+        // Mock router which only records whether it's been contacted.
+        let router = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+
+        let (_connector, _acceptor, side_channel_maker) =
+            quic::configure((Ipv4Addr::LOCALHOST, 0).into())
+                .await
+                .unwrap();
+
+        let dht_discovery = DhtDiscovery::new(
+            Some(side_channel_maker),
+            None,
+            None,
+            StateMonitor::make_root(),
+        );
+        dht_discovery.set_routers(vec![router.local_addr().unwrap().to_string()]);
+
+        let (found_peers_tx, _found_peers_rx) = mpsc::unbounded_channel();
+        let info_hash = InfoHash::try_from(&[0; 20][..]).unwrap();
+        let _request = dht_discovery.start_lookup(info_hash, found_peers_tx);
+
+        let mut buffer = [0; 1500];
+        timeout(Duration::from_secs(10), router.recv_from(&mut buffer))
+            .await
+            .expect("router not contacted")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn announce_now_is_rate_limited() {
+        let router = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+
+        let (_connector, _acceptor, side_channel_maker) =
+            quic::configure((Ipv4Addr::LOCALHOST, 0).into())
+                .await
+                .unwrap();
+
+        let dht_discovery = DhtDiscovery::new(
+            Some(side_channel_maker),
+            None,
+            None,
+            StateMonitor::make_root(),
+        );
+        dht_discovery.set_routers(vec![router.local_addr().unwrap().to_string()]);
+
+        let (found_peers_tx, _found_peers_rx) = mpsc::unbounded_channel();
+        let info_hash = InfoHash::try_from(&[0; 20][..]).unwrap();
+        let request = dht_discovery.start_lookup(info_hash, found_peers_tx);
+
+        assert!(request.announce_now());
+        assert!(!request.announce_now());
+
+        // Pretend the last forced announce happened long enough ago.
+        dht_discovery
+            .lookups
+            .lock()
+            .unwrap()
+            .get_mut(&info_hash)
+            .unwrap()
+            .last_forced_announce = Some(Instant::now() - MIN_FORCED_ANNOUNCE_INTERVAL);
+
+        assert!(request.announce_now());
+        assert!(!request.announce_now());
+    }
+
+    #[tokio::test]
+    async fn announce_now_without_dht() {
+        let dht_discovery = DhtDiscovery::new(None, None, None, StateMonitor::make_root());
+
+        let (found_peers_tx, _found_peers_rx) = mpsc::unbounded_channel();
+        let info_hash = InfoHash::try_from(&[0; 20][..]).unwrap();
+        let request = dht_discovery.start_lookup(info_hash, found_peers_tx);
+
+        assert!(!request.announce_now());
+
+        // Also when the discovery itself is gone.
+        drop(dht_discovery);
+        assert!(!request.announce_now());
+    }
+}
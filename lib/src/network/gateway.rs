@@ -3,7 +3,7 @@ use crate::sync::atomic_slot::AtomicSlot;
 use backoff::{backoff::Backoff, ExponentialBackoffBuilder};
 use net::{
     quic,
-    tcp::{TcpListener, TcpStream},
+    tcp::{self, TcpListener},
 };
 use scoped_task::ScopedJoinHandle;
 use std::{
@@ -23,6 +23,7 @@ use tracing::{field, Instrument, Span};
 pub(super) struct Gateway {
     stacks: AtomicSlot<Stacks>,
     incoming_tx: mpsc::Sender<(raw::Stream, PeerAddr)>,
+    quic_tuning: Mutex<quic::QuicTuning>,
 }
 
 impl Gateway {
@@ -36,84 +37,118 @@ impl Gateway {
         Self {
             stacks,
             incoming_tx,
+            quic_tuning: Mutex::new(quic::QuicTuning::default()),
         }
     }
 
+    /// Sets the tuning of the QUIC transport. Applied only to the QUIC stacks created by the
+    /// subsequent `bind` calls.
+    pub fn set_quic_tuning(&self, tuning: quic::QuicTuning) {
+        *self.quic_tuning.lock().unwrap() = tuning;
+    }
+
+    pub fn quic_tuning(&self) -> quic::QuicTuning {
+        *self.quic_tuning.lock().unwrap()
+    }
+
     pub fn listener_local_addrs(&self) -> Vec<PeerAddr> {
         let stacks = self.stacks.read();
-        [
-            stacks
-                .quic_listener_local_addr_v4()
-                .copied()
-                .map(PeerAddr::Quic),
-            stacks
-                .quic_listener_local_addr_v6()
-                .copied()
-                .map(PeerAddr::Quic),
-            stacks
-                .tcp_listener_local_addr_v4()
-                .copied()
-                .map(PeerAddr::Tcp),
-            stacks
-                .tcp_listener_local_addr_v6()
-                .copied()
-                .map(PeerAddr::Tcp),
-        ]
-        .into_iter()
-        .flatten()
-        .collect()
+        let addrs = stacks.addresses();
+
+        addrs
+            .quic_v4
+            .into_iter()
+            .chain(addrs.quic_v6)
+            .map(PeerAddr::Quic)
+            .chain(
+                addrs
+                    .tcp_v4
+                    .into_iter()
+                    .chain(addrs.tcp_v6)
+                    .map(PeerAddr::Tcp),
+            )
+            .collect()
     }
 
-    /// Binds the gateway to the specified addresses. Rebinds if already bound.
+    /// Hands an in-memory stream over to the incoming connection handler as if it was accepted
+    /// by one of the listeners.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub async fn accept_in_memory(&self, stream: tokio::io::DuplexStream, addr: PeerAddr) {
+        self.incoming_tx
+            .send((raw::Stream::Memory(stream), addr))
+            .await
+            .ok();
+    }
+
+    /// Binds the gateway to the specified addresses. Rebinds if already bound. Returns also the
+    /// addresses that failed to bind. If `interface` is given, all the sockets (including the ones
+    /// of the outgoing TCP connections) are bound to the network interface with that name.
     pub async fn bind(
         &self,
         bind: &StackAddresses,
+        interface: Option<&str>,
     ) -> (
         Option<quic::SideChannelMaker>,
         Option<quic::SideChannelMaker>,
+        Vec<PeerAddr>,
     ) {
-        let (next, side_channel_maker_v4, side_channel_maker_v6) =
-            Stacks::bind(bind, self.incoming_tx.clone()).await;
+        let quic_tuning = self.quic_tuning();
+        let mut failed = Vec::new();
+        let (next, side_channel_maker_v4, side_channel_maker_v6) = Stacks::bind(
+            bind,
+            interface,
+            &quic_tuning,
+            &self.incoming_tx,
+            &mut failed,
+        )
+        .await;
 
         let prev = self.stacks.swap(next);
         let next = self.stacks.read();
 
-        if prev.quic_v4.is_some() && next.quic_v4.is_none() {
+        if !prev.quic_v4.is_empty() && next.quic_v4.is_empty() {
             tracing::info!("Terminated IPv4 QUIC stack");
         }
 
-        if prev.quic_v6.is_some() && next.quic_v6.is_none() {
+        if !prev.quic_v6.is_empty() && next.quic_v6.is_empty() {
             tracing::info!("Terminated IPv6 QUIC stack");
         }
 
-        if prev.tcp_v4.is_some() && next.tcp_v4.is_none() {
+        if !prev.tcp_v4.is_empty() && next.tcp_v4.is_empty() {
             tracing::info!("Terminated IPv4 TCP stack");
         }
 
-        if prev.tcp_v6.is_some() && next.tcp_v6.is_none() {
+        if !prev.tcp_v6.is_empty() && next.tcp_v6.is_empty() {
             tracing::info!("Terminated IPv6 TCP stack");
         }
 
         prev.close();
 
-        (side_channel_maker_v4, side_channel_maker_v6)
+        (side_channel_maker_v4, side_channel_maker_v6, failed)
     }
 
+    /// Keeps trying to connect to the peer for as long as it's being seen, but at most for
+    /// `max_elapsed` (`None` means no limit).
     pub async fn connect_with_retries(
         &self,
         peer: &SeenPeer,
         source: PeerSource,
-    ) -> Option<raw::Stream> {
-        if !ok_to_connect(peer.addr_if_seen()?.socket_addr(), source) {
+        max_elapsed: Option<Duration>,
+    ) -> ConnectResult {
+        let Some(addr) = peer.addr_if_seen() else {
+            return ConnectResult::Discarded;
+        };
+
+        if !ok_to_connect(addr.socket_addr(), source) {
             tracing::debug!("Invalid peer address - discarding");
-            return None;
+            return ConnectResult::Discarded;
         }
 
         let mut backoff = ExponentialBackoffBuilder::new()
             .with_initial_interval(Duration::from_millis(200))
             .with_max_interval(Duration::from_secs(10))
-            // We'll continue trying for as long as `peer.addr().is_some()`.
-            .with_max_elapsed_time(None)
+            // We'll continue trying for as long as `peer.addr().is_some()`, unless limited.
+            .with_max_elapsed_time(max_elapsed)
             .build();
 
         let mut hole_punching_task = None;
@@ -122,7 +157,9 @@ impl Gateway {
             // Note: This needs to be probed each time the loop starts. When the `addr` fn returns
             // `None` that means whatever discovery mechanism (LocalDiscovery or DhtDiscovery)
             // found it is no longer seeing it.
-            let addr = *peer.addr_if_seen()?;
+            let Some(addr) = peer.addr_if_seen().copied() else {
+                return ConnectResult::Discarded;
+            };
 
             // Note: we need to grab fresh stacks on each loop because the network might get
             // re-bound in the meantime which would change the connectors.
@@ -134,14 +171,14 @@ impl Gateway {
 
             match stacks.connect(addr).await {
                 Ok(socket) => {
-                    return Some(socket);
+                    return ConnectResult::Connected(socket);
                 }
                 Err(error) => {
                     tracing::debug!(?error, "Connection failed");
 
                     if error.is_localy_closed() {
                         // Connector locally closed - no point in retrying.
-                        return None;
+                        return ConnectResult::Discarded;
                     }
 
                     match backoff.next_backoff() {
@@ -149,8 +186,10 @@ impl Gateway {
                             tracing::debug!("Next connection attempt in {:?}", duration);
                             time::sleep(duration).await;
                         }
-                        // We set max elapsed time to None above.
-                        None => unreachable!(),
+                        None => {
+                            tracing::debug!("Giving up connecting after {:?}", max_elapsed);
+                            return ConnectResult::GaveUp;
+                        }
                     }
                 }
             }
@@ -160,6 +199,19 @@ impl Gateway {
     pub fn addresses(&self) -> StackAddresses {
         self.stacks.read().addresses()
     }
+
+    /// Network interface the gateway is currently bound to, if any.
+    pub fn interface(&self) -> Option<String> {
+        self.stacks.read().interface.clone()
+    }
+}
+
+pub(super) enum ConnectResult {
+    Connected(raw::Stream),
+    // The peer is no longer seen or is not suitable for connecting.
+    Discarded,
+    // The peer didn't respond within the max retry time.
+    GaveUp,
 }
 
 #[derive(Debug, Error)]
@@ -183,66 +235,52 @@ impl ConnectError {
     }
 }
 
+// There can be multiple stacks per protocol and family (e.g., on a multi-homed host). All of them
+// accept incoming connections but only the first QUIC stack of each family is used for outgoing
+// connections, hole punching and as the side channel for DHT and STUN.
 struct Stacks {
-    quic_v4: Option<QuicStack>,
-    quic_v6: Option<QuicStack>,
-    tcp_v4: Option<TcpStack>,
-    tcp_v6: Option<TcpStack>,
+    quic_v4: Vec<QuicStack>,
+    quic_v6: Vec<QuicStack>,
+    tcp_v4: Vec<TcpStack>,
+    tcp_v6: Vec<TcpStack>,
+    interface: Option<String>,
 }
 
 impl Stacks {
     fn unbound() -> Self {
         Self {
-            quic_v4: None,
-            quic_v6: None,
-            tcp_v4: None,
-            tcp_v6: None,
+            quic_v4: Vec::new(),
+            quic_v6: Vec::new(),
+            tcp_v4: Vec::new(),
+            tcp_v6: Vec::new(),
+            interface: None,
         }
     }
 
     async fn bind(
         bind: &StackAddresses,
-        incoming_tx: mpsc::Sender<(raw::Stream, PeerAddr)>,
+        interface: Option<&str>,
+        quic_tuning: &quic::QuicTuning,
+        incoming_tx: &mpsc::Sender<(raw::Stream, PeerAddr)>,
+        failed: &mut Vec<PeerAddr>,
     ) -> (
         Self,
         Option<quic::SideChannelMaker>,
         Option<quic::SideChannelMaker>,
     ) {
-        let (quic_v4, side_channel_maker_v4) = if let Some(addr) = bind.quic_v4 {
-            QuicStack::new(addr, incoming_tx.clone())
-                .await
-                .map(|(stack, side_channel)| (Some(stack), Some(side_channel)))
-                .unwrap_or((None, None))
-        } else {
-            (None, None)
-        };
-
-        let (quic_v6, side_channel_maker_v6) = if let Some(addr) = bind.quic_v6 {
-            QuicStack::new(addr, incoming_tx.clone())
-                .await
-                .map(|(stack, side_channel)| (Some(stack), Some(side_channel)))
-                .unwrap_or((None, None))
-        } else {
-            (None, None)
-        };
-
-        let tcp_v4 = if let Some(addr) = bind.tcp_v4 {
-            TcpStack::new(addr, incoming_tx.clone()).await
-        } else {
-            None
-        };
-
-        let tcp_v6 = if let Some(addr) = bind.tcp_v6 {
-            TcpStack::new(addr, incoming_tx).await
-        } else {
-            None
-        };
+        let (quic_v4, side_channel_maker_v4) =
+            QuicStack::new_all(&bind.quic_v4, interface, quic_tuning, incoming_tx, failed).await;
+        let (quic_v6, side_channel_maker_v6) =
+            QuicStack::new_all(&bind.quic_v6, interface, quic_tuning, incoming_tx, failed).await;
+        let tcp_v4 = TcpStack::new_all(&bind.tcp_v4, interface, incoming_tx, failed).await;
+        let tcp_v6 = TcpStack::new_all(&bind.tcp_v6, interface, incoming_tx, failed).await;
 
         let this = Self {
             quic_v4,
             quic_v6,
             tcp_v4,
             tcp_v6,
+            interface: interface.map(ToOwned::to_owned),
         };
 
         (this, side_channel_maker_v4, side_channel_maker_v6)
@@ -250,36 +288,32 @@ impl Stacks {
 
     fn addresses(&self) -> StackAddresses {
         StackAddresses {
-            quic_v4: self.quic_v4.as_ref().map(|stack| stack.listener_local_addr),
-            quic_v6: self.quic_v6.as_ref().map(|stack| stack.listener_local_addr),
-            tcp_v4: self.tcp_v6.as_ref().map(|stack| stack.listener_local_addr),
-            tcp_v6: self.tcp_v6.as_ref().map(|stack| stack.listener_local_addr),
+            quic_v4: self
+                .quic_v4
+                .iter()
+                .map(|stack| stack.listener_local_addr)
+                .collect(),
+            quic_v6: self
+                .quic_v6
+                .iter()
+                .map(|stack| stack.listener_local_addr)
+                .collect(),
+            tcp_v4: self
+                .tcp_v4
+                .iter()
+                .map(|stack| stack.listener_local_addr)
+                .collect(),
+            tcp_v6: self
+                .tcp_v6
+                .iter()
+                .map(|stack| stack.listener_local_addr)
+                .collect(),
         }
     }
 
-    fn quic_listener_local_addr_v4(&self) -> Option<&SocketAddr> {
-        self.quic_v4
-            .as_ref()
-            .map(|stack| &stack.listener_local_addr)
-    }
-
-    fn quic_listener_local_addr_v6(&self) -> Option<&SocketAddr> {
-        self.quic_v6
-            .as_ref()
-            .map(|stack| &stack.listener_local_addr)
-    }
-
-    fn tcp_listener_local_addr_v4(&self) -> Option<&SocketAddr> {
-        self.tcp_v4.as_ref().map(|stack| &stack.listener_local_addr)
-    }
-
-    fn tcp_listener_local_addr_v6(&self) -> Option<&SocketAddr> {
-        self.tcp_v6.as_ref().map(|stack| &stack.listener_local_addr)
-    }
-
     async fn connect(&self, addr: PeerAddr) -> Result<raw::Stream, ConnectError> {
         match addr {
-            PeerAddr::Tcp(addr) => TcpStream::connect(addr)
+            PeerAddr::Tcp(addr) => tcp::connect_via_device(addr, self.interface.as_deref())
                 .await
                 .map(raw::Stream::Tcp)
                 .map_err(ConnectError::Tcp),
@@ -353,17 +387,13 @@ impl Stacks {
 
     fn quic_stack_for(&self, ip: &IpAddr) -> Option<&QuicStack> {
         match ip {
-            IpAddr::V4(_) => self.quic_v4.as_ref(),
-            IpAddr::V6(_) => self.quic_v6.as_ref(),
+            IpAddr::V4(_) => self.quic_v4.first(),
+            IpAddr::V6(_) => self.quic_v6.first(),
         }
     }
 
     fn close(&self) {
-        if let Some(stack) = &self.quic_v4 {
-            stack.close();
-        }
-
-        if let Some(stack) = &self.quic_v6 {
+        for stack in self.quic_v4.iter().chain(&self.quic_v6) {
             stack.close();
         }
     }
@@ -379,11 +409,14 @@ struct QuicStack {
 impl QuicStack {
     async fn new(
         bind_addr: SocketAddr,
+        interface: Option<&str>,
+        tuning: &quic::QuicTuning,
         incoming_tx: mpsc::Sender<(raw::Stream, PeerAddr)>,
     ) -> Option<(Self, quic::SideChannelMaker)> {
         let span = tracing::info_span!("listener", addr = field::Empty);
 
-        let (connector, listener, side_channel_maker) = match quic::configure(bind_addr).await {
+        let result = quic::configure_on_device(bind_addr, tuning, interface).await;
+        let (connector, listener, side_channel_maker) = match result {
             Ok((connector, listener, side_channel_maker)) => {
                 span.record(
                     "addr",
@@ -420,6 +453,33 @@ impl QuicStack {
         Some((this, side_channel_maker))
     }
 
+    // Binds a stack to each of the given addresses, skipping (and adding to `failed`) those that
+    // fail to bind. Returns the side channel maker of the first one.
+    async fn new_all(
+        bind_addrs: &[SocketAddr],
+        interface: Option<&str>,
+        tuning: &quic::QuicTuning,
+        incoming_tx: &mpsc::Sender<(raw::Stream, PeerAddr)>,
+        failed: &mut Vec<PeerAddr>,
+    ) -> (Vec<Self>, Option<quic::SideChannelMaker>) {
+        let mut stacks = Vec::with_capacity(bind_addrs.len());
+        let mut first_side_channel_maker = None;
+
+        for bind_addr in bind_addrs {
+            let Some((stack, side_channel_maker)) =
+                Self::new(*bind_addr, interface, tuning, incoming_tx.clone()).await
+            else {
+                failed.push(PeerAddr::Quic(*bind_addr));
+                continue;
+            };
+
+            stacks.push(stack);
+            first_side_channel_maker.get_or_insert(side_channel_maker);
+        }
+
+        (stacks, first_side_channel_maker)
+    }
+
     fn close(&self) {
         self.listener_task.abort();
         self.connector.close();
@@ -434,11 +494,12 @@ struct TcpStack {
 impl TcpStack {
     async fn new(
         bind_addr: SocketAddr,
+        interface: Option<&str>,
         incoming_tx: mpsc::Sender<(raw::Stream, PeerAddr)>,
     ) -> Option<Self> {
         let span = tracing::info_span!("listener", addr = field::Empty);
 
-        let listener = match TcpListener::bind(bind_addr).await {
+        let listener = match tcp::bind_to_device(bind_addr, interface).await {
             Ok(listener) => listener,
             Err(error) => {
                 tracing::warn!(
@@ -477,6 +538,27 @@ impl TcpStack {
             _listener_task: listener_task,
         })
     }
+
+    // Binds a stack to each of the given addresses, skipping (and adding to `failed`) those that
+    // fail to bind.
+    async fn new_all(
+        bind_addrs: &[SocketAddr],
+        interface: Option<&str>,
+        incoming_tx: &mpsc::Sender<(raw::Stream, PeerAddr)>,
+        failed: &mut Vec<PeerAddr>,
+    ) -> Vec<Self> {
+        let mut stacks = Vec::with_capacity(bind_addrs.len());
+
+        for bind_addr in bind_addrs {
+            if let Some(stack) = Self::new(*bind_addr, interface, incoming_tx.clone()).await {
+                stacks.push(stack);
+            } else {
+                failed.push(PeerAddr::Tcp(*bind_addr));
+            }
+        }
+
+        stacks
+    }
 }
 
 async fn run_tcp_listener(listener: TcpListener, tx: mpsc::Sender<(raw::Stream, PeerAddr)>) {
@@ -605,88 +687,107 @@ fn ok_to_connect(addr: &SocketAddr, source: PeerSource) -> bool {
 
 #[derive(Debug)]
 pub(super) struct StackAddresses {
-    quic_v4: Option<SocketAddr>,
-    quic_v6: Option<SocketAddr>,
-    tcp_v4: Option<SocketAddr>,
-    tcp_v6: Option<SocketAddr>,
+    quic_v4: Vec<SocketAddr>,
+    quic_v6: Vec<SocketAddr>,
+    tcp_v4: Vec<SocketAddr>,
+    tcp_v6: Vec<SocketAddr>,
 }
 
 impl StackAddresses {
     pub(super) fn any_stack_needs_rebind(&self, new_stack_addresses: &StackAddresses) -> bool {
-        needs_rebind(&self.quic_v4, &new_stack_addresses.quic_v4)
-            || needs_rebind(&self.quic_v6, &new_stack_addresses.quic_v6)
-            || needs_rebind(&self.tcp_v4, &new_stack_addresses.tcp_v4)
-            || needs_rebind(&self.tcp_v6, &new_stack_addresses.tcp_v6)
+        any_needs_rebind(&self.quic_v4, &new_stack_addresses.quic_v4)
+            || any_needs_rebind(&self.quic_v6, &new_stack_addresses.quic_v6)
+            || any_needs_rebind(&self.tcp_v4, &new_stack_addresses.tcp_v4)
+            || any_needs_rebind(&self.tcp_v6, &new_stack_addresses.tcp_v6)
     }
 }
 
-fn needs_rebind(old_addr: &Option<SocketAddr>, new_addr: &Option<SocketAddr>) -> bool {
-    match (old_addr, new_addr) {
-        (Some(old_addr), Some(new_addr)) => {
-            let old_ip = old_addr.ip();
-            let old_port = old_addr.port();
-            let new_ip = new_addr.ip();
-            let new_port = new_addr.port();
-
-            // Just for readability as "true" and "false" have different lengths.
-            const T: bool = true;
-            const F: bool = false;
-
-            // `old_port` is not expected to be 0, but doesn't hurt to cover that case as well.
-            match (
-                old_ip.is_unspecified(),
-                old_port == 0,
-                new_ip.is_unspecified(),
-                new_port == 0,
-            ) {
-                (T, T, T, T) => false,
-                (F, T, T, T) => true,
-                (T, F, T, T) => false,
-                (F, F, T, T) => true,
-                (T, T, F, T) => true,
-                (F, T, F, T) => old_ip != new_ip,
-                (T, F, F, T) => true,
-                (F, F, F, T) => old_ip != new_ip,
-                (T, T, T, F) => true,
-                (F, T, T, F) => true,
-                (T, F, T, F) => old_port != new_port,
-                (F, F, T, F) => true,
-                (T, T, F, F) => true,
-                (F, T, F, F) => true,
-                (T, F, F, F) => true,
-                (F, F, F, F) => old_ip != new_ip || old_port != new_port,
+// Rebind is needed unless every new address can be paired with a distinct old one that doesn't
+// need rebinding.
+fn any_needs_rebind(old_addrs: &[SocketAddr], new_addrs: &[SocketAddr]) -> bool {
+    if old_addrs.len() != new_addrs.len() {
+        return true;
+    }
+
+    let mut unpaired: Vec<_> = old_addrs.iter().collect();
+
+    for new_addr in new_addrs {
+        match unpaired
+            .iter()
+            .position(|old_addr| !needs_rebind(old_addr, new_addr))
+        {
+            Some(index) => {
+                unpaired.swap_remove(index);
             }
+            None => return true,
         }
-        (Some(_), None) => true,
-        (None, Some(_)) => true,
-        (None, None) => false,
+    }
+
+    false
+}
+
+fn needs_rebind(old_addr: &SocketAddr, new_addr: &SocketAddr) -> bool {
+    let old_ip = old_addr.ip();
+    let old_port = old_addr.port();
+    let new_ip = new_addr.ip();
+    let new_port = new_addr.port();
+
+    // Just for readability as "true" and "false" have different lengths.
+    const T: bool = true;
+    const F: bool = false;
+
+    // `old_port` is not expected to be 0, but doesn't hurt to cover that case as well.
+    match (
+        old_ip.is_unspecified(),
+        old_port == 0,
+        new_ip.is_unspecified(),
+        new_port == 0,
+    ) {
+        (T, T, T, T) => false,
+        (F, T, T, T) => true,
+        (T, F, T, T) => false,
+        (F, F, T, T) => true,
+        (T, T, F, T) => true,
+        (F, T, F, T) => old_ip != new_ip,
+        (T, F, F, T) => true,
+        (F, F, F, T) => old_ip != new_ip,
+        (T, T, T, F) => true,
+        (F, T, T, F) => true,
+        (T, F, T, F) => old_port != new_port,
+        (F, F, T, F) => true,
+        (T, T, F, F) => true,
+        (F, T, F, F) => true,
+        (T, F, F, F) => true,
+        (F, F, F, F) => old_ip != new_ip || old_port != new_port,
     }
 }
 
 impl From<&[PeerAddr]> for StackAddresses {
     fn from(addrs: &[PeerAddr]) -> Self {
-        let quic_v4 = addrs.iter().find_map(|addr| match addr {
-            PeerAddr::Quic(addr @ SocketAddr::V4(_)) => Some(*addr),
-            _ => None,
-        });
-        let quic_v6 = addrs.iter().find_map(|addr| match addr {
-            PeerAddr::Quic(addr @ SocketAddr::V6(_)) => Some(*addr),
-            _ => None,
-        });
-        let tcp_v4 = addrs.iter().find_map(|addr| match addr {
-            PeerAddr::Tcp(addr @ SocketAddr::V4(_)) => Some(*addr),
-            _ => None,
-        });
-        let tcp_v6 = addrs.iter().find_map(|addr| match addr {
-            PeerAddr::Tcp(addr @ SocketAddr::V6(_)) => Some(*addr),
-            _ => None,
-        });
+        let mut this = StackAddresses {
+            quic_v4: Vec::new(),
+            quic_v6: Vec::new(),
+            tcp_v4: Vec::new(),
+            tcp_v6: Vec::new(),
+        };
 
-        StackAddresses {
-            quic_v4,
-            quic_v6,
-            tcp_v4,
-            tcp_v6,
+        for addr in addrs {
+            let list = match addr {
+                PeerAddr::Quic(SocketAddr::V4(_)) => &mut this.quic_v4,
+                PeerAddr::Quic(SocketAddr::V6(_)) => &mut this.quic_v6,
+                PeerAddr::Tcp(SocketAddr::V4(_)) => &mut this.tcp_v4,
+                PeerAddr::Tcp(SocketAddr::V6(_)) => &mut this.tcp_v6,
+            };
+
+            let addr = addr.socket_addr();
+
+            // Binding the same address twice would fail anyway (unless the port is zero in which
+            // case each bind gets a different one).
+            if addr.port() == 0 || !list.contains(addr) {
+                list.push(*addr);
+            }
         }
+
+        this
     }
 }
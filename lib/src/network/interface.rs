@@ -0,0 +1,102 @@
+//! Restricting the network to a single network interface (e.g. a VPN tunnel).
+//!
+//! The bind addresses with unspecified IP are replaced with the current addresses of the interface
+//! and the addresses of other interfaces are dropped. Where supported, the sockets are also bound
+//! to the interface itself (`SO_BINDTODEVICE`) so their traffic can't be routed through any other
+//! interface even if the routing table says otherwise.
+
+use super::peer_addr::PeerAddr;
+use std::net::{IpAddr, SocketAddr};
+
+/// Whether sockets can be bound to a network interface (as opposed to just to its addresses).
+pub(super) const BIND_TO_DEVICE_SUPPORTED: bool =
+    cfg!(any(target_os = "android", target_os = "linux"));
+
+/// Returns the interface name to bind the sockets to, if supported on this platform.
+pub(super) fn device(interface: &str) -> Option<&str> {
+    BIND_TO_DEVICE_SUPPORTED.then_some(interface)
+}
+
+/// Resolves `addrs` to the current addresses of the interface with the given name. If the
+/// interface doesn't exist or has no addresses (e.g. the tunnel is down), returns nothing so that
+/// the network stays unbound instead of falling back to the other interfaces.
+pub(super) fn resolve(interface: &str, addrs: &[PeerAddr]) -> Vec<PeerAddr> {
+    let ips: Vec<_> = match if_addrs::get_if_addrs() {
+        Ok(ifaces) => ifaces
+            .into_iter()
+            .filter(|iface| iface.name == interface)
+            .map(|iface| iface.ip())
+            // Link-local IPv6 addresses need a scope id and are useless for the tunnel use case.
+            .filter(|ip| !matches!(ip, IpAddr::V6(ip) if super::ip::is_unicast_link_local(ip)))
+            .collect(),
+        Err(error) => {
+            tracing::error!(?error, "Failed to list network interfaces");
+            Vec::new()
+        }
+    };
+
+    let mut resolved = Vec::new();
+
+    for addr in addrs {
+        if addr.ip().is_unspecified() {
+            resolved.extend(
+                ips.iter()
+                    .filter(|ip| ip.is_ipv4() == addr.ip().is_ipv4())
+                    .map(|ip| with_ip(*addr, *ip)),
+            );
+        } else if ips.contains(&addr.ip()) {
+            resolved.push(*addr);
+        } else {
+            tracing::warn!(%addr, interface, "Bind address doesn't belong to the interface");
+        }
+    }
+
+    resolved
+}
+
+fn with_ip(addr: PeerAddr, ip: IpAddr) -> PeerAddr {
+    let socket_addr = SocketAddr::new(ip, addr.port());
+
+    match addr {
+        PeerAddr::Quic(_) => PeerAddr::Quic(socket_addr),
+        PeerAddr::Tcp(_) => PeerAddr::Tcp(socket_addr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn resolve_unknown_interface() {
+        let addrs = [
+            PeerAddr::Quic((Ipv4Addr::UNSPECIFIED, 0).into()),
+            PeerAddr::Tcp((Ipv4Addr::LOCALHOST, 0).into()),
+        ];
+
+        assert!(resolve("no-such-interface-0", &addrs).is_empty());
+    }
+
+    #[test]
+    fn resolve_loopback() {
+        let Some(name) = if_addrs::get_if_addrs()
+            .unwrap()
+            .into_iter()
+            .find(|iface| iface.ip() == IpAddr::from(Ipv4Addr::LOCALHOST))
+            .map(|iface| iface.name)
+        else {
+            return;
+        };
+
+        let addrs = [
+            PeerAddr::Quic((Ipv4Addr::UNSPECIFIED, 1234).into()),
+            PeerAddr::Tcp((Ipv4Addr::new(192, 0, 2, 1), 0).into()),
+        ];
+
+        assert_eq!(
+            resolve(&name, &addrs),
+            [PeerAddr::Quic((Ipv4Addr::LOCALHOST, 1234).into())]
+        );
+    }
+}
@@ -41,13 +41,25 @@ pub(crate) struct LocalDiscovery {
 }
 
 impl LocalDiscovery {
-    pub fn new(listener_port: PeerPort, monitor: StateMonitor) -> Self {
+    /// Creates local discovery announcing the given IPv4 listeners. On each interface, the first
+    /// listener bound to the address of that interface or, if there is none, the first one bound
+    /// to the unspecified address is announced.
+    ///
+    /// If `bind_interface` is set (see `Network::set_bind_interface`), the discovery runs only on
+    /// the addresses that have a listener bound to them (the unspecified address doesn't count)
+    /// and its sockets send only through that interface, so nothing is announced outside of it.
+    pub fn new(
+        listeners: Vec<(Ipv4Addr, PeerPort)>,
+        bind_interface: Option<String>,
+        monitor: StateMonitor,
+    ) -> Self {
         let (peer_tx, peer_rx) = mpsc::channel(1);
 
         let work_handle = scoped_task::spawn(
             async move {
                 let mut inner = LocalDiscoveryInner {
-                    listener_port,
+                    listeners,
+                    bind_interface,
                     peer_tx,
                     per_interface_discovery: HashMap::default(),
                 };
@@ -101,7 +113,8 @@ impl LocalDiscovery {
 }
 
 struct LocalDiscoveryInner {
-    listener_port: PeerPort,
+    listeners: Vec<(Ipv4Addr, PeerPort)>,
+    bind_interface: Option<String>,
     peer_tx: mpsc::Sender<SeenPeer>,
     per_interface_discovery: HashMap<Ipv4Addr, PerInterfaceLocalDiscovery>,
 }
@@ -118,13 +131,18 @@ impl LocalDiscoveryInner {
             return;
         };
 
+        let Some(listener_port) = self.listener_port_for(interface) else {
+            return;
+        };
+
         match self.per_interface_discovery.entry(interface) {
             Entry::Vacant(entry) => {
                 let _enter = tracing::info_span!("local_discovery", %interface).entered();
                 let discovery = PerInterfaceLocalDiscovery::new(
                     self.peer_tx.clone(),
-                    self.listener_port,
+                    listener_port,
                     interface,
+                    self.bind_interface.as_deref(),
                     parent_monitor,
                 );
 
@@ -142,6 +160,24 @@ impl LocalDiscoveryInner {
         }
     }
 
+    fn listener_port_for(&self, interface: Ipv4Addr) -> Option<PeerPort> {
+        self.listeners
+            .iter()
+            .find(|(addr, _)| *addr == interface)
+            .or_else(|| {
+                // When restricted to the bind interface, the listeners are bound to its addresses.
+                // Don't fall back to an unspecified one which would match any other interface.
+                if self.bind_interface.is_some() {
+                    return None;
+                }
+
+                self.listeners
+                    .iter()
+                    .find(|(addr, _)| addr.is_unspecified())
+            })
+            .map(|(_, port)| *port)
+    }
+
     fn remove(&mut self, interface: IpAddr) {
         let IpAddr::V4(interface) = interface else {
             return;
@@ -162,11 +198,12 @@ impl PerInterfaceLocalDiscovery {
         peer_tx: mpsc::Sender<SeenPeer>,
         listener_port: PeerPort,
         interface: Ipv4Addr,
+        bind_interface: Option<&str>,
         parent_monitor: &StateMonitor,
     ) -> io::Result<Self> {
         // Only used to filter out multicast packets from self.
         let id = OsRng.gen();
-        let socket_provider = Arc::new(SocketProvider::new(interface));
+        let socket_provider = Arc::new(SocketProvider::new(interface, bind_interface));
 
         let monitor = parent_monitor.make_child(format!("{interface}"));
         let span = Span::current();
@@ -381,17 +418,31 @@ enum Message {
 
 struct SocketProvider {
     interface: Ipv4Addr,
+    // `Some` if restricted to the bind interface. The inner value is the device to bind the socket
+    // to, if supported on this platform.
+    restrict: Option<Option<String>>,
     socket: AsyncMutex<Option<Arc<UdpSocket>>>,
 }
 
 impl SocketProvider {
-    fn new(interface: Ipv4Addr) -> Self {
+    fn new(interface: Ipv4Addr, bind_interface: Option<&str>) -> Self {
         Self {
             interface,
+            restrict: bind_interface
+                .map(|name| super::interface::device(name).map(ToOwned::to_owned)),
             socket: AsyncMutex::new(None),
         }
     }
 
+    async fn bind(&self) -> io::Result<UdpSocket> {
+        match &self.restrict {
+            Some(device) => {
+                UdpSocket::bind_multicast_restricted(self.interface, device.as_deref()).await
+            }
+            None => UdpSocket::bind_multicast(self.interface).await,
+        }
+    }
+
     async fn provide(&self) -> Arc<UdpSocket> {
         let mut guard = self.socket.lock().await;
 
@@ -401,7 +452,7 @@ impl SocketProvider {
                 let mut last_error: Option<io::ErrorKind> = None;
 
                 let socket = loop {
-                    match UdpSocket::bind_multicast(self.interface).await {
+                    match self.bind().await {
                         Ok(socket) => break Arc::new(socket),
                         Err(error) => {
                             if last_error != Some(error.kind()) {
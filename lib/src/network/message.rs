@@ -192,6 +192,10 @@ define_byte_array_wrapper! {
 }
 
 impl MessageChannelId {
+    /// Channel reserved for the keep-alive pings exchanged directly between the message
+    /// dispatchers. Not used by any repository.
+    pub(super) const KEEP_ALIVE: Self = Self([0; Self::SIZE]);
+
     pub(super) fn new(
         repo_id: &'_ RepositoryId,
         this_runtime_id: &'_ PublicRuntimeId,
@@ -1,17 +1,23 @@
 use super::{
     barrier::{Barrier, BarrierError},
+    capability::{Capabilities, Proof},
     client::Client,
     connection::ConnectionPermit,
     crypto::{self, DecryptingStream, EncryptingSink, EstablishError, RecvError, Role, SendError},
     message::{Content, MessageChannelId, Request, Response},
-    message_dispatcher::{ContentSink, ContentStream, MessageDispatcher},
+    message_dispatcher::{ContentSink, ContentStream, KeepAliveConfig, MessageDispatcher},
+    peer_addr::PeerAddr,
     peer_exchange::{PexPeer, PexReceiver, PexRepository, PexSender},
+    protocol::Version,
     raw,
     runtime_id::PublicRuntimeId,
     server::Server,
-    stats::{ByteCounters, Instrumented},
+    stats::{
+        BandwidthLimits, ByteCounters, Instrumented, MessageCounters, Throttle, ThrottleBuckets,
+    },
 };
 use crate::{
+    access_control::AccessMode,
     collections::{hash_map::Entry, HashMap},
     network::constants::{REQUEST_BUFFER_SIZE, RESPONSE_BUFFER_SIZE},
     protocol::RepositoryId,
@@ -19,7 +25,10 @@ use crate::{
 };
 use backoff::{backoff::Backoff, ExponentialBackoffBuilder};
 use state_monitor::StateMonitor;
-use std::{future, sync::Arc};
+use std::{
+    future,
+    sync::{atomic::AtomicBool, Arc},
+};
 use tokio::{
     select,
     sync::{mpsc, oneshot, Semaphore},
@@ -43,6 +52,8 @@ pub(super) struct MessageBroker {
     dispatcher: MessageDispatcher,
     links: HashMap<RepositoryId, oneshot::Sender<()>>,
     pex_peer: PexPeer,
+    // Bandwidth throttling state of this peer, shared by all its links.
+    throttle_buckets: Arc<ThrottleBuckets>,
     monitor: StateMonitor,
     span: SpanGuard,
 }
@@ -53,15 +64,18 @@ impl MessageBroker {
         that_runtime_id: PublicRuntimeId,
         pex_peer: PexPeer,
         monitor: StateMonitor,
+        keep_alive: KeepAliveConfig,
+        protocol_version: Version,
     ) -> Self {
-        let span = SpanGuard::new(&that_runtime_id);
+        let span = SpanGuard::new(&that_runtime_id, protocol_version);
 
         Self {
             this_runtime_id,
             that_runtime_id,
-            dispatcher: MessageDispatcher::new(),
+            dispatcher: MessageDispatcher::new(keep_alive),
             links: HashMap::default(),
             pex_peer,
+            throttle_buckets: Arc::new(ThrottleBuckets::default()),
             monitor,
             span,
         }
@@ -73,6 +87,14 @@ impl MessageBroker {
         self.dispatcher.bind(stream, permit)
     }
 
+    /// Gracefully closes the connections whose address matches the predicate.
+    pub fn remove_connections<F>(&self, pred: F)
+    where
+        F: Fn(&PeerAddr) -> bool + Send + 'static,
+    {
+        self.dispatcher.unbind(pred)
+    }
+
     /// Has this broker at least one live connection?
     pub fn has_connections(&self) -> bool {
         self.dispatcher.is_bound()
@@ -87,12 +109,34 @@ impl MessageBroker {
         pex_repo: &PexRepository,
         response_limiter: Arc<Semaphore>,
         byte_counters: Arc<ByteCounters>,
+        message_counters: Arc<MessageCounters>,
+        bandwidth_limits: Arc<BandwidthLimits>,
+        capabilities: Capabilities,
+        upload_enabled: Arc<AtomicBool>,
+        block_storage_enabled: Arc<AtomicBool>,
     ) {
         let monitor = self.monitor.make_child(vault.monitor.name());
+
+        let role = Role::determine(
+            vault.repository_id(),
+            &self.this_runtime_id,
+            &self.that_runtime_id,
+        );
+
+        let channel_id = MessageChannelId::new(
+            vault.repository_id(),
+            &self.this_runtime_id,
+            &self.that_runtime_id,
+            role,
+        );
+
+        // The channel id is recorded so the request/response events from both ends of the link
+        // can be correlated.
         let span = tracing::info_span!(
             parent: &self.span.0,
             "link",
             message = vault.monitor.name(),
+            channel = ?channel_id,
         );
 
         let span_enter = span.enter();
@@ -113,31 +157,29 @@ impl MessageBroker {
             }
         }
 
-        let role = Role::determine(
-            vault.repository_id(),
-            &self.this_runtime_id,
-            &self.that_runtime_id,
-        );
-
-        let channel_id = MessageChannelId::new(
-            vault.repository_id(),
-            &self.this_runtime_id,
-            &self.that_runtime_id,
-            role,
-        );
-
         let (pex_tx, pex_rx) = self.pex_peer.new_link(pex_repo);
 
+        let throttle = Arc::new(Throttle::new(
+            bandwidth_limits,
+            self.throttle_buckets.clone(),
+        ));
         let stream =
-            Instrumented::new(self.dispatcher.open_recv(channel_id), byte_counters.clone());
-        let sink = Instrumented::new(self.dispatcher.open_send(channel_id), byte_counters);
+            Instrumented::new(self.dispatcher.open_recv(channel_id), byte_counters.clone())
+                .with_throttle(throttle.clone());
+        let sink = Instrumented::new(self.dispatcher.open_send(channel_id), byte_counters)
+            .with_throttle(throttle);
 
         let mut link = Link {
             role,
+            that_runtime_id: self.that_runtime_id,
             stream,
             sink,
             vault,
             response_limiter,
+            message_counters,
+            capabilities,
+            upload_enabled,
+            block_storage_enabled,
             pex_tx,
             pex_rx,
             monitor,
@@ -170,10 +212,11 @@ impl MessageBroker {
 struct SpanGuard(Span);
 
 impl SpanGuard {
-    fn new(that_runtime_id: &PublicRuntimeId) -> Self {
+    fn new(that_runtime_id: &PublicRuntimeId, protocol_version: Version) -> Self {
         let span = tracing::info_span!(
             "message_broker",
             message = ?that_runtime_id.as_public_key(),
+            ?protocol_version,
         );
 
         tracing::info!(parent: &span, "Message broker created");
@@ -190,10 +233,15 @@ impl Drop for SpanGuard {
 
 struct Link {
     role: Role,
+    that_runtime_id: PublicRuntimeId,
     stream: Instrumented<ContentStream>,
     sink: Instrumented<ContentSink>,
     vault: Vault,
     response_limiter: Arc<Semaphore>,
+    message_counters: Arc<MessageCounters>,
+    capabilities: Capabilities,
+    upload_enabled: Arc<AtomicBool>,
+    block_storage_enabled: Arc<AtomicBool>,
     pex_tx: PexSender,
     pex_rx: PexReceiver,
     monitor: StateMonitor,
@@ -207,6 +255,7 @@ impl Link {
             Sleeping(#[allow(dead_code)] Duration),
             AwaitingBarrier,
             EstablishingChannel,
+            ExchangingCapabilities,
             Running,
         }
 
@@ -241,7 +290,7 @@ impl Link {
 
             *state.get() = State::EstablishingChannel;
 
-            let (crypto_stream, crypto_sink) =
+            let (mut crypto_stream, mut crypto_sink, session_hash) =
                 match establish_channel(self.role, &mut self.stream, &mut self.sink, &self.vault)
                     .await
                 {
@@ -251,18 +300,42 @@ impl Link {
                     Err(EstablishError::TransportChanged) => continue,
                 };
 
+            *state.get() = State::ExchangingCapabilities;
+
+            let access_mode = match exchange_capabilities(
+                &mut crypto_stream,
+                &mut crypto_sink,
+                &self.capabilities,
+                &session_hash,
+                self.role,
+            )
+            .await
+            {
+                Ok(access_mode) => access_mode,
+                Err(ControlFlow::Continue) => continue,
+                Err(ControlFlow::Break) => break,
+            };
+
+            let _peer_guard = self
+                .capabilities
+                .insert_peer(self.that_runtime_id, access_mode);
+
             *state.get() = State::Running;
 
-            match run_link(
+            let flow = run_link(
                 crypto_stream,
                 crypto_sink,
                 &self.vault,
                 self.response_limiter.clone(),
+                self.upload_enabled.clone(),
+                self.block_storage_enabled.clone(),
+                &self.message_counters,
                 &mut self.pex_tx,
                 &mut self.pex_rx,
             )
-            .await
-            {
+            .await;
+
+            match flow {
                 ControlFlow::Continue => continue,
                 ControlFlow::Break => break,
             }
@@ -275,7 +348,7 @@ async fn establish_channel<'a>(
     stream: &'a mut Instrumented<ContentStream>,
     sink: &'a mut Instrumented<ContentSink>,
     vault: &Vault,
-) -> Result<(DecryptingStream<'a>, EncryptingSink<'a>), EstablishError> {
+) -> Result<(DecryptingStream<'a>, EncryptingSink<'a>, Vec<u8>), EstablishError> {
     match crypto::establish_channel(role, vault.repository_id(), stream, sink).await {
         Ok(io) => {
             tracing::debug!("Established encrypted channel");
@@ -289,11 +362,57 @@ async fn establish_channel<'a>(
     }
 }
 
+// Send proof of our access mode to the peer and verify theirs. Returns the access mode proven by
+// the peer.
+async fn exchange_capabilities(
+    stream: &mut DecryptingStream<'_>,
+    sink: &mut EncryptingSink<'_>,
+    capabilities: &Capabilities,
+    session_hash: &[u8],
+    role: Role,
+) -> Result<AccessMode, ControlFlow> {
+    let proof = capabilities.prove(session_hash, role);
+
+    // unwrap is OK because serialization into a vec should never fail unless we have a bug
+    // somewhere.
+    match sink.send(bincode::serialize(&proof).unwrap()).await {
+        Ok(()) => (),
+        Err(SendError::Exhausted) => return Err(ControlFlow::Continue),
+        Err(SendError::Closed) => return Err(ControlFlow::Break),
+    }
+
+    let proof = match stream.recv().await {
+        Ok(proof) => proof,
+        Err(RecvError::Closed) => return Err(ControlFlow::Break),
+        Err(error) => {
+            tracing::debug!(?error, "Failed to receive capability proof");
+            return Err(ControlFlow::Continue);
+        }
+    };
+
+    let proof: Proof = match bincode::deserialize(&proof) {
+        Ok(proof) => proof,
+        Err(error) => {
+            tracing::warn!(?error, "Failed to deserialize capability proof");
+            return Err(ControlFlow::Continue);
+        }
+    };
+
+    let access_mode = capabilities.verify(session_hash, role.opposite(), &proof);
+
+    tracing::debug!(?access_mode, "Peer capabilities verified");
+
+    Ok(access_mode)
+}
+
 async fn run_link(
     stream: DecryptingStream<'_>,
     sink: EncryptingSink<'_>,
     repo: &Vault,
     response_limiter: Arc<Semaphore>,
+    upload_enabled: Arc<AtomicBool>,
+    block_storage_enabled: Arc<AtomicBool>,
+    message_counters: &MessageCounters,
     pex_tx: &mut PexSender,
     pex_rx: &mut PexReceiver,
 ) -> ControlFlow {
@@ -308,10 +427,22 @@ async fn run_link(
 
     // Run everything in parallel:
     let flow = select! {
-        flow = run_client(repo.clone(), content_tx.clone(), response_rx) => flow,
-        flow = run_server(repo.clone(), content_tx.clone(), request_rx, response_limiter) => flow,
-        flow = recv_messages(stream, request_tx, response_tx, pex_rx) => flow,
-        flow = send_messages(content_rx, sink) => flow,
+        flow = run_client(
+            repo.clone(),
+            content_tx.clone(),
+            response_rx,
+            block_storage_enabled.clone(),
+        ) => flow,
+        flow = run_server(
+            repo.clone(),
+            content_tx.clone(),
+            request_rx,
+            response_limiter,
+            upload_enabled,
+            block_storage_enabled,
+        ) => flow,
+        flow = recv_messages(stream, request_tx, response_tx, pex_rx, message_counters) => flow,
+        flow = send_messages(content_rx, sink, message_counters) => flow,
         _ = pex_tx.run(content_tx) => ControlFlow::Continue,
     };
 
@@ -326,6 +457,7 @@ async fn recv_messages(
     request_tx: mpsc::Sender<Request>,
     response_tx: mpsc::Sender<Response>,
     pex_rx: &PexReceiver,
+    message_counters: &MessageCounters,
 ) -> ControlFlow {
     loop {
         let content = match stream.recv().await {
@@ -356,6 +488,8 @@ async fn recv_messages(
             }
         };
 
+        message_counters.record_rx(&content);
+
         match content {
             Content::Request(request) => request_tx.send(request).await.unwrap_or(()),
             Content::Response(response) => response_tx.send(response).await.unwrap_or(()),
@@ -368,6 +502,7 @@ async fn recv_messages(
 async fn send_messages(
     mut content_rx: mpsc::UnboundedReceiver<Content>,
     mut sink: EncryptingSink<'_>,
+    message_counters: &MessageCounters,
 ) -> ControlFlow {
     loop {
         let content = if let Some(content) = content_rx.recv().await {
@@ -376,6 +511,8 @@ async fn send_messages(
             forever().await
         };
 
+        message_counters.record_tx(&content);
+
         // unwrap is OK because serialization into a vec should never fail unless we have a bug
         // somewhere.
         let content = bincode::serialize(&content).unwrap();
@@ -399,8 +536,9 @@ async fn run_client(
     repo: Vault,
     content_tx: mpsc::UnboundedSender<Content>,
     response_rx: mpsc::Receiver<Response>,
+    block_storage_enabled: Arc<AtomicBool>,
 ) -> ControlFlow {
-    let mut client = Client::new(repo, content_tx, response_rx);
+    let mut client = Client::new(repo, content_tx, response_rx, block_storage_enabled);
     let result = client.run().await;
 
     tracing::debug!("Client stopped running with result {:?}", result);
@@ -417,8 +555,17 @@ async fn run_server(
     content_tx: mpsc::UnboundedSender<Content>,
     request_rx: mpsc::Receiver<Request>,
     response_limiter: Arc<Semaphore>,
+    upload_enabled: Arc<AtomicBool>,
+    block_storage_enabled: Arc<AtomicBool>,
 ) -> ControlFlow {
-    let mut server = Server::new(repo, content_tx, request_rx, response_limiter);
+    let mut server = Server::new(
+        repo,
+        content_tx,
+        request_rx,
+        response_limiter,
+        upload_enabled,
+        block_storage_enabled,
+    );
 
     let result = server.run().await;
 
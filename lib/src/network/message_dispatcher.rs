@@ -4,12 +4,15 @@ use super::{
     connection::{ConnectionId, ConnectionPermit, ConnectionPermitHalf},
     message::{Message, MessageChannelId},
     message_io::{MessageSink, MessageStream, MESSAGE_OVERHEAD},
+    peer_addr::PeerAddr,
     raw,
     stats::Instrumented,
 };
 use crate::{collections::HashMap, sync::AwaitDrop};
 use async_trait::async_trait;
-use futures_util::{future, ready, stream::SelectAll, FutureExt, Sink, SinkExt, Stream, StreamExt};
+use futures_util::{
+    future, ready, stream::SelectAll, Future, FutureExt, Sink, SinkExt, Stream, StreamExt,
+};
 use std::{
     io,
     pin::Pin,
@@ -18,15 +21,41 @@ use std::{
         Arc,
     },
     task::{Context, Poll},
+    time::Duration,
 };
 use tokio::{
     select,
     sync::{mpsc, oneshot},
     task,
+    time::{self, Instant, Interval, MissedTickBehavior, Sleep},
 };
 
 const CONTENT_STREAM_BUFFER_SIZE: usize = 1024;
 
+const KEEP_ALIVE_PING: u8 = 0;
+const KEEP_ALIVE_PONG: u8 = 1;
+
+/// Keep-alive settings of peer connections.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct KeepAliveConfig {
+    /// How often to ping the peer. The pings also measure the round-trip time.
+    pub send_interval: Duration,
+    /// Connection is considered dead and closed if nothing is received from the peer for this
+    /// long. Only applies to peers that have sent at least one keep-alive message (older peers
+    /// don't send them so they might be legitimately silent for a long time). Must be longer than
+    /// the send interval (of both us and the peer).
+    pub recv_timeout: Duration,
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            send_interval: Duration::from_secs(10),
+            recv_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
 /// Reads/writes messages from/to the underlying TCP or QUIC streams and dispatches them to
 /// individual streams/sinks based on their channel ids (in the MessageDispatcher's and
 /// MessageBroker's contexts, there is a one-to-one relationship between the channel id and a
@@ -39,12 +68,18 @@ pub(super) struct MessageDispatcher {
 }
 
 impl MessageDispatcher {
-    pub fn new() -> Self {
+    pub fn new(keep_alive: KeepAliveConfig) -> Self {
         let (command_tx, command_rx) = mpsc::unbounded_channel();
         let (sink_tx, sink_rx) = mpsc::channel(1);
         let connection_count = Arc::new(AtomicUsize::new(0));
 
-        let worker = Worker::new(command_rx, sink_rx, connection_count.clone());
+        let worker = Worker::new(
+            command_rx,
+            sink_tx.clone(),
+            sink_rx,
+            connection_count.clone(),
+            keep_alive,
+        );
         task::spawn(worker.run());
 
         Self {
@@ -60,6 +95,18 @@ impl MessageDispatcher {
         self.command_tx.send(Command::Bind { socket, permit }).ok();
     }
 
+    /// Closes the connections whose address matches the predicate.
+    pub fn unbind<F>(&self, pred: F)
+    where
+        F: Fn(&PeerAddr) -> bool + Send + 'static,
+    {
+        self.command_tx
+            .send(Command::Unbind {
+                pred: Box::new(pred),
+            })
+            .ok();
+    }
+
     /// Is this dispatcher bound to at least one connection?
     pub fn is_bound(&self) -> bool {
         self.connection_count.load(Ordering::Acquire) > 0
@@ -148,8 +195,13 @@ impl ContentStream {
 impl Instrumented<ContentStream> {
     pub async fn recv(&mut self) -> Result<Vec<u8>, ContentStreamError> {
         let content = self.as_mut().recv().await?;
-        self.counters()
-            .increment_rx(content.len() as u64 + MESSAGE_OVERHEAD as u64);
+        let len = content.len() as u64 + MESSAGE_OVERHEAD as u64;
+
+        if let Some(throttle) = self.throttle() {
+            throttle.download(len).await;
+        }
+
+        self.counters().increment_rx(len);
         Ok(content)
     }
 
@@ -199,10 +251,14 @@ impl ContentSink {
 
 impl Instrumented<ContentSink> {
     pub async fn send(&self, content: Vec<u8>) -> Result<(), ChannelClosed> {
-        let len = content.len();
+        let len = content.len() as u64 + MESSAGE_OVERHEAD as u64;
+
+        if let Some(throttle) = self.throttle() {
+            throttle.upload(len).await;
+        }
+
         self.as_ref().send(content).await?;
-        self.counters()
-            .increment_tx(len as u64 + MESSAGE_OVERHEAD as u64);
+        self.counters().increment_tx(len);
         Ok(())
     }
 
@@ -254,6 +310,10 @@ struct ConnectionStream {
     permit: ConnectionPermitHalf,
     permit_released: AwaitDrop,
     connection_count: Arc<AtomicUsize>,
+    recv_timeout: Duration,
+    recv_deadline: Pin<Box<Sleep>>,
+    // Whether the peer sends keep-alive messages. The receive timeout is enforced only if it does.
+    keep_alive_seen: bool,
 }
 
 impl ConnectionStream {
@@ -261,6 +321,7 @@ impl ConnectionStream {
         reader: Instrumented<raw::OwnedReadHalf>,
         permit: ConnectionPermitHalf,
         connection_count: Arc<AtomicUsize>,
+        recv_timeout: Duration,
     ) -> Self {
         connection_count.fetch_add(1, Ordering::Release);
 
@@ -271,6 +332,9 @@ impl ConnectionStream {
             permit,
             permit_released,
             connection_count,
+            recv_timeout,
+            recv_deadline: Box::pin(time::sleep(recv_timeout)),
+            keep_alive_seen: false,
         }
     }
 }
@@ -287,10 +351,28 @@ impl Stream for ConnectionStream {
             }
         }
 
-        match ready!(self.reader.poll_next_unpin(cx)) {
-            Some(Ok(message)) => Poll::Ready(Some((self.permit.id(), message))),
-            Some(Err(_)) | None => Poll::Ready(None),
+        match self.reader.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(message))) => {
+                if message.channel == MessageChannelId::KEEP_ALIVE {
+                    self.keep_alive_seen = true;
+                }
+
+                let deadline = Instant::now() + self.recv_timeout;
+                self.recv_deadline.as_mut().reset(deadline);
+
+                return Poll::Ready(Some((self.permit.id(), message)));
+            }
+            Poll::Ready(Some(Err(_)) | None) => return Poll::Ready(None),
+            Poll::Pending => (),
+        }
+
+        if self.keep_alive_seen {
+            ready!(self.recv_deadline.as_mut().poll(cx));
+            tracing::debug!("nothing received from the peer in time, closing connection");
+            return Poll::Ready(None);
         }
+
+        Poll::Pending
     }
 }
 
@@ -306,7 +388,7 @@ struct ConnectionSink {
     // The writer is doubly instrumented - first time to track per connection stats and second time
     // to track cumulative stats across all connections.
     writer: MessageSink<Instrumented<Instrumented<raw::OwnedWriteHalf>>>,
-    _permit: ConnectionPermitHalf,
+    permit: ConnectionPermitHalf,
     permit_released: AwaitDrop,
 }
 
@@ -316,7 +398,7 @@ impl ConnectionSink {
 
         Self {
             writer: MessageSink::new(Instrumented::new(writer, permit.byte_counters())),
-            _permit: permit,
+            permit,
             permit_released,
         }
     }
@@ -358,14 +440,24 @@ struct Worker {
     connection_count: Arc<AtomicUsize>,
     send: SendState,
     recv: RecvState,
+    keep_alive_timer: Interval,
+    recv_timeout: Duration,
 }
 
 impl Worker {
     fn new(
         command_rx: mpsc::UnboundedReceiver<Command>,
+        sink_tx: mpsc::Sender<Message>,
         sink_rx: mpsc::Receiver<Message>,
         connection_count: Arc<AtomicUsize>,
+        keep_alive: KeepAliveConfig,
     ) -> Self {
+        let mut keep_alive_timer = time::interval_at(
+            Instant::now() + keep_alive.send_interval,
+            keep_alive.send_interval,
+        );
+        keep_alive_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         Self {
             command_rx,
             connection_count,
@@ -377,7 +469,14 @@ impl Worker {
                 streams: SelectAll::default(),
                 channels: HashMap::default(),
                 message: None,
+                keep_alive: KeepAlive {
+                    sink_tx,
+                    next_seq: 0,
+                    pending: None,
+                },
             },
+            keep_alive_timer,
+            recv_timeout: keep_alive.recv_timeout,
         }
     }
 
@@ -393,6 +492,11 @@ impl Worker {
                 }
                 _ = self.send.run()=> unreachable!(),
                 _ = self.recv.run()=> unreachable!(),
+                _ = self.keep_alive_timer.tick() => {
+                    if !self.recv.streams.is_empty() {
+                        self.recv.keep_alive.ping();
+                    }
+                }
             }
         }
 
@@ -419,8 +523,26 @@ impl Worker {
                     reader,
                     recv_permit,
                     self.connection_count.clone(),
+                    self.recv_timeout,
                 ));
             }
+            Command::Unbind { pred } => {
+                let (removed, kept): (Vec<_>, Vec<_>) = self
+                    .send
+                    .sinks
+                    .drain(..)
+                    .partition(|sink| pred(&sink.permit.addr()));
+                self.send.sinks = kept;
+
+                // Closing the sink releases the permit which closes the corresponding stream as
+                // well.
+                future::join_all(
+                    removed
+                        .into_iter()
+                        .map(|mut sink| async move { sink.close().await.ok() }),
+                )
+                .await;
+            }
             Command::Shutdown { tx } => {
                 self.shutdown().await;
                 tx.send(()).ok();
@@ -456,6 +578,9 @@ enum Command {
         socket: Instrumented<raw::Stream>,
         permit: ConnectionPermit,
     },
+    Unbind {
+        pred: Box<dyn Fn(&PeerAddr) -> bool + Send>,
+    },
     Shutdown {
         tx: oneshot::Sender<()>,
     },
@@ -503,6 +628,7 @@ struct RecvState {
     streams: SelectAll<ConnectionStream>,
     channels: HashMap<MessageChannelId, mpsc::Sender<(ConnectionId, Vec<u8>)>>,
     message: Option<(MessageChannelId, ConnectionId, Vec<u8>)>,
+    keep_alive: KeepAlive,
 }
 
 impl RecvState {
@@ -520,6 +646,20 @@ impl RecvState {
                 },
             };
 
+            if channel == MessageChannelId::KEEP_ALIVE {
+                if let Some(rtt) = self.keep_alive.handle(&content) {
+                    if let Some(stream) = self
+                        .streams
+                        .iter()
+                        .find(|stream| stream.permit.id() == connection_id)
+                    {
+                        stream.permit.set_rtt(rtt);
+                    }
+                }
+
+                continue;
+            }
+
             let Some(tx) = self.channels.get(&channel) else {
                 continue;
             };
@@ -543,6 +683,64 @@ impl RecvState {
     }
 }
 
+// Measures the round-trip time by sending pings to the peer and timing their pongs. Old peers
+// that don't know about the keep-alive channel simply discard the pings.
+//
+// Keep-alive message content: [ tag (ping or pong): 1 byte ][ sequence number: 8 bytes ]
+struct KeepAlive {
+    sink_tx: mpsc::Sender<Message>,
+    next_seq: u64,
+    pending: Option<(u64, Instant)>,
+}
+
+impl KeepAlive {
+    fn ping(&mut self) {
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+
+        if self.send(KEEP_ALIVE_PING, seq) {
+            self.pending = Some((seq, Instant::now()));
+        }
+    }
+
+    // Handles received keep-alive message. Returns the round-trip time if the message is a pong
+    // to our last ping.
+    fn handle(&mut self, content: &[u8]) -> Option<Duration> {
+        let (tag, seq) = content.split_first()?;
+        let seq = u64::from_be_bytes(seq.try_into().ok()?);
+
+        match *tag {
+            KEEP_ALIVE_PING => {
+                self.send(KEEP_ALIVE_PONG, seq);
+                None
+            }
+            KEEP_ALIVE_PONG => match self.pending {
+                Some((pending_seq, sent)) if pending_seq == seq => {
+                    self.pending = None;
+                    Some(sent.elapsed())
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Sends the keep-alive message unless the outgoing queue is full. Skipping a ping or pong only
+    // means we miss one RTT sample so it's better than blocking the receiving.
+    fn send(&self, tag: u8, seq: u64) -> bool {
+        let mut content = Vec::with_capacity(9);
+        content.push(tag);
+        content.extend_from_slice(&seq.to_be_bytes());
+
+        self.sink_tx
+            .try_send(Message {
+                channel: MessageChannelId::KEEP_ALIVE,
+                content,
+            })
+            .is_ok()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{super::stats::ByteCounters, *};
@@ -556,7 +754,7 @@ mod tests {
         let channel = MessageChannelId::random();
         let send_content = b"hello world";
 
-        let server_dispatcher = MessageDispatcher::new();
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
         let mut server_stream = server_dispatcher.open_recv(channel);
 
         let (client_socket, server_socket) = create_connected_sockets().await;
@@ -583,7 +781,7 @@ mod tests {
         let send_content0 = b"one two three";
         let send_content1 = b"four five six";
 
-        let server_dispatcher = MessageDispatcher::new();
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
         let server_stream0 = server_dispatcher.open_recv(channel0);
         let server_stream1 = server_dispatcher.open_recv(channel1);
 
@@ -617,11 +815,11 @@ mod tests {
         let channel0 = MessageChannelId::random();
         let channel1 = MessageChannelId::random();
 
-        let client_dispatcher = MessageDispatcher::new();
+        let client_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
         let client_sink0 = client_dispatcher.open_send(channel0);
         let client_sink1 = client_dispatcher.open_send(channel1);
 
-        let server_dispatcher = MessageDispatcher::new();
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
         let server_stream0 = server_dispatcher.open_recv(channel0);
         let server_stream1 = server_dispatcher.open_recv(channel1);
 
@@ -667,7 +865,7 @@ mod tests {
         let send_content0 = b"one two three";
         let send_content1 = b"four five six";
 
-        let server_dispatcher = MessageDispatcher::new();
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
         let mut server_stream0 = server_dispatcher.open_recv(channel);
         let mut server_stream1 = server_dispatcher.open_recv(channel);
 
@@ -700,7 +898,7 @@ mod tests {
         let send_content0 = b"one two three";
         let send_content1 = b"four five six";
 
-        let server_dispatcher = MessageDispatcher::new();
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
         let mut server_stream = server_dispatcher.open_recv(channel);
 
         let (client_socket0, server_socket0) = create_connected_sockets().await;
@@ -751,7 +949,7 @@ mod tests {
         let send_content0 = b"one two three";
         let send_content1 = b"four five six";
 
-        let server_dispatcher = MessageDispatcher::new();
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
         let server_sink = server_dispatcher.open_send(channel);
 
         let (client_socket0, server_socket0) = create_connected_sockets().await;
@@ -782,9 +980,71 @@ mod tests {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn keep_alive_ping_is_answered_with_pong() {
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
+
+        let (client_socket, server_socket) = create_connected_sockets().await;
+        let (client_reader, client_writer) = tokio::io::split(client_socket);
+        let mut client_stream = MessageStream::new(client_reader);
+        let mut client_sink = MessageSink::new(client_writer);
+        server_dispatcher.bind(server_socket, ConnectionPermit::dummy());
+
+        let mut ping = vec![KEEP_ALIVE_PING];
+        ping.extend_from_slice(&42u64.to_be_bytes());
+
+        client_sink
+            .send(Message {
+                channel: MessageChannelId::KEEP_ALIVE,
+                content: ping,
+            })
+            .await
+            .unwrap();
+
+        let pong = client_stream.next().await.unwrap().unwrap();
+        assert_eq!(pong.channel, MessageChannelId::KEEP_ALIVE);
+
+        let mut expected = vec![KEEP_ALIVE_PONG];
+        expected.extend_from_slice(&42u64.to_be_bytes());
+        assert_eq!(pong.content, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn silent_peer_is_disconnected_after_recv_timeout() {
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig {
+            send_interval: Duration::from_millis(50),
+            recv_timeout: Duration::from_millis(200),
+        });
+
+        let (client_socket, server_socket) = create_connected_sockets().await;
+        let (_client_reader, client_writer) = tokio::io::split(client_socket);
+        let mut client_sink = MessageSink::new(client_writer);
+        server_dispatcher.bind(server_socket, ConnectionPermit::dummy());
+
+        let mut ping = vec![KEEP_ALIVE_PING];
+        ping.extend_from_slice(&0u64.to_be_bytes());
+
+        client_sink
+            .send(Message {
+                channel: MessageChannelId::KEEP_ALIVE,
+                content: ping,
+            })
+            .await
+            .unwrap();
+
+        // The client never sends anything else so the server should eventually drop it.
+        time::timeout(Duration::from_secs(5), async {
+            while server_dispatcher.is_bound() {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn shutdown() {
-        let server_dispatcher = MessageDispatcher::new();
+        let server_dispatcher = MessageDispatcher::new(KeepAliveConfig::default());
         let mut server_stream = server_dispatcher.open_recv(MessageChannelId::random());
         let server_sink = server_dispatcher.open_send(MessageChannelId::random());
 
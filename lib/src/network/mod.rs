@@ -1,12 +1,15 @@
 mod barrier;
+mod capability;
 mod client;
 mod connection;
 mod connection_monitor;
 mod constants;
 mod crypto;
 mod debug_payload;
+mod device_name;
 mod dht_discovery;
 mod gateway;
+mod interface;
 mod ip;
 mod local_discovery;
 mod message;
@@ -15,6 +18,7 @@ mod message_dispatcher;
 mod message_io;
 mod peer_addr;
 mod peer_exchange;
+mod peer_host;
 mod peer_info;
 mod peer_source;
 mod peer_state;
@@ -29,59 +33,79 @@ mod stun;
 mod stun_server_list;
 #[cfg(test)]
 mod tests;
+mod transport_preference;
 mod upnp;
 
 pub use self::{
     connection::{ConnectionSetSubscription, PeerInfoCollector},
+    device_name::MAX_DEVICE_NAME_LEN,
     dht_discovery::{DhtContactsStoreTrait, DHT_ROUTERS},
+    message_dispatcher::KeepAliveConfig,
     peer_addr::PeerAddr,
+    peer_host::PeerHost,
     peer_info::PeerInfo,
     peer_source::PeerSource,
     peer_state::PeerState,
     runtime_id::{PublicRuntimeId, SecretRuntimeId},
-    stats::Stats,
+    stats::{BandwidthLimit, BandwidthLimitOverride, MessageCounts, MessageStats, Stats},
+    stun::ExternalAddrs,
+    transport_preference::TransportPreference,
+};
+pub use net::{
+    quic::{CongestionKind, QuicTuning},
+    stun::NatBehavior,
 };
-pub use net::stun::NatBehavior;
 
 use self::{
+    capability::Capabilities,
     connection::{ConnectionPermit, ConnectionSet, ReserveResult},
     connection_monitor::ConnectionMonitor,
     constants::MAX_UNCHOKED_COUNT,
     dht_discovery::DhtDiscovery,
-    gateway::{Gateway, StackAddresses},
+    gateway::{ConnectResult, Gateway, StackAddresses},
     local_discovery::LocalDiscovery,
     message_broker::MessageBroker,
     peer_addr::PeerPort,
     peer_exchange::{PexDiscovery, PexRepository},
-    protocol::{Version, MAGIC, VERSION},
+    protocol::{
+        Version, DEVICE_NAME_VERSION, MAGIC, MIN_SUPPORTED_VERSION, TRANSPORT_PREFERENCE_VERSION,
+        VERSION,
+    },
     seen_peers::{SeenPeer, SeenPeers},
-    stats::{ByteCounters, StatsTracker},
+    stats::{BandwidthLimits, ByteCounters, MessageCounters, StatsTracker},
     stun::StunClients,
 };
 use crate::{
+    access_control::AccessMode,
     collections::{hash_map::Entry, HashMap, HashSet},
     network::stats::Instrumented,
     protocol::RepositoryId,
     repository::{RepositoryHandle, Vault},
+    store::Error as StoreError,
     sync::uninitialized_watch,
 };
-use backoff::{backoff::Backoff, ExponentialBackoffBuilder};
+use backoff::{backoff::Backoff, ExponentialBackoff, ExponentialBackoffBuilder};
 use btdht::{self, InfoHash, INFO_HASH_LEN};
 use deadlock::BlockingMutex;
-use futures_util::future;
+use futures_util::{future, StreamExt};
+use if_watch::tokio::IfWatcher;
 use scoped_task::ScopedAbortHandle;
 use slab::Slab;
 use state_monitor::StateMonitor;
 use std::{
+    collections::BTreeMap,
     future::Future,
     io, mem,
-    net::{SocketAddr, SocketAddrV4, SocketAddrV6},
-    sync::{Arc, Weak},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Weak,
+    },
 };
 use thiserror::Error;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
-    sync::{mpsc, Semaphore},
+    sync::{broadcast, mpsc, Semaphore},
     task::{AbortHandle, JoinSet},
     time::Duration,
 };
@@ -89,6 +113,85 @@ use tracing::{Instrument, Span};
 
 const DHT_ENABLED: &str = "dht_enabled";
 const PEX_ENABLED: &str = "pex_enabled";
+const UPLOAD_ENABLED: &str = "upload_enabled";
+const BLOCK_STORAGE_ENABLED: &str = "block_storage_enabled";
+
+const PEER_EVENT_CHANNEL_CAPACITY: usize = 32;
+
+// Buffer size of each direction of the in-memory connections (see `Network::connect_in_memory`).
+#[cfg(any(test, feature = "test-utils"))]
+const MEMORY_STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+// How often to re-resolve the user provided peer hosts.
+const HOST_RESOLVE_INTERVAL: Duration = Duration::from_secs(60);
+const HOST_RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Policy deciding whether a peer is allowed to connect. See `Network::set_peer_filter`.
+type PeerFilter = dyn Fn(&PublicRuntimeId, &PeerAddr) -> bool + Send + Sync;
+
+/// Details of an encountered peer that uses a higher protocol version than us.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct ProtocolMismatch {
+    pub our_version: u32,
+    pub their_version: u32,
+    pub peer_addr: PeerAddr,
+    /// Runtime id of the peer. `None` if the peer disconnected before revealing it, which happens
+    /// when it no longer supports our protocol version.
+    pub runtime_id: Option<PublicRuntimeId>,
+}
+
+/// Connection or disconnection of a single peer connection.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct PeerEvent {
+    pub addr: PeerAddr,
+    pub runtime_id: PublicRuntimeId,
+    pub source: PeerSource,
+    pub kind: PeerEventKind,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PeerEventKind {
+    Connected,
+    Disconnected,
+}
+
+/// Parameters of the exponential backoff used when reconnecting to a peer.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ReconnectBackoff {
+    /// Delay before the first reconnection attempt.
+    pub initial_interval: Duration,
+    /// Upper bound on the delay between attempts.
+    pub max_interval: Duration,
+    /// Factor by which the delay grows after each attempt.
+    pub multiplier: f64,
+    /// How much to randomize each delay, in the `[0, 1]` range. A delay `d` becomes a random
+    /// value from `[d * (1 - randomization_factor), d * (1 + randomization_factor)]`. This keeps
+    /// peers which got disconnected at the same time from reconnecting in lockstep.
+    pub randomization_factor: f64,
+}
+
+impl ReconnectBackoff {
+    fn build(&self) -> ExponentialBackoff {
+        ExponentialBackoffBuilder::new()
+            .with_initial_interval(self.initial_interval)
+            .with_max_interval(self.max_interval)
+            .with_multiplier(self.multiplier)
+            .with_randomization_factor(self.randomization_factor)
+            .with_max_elapsed_time(None)
+            .build()
+    }
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self {
+            initial_interval: Duration::from_millis(100),
+            max_interval: Duration::from_secs(8),
+            multiplier: backoff::default::MULTIPLIER,
+            randomization_factor: backoff::default::RANDOMIZATION_FACTOR,
+        }
+    }
+}
 
 pub struct Network {
     inner: Arc<Inner>,
@@ -122,6 +225,7 @@ impl Network {
         let pex_discovery = PexDiscovery::new(pex_discovery_tx);
 
         let (on_protocol_mismatch_tx, _) = uninitialized_watch::channel();
+        let (on_peer_event_tx, _) = broadcast::channel(PEER_EVENT_CHANNEL_CAPACITY);
 
         let user_provided_peers = SeenPeers::new();
 
@@ -157,11 +261,25 @@ impl Network {
             stun_clients: StunClients::new(),
             connections: ConnectionSet::new(),
             on_protocol_mismatch_tx,
+            on_peer_event_tx,
             user_provided_peers,
+            user_provided_hosts: BlockingMutex::new(HashMap::default()),
             tasks: Arc::downgrade(&tasks),
             highest_seen_protocol_version: BlockingMutex::new(VERSION),
+            last_protocol_mismatch: BlockingMutex::new(None),
             our_addresses: BlockingMutex::new(HashSet::default()),
             stats_tracker: StatsTracker::default(),
+            message_counters: Arc::new(MessageCounters::default()),
+            bandwidth_limits: Arc::new(BandwidthLimits::default()),
+            keep_alive: BlockingMutex::new(KeepAliveConfig::default()),
+            reconnect_backoff: BlockingMutex::new(ReconnectBackoff::default()),
+            peer_max_retry_elapsed: BlockingMutex::new(BTreeMap::new()),
+            transport_preference: BlockingMutex::new(TransportPreference::default()),
+            device_name: BlockingMutex::new(String::new()),
+            bind_addrs: BlockingMutex::new(Vec::new()),
+            bind_interface: BlockingMutex::new(None),
+            blocked_runtime_ids: BlockingMutex::new(HashSet::default()),
+            peer_filter: BlockingMutex::new(None),
         });
 
         inner.spawn(inner.clone().handle_incoming_connections(incoming_rx));
@@ -179,16 +297,110 @@ impl Network {
     /// Binds the network to the specified addresses.
     /// Rebinds if already bound. Unbinds and disables the network if `addrs` is empty.
     ///
-    /// NOTE: currently at most one address per protocol (QUIC/TCP) and family (IPv4/IPv6) is used
-    /// and the rest are ignored, but this might change in the future.
+    /// Multiple addresses per protocol (QUIC/TCP) and family (IPv4/IPv6) can be used (e.g., to
+    /// listen on several interfaces of a multi-homed host). Incoming connections are accepted on
+    /// all of them but outgoing QUIC connections (and DHT) use only the first QUIC address of each
+    /// family.
     pub async fn bind(&self, addrs: &[PeerAddr]) {
-        self.inner.bind(addrs).await
+        self.inner.bind(addrs).await;
+    }
+
+    /// Like [`Self::bind`] but fails if any of the addresses fails to bind (instead of just
+    /// logging it) and on success returns the local addresses of all the listeners, with port 0
+    /// resolved to the actual port assigned by the OS. All the listeners are already accepting
+    /// connections when this returns which makes it suitable for scripted startup that needs to
+    /// know where the network is listening.
+    ///
+    /// On failure the addresses that did bind stay bound. Calling this again retries the failed
+    /// ones.
+    pub async fn bind_and_wait(&self, addrs: &[PeerAddr]) -> Result<Vec<PeerAddr>, BindError> {
+        let failed = self.inner.bind(addrs).await;
+
+        if failed.is_empty() {
+            Ok(self.listener_local_addrs())
+        } else {
+            Err(BindError { addrs: failed })
+        }
     }
 
     pub fn listener_local_addrs(&self) -> Vec<PeerAddr> {
         self.inner.gateway.listener_local_addrs()
     }
 
+    /// Restricts the network to the network interface with the given name (e.g. `wg0`) or lifts
+    /// the restriction if `None` (the default).
+    ///
+    /// While set, the bind addresses with unspecified IP (`0.0.0.0` / `[::]`) are replaced with the
+    /// current addresses of the interface and the other addresses not belonging to it are ignored.
+    /// The interface is watched and the network is rebound whenever its addresses change. If the
+    /// interface doesn't exist or is down, the network stays unbound rather than falling back to
+    /// the other interfaces.
+    ///
+    /// On Linux and Android the sockets (including the outgoing TCP connections) are additionally
+    /// bound to the interface itself using `SO_BINDTODEVICE`, so their traffic can't leave through
+    /// any other interface. On older kernels (before 5.7) this requires `CAP_NET_RAW` and the
+    /// binding fails without it. On other platforms only the listeners are bound to the interface
+    /// addresses and outgoing TCP connections follow the routing table.
+    ///
+    /// Local discovery runs only on the addresses of the interface and its multicast sockets send
+    /// only through it (and are bound to it where `SO_BINDTODEVICE` is supported), so the node
+    /// doesn't announce itself on any other interface.
+    ///
+    /// Port forwarding (UPnP) and resolving user provided host names are not affected by this.
+    pub async fn set_bind_interface(&self, interface: Option<String>) {
+        let interface = interface.filter(|interface| !interface.is_empty());
+
+        {
+            let mut current = self.inner.bind_interface.lock().unwrap();
+
+            if current.as_ref().map(|(name, _)| name) == interface.as_ref() {
+                return;
+            }
+
+            *current = interface.map(|interface| {
+                let handle = self.inner.spawn(self.inner.clone().watch_bind_interface());
+                (interface, handle.into())
+            });
+        }
+
+        self.inner.rebind().await;
+    }
+
+    /// Returns the interface set with [Self::set_bind_interface].
+    pub fn bind_interface(&self) -> Option<String> {
+        self.inner
+            .bind_interface
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Sets the tuning of the QUIC transport (congestion controller, initial MTU and idle
+    /// timeout). The defaults match the behaviour before this was configurable.
+    ///
+    /// The tuning is applied only when the QUIC endpoints are created, so changing it (in
+    /// particular the MTU) while the network is bound requires a rebind. Because `bind` is a no-op
+    /// when the addresses don't change, unbind first (`bind(&[])`) and then bind again.
+    ///
+    /// Fails with `InvalidArgument` if `initial_mtu` is less than 1200 or if `max_idle_timeout`
+    /// is not longer than the QUIC keep-alive interval.
+    pub fn set_quic_transport_config(&self, tuning: QuicTuning) -> crate::Result<()> {
+        if tuning.initial_mtu < net::quic::MIN_INITIAL_MTU
+            || tuning.max_idle_timeout <= net::KEEP_ALIVE_INTERVAL
+        {
+            return Err(crate::Error::InvalidArgument);
+        }
+
+        self.inner.gateway.set_quic_tuning(tuning);
+
+        Ok(())
+    }
+
+    pub fn quic_transport_config(&self) -> QuicTuning {
+        self.inner.gateway.quic_tuning()
+    }
+
     pub fn set_port_forwarding_enabled(&self, enabled: bool) {
         let mut state = self.inner.port_forwarder_state.lock().unwrap();
 
@@ -277,11 +489,204 @@ impl Network {
         self.inner.stun_clients.nat_behavior().await
     }
 
+    /// Subscribe to changes of our external addresses or the behavior of the NAT we are behind.
+    /// STUN is re-run periodically (see [Self::set_stun_poll_interval]) and on every rebind, and
+    /// a notification is sent whenever the result differs from the previous one.
+    pub fn on_external_addr_change(&self) -> uninitialized_watch::Receiver<ExternalAddrs> {
+        self.inner.stun_clients.on_change()
+    }
+
+    /// Sets how often to re-run STUN to detect changes of our external addresses. Fails with
+    /// `InvalidArgument` if `interval` is zero.
+    pub fn set_stun_poll_interval(&self, interval: Duration) -> crate::Result<()> {
+        if interval.is_zero() {
+            return Err(crate::Error::InvalidArgument);
+        }
+
+        self.inner.stun_clients.set_poll_interval(interval);
+
+        Ok(())
+    }
+
+    /// Sets the STUN servers to use instead of the built-in ones. Empty `servers` disables STUN
+    /// entirely, in which case [Self::external_addr_v4], [Self::external_addr_v6] and
+    /// [Self::nat_behavior] return `None`. The setting persists across rebinds.
+    pub fn set_stun_servers(&self, servers: Vec<SocketAddr>) {
+        self.inner.stun_clients.set_servers(Some(servers))
+    }
+
+    /// Restores the built-in STUN servers after [Self::set_stun_servers].
+    pub fn reset_stun_servers(&self) {
+        self.inner.stun_clients.set_servers(None)
+    }
+
     /// Get the network traffic stats.
     pub fn stats(&self) -> Stats {
         self.inner.stats_tracker.read()
     }
 
+    /// Get the number of sync protocol messages of each kind sent and received, aggregated across
+    /// all repositories and peers.
+    pub fn message_stats(&self) -> MessageStats {
+        self.inner.message_counters.read()
+    }
+
+    /// Sets the max upload and download bandwidth (in bytes per second) a single peer can consume
+    /// when syncing, across all the repositories shared with it. `None` (or zero) means unlimited.
+    /// Only the sync traffic is throttled, not the connection maintenance (e.g., keep-alive)
+    /// traffic. Can be overridden per repository with [Registration::set_bandwidth_limit].
+    pub fn set_bandwidth_limit(&self, upload_bps: Option<u64>, download_bps: Option<u64>) {
+        self.inner
+            .bandwidth_limits
+            .set(upload_bps.into(), download_bps.into())
+    }
+
+    /// Returns the bandwidth limit set with [Self::set_bandwidth_limit].
+    pub fn bandwidth_limit(&self) -> BandwidthLimit {
+        self.inner.bandwidth_limits.read()
+    }
+
+    /// Sets how often to ping the peers (`send_interval`) and how long to wait for any message
+    /// from a peer before considering the connection dead and closing it (`recv_timeout`). Shorter
+    /// intervals detect dead peers sooner at the cost of more traffic. Only affects peers connected
+    /// from now on; existing connections keep their current settings. Fails with `InvalidArgument`
+    /// unless `recv_timeout` is longer than `send_interval`.
+    pub fn set_keep_alive_config(
+        &self,
+        send_interval: Duration,
+        recv_timeout: Duration,
+    ) -> crate::Result<()> {
+        if send_interval.is_zero() || recv_timeout <= send_interval {
+            return Err(crate::Error::InvalidArgument);
+        }
+
+        *self.inner.keep_alive.lock().unwrap() = KeepAliveConfig {
+            send_interval,
+            recv_timeout,
+        };
+
+        Ok(())
+    }
+
+    /// Returns the keep-alive settings set with [Self::set_keep_alive_config].
+    pub fn keep_alive_config(&self) -> KeepAliveConfig {
+        *self.inner.keep_alive.lock().unwrap()
+    }
+
+    /// Sets the backoff parameters used when reconnecting to peers. Only affects peers discovered
+    /// from now on. Fails with `InvalidArgument` if the intervals are zero or `max_interval` is
+    /// shorter than `initial_interval`, if `multiplier` is less than 1 or if
+    /// `randomization_factor` is not within `[0, 1]`.
+    pub fn set_reconnect_backoff(&self, config: ReconnectBackoff) -> crate::Result<()> {
+        if config.initial_interval.is_zero()
+            || config.max_interval < config.initial_interval
+            || config.multiplier.is_nan()
+            || config.multiplier < 1.0
+            || !(0.0..=1.0).contains(&config.randomization_factor)
+        {
+            return Err(crate::Error::InvalidArgument);
+        }
+
+        *self.inner.reconnect_backoff.lock().unwrap() = config;
+
+        Ok(())
+    }
+
+    /// Sets the max total time spent retrying to connect to an unresponsive peer from the given
+    /// source. When exceeded, the peer is given up on and reported as [PeerState::Dead]. User
+    /// provided peers are also removed from the user provided peers. Discovered peers are retried
+    /// only when they are discovered again. `None` means retry indefinitely (the default). Only
+    /// affects connection attempts started from now on.
+    pub fn set_peer_max_retry_elapsed(&self, source: PeerSource, max: Option<Duration>) {
+        let mut map = self.inner.peer_max_retry_elapsed.lock().unwrap();
+
+        match max {
+            Some(max) => map.insert(source, max),
+            None => map.remove(&source),
+        };
+    }
+
+    /// Returns the max retry time set with [Self::set_peer_max_retry_elapsed].
+    pub fn peer_max_retry_elapsed(&self, source: PeerSource) -> Option<Duration> {
+        self.inner
+            .peer_max_retry_elapsed
+            .lock()
+            .unwrap()
+            .get(&source)
+            .copied()
+    }
+
+    /// Sets which transport to prefer when a peer is reachable over both QUIC and TCP. Once a
+    /// connection over the preferred transport completes the handshake, the connections to the
+    /// same peer over the other transport are closed. Connections over the other transport are
+    /// then only reestablished after all the preferred ones are lost.
+    ///
+    /// The preferences are exchanged during the handshake so both peers apply the same one. If
+    /// only one of them has a preference, that one applies. If they conflict, the preference of
+    /// the peer with the lower runtime id applies. The new preference applies only to the
+    /// connections established after this call.
+    pub fn set_transport_preference(&self, preference: TransportPreference) {
+        *self.inner.transport_preference.lock().unwrap() = preference;
+    }
+
+    /// Returns the transport preference set with [Self::set_transport_preference].
+    pub fn transport_preference(&self) -> TransportPreference {
+        *self.inner.transport_preference.lock().unwrap()
+    }
+
+    /// Sets the human readable name of this replica which is advertised to the peers during the
+    /// handshake and shown to them in [PeerInfo::display_name]. Control characters are removed and
+    /// the name is truncated to [MAX_DEVICE_NAME_LEN] bytes. An empty name means no name (the
+    /// default). Only affects peers connected from now on.
+    pub fn set_device_name(&self, name: String) {
+        *self.inner.device_name.lock().unwrap() = device_name::sanitize(&name);
+    }
+
+    /// Returns the (sanitized) device name set with [Self::set_device_name].
+    pub fn device_name(&self) -> String {
+        self.inner.device_name.lock().unwrap().clone()
+    }
+
+    /// Sets the max number of simultaneous peer connections (including the ones still being
+    /// established). `None` means unlimited (the default). Once the limit is reached, incoming
+    /// connections are dropped and outgoing connection attempts wait until a slot frees up.
+    /// Existing connections are never closed because of the limit, even if it's lowered below
+    /// their current number.
+    pub fn set_max_connections(&self, max: Option<usize>) {
+        self.inner.connections.set_max_connections(max)
+    }
+
+    /// Returns the max number of simultaneous peer connections set with
+    /// [Self::set_max_connections].
+    pub fn max_connections(&self) -> Option<usize> {
+        self.inner.connections.max_connections()
+    }
+
+    /// Returns the backoff parameters set with [Self::set_reconnect_backoff].
+    pub fn reconnect_backoff(&self) -> ReconnectBackoff {
+        *self.inner.reconnect_backoff.lock().unwrap()
+    }
+
+    /// Sets the nodes to bootstrap the DHT against. Empty `nodes` means use the default routers
+    /// ([DHT_ROUTERS]). Running DHT instances are restarted so the change takes effect immediately.
+    pub fn set_dht_bootstrap_nodes(&self, nodes: Vec<SocketAddr>) {
+        self.inner
+            .dht_discovery
+            .set_routers(nodes.iter().map(|node| node.to_string()).collect())
+    }
+
+    /// Enables/disables DHT globally. Enabled by default.
+    ///
+    /// Note: DHT for a given repo is used only if it's enabled globally using this function and
+    /// also for the repo using [Registration::set_dht_enabled].
+    pub fn set_dht_enabled(&self, enabled: bool) {
+        self.inner.dht_discovery.set_enabled(enabled)
+    }
+
+    pub fn is_dht_enabled(&self) -> bool {
+        self.inner.dht_discovery.is_enabled()
+    }
+
     pub fn add_user_provided_peer(&self, peer: &PeerAddr) {
         self.inner.clone().establish_user_provided_connection(peer);
     }
@@ -290,10 +695,140 @@ impl Network {
         self.inner.user_provided_peers.remove(peer)
     }
 
+    /// Adds a peer given by a host name (e.g. `peer.example.com:1234`). The name is resolved
+    /// using DNS and all the resolved addresses are connected to. The name is periodically
+    /// resolved again so peers whose address changes (e.g. behind a dynamic DNS) stay reachable.
+    pub fn add_user_provided_host(&self, host: PeerHost) {
+        let mut hosts = self.inner.user_provided_hosts.lock().unwrap();
+
+        if hosts.contains_key(&host) {
+            return;
+        }
+
+        let seen_peers = SeenPeers::new();
+        let handle = self.inner.spawn(
+            self.inner
+                .clone()
+                .run_user_provided_host(host.clone(), seen_peers.clone()),
+        );
+
+        hosts.insert(host, (seen_peers, handle.into()));
+    }
+
+    /// Removes a peer previously added with [Self::add_user_provided_host].
+    pub fn remove_user_provided_host(&self, host: &PeerHost) {
+        let Some((seen_peers, _handle)) =
+            self.inner.user_provided_hosts.lock().unwrap().remove(host)
+        else {
+            return;
+        };
+
+        for peer in seen_peers.collect() {
+            seen_peers.remove(peer.initial_addr());
+        }
+    }
+
+    /// Sets a filter deciding which peers are allowed to connect. It's consulted right after the
+    /// handshake of every new connection (both incoming and outgoing) and if it returns `false`,
+    /// the connection is dropped and not retried. Peers blocked with [Self::block_runtime_id] are
+    /// rejected before the filter is consulted. Existing connections are not affected.
+    pub fn set_peer_filter<F>(&self, filter: F)
+    where
+        F: Fn(&PublicRuntimeId, &PeerAddr) -> bool + Send + Sync + 'static,
+    {
+        *self.inner.peer_filter.lock().unwrap() = Some(Arc::new(filter));
+    }
+
+    /// Removes the filter set with [Self::set_peer_filter].
+    pub fn clear_peer_filter(&self) {
+        *self.inner.peer_filter.lock().unwrap() = None;
+    }
+
+    /// Blocks the peer with the given runtime id: disconnects it immediately and rejects any of
+    /// its further connections.
+    pub fn block_runtime_id(&self, runtime_id: PublicRuntimeId) {
+        self.inner
+            .blocked_runtime_ids
+            .lock()
+            .unwrap()
+            .insert(runtime_id);
+
+        let broker = self
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .message_brokers
+            .as_mut()
+            .and_then(|brokers| brokers.remove(&runtime_id));
+
+        if let Some(broker) = broker {
+            tracing::debug!(?runtime_id, "Disconnecting blocked peer");
+            self.inner.spawn(broker.shutdown());
+        }
+    }
+
+    /// Unblocks a peer previously blocked with [Self::block_runtime_id].
+    pub fn unblock_runtime_id(&self, runtime_id: &PublicRuntimeId) {
+        self.inner
+            .blocked_runtime_ids
+            .lock()
+            .unwrap()
+            .remove(runtime_id);
+    }
+
+    /// Returns the runtime ids of the blocked peers.
+    pub fn blocked_runtime_ids(&self) -> Vec<PublicRuntimeId> {
+        self.inner
+            .blocked_runtime_ids
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    /// Returns the addresses that were detected as belonging to this node (connecting to them
+    /// resulted in connection to self). No connections to these addresses are attempted.
+    pub fn self_addresses(&self) -> Vec<PeerAddr> {
+        self.inner
+            .our_addresses
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    /// Forgets the addresses detected as belonging to this node so that connecting to them is
+    /// attempted again. This is done automatically on rebind because after a network change those
+    /// addresses might belong to other peers.
+    pub fn clear_self_addresses(&self) {
+        self.inner.our_addresses.lock().unwrap().clear();
+    }
+
     pub fn this_runtime_id(&self) -> PublicRuntimeId {
         self.inner.this_runtime_id.public()
     }
 
+    /// Connects this network to `other` over an in-memory channel instead of a socket, so tests
+    /// can wire several networks in the same process together quickly and deterministically. Both
+    /// sides treat the connection as incoming and give each other a placeholder address.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub async fn connect_in_memory(&self, other: &Network) {
+        let (this_stream, that_stream) = tokio::io::duplex(MEMORY_STREAM_BUFFER_SIZE);
+
+        self.inner
+            .gateway
+            .accept_in_memory(this_stream, raw::next_memory_addr())
+            .await;
+        other
+            .inner
+            .gateway
+            .accept_in_memory(that_stream, raw::next_memory_addr())
+            .await;
+    }
+
     pub fn peer_info_collector(&self) -> PeerInfoCollector {
         self.inner.connections.peer_info_collector()
     }
@@ -310,16 +845,31 @@ impl Network {
         (*self.inner.highest_seen_protocol_version.lock().unwrap()).into()
     }
 
-    /// Subscribe to network protocol mismatch events.
-    pub fn on_protocol_mismatch(&self) -> uninitialized_watch::Receiver<()> {
+    /// Subscribe to network protocol mismatch events. Only mismatches with a protocol version
+    /// higher than any seen before are reported.
+    pub fn on_protocol_mismatch(&self) -> uninitialized_watch::Receiver<ProtocolMismatch> {
         self.inner.on_protocol_mismatch_tx.subscribe()
     }
 
+    /// Details of the most recently reported protocol mismatch (see [Self::on_protocol_mismatch])
+    /// or `None` if no peer with a higher protocol version has been seen yet.
+    pub fn last_protocol_mismatch(&self) -> Option<ProtocolMismatch> {
+        *self.inner.last_protocol_mismatch.lock().unwrap()
+    }
+
     /// Subscribe change in connected peers events.
     pub fn on_peer_set_change(&self) -> ConnectionSetSubscription {
         self.inner.connections.subscribe()
     }
 
+    /// Subscribe to connections and disconnections of individual peers. Unlike
+    /// [Self::on_peer_set_change] this reports which peer changed so there is no need to re-fetch
+    /// the whole peer list. Events are emitted per connection so a peer connected over multiple
+    /// transports produces multiple events.
+    pub fn on_peer_event(&self) -> broadcast::Receiver<PeerEvent> {
+        self.inner.on_peer_event_tx.subscribe()
+    }
+
     /// Register a local repository into the network. This links the repository with all matching
     /// repositories of currently connected remote replicas as well as any replicas connected in
     /// the future. The repository is automatically deregistered when the returned handle is
@@ -343,6 +893,18 @@ impl Network {
             .await
             .unwrap_or(Some(false))
             .unwrap_or(false);
+        let upload_enabled = metadata
+            .get(UPLOAD_ENABLED)
+            .await
+            .unwrap_or(Some(true))
+            .unwrap_or(true);
+        let upload_enabled = Arc::new(AtomicBool::new(upload_enabled));
+        let block_storage_enabled = metadata
+            .get(BLOCK_STORAGE_ENABLED)
+            .await
+            .unwrap_or(Some(true))
+            .unwrap_or(true);
+        let block_storage_enabled = Arc::new(AtomicBool::new(block_storage_enabled));
 
         let dht = if dht_enabled {
             Some(
@@ -359,6 +921,13 @@ impl Network {
         // TODO: This should be global, not per repo
         let response_limiter = Arc::new(Semaphore::new(MAX_UNCHOKED_COUNT));
         let stats_tracker = StatsTracker::default();
+        let message_counters = Arc::new(MessageCounters::with_parent(
+            self.inner.message_counters.clone(),
+        ));
+        let bandwidth_limits = Arc::new(BandwidthLimits::with_parent(
+            self.inner.bandwidth_limits.clone(),
+        ));
+        let capabilities = Capabilities::new(handle.credentials);
 
         let mut network_state = self.inner.state.lock().unwrap();
 
@@ -367,6 +936,11 @@ impl Network {
             &pex,
             response_limiter.clone(),
             stats_tracker.bytes.clone(),
+            message_counters.clone(),
+            bandwidth_limits.clone(),
+            capabilities.clone(),
+            upload_enabled.clone(),
+            block_storage_enabled.clone(),
         );
 
         let key = network_state.registry.insert(RegistrationHolder {
@@ -375,6 +949,12 @@ impl Network {
             pex,
             response_limiter,
             stats_tracker,
+            message_counters,
+            bandwidth_limits,
+            capabilities,
+            upload_enabled,
+            block_storage_enabled,
+            sync_enabled: true,
         });
 
         Registration {
@@ -388,6 +968,8 @@ impl Network {
     /// once the keep-alive mechanism kicks in, but in the mean time we will not be able to
     /// reconnect (by starting the app again) because the remote peer will keep dropping new
     /// connections from us.
+    ///
+    /// Also saves the current DHT contacts into the contacts store passed to [`Self::new`], if any.
     pub async fn shutdown(&self) {
         // TODO: Would be a nice-to-have to also wait for all the spawned tasks here (e.g. dicovery
         // mechanisms).
@@ -396,6 +978,9 @@ impl Network {
             return;
         };
 
+        // Save the DHT contacts so the next start doesn't have to bootstrap from scratch.
+        self.inner.dht_discovery.save_contacts().await;
+
         shutdown_brokers(message_brokers).await;
     }
 }
@@ -422,6 +1007,19 @@ impl Registration {
         }
     }
 
+    /// Triggers an immediate DHT announce of this repository instead of waiting for the next
+    /// periodic one, so that peers can discover it faster (e.g., right after enabling DHT or after
+    /// changing networks). Rate-limited so repeated calls don't spam the DHT. Returns whether the
+    /// announce was actually triggered, which is never the case when DHT is disabled for this
+    /// repository.
+    pub fn announce_now(&self) -> bool {
+        self.inner.state.lock().unwrap().registry[self.key]
+            .dht
+            .as_ref()
+            .map(|dht| dht.announce_now())
+            .unwrap_or(false)
+    }
+
     /// This function provides the information to the user whether DHT is enabled for this
     /// repository, not necessarily whether the DHT tasks are currently running. The subtle
     /// difference is in that this function should return true even in case e.g. the whole network
@@ -450,12 +1048,126 @@ impl Registration {
             .is_enabled()
     }
 
+    /// Enables/disables uploading of this repository's blocks to peers. When disabled, the
+    /// repository is "receive-only": it still downloads from peers and still shares its index with
+    /// them (so they know what it has) but declines their block requests. Unlike disabling sync,
+    /// the repository keeps receiving changes.
+    ///
+    /// Note: peers can't download blocks that only receive-only replicas have, so a swarm
+    /// consisting only of receive-only replicas can't make any progress. At least one replica
+    /// with upload enabled that has the blocks is needed.
+    pub async fn set_upload_enabled(&self, enabled: bool) {
+        set_metadata_bool(&self.inner, self.key, UPLOAD_ENABLED, enabled).await;
+
+        let state = self.inner.state.lock().unwrap();
+        state.registry[self.key]
+            .upload_enabled
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_upload_enabled(&self) -> bool {
+        self.inner.state.lock().unwrap().registry[self.key]
+            .upload_enabled
+            .load(Ordering::Relaxed)
+    }
+
+    /// Enables/disables storing of this repository's blocks. When disabled, the repository acts as
+    /// a pure coordination node: it still syncs the index with peers and relays it between them
+    /// (and, if PEX is enabled, tells them about each other) but it never requests, stores or
+    /// serves block content. This keeps the disk usage low on always-on replicas whose only
+    /// purpose is to improve availability.
+    ///
+    /// Note: blocks already stored are kept but no longer served. Blocks offered while storage was
+    /// disabled are requested only once they are offered again (e.g., after reconnecting).
+    pub async fn set_block_storage_enabled(&self, enabled: bool) {
+        set_metadata_bool(&self.inner, self.key, BLOCK_STORAGE_ENABLED, enabled).await;
+
+        let state = self.inner.state.lock().unwrap();
+        state.registry[self.key]
+            .block_storage_enabled
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_block_storage_enabled(&self) -> bool {
+        self.inner.state.lock().unwrap().registry[self.key]
+            .block_storage_enabled
+            .load(Ordering::Relaxed)
+    }
+
+    /// Pauses/resumes syncing of this repository. When paused, the links with all peers are torn
+    /// down so the repository neither sends nor receives anything, but it stays registered so its
+    /// DHT and PEX announcements keep going and resuming doesn't require rediscovering the peers.
+    pub fn set_sync_enabled(&self, enabled: bool) {
+        let mut state = self.inner.state.lock().unwrap();
+        let state = &mut *state;
+        let holder = &mut state.registry[self.key];
+
+        if holder.sync_enabled == enabled {
+            return;
+        }
+
+        holder.sync_enabled = enabled;
+
+        let Some(brokers) = &mut state.message_brokers else {
+            return;
+        };
+
+        for broker in brokers.values_mut() {
+            if enabled {
+                holder.create_link(broker);
+            } else {
+                broker.destroy_link(holder.vault.repository_id());
+            }
+        }
+    }
+
+    pub fn is_sync_enabled(&self) -> bool {
+        self.inner.state.lock().unwrap().registry[self.key].sync_enabled
+    }
+
     /// Fetch per-repository network statistics.
     pub fn stats(&self) -> Stats {
         self.inner.state.lock().unwrap().registry[self.key]
             .stats_tracker
             .read()
     }
+
+    /// Fetch per-repository sync protocol message statistics.
+    pub fn message_stats(&self) -> MessageStats {
+        self.inner.state.lock().unwrap().registry[self.key]
+            .message_counters
+            .read()
+    }
+
+    /// Overrides the network-wide bandwidth limit (see [Network::set_bandwidth_limit]) for this
+    /// repository. The limit still applies to each peer as a whole: the traffic of this repository
+    /// is throttled together with the traffic of the other repositories synced with the same peer.
+    pub fn set_bandwidth_limit(
+        &self,
+        upload: BandwidthLimitOverride,
+        download: BandwidthLimitOverride,
+    ) {
+        self.inner.state.lock().unwrap().registry[self.key]
+            .bandwidth_limits
+            .set(upload, download)
+    }
+
+    /// Returns the effective bandwidth limit of this repository.
+    pub fn bandwidth_limit(&self) -> BandwidthLimit {
+        self.inner.state.lock().unwrap().registry[self.key]
+            .bandwidth_limits
+            .read()
+    }
+
+    /// Access mode the peer with the given runtime id has to this repository, as proven by the peer
+    /// when the link with it was established. Returns `None` if there is currently no link with
+    /// the peer. A peer whose proof can't be verified (e.g., a reader when this replica is blind)
+    /// is reported as blind.
+    pub fn peer_access(&self, runtime_id: &PublicRuntimeId) -> Option<AccessMode> {
+        self.inner.state.lock().unwrap().registry[self.key]
+            .capabilities
+            .peer(runtime_id)
+    }
 }
 
 impl Drop for Registration {
@@ -472,6 +1184,22 @@ impl Drop for Registration {
     }
 }
 
+/// Sets whether DHT and PEX will be enabled for the given repository once it's registered with
+/// [Network::register]. Unlike [Registration::set_dht_enabled] and
+/// [Registration::set_pex_enabled] this works on unregistered repositories which allows to decide
+/// whether a repository should be discoverable before it announces itself for the first time.
+pub async fn set_discovery_enabled(
+    handle: &RepositoryHandle,
+    dht_enabled: bool,
+    pex_enabled: bool,
+) -> Result<(), StoreError> {
+    let metadata = handle.vault.metadata();
+    metadata.set(DHT_ENABLED, dht_enabled).await?;
+    metadata.set(PEX_ENABLED, pex_enabled).await?;
+
+    Ok(())
+}
+
 async fn set_metadata_bool(inner: &Inner, key: usize, name: &str, value: bool) {
     let metadata = inner.state.lock().unwrap().registry[key].vault.metadata();
     metadata.set(name, value).await.ok();
@@ -483,6 +1211,28 @@ struct RegistrationHolder {
     pex: PexRepository,
     response_limiter: Arc<Semaphore>,
     stats_tracker: StatsTracker,
+    message_counters: Arc<MessageCounters>,
+    bandwidth_limits: Arc<BandwidthLimits>,
+    capabilities: Capabilities,
+    upload_enabled: Arc<AtomicBool>,
+    block_storage_enabled: Arc<AtomicBool>,
+    sync_enabled: bool,
+}
+
+impl RegistrationHolder {
+    fn create_link(&self, broker: &mut MessageBroker) {
+        broker.create_link(
+            self.vault.clone(),
+            &self.pex,
+            self.response_limiter.clone(),
+            self.stats_tracker.bytes.clone(),
+            self.message_counters.clone(),
+            self.bandwidth_limits.clone(),
+            self.capabilities.clone(),
+            self.upload_enabled.clone(),
+            self.block_storage_enabled.clone(),
+        );
+    }
 }
 
 struct Inner {
@@ -501,15 +1251,42 @@ struct Inner {
     pex_discovery: PexDiscovery,
     stun_clients: StunClients,
     connections: ConnectionSet,
-    on_protocol_mismatch_tx: uninitialized_watch::Sender<()>,
+    on_protocol_mismatch_tx: uninitialized_watch::Sender<ProtocolMismatch>,
+    on_peer_event_tx: broadcast::Sender<PeerEvent>,
     user_provided_peers: SeenPeers,
+    // Peers given by host names. Each has its own set of the addresses it resolved to and the
+    // task that periodically re-resolves it.
+    user_provided_hosts: BlockingMutex<HashMap<PeerHost, (SeenPeers, ScopedAbortHandle)>>,
     // Note that unwrapping the upgraded weak pointer should be fine because if the underlying Arc
     // was Dropped, we would not be asking for the upgrade in the first place.
     tasks: Weak<BlockingMutex<JoinSet<()>>>,
     highest_seen_protocol_version: BlockingMutex<Version>,
+    last_protocol_mismatch: BlockingMutex<Option<ProtocolMismatch>>,
     // Used to prevent repeatedly connecting to self.
     our_addresses: BlockingMutex<HashSet<PeerAddr>>,
     stats_tracker: StatsTracker,
+    // Aggregate of the per-repository message counters.
+    message_counters: Arc<MessageCounters>,
+    // Default bandwidth limits of all repositories.
+    bandwidth_limits: Arc<BandwidthLimits>,
+    // Keep-alive settings for new connections.
+    keep_alive: BlockingMutex<KeepAliveConfig>,
+    // Backoff parameters of peer reconnections.
+    reconnect_backoff: BlockingMutex<ReconnectBackoff>,
+    // Max time spent retrying to connect to a peer, per peer source.
+    peer_max_retry_elapsed: BlockingMutex<BTreeMap<PeerSource, Duration>>,
+    // Which transport to keep when a peer is connected over both.
+    transport_preference: BlockingMutex<TransportPreference>,
+    // Name of this replica advertised to the peers (sanitized, empty if not set).
+    device_name: BlockingMutex<String>,
+    // Addresses passed to the last `bind`, before resolving them to the bind interface.
+    bind_addrs: BlockingMutex<Vec<PeerAddr>>,
+    // Interface to restrict the network to and the task rebinding on its address changes.
+    bind_interface: BlockingMutex<Option<(String, ScopedAbortHandle)>>,
+    // Runtime ids of the peers that are not allowed to connect.
+    blocked_runtime_ids: BlockingMutex<HashSet<PublicRuntimeId>>,
+    // Additional user provided policy deciding which peers are allowed to connect.
+    peer_filter: BlockingMutex<Option<Arc<PeerFilter>>>,
 }
 
 struct State {
@@ -525,6 +1302,11 @@ impl State {
         pex: &PexRepository,
         response_limiter: Arc<Semaphore>,
         byte_counters: Arc<ByteCounters>,
+        message_counters: Arc<MessageCounters>,
+        bandwidth_limits: Arc<BandwidthLimits>,
+        capabilities: Capabilities,
+        upload_enabled: Arc<AtomicBool>,
+        block_storage_enabled: Arc<AtomicBool>,
     ) {
         if let Some(brokers) = &mut self.message_brokers {
             for broker in brokers.values_mut() {
@@ -533,6 +1315,11 @@ impl State {
                     pex,
                     response_limiter.clone(),
                     byte_counters.clone(),
+                    message_counters.clone(),
+                    bandwidth_limits.clone(),
+                    capabilities.clone(),
+                    upload_enabled.clone(),
+                    block_storage_enabled.clone(),
                 )
             }
         }
@@ -544,21 +1331,54 @@ impl Inner {
         self.state.lock().unwrap().message_brokers.is_none()
     }
 
-    async fn bind(self: &Arc<Self>, bind: &[PeerAddr]) {
-        let conn = Connectivity::infer(bind);
+    // Returns the addresses that failed to bind.
+    async fn bind(self: &Arc<Self>, bind: &[PeerAddr]) -> Vec<PeerAddr> {
+        *self.bind_addrs.lock().unwrap() = bind.to_vec();
+        self.rebind().await
+    }
+
+    // Binds to the addresses of the last `bind`, resolved to the bind interface if set. Returns
+    // the addresses that failed to bind.
+    async fn rebind(self: &Arc<Self>) -> Vec<PeerAddr> {
+        let bind = self.bind_addrs.lock().unwrap().clone();
+        let interface = self
+            .bind_interface
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(name, _)| name.clone());
+
+        let (bind, device) = match &interface {
+            Some(interface) => (
+                interface::resolve(interface, &bind),
+                interface::device(interface),
+            ),
+            None => (bind, None),
+        };
+
+        let conn = Connectivity::infer(&bind);
 
-        let bind = StackAddresses::from(bind);
+        let bind = StackAddresses::from(&bind[..]);
 
         // TODO: Would be preferable to only rebind those stacks that actually need rebinding.
-        if !self.gateway.addresses().any_stack_needs_rebind(&bind) {
-            return;
+        if !self.gateway.addresses().any_stack_needs_rebind(&bind)
+            && self.gateway.interface().as_deref() == device
+        {
+            return Vec::new();
         }
 
+        // Our addresses might have changed, and the old ones might now belong to other peers.
+        self.our_addresses.lock().unwrap().clear();
+
         // Gateway
-        let side_channel_makers = self.gateway.bind(&bind).instrument(self.span.clone()).await;
+        let (side_channel_maker_v4, side_channel_maker_v6, failed) = self
+            .gateway
+            .bind(&bind, device)
+            .instrument(self.span.clone())
+            .await;
 
         let (side_channel_maker_v4, side_channel_maker_v6) = match conn {
-            Connectivity::Full => side_channel_makers,
+            Connectivity::Full => (side_channel_maker_v4, side_channel_maker_v6),
             Connectivity::LocalOnly | Connectivity::Disabled => (None, None),
         };
 
@@ -612,6 +1432,8 @@ impl Inner {
         if matches!(conn, Connectivity::LocalOnly | Connectivity::Disabled) {
             self.disconnect_all().await;
         }
+
+        failed
     }
 
     // Disconnect from all currently connected peers, regardless of their source.
@@ -628,24 +1450,23 @@ impl Inner {
 
     fn spawn_local_discovery(self: &Arc<Self>) -> Option<AbortHandle> {
         let addrs = self.gateway.listener_local_addrs();
-        let tcp_port = addrs
-            .iter()
-            .find(|addr| matches!(addr, PeerAddr::Tcp(SocketAddr::V4(_))))
-            .map(|addr| PeerPort::Tcp(addr.port()));
-        let quic_port = addrs
-            .iter()
-            .find(|addr| matches!(addr, PeerAddr::Quic(SocketAddr::V4(_))))
-            .map(|addr| PeerPort::Quic(addr.port()));
+        let tcp_listeners = addrs.iter().filter_map(|addr| match addr {
+            PeerAddr::Tcp(SocketAddr::V4(addr)) => Some((*addr.ip(), PeerPort::Tcp(addr.port()))),
+            _ => None,
+        });
+        let quic_listeners = addrs.iter().filter_map(|addr| match addr {
+            PeerAddr::Quic(SocketAddr::V4(addr)) => Some((*addr.ip(), PeerPort::Quic(addr.port()))),
+            _ => None,
+        });
 
         // Arbitrary order of preference.
-        // TODO: Should we support all available?
-        let port = tcp_port.or(quic_port);
+        let listeners: Vec<_> = tcp_listeners.chain(quic_listeners).collect();
 
-        if let Some(port) = port {
+        if !listeners.is_empty() {
             Some(
                 self.spawn(
                     self.clone()
-                        .run_local_discovery(port)
+                        .run_local_discovery(listeners)
                         .instrument(self.span.clone()),
                 ),
             )
@@ -655,9 +1476,16 @@ impl Inner {
         }
     }
 
-    async fn run_local_discovery(self: Arc<Self>, listener_port: PeerPort) {
+    async fn run_local_discovery(self: Arc<Self>, listeners: Vec<(Ipv4Addr, PeerPort)>) {
+        let bind_interface = self
+            .bind_interface
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|(name, _)| name.clone());
         let mut discovery = LocalDiscovery::new(
-            listener_port,
+            listeners,
+            bind_interface,
             self.main_monitor.make_child("LocalDiscovery"),
         );
 
@@ -716,6 +1544,54 @@ impl Inner {
         );
     }
 
+    // Rebinds whenever the addresses of any interface change, to pick up the current addresses of
+    // the bind interface. Rebinding is a no-op if the resolved addresses stay the same.
+    async fn watch_bind_interface(self: Arc<Self>) {
+        let mut watcher = match IfWatcher::new() {
+            Ok(watcher) => watcher,
+            Err(error) => {
+                tracing::error!(?error, "Failed to initialize network interface watcher");
+                return;
+            }
+        };
+
+        while let Some(event) = watcher.next().await {
+            if let Err(error) = event {
+                tracing::error!(?error, "Failed to poll network interface watcher");
+                break;
+            }
+
+            self.rebind().await;
+        }
+    }
+
+    async fn run_user_provided_host(self: Arc<Self>, host: PeerHost, seen_peers: SeenPeers) {
+        loop {
+            seen_peers.start_new_round();
+
+            match tokio::time::timeout(HOST_RESOLVE_TIMEOUT, host.resolve()).await {
+                Ok(Ok(addrs)) => {
+                    for addr in addrs {
+                        if let Some(peer) = seen_peers.insert(addr) {
+                            self.spawn(
+                                self.clone()
+                                    .handle_peer_found(peer, PeerSource::UserProvided),
+                            );
+                        }
+                    }
+                }
+                Ok(Err(error)) => {
+                    tracing::debug!(%host, ?error, "Failed to resolve user provided host");
+                }
+                Err(_) => {
+                    tracing::debug!(%host, "Timeout resolving user provided host");
+                }
+            }
+
+            tokio::time::sleep(HOST_RESOLVE_INTERVAL).await;
+        }
+    }
+
     async fn handle_incoming_connections(
         self: Arc<Self>,
         mut rx: mpsc::Receiver<(raw::Stream, PeerAddr)>,
@@ -745,16 +1621,18 @@ impl Inner {
                 ReserveResult::Occupied(_, _their_source, permit_id) => {
                     tracing::debug!(?addr, ?permit_id, "dropping accepted duplicate connection");
                 }
+                ReserveResult::Full => {
+                    tracing::debug!(
+                        ?addr,
+                        "dropping accepted connection - connection limit reached"
+                    );
+                }
             }
         }
     }
 
     async fn handle_peer_found(self: Arc<Self>, peer: SeenPeer, source: PeerSource) {
-        let mut backoff = ExponentialBackoffBuilder::new()
-            .with_initial_interval(Duration::from_millis(100))
-            .with_max_interval(Duration::from_secs(8))
-            .with_max_elapsed_time(None)
-            .build();
+        let mut backoff = self.reconnect_backoff.lock().unwrap().build();
 
         let mut next_sleep = None;
 
@@ -807,20 +1685,48 @@ impl Inner {
                     on_release.await;
                     continue;
                 }
+                ReserveResult::Full => {
+                    monitor.mark_as_awaiting_permit();
+                    tracing::debug!(
+                        parent: monitor.span(),
+                        "Connection limit reached - awaiting free slot"
+                    );
+
+                    self.connections.wait_for_free_slot().await;
+                    continue;
+                }
             };
 
             permit.mark_as_connecting();
             monitor.mark_as_connecting(permit.id());
             tracing::trace!(parent: monitor.span(), "Connecting");
 
+            let max_retry_elapsed = self
+                .peer_max_retry_elapsed
+                .lock()
+                .unwrap()
+                .get(&source)
+                .copied();
+
             let socket = match self
                 .gateway
-                .connect_with_retries(&peer, source)
+                .connect_with_retries(&peer, source, max_retry_elapsed)
                 .instrument(monitor.span().clone())
                 .await
             {
-                Some(socket) => socket,
-                None => break,
+                ConnectResult::Connected(socket) => socket,
+                ConnectResult::Discarded => break,
+                ConnectResult::GaveUp => {
+                    tracing::debug!(parent: monitor.span(), "Peer is dead - giving up");
+
+                    permit.mark_as_dead();
+
+                    if source == PeerSource::UserProvided {
+                        self.user_provided_peers.remove(&addr);
+                    }
+
+                    break;
+                }
             };
 
             if !self.handle_connection(socket, permit, &monitor).await {
@@ -841,23 +1747,46 @@ impl Inner {
         permit.mark_as_handshaking();
         monitor.mark_as_handshaking();
 
-        let handshake_result = perform_handshake(&mut stream, VERSION, &self.this_runtime_id).await;
+        let this_device_name = self.device_name.lock().unwrap().clone();
+        let this_transport_preference = *self.transport_preference.lock().unwrap();
+        let handshake_result = perform_handshake(
+            &mut stream,
+            VERSION,
+            &self.this_runtime_id,
+            &this_device_name,
+            this_transport_preference,
+            // Report the newer peer right away because it might not support our version anymore
+            // and close the connection before telling us its runtime id.
+            |that_version| self.on_protocol_mismatch(that_version, permit.addr(), None),
+        )
+        .await;
 
         if let Err(error) = &handshake_result {
             tracing::debug!(parent: monitor.span(), ?error, "Handshake failed");
         }
 
-        let that_runtime_id = match handshake_result {
-            Ok(writer_id) => writer_id,
-            Err(HandshakeError::ProtocolVersionMismatch(their_version)) => {
-                self.on_protocol_mismatch(their_version);
-                return false;
-            }
-            Err(HandshakeError::Timeout | HandshakeError::BadMagic | HandshakeError::Fatal(_)) => {
-                return false
-            }
+        let Handshake {
+            that_runtime_id,
+            that_version,
+            negotiated_version,
+            that_device_name,
+            that_transport_preference,
+        } = match handshake_result {
+            Ok(handshake) => handshake,
+            Err(
+                HandshakeError::ProtocolVersionTooLow(_)
+                | HandshakeError::Timeout
+                | HandshakeError::BadMagic
+                | HandshakeError::Fatal(_),
+            ) => return false,
         };
 
+        // The peer is newer than us but still supports our version. The mismatch has already been
+        // reported during the handshake, now complete it with the peer's runtime id.
+        if that_version > VERSION {
+            self.on_protocol_mismatch(that_version, permit.addr(), Some(that_runtime_id));
+        }
+
         // prevent self-connections.
         if that_runtime_id == self.this_runtime_id.public() {
             tracing::debug!(parent: monitor.span(), "Connection from self, discarding");
@@ -865,11 +1794,43 @@ impl Inner {
             return false;
         }
 
-        permit.mark_as_active(that_runtime_id);
+        if !self.is_peer_allowed(&that_runtime_id, &permit.addr()) {
+            tracing::debug!(parent: monitor.span(), "Peer not allowed, discarding");
+            return false;
+        }
+
+        let transport_preference = this_transport_preference.negotiate(
+            that_transport_preference,
+            self.this_runtime_id.public() < that_runtime_id,
+        );
+
+        if !transport_preference.is_preferred(&permit.addr()) {
+            let preferred = self.connections.active_released(&that_runtime_id, |addr| {
+                transport_preference.is_preferred(addr)
+            });
+
+            if !preferred.is_empty() {
+                tracing::debug!(
+                    parent: monitor.span(),
+                    "Already connected over the preferred transport, discarding"
+                );
+
+                // Wait until the preferred connections are gone before reconnecting.
+                drop(stream);
+                drop(permit);
+                future::join_all(preferred).await;
+
+                return true;
+            }
+        }
+
+        permit.mark_as_active(that_runtime_id, that_device_name);
         monitor.mark_as_active(that_runtime_id);
         tracing::info!(parent: monitor.span(), "Connected");
 
         let released = permit.released();
+        let addr = permit.addr();
+        let source = permit.source();
 
         {
             let mut state = self.state.lock().unwrap();
@@ -889,6 +1850,8 @@ impl Inner {
                         self.pex_discovery.new_peer(),
                         self.peers_monitor
                             .make_child(format!("{:?}", that_runtime_id.as_public_key())),
+                        *self.keep_alive.lock().unwrap(),
+                        negotiated_version,
                     )
                 });
 
@@ -896,12 +1859,9 @@ impl Inner {
                 // lookup but make sure we correctly handle edge cases, for example, when we have
                 // more than one repository shared with the peer.
                 for (_, holder) in &state.registry {
-                    broker.create_link(
-                        holder.vault.clone(),
-                        &holder.pex,
-                        holder.response_limiter.clone(),
-                        holder.stats_tracker.bytes.clone(),
-                    );
+                    if holder.sync_enabled {
+                        holder.create_link(&mut broker);
+                    }
                 }
 
                 broker
@@ -909,11 +1869,30 @@ impl Inner {
 
             let stream = Instrumented::new(stream, self.stats_tracker.bytes.clone());
             broker.add_connection(stream, permit);
+
+            if transport_preference != TransportPreference::Either
+                && transport_preference.is_preferred(&addr)
+            {
+                // Replace the connections over the non-preferred transport with this one.
+                broker.remove_connections(move |addr| !transport_preference.is_preferred(addr));
+            }
         }
 
+        self.on_peer_event_tx
+            .send(PeerEvent {
+                addr,
+                runtime_id: that_runtime_id,
+                source,
+                kind: PeerEventKind::Connected,
+            })
+            .ok();
+
         let _remover = MessageBrokerEntryGuard {
             state: &self.state,
+            on_peer_event_tx: &self.on_peer_event_tx,
             that_runtime_id,
+            addr,
+            source,
             monitor,
         };
 
@@ -922,17 +1901,59 @@ impl Inner {
         true
     }
 
-    fn on_protocol_mismatch(&self, their_version: Version) {
+    fn is_peer_allowed(&self, runtime_id: &PublicRuntimeId, addr: &PeerAddr) -> bool {
+        if self
+            .blocked_runtime_ids
+            .lock()
+            .unwrap()
+            .contains(runtime_id)
+        {
+            return false;
+        }
+
+        // Clone the filter so it's not called with the lock held.
+        let filter = self.peer_filter.lock().unwrap().clone();
+
+        filter
+            .map(|filter| filter(runtime_id, addr))
+            .unwrap_or(true)
+    }
+
+    fn on_protocol_mismatch(
+        &self,
+        their_version: Version,
+        peer_addr: PeerAddr,
+        runtime_id: Option<PublicRuntimeId>,
+    ) {
         // We know that `their_version` is higher than our version because otherwise this function
         // wouldn't get called, but let's double check.
         assert!(VERSION < their_version);
 
         let mut highest = self.highest_seen_protocol_version.lock().unwrap();
+        let mut last = self.last_protocol_mismatch.lock().unwrap();
+
+        let mismatch = ProtocolMismatch {
+            our_version: VERSION.into(),
+            their_version: their_version.into(),
+            peer_addr,
+            runtime_id,
+        };
 
         if *highest < their_version {
             *highest = their_version;
-            self.on_protocol_mismatch_tx.send(()).unwrap_or(());
+        } else if runtime_id.is_none()
+            || *last
+                != Some(ProtocolMismatch {
+                    runtime_id: None,
+                    ..mismatch
+                })
+        {
+            // Already reported, unless this completes the last mismatch with the runtime id.
+            return;
         }
+
+        *last = Some(mismatch);
+        self.on_protocol_mismatch_tx.send(mismatch).unwrap_or(());
     }
 
     fn spawn<Fut>(&self, f: Fut) -> AbortHandle
@@ -952,12 +1973,18 @@ impl Inner {
 
 //------------------------------------------------------------------------------
 
-// Exchange runtime ids with the peer. Returns their (verified) runtime id.
+// Exchange runtime ids, protocol versions and device names with the peer. Returns their (verified)
+// runtime id, the protocol version they advertised, the negotiated version both sides are going to
+// speak and their device name, if any. `on_newer_version` is called as soon as the peer advertises a
+// version higher than ours, before anything else is exchanged.
 async fn perform_handshake(
     stream: &mut raw::Stream,
     this_version: Version,
     this_runtime_id: &SecretRuntimeId,
-) -> Result<PublicRuntimeId, HandshakeError> {
+    this_device_name: &str,
+    this_transport_preference: TransportPreference,
+    on_newer_version: impl FnOnce(Version) + Send,
+) -> Result<Handshake, HandshakeError> {
     let result = tokio::time::timeout(std::time::Duration::from_secs(5), async move {
         stream.write_all(MAGIC).await?;
 
@@ -971,13 +1998,36 @@ async fn perform_handshake(
         }
 
         let that_version = Version::read_from(stream).await?;
+        if that_version < MIN_SUPPORTED_VERSION {
+            return Err(HandshakeError::ProtocolVersionTooLow(that_version));
+        }
+
         if that_version > this_version {
-            return Err(HandshakeError::ProtocolVersionMismatch(that_version));
+            on_newer_version(that_version);
         }
 
         let that_runtime_id = runtime_id::exchange(this_runtime_id, stream).await?;
+        let negotiated_version = this_version.min(that_version);
 
-        Ok(that_runtime_id)
+        let that_device_name = if negotiated_version >= DEVICE_NAME_VERSION {
+            device_name::exchange(this_device_name, stream).await?
+        } else {
+            None
+        };
+
+        let that_transport_preference = if negotiated_version >= TRANSPORT_PREFERENCE_VERSION {
+            Some(transport_preference::exchange(this_transport_preference, stream).await?)
+        } else {
+            None
+        };
+
+        Ok(Handshake {
+            that_runtime_id,
+            that_version,
+            negotiated_version,
+            that_device_name,
+            that_transport_preference,
+        })
     })
     .await;
 
@@ -987,10 +2037,27 @@ async fn perform_handshake(
     }
 }
 
+/// Error returned from [`Network::bind_and_wait`] when some of the addresses failed to bind.
+#[derive(Debug, Error)]
+#[error("failed to bind to {}", .addrs.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+pub struct BindError {
+    /// The addresses that failed to bind.
+    pub addrs: Vec<PeerAddr>,
+}
+
+#[derive(Debug)]
+struct Handshake {
+    that_runtime_id: PublicRuntimeId,
+    that_version: Version,
+    negotiated_version: Version,
+    that_device_name: Option<String>,
+    that_transport_preference: Option<TransportPreference>,
+}
+
 #[derive(Debug, Error)]
 enum HandshakeError {
-    #[error("protocol version mismatch")]
-    ProtocolVersionMismatch(Version),
+    #[error("protocol version too low")]
+    ProtocolVersionTooLow(Version),
     #[error("bad magic")]
     BadMagic,
     #[error("timeout")]
@@ -1002,7 +2069,10 @@ enum HandshakeError {
 // RAII guard which when dropped removes the broker from the network state if it has no connections.
 struct MessageBrokerEntryGuard<'a> {
     state: &'a BlockingMutex<State>,
+    on_peer_event_tx: &'a broadcast::Sender<PeerEvent>,
     that_runtime_id: PublicRuntimeId,
+    addr: PeerAddr,
+    source: PeerSource,
     monitor: &'a ConnectionMonitor,
 }
 
@@ -1018,6 +2088,15 @@ impl Drop for MessageBrokerEntryGuard<'_> {
                 }
             }
         }
+
+        self.on_peer_event_tx
+            .send(PeerEvent {
+                addr: self.addr,
+                runtime_id: self.that_runtime_id,
+                source: self.source,
+                kind: PeerEventKind::Disconnected,
+            })
+            .ok();
     }
 }
 
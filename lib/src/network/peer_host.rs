@@ -0,0 +1,109 @@
+use super::PeerAddr;
+use std::{fmt, io, str::FromStr};
+
+/// Address of a peer given as a host name (or IP address) and port, e.g.
+/// `tcp/peer.example.com:1234`. Unlike `PeerAddr` it needs to be resolved before connecting to the
+/// peer and it can resolve to multiple addresses.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum PeerHost {
+    Tcp(String),
+    Quic(String),
+}
+
+impl PeerHost {
+    /// Returns the `host:port` part of the address.
+    pub fn host(&self) -> &str {
+        match self {
+            Self::Tcp(host) => host,
+            Self::Quic(host) => host,
+        }
+    }
+
+    /// Resolves the host into the peer addresses.
+    pub(super) async fn resolve(&self) -> io::Result<Vec<PeerAddr>> {
+        let addrs = tokio::net::lookup_host(self.host()).await?;
+        let addrs = match self {
+            Self::Tcp(_) => addrs.map(PeerAddr::Tcp).collect(),
+            Self::Quic(_) => addrs.map(PeerAddr::Quic).collect(),
+        };
+
+        Ok(addrs)
+    }
+}
+
+impl FromStr for PeerHost {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((proto, host)) = s.split_once('/') else {
+            return Err(format!(
+                "Could not find '/' delimiter in the address {:?}",
+                s
+            ));
+        };
+
+        match host.rsplit_once(':') {
+            Some((name, port)) if !name.is_empty() && port.parse::<u16>().is_ok() => (),
+            _ => return Err(format!("Failed to parse HOST:PORT {:?}", host)),
+        }
+
+        if proto.eq_ignore_ascii_case("tcp") {
+            Ok(Self::Tcp(host.to_owned()))
+        } else if proto.eq_ignore_ascii_case("quic") {
+            Ok(Self::Quic(host.to_owned()))
+        } else {
+            Err(format!("Unrecognized protocol {:?} in {:?}", proto, s))
+        }
+    }
+}
+
+impl fmt::Display for PeerHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(host) => write!(f, "tcp/{}", host),
+            Self::Quic(host) => write!(f, "quic/{}", host),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for (orig, expected) in [
+            (
+                PeerHost::Tcp("example.com:1234".into()),
+                "tcp/example.com:1234",
+            ),
+            (
+                PeerHost::Quic("example.com:1234".into()),
+                "quic/example.com:1234",
+            ),
+            (PeerHost::Tcp("[::1]:1234".into()), "tcp/[::1]:1234"),
+        ] {
+            assert_eq!(orig.to_string(), expected);
+            assert_eq!(expected.parse::<PeerHost>().unwrap(), orig);
+        }
+
+        for invalid in [
+            "example.com:1234",
+            "tcp/example.com",
+            "tcp/:1234",
+            "tcp/example.com:port",
+            "udp/example.com:1234",
+        ] {
+            assert!(invalid.parse::<PeerHost>().is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve() {
+        let host = PeerHost::Quic("127.0.0.1:1234".into());
+        assert_eq!(
+            host.resolve().await.unwrap(),
+            [PeerAddr::Quic(([127, 0, 0, 1], 1234).into())]
+        );
+    }
+}
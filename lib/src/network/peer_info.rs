@@ -1,5 +1,6 @@
 use super::{peer_addr::PeerAddr, peer_source::PeerSource, peer_state::PeerState, stats::Stats};
 use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::time::{Duration, SystemTime};
 
 /// Information about a peer.
 #[derive(Eq, PartialEq, Serialize, Deserialize, Debug)]
@@ -9,6 +10,19 @@ pub struct PeerInfo {
     pub source: PeerSource,
     pub state: PeerState,
     pub stats: Stats,
+    // NOTE: The following fields were added later. Keep them at the end and defaulted so that the
+    // serialized form stays compatible with the older one.
+    /// When the connection became active or, if it's not active yet, when the connection attempt
+    /// started.
+    #[serde(with = "as_millis_since_epoch", default = "unix_epoch")]
+    pub connected_since: SystemTime,
+    /// Round-trip time measured by the keep-alive pings. `None` until the first ping is answered
+    /// or if the peer doesn't answer pings (older versions don't).
+    #[serde(with = "as_opt_micros", default)]
+    pub rtt: Option<Duration>,
+    /// Name the peer advertised for itself, if any. Not authenticated, for display only.
+    #[serde(default)]
+    pub display_name: Option<String>,
 }
 
 mod as_str {
@@ -28,3 +42,49 @@ mod as_str {
         <&str>::deserialize(d)?.parse().map_err(D::Error::custom)
     }
 }
+
+mod as_millis_since_epoch {
+    use super::*;
+    use crate::time;
+    use serde::ser::Error as _;
+
+    pub fn serialize<S>(value: &SystemTime, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        time::to_millis_since_epoch(*value)
+            .map_err(S::Error::custom)?
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(time::from_millis_since_epoch(u64::deserialize(d)?))
+    }
+}
+
+mod as_opt_micros {
+    use super::*;
+
+    pub fn serialize<S>(value: &Option<Duration>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value
+            .map(|value| u64::try_from(value.as_micros()).unwrap_or(u64::MAX))
+            .serialize(s)
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<u64>::deserialize(d)?.map(Duration::from_micros))
+    }
+}
+
+fn unix_epoch() -> SystemTime {
+    SystemTime::UNIX_EPOCH
+}
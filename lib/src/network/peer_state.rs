@@ -17,6 +17,7 @@ pub enum PeerState {
         id: PublicRuntimeId,
         since: SystemTime,
     },
+    Dead,
 }
 
 impl Serialize for PeerState {
@@ -37,6 +38,7 @@ impl Serialize for PeerState {
                 )?;
                 t.end()
             }
+            Self::Dead => PeerStateKind::Dead.serialize(s),
         }
     }
 }
@@ -54,10 +56,11 @@ impl<'de> Deserialize<'de> for PeerState {
             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
                 write!(
                     f,
-                    "one of {}, {}, {} or a tuple of {}, a byte array and a timestamp",
+                    "one of {}, {}, {}, {} or a tuple of {}, a byte array and a timestamp",
                     u8::from(PeerStateKind::Known),
                     u8::from(PeerStateKind::Connecting),
                     u8::from(PeerStateKind::Handshaking),
+                    u8::from(PeerStateKind::Dead),
                     u8::from(PeerStateKind::Active),
                 )
             }
@@ -70,6 +73,7 @@ impl<'de> Deserialize<'de> for PeerState {
                     Ok(PeerStateKind::Known) => Ok(PeerState::Known),
                     Ok(PeerStateKind::Connecting) => Ok(PeerState::Connecting),
                     Ok(PeerStateKind::Handshaking) => Ok(PeerState::Handshaking),
+                    Ok(PeerStateKind::Dead) => Ok(PeerState::Dead),
                     Ok(PeerStateKind::Active) => {
                         Err(E::invalid_value(Unexpected::Unsigned(v.into()), &self))
                     }
@@ -105,6 +109,7 @@ impl<'de> Deserialize<'de> for PeerState {
                     Some(PeerStateKind::Known) => Ok(PeerState::Known),
                     Some(PeerStateKind::Connecting) => Ok(PeerState::Connecting),
                     Some(PeerStateKind::Handshaking) => Ok(PeerState::Handshaking),
+                    Some(PeerStateKind::Dead) => Ok(PeerState::Dead),
                     Some(PeerStateKind::Active) => {
                         let Some(id) = seq.next_element()? else {
                             return Err(<A::Error as de::Error>::invalid_length(1, &self));
@@ -144,6 +149,9 @@ pub enum PeerStateKind {
     Handshaking,
     /// The peer connection is active.
     Active,
+    /// We gave up connecting to the peer because it didn't respond for longer than the max retry
+    /// time (see `Network::set_peer_max_retry_elapsed`).
+    Dead,
 }
 
 #[cfg(test)]
@@ -180,6 +188,7 @@ mod tests {
                 Token::TupleEnd,
             ],
         );
+        assert_tokens(&PeerState::Dead, &[Token::U8(PeerStateKind::Dead.into())]);
     }
 
     #[test]
@@ -188,6 +197,7 @@ mod tests {
             PeerState::Known,
             PeerState::Connecting,
             PeerState::Handshaking,
+            PeerState::Dead,
             PeerState::Active {
                 id: SecretRuntimeId::random().public(),
                 // The timestamp is serialized as the number of milliseconds since the epoch so we
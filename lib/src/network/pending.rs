@@ -10,6 +10,7 @@ use crate::{
     protocol::{Block, BlockId, InnerNodes, LeafNodes, MultiBlockPresence, UntrustedProof},
     repository::RepositoryMonitor,
     sync::delay_map::DelayMap,
+    transfer_tracker::{TransferGuard, TransferTracker},
 };
 use deadlock::BlockingMutex;
 use scoped_task::ScopedJoinHandle;
@@ -99,6 +100,7 @@ pub(super) struct PendingRequests {
     monitor: Arc<RepositoryMonitor>,
     index: PendingIndexRequests,
     block: Arc<PendingBlockRequests>,
+    transfer_tracker: TransferTracker,
     // This is to ensure the `run_expiration_tracker` task is destroyed with PendingRequests (as
     // opposed to the task being destroyed "sometime after"). This is important because the task
     // holds an Arc to the RepositoryMonitor which must be destroyed prior to reimporting its
@@ -107,7 +109,7 @@ pub(super) struct PendingRequests {
 }
 
 impl PendingRequests {
-    pub fn new(monitor: Arc<RepositoryMonitor>) -> Self {
+    pub fn new(monitor: Arc<RepositoryMonitor>, transfer_tracker: TransferTracker) -> Self {
         let index = PendingIndexRequests::default();
         let block = Arc::new(PendingBlockRequests::default());
 
@@ -115,6 +117,7 @@ impl PendingRequests {
             monitor: monitor.clone(),
             index,
             block: block.clone(),
+            transfer_tracker,
             _expiration_tracker_task: scoped_task::spawn(run_expiration_tracker(monitor, block)),
         }
     }
@@ -133,7 +136,7 @@ impl PendingRequests {
                 let block_promise = block_offer.accept()?;
                 let block_id = *block_promise.block_id();
                 self.block
-                    .try_insert(block_promise)
+                    .try_insert(block_promise, &self.transfer_tracker)
                     .then(|| Request::Block(block_id, debug.send()))?
             }
         };
@@ -197,7 +200,18 @@ impl PendingRequests {
         };
 
         if let Some((timestamp, kind)) = status {
-            self.monitor.request_latency.record(timestamp.elapsed());
+            let elapsed = timestamp.elapsed();
+
+            tracing::trace!(
+                kind = match kind {
+                    ResponseKind::Index => "index",
+                    ResponseKind::Block => "block",
+                },
+                latency_us = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX),
+                "Response received"
+            );
+
+            self.monitor.request_latency.record(elapsed);
 
             match kind {
                 ResponseKind::Index => self.monitor.index_requests_inflight.decrement(1.0),
@@ -243,18 +257,21 @@ impl PendingIndexRequests {
 
 #[derive(Default)]
 struct PendingBlockRequests {
-    map: BlockingMutex<DelayMap<BlockId, (Instant, BlockPromise)>>,
+    map: BlockingMutex<DelayMap<BlockId, (Instant, BlockPromise, TransferGuard)>>,
     // Notify when item is inserted into previously empty map. This restarts the expiration tracker
     // task.
     notify: Notify,
 }
 
 impl PendingBlockRequests {
-    fn try_insert(&self, block_promise: BlockPromise) -> bool {
+    fn try_insert(&self, block_promise: BlockPromise, transfer_tracker: &TransferTracker) -> bool {
         let mut map = self.map.lock().unwrap();
 
         if let Some(entry) = map.try_insert(*block_promise.block_id()) {
-            entry.insert((Instant::now(), block_promise), REQUEST_TIMEOUT);
+            entry.insert(
+                (Instant::now(), block_promise, transfer_tracker.begin()),
+                REQUEST_TIMEOUT,
+            );
 
             if map.len() == 1 {
                 drop(map);
@@ -267,8 +284,13 @@ impl PendingBlockRequests {
         }
     }
 
+    // Note: this also drops the transfer guard of the request.
     fn remove(&self, block_id: &BlockId) -> Option<(Instant, BlockPromise)> {
-        self.map.lock().unwrap().remove(block_id)
+        self.map
+            .lock()
+            .unwrap()
+            .remove(block_id)
+            .map(|(timestamp, block_promise, _)| (timestamp, block_promise))
     }
 }
 
@@ -296,7 +318,9 @@ async fn run_expiration_tracker(
 // Wait for the next expired request. This does not block the map so it can be inserted / removed
 // from while this is being awaited.
 // Returns `true` if a request expired and `false` if there are no more pending requests.
-async fn expired(map: &BlockingMutex<DelayMap<BlockId, (Instant, BlockPromise)>>) -> bool {
+async fn expired(
+    map: &BlockingMutex<DelayMap<BlockId, (Instant, BlockPromise, TransferGuard)>>,
+) -> bool {
     future::poll_fn(|cx| Poll::Ready(ready!(map.lock().unwrap().poll_expired(cx))))
         .await
         .is_some()
@@ -4,11 +4,22 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 // First string in a handshake, helps with weeding out connections with completely different
 // protocols on the other end.
 pub(super) const MAGIC: &[u8; 7] = b"OUISYNC";
-pub(super) const VERSION: Version = Version(12);
+pub(super) const VERSION: Version = Version(15);
+// Lowest protocol version we can still talk to. When two peers with different versions connect,
+// they both speak the lower of the two versions as long as it's not lower than this.
+//
+// When bumping `VERSION` in a backwards compatible way, keep this one unchanged and make the
+// message handling (`Client`/`Server`) branch on the negotiated version where the behaviour
+// differs. Bump this only when dropping support for the old versions.
+pub(super) const MIN_SUPPORTED_VERSION: Version = Version(13);
+// First version that exchanges device names during the handshake.
+pub(super) const DEVICE_NAME_VERSION: Version = Version(14);
+// First version that exchanges transport preferences during the handshake.
+pub(super) const TRANSPORT_PREFERENCE_VERSION: Version = Version(15);
 
 /// Protocol version
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
-pub(super) struct Version(u64);
+pub(super) struct Version(pub(super) u64);
 
 impl Version {
     pub async fn read_from<R>(io: &mut R) -> io::Result<Self>
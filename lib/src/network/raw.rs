@@ -8,10 +8,22 @@ use std::{
     task::{Context, Poll},
 };
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+#[cfg(any(test, feature = "test-utils"))]
+use {
+    super::peer_addr::PeerAddr,
+    std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::atomic::{AtomicU16, Ordering},
+    },
+    tokio::io::{DuplexStream, ReadHalf, WriteHalf},
+};
 
 pub enum Stream {
     Tcp(TcpStream),
     Quic(quic::Connection),
+    /// In-memory stream for tests. Connects two networks in the same process without sockets.
+    #[cfg(any(test, feature = "test-utils"))]
+    Memory(DuplexStream),
 }
 
 impl Stream {
@@ -25,10 +37,26 @@ impl Stream {
                 let (rx, tx) = con.into_split();
                 (OwnedReadHalf::Quic(rx), OwnedWriteHalf::Quic(tx))
             }
+            #[cfg(any(test, feature = "test-utils"))]
+            Stream::Memory(con) => {
+                let (rx, tx) = tokio::io::split(con);
+                (OwnedReadHalf::Memory(rx), OwnedWriteHalf::Memory(tx))
+            }
         }
     }
 }
 
+/// Returns a placeholder address for a new in-memory connection. Connections are tracked by their
+/// address so every in-memory connection needs a distinct one. The unspecified IP guarantees it
+/// never clashes with the address of a real peer.
+#[cfg(any(test, feature = "test-utils"))]
+pub fn next_memory_addr() -> PeerAddr {
+    static NEXT_PORT: AtomicU16 = AtomicU16::new(1);
+
+    let port = NEXT_PORT.fetch_add(1, Ordering::Relaxed);
+    PeerAddr::Tcp(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port))
+}
+
 impl AsyncRead for Stream {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -38,6 +66,8 @@ impl AsyncRead for Stream {
         match self.get_mut() {
             Stream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
             Stream::Quic(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(any(test, feature = "test-utils"))]
+            Stream::Memory(s) => Pin::new(s).poll_read(cx, buf),
         }
     }
 }
@@ -51,6 +81,8 @@ impl AsyncWrite for Stream {
         match self.get_mut() {
             Stream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
             Stream::Quic(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(any(test, feature = "test-utils"))]
+            Stream::Memory(s) => Pin::new(s).poll_write(cx, buf),
         }
     }
 
@@ -62,6 +94,8 @@ impl AsyncWrite for Stream {
         match self.get_mut() {
             Stream::Tcp(s) => Pin::new(s).poll_write_vectored(cx, bufs),
             Stream::Quic(s) => Pin::new(s).poll_write_vectored(cx, bufs),
+            #[cfg(any(test, feature = "test-utils"))]
+            Stream::Memory(s) => Pin::new(s).poll_write_vectored(cx, bufs),
         }
     }
 
@@ -69,6 +103,8 @@ impl AsyncWrite for Stream {
         match self {
             Stream::Tcp(s) => s.is_write_vectored(),
             Stream::Quic(s) => s.is_write_vectored(),
+            #[cfg(any(test, feature = "test-utils"))]
+            Stream::Memory(s) => s.is_write_vectored(),
         }
     }
 
@@ -76,6 +112,8 @@ impl AsyncWrite for Stream {
         match self.get_mut() {
             Stream::Tcp(s) => Pin::new(s).poll_flush(cx),
             Stream::Quic(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(any(test, feature = "test-utils"))]
+            Stream::Memory(s) => Pin::new(s).poll_flush(cx),
         }
     }
 
@@ -83,6 +121,8 @@ impl AsyncWrite for Stream {
         match self.get_mut() {
             Stream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
             Stream::Quic(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(any(test, feature = "test-utils"))]
+            Stream::Memory(s) => Pin::new(s).poll_shutdown(cx),
         }
     }
 }
@@ -90,6 +130,8 @@ impl AsyncWrite for Stream {
 pub enum OwnedReadHalf {
     Tcp(tcp::OwnedReadHalf),
     Quic(quic::OwnedReadHalf),
+    #[cfg(any(test, feature = "test-utils"))]
+    Memory(ReadHalf<DuplexStream>),
 }
 
 impl AsyncRead for OwnedReadHalf {
@@ -101,6 +143,8 @@ impl AsyncRead for OwnedReadHalf {
         match self.get_mut() {
             OwnedReadHalf::Tcp(rx) => Pin::new(rx).poll_read(cx, buf),
             OwnedReadHalf::Quic(rx) => Pin::new(rx).poll_read(cx, buf),
+            #[cfg(any(test, feature = "test-utils"))]
+            OwnedReadHalf::Memory(rx) => Pin::new(rx).poll_read(cx, buf),
         }
     }
 }
@@ -108,6 +152,8 @@ impl AsyncRead for OwnedReadHalf {
 pub enum OwnedWriteHalf {
     Tcp(tcp::OwnedWriteHalf),
     Quic(quic::OwnedWriteHalf),
+    #[cfg(any(test, feature = "test-utils"))]
+    Memory(WriteHalf<DuplexStream>),
 }
 
 impl AsyncWrite for OwnedWriteHalf {
@@ -119,6 +165,8 @@ impl AsyncWrite for OwnedWriteHalf {
         match self.get_mut() {
             Self::Tcp(s) => Pin::new(s).poll_write(cx, buf),
             Self::Quic(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(any(test, feature = "test-utils"))]
+            Self::Memory(s) => Pin::new(s).poll_write(cx, buf),
         }
     }
 
@@ -130,6 +178,8 @@ impl AsyncWrite for OwnedWriteHalf {
         match self.get_mut() {
             Self::Tcp(s) => Pin::new(s).poll_write_vectored(cx, bufs),
             Self::Quic(s) => Pin::new(s).poll_write_vectored(cx, bufs),
+            #[cfg(any(test, feature = "test-utils"))]
+            Self::Memory(s) => Pin::new(s).poll_write_vectored(cx, bufs),
         }
     }
 
@@ -137,6 +187,8 @@ impl AsyncWrite for OwnedWriteHalf {
         match self {
             Self::Tcp(s) => s.is_write_vectored(),
             Self::Quic(s) => s.is_write_vectored(),
+            #[cfg(any(test, feature = "test-utils"))]
+            Self::Memory(s) => s.is_write_vectored(),
         }
     }
 
@@ -144,6 +196,8 @@ impl AsyncWrite for OwnedWriteHalf {
         match self.get_mut() {
             Self::Tcp(s) => Pin::new(s).poll_flush(cx),
             Self::Quic(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(any(test, feature = "test-utils"))]
+            Self::Memory(s) => Pin::new(s).poll_flush(cx),
         }
     }
 
@@ -151,6 +205,8 @@ impl AsyncWrite for OwnedWriteHalf {
         match self.get_mut() {
             Self::Tcp(s) => Pin::new(s).poll_shutdown(cx),
             Self::Quic(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(any(test, feature = "test-utils"))]
+            Self::Memory(s) => Pin::new(s).poll_shutdown(cx),
         }
     }
 }
@@ -73,6 +73,12 @@ impl PublicRuntimeId {
     }
 }
 
+impl From<PublicKey> for PublicRuntimeId {
+    fn from(public: PublicKey) -> Self {
+        Self { public }
+    }
+}
+
 impl AsRef<[u8]> for PublicRuntimeId {
     fn as_ref(&self) -> &[u8] {
         self.public.as_ref()
@@ -12,7 +12,13 @@ use crate::{
     store,
 };
 use futures_util::TryStreamExt;
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::{
     select,
     sync::{
@@ -21,7 +27,7 @@ use tokio::{
     },
     time::{self, Instant},
 };
-use tracing::instrument;
+use tracing::{instrument, Instrument};
 
 pub(crate) struct Server {
     inner: Inner,
@@ -35,6 +41,8 @@ impl Server {
         content_tx: mpsc::UnboundedSender<Content>,
         request_rx: mpsc::Receiver<Request>,
         response_limiter: Arc<Semaphore>,
+        upload_enabled: Arc<AtomicBool>,
+        block_storage_enabled: Arc<AtomicBool>,
     ) -> Self {
         let (response_tx, response_rx) = mpsc::channel(1);
 
@@ -44,6 +52,8 @@ impl Server {
                 response_tx,
                 content_tx,
                 response_limiter,
+                upload_enabled,
+                block_storage_enabled,
             },
             request_rx,
             response_rx,
@@ -66,6 +76,10 @@ struct Inner {
     response_tx: mpsc::Sender<Response>,
     content_tx: mpsc::UnboundedSender<Content>,
     response_limiter: Arc<Semaphore>,
+    // If false, block requests are declined (but the index is still served).
+    upload_enabled: Arc<AtomicBool>,
+    // If false, blocks are not stored and so all block requests are declined.
+    block_storage_enabled: Arc<AtomicBool>,
 }
 
 impl Inner {
@@ -94,13 +108,34 @@ impl Inner {
     async fn handle_request(&self, request: Request) -> Result<()> {
         self.vault.monitor.requests_received.increment(1);
 
-        match request {
-            Request::RootNode(public_key, debug) => self.handle_root_node(public_key, debug).await,
-            Request::ChildNodes(hash, disambiguator, debug) => {
-                self.handle_child_nodes(hash, disambiguator, debug).await
+        let span = tracing::trace_span!("request", kind = request_kind(&request));
+        let start = Instant::now();
+
+        let result = async {
+            match request {
+                Request::RootNode(public_key, debug) => {
+                    self.handle_root_node(public_key, debug).await
+                }
+                Request::ChildNodes(hash, disambiguator, debug) => {
+                    self.handle_child_nodes(hash, disambiguator, debug).await
+                }
+                Request::Block(block_id, debug) => self.handle_block(block_id, debug).await,
             }
-            Request::Block(block_id, debug) => self.handle_block(block_id, debug).await,
         }
+        .instrument(span.clone())
+        .await;
+
+        // Time from receiving the request until its response is enqueued (which includes waiting
+        // for the response slot, i.e., for being unchoked).
+        span.in_scope(|| {
+            tracing::trace!(
+                elapsed_us = duration_to_micros(start.elapsed()),
+                ok = result.is_ok(),
+                "Request handled"
+            )
+        });
+
+        result
     }
 
     #[instrument(skip(self, debug), err(Debug))]
@@ -191,6 +226,22 @@ impl Inner {
     #[instrument(skip(self, debug), err(Debug))]
     async fn handle_block(&self, block_id: BlockId, debug: DebugRequest) -> Result<()> {
         let debug = debug.begin_reply();
+
+        if !self.upload_enabled.load(Ordering::Relaxed) {
+            tracing::trace!("upload disabled");
+            self.enqueue_response(Response::BlockError(block_id, debug.send()))
+                .await;
+            return Ok(());
+        }
+
+        if !self.block_storage_enabled.load(Ordering::Relaxed) {
+            tracing::trace!("block storage disabled");
+            self.enqueue_response(Response::BlockError(block_id, debug.send()))
+                .await;
+            return Ok(());
+        }
+
+        let _transfer_guard = self.vault.transfer_tracker.begin();
         let mut content = BlockContent::new();
         let result = self
             .vault
@@ -235,7 +286,9 @@ impl Inner {
                     Payload::BlockReceived(block_id) => {
                         self.handle_block_received_event(block_id).await?;
                     }
-                    Payload::SnapshotRejected(_) | Payload::MaintenanceCompleted => continue,
+                    Payload::SnapshotRejected(_)
+                    | Payload::MaintenanceCompleted
+                    | Payload::TransferStateChanged => continue,
                 },
                 Err(RecvError::Lagged(_)) => self.handle_unknown_event().await?,
                 Err(RecvError::Closed) => return Ok(()),
@@ -257,6 +310,11 @@ impl Inner {
     }
 
     async fn handle_block_received_event(&self, block_id: BlockId) -> Result<()> {
+        // Don't offer blocks we are not going to serve.
+        if !self.upload_enabled.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
         self.enqueue_response(Response::BlockOffer(block_id, DebugResponse::unsolicited()))
             .await;
         Ok(())
@@ -366,3 +424,15 @@ impl Inner {
         }
     }
 }
+
+fn request_kind(request: &Request) -> &'static str {
+    match request {
+        Request::RootNode(..) => "root_node",
+        Request::ChildNodes(..) => "child_nodes",
+        Request::Block(..) => "block",
+    }
+}
+
+fn duration_to_micros(duration: Duration) -> u64 {
+    u64::try_from(duration.as_micros()).unwrap_or(u64::MAX)
+}
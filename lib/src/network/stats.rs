@@ -1,4 +1,7 @@
-use super::raw;
+use super::{
+    message::{Content, Request, Response},
+    raw,
+};
 use pin_project_lite::pin_project;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -9,9 +12,12 @@ use std::{
         Arc, Mutex,
     },
     task::{ready, Context, Poll},
-    time::Instant,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time,
 };
-use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 /// Network traffic statistics.
 #[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
@@ -82,6 +88,333 @@ impl ByteCounters {
     }
 }
 
+/// Bandwidth limit in bytes per second. `None` means unlimited.
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BandwidthLimit {
+    pub upload: Option<u64>,
+    pub download: Option<u64>,
+}
+
+/// Bandwidth limit of a single repository, overriding the network-wide one.
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum BandwidthLimitOverride {
+    /// The network-wide limit applies.
+    #[default]
+    Inherit,
+    /// Unlimited, even if there is a network-wide limit.
+    Unlimited,
+    /// Limited to the given number of bytes per second. Zero means unlimited.
+    Limited(u64),
+}
+
+impl From<Option<u64>> for BandwidthLimitOverride {
+    /// `None` means unlimited.
+    fn from(limit: Option<u64>) -> Self {
+        match limit {
+            Some(limit) => Self::Limited(limit),
+            None => Self::Unlimited,
+        }
+    }
+}
+
+// Encoding of `BandwidthLimitOverride` into an `AtomicU64`.
+const LIMIT_INHERIT: u64 = 0;
+const LIMIT_UNLIMITED: u64 = u64::MAX;
+
+impl BandwidthLimitOverride {
+    fn encode(self) -> u64 {
+        match self {
+            Self::Inherit => LIMIT_INHERIT,
+            Self::Unlimited | Self::Limited(0) => LIMIT_UNLIMITED,
+            Self::Limited(limit) => limit,
+        }
+    }
+
+    // Returns the limit this override resolves to. `parent` is called only if it's `Inherit`.
+    fn resolve(encoded: u64, parent: impl FnOnce() -> Option<u64>) -> Option<u64> {
+        match encoded {
+            LIMIT_INHERIT => parent(),
+            LIMIT_UNLIMITED => None,
+            limit => Some(limit),
+        }
+    }
+}
+
+/// Configurable bandwidth limits. Each limit can either be inherited from the parent (if any),
+/// explicitly unlimited or set to a specific value (used to override the global limits per
+/// repository).
+#[derive(Default)]
+pub(super) struct BandwidthLimits {
+    upload: AtomicU64,
+    download: AtomicU64,
+    parent: Option<Arc<BandwidthLimits>>,
+}
+
+impl BandwidthLimits {
+    pub fn with_parent(parent: Arc<BandwidthLimits>) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Self::default()
+        }
+    }
+
+    pub fn set(&self, upload: BandwidthLimitOverride, download: BandwidthLimitOverride) {
+        self.upload.store(upload.encode(), Ordering::Relaxed);
+        self.download.store(download.encode(), Ordering::Relaxed);
+    }
+
+    /// Returns the effective limit, taking the parent into account.
+    pub fn read(&self) -> BandwidthLimit {
+        BandwidthLimit {
+            upload: self.upload(),
+            download: self.download(),
+        }
+    }
+
+    fn upload(&self) -> Option<u64> {
+        BandwidthLimitOverride::resolve(self.upload.load(Ordering::Relaxed), || {
+            self.parent.as_ref().and_then(|parent| parent.upload())
+        })
+    }
+
+    fn download(&self) -> Option<u64> {
+        BandwidthLimitOverride::resolve(self.download.load(Ordering::Relaxed), || {
+            self.parent.as_ref().and_then(|parent| parent.download())
+        })
+    }
+}
+
+/// Token buckets of a single peer, shared by all its links.
+#[derive(Default)]
+pub(super) struct ThrottleBuckets {
+    upload: TokenBucket,
+    download: TokenBucket,
+}
+
+/// Throttles the traffic of a single link (peer and repository). The limits are those of the
+/// repository but the buckets are shared by all the links of the peer so a peer syncing multiple
+/// repositories doesn't get a multiple of the limit.
+pub(super) struct Throttle {
+    limits: Arc<BandwidthLimits>,
+    buckets: Arc<ThrottleBuckets>,
+}
+
+impl Throttle {
+    pub fn new(limits: Arc<BandwidthLimits>, buckets: Arc<ThrottleBuckets>) -> Self {
+        Self { limits, buckets }
+    }
+
+    pub async fn upload(&self, bytes: u64) {
+        self.buckets
+            .upload
+            .acquire(bytes, self.limits.upload())
+            .await
+    }
+
+    pub async fn download(&self, bytes: u64) {
+        self.buckets
+            .download
+            .acquire(bytes, self.limits.download())
+            .await
+    }
+}
+
+/// Token bucket with capacity of one second worth of traffic.
+#[derive(Default)]
+struct TokenBucket {
+    state: Mutex<Option<TokenBucketState>>,
+}
+
+struct TokenBucketState {
+    // Can be negative which means the previous transfers exceeded the limit and the next one has
+    // to wait until the debt is repaid. This allows transferring messages larger than the
+    // capacity.
+    tokens: f64,
+    timestamp: Instant,
+}
+
+impl TokenBucket {
+    /// Waits until `bytes` can be transferred without exceeding `rate` (in bytes per second).
+    /// Returns immediately if `rate` is `None`.
+    async fn acquire(&self, bytes: u64, rate: Option<u64>) {
+        let Some(rate) = rate else {
+            return;
+        };
+
+        let delay = self.consume(bytes, rate, Instant::now());
+
+        if !delay.is_zero() {
+            time::sleep(delay).await;
+        }
+    }
+
+    /// Consumes `bytes` tokens and returns how long to wait before the transfer can proceed.
+    fn consume(&self, bytes: u64, rate: u64, now: Instant) -> Duration {
+        let rate = rate as f64;
+        let mut state = self.state.lock().unwrap();
+        let state = state.get_or_insert(TokenBucketState {
+            tokens: rate,
+            timestamp: now,
+        });
+
+        let elapsed = now.saturating_duration_since(state.timestamp);
+        state.timestamp = now;
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        state.tokens -= bytes as f64;
+
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / rate)
+        }
+    }
+}
+
+/// Number of protocol messages of each kind.
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MessageCounts {
+    pub root_node: u64,
+    pub child_nodes: u64,
+    pub inner_nodes: u64,
+    pub leaf_nodes: u64,
+    pub block: u64,
+    pub block_offer: u64,
+    /// Responses reporting that the requested root node, child nodes or block is not available.
+    pub error: u64,
+}
+
+/// Number of sync protocol messages sent and received, broken down by their kind.
+///
+/// Note: requests are only ever of the `root_node`, `child_nodes` and `block` kinds. Child nodes
+/// requests are answered with either `inner_nodes` or `leaf_nodes` responses.
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct MessageStats {
+    pub requests_sent: MessageCounts,
+    pub requests_received: MessageCounts,
+    pub responses_sent: MessageCounts,
+    pub responses_received: MessageCounts,
+}
+
+/// Counters of sent/received messages by kind. If a parent is set, every message counted here is
+/// also counted in the parent (used to aggregate the per-repository counters into global ones).
+#[derive(Default)]
+pub(super) struct MessageCounters {
+    requests_tx: KindCounters,
+    requests_rx: KindCounters,
+    responses_tx: KindCounters,
+    responses_rx: KindCounters,
+    parent: Option<Arc<MessageCounters>>,
+}
+
+impl MessageCounters {
+    pub fn with_parent(parent: Arc<MessageCounters>) -> Self {
+        Self {
+            parent: Some(parent),
+            ..Self::default()
+        }
+    }
+
+    pub fn record_tx(&self, content: &Content) {
+        match content {
+            Content::Request(request) => {
+                self.requests_tx.increment(MessageKind::of_request(request))
+            }
+            Content::Response(response) => self
+                .responses_tx
+                .increment(MessageKind::of_response(response)),
+            Content::Pex(_) => return,
+        }
+
+        if let Some(parent) = &self.parent {
+            parent.record_tx(content);
+        }
+    }
+
+    pub fn record_rx(&self, content: &Content) {
+        match content {
+            Content::Request(request) => {
+                self.requests_rx.increment(MessageKind::of_request(request))
+            }
+            Content::Response(response) => self
+                .responses_rx
+                .increment(MessageKind::of_response(response)),
+            Content::Pex(_) => return,
+        }
+
+        if let Some(parent) = &self.parent {
+            parent.record_rx(content);
+        }
+    }
+
+    pub fn read(&self) -> MessageStats {
+        MessageStats {
+            requests_sent: self.requests_tx.read(),
+            requests_received: self.requests_rx.read(),
+            responses_sent: self.responses_tx.read(),
+            responses_received: self.responses_rx.read(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum MessageKind {
+    RootNode,
+    ChildNodes,
+    InnerNodes,
+    LeafNodes,
+    Block,
+    BlockOffer,
+    Error,
+}
+
+impl MessageKind {
+    const COUNT: usize = 7;
+
+    fn of_request(request: &Request) -> Self {
+        match request {
+            Request::RootNode(..) => Self::RootNode,
+            Request::ChildNodes(..) => Self::ChildNodes,
+            Request::Block(..) => Self::Block,
+        }
+    }
+
+    fn of_response(response: &Response) -> Self {
+        match response {
+            Response::RootNode(..) => Self::RootNode,
+            Response::InnerNodes(..) => Self::InnerNodes,
+            Response::LeafNodes(..) => Self::LeafNodes,
+            Response::BlockOffer(..) => Self::BlockOffer,
+            Response::Block(..) => Self::Block,
+            Response::RootNodeError(..)
+            | Response::ChildNodesError(..)
+            | Response::BlockError(..) => Self::Error,
+        }
+    }
+}
+
+#[derive(Default)]
+struct KindCounters([AtomicU64; MessageKind::COUNT]);
+
+impl KindCounters {
+    fn increment(&self, kind: MessageKind) {
+        self.0[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn read(&self) -> MessageCounts {
+        let get = |kind: MessageKind| self.0[kind as usize].load(Ordering::Relaxed);
+
+        MessageCounts {
+            root_node: get(MessageKind::RootNode),
+            child_nodes: get(MessageKind::ChildNodes),
+            inner_nodes: get(MessageKind::InnerNodes),
+            leaf_nodes: get(MessageKind::LeafNodes),
+            block: get(MessageKind::Block),
+            block_offer: get(MessageKind::BlockOffer),
+            error: get(MessageKind::Error),
+        }
+    }
+}
+
 /// Throughput caculator
 #[derive(Default)]
 pub(super) struct Throughput {
@@ -127,17 +460,30 @@ struct ThroughputSample {
 }
 
 pin_project! {
-    /// Wrapper for an IO object (reader or writer) that counts the transferred bytes.
+    /// Wrapper for an IO object (reader or writer) that counts the transferred bytes and
+    /// optionally throttles them.
     pub(super) struct Instrumented<T> {
         #[pin]
         inner: T,
         counters: Arc<ByteCounters>,
+        throttle: Option<Arc<Throttle>>,
     }
 }
 
 impl<T> Instrumented<T> {
     pub fn new(inner: T, counters: Arc<ByteCounters>) -> Self {
-        Self { inner, counters }
+        Self {
+            inner,
+            counters,
+            throttle: None,
+        }
+    }
+
+    pub fn with_throttle(self, throttle: Arc<Throttle>) -> Self {
+        Self {
+            throttle: Some(throttle),
+            ..self
+        }
     }
 
     pub fn as_ref(&self) -> &T {
@@ -151,6 +497,10 @@ impl<T> Instrumented<T> {
     pub fn counters(&self) -> &ByteCounters {
         &self.counters
     }
+
+    pub fn throttle(&self) -> Option<&Throttle> {
+        self.throttle.as_deref()
+    }
 }
 
 impl<T> AsyncRead for Instrumented<T>
@@ -233,16 +583,70 @@ impl Instrumented<raw::Stream> {
         let (reader, writer) = self.inner.into_split();
 
         (
-            Instrumented::new(reader, self.counters.clone()),
-            Instrumented::new(writer, self.counters),
+            Instrumented {
+                inner: reader,
+                counters: self.counters.clone(),
+                throttle: self.throttle.clone(),
+            },
+            Instrumented {
+                inner: writer,
+                counters: self.counters,
+                throttle: self.throttle,
+            },
         )
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use std::time::Duration;
+    use super::{
+        super::{
+            debug_payload::{DebugResponse, PendingDebugRequest},
+            message::ResponseDisambiguator,
+        },
+        *,
+    };
+    use crate::{crypto::Hash, protocol::MultiBlockPresence};
+
+    #[test]
+    fn message_counters_propagate_to_parent() {
+        let global = Arc::new(MessageCounters::default());
+        let repo_a = MessageCounters::with_parent(global.clone());
+        let repo_b = MessageCounters::with_parent(global.clone());
+
+        let hash: Hash = rand::random();
+        let disambiguator = ResponseDisambiguator::new(MultiBlockPresence::Full);
+
+        let request = Content::Request(Request::ChildNodes(
+            hash,
+            disambiguator,
+            PendingDebugRequest::start().send(),
+        ));
+        let response = Content::Response(Response::ChildNodesError(
+            hash,
+            disambiguator,
+            DebugResponse::unsolicited(),
+        ));
+
+        repo_a.record_tx(&request);
+        repo_a.record_rx(&response);
+        repo_b.record_rx(&request);
+
+        let stats = repo_a.read();
+        assert_eq!(stats.requests_sent.child_nodes, 1);
+        assert_eq!(stats.responses_received.error, 1);
+        assert_eq!(stats.requests_received, MessageCounts::default());
+
+        let stats = repo_b.read();
+        assert_eq!(stats.requests_received.child_nodes, 1);
+        assert_eq!(stats.requests_sent, MessageCounts::default());
+
+        let stats = global.read();
+        assert_eq!(stats.requests_sent.child_nodes, 1);
+        assert_eq!(stats.requests_received.child_nodes, 1);
+        assert_eq!(stats.responses_received.error, 1);
+        assert_eq!(stats.responses_sent, MessageCounts::default());
+    }
 
     #[test]
     fn throughput_sanity_check() {
@@ -291,6 +695,100 @@ mod tests {
         assert_eq!(throughput.sample(1024, start + s(2)), 0);
     }
 
+    #[test]
+    fn token_bucket_allows_burst_up_to_capacity() {
+        let bucket = TokenBucket::default();
+        let start = Instant::now();
+
+        assert_approx(bucket.consume(512, 1024, start), Duration::ZERO);
+        assert_approx(bucket.consume(512, 1024, start), Duration::ZERO);
+        assert_approx(bucket.consume(512, 1024, start), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn token_bucket_refills_over_time() {
+        let bucket = TokenBucket::default();
+        let start = Instant::now();
+
+        assert_approx(bucket.consume(1024, 1024, start), Duration::ZERO);
+        assert_approx(
+            bucket.consume(1024, 1024, start + Duration::from_millis(500)),
+            Duration::from_millis(500),
+        );
+
+        // Refill doesn't exceed the capacity.
+        assert_approx(bucket.consume(1024, 1024, start + s(10)), Duration::ZERO);
+        assert_approx(
+            bucket.consume(1, 1024, start + s(10)),
+            Duration::from_secs_f64(1.0 / 1024.0),
+        );
+    }
+
+    #[test]
+    fn bandwidth_limits_fall_back_to_parent() {
+        let global = Arc::new(BandwidthLimits::default());
+        let repo = BandwidthLimits::with_parent(global.clone());
+
+        assert_eq!(repo.read(), BandwidthLimit::default());
+
+        global.set(
+            BandwidthLimitOverride::Limited(1000),
+            BandwidthLimitOverride::Limited(2000),
+        );
+        assert_eq!(repo.read(), global.read());
+
+        repo.set(
+            BandwidthLimitOverride::Limited(500),
+            BandwidthLimitOverride::Inherit,
+        );
+        assert_eq!(
+            repo.read(),
+            BandwidthLimit {
+                upload: Some(500),
+                download: Some(2000),
+            }
+        );
+
+        // Explicitly unlimited despite the global limit.
+        repo.set(
+            BandwidthLimitOverride::Unlimited,
+            BandwidthLimitOverride::Limited(0),
+        );
+        assert_eq!(repo.read(), BandwidthLimit::default());
+    }
+
+    #[test]
+    fn throttle_buckets_are_shared_by_links_of_the_same_peer() {
+        let limits = Arc::new(BandwidthLimits::default());
+        limits.set(
+            BandwidthLimitOverride::Limited(1024),
+            BandwidthLimitOverride::Inherit,
+        );
+
+        let buckets = Arc::new(ThrottleBuckets::default());
+        let link_a = Throttle::new(limits.clone(), buckets.clone());
+        let link_b = Throttle::new(limits, buckets);
+        let start = Instant::now();
+
+        assert_approx(
+            link_a.buckets.upload.consume(1024, 1024, start),
+            Duration::ZERO,
+        );
+        assert_approx(
+            link_b.buckets.upload.consume(512, 1024, start),
+            Duration::from_millis(500),
+        );
+    }
+
+    #[track_caller]
+    fn assert_approx(actual: Duration, expected: Duration) {
+        let diff = actual.max(expected) - actual.min(expected);
+        assert!(
+            diff < Duration::from_micros(1),
+            "{actual:?} differs from {expected:?} by {diff:?}"
+        );
+    }
+
     fn s(value: u64) -> Duration {
         Duration::from_secs(value)
     }
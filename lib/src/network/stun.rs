@@ -1,6 +1,7 @@
 //! STUN protocol handling
 
 use super::stun_server_list::STUN_SERVERS;
+use crate::sync::uninitialized_watch;
 use futures_util::{future, StreamExt};
 use net::{
     quic::SideChannel,
@@ -8,68 +9,230 @@ use net::{
     udp::DatagramSocket,
 };
 use rand::seq::SliceRandom;
+use scoped_task::ScopedJoinHandle;
 use std::{
     future::Future,
     net::{SocketAddr, SocketAddrV4, SocketAddrV6},
     sync::{Arc, Mutex},
     time::Duration,
 };
-use tokio::{select, sync::mpsc, time};
+use tokio::{
+    select,
+    sync::{mpsc, watch},
+    time,
+};
 use tokio_stream::wrappers::ReceiverStream;
 use tracing::Instrument;
 
 const LOOKUP_HOST_TIMEOUT: Duration = Duration::from_secs(5);
 const MAX_CONCURRENCY: usize = 4;
 
+// How often to re-run STUN to detect changes of our external addresses / NAT behavior.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+type Client = Arc<StunClient<SideChannel>>;
+
+/// STUN servers to query. `None` means the built-in list ([STUN_SERVERS]), empty means disabled.
+type Servers = Option<Arc<[SocketAddr]>>;
+
+/// Our external addresses and the behavior of the NAT we are behind, as detected by STUN. `None`
+/// means unknown.
+#[derive(Clone, Copy, Eq, PartialEq, Default, Debug)]
+pub struct ExternalAddrs {
+    pub v4: Option<SocketAddrV4>,
+    pub v6: Option<SocketAddrV6>,
+    pub nat_behavior: Option<NatBehavior>,
+}
+
 pub(super) struct StunClients {
-    client_v4: Mutex<Option<Arc<StunClient<SideChannel>>>>,
-    client_v6: Mutex<Option<Arc<StunClient<SideChannel>>>>,
+    client_v4: Mutex<Option<Client>>,
+    client_v6: Mutex<Option<Client>>,
+    last: Arc<Mutex<ExternalAddrs>>,
+    on_change_tx: uninitialized_watch::Sender<ExternalAddrs>,
+    poll_interval_tx: watch::Sender<Duration>,
+    poll_task: Mutex<Option<ScopedJoinHandle<()>>>,
+    servers: Mutex<Servers>,
 }
 
 impl StunClients {
     pub fn new() -> Self {
+        let (on_change_tx, _) = uninitialized_watch::channel();
+        let (poll_interval_tx, _) = watch::channel(DEFAULT_POLL_INTERVAL);
+
         Self {
             client_v4: Mutex::new(None),
             client_v6: Mutex::new(None),
+            last: Arc::new(Mutex::new(ExternalAddrs::default())),
+            on_change_tx,
+            poll_interval_tx,
+            poll_task: Mutex::new(None),
+            servers: Mutex::new(None),
         }
     }
 
-    /// Binds the STUN clients to the given sockets.
+    /// Binds the STUN clients to the given sockets and (re)starts polling them for changes.
     pub fn rebind(&self, socket_v4: Option<SideChannel>, socket_v6: Option<SideChannel>) {
         *self.client_v4.lock().unwrap() = socket_v4.map(StunClient::new).map(Arc::new);
         *self.client_v6.lock().unwrap() = socket_v6.map(StunClient::new).map(Arc::new);
+
+        self.restart();
+    }
+
+    /// Replaces the STUN servers to query. `None` restores the built-in list, empty list disables
+    /// STUN. Survives rebinds.
+    pub fn set_servers(&self, servers: Option<Vec<SocketAddr>>) {
+        *self.servers.lock().unwrap() = servers.map(Arc::from);
+        self.restart();
+    }
+
+    fn restart(&self) {
+        let client_v4 = self.client_v4.lock().unwrap().clone();
+        let client_v6 = self.client_v6.lock().unwrap().clone();
+        let servers = self.servers.lock().unwrap().clone();
+
+        let mut poll_task = self.poll_task.lock().unwrap();
+
+        if (client_v4.is_none() && client_v6.is_none()) || is_disabled(&servers) {
+            *poll_task = None;
+            update(&self.last, &self.on_change_tx, ExternalAddrs::default());
+        } else {
+            *poll_task = Some(scoped_task::spawn(
+                poll(
+                    client_v4,
+                    client_v6,
+                    servers,
+                    self.last.clone(),
+                    self.on_change_tx.clone(),
+                    self.poll_interval_tx.subscribe(),
+                )
+                .instrument(tracing::info_span!("stun_poll")),
+            ));
+        }
     }
 
     /// Queries our external address.
     pub async fn external_addr_v4(&self) -> Option<SocketAddrV4> {
         let client = self.client_v4.lock().unwrap().as_ref().cloned()?;
-        external_addr(client).await.and_then(|addr| match addr {
-            SocketAddr::V4(addr) => Some(addr),
-            SocketAddr::V6(_) => None,
-        })
+        let servers = self.enabled_servers()?;
+        external_addr_v4(client, servers).await
     }
 
     /// Queries our external address.
     pub async fn external_addr_v6(&self) -> Option<SocketAddrV6> {
         let client = self.client_v6.lock().unwrap().as_ref().cloned()?;
-        external_addr(client).await.and_then(|addr| match addr {
-            SocketAddr::V6(addr) => Some(addr),
-            SocketAddr::V4(_) => None,
-        })
+        let servers = self.enabled_servers()?;
+        external_addr_v6(client, servers).await
     }
 
     /// Determines the behavior of the NAT we are behind. Returns `None` if unknown.
     pub async fn nat_behavior(&self) -> Option<NatBehavior> {
         let client = self.client_v4.lock().unwrap().as_ref().cloned()?;
-        nat_behavior(client).await
+        let servers = self.enabled_servers()?;
+        nat_behavior(client, servers).await
+    }
+
+    // Returns the servers to query or `None` if STUN is disabled.
+    fn enabled_servers(&self) -> Option<Servers> {
+        let servers = self.servers.lock().unwrap().clone();
+        (!is_disabled(&servers)).then_some(servers)
+    }
+
+    /// Subscribe to changes of the external addresses or NAT behavior.
+    pub fn on_change(&self) -> uninitialized_watch::Receiver<ExternalAddrs> {
+        self.on_change_tx.subscribe()
+    }
+
+    /// Sets how often to re-run STUN to detect changes. Takes effect after the current interval
+    /// elapses.
+    pub fn set_poll_interval(&self, interval: Duration) {
+        self.poll_interval_tx.send_replace(interval);
+    }
+}
+
+async fn poll(
+    client_v4: Option<Client>,
+    client_v6: Option<Client>,
+    servers: Servers,
+    last: Arc<Mutex<ExternalAddrs>>,
+    on_change_tx: uninitialized_watch::Sender<ExternalAddrs>,
+    mut poll_interval_rx: watch::Receiver<Duration>,
+) {
+    loop {
+        let (v4, v6, nat_behavior) = future::join3(
+            async {
+                match &client_v4 {
+                    Some(client) => external_addr_v4(client.clone(), servers.clone()).await,
+                    None => None,
+                }
+            },
+            async {
+                match &client_v6 {
+                    Some(client) => external_addr_v6(client.clone(), servers.clone()).await,
+                    None => None,
+                }
+            },
+            async {
+                match &client_v4 {
+                    Some(client) => nat_behavior(client.clone(), servers.clone()).await,
+                    None => None,
+                }
+            },
+        )
+        .await;
+
+        update(
+            &last,
+            &on_change_tx,
+            ExternalAddrs {
+                v4,
+                v6,
+                nat_behavior,
+            },
+        );
+
+        let interval = *poll_interval_rx.borrow_and_update();
+        time::sleep(interval).await;
+    }
+}
+
+fn update(
+    last: &Mutex<ExternalAddrs>,
+    on_change_tx: &uninitialized_watch::Sender<ExternalAddrs>,
+    new: ExternalAddrs,
+) {
+    let mut last = last.lock().unwrap();
+
+    if *last != new {
+        tracing::debug!(?new, "external addresses changed");
+
+        *last = new;
+        on_change_tx.send(new).unwrap_or(());
     }
 }
 
-async fn external_addr(client: Arc<StunClient<SideChannel>>) -> Option<SocketAddr> {
+async fn external_addr_v4(client: Client, servers: Servers) -> Option<SocketAddrV4> {
+    external_addr(client, servers)
+        .await
+        .and_then(|addr| match addr {
+            SocketAddr::V4(addr) => Some(addr),
+            SocketAddr::V6(_) => None,
+        })
+}
+
+async fn external_addr_v6(client: Client, servers: Servers) -> Option<SocketAddrV6> {
+    external_addr(client, servers)
+        .await
+        .and_then(|addr| match addr {
+            SocketAddr::V6(addr) => Some(addr),
+            SocketAddr::V4(_) => None,
+        })
+}
+
+async fn external_addr(client: Client, servers: Servers) -> Option<SocketAddr> {
     let client = client.as_ref();
     let local_addr = client.get_ref().local_addr().ok()?;
 
-    run(|server_addr| async move {
+    run(servers, |server_addr| async move {
         if !is_same_family(&server_addr, &local_addr) {
             return None;
         }
@@ -88,11 +251,11 @@ async fn external_addr(client: Arc<StunClient<SideChannel>>) -> Option<SocketAdd
     .await
 }
 
-async fn nat_behavior(client: Arc<StunClient<SideChannel>>) -> Option<NatBehavior> {
+async fn nat_behavior(client: Client, servers: Servers) -> Option<NatBehavior> {
     let client = client.as_ref();
     let local_addr = client.get_ref().local_addr().ok()?;
 
-    run(|server_addr| async move {
+    run(servers, |server_addr| async move {
         if !is_same_family(&server_addr, &local_addr) {
             return None;
         }
@@ -112,39 +275,58 @@ async fn nat_behavior(client: Arc<StunClient<SideChannel>>) -> Option<NatBehavio
 }
 
 /// Runs task on every STUN server until one of them succeeds.
-async fn run<F, Fut, R>(mut f: F) -> Option<R>
+async fn run<F, Fut, R>(servers: Servers, mut f: F) -> Option<R>
 where
     F: FnMut(SocketAddr) -> Fut,
     Fut: Future<Output = Option<R>>,
 {
-    // Try all the servers in random order.
-    let mut hosts: Vec<_> = STUN_SERVERS.to_vec();
-    hosts.shuffle(&mut rand::thread_rng());
-
     let (tasks_tx, tasks_rx) = mpsc::channel(32);
 
     // Resolve the individual server hosts sequentially (to avoid getting rate-limitted) but run
     // the whole thing concurrently with the tasks. Run the tasks themselves also concurrently, but
     // with a concurency limit.
     let push = async {
-        for host in hosts {
-            let span = tracing::info_span!("stun_server", message = host);
-
-            let server_addrs =
-                match time::timeout(LOOKUP_HOST_TIMEOUT, tokio::net::lookup_host(host)).await {
-                    Ok(Ok(addrs)) => addrs,
-                    Ok(Err(_)) | Err(_) => {
-                        let _enter = span.enter();
-                        tracing::debug!(stun_server = host, "failed to resolve host");
-                        continue;
-                    }
-                };
+        match servers {
+            Some(servers) => {
+                // Custom servers are given as addresses so there is nothing to resolve.
+                let mut server_addrs = servers.to_vec();
+                server_addrs.shuffle(&mut rand::thread_rng());
 
-            for server_addr in server_addrs {
-                tasks_tx
-                    .send(f(server_addr).instrument(span.clone()))
-                    .await
-                    .unwrap();
+                for server_addr in server_addrs {
+                    let span = tracing::info_span!("stun_server", message = %server_addr);
+                    tasks_tx
+                        .send(f(server_addr).instrument(span))
+                        .await
+                        .unwrap();
+                }
+            }
+            None => {
+                // Try all the servers in random order.
+                let mut hosts: Vec<_> = STUN_SERVERS.to_vec();
+                hosts.shuffle(&mut rand::thread_rng());
+
+                for host in hosts {
+                    let span = tracing::info_span!("stun_server", message = host);
+
+                    let server_addrs =
+                        match time::timeout(LOOKUP_HOST_TIMEOUT, tokio::net::lookup_host(host))
+                            .await
+                        {
+                            Ok(Ok(addrs)) => addrs,
+                            Ok(Err(_)) | Err(_) => {
+                                let _enter = span.enter();
+                                tracing::debug!(stun_server = host, "failed to resolve host");
+                                continue;
+                            }
+                        };
+
+                    for server_addr in server_addrs {
+                        tasks_tx
+                            .send(f(server_addr).instrument(span.clone()))
+                            .await
+                            .unwrap();
+                    }
+                }
             }
         }
 
@@ -165,6 +347,10 @@ where
     }
 }
 
+fn is_disabled(servers: &Servers) -> bool {
+    servers.as_ref().is_some_and(|servers| servers.is_empty())
+}
+
 fn is_same_family(a: &SocketAddr, b: &SocketAddr) -> bool {
     match (a, b) {
         (SocketAddr::V4(_), SocketAddr::V4(_)) | (SocketAddr::V6(_), SocketAddr::V6(_)) => true,
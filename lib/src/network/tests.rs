@@ -2,7 +2,14 @@ use super::{
     client::Client,
     constants::MAX_UNCHOKED_COUNT,
     message::{Content, Request, Response},
+    peer_addr::PeerAddr,
+    peer_state::PeerState,
+    perform_handshake,
+    protocol::{Version, DEVICE_NAME_VERSION, MAGIC, MIN_SUPPORTED_VERSION, VERSION},
+    raw,
+    runtime_id::{PublicRuntimeId, SecretRuntimeId},
     server::Server,
+    CongestionKind, Handshake, HandshakeError, Network, QuicTuning, TransportPreference,
 };
 use crate::{
     block_tracker::OfferState,
@@ -17,14 +24,25 @@ use crate::{
     test_utils,
     version_vector::VersionVector,
 };
+use assert_matches::assert_matches;
 use futures_util::{future, TryStreamExt};
 use metrics::NoopRecorder;
+use net::tcp::{TcpListener, TcpStream};
 use rand::prelude::*;
 use state_monitor::StateMonitor;
-use std::{fmt, future::Future, sync::Arc};
+use std::{
+    fmt,
+    future::Future,
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 use tempfile::TempDir;
 use test_strategy::proptest;
 use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
     pin, select,
     sync::{
         broadcast::{self, error::RecvError},
@@ -399,6 +417,297 @@ async fn failed_block_other_peer() {
     }
 }
 
+// Receive-only replica serves the index but not the blocks.
+#[tokio::test]
+async fn upload_disabled() {
+    test_utils::init_log();
+
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let write_keys = Keypair::generate(&mut rng);
+    let (_a_base_dir, a_vault, a_choker, a_id) = create_repository(&mut rng, &write_keys).await;
+    let (_b_base_dir, b_vault, _, _) = create_repository(&mut rng, &write_keys).await;
+
+    let snapshot = Snapshot::generate(&mut rng, 1);
+    save_snapshot(&a_vault, a_id, &write_keys, &snapshot).await;
+    save_blocks(&a_vault, &snapshot).await;
+
+    let upload_enabled = Arc::new(AtomicBool::new(false));
+    let mut server =
+        create_server_with_upload(a_vault.clone(), a_choker.clone(), upload_enabled.clone());
+    let mut client = create_client(b_vault.clone());
+
+    simulate_connection_until(&mut server, &mut client, async {
+        wait_until_snapshots_in_sync(&a_vault, a_id, &b_vault).await;
+        // Give the client a chance to request the blocks.
+        time::sleep(Duration::from_secs(1)).await;
+    })
+    .await;
+
+    for id in snapshot.blocks().keys() {
+        assert!(!b_vault
+            .store()
+            .acquire_read()
+            .await
+            .unwrap()
+            .block_exists(id)
+            .await
+            .unwrap());
+    }
+
+    // Enable upload and reconnect.
+    drop(server);
+    drop(client);
+
+    upload_enabled.store(true, Ordering::Relaxed);
+
+    let mut server = create_server_with_upload(a_vault.clone(), a_choker, upload_enabled);
+    let mut client = create_client(b_vault.clone());
+
+    simulate_connection_until(&mut server, &mut client, async {
+        for id in snapshot.blocks().keys() {
+            wait_until_block_exists(&b_vault, id).await
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn handshake_with_newer_peer() {
+    let newer_version = Version(VERSION.0 + 1);
+    let (a, b) = handshake_pair(VERSION, newer_version).await;
+
+    let a = a.unwrap();
+    let b = b.unwrap();
+
+    assert_eq!(a.that_version, newer_version);
+    assert_eq!(b.that_version, VERSION);
+    assert_eq!(a.negotiated_version, VERSION);
+    assert_eq!(b.negotiated_version, VERSION);
+    assert_eq!(a.that_device_name, None);
+    assert_eq!(b.that_device_name.as_deref(), Some("a"));
+    assert_eq!(
+        a.that_transport_preference,
+        Some(TransportPreference::Either)
+    );
+    assert_eq!(b.that_transport_preference, Some(TransportPreference::Quic));
+}
+
+#[tokio::test]
+async fn handshake_with_older_peer_skips_device_name() {
+    let older_version = MIN_SUPPORTED_VERSION;
+    assert!(older_version < DEVICE_NAME_VERSION);
+
+    let (a, b) = handshake_pair(VERSION, older_version).await;
+
+    let a = a.unwrap();
+    let b = b.unwrap();
+
+    assert_eq!(a.negotiated_version, older_version);
+    assert_eq!(b.negotiated_version, older_version);
+    assert_eq!(b.that_device_name, None);
+    assert_eq!(b.that_transport_preference, None);
+}
+
+#[tokio::test]
+async fn protocol_mismatch() {
+    let network = Network::new(StateMonitor::make_root(), None, None);
+    network
+        .bind(&[PeerAddr::Tcp((Ipv4Addr::LOCALHOST, 0).into())])
+        .await;
+
+    let addr = match network.listener_local_addrs().as_slice() {
+        [PeerAddr::Tcp(addr)] => *addr,
+        addrs => panic!("unexpected listener addrs: {addrs:?}"),
+    };
+
+    let mut on_protocol_mismatch = network.on_protocol_mismatch();
+    assert_eq!(network.last_protocol_mismatch(), None);
+
+    // Pretend to be a peer running a newer version of the protocol.
+    let newer_version = Version(VERSION.0 + 1);
+    let peer_id = SecretRuntimeId::random();
+
+    let mut stream = raw::Stream::Tcp(TcpStream::connect(addr).await.unwrap());
+    perform_handshake(
+        &mut stream,
+        newer_version,
+        &peer_id,
+        "",
+        TransportPreference::Either,
+        |_| (),
+    )
+    .await
+    .unwrap();
+
+    // The mismatch is first reported without the runtime id and then completed with it once the
+    // handshake finishes.
+    let mismatch = time::timeout(TIMEOUT, async {
+        loop {
+            let mismatch = on_protocol_mismatch.changed().await.unwrap();
+            if mismatch.runtime_id.is_some() {
+                break mismatch;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(mismatch.our_version, u32::from(VERSION));
+    assert_eq!(mismatch.their_version, u32::from(newer_version));
+    assert_eq!(mismatch.runtime_id, Some(peer_id.public()));
+    assert_matches!(mismatch.peer_addr, PeerAddr::Tcp(_));
+
+    assert_eq!(network.last_protocol_mismatch(), Some(mismatch));
+    assert_eq!(
+        network.highest_seen_protocol_version(),
+        u32::from(newer_version)
+    );
+}
+
+// A newer peer which no longer supports our version closes the connection right after exchanging
+// the versions. The mismatch must still be reported.
+#[tokio::test]
+async fn protocol_mismatch_with_incompatible_peer() {
+    let network = Network::new(StateMonitor::make_root(), None, None);
+    network
+        .bind(&[PeerAddr::Tcp((Ipv4Addr::LOCALHOST, 0).into())])
+        .await;
+
+    let addr = match network.listener_local_addrs().as_slice() {
+        [PeerAddr::Tcp(addr)] => *addr,
+        addrs => panic!("unexpected listener addrs: {addrs:?}"),
+    };
+
+    let mut on_protocol_mismatch = network.on_protocol_mismatch();
+
+    let newer_version = Version(VERSION.0 + 1);
+
+    let mut stream = raw::Stream::Tcp(TcpStream::connect(addr).await.unwrap());
+    stream.write_all(MAGIC).await.unwrap();
+    newer_version.write_into(&mut stream).await.unwrap();
+
+    let mut magic = [0; MAGIC.len()];
+    stream.read_exact(&mut magic).await.unwrap();
+    assert_eq!(Version::read_from(&mut stream).await.unwrap(), VERSION);
+
+    drop(stream);
+
+    let mismatch = time::timeout(TIMEOUT, on_protocol_mismatch.changed())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(mismatch.our_version, u32::from(VERSION));
+    assert_eq!(mismatch.their_version, u32::from(newer_version));
+    assert_eq!(mismatch.runtime_id, None);
+    assert_eq!(network.last_protocol_mismatch(), Some(mismatch));
+}
+
+#[tokio::test]
+async fn connect_in_memory() {
+    let a = Network::new(StateMonitor::make_root(), None, None);
+    let b = Network::new(StateMonitor::make_root(), None, None);
+
+    a.connect_in_memory(&b).await;
+
+    time::timeout(
+        TIMEOUT,
+        future::join(
+            wait_until_peer_active(&a, b.this_runtime_id()),
+            wait_until_peer_active(&b, a.this_runtime_id()),
+        ),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn stun_disabled() {
+    let network = Network::new(StateMonitor::make_root(), None, None);
+    network.set_stun_servers(Vec::new());
+
+    // Binding to the unspecified address enables STUN (if it wasn't disabled).
+    network
+        .bind(&[PeerAddr::Quic((Ipv4Addr::UNSPECIFIED, 0).into())])
+        .await;
+
+    // Returns immediately without contacting any server.
+    let timeout = Duration::from_secs(1);
+    assert_eq!(
+        time::timeout(timeout, network.external_addr_v4()).await,
+        Ok(None)
+    );
+    assert_eq!(
+        time::timeout(timeout, network.nat_behavior()).await,
+        Ok(None)
+    );
+
+    // The setting survives rebind.
+    network
+        .bind(&[PeerAddr::Quic((Ipv4Addr::UNSPECIFIED, 0).into())])
+        .await;
+    assert_eq!(
+        time::timeout(timeout, network.external_addr_v4()).await,
+        Ok(None)
+    );
+}
+
+#[tokio::test]
+async fn stun_poll_interval() {
+    let network = Network::new(StateMonitor::make_root(), None, None);
+
+    // Zero would re-run STUN in a busy loop.
+    assert_matches!(
+        network.set_stun_poll_interval(Duration::ZERO),
+        Err(crate::Error::InvalidArgument)
+    );
+    assert_matches!(
+        network.set_stun_poll_interval(Duration::from_secs(60)),
+        Ok(())
+    );
+}
+
+#[tokio::test]
+async fn quic_transport_config() {
+    let network = Network::new(StateMonitor::make_root(), None, None);
+    let default = network.quic_transport_config();
+
+    assert_matches!(
+        network.set_quic_transport_config(QuicTuning {
+            initial_mtu: 1199,
+            ..default
+        }),
+        Err(crate::Error::InvalidArgument)
+    );
+    assert_matches!(
+        network.set_quic_transport_config(QuicTuning {
+            max_idle_timeout: net::KEEP_ALIVE_INTERVAL,
+            ..default
+        }),
+        Err(crate::Error::InvalidArgument)
+    );
+    assert_eq!(network.quic_transport_config(), default);
+
+    let tuning = QuicTuning {
+        congestion: CongestionKind::Bbr,
+        initial_mtu: 1400,
+        max_idle_timeout: 3 * net::KEEP_ALIVE_INTERVAL,
+    };
+    assert_matches!(network.set_quic_transport_config(tuning), Ok(()));
+    assert_eq!(network.quic_transport_config(), tuning);
+}
+
+#[tokio::test]
+async fn handshake_with_unsupported_peer() {
+    let older_version = Version(MIN_SUPPORTED_VERSION.0 - 1);
+    let (a, _) = handshake_pair(VERSION, older_version).await;
+
+    assert_matches!(a, Err(HandshakeError::ProtocolVersionTooLow(version)) => {
+        assert_eq!(version, older_version)
+    });
+}
+
 async fn create_repository<R: Rng + CryptoRng>(
     rng: &mut R,
     write_keys: &Keypair,
@@ -516,6 +825,24 @@ async fn wait_until_block_exists(vault: &Vault, block_id: &BlockId) {
     }
 }
 
+async fn wait_until_peer_active(network: &Network, peer_id: PublicRuntimeId) {
+    let mut rx = network.on_peer_set_change();
+
+    loop {
+        let active = network
+            .peer_info_collector()
+            .collect()
+            .into_iter()
+            .any(|info| matches!(info.state, PeerState::Active { id, .. } if id == peer_id));
+
+        if active {
+            break;
+        }
+
+        rx.changed().await.unwrap();
+    }
+}
+
 async fn recv_any(rx: &mut broadcast::Receiver<Event>) {
     match rx.recv().await {
         Ok(_) | Err(RecvError::Lagged(_)) => (),
@@ -618,6 +945,52 @@ where
     }
 }
 
+// Performs the handshake between two peers with the given protocol versions connected over a
+// local TCP connection.
+async fn handshake_pair(
+    a_version: Version,
+    b_version: Version,
+) -> (
+    Result<Handshake, HandshakeError>,
+    Result<Handshake, HandshakeError>,
+) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let a_id = SecretRuntimeId::random();
+    let b_id = SecretRuntimeId::random();
+
+    let a = async {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = raw::Stream::Tcp(stream);
+        perform_handshake(
+            &mut stream,
+            a_version,
+            &a_id,
+            "a",
+            TransportPreference::Quic,
+            |_| (),
+        )
+        .await
+    };
+
+    let b = async {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut stream = raw::Stream::Tcp(stream);
+        perform_handshake(
+            &mut stream,
+            b_version,
+            &b_id,
+            "",
+            TransportPreference::Either,
+            |_| (),
+        )
+        .await
+    };
+
+    future::join(a, b).await
+}
+
 type ServerData = (
     Server,
     mpsc::UnboundedReceiver<Content>,
@@ -630,9 +1003,24 @@ type ClientData = (
 );
 
 fn create_server(repo: Vault, response_limiter: Arc<Semaphore>) -> ServerData {
+    create_server_with_upload(repo, response_limiter, Arc::new(AtomicBool::new(true)))
+}
+
+fn create_server_with_upload(
+    repo: Vault,
+    response_limiter: Arc<Semaphore>,
+    upload_enabled: Arc<AtomicBool>,
+) -> ServerData {
     let (send_tx, send_rx) = mpsc::unbounded_channel();
     let (recv_tx, recv_rx) = mpsc::channel(CAPACITY);
-    let server = Server::new(repo, send_tx, recv_rx, response_limiter);
+    let server = Server::new(
+        repo,
+        send_tx,
+        recv_rx,
+        response_limiter,
+        upload_enabled,
+        Arc::new(AtomicBool::new(true)),
+    );
 
     (server, send_rx, recv_tx)
 }
@@ -640,7 +1028,7 @@ fn create_server(repo: Vault, response_limiter: Arc<Semaphore>) -> ServerData {
 fn create_client(repo: Vault) -> ClientData {
     let (send_tx, send_rx) = mpsc::unbounded_channel();
     let (recv_tx, recv_rx) = mpsc::channel(CAPACITY);
-    let client = Client::new(repo, send_tx, recv_rx);
+    let client = Client::new(repo, send_tx, recv_rx, Arc::new(AtomicBool::new(true)));
 
     (client, send_rx, recv_tx)
 }
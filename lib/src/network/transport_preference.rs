@@ -0,0 +1,93 @@
+use super::peer_addr::PeerAddr;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Which transport to prefer when a peer is reachable over both QUIC and TCP.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    IntoPrimitive,
+    TryFromPrimitive,
+)]
+#[repr(u8)]
+#[serde(into = "u8", try_from = "u8")]
+pub enum TransportPreference {
+    /// No preference - keep all the connections to the peer (the default).
+    #[default]
+    Either,
+    /// Prefer QUIC. A QUIC connection replaces TCP connections to the same peer.
+    Quic,
+    /// Prefer TCP. A TCP connection replaces QUIC connections to the same peer.
+    Tcp,
+}
+
+impl TransportPreference {
+    /// Is the transport of the given address preferred? Always true for `Either`.
+    pub(super) fn is_preferred(&self, addr: &PeerAddr) -> bool {
+        match self {
+            Self::Either => true,
+            Self::Quic => addr.is_quic(),
+            Self::Tcp => addr.is_tcp(),
+        }
+    }
+
+    /// Resolves the preference that applies to the connections with a peer, given ours and
+    /// theirs (`None` if the peer is too old to advertise it). Both sides resolve to the same
+    /// preference: a preference applies if the other side has none, and if the two conflict, the
+    /// side with the lower runtime id decides. Otherwise both sides would keep closing each
+    /// other's connections.
+    pub(super) fn negotiate(self, theirs: Option<Self>, ours_decides: bool) -> Self {
+        match theirs {
+            None | Some(Self::Either) => self,
+            Some(theirs) if self == Self::Either || !ours_decides => theirs,
+            Some(_) => self,
+        }
+    }
+}
+
+/// Sends our transport preference to the peer and receives theirs. An unknown value received
+/// from the peer is treated as no preference.
+pub(super) async fn exchange<IO>(
+    ours: TransportPreference,
+    io: &mut IO,
+) -> io::Result<TransportPreference>
+where
+    IO: AsyncRead + AsyncWrite + Unpin,
+{
+    io.write_all(&[ours.into()]).await?;
+
+    let mut theirs = [0; 1];
+    io.read_exact(&mut theirs).await?;
+
+    Ok(TransportPreference::try_from(theirs[0]).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TransportPreference::*;
+
+    #[test]
+    fn negotiate() {
+        for ours in [Either, Quic, Tcp] {
+            assert_eq!(ours.negotiate(None, false), ours);
+            assert_eq!(ours.negotiate(Some(Either), false), ours);
+            assert_eq!(Either.negotiate(Some(ours), false), ours);
+            assert_eq!(ours.negotiate(Some(ours), false), ours);
+        }
+
+        // Conflicting preferences resolve the same on both sides.
+        assert_eq!(Quic.negotiate(Some(Tcp), true), Quic);
+        assert_eq!(Tcp.negotiate(Some(Quic), false), Quic);
+        assert_eq!(Quic.negotiate(Some(Tcp), false), Tcp);
+        assert_eq!(Tcp.negotiate(Some(Quic), true), Tcp);
+    }
+}
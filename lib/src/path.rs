@@ -1,6 +1,6 @@
 //! Utilities for working with filesystem paths.
 
-use camino::Utf8Path;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 
 /// Decomposes `path` into parent and filename. Returns `None` if `path` doesn't have parent
 /// (it's the root).
@@ -10,3 +10,25 @@ pub fn decompose(path: &Utf8Path) -> Option<(&Utf8Path, &str)> {
         _ => None,
     }
 }
+
+/// Lexically normalizes `path` by removing `.` components and resolving `..` components against
+/// the preceding ones. Returns `None` if `path` would escape the root.
+pub fn normalize(path: &Utf8Path) -> Option<Utf8PathBuf> {
+    let mut output = Utf8PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => (),
+            Utf8Component::ParentDir => {
+                if !output.pop() {
+                    return None;
+                }
+            }
+            Utf8Component::RootDir | Utf8Component::Prefix(_) | Utf8Component::Normal(_) => {
+                output.push(component)
+            }
+        }
+    }
+
+    Some(output)
+}
@@ -28,6 +28,12 @@ impl StorageSize {
     //     self.bytes / BLOCK_RECORD_SIZE
     // }
 
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        Self {
+            bytes: self.bytes.saturating_add(rhs.bytes),
+        }
+    }
+
     pub fn saturating_sub(self, rhs: Self) -> Self {
         Self {
             bytes: self.bytes.saturating_sub(rhs.bytes),
@@ -0,0 +1,301 @@
+//! Offline transfer of the repository index and blocks through a byte stream (e.g., a file on a
+//! removable drive).
+//!
+//! The archive consists of a header (magic bytes, format version and repository id) followed by a
+//! sequence of length-prefixed records. The records are ordered so that every node comes after its
+//! parent which allows importing them the same way they'd be received from a remote replica.
+
+use super::Vault;
+use crate::{
+    error::{Error, Result},
+    event::Payload,
+    protocol::{
+        Block, BlockContent, BlockId, BlockNonce, InnerNodes, LeafNodes, MultiBlockPresence,
+        ProofError, RepositoryId, RootNodeFilter, UntrustedProof, BLOCK_SIZE,
+    },
+    store::{self, ClientWriter},
+};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    io,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const MAGIC: &[u8; 8] = b"OUISYNCA";
+const VERSION: u8 = 1;
+
+// Upper bound on the size of a single serialized record. Guards against allocating huge buffers
+// when reading corrupted archives.
+const MAX_RECORD_SIZE: u32 = 16 * 1024 * 1024;
+
+// How many records to import in a single transaction.
+const IMPORT_BATCH_SIZE: usize = 64;
+
+// How many blocks to read into memory at a time when exporting.
+const EXPORT_BATCH_SIZE: usize = 64;
+
+#[derive(Serialize, Deserialize)]
+enum Record {
+    RootNode(UntrustedProof, MultiBlockPresence),
+    InnerNodes(InnerNodes),
+    LeafNodes(LeafNodes),
+    Block(BlockId, BlockContent, BlockNonce),
+    End,
+}
+
+/// Writes the latest approved snapshots of all branches together with all their locally present
+/// blocks into `dst`.
+///
+/// The index is first loaded into memory within a single read transaction so the exported
+/// snapshots are consistent, and only then written out. The blocks are read in batches, each in
+/// its own short transaction (blocks are immutable so this doesn't affect consistency). This way
+/// no transaction is held open while waiting on a possibly slow `dst`.
+pub(super) async fn export<W>(vault: &Vault, dst: &mut W) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let (records, block_ids) = load_index(vault).await?;
+
+    dst.write_all(MAGIC).await.map_err(Error::Writer)?;
+    dst.write_u8(VERSION).await.map_err(Error::Writer)?;
+    dst.write_all(vault.repository_id().as_ref())
+        .await
+        .map_err(Error::Writer)?;
+
+    for record in &records {
+        write_record(dst, record).await?;
+    }
+
+    for chunk in block_ids.chunks(EXPORT_BATCH_SIZE) {
+        let mut reader = vault.store().acquire_read().await?;
+        let mut records = Vec::with_capacity(chunk.len());
+
+        for block_id in chunk {
+            let mut content = BlockContent::new();
+            let nonce = match reader.read_block(block_id, &mut content).await {
+                Ok(nonce) => nonce,
+                // Not present locally or removed since the index was loaded.
+                Err(store::Error::BlockNotFound) => continue,
+                Err(error) => return Err(error.into()),
+            };
+
+            records.push(Record::Block(*block_id, content, nonce));
+        }
+
+        drop(reader);
+
+        for record in &records {
+            write_record(dst, record).await?;
+        }
+    }
+
+    write_record(dst, &Record::End).await?;
+    dst.flush().await.map_err(Error::Writer)?;
+
+    Ok(())
+}
+
+/// Loads the node records of the latest approved snapshots of all branches (ordered so every node
+/// comes after its parent) and the ids of the blocks they reference.
+async fn load_index(vault: &Vault) -> Result<(Vec<Record>, Vec<BlockId>)> {
+    // Use a single transaction so the exported snapshots are consistent.
+    let mut tx = vault.store().begin_read().await?;
+
+    let writer_ids: Vec<_> = tx.load_writer_ids().try_collect().await?;
+    let mut records = Vec::new();
+    let mut queue = VecDeque::new();
+    let mut visited = HashSet::new();
+    let mut block_ids = HashSet::new();
+
+    for writer_id in writer_ids {
+        let root_node = match tx
+            .load_latest_approved_root_node(&writer_id, RootNodeFilter::Published)
+            .await
+        {
+            Ok(node) => node,
+            Err(store::Error::BranchNotFound) => continue,
+            Err(error) => return Err(error.into()),
+        };
+
+        if visited.insert(root_node.proof.hash) {
+            queue.push_back(root_node.proof.hash);
+        }
+
+        records.push(Record::RootNode(
+            root_node.proof.into(),
+            root_node.summary.block_presence,
+        ));
+    }
+
+    while let Some(parent_hash) = queue.pop_front() {
+        // At most one of these is non-empty.
+        let inner_nodes = tx.load_inner_nodes(&parent_hash).await?;
+        let leaf_nodes = tx.load_leaf_nodes(&parent_hash).await?;
+
+        if !inner_nodes.is_empty() {
+            for (_, node) in inner_nodes.iter() {
+                if visited.insert(node.hash) {
+                    queue.push_back(node.hash);
+                }
+            }
+
+            records.push(Record::InnerNodes(inner_nodes));
+        }
+
+        if !leaf_nodes.is_empty() {
+            block_ids.extend(leaf_nodes.iter().map(|node| node.block_id));
+            records.push(Record::LeafNodes(leaf_nodes));
+        }
+    }
+
+    Ok((records, block_ids.into_iter().collect()))
+}
+
+/// Reads an archive previously created with [`export`] and merges its content into the
+/// repository.
+pub(super) async fn import<R>(vault: &Vault, src: &mut R) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut magic = [0; MAGIC.len()];
+    src.read_exact(&mut magic).await.map_err(read_error)?;
+
+    if &magic != MAGIC {
+        return Err(Error::MalformedData);
+    }
+
+    if src.read_u8().await.map_err(read_error)? != VERSION {
+        return Err(Error::ArchiveVersionMismatch);
+    }
+
+    let mut repository_id = [0; RepositoryId::SIZE];
+    src.read_exact(&mut repository_id)
+        .await
+        .map_err(read_error)?;
+
+    if repository_id != vault.repository_id().as_ref() {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut writer = vault.store().begin_client_write().await?;
+    let mut batch_len = 0;
+
+    loop {
+        match read_record(src).await? {
+            Record::RootNode(proof, block_presence) => {
+                let proof = match proof.verify(vault.repository_id()) {
+                    Ok(proof) => proof,
+                    Err(ProofError(_)) => {
+                        tracing::trace!("Invalid proof");
+                        continue;
+                    }
+                };
+
+                // Ignore branches with empty version vectors because they have no content yet.
+                if proof.version_vector.is_empty() {
+                    continue;
+                }
+
+                writer.save_root_node(proof, &block_presence).await?;
+            }
+            Record::InnerNodes(nodes) => {
+                writer.save_inner_nodes(nodes.into()).await?;
+            }
+            Record::LeafNodes(nodes) => {
+                writer.save_leaf_nodes(nodes.into()).await?;
+            }
+            Record::Block(block_id, content, nonce) => {
+                if content.len() != BLOCK_SIZE {
+                    return Err(Error::MalformedData);
+                }
+
+                let block = Block::new(content, nonce);
+
+                if block.id != block_id {
+                    return Err(Error::MalformedData);
+                }
+
+                writer.save_block(&block, None).await?;
+            }
+            Record::End => break,
+        }
+
+        batch_len += 1;
+
+        if batch_len >= IMPORT_BATCH_SIZE {
+            commit(vault, writer).await?;
+            writer = vault.store().begin_client_write().await?;
+            batch_len = 0;
+        }
+    }
+
+    commit(vault, writer).await
+}
+
+async fn commit(vault: &Vault, writer: ClientWriter) -> Result<()> {
+    let event_tx = vault.event_tx.clone();
+    let status = writer
+        .commit_and_then(move |status| {
+            for block_id in &status.new_blocks {
+                event_tx.send(Payload::BlockReceived(*block_id));
+            }
+
+            for branch_id in &status.approved_branches {
+                event_tx.send(Payload::SnapshotApproved(*branch_id));
+            }
+
+            for branch_id in &status.rejected_branches {
+                event_tx.send(Payload::SnapshotRejected(*branch_id));
+            }
+
+            status
+        })
+        .await?;
+
+    for block_id in status.approved_missing_blocks {
+        vault.block_tracker.approve(block_id);
+    }
+
+    Ok(())
+}
+
+async fn write_record<W>(dst: &mut W, record: &Record) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    // unwrap is OK because serializing into a `Vec` can't fail.
+    let buffer = bincode::serialize(record).unwrap();
+
+    dst.write_u32(buffer.len() as u32)
+        .await
+        .map_err(Error::Writer)?;
+    dst.write_all(&buffer).await.map_err(Error::Writer)?;
+
+    Ok(())
+}
+
+async fn read_record<R>(src: &mut R) -> Result<Record>
+where
+    R: AsyncRead + Unpin,
+{
+    let len = src.read_u32().await.map_err(read_error)?;
+
+    if len > MAX_RECORD_SIZE {
+        return Err(Error::MalformedData);
+    }
+
+    let mut buffer = vec![0; len as usize];
+    src.read_exact(&mut buffer).await.map_err(read_error)?;
+
+    bincode::deserialize(&buffer).map_err(|_| Error::MalformedData)
+}
+
+fn read_error(error: io::Error) -> Error {
+    match error.kind() {
+        // Archive is truncated.
+        io::ErrorKind::UnexpectedEof => Error::MalformedData,
+        _ => Error::Reader(error),
+    }
+}
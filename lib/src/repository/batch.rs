@@ -0,0 +1,188 @@
+//! Atomic writes to multiple files.
+
+use crate::{
+    branch::Branch,
+    directory::{Directory, DirectoryFallback, EntryRef},
+    error::{Error, Result},
+    file::File,
+    path,
+};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+
+/// Set of files whose modifications are committed atomically, in a single snapshot of the local
+/// branch. Remote peers observe either all of the modifications or none of them.
+///
+/// Files are added to the batch using [`Self::create_file`] or [`Self::add_file`] and then
+/// modified as usual. All the files are flushed together by [`Self::commit`], calling
+/// [`File::flush`] on a batched file fails with `OperationNotSupported`. The missing parent
+/// directories of the created files are created by the commit as well. If the commit fails, none
+/// of the modifications are applied. Dropping the batch without committing it discards the
+/// modifications.
+///
+/// Limitations:
+///
+/// - The forks of the added files (if they don't already live in the local branch) are committed
+///   immediately, not as part of the batch. They don't change the content of the files.
+/// - The pending modifications of each file are kept in memory so a single batched file can't
+///   have more than 64 MiB of them. Writing more fails with `OperationNotSupported`.
+pub struct Batch {
+    branch: Branch,
+    // Directories to be created by the commit, parents before their children.
+    directories: Vec<(Utf8PathBuf, Directory)>,
+    entries: Vec<BatchEntry>,
+}
+
+struct BatchEntry {
+    path: Utf8PathBuf,
+    file: File,
+    // Whether the file was created by this batch and so its entry is not yet in the parent
+    // directory.
+    created: bool,
+}
+
+impl Batch {
+    pub(super) fn new(branch: Branch) -> Self {
+        Self {
+            branch,
+            directories: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Creates a new file at the given path (relative to the repository root). The file becomes
+    /// visible only after the batch is committed. Fails with `EntryExists` if the file already
+    /// exists or has already been added to this batch.
+    pub async fn create_file<P: AsRef<Utf8Path>>(&mut self, path: P) -> Result<&mut File> {
+        let path = path.as_ref();
+
+        if self.position(path).is_some() {
+            return Err(Error::EntryExists);
+        }
+
+        let (parent, name) = path::decompose(path).ok_or(Error::EntryIsDirectory)?;
+        let mut file = self
+            .prepare_directory(parent)
+            .await?
+            .prepare_file(name.to_owned())?;
+        file.set_batched();
+
+        Ok(self.insert(path, file, true))
+    }
+
+    /// Adds an existing file at the given path (e.g., opened with `Repository::open_file`) to this
+    /// batch. The file is forked into the local branch first if needed.
+    /// If a file with the same path has already been added to this batch, returns that file
+    /// instead.
+    pub async fn add_file<P: AsRef<Utf8Path>>(
+        &mut self,
+        path: P,
+        mut file: File,
+    ) -> Result<&mut File> {
+        let path = path.as_ref();
+
+        if let Some(index) = self.position(path) {
+            return Ok(&mut self.entries[index].file);
+        }
+
+        file.fork(self.branch.clone()).await?;
+        file.set_batched();
+
+        Ok(self.insert(path, file, false))
+    }
+
+    /// Returns the file at the given index. Files are indexed in the order they were added to this
+    /// batch.
+    pub fn file_mut(&mut self, index: usize) -> Option<&mut File> {
+        self.entries.get_mut(index).map(|entry| &mut entry.file)
+    }
+
+    /// Returns the index of the file with the given path, if it's been added to this batch.
+    pub fn position<P: AsRef<Utf8Path>>(&self, path: P) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.path == path.as_ref())
+    }
+
+    /// Number of files in this batch.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Atomically saves the pending modifications of all the files in this batch and updates the
+    /// version vectors of the files and all their ancestors.
+    pub async fn commit(mut self) -> Result<()> {
+        let mut tx = self.branch.store().begin_write().await?;
+
+        for (_, directory) in &mut self.directories {
+            directory.insert_in(&mut tx).await?;
+        }
+
+        for entry in &mut self.entries {
+            if entry.created {
+                entry.file.insert_in(&mut tx).await?;
+            } else {
+                entry.file.flush_in(&mut tx).await?;
+            }
+        }
+
+        let event_tx = self.branch.notify();
+        tx.commit_and_then(move || event_tx.send()).await?;
+
+        Ok(())
+    }
+
+    // Returns the directory at the given path. If it or any of its ancestors don't exist yet, they
+    // are prepared to be created by the commit.
+    async fn prepare_directory(&mut self, path: &Utf8Path) -> Result<Directory> {
+        let mut curr = self.branch.open_or_create_root().await?;
+        let mut curr_path = Utf8PathBuf::new();
+
+        for component in path.components() {
+            let name = match component {
+                Utf8Component::RootDir | Utf8Component::CurDir => continue,
+                Utf8Component::Normal(name) => name,
+                Utf8Component::Prefix(_) | Utf8Component::ParentDir => {
+                    return Err(Error::OperationNotSupported)
+                }
+            };
+
+            curr_path.push(name);
+
+            if let Some((_, directory)) =
+                self.directories.iter().find(|(path, _)| *path == curr_path)
+            {
+                curr = directory.clone();
+                continue;
+            }
+
+            curr = match curr.lookup(name) {
+                Ok(EntryRef::Directory(entry)) => entry.open(DirectoryFallback::Disabled).await?,
+                Ok(EntryRef::File(_)) => return Err(Error::EntryIsFile),
+                Ok(EntryRef::Link(_)) => return Err(Error::EntryIsLink),
+                Ok(EntryRef::Tombstone(_)) | Err(Error::EntryNotFound) => {
+                    let directory = curr.prepare_directory(name.to_owned())?;
+                    self.directories
+                        .push((curr_path.clone(), directory.clone()));
+                    directory
+                }
+                Err(error) => return Err(error),
+            };
+        }
+
+        Ok(curr)
+    }
+
+    fn insert(&mut self, path: &Utf8Path, file: File, created: bool) -> &mut File {
+        self.entries.push(BatchEntry {
+            path: path.to_owned(),
+            file,
+            created,
+        });
+
+        &mut self.entries.last_mut().unwrap().file
+    }
+}
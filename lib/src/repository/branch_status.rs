@@ -0,0 +1,51 @@
+//! Sync status of the individual branches of a repository.
+
+use super::Shared;
+use crate::{
+    crypto::sign::PublicKey, error::Result, protocol::NodeState, version_vector::VersionVector,
+};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+/// Sync status of a single branch (writer) of a repository.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BranchStatus {
+    /// Id of the branch (the writer's public key).
+    pub branch_id: PublicKey,
+    /// Version vector of the latest known snapshot of the branch.
+    pub version_vector: VersionVector,
+    /// Whether the index of the latest snapshot has been completely downloaded.
+    pub is_complete: bool,
+    /// Number of blocks referenced from the latest snapshot that are not yet downloaded. If the
+    /// snapshot is not complete, this counts only the part of it that's been downloaded so far.
+    pub missing_blocks: u64,
+}
+
+/// Collects the status of all the branches known to the repository. Uses only the index so it
+/// works in any access mode, including blind.
+pub(super) async fn collect(shared: &Shared) -> Result<Vec<BranchStatus>> {
+    let mut reader = shared.vault.store().acquire_read().await?;
+
+    let nodes: Vec<_> = reader
+        .load_latest_preferred_root_nodes()
+        .try_collect()
+        .await?;
+    let mut statuses = Vec::with_capacity(nodes.len());
+
+    for node in nodes {
+        let missing_blocks = reader.count_missing_blocks_in(&node.proof.hash).await?;
+        let is_complete = matches!(
+            node.summary.state,
+            NodeState::Complete | NodeState::Approved
+        );
+
+        statuses.push(BranchStatus {
+            branch_id: node.proof.writer_id,
+            version_vector: node.proof.version_vector.clone(),
+            is_complete,
+            missing_blocks,
+        });
+    }
+
+    Ok(statuses)
+}
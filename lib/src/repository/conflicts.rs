@@ -0,0 +1,175 @@
+//! Finding and resolving files with concurrent versions.
+
+use super::Repository;
+use crate::{
+    crypto::sign::PublicKey,
+    error::{Error, Result},
+    joint_directory::JointEntryRef,
+    path,
+    version_vector::VersionVector,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::collections::VecDeque;
+
+/// File with two or more concurrent versions.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Conflict {
+    /// Path of the file (without the disambiguation suffix).
+    pub path: Utf8PathBuf,
+    /// The concurrent versions of the file.
+    pub versions: Vec<ConflictVersion>,
+}
+
+/// Single version of a conflicting file.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ConflictVersion {
+    /// Path under which this version can be opened (with the disambiguation suffix).
+    pub unique_path: Utf8PathBuf,
+    /// Branch this version belongs to.
+    pub branch_id: PublicKey,
+    pub version_vector: VersionVector,
+}
+
+/// Finds all files in the repository that have concurrent versions. Conflicts between files and
+/// directories are not reported.
+pub(super) async fn find(repo: &Repository) -> Result<Vec<Conflict>> {
+    let mut conflicts = Vec::new();
+    let mut dirs = VecDeque::from([Utf8PathBuf::from("/")]);
+
+    while let Some(dir_path) = dirs.pop_front() {
+        let dir = repo.cd(&dir_path).await?;
+        let mut current: Option<Conflict> = None;
+
+        // Entries are sorted by name so all versions of the same file are adjacent.
+        for entry in dir.entries() {
+            let path = dir_path.join(entry.name());
+
+            let entry = match entry {
+                JointEntryRef::File(entry) => entry,
+                JointEntryRef::Directory(_) => {
+                    dirs.push_back(path);
+                    continue;
+                }
+                JointEntryRef::Link(_) => continue,
+            };
+
+            let version = ConflictVersion {
+                unique_path: dir_path.join(entry.unique_name().as_ref()),
+                branch_id: *entry.branch().id(),
+                version_vector: entry.version_vector().clone(),
+            };
+
+            match &mut current {
+                Some(conflict) if conflict.path == path => conflict.versions.push(version),
+                _ => {
+                    conflicts.extend(current.take().filter(|c| c.versions.len() > 1));
+                    current = Some(Conflict {
+                        path,
+                        versions: vec![version],
+                    });
+                }
+            }
+        }
+
+        conflicts.extend(current.filter(|c| c.versions.len() > 1));
+    }
+
+    Ok(conflicts)
+}
+
+/// Checks whether the file at `path` has concurrent versions, that is, versions whose version
+/// vectors are neither ancestors nor descendants of each other. Versions that are outdated by
+/// another version are not included. Only the index is read, not the file content. Returns `None`
+/// if there is no conflict or if the entry is a directory (directories are merged automatically).
+pub(super) async fn detect(repo: &Repository, path: &Utf8Path) -> Result<Option<Conflict>> {
+    let (parent, name) = match path::decompose(path) {
+        Some(components) => components,
+        None => return Ok(None),
+    };
+
+    let dir = repo.cd(parent).await?;
+    let mut versions = Vec::new();
+
+    for entry in dir.lookup(name) {
+        match entry {
+            JointEntryRef::File(entry) => versions.push(ConflictVersion {
+                unique_path: parent.join(entry.unique_name().as_ref()),
+                branch_id: *entry.branch().id(),
+                version_vector: entry.version_vector().clone(),
+            }),
+            JointEntryRef::Directory(_) | JointEntryRef::Link(_) => continue,
+        }
+    }
+
+    if versions.is_empty() {
+        return Err(Error::EntryNotFound);
+    }
+
+    let versions: Vec<_> = versions
+        .iter()
+        .filter(|version| {
+            !versions
+                .iter()
+                .any(|other| other.version_vector > version.version_vector)
+        })
+        .cloned()
+        .collect();
+
+    if versions.len() > 1 {
+        Ok(Some(Conflict {
+            path: parent.join(name),
+            versions,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Replaces the content of the local version of the file at `path` with `content` and makes it
+/// supersede all the currently known versions of the file, which resolves the conflict. If there
+/// is no local version yet, one is forked first.
+pub(super) async fn resolve(repo: &Repository, path: &Utf8Path, content: &[u8]) -> Result<()> {
+    let local_branch = repo.local_branch()?;
+    let (parent, name) = path::decompose(path).ok_or(Error::EntryIsDirectory)?;
+
+    let mut merge = VersionVector::new();
+    let mut found = false;
+    let mut local_exists = false;
+
+    {
+        let dir = repo.cd(parent).await?;
+
+        for entry in dir.lookup(name) {
+            let entry = match entry {
+                JointEntryRef::File(entry) => entry,
+                JointEntryRef::Directory(_) => return Err(Error::EntryIsDirectory),
+                JointEntryRef::Link(_) => return Err(Error::EntryIsLink),
+            };
+
+            merge.merge(entry.version_vector());
+            found = true;
+
+            if entry.branch().id() == local_branch.id() {
+                local_exists = true;
+            }
+        }
+
+        if !found {
+            return Err(Error::EntryNotFound);
+        }
+
+        if !local_exists {
+            // unwrap is OK because we already checked there is at least one version.
+            let entry = dir.lookup(name).next().unwrap();
+
+            if let JointEntryRef::File(entry) = entry {
+                entry.fork(&local_branch).await?;
+            }
+        }
+    }
+
+    let mut file = repo.open_file_version(path, local_branch.id()).await?;
+    file.truncate(0)?;
+    file.write_all(content).await?;
+    file.flush_merged(merge).await
+}
@@ -22,6 +22,10 @@ impl Credentials {
         }
     }
 
+    pub(crate) fn secrets(&self) -> &AccessSecrets {
+        &self.secrets
+    }
+
     pub fn with_mode(self, access_mode: AccessMode) -> Self {
         Self {
             secrets: self.secrets.with_mode(access_mode),
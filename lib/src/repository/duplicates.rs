@@ -0,0 +1,150 @@
+//! Finding files with identical content.
+
+use super::Repository;
+use crate::{
+    crypto::Hash,
+    error::{Error, Result},
+    file::File,
+    joint_directory::JointEntryRef,
+    progress::Progress,
+    store,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use futures_util::{future, stream, Stream, StreamExt, TryStreamExt};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Event emitted while searching for duplicates.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum DuplicatesSearchEvent {
+    /// Number of files hashed so far out of all the files that share their length with some other
+    /// file (the only ones that need to be hashed). Emitted once the files have been listed and
+    /// then after each batch of files of the same length.
+    Progress(Progress),
+    /// Group of paths of files with identical content.
+    Group(Vec<Utf8PathBuf>),
+}
+
+/// Finds groups of files with identical content. The files are first grouped by their length and
+/// only the files that share their length with some other file are then read and hashed. The
+/// groups are yielded one by one as they are found so only the candidates of a single length are
+/// kept in memory at a time. Dropping the returned stream cancels the search.
+///
+/// Empty files and files that are not fully downloaded are skipped.
+pub(super) fn find(repo: &Repository) -> impl Stream<Item = Result<Vec<Utf8PathBuf>>> + '_ {
+    find_with_progress(repo).try_filter_map(|event| {
+        future::ready(Ok(match event {
+            DuplicatesSearchEvent::Group(group) => Some(group),
+            DuplicatesSearchEvent::Progress(_) => None,
+        }))
+    })
+}
+
+/// Like `find` but interleaves the groups with the progress of the search.
+pub(super) fn find_with_progress(
+    repo: &Repository,
+) -> impl Stream<Item = Result<DuplicatesSearchEvent>> + '_ {
+    stream::once(collect_candidates(repo))
+        .map_ok(move |candidates| {
+            let candidates: Vec<_> = candidates
+                .into_values()
+                .filter(|paths| paths.len() > 1)
+                .collect();
+            let total = candidates.iter().map(|paths| paths.len() as u64).sum();
+
+            // Pair each batch with the number of files hashed once it's done.
+            let batches = candidates.into_iter().scan(0, |value, paths| {
+                *value += paths.len() as u64;
+                Some((*value, paths))
+            });
+
+            let start = DuplicatesSearchEvent::Progress(Progress { value: 0, total });
+
+            stream::once(future::ready(Ok(start))).chain(
+                stream::iter(batches)
+                    .then(move |(value, paths)| async move {
+                        let groups = group_by_content(repo, paths).await?;
+                        let progress = DuplicatesSearchEvent::Progress(Progress { value, total });
+
+                        Ok::<_, Error>(stream::iter(
+                            groups
+                                .into_iter()
+                                .map(DuplicatesSearchEvent::Group)
+                                .chain([progress])
+                                .map(Ok),
+                        ))
+                    })
+                    .try_flatten(),
+            )
+        })
+        .try_flatten()
+}
+
+/// Collects paths of all non-empty files in the repository, grouped by their length.
+async fn collect_candidates(repo: &Repository) -> Result<BTreeMap<u64, Vec<Utf8PathBuf>>> {
+    let mut candidates: BTreeMap<u64, Vec<Utf8PathBuf>> = BTreeMap::new();
+    let mut dirs = VecDeque::from([Utf8PathBuf::from("/")]);
+
+    while let Some(dir_path) = dirs.pop_front() {
+        let mut files = Vec::new();
+
+        for entry in repo.cd(&dir_path).await?.entries() {
+            let path = dir_path.join(entry.unique_name().as_ref());
+
+            match entry {
+                JointEntryRef::File(_) => files.push(path),
+                JointEntryRef::Directory(_) => dirs.push_back(path),
+                JointEntryRef::Link(_) => (),
+            }
+        }
+
+        for path in files {
+            let Some(file) = open_file(repo, &path).await? else {
+                continue;
+            };
+
+            let len = file.len();
+
+            if len > 0 {
+                candidates.entry(len).or_default().push(path);
+            }
+        }
+    }
+
+    Ok(candidates)
+}
+
+/// Groups the given files (all of the same length) by their content. Returns only the groups
+/// with more than one file.
+async fn group_by_content(
+    repo: &Repository,
+    paths: Vec<Utf8PathBuf>,
+) -> Result<Vec<Vec<Utf8PathBuf>>> {
+    let mut groups: HashMap<Hash, Vec<Utf8PathBuf>> = HashMap::new();
+
+    for path in paths {
+        let Some(mut file) = open_file(repo, &path).await? else {
+            continue;
+        };
+
+        match file.content_hash().await {
+            Ok(hash) => groups.entry(hash).or_default().push(path),
+            Err(Error::Store(store::Error::BlockNotFound)) => continue,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect())
+}
+
+/// Opens the file at the given path. Returns `None` if the file no longer exists or if it's not
+/// (fully) downloaded yet.
+async fn open_file(repo: &Repository, path: &Utf8Path) -> Result<Option<File>> {
+    match repo.open_file(path).await {
+        Ok(file) => Ok(Some(file)),
+        Err(Error::EntryNotFound | Error::Store(store::Error::BlockNotFound)) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
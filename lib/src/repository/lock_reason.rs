@@ -0,0 +1,28 @@
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
+
+/// Reason why a repository is not in the highest access mode it could be in.
+///
+/// Note that because the repository obfuscates its secret keys (to provide plausible deniability),
+/// it's in general not possible to tell whether a locked mode is actually protected by a password
+/// or whether it has been disabled. `NeedsReadPassword` and `NeedsWritePassword` are thus reported
+/// in both cases.
+#[derive(
+    Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize, IntoPrimitive, TryFromPrimitive,
+)]
+#[repr(u8)]
+#[serde(into = "u8", try_from = "u8")]
+pub enum LockReason {
+    /// Repository is unlocked - either in write mode or in the highest mode available without
+    /// providing a password.
+    Unlocked = 0,
+    /// Repository is blind and reading it requires a password.
+    NeedsReadPassword = 1,
+    /// Repository is readable and writing to it requires a password.
+    NeedsWritePassword = 2,
+    /// The last attempt to unlock the repository used a password that didn't unlock anything.
+    WrongPassword = 3,
+    /// Repository is blind and there are no read secrets stored in it at all, so it can't be
+    /// unlocked with any password (only by a share token).
+    NoReadSecrets = 4,
+}
@@ -35,6 +35,11 @@ const READ_KEY_VALIDATOR: &[u8] = b"read_key_validator";
 
 const QUOTA: &[u8] = b"quota";
 const BLOCK_EXPIRATION: &[u8] = b"block_expiration";
+const POOL_WARMUP: &[u8] = b"pool_warmup";
+const SNAPSHOT_RETENTION: &[u8] = b"snapshot_retention";
+const MERGE_STRATEGY: &[u8] = b"merge_strategy";
+const PINNED_BLOBS: &[u8] = b"pinned_blobs";
+const BRANCH_SYNC_TIMES: &[u8] = b"branch_sync_times";
 
 // Support for data migrations.
 const DATA_VERSION: &[u8] = b"data_version";
@@ -463,6 +468,20 @@ pub(crate) async fn requires_local_secret_for_writing(
     }
 }
 
+/// Returns whether any secret (local secret protected) keys are stored in the repository. Note
+/// that disabled keys are obfuscated rather than removed so this returns `false` only if nothing
+/// has been stored at all (e.g., in databases created by some old versions).
+pub(crate) async fn has_secret_keys(conn: &mut db::Connection) -> Result<bool, StoreError> {
+    let row = sqlx::query("SELECT 1 FROM metadata_secret WHERE name IN (?, ?, ?) LIMIT 1")
+        .bind(READ_KEY)
+        .bind(WRITE_KEY)
+        .bind(DEPRECATED_ACCESS_KEY)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(row.is_some())
+}
+
 pub(crate) async fn initialize_access_secrets<'a>(
     tx: &mut db::WriteTransaction,
     access: &'a Access,
@@ -726,6 +745,180 @@ pub(crate) mod block_expiration {
     }
 }
 
+// -------------------------------------------------------------------
+// Pool warmup
+// -------------------------------------------------------------------
+pub(crate) mod pool_warmup {
+    use super::*;
+
+    pub(crate) async fn get(conn: &mut db::Connection) -> Result<u32, StoreError> {
+        Ok(get_public::<u64>(conn, POOL_WARMUP)
+            .await?
+            .map(|value| u32::try_from(value).unwrap_or(u32::MAX))
+            .unwrap_or(0))
+    }
+
+    pub(crate) async fn set(tx: &mut db::WriteTransaction, value: u32) -> Result<(), StoreError> {
+        if value > 0 {
+            set_public(tx, POOL_WARMUP, u64::from(value)).await
+        } else {
+            remove_public(tx, POOL_WARMUP).await
+        }
+    }
+}
+
+// -------------------------------------------------------------------
+// Snapshot retention
+// -------------------------------------------------------------------
+pub(crate) mod snapshot_retention {
+    use super::*;
+
+    /// Number of complete snapshots retained per branch unless configured otherwise. Unlimited by
+    /// default because removing the snapshots that still serve as fallback could break syncing of
+    /// the newer ones, which must be opted into explicitly.
+    pub(crate) const DEFAULT: u32 = 0;
+
+    /// Zero means unlimited.
+    pub(crate) async fn get(conn: &mut db::Connection) -> Result<u32, StoreError> {
+        Ok(get_public::<u64>(conn, SNAPSHOT_RETENTION)
+            .await?
+            .map(|value| u32::try_from(value).unwrap_or(u32::MAX))
+            .unwrap_or(DEFAULT))
+    }
+
+    pub(crate) async fn set(tx: &mut db::WriteTransaction, value: u32) -> Result<(), StoreError> {
+        if value != DEFAULT {
+            set_public(tx, SNAPSHOT_RETENTION, u64::from(value)).await
+        } else {
+            remove_public(tx, SNAPSHOT_RETENTION).await
+        }
+    }
+}
+
+// -------------------------------------------------------------------
+// Directory merge strategy
+// -------------------------------------------------------------------
+pub(crate) mod merge_strategy {
+    use super::*;
+    use crate::joint_directory::MergeStrategy;
+
+    pub(crate) async fn get(conn: &mut db::Connection) -> Result<MergeStrategy, StoreError> {
+        Ok(get_public::<u64>(conn, MERGE_STRATEGY)
+            .await?
+            .and_then(|value| u8::try_from(value).ok())
+            .and_then(|value| MergeStrategy::try_from(value).ok())
+            .unwrap_or_default())
+    }
+
+    pub(crate) async fn set(
+        tx: &mut db::WriteTransaction,
+        value: MergeStrategy,
+    ) -> Result<(), StoreError> {
+        if value != MergeStrategy::default() {
+            set_public(tx, MERGE_STRATEGY, u64::from(u8::from(value))).await
+        } else {
+            remove_public(tx, MERGE_STRATEGY).await
+        }
+    }
+}
+
+// -------------------------------------------------------------------
+// Pinned blobs
+// -------------------------------------------------------------------
+pub(crate) mod pinned_blobs {
+    use super::*;
+    use crate::blob::BlobId;
+    use std::collections::BTreeSet;
+
+    pub(crate) async fn get(conn: &mut db::Connection) -> Result<BTreeSet<BlobId>, StoreError> {
+        let Some(bytes) = get_public_blob::<Vec<u8>>(conn, PINNED_BLOBS).await? else {
+            return Ok(BTreeSet::new());
+        };
+
+        bytes
+            .chunks_exact(BlobId::SIZE)
+            .map(|chunk| BlobId::try_from(chunk).map_err(|_| StoreError::MalformedData))
+            .collect()
+    }
+
+    pub(crate) async fn set(
+        tx: &mut db::WriteTransaction,
+        value: &BTreeSet<BlobId>,
+    ) -> Result<(), StoreError> {
+        if value.is_empty() {
+            remove_public(tx, PINNED_BLOBS).await
+        } else {
+            let bytes: Vec<u8> = value.iter().flat_map(|id| id.as_ref()).copied().collect();
+            set_public_blob(tx, PINNED_BLOBS, bytes).await
+        }
+    }
+}
+
+// -------------------------------------------------------------------
+// Branch sync times
+// -------------------------------------------------------------------
+pub(crate) mod branch_sync_times {
+    use super::*;
+    use crate::crypto::sign::PublicKey;
+    use std::{
+        collections::HashMap,
+        mem,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    const ENTRY_SIZE: usize = PublicKey::SIZE + mem::size_of::<u64>();
+
+    /// Time of the last approved snapshot of each remote branch.
+    pub(crate) async fn get(
+        conn: &mut db::Connection,
+    ) -> Result<HashMap<PublicKey, SystemTime>, StoreError> {
+        let Some(bytes) = get_public_blob::<Vec<u8>>(conn, BRANCH_SYNC_TIMES).await? else {
+            return Ok(HashMap::new());
+        };
+
+        bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|entry| {
+                let (branch_id, millis) = entry.split_at(PublicKey::SIZE);
+                let branch_id =
+                    PublicKey::try_from(branch_id).map_err(|_| StoreError::MalformedData)?;
+                let millis = u64::from_be_bytes(millis.try_into().unwrap());
+
+                Ok((branch_id, UNIX_EPOCH + Duration::from_millis(millis)))
+            })
+            .collect()
+    }
+
+    /// Records `time` as the sync time of the given branches.
+    pub(crate) async fn update(
+        tx: &mut db::WriteTransaction,
+        branch_ids: &[PublicKey],
+        time: SystemTime,
+    ) -> Result<(), StoreError> {
+        if branch_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut times = get(tx).await?;
+        times.extend(branch_ids.iter().map(|branch_id| (*branch_id, time)));
+
+        let mut bytes = Vec::with_capacity(times.len() * ENTRY_SIZE);
+
+        for (branch_id, time) in times {
+            let millis = time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            let millis = u64::try_from(millis).unwrap_or(u64::MAX);
+
+            bytes.extend_from_slice(branch_id.as_ref());
+            bytes.extend_from_slice(&millis.to_be_bytes());
+        }
+
+        set_public_blob(tx, BRANCH_SYNC_TIMES, bytes).await
+    }
+}
+
 // -------------------------------------------------------------------
 // Data version
 // -------------------------------------------------------------------
@@ -1,57 +1,105 @@
+mod archive;
+mod batch;
+mod branch_status;
+mod conflicts;
 mod credentials;
+mod duplicates;
+mod lock_reason;
 mod metadata;
 mod monitor;
 mod params;
+mod pin;
+mod rebuild_index;
+mod rotate;
+mod size_breakdown;
+mod storage_stats;
 mod vault;
+mod watch;
 mod worker;
 
 #[cfg(test)]
 mod tests;
 
-pub use self::{credentials::Credentials, metadata::Metadata, params::RepositoryParams};
+pub use self::{
+    batch::Batch,
+    branch_status::BranchStatus,
+    conflicts::{Conflict, ConflictVersion},
+    credentials::Credentials,
+    duplicates::DuplicatesSearchEvent,
+    lock_reason::LockReason,
+    metadata::Metadata,
+    params::RepositoryParams,
+    rebuild_index::{RebuildIndexEvent, RebuildIndexReport},
+    size_breakdown::SizeBreakdown,
+    storage_stats::StorageStats,
+    watch::{DirEvent, PathEvent},
+};
 
 pub(crate) use self::{
-    metadata::{data_version, quota},
+    metadata::{branch_sync_times, data_version, quota},
     monitor::RepositoryMonitor,
     vault::Vault,
 };
 
 use crate::{
-    access_control::{Access, AccessChange, AccessKeys, AccessMode, AccessSecrets, LocalSecret},
+    access_control::{
+        Access, AccessChange, AccessKeys, AccessMode, AccessSecrets, LocalSecret, SetLocalSecret,
+    },
+    blob::BlobId,
     block_tracker::RequestMode,
     branch::{Branch, BranchShared},
-    crypto::{sign::PublicKey, PasswordSalt},
+    crypto::{cipher, sign::PublicKey, PasswordSalt},
     db::{self, DatabaseId},
     debug::DebugPrinter,
     directory::{Directory, DirectoryFallback, DirectoryLocking, EntryRef, EntryType},
     error::{Error, Result},
-    event::{Event, EventSender},
+    event::{Event, EventSender, LoggedEvent},
     file::File,
-    joint_directory::{JointDirectory, JointEntryRef, MissingVersionStrategy},
+    joint_directory::{JointDirectory, JointEntryRef, MergeStrategy, MissingVersionStrategy},
     path,
     progress::Progress,
-    protocol::{RootNodeFilter, StorageSize, BLOCK_SIZE},
+    protocol::{BlockId, RootNodeFilter, StorageSize, BLOCK_SIZE},
     store,
     sync::stream::Throttle,
     version_vector::VersionVector,
 };
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use deadlock::{BlockingMutex, BlockingRwLock};
 use futures_util::{future, TryStreamExt};
-use futures_util::{stream, StreamExt};
+use futures_util::{stream, Stream, StreamExt};
 use metrics::{NoopRecorder, Recorder};
 use scoped_task::ScopedJoinHandle;
 use state_monitor::StateMonitor;
-use std::{borrow::Cow, io, path::Path, pin::pin, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    io, panic,
+    path::Path,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
 use tokio::{
     fs,
+    io::{AsyncRead, AsyncWrite},
+    runtime,
     sync::broadcast::{self, error::RecvError},
+    task,
     time::Duration,
 };
 use tracing::instrument::Instrument;
 
 const EVENT_CHANNEL_CAPACITY: usize = 10000;
 
+/// Minimal interval between two consecutive `subscribe_sync_progress` updates.
+const SYNC_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Maximum number of links followed when resolving a path. Prevents infinite loops on cyclic links.
+const MAX_LINK_DEPTH: usize = 8;
+
 pub struct Repository {
     shared: Arc<Shared>,
     worker_handle: BlockingMutex<Option<ScopedJoinHandle<()>>>,
@@ -105,20 +153,90 @@ impl Repository {
         Self::new(pool, credentials, monitor).init().await
     }
 
+    /// Like [`Self::create`] but the repository is created on the given runtime so that its
+    /// background tasks (syncing, progress reporting, ...) run there instead of on the runtime the
+    /// caller is running on. Useful when embedding the library in an application that manages
+    /// its own runtime.
+    ///
+    /// Note tasks spawned later by the methods of the returned repository (e.g.,
+    /// [`Self::set_block_expiration`]) run on the runtime those methods are called from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `runtime` shuts down before the repository is created.
+    pub async fn create_on<R>(
+        runtime: &runtime::Handle,
+        params: RepositoryParams<R>,
+        access: Access,
+    ) -> Result<Self>
+    where
+        R: Recorder + Send + Sync + 'static,
+    {
+        join(runtime.spawn(async move { Self::create(&params, access).await })).await
+    }
+
     /// Opens an existing repository.
     pub async fn open(
         params: &RepositoryParams<impl Recorder>,
         local_secret: Option<LocalSecret>,
         access_mode: AccessMode,
     ) -> Result<Self> {
-        let pool = params.open().await?;
+        let (repo, _) =
+            Self::open_with_secrets(params, local_secret.as_slice(), access_mode).await?;
+        Ok(repo)
+    }
+
+    /// Like [`Self::open`] but the repository is opened on the given runtime. See
+    /// [`Self::create_on`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `runtime` shuts down before the repository is opened.
+    pub async fn open_on<R>(
+        runtime: &runtime::Handle,
+        params: RepositoryParams<R>,
+        local_secret: Option<LocalSecret>,
+        access_mode: AccessMode,
+    ) -> Result<Self>
+    where
+        R: Recorder + Send + Sync + 'static,
+    {
+        join(runtime.spawn(async move { Self::open(&params, local_secret, access_mode).await }))
+            .await
+    }
+
+    /// Opens an existing repository trying each of the given local secrets and using the one that
+    /// unlocks the highest access mode (up to `access_mode`). If several secrets unlock the same
+    /// mode, the first one wins. Returns the repository together with the index of the secret
+    /// that was used or `None` if none of them unlocked more than what's accessible without a
+    /// secret. The achieved access mode can be queried with [Self::access_mode].
+    ///
+    /// Fails with `WrongPassword` if some secrets were given but none of them unlocked anything
+    /// and what's accessible without a secret is less than `access_mode`.
+    pub async fn open_with_secrets(
+        params: &RepositoryParams<impl Recorder>,
+        local_secrets: &[LocalSecret],
+        access_mode: AccessMode,
+    ) -> Result<(Self, Option<usize>)> {
+        let pool = params.open().await.map_err(|error| match error {
+            db::Error::NotARepository => Error::NotARepository,
+            error => error.into(),
+        })?;
         let monitor = params.monitor();
         let device_id = params.device_id();
 
         let mut tx = pool.begin_write().await?;
 
-        let (secrets, local_key) =
-            metadata::get_access_secrets(&mut tx, local_secret.as_ref()).await?;
+        let (index, secrets, local_key) = select_local_secret(&mut tx, local_secrets).await?;
+        let unlock_failed =
+            unlock_failed(&mut tx, index.map(|index| &local_secrets[index]), &secrets).await?;
+        let index = index.filter(|_| !unlock_failed);
+
+        if unlock_failed && secrets.access_mode() < access_mode {
+            drop(tx);
+            pool.close().await.ok();
+            return Err(Error::WrongPassword);
+        }
 
         let secrets = secrets.with_mode(access_mode);
 
@@ -147,7 +265,13 @@ impl Repository {
 
         let credentials = Credentials { secrets, writer_id };
 
-        Self::new(pool, credentials, monitor).init().await
+        let repo = Self::new(pool, credentials, monitor);
+        repo.shared
+            .unlock_failed
+            .store(unlock_failed, Ordering::Relaxed);
+        let repo = repo.init().await?;
+
+        Ok((repo, index))
     }
 
     fn new(pool: db::Pool, credentials: Credentials, monitor: RepositoryMonitor) -> Self {
@@ -173,6 +297,10 @@ impl Repository {
                 .await?;
         }
 
+        // Load the pinned blocks before the block expiration starts so they are protected from the
+        // very first expiration pass.
+        pin::refresh(&self.shared).await?;
+
         {
             let mut conn = self.shared.vault.store().db().acquire().await?;
             if let Some(block_expiration) = metadata::block_expiration::get(&mut conn).await? {
@@ -181,6 +309,11 @@ impl Repository {
                     .set_block_expiration(Some(block_expiration))
                     .await?;
             }
+
+            let pool_warmup = metadata::pool_warmup::get(&mut conn).await?;
+            if pool_warmup > 0 {
+                spawn_pool_warmup(&self.shared.vault, pool_warmup);
+            }
         }
 
         tracing::debug!(
@@ -329,6 +462,23 @@ impl Repository {
         self.shared.credentials.read().unwrap().secrets.clone()
     }
 
+    /// Human readable fingerprint of the secrets of this repository for out-of-band verification
+    /// that two replicas share the same secrets. See [AccessSecrets::security_fingerprint] for
+    /// what a match proves at each access mode.
+    ///
+    /// If `access_mode` is given, the fingerprint is computed for that mode instead of the current
+    /// one, which allows comparing with a replica of lower access. It's clamped to the current
+    /// mode, that is, requesting a higher mode than the current one returns the fingerprint of the
+    /// current mode.
+    pub fn security_fingerprint(&self, access_mode: Option<AccessMode>) -> String {
+        let secrets = &self.shared.credentials.read().unwrap().secrets;
+
+        match access_mode {
+            Some(access_mode) => secrets.with_mode(access_mode).security_fingerprint(),
+            None => secrets.security_fingerprint(),
+        }
+    }
+
     /// Gets the current access mode of this repository.
     pub fn access_mode(&self) -> AccessMode {
         self.shared
@@ -344,6 +494,9 @@ impl Repository {
     /// The actual mode the repository gets switched to is the higher of the current access mode
     /// and the mode provided by `local_key` but at most the mode specified in `access_mode`
     /// ("higher" means according to `AccessMode`'s `Ord` impl, that is: Write > Read > Blind).
+    ///
+    /// Fails with `WrongPassword` if `local_secret` is needed to reach `access_mode` but doesn't
+    /// unlock anything. The access mode is left unchanged in that case.
     pub async fn set_access_mode(
         &self,
         access_mode: AccessMode,
@@ -369,6 +522,15 @@ impl Repository {
             let (new_secrets, local_key) =
                 metadata::get_access_secrets(&mut tx, local_secret.as_ref()).await?;
 
+            let failed = unlock_failed(&mut tx, local_secret.as_ref(), &new_secrets).await?;
+            if local_secret.is_some() {
+                self.shared.unlock_failed.store(failed, Ordering::Relaxed);
+            }
+
+            if failed && new_secrets.access_mode().max(old_secrets.access_mode()) < access_mode {
+                return Err(Error::WrongPassword);
+            }
+
             if new_secrets.access_mode() > old_secrets.access_mode() {
                 (new_secrets, local_key)
             } else {
@@ -401,6 +563,32 @@ impl Repository {
         Ok(())
     }
 
+    /// Switches the repository to blind mode without touching the stored secrets. Any open files
+    /// start failing with `PermissionDenied` (on reads as well as on writes, flushes, truncation
+    /// and forking) until the repository is unlocked again with [Self::unlock]. The cache of
+    /// decrypted blocks is cleared as well.
+    pub async fn lock(&self) -> Result<()> {
+        self.set_access_mode(AccessMode::Blind, None).await
+    }
+
+    /// Unlocks the repository previously locked with [Self::lock] (or opened in blind mode) using
+    /// the given local secret. The resulting access mode is the highest one the secret unlocks.
+    ///
+    /// Fails with `WrongPassword` if the secret doesn't raise the access mode of a repository that
+    /// is not already in write mode.
+    pub async fn unlock(&self, local_secret: LocalSecret) -> Result<()> {
+        let old_mode = self.access_mode();
+
+        self.set_access_mode(AccessMode::Write, Some(local_secret))
+            .await?;
+
+        if old_mode < AccessMode::Write && self.access_mode() <= old_mode {
+            return Err(Error::WrongPassword);
+        }
+
+        Ok(())
+    }
+
     /// Overrides the current credentials of this repository.
     ///
     /// This is useful for moving/renaming the repo database or to restore access which has been
@@ -440,11 +628,54 @@ impl Repository {
                 .await?;
         }
 
+        self.shared.unlock_failed.store(false, Ordering::Relaxed);
         self.update_credentials(credentials);
 
         Ok(())
     }
 
+    /// Returns the reason why this repository is not in a higher access mode than it currently is.
+    ///
+    /// `WrongPassword` is returned when the last local secret passed to [Self::open] or
+    /// [Self::set_access_mode] didn't unlock anything (those calls fail with `WrongPassword` too,
+    /// unless the secret wasn't needed). It's cleared by the next successful unlock attempt or by
+    /// [Self::set_credentials].
+    pub async fn lock_reason(&self) -> Result<LockReason> {
+        let access_mode = self.access_mode();
+
+        if access_mode == AccessMode::Write {
+            return Ok(LockReason::Unlocked);
+        }
+
+        if self.shared.unlock_failed.load(Ordering::Relaxed) {
+            return Ok(LockReason::WrongPassword);
+        }
+
+        let mut conn = self.db().acquire().await?;
+
+        match access_mode {
+            AccessMode::Blind => {
+                if !metadata::requires_local_secret_for_reading(&mut conn).await? {
+                    Ok(LockReason::Unlocked)
+                } else if !metadata::has_secret_keys(&mut conn).await? {
+                    Ok(LockReason::NoReadSecrets)
+                } else {
+                    Ok(LockReason::NeedsReadPassword)
+                }
+            }
+            AccessMode::Read => {
+                if metadata::requires_local_secret_for_writing(&mut conn).await? {
+                    Ok(LockReason::NeedsWritePassword)
+                } else {
+                    Ok(LockReason::Unlocked)
+                }
+            }
+            AccessMode::Write => unreachable!(),
+        }
+    }
+
+    /// Returns the secrets the given local secret unlocks (blind secrets if it unlocks nothing).
+    /// Doesn't change the current access mode of this repository.
     pub async fn unlock_secrets(&self, local_secret: LocalSecret) -> Result<AccessSecrets> {
         let mut tx = self.db().begin_write().await?;
         Ok(metadata::get_access_secrets(&mut tx, Some(&local_secret))
@@ -452,6 +683,65 @@ impl Repository {
             .0)
     }
 
+    /// Returns the access mode the given local secret unlocks by itself, that is, `Blind` if it
+    /// doesn't unlock more than what's accessible without any secret (e.g., a wrong password on a
+    /// repository with public read access). Doesn't change the current access mode of this
+    /// repository.
+    pub async fn verify_password(&self, local_secret: LocalSecret) -> Result<AccessMode> {
+        let mut tx = self.db().begin_write().await?;
+        let (secrets, _) = metadata::get_access_secrets(&mut tx, Some(&local_secret)).await?;
+
+        if unlock_failed(&mut tx, Some(&local_secret), &secrets).await? {
+            Ok(AccessMode::Blind)
+        } else {
+            Ok(secrets.access_mode())
+        }
+    }
+
+    /// Exports the secrets unlocked by `local_secret` as a recovery string the owner can keep in
+    /// a safe place (e.g. a password manager) to restore the access on another device with
+    /// [Self::import_access_secrets] should this one be lost.
+    ///
+    /// **Security:** the recovery string is not a share token. It contains the raw secrets
+    /// (with write access, the write key itself) without any password protection, never expires
+    /// and can't be revoked - anyone who obtains it gets the same access as the owner, forever.
+    /// It must never be shared with anyone.
+    ///
+    /// Fails with `WrongPassword` if `local_secret` doesn't unlock at least read access. Note that
+    /// if the repository stores its secrets without local secret protection, they are exported
+    /// regardless of `local_secret` (they are accessible to anyone with the database anyway).
+    pub async fn export_access_secrets(&self, local_secret: LocalSecret) -> Result<String> {
+        let secrets = self.unlock_secrets(local_secret).await?;
+
+        if !secrets.can_read() {
+            return Err(Error::WrongPassword);
+        }
+
+        Ok(secrets.to_recovery_string())
+    }
+
+    /// Restores the access to this repository from a recovery string produced by
+    /// [Self::export_access_secrets]. Fails with `PermissionDenied` if the recovery string belongs
+    /// to a different repository and with `MalformedData` if it's not a valid recovery string.
+    /// Does nothing if it doesn't grant higher access than the repository currently has.
+    ///
+    /// The restored access lasts only until the repository is closed. To persist it, protected by
+    /// a new local secret, call [Self::set_access] afterwards.
+    pub async fn import_access_secrets(&self, recovery: &str) -> Result<()> {
+        let secrets = AccessSecrets::from_recovery_string(recovery)?;
+        let current = self.credentials();
+
+        if secrets.access_mode() <= current.secrets.access_mode() {
+            return Ok(());
+        }
+
+        self.set_credentials(Credentials {
+            secrets,
+            writer_id: current.writer_id,
+        })
+        .await
+    }
+
     /// Get accessor for repository metadata. The metadata are arbitrary key-value entries that are
     /// stored inside the repository but not synced to other replicas.
     pub fn metadata(&self) -> Metadata {
@@ -468,6 +758,13 @@ impl Repository {
         self.shared.vault.quota().await
     }
 
+    /// Get the size counted against the storage quota. Unlike [`Self::size`] this counts the blocks
+    /// referenced from the latest snapshots of all the branches, including those not downloaded
+    /// yet, and not the blocks that are stored but no longer referenced.
+    pub async fn quota_usage(&self) -> Result<StorageSize> {
+        Ok(self.shared.vault.store().quota_usage().await?)
+    }
+
     /// Set the duration after which blocks start to expire (are deleted) when not used. Use `None`
     /// to disable expiration. Default is `None`.
     pub async fn set_block_expiration(&self, block_expiration: Option<Duration>) -> Result<()> {
@@ -487,14 +784,121 @@ impl Repository {
         self.shared.vault.block_expiration().await
     }
 
+    /// Set the number of database connections to pre-open (and warm up) every time this repository
+    /// is opened, so the first operations after opening don't pay the connection establishment
+    /// cost. The warmup runs in the background so it doesn't delay opening. The setting is
+    /// persisted and also applied immediately. Use zero to disable. Default is zero.
+    pub async fn set_pool_warmup(&self, count: u32) -> Result<()> {
+        let mut tx = self.db().begin_write().await?;
+        metadata::pool_warmup::set(&mut tx, count).await?;
+        tx.commit().await?;
+
+        if count > 0 {
+            spawn_pool_warmup(&self.shared.vault, count);
+        }
+
+        Ok(())
+    }
+
+    /// Get the number of database connections pre-opened when this repository is opened.
+    pub async fn pool_warmup(&self) -> Result<u32> {
+        let mut conn = self.db().acquire().await?;
+        Ok(metadata::pool_warmup::get(&mut conn).await?)
+    }
+
+    /// Set the max number of complete snapshots retained per branch. Older snapshots are removed
+    /// by the background pruning even if they could still serve as fallback for missing blocks of
+    /// newer snapshots. This bounds the index growth in repositories with frequent writes. The
+    /// latest complete snapshot and any snapshots still being downloaded are never removed. Use
+    /// zero for unlimited retention, which is the default.
+    pub async fn set_snapshot_retention(&self, count: u32) -> Result<()> {
+        let mut tx = self.db().begin_write().await?;
+        metadata::snapshot_retention::set(&mut tx, count).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Set the strategy for combining concurrent versions of the same directory entry when merging
+    /// branches. Default is `MergeStrategy::UnionEntries`.
+    ///
+    /// Note the strategy is stored locally and not synced with the other replicas. Replicas using
+    /// different strategies can temporarily show different content, see
+    /// `MergeStrategy::LastWriterWins` for details.
+    pub async fn set_directory_merge_strategy(&self, strategy: MergeStrategy) -> Result<()> {
+        let mut tx = self.db().begin_write().await?;
+        metadata::merge_strategy::set(&mut tx, strategy).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Get the strategy for combining concurrent versions of the same directory entry.
+    pub async fn directory_merge_strategy(&self) -> Result<MergeStrategy> {
+        let mut conn = self.db().acquire().await?;
+        Ok(metadata::merge_strategy::get(&mut conn).await?)
+    }
+
+    /// Get the max number of complete snapshots retained per branch. Zero means unlimited.
+    pub async fn snapshot_retention(&self) -> Result<u32> {
+        let mut conn = self.db().acquire().await?;
+        Ok(metadata::snapshot_retention::get(&mut conn).await?)
+    }
+
     /// Get the total size of the data stored in this repository.
     pub async fn size(&self) -> Result<StorageSize> {
         self.shared.vault.size().await
     }
 
+    /// Get the breakdown of the repository size into the logical size (total length of the files)
+    /// and the physical size (the stored blocks, index and metadata) together with the overhead
+    /// between them. Note this needs to read the header of every file so it's not free on large
+    /// repositories - avoid calling it often (e.g. on every change notification). Use
+    /// [`Self::size`] or [`Self::storage_stats`] if the logical size is not needed.
+    pub async fn size_breakdown(&self) -> Result<SizeBreakdown> {
+        size_breakdown::compute(&self.shared).await
+    }
+
+    /// Get the number of locally present and missing blocks together with the size of the stored
+    /// blocks and of the index. Cheaper than [`Self::size_breakdown`] as it only runs a few
+    /// aggregate queries.
+    pub async fn storage_stats(&self) -> Result<StorageStats> {
+        storage_stats::compute(&self.shared).await
+    }
+
+    /// Returns, for each remote branch (writer), when we last received a complete snapshot of it
+    /// (wall-clock time of this replica). Branches we haven't received any snapshot of since this
+    /// was first tracked are not included. Persisted across restarts. Purely informational, e.g.
+    /// to show when each writer's changes were last synced.
+    pub async fn branch_sync_times(&self) -> Result<HashMap<PublicKey, SystemTime>> {
+        let mut conn = self.db().acquire().await?;
+        Ok(branch_sync_times::get(&mut conn).await?)
+    }
+
+    /// Returns the sync status of every branch (writer) known to this repository: its latest
+    /// version vector, whether its index is complete and how many of its blocks are still missing.
+    /// Useful for diagnosing why the replicas haven't converged yet. Reads only the index.
+    pub async fn branches(&self) -> Result<Vec<BranchStatus>> {
+        branch_status::collect(&self.shared).await
+    }
+
+    /// Set the max size (in bytes) of the in-memory cache of decrypted blocks. Repeated reads of
+    /// the same blocks (e.g., reading the same file twice) are served from this cache instead of
+    /// being loaded and decrypted again. The size is rounded down to a whole number of blocks. Use
+    /// zero to disable the cache. Default is zero.
+    pub fn set_block_cache_size(&self, size: u64) {
+        self.shared.branch_shared.block_cache.set_size(size)
+    }
+
+    /// Get the max size (in bytes) of the in-memory cache of decrypted blocks.
+    pub fn block_cache_size(&self) -> u64 {
+        self.shared.branch_shared.block_cache.size()
+    }
+
     pub fn handle(&self) -> RepositoryHandle {
         RepositoryHandle {
             vault: self.shared.vault.clone(),
+            credentials: self.shared.credentials.clone(),
         }
     }
 
@@ -578,10 +982,87 @@ impl Repository {
         Ok(())
     }
 
+    /// Writes the index and all locally present blocks of this repository into `dst` so they can
+    /// be transferred to another replica without a network connection (e.g., on a removable drive)
+    /// and merged into it with [`Self::import_archive`].
+    ///
+    /// Only the latest approved snapshot of each branch is exported. The data is written in the
+    /// same encrypted form it's stored in which means this works in any access mode (blind
+    /// replicas export whatever they store, which is everything they can).
+    pub async fn export_archive<W: AsyncWrite + Unpin>(&self, dst: &mut W) -> Result<()> {
+        archive::export(&self.shared.vault, dst).await
+    }
+
+    /// Merges an archive created with [`Self::export_archive`] into this repository. Snapshots
+    /// are merged the same way as if they were received from a remote replica, that is, only those
+    /// newer than or concurrent with the local ones are accepted.
+    ///
+    /// Fails with `MalformedData` if the archive is corrupted (including blocks whose content
+    /// doesn't match their id), with `ArchiveVersionMismatch` if it was created with an
+    /// incompatible version of the format and with `InvalidArgument` if it belongs to a different
+    /// repository. Records imported before the failure are kept.
+    pub async fn import_archive<R: AsyncRead + Unpin>(&self, src: &mut R) -> Result<()> {
+        archive::import(&self.shared.vault, src).await
+    }
+
+    /// Rotates the keys of this repository. Generates new random write secrets and re-encrypts
+    /// the whole content of this repository under them into a new repository created at `params`.
+    /// The progress (bytes copied / total bytes) is yielded as the content is copied. Dropping
+    /// the stream interrupts the rotation. Calling this again with the same `params` resumes it,
+    /// skipping the entries that have already been copied.
+    ///
+    /// The rotated repository has a different id which means all previously issued share tokens
+    /// remain tied to the old repository. After the rotation the old repository should be deleted
+    /// and a new share token created from the rotated one must be distributed to all the other
+    /// replicas. The rotated repository is protected by `local_read_secret` and
+    /// `local_write_secret` (see [`Access::new`]), which should normally match the local secrets of
+    /// this repository. When resuming, `local_write_secret` is used to open the partially rotated
+    /// repository.
+    ///
+    /// All the content must be available locally (see [`Self::is_complete`]), otherwise the
+    /// rotation fails with `BlockNotFound`. Concurrent conflicting versions of a file are copied
+    /// as separate files. Changes made to this repository while the rotation is running are
+    /// picked up: whenever this repository changed during a pass over its content, another pass
+    /// is made (the progress total grows accordingly) and entries removed in the meantime are
+    /// removed from the rotated repository as well.
+    pub fn rotate_keys<'a, R: Recorder>(
+        &'a self,
+        params: &'a RepositoryParams<R>,
+        local_read_secret: Option<SetLocalSecret>,
+        local_write_secret: Option<SetLocalSecret>,
+    ) -> impl Stream<Item = Result<Progress>> + 'a {
+        rotate::rotate(self, params, local_read_secret, local_write_secret)
+    }
+
+    /// Rebuilds the index of the local branch from the locally stored blocks. Useful to recover
+    /// from a corrupted index. Requires write access.
+    ///
+    /// The blocks are matched to their locators by recomputing their nonces, starting from the
+    /// root directory and following the entries of the recovered directories. The progress
+    /// (blocks matched / blocks stored) is yielded after each scan of the store and the last item
+    /// is a report of what was recovered and what was lost. The rebuilt index replaces the current
+    /// content of the local branch only if every stored block and every discovered blob was
+    /// recovered, otherwise the recovered blocks are linked into the current index and the rest
+    /// of it is kept. Blobs with several versions stored are left out because their blocks can't
+    /// be told apart.
+    ///
+    /// Blocks still referenced by any stored snapshot are matched in linear time, the others
+    /// require testing every wanted locator and so recovering them is practical only for small
+    /// repositories.
+    ///
+    /// Only the local branch is rebuilt. The repository should not be synced while rebuilding the
+    /// index, otherwise the result is unspecified.
+    pub fn rebuild_index(&self) -> impl Stream<Item = Result<RebuildIndexEvent>> + '_ {
+        rebuild_index::rebuild(&self.shared)
+    }
+
     /// Looks up an entry by its path. The path must be relative to the repository root.
     /// If the entry exists, returns its `JointEntryType`, otherwise returns `EntryNotFound`.
+    /// If the entry is a link, it's followed and the type of its target is returned.
     pub async fn lookup_type<P: AsRef<Utf8Path>>(&self, path: P) -> Result<EntryType> {
-        match path::decompose(path.as_ref()) {
+        let path = self.resolve_links(path.as_ref()).await?;
+
+        match path::decompose(&path) {
             Some((parent, name)) => {
                 let parent = self.open_directory(parent).await?;
                 Ok(parent.lookup_unique(name)?.entry_type())
@@ -590,9 +1071,110 @@ impl Repository {
         }
     }
 
-    /// Opens a file at the given path (relative to the repository root)
+    /// Checks whether modifying the entry at the given path would create a conflict, that is,
+    /// whether some remote branch has a version of the entry that is newer than or concurrent with
+    /// the local one. Works for both files and directories. Returns `false` if the entry has no
+    /// local version yet (writing to it would just fork the latest remote version) or if the path
+    /// is the repository root. Remote removals of the entry are not considered conflicting. Does
+    /// not modify the repository.
+    pub async fn would_conflict<P: AsRef<Utf8Path>>(&self, path: P) -> Result<bool> {
+        match path::decompose(path.as_ref()) {
+            Some((parent, name)) => Ok(self.cd(parent).await?.has_unmerged_remote_version(name)),
+            None => Ok(false),
+        }
+    }
+
+    /// Checks whether the entry at the given path is fully available locally, that is, whether it
+    /// can be read without being connected to any other replica. A file is available if all its
+    /// blocks are present. A directory is available if all versions of it can be loaded (its
+    /// content is not checked recursively).
+    pub async fn is_available_offline<P: AsRef<Utf8Path>>(&self, path: P) -> Result<bool> {
+        let result = async {
+            match path::decompose(path.as_ref()) {
+                Some((parent, name)) => match self.cd(parent).await?.lookup_unique(name)? {
+                    JointEntryRef::File(entry) => {
+                        let file = entry.open().await?;
+                        // Release the file before awaiting the check.
+                        let future = file.is_available_offline();
+                        drop(file);
+                        future.await
+                    }
+                    JointEntryRef::Directory(entry) => {
+                        entry
+                            .open_with(MissingVersionStrategy::Fail, DirectoryFallback::Disabled)
+                            .await?;
+                        Ok(true)
+                    }
+                    // Links have no content of their own.
+                    JointEntryRef::Link(_) => Ok(true),
+                },
+                None => self.root().await.map(|_| true),
+            }
+        }
+        .await;
+
+        match result {
+            Err(Error::Store(store::Error::BlockNotFound)) => Ok(false),
+            result => result,
+        }
+    }
+
+    /// Pins the file at the given path so it stays available offline: its missing blocks are
+    /// downloaded with the highest priority and its blocks are never expired. The pin follows the
+    /// file when it's moved or modified and is removed with [`Self::unpin`]. Pinning a directory
+    /// is not supported.
+    pub async fn pin<P: AsRef<Utf8Path>>(&self, path: P) -> Result<()> {
+        let blob_ids = self.lookup_pinnable(path.as_ref()).await?;
+        pin::update(&self.shared, |pinned| pinned.extend(blob_ids)).await
+    }
+
+    /// Removes the pin of the file at the given path. Does nothing if the file is not pinned.
+    pub async fn unpin<P: AsRef<Utf8Path>>(&self, path: P) -> Result<()> {
+        let blob_ids = self.lookup_pinnable(path.as_ref()).await?;
+        pin::update(&self.shared, |pinned| {
+            for blob_id in &blob_ids {
+                pinned.remove(blob_id);
+            }
+        })
+        .await
+    }
+
+    /// Checks whether the file at the given path is pinned.
+    pub async fn is_pinned<P: AsRef<Utf8Path>>(&self, path: P) -> Result<bool> {
+        let blob_ids = self.lookup_pinnable(path.as_ref()).await?;
+        let pinned = pin::load(&self.shared).await?;
+
+        Ok(blob_ids.iter().any(|blob_id| pinned.contains(blob_id)))
+    }
+
+    // Returns the blob ids of all the concurrent versions of the file at the given path.
+    async fn lookup_pinnable(&self, path: &Utf8Path) -> Result<Vec<BlobId>> {
+        let path = self.resolve_links(path).await?;
+        let (parent, name) = path::decompose(&path).ok_or(Error::EntryIsDirectory)?;
+        let parent = self.cd(parent).await?;
+
+        let mut blob_ids = Vec::new();
+
+        for entry in parent.lookup(name) {
+            match entry {
+                JointEntryRef::File(entry) => blob_ids.push(*entry.inner().blob_id()),
+                JointEntryRef::Directory(_) => return Err(Error::EntryIsDirectory),
+                JointEntryRef::Link(_) => (),
+            }
+        }
+
+        if blob_ids.is_empty() {
+            Err(Error::EntryNotFound)
+        } else {
+            Ok(blob_ids)
+        }
+    }
+
+    /// Opens a file at the given path (relative to the repository root). If the path points to a
+    /// link, it's followed.
     pub async fn open_file<P: AsRef<Utf8Path>>(&self, path: P) -> Result<File> {
-        let (parent, name) = path::decompose(path.as_ref()).ok_or(Error::EntryIsDirectory)?;
+        let path = self.resolve_links(path.as_ref()).await?;
+        let (parent, name) = path::decompose(&path).ok_or(Error::EntryIsDirectory)?;
 
         self.cd(parent)
             .await?
@@ -632,6 +1214,71 @@ impl Repository {
         Ok(file)
     }
 
+    /// Creates a link at the given path pointing to `target`. Relative targets are resolved
+    /// against the directory containing the link, absolute ones against the repository root. The
+    /// target doesn't need to exist.
+    pub async fn create_link<P: AsRef<Utf8Path>>(&self, path: P, target: &str) -> Result<()> {
+        let (parent, name) = path::decompose(path.as_ref()).ok_or(Error::EntryExists)?;
+
+        self.local_branch()?
+            .ensure_directory_exists(parent)
+            .await?
+            .create_link(name.to_owned(), target.to_owned())
+            .await
+    }
+
+    /// Returns the target of the link at the given path. Fails with `EntryIsFile` or
+    /// `EntryIsDirectory` if the entry is not a link.
+    pub async fn read_link<P: AsRef<Utf8Path>>(&self, path: P) -> Result<String> {
+        let (parent, name) = path::decompose(path.as_ref()).ok_or(Error::EntryIsDirectory)?;
+
+        Ok(self
+            .cd(parent)
+            .await?
+            .lookup_unique(name)?
+            .link()?
+            .target()
+            .to_owned())
+    }
+
+    /// Follows the link at `path` (and the links it points to, if any) and returns the path of the
+    /// final target. Returns `path` unchanged if it doesn't point to a link. Only the last
+    /// component of the path is followed. Fails with `TooManyLinks` if more than `MAX_LINK_DEPTH`
+    /// links would have to be followed, which is the case with cyclic links.
+    async fn resolve_links(&self, path: &Utf8Path) -> Result<Utf8PathBuf> {
+        let mut path = path.to_owned();
+
+        for _ in 0..=MAX_LINK_DEPTH {
+            let Some((parent, name)) = path::decompose(&path) else {
+                return Ok(path);
+            };
+
+            let target = match self.cd(parent).await?.lookup_unique(name)? {
+                JointEntryRef::Link(link) => Some(parent.join(link.target())),
+                JointEntryRef::File(_) | JointEntryRef::Directory(_) => None,
+            };
+
+            let Some(target) = target else {
+                return Ok(path);
+            };
+
+            path = path::normalize(&target).ok_or(Error::EntryNotFound)?;
+        }
+
+        Err(Error::TooManyLinks)
+    }
+
+    /// Starts a batch of file modifications to be committed atomically. See [`Batch`] for details.
+    pub fn batch(&self) -> Result<Batch> {
+        let branch = self.local_branch()?;
+
+        if branch.keys().write().is_none() {
+            return Err(Error::PermissionDenied);
+        }
+
+        Ok(Batch::new(branch))
+    }
+
     /// Creates a new directory at the given path.
     pub async fn create_directory<P: AsRef<Utf8Path>>(&self, path: P) -> Result<Directory> {
         let dir = self
@@ -662,12 +1309,41 @@ impl Repository {
 
     /// Moves (renames) an entry from the source path to the destination path.
     /// If both source and destination refer to the same entry, this is a no-op.
+    ///
+    /// The move preserves the version history of the entry: the entry keeps its content (the blob
+    /// is not copied) and its version vector is carried over to the destination, while the source
+    /// is replaced with a tombstone marked as moved. Other replicas thus see it as a single move
+    /// and not as an unrelated removal and creation.
+    ///
+    /// If the source exists only in a remote branch, it's forked into the local branch first. The
+    /// fork and the move are each atomic and the fork doesn't change the content, so an
+    /// interruption between them leaves the entry merged but not moved.
+    ///
+    /// Fails with `EntryExists` if the destination has a remote version which is newer than or
+    /// concurrent with the local one, as the move would otherwise create a conflict with it.
     pub async fn move_entry<S: AsRef<Utf8Path>, D: AsRef<Utf8Path>>(
         &self,
         src_dir_path: S,
         src_name: &str,
         dst_dir_path: D,
         dst_name: &str,
+    ) -> Result<()> {
+        self.move_entry_with(src_dir_path, src_name, dst_dir_path, dst_name, true)
+            .await
+    }
+
+    /// Like [Self::move_entry] but with control over the version history of the moved entry. If
+    /// `preserve_history` is false, the destination starts a new version history instead of
+    /// continuing the one of the source, so it's no longer seen as descending from the source
+    /// entry (it's still a move though - the content is not copied and the source becomes a
+    /// tombstone marked as moved).
+    pub async fn move_entry_with<S: AsRef<Utf8Path>, D: AsRef<Utf8Path>>(
+        &self,
+        src_dir_path: S,
+        src_name: &str,
+        dst_dir_path: D,
+        dst_name: &str,
+        preserve_history: bool,
     ) -> Result<()> {
         let local_branch = self.local_branch()?;
         let src_joint_dir = self.cd(src_dir_path).await?;
@@ -695,11 +1371,34 @@ impl Repository {
 
                 (src_dir, Cow::Borrowed(src_name), EntryType::Directory)
             }
+            JointEntryRef::Link(entry) => {
+                let src_name = entry.name().to_string();
+
+                entry.fork(&local_branch).await?;
+
+                let mut src_dir = entry.inner().parent().fork(&local_branch).await?;
+                src_dir.refresh().await?;
+
+                (src_dir, Cow::Owned(src_name), EntryType::Link)
+            }
         };
 
         let src_entry = src_dir.lookup(&src_name)?.clone_data();
 
         let mut dst_joint_dir = self.cd(&dst_dir_path).await?;
+
+        // Refuse to move onto an entry which has a remote version that hasn't been merged into the
+        // local one yet. Otherwise the moved entry would end up concurrent with it.
+        let dst_local_vv = dst_joint_dir
+            .local_version()
+            .and_then(|dir| dir.lookup(dst_name).ok())
+            .map(|entry| entry.version_vector().clone())
+            .unwrap_or_default();
+
+        if dst_joint_dir.has_remote_version_not_covered_by(dst_name, &dst_local_vv) {
+            return Err(Error::EntryExists);
+        }
+
         let dst_dir = dst_joint_dir
             .local_version_mut()
             .ok_or(Error::PermissionDenied)?;
@@ -709,14 +1408,13 @@ impl Repository {
         // Emulating the behaviour of the libc's `rename` function
         // (https://www.man7.org/linux/man-pages/man2/rename.2.html)
         let dst_old_vv = match (src_type, dst_old_entry) {
-            (EntryType::File | EntryType::Directory, Ok(EntryRef::Tombstone(old_entry))) => {
-                old_entry.version_vector().clone()
-            }
-            (EntryType::File | EntryType::Directory, Err(Error::EntryNotFound)) => {
-                VersionVector::new()
-            }
-            (EntryType::File | EntryType::Directory, Err(error)) => return Err(error),
-            (EntryType::File, Ok(EntryRef::File(old_entry))) => old_entry.version_vector().clone(),
+            (_, Ok(EntryRef::Tombstone(old_entry))) => old_entry.version_vector().clone(),
+            (_, Err(Error::EntryNotFound)) => VersionVector::new(),
+            (_, Err(error)) => return Err(error),
+            (
+                EntryType::File | EntryType::Link,
+                Ok(old_entry @ (EntryRef::File(_) | EntryRef::Link(_))),
+            ) => old_entry.version_vector().clone(),
             (EntryType::Directory, Ok(EntryRef::Directory(old_entry))) => {
                 if old_entry
                     .open(DirectoryFallback::Disabled)
@@ -729,13 +1427,19 @@ impl Repository {
                     return Err(Error::DirectoryNotEmpty);
                 }
             }
-            (EntryType::File, Ok(EntryRef::Directory(_))) => return Err(Error::EntryIsDirectory),
+            (EntryType::File | EntryType::Link, Ok(EntryRef::Directory(_))) => {
+                return Err(Error::EntryIsDirectory)
+            }
             (EntryType::Directory, Ok(EntryRef::File(_))) => return Err(Error::EntryIsFile),
+            (EntryType::Directory, Ok(EntryRef::Link(_))) => return Err(Error::EntryIsLink),
         };
 
-        let dst_vv = dst_old_vv
-            .merged(src_entry.version_vector())
-            .incremented(*local_branch.id());
+        let dst_vv = if preserve_history {
+            dst_old_vv.merged(src_entry.version_vector())
+        } else {
+            dst_old_vv
+        }
+        .incremented(*local_branch.id());
 
         src_dir
             .move_entry(&src_name, src_entry, dst_dir, dst_name, dst_vv)
@@ -744,6 +1448,47 @@ impl Repository {
         Ok(())
     }
 
+    /// Copies the file at `src` to `dst` without copying its content: the new file references the
+    /// same blocks as the original, so nothing is re-encrypted and peers that already have the
+    /// blocks don't need to download them again. The blocks are removed from the store only after
+    /// neither file references them anymore. Modifying one of the files afterwards doesn't affect
+    /// the other one.
+    ///
+    /// Only the flushed content of the source file is copied. If `src` is a link, the link itself
+    /// is copied, not its target. Copying directories is not supported (fails with
+    /// `EntryIsDirectory`). Fails with `EntryExists` if `dst` already exists.
+    pub async fn copy_entry<S: AsRef<Utf8Path>, D: AsRef<Utf8Path>>(
+        &self,
+        src: S,
+        dst: D,
+    ) -> Result<()> {
+        let (src_parent, src_name) =
+            path::decompose(src.as_ref()).ok_or(Error::EntryIsDirectory)?;
+        let (dst_parent, dst_name) = path::decompose(dst.as_ref()).ok_or(Error::EntryExists)?;
+
+        let src_dir = self.cd(src_parent).await?;
+        let mut dst_dir = self
+            .local_branch()?
+            .ensure_directory_exists(dst_parent)
+            .await?;
+
+        match src_dir.lookup_unique(src_name)? {
+            JointEntryRef::File(entry) => {
+                let entry = entry.inner();
+
+                dst_dir
+                    .copy_file(dst_name.to_owned(), entry.branch(), *entry.blob_id())
+                    .await
+            }
+            JointEntryRef::Link(entry) => {
+                dst_dir
+                    .create_link(dst_name.to_owned(), entry.target().to_owned())
+                    .await
+            }
+            JointEntryRef::Directory(_) => Err(Error::EntryIsDirectory),
+        }
+    }
+
     /// Returns the local branch or `Error::PermissionDenied` if this repo doesn't have at least
     /// read access.
     pub fn local_branch(&self) -> Result<Branch> {
@@ -791,23 +1536,189 @@ impl Repository {
             .await?)
     }
 
+    /// Returns the current version of the repository: the version vector merged across all the
+    /// branches (including the local one). The returned value can be serialized and persisted as
+    /// a checkpoint for incremental change tracking.
+    pub async fn current_version(&self) -> Result<VersionVector> {
+        self.get_merged_version_vector().await
+    }
+
+    /// Returns the ids of the blocks that are currently being downloaded (required and not yet
+    /// received).
+    pub fn downloading(&self) -> Vec<BlockId> {
+        self.shared.vault.block_tracker.pending()
+    }
+
+    /// Stops downloading the missing blocks of the file at the given path. See
+    /// [`File::cancel_download`] for details.
+    pub async fn cancel_download<P: AsRef<Utf8Path>>(&self, path: P) -> Result<()> {
+        self.open_file(path).await?.cancel_download().await
+    }
+
     /// Subscribe to event notifications.
     pub fn subscribe(&self) -> broadcast::Receiver<Event> {
         self.shared.vault.event_tx.subscribe()
     }
 
+    /// Enables the in-memory event log which keeps the last `capacity` events emitted by this
+    /// repository (the same ones as delivered by [`Self::subscribe`]) together with their
+    /// timestamps. Useful for diagnostics. Zero (the default) disables the log and discards the
+    /// events recorded so far.
+    pub fn set_event_log_capacity(&self, capacity: usize) {
+        self.shared.vault.event_tx.set_log_capacity(capacity)
+    }
+
+    /// Returns the events recorded in the event log, oldest first. Empty if the log is disabled.
+    /// See [`Self::set_event_log_capacity`].
+    pub fn event_log(&self) -> Vec<LoggedEvent> {
+        self.shared.vault.event_tx.log()
+    }
+
     /// Gets the syncing progress of this repository (number of downloaded blocks / number of
     /// all blocks)
     pub async fn sync_progress(&self) -> Result<Progress> {
         Ok(self.shared.vault.store().sync_progress().await?)
     }
 
+    /// Subscribes to the syncing progress of this repository. Yields the current progress
+    /// (number of downloaded blocks / number of all blocks) right away and then whenever it
+    /// changes, at most a few times per second. Useful to drive a progress indicator without
+    /// polling [`Self::sync_progress`].
+    pub fn subscribe_sync_progress(&self) -> impl Stream<Item = Progress> {
+        sync_progress_stream(self.shared.vault.clone(), SYNC_PROGRESS_INTERVAL)
+    }
+
+    /// Returns whether this repository is fully synced, that is, whether all the blocks
+    /// referenced from the latest snapshots of all its complete branches have been downloaded.
+    /// Unlike comparing [`Self::sync_progress`] against its total, this is not affected by
+    /// rounding and ignores blocks that are no longer referenced.
+    pub async fn is_complete(&self) -> Result<bool> {
+        Ok(self.shared.vault.store().is_complete().await?)
+    }
+
+    /// Waits until this repository becomes fully synced (see [`Self::is_complete`]). Returns
+    /// immediately if it already is. Note the repository can become incomplete again afterwards,
+    /// for example when a remote replica makes new changes.
+    pub async fn on_complete(&self) -> Result<()> {
+        // Subscribe before the first check so no change is missed in between.
+        let mut rx = self.shared.vault.event_tx.subscribe();
+
+        loop {
+            if self.is_complete().await? {
+                return Ok(());
+            }
+
+            match rx.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => (),
+                // The sender is owned by `self` so the channel can't be closed here.
+                Err(RecvError::Closed) => unreachable!(),
+            }
+        }
+    }
+
+    /// Gets the index syncing progress of this repository, that is, how much of the metadata
+    /// (directory structure and file listings) has been received. During the initial sync the
+    /// index is downloaded before the blocks, so this advances while `sync_progress` is still at
+    /// zero. Both return `Progress` so they can be combined into a single progress indicator.
+    pub async fn index_progress(&self) -> Result<Progress> {
+        Ok(self.shared.vault.store().index_progress().await?)
+    }
+
+    /// Returns whether any blocks of this repository are currently being transferred to or from
+    /// remote replicas, that is, whether there are any outstanding block requests or block
+    /// responses in flight. `Payload::TransferStateChanged` is emitted every time this changes.
+    pub fn is_transferring(&self) -> bool {
+        self.shared.vault.transfer_tracker.is_transferring()
+    }
+
+    /// Finds groups of files with identical content. The groups are yielded as they are found and
+    /// the search can be cancelled by dropping the returned stream. Only files whose length is
+    /// shared with some other file are actually read. Empty files and files that are not fully
+    /// downloaded are skipped.
+    pub fn duplicates(&self) -> impl Stream<Item = Result<Vec<Utf8PathBuf>>> + '_ {
+        duplicates::find(self)
+    }
+
+    /// Like `duplicates` but also reports the progress of the search, see
+    /// [`DuplicatesSearchEvent`].
+    pub fn duplicates_with_progress(
+        &self,
+    ) -> impl Stream<Item = Result<DuplicatesSearchEvent>> + '_ {
+        duplicates::find_with_progress(self)
+    }
+
+    /// Like `duplicates` but collects all the groups into a `Vec`.
+    pub async fn find_duplicates(&self) -> Result<Vec<Vec<Utf8PathBuf>>> {
+        self.duplicates().try_collect().await
+    }
+
+    /// Watches the directory at `path` for created, modified and removed entries. The watch is
+    /// path based: if the directory is removed or moved away, `DirEvent::DirectoryRemoved` is
+    /// yielded and the stream ends. Only changes made after this function returns are reported.
+    /// Fails with `EntryNotFound` if the directory doesn't exist.
+    pub async fn watch_directory<P: Into<Utf8PathBuf>>(
+        &self,
+        path: P,
+    ) -> Result<impl Stream<Item = Result<DirEvent>> + '_> {
+        watch::watch(self, path.into()).await
+    }
+
+    /// Watches the file or directory at `path` and yields `PathEvent::Changed` with its new version
+    /// vector whenever it or any of its descendants changes. Clients can use the version vector to
+    /// skip duplicate notifications. The watch is path based: if the entry is removed or moved
+    /// away, `PathEvent::Removed` is yielded and the stream ends. Dropping the stream stops the
+    /// watch. Fails with `EntryNotFound` if the entry doesn't exist.
+    pub async fn subscribe_path<P: Into<Utf8PathBuf>>(
+        &self,
+        path: P,
+    ) -> Result<impl Stream<Item = Result<PathEvent>> + '_> {
+        watch::watch_path(self, path.into()).await
+    }
+
+    /// Finds all files that have concurrent (conflicting) versions. Such files are by default kept
+    /// side by side, each version accessible under its unique (disambiguated) name. Use
+    /// [`Self::resolve_conflict`] to merge them.
+    pub async fn conflicts(&self) -> Result<Vec<Conflict>> {
+        conflicts::find(self).await
+    }
+
+    /// Checks whether the file at `path` has concurrent versions without modifying anything.
+    /// Useful to find out whether writing to the file would fork it into a conflict so the user
+    /// can be asked to merge first. Returns the competing versions with their authors (branches)
+    /// and version vectors, or `None` if there is no conflict. Only the index is read.
+    pub async fn detect_conflict<P: AsRef<Utf8Path>>(&self, path: P) -> Result<Option<Conflict>> {
+        conflicts::detect(self, path.as_ref()).await
+    }
+
+    /// Resolves the conflict of the file at `path` by writing `content` into its local version
+    /// and making it supersede all the currently known versions of the file. The other versions
+    /// then become outdated and are eventually removed. If a new concurrent version appears in the
+    /// meantime, the conflict remains.
+    pub async fn resolve_conflict<P: AsRef<Utf8Path>>(
+        &self,
+        path: P,
+        content: &[u8],
+    ) -> Result<()> {
+        conflicts::resolve(self, path.as_ref(), content).await
+    }
+
     /// Check integrity of the stored data.
     // TODO: Return more detailed info about any integrity violation.
     pub async fn check_integrity(&self) -> Result<bool> {
         Ok(self.shared.vault.store().check_integrity().await?)
     }
 
+    /// Verifies the stored data against their hashes: checks that the children of every index
+    /// node of the latest snapshots hash to the hash of the node and that every block the index
+    /// marks as present is stored and its content hashes to its id. The problems are yielded as
+    /// they are found so a long scan reports them incrementally. Dropping the stream cancels the
+    /// scan.
+    ///
+    /// This is read-only and safe to run while the repository is being used and synced.
+    pub fn verify_integrity(&self) -> impl Stream<Item = Result<store::IntegrityReport>> + '_ {
+        self.shared.vault.store().verify_integrity().err_into()
+    }
+
     // Opens the root directory across all branches as JointDirectory.
     async fn root(&self) -> Result<JointDirectory> {
         let local_branch = self.local_branch()?;
@@ -877,6 +1788,19 @@ impl Repository {
         self.root().await?.cd(path).await
     }
 
+    /// Moves the database file of this repository to `dst` while keeping the repository open.
+    /// Operations that need the database in the meantime wait until the move completes. The
+    /// repository id doesn't change so any network registration remains valid. Fails with
+    /// `EntryExists` if `dst` already exists (it's never overwritten). Also fails if some
+    /// operation keeps using the database for too long, instead of waiting for it indefinitely. If
+    /// the move fails, the repository stays at the original location.
+    pub async fn rename_store(&self, dst: impl AsRef<Path>) -> Result<()> {
+        self.db().relocate(dst).await.map_err(|error| match error {
+            db::Error::Exists => Error::EntryExists,
+            error => error.into(),
+        })
+    }
+
     /// Close all db connections held by this repository. After this function returns, any
     /// subsequent operation on this repository that requires to access the db returns an error.
     pub async fn close(&self) -> Result<()> {
@@ -890,6 +1814,7 @@ impl Repository {
             }
         }
 
+        self.shared.branch_shared.block_cache.clear();
         self.shared.vault.store().close().await?;
 
         Ok(())
@@ -938,6 +1863,16 @@ impl Repository {
         self.shared.vault.debug_print(print).await;
     }
 
+    /// Reclaims the space taken by data that's no longer needed: prunes outdated branches and
+    /// snapshots and removes the blocks not reachable from any of the remaining ones. This is
+    /// normally done automatically in the background after every change; calling this performs it
+    /// immediately. The work is done in batches, each in its own transaction, so it doesn't block
+    /// other operations for long. Returns the number of bytes of block content reclaimed.
+    pub async fn gc(&self) -> Result<u64> {
+        let removed_count = worker::collect_garbage(&self.shared).await?;
+        Ok(removed_count * BLOCK_SIZE as u64)
+    }
+
     /// Returns the total number of blocks in this repository. This is useful for diagnostics and
     /// tests.
     pub async fn count_blocks(&self) -> Result<u64> {
@@ -961,6 +1896,16 @@ impl Repository {
             .block_tracker
             .set_request_mode(request_mode(&credentials.secrets));
 
+        self.shared
+            .branch_shared
+            .locked
+            .store(!credentials.secrets.can_read(), Ordering::Relaxed);
+
+        // Don't keep any decrypted content around once it can no longer be read.
+        if !credentials.secrets.can_read() {
+            self.shared.branch_shared.block_cache.clear();
+        }
+
         *self.shared.credentials.write().unwrap() = credentials;
         *self.worker_handle.lock().unwrap() = Some(spawn_worker(self.shared.clone()));
     }
@@ -968,12 +1913,15 @@ impl Repository {
 
 pub struct RepositoryHandle {
     pub(crate) vault: Vault,
+    pub(crate) credentials: Arc<BlockingRwLock<Credentials>>,
 }
 
 struct Shared {
     vault: Vault,
-    credentials: BlockingRwLock<Credentials>,
+    credentials: Arc<BlockingRwLock<Credentials>>,
     branch_shared: BranchShared,
+    // Whether the last attempt to unlock the repository with a local secret failed.
+    unlock_failed: AtomicBool,
 }
 
 impl Shared {
@@ -985,10 +1933,13 @@ impl Shared {
             .block_tracker
             .set_request_mode(request_mode(&credentials.secrets));
 
+        let branch_shared = BranchShared::new(vault.block_tracker.clone());
+
         Self {
             vault,
-            credentials: BlockingRwLock::new(credentials),
-            branch_shared: BranchShared::new(),
+            credentials: Arc::new(BlockingRwLock::new(credentials)),
+            branch_shared,
+            unlock_failed: AtomicBool::new(false),
         }
     }
 
@@ -1038,41 +1989,133 @@ impl Shared {
     }
 }
 
+/// Finds the first of the given local secrets that unlocks the highest access mode. Returns its
+/// index (`None` if `local_secrets` is empty) together with the unlocked secrets and the local key.
+async fn select_local_secret<'a>(
+    tx: &mut db::WriteTransaction,
+    local_secrets: &'a [LocalSecret],
+) -> Result<(
+    Option<usize>,
+    AccessSecrets,
+    Option<Cow<'a, cipher::SecretKey>>,
+)> {
+    let mut selected: Option<(usize, AccessSecrets, Option<Cow<'a, cipher::SecretKey>>)> = None;
+
+    for (index, local_secret) in local_secrets.iter().enumerate() {
+        let (secrets, local_key) = metadata::get_access_secrets(tx, Some(local_secret)).await?;
+        let mode = secrets.access_mode();
+
+        let better = match &selected {
+            Some((_, best, _)) => mode > best.access_mode(),
+            None => true,
+        };
+
+        if better {
+            selected = Some((index, secrets, local_key));
+        }
+
+        if mode == AccessMode::Write {
+            break;
+        }
+    }
+
+    match selected {
+        Some((index, secrets, local_key)) => Ok((Some(index), secrets, local_key)),
+        None => {
+            let (secrets, local_key) = metadata::get_access_secrets(tx, None).await?;
+            Ok((None, secrets, local_key))
+        }
+    }
+}
+
+/// Checks whether the given local secret failed to unlock anything beyond what's accessible
+/// without it. Only the outcome is reported, never how "close" the secret was.
+async fn unlock_failed(
+    tx: &mut db::WriteTransaction,
+    local_secret: Option<&LocalSecret>,
+    unlocked: &AccessSecrets,
+) -> Result<bool> {
+    if local_secret.is_none() {
+        return Ok(false);
+    }
+
+    let (public, _) = metadata::get_access_secrets(tx, None).await?;
+
+    Ok(unlocked.access_mode() <= public.access_mode())
+}
+
+fn spawn_pool_warmup(vault: &Vault, count: u32) {
+    let pool = vault.store().db().clone();
+    let span = vault.monitor.span().clone();
+
+    task::spawn(
+        async move {
+            if let Err(error) = pool.warmup(count).await {
+                tracing::warn!(?error, "Failed to warm up the database connection pool");
+            }
+        }
+        .instrument(span),
+    );
+}
+
+// Awaits a task spawned on a different runtime, propagating its panic (if any).
+async fn join<T>(handle: task::JoinHandle<T>) -> T {
+    match handle.await {
+        Ok(output) => output,
+        Err(error) if error.is_panic() => panic::resume_unwind(error.into_panic()),
+        Err(_) => panic!("runtime shut down"),
+    }
+}
+
 fn spawn_worker(shared: Arc<Shared>) -> ScopedJoinHandle<()> {
     let span = shared.vault.monitor.span().clone();
     scoped_task::spawn(worker::run(shared).instrument(span))
 }
 
 async fn report_sync_progress(vault: Vault) {
-    let mut prev_progress = Progress { value: 0, total: 0 };
+    let mut progresses = pin!(sync_progress_stream(vault, Duration::from_secs(1)));
 
+    while let Some(progress) = progresses.next().await {
+        tracing::debug!(
+            "Sync progress: {} bytes ({:.1})",
+            progress * BLOCK_SIZE as u64,
+            progress.percent()
+        );
+    }
+}
+
+/// Stream of sync progress updates. Yields the current progress first and then every time it
+/// changes, but at most once per `interval`.
+fn sync_progress_stream(vault: Vault, interval: Duration) -> impl Stream<Item = Progress> {
     let events = stream::unfold(vault.event_tx.subscribe(), |mut rx| async move {
         match rx.recv().await {
             Ok(_) | Err(RecvError::Lagged(_)) => Some(((), rx)),
             Err(RecvError::Closed) => None,
         }
     });
-    let events = Throttle::new(events, Duration::from_secs(1));
-    let mut events = pin!(events);
+    let events = stream::once(future::ready(())).chain(Throttle::new(events, interval));
+    let mut prev_progress = None;
+
+    events
+        .then(move |_| {
+            let vault = vault.clone();
+            async move { vault.store().sync_progress().await }
+        })
+        .filter_map(move |result| {
+            let progress = match result {
+                Ok(progress) => progress,
+                Err(error) => {
+                    tracing::error!("Failed to retrieve sync progress: {:?}", error);
+                    return future::ready(None);
+                }
+            };
 
-    while events.next().await.is_some() {
-        let next_progress = match vault.store().sync_progress().await {
-            Ok(progress) => progress,
-            Err(error) => {
-                tracing::error!("Failed to retrieve sync progress: {:?}", error);
-                continue;
+            if prev_progress.replace(progress) == Some(progress) {
+                future::ready(None)
+            } else {
+                future::ready(Some(progress))
             }
-        };
-
-        if next_progress != prev_progress {
-            prev_progress = next_progress;
-            tracing::debug!(
-                "Sync progress: {} bytes ({:.1})",
-                prev_progress * BLOCK_SIZE as u64,
-                prev_progress.percent()
-            );
-        }
-    }
+        })
 }
 
 fn request_mode(secrets: &AccessSecrets) -> RequestMode {
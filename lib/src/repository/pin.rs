@@ -0,0 +1,91 @@
+//! Pinning files to keep them available offline.
+//!
+//! The pins are persisted as the blob ids of the pinned files so they follow the files when they
+//! are moved or modified. The blocks of the pinned blobs (in all branches) are exempt from the
+//! block expiration and the missing ones are required with the highest priority. The set of the
+//! pinned blocks is refreshed on every pin change and every time the worker scans the repository.
+
+use super::{metadata, Shared};
+use crate::{
+    blob::{BlobId, BlockIds},
+    branch::Branch,
+    collections::HashSet,
+    error::{Error, Result},
+    protocol::{BlockId, SingleBlockPresence},
+    store,
+};
+use std::collections::BTreeSet;
+
+/// Priority of the missing blocks of the pinned files.
+const PIN_PRIORITY: u8 = u8::MAX;
+
+/// Loads the pinned blob ids.
+pub(super) async fn load(shared: &Shared) -> Result<BTreeSet<BlobId>> {
+    let mut conn = shared.vault.store().db().acquire().await?;
+    Ok(metadata::pinned_blobs::get(&mut conn).await?)
+}
+
+/// Modifies the pinned blob ids with `f`, persists them and refreshes the pinned blocks.
+pub(super) async fn update<F>(shared: &Shared, f: F) -> Result<()>
+where
+    F: FnOnce(&mut BTreeSet<BlobId>),
+{
+    let mut tx = shared.vault.store().db().begin_write().await?;
+    let mut blob_ids = metadata::pinned_blobs::get(&mut tx).await?;
+    f(&mut blob_ids);
+    metadata::pinned_blobs::set(&mut tx, &blob_ids).await?;
+    tx.commit().await?;
+
+    refresh(shared).await
+}
+
+/// Recomputes the set of pinned blocks and requires the missing ones.
+pub(super) async fn refresh(shared: &Shared) -> Result<()> {
+    let blob_ids = load(shared).await?;
+    let mut block_ids = HashSet::default();
+
+    // Without the read key the pinned blobs can't be traversed.
+    let readable = shared.credentials.read().unwrap().secrets.keys().is_some();
+
+    if readable && !blob_ids.is_empty() {
+        for branch in shared.load_branches().await? {
+            for blob_id in &blob_ids {
+                collect(shared, branch.clone(), *blob_id, &mut block_ids).await?;
+            }
+        }
+    }
+
+    shared.vault.store().set_pinned_blocks(block_ids);
+
+    Ok(())
+}
+
+async fn collect(
+    shared: &Shared,
+    branch: Branch,
+    blob_id: BlobId,
+    block_ids: &mut HashSet<BlockId>,
+) -> Result<()> {
+    let mut blob_block_ids = match BlockIds::open(branch, blob_id).await {
+        Ok(blob_block_ids) => blob_block_ids,
+        // The blob doesn't exist in this branch or the branch has been pruned in the meantime.
+        Err(Error::Store(store::Error::LocatorNotFound | store::Error::BranchNotFound)) => {
+            return Ok(())
+        }
+        Err(error) => return Err(error),
+    };
+
+    while let Some((block_id, block_presence)) = blob_block_ids.try_next().await? {
+        block_ids.insert(block_id);
+
+        match block_presence {
+            SingleBlockPresence::Present => (),
+            SingleBlockPresence::Missing | SingleBlockPresence::Expired => shared
+                .vault
+                .block_tracker
+                .require_with_priority(block_id, PIN_PRIORITY),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,413 @@
+//! Rebuilding the index of the local branch from the locally stored blocks.
+//!
+//! Without the index there is no record of which block belongs at which locator. However, block
+//! nonces are derived from the locator and the plaintext (see `blob::make_block_nonce`) so a
+//! decrypted block can be matched to a locator by recomputing its nonce. Starting from the head of
+//! the root directory, the blocks of each blob are matched and the recovered directories are
+//! parsed to discover the locators of their children. The store is scanned repeatedly until a scan
+//! matches no new block.
+//!
+//! Recomputing a nonce means hashing the whole block so each block is first tested only against
+//! the wanted locators it's still referenced at by any stored snapshot (of any branch), which
+//! costs one hash per block. Only blocks not referenced by any snapshot are tested against all the
+//! wanted locators, which is quadratic and so practical only for small repositories.
+//!
+//! Several versions of a blob can be stored at once (e.g., from different branches). Their blocks
+//! can't be told apart so such blobs are not linked at all, as mixing blocks of different versions
+//! would corrupt them. The current index is replaced only if the rebuild accounts for every stored
+//! block and every discovered blob, otherwise the recovered blocks are linked into it and the rest
+//! of it is kept.
+
+use super::Shared;
+use crate::{
+    blob::{self, BlobId, HEADER_SIZE},
+    branch::Branch,
+    collections::HashMap,
+    crypto::Hash,
+    directory::{self, EntryType},
+    error::{Error, Result},
+    progress::Progress,
+    protocol::{BlockContent, BlockId, Bump, Locator, SingleBlockPresence},
+    store::{self, Changeset},
+};
+use futures_util::{stream, Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Event emitted while rebuilding the index.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RebuildIndexEvent {
+    /// Number of blocks matched so far out of all the stored blocks. Emitted after each scan of
+    /// the store.
+    Progress(Progress),
+    /// The rebuilt index has been committed. This is the last event.
+    Done(RebuildIndexReport),
+}
+
+/// Summary of what was recovered and what was lost by rebuilding the index.
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RebuildIndexReport {
+    /// Number of blobs (files and directories) whose blocks were all recovered.
+    pub recovered_blobs: u64,
+    /// Number of blobs whose head block was recovered but some of the other blocks were not.
+    pub incomplete_blobs: u64,
+    /// Number of blobs referenced from a recovered directory whose head block was not found.
+    pub lost_blobs: u64,
+    /// Number of blocks linked into the rebuilt index.
+    pub recovered_blocks: u64,
+    /// Number of stored blocks that couldn't be matched to any locator.
+    pub unassigned_blocks: u64,
+    /// Number of blobs with several versions stored which were left out of the rebuilt index.
+    pub ambiguous_blobs: u64,
+    /// Whether the rebuilt index replaced the current one (`true`) or was merged into it
+    /// (`false`) because the rebuild didn't account for all the stored blocks and blobs.
+    pub replaced: bool,
+}
+
+pub(super) fn rebuild(shared: &Shared) -> impl Stream<Item = Result<RebuildIndexEvent>> + '_ {
+    stream::once(async move {
+        let rebuilder = Rebuilder::new(shared.local_branch()?).await?;
+
+        Ok(stream::try_unfold(
+            Some(rebuilder),
+            |rebuilder| async move {
+                let Some(mut rebuilder) = rebuilder else {
+                    return Ok(None);
+                };
+
+                if rebuilder.scan().await? {
+                    let event = RebuildIndexEvent::Progress(rebuilder.progress());
+                    Ok(Some((event, Some(rebuilder))))
+                } else {
+                    let event = RebuildIndexEvent::Done(rebuilder.commit().await?);
+                    Ok(Some((event, None)))
+                }
+            },
+        ))
+    })
+    .try_flatten()
+}
+
+struct Rebuilder {
+    branch: Branch,
+    total: u64,
+    // Number of scans started so far.
+    scans: u32,
+    // Stored blocks not yet matched to any locator.
+    unassigned: Vec<BlockId>,
+    // Locators of the blocks being looked for, keyed by their encoded form. Locators stay wanted
+    // even after a block is matched to them to detect other versions of the same block.
+    wanted: HashMap<Hash, Locator>,
+    blobs: HashMap<BlobId, BlobState>,
+    // Blocks matched to each locator. More than one means several versions of the block.
+    found: HashMap<Locator, Vec<BlockId>>,
+}
+
+struct BlobState {
+    entry_type: EntryType,
+    // Blob length, known once the head block is found.
+    len: Option<u64>,
+    // Scan during which the head block was found.
+    head_scan: u32,
+    // Number of locators of this blob with at least one matched block.
+    found: u32,
+    // Whether several versions of this blob are stored.
+    ambiguous: bool,
+    // Whether the children of this directory have been added.
+    parsed: bool,
+    // Plaintext of the found blocks. Kept only for directories until they are parsed.
+    blocks: BTreeMap<u32, Vec<u8>>,
+}
+
+impl BlobState {
+    fn is_complete(&self) -> bool {
+        self.len
+            .map(|len| self.found == blob::block_count(len))
+            .unwrap_or(false)
+    }
+}
+
+impl Rebuilder {
+    async fn new(branch: Branch) -> Result<Self> {
+        branch.keys().write().ok_or(Error::PermissionDenied)?;
+
+        let unassigned = branch
+            .store()
+            .acquire_read()
+            .await?
+            .load_block_ids()
+            .await?;
+
+        let mut rebuilder = Self {
+            branch,
+            total: unassigned.len() as u64,
+            scans: 0,
+            unassigned,
+            wanted: HashMap::default(),
+            blobs: HashMap::default(),
+            found: HashMap::default(),
+        };
+
+        rebuilder.add_blob(BlobId::ROOT, EntryType::Directory);
+
+        Ok(rebuilder)
+    }
+
+    fn progress(&self) -> Progress {
+        Progress {
+            value: self.total - self.unassigned.len() as u64,
+            total: self.total,
+        }
+    }
+
+    /// Tries to match every unassigned block to one of the wanted locators, then parses the
+    /// directories that became complete. Returns whether anything new was found. Blocks matched
+    /// during a scan can make more locators wanted which are then looked for in the rest of the
+    /// same scan.
+    async fn scan(&mut self) -> Result<bool> {
+        self.scans += 1;
+
+        let read_key = self.branch.keys().read().clone();
+        let mut tx = self.branch.store().begin_read().await?;
+        let mut content = BlockContent::new();
+        let mut matched = false;
+        let mut index = 0;
+
+        while index < self.unassigned.len() {
+            let id = self.unassigned[index];
+
+            let hints: Vec<Hash> = tx.load_locators(&id).try_collect().await?;
+            let candidates: Vec<Locator> = if hints.is_empty() {
+                self.wanted.values().copied().collect()
+            } else {
+                hints
+                    .iter()
+                    .filter_map(|hint| self.wanted.get(hint))
+                    .copied()
+                    .collect()
+            };
+
+            if candidates.is_empty() {
+                index += 1;
+                continue;
+            }
+
+            let nonce = match tx.read_block(&id, &mut content).await {
+                Ok(nonce) => nonce,
+                Err(store::Error::BlockNotFound) => {
+                    self.unassigned.swap_remove(index);
+                    continue;
+                }
+                Err(error) => return Err(error.into()),
+            };
+
+            blob::decrypt_block(&read_key, &nonce, &mut content);
+
+            let locator = candidates
+                .into_iter()
+                .find(|locator| blob::make_block_nonce(locator, &content, &read_key) == nonce);
+
+            let Some(locator) = locator else {
+                index += 1;
+                continue;
+            };
+
+            self.unassigned.swap_remove(index);
+            self.handle_block_found(locator, id, &content);
+
+            matched = true;
+        }
+
+        let parsed = self.parse_directories();
+
+        Ok(matched || parsed)
+    }
+
+    fn handle_block_found(&mut self, locator: Locator, id: BlockId, content: &BlockContent) {
+        let versions = self.found.entry(locator).or_default();
+        versions.push(id);
+        let first = versions.len() == 1;
+
+        let Some(state) = self.blobs.get_mut(locator.blob_id()) else {
+            return;
+        };
+
+        if first {
+            state.found += 1;
+        } else {
+            state.ambiguous = true;
+        }
+
+        if locator.number() == 0 {
+            let len = content.read_u64(0);
+
+            match state.len {
+                Some(prev_len) if prev_len != len => state.ambiguous = true,
+                Some(_) => (),
+                None => {
+                    state.len = Some(len);
+                    state.head_scan = self.scans;
+                }
+            }
+
+            let read_key = self.branch.keys().read();
+
+            for number in 1..blob::block_count(len) {
+                let locator = locator.nth(number);
+                self.wanted.insert(locator.encode(read_key), locator);
+            }
+        }
+
+        if state.entry_type == EntryType::Directory && first {
+            state.blocks.insert(locator.number(), content.to_vec());
+        }
+    }
+
+    /// Adds the children of the complete, unambiguous directories. A directory is parsed only
+    /// after all its blocks have been wanted for a whole scan, that is, once every stored version
+    /// of them has been found, so that a directory whose blocks come from different versions is
+    /// never parsed. Returns whether any directory was parsed.
+    fn parse_directories(&mut self) -> bool {
+        let mut children = Vec::new();
+
+        for (blob_id, state) in &mut self.blobs {
+            if state.entry_type != EntryType::Directory
+                || state.parsed
+                || state.len.is_none()
+                || state.head_scan >= self.scans
+            {
+                continue;
+            }
+
+            if state.ambiguous {
+                state.blocks.clear();
+                continue;
+            }
+
+            if !state.is_complete() {
+                continue;
+            }
+
+            state.parsed = true;
+
+            let len = state.len.unwrap_or(0) as usize;
+            let buffer: Vec<u8> = state.blocks.values().flatten().copied().collect();
+            state.blocks.clear();
+
+            let end = (HEADER_SIZE + len).min(buffer.len());
+
+            match directory::parse_child_blobs(&buffer[HEADER_SIZE..end]) {
+                Ok(blob_children) => children.extend(blob_children),
+                Err(error) => {
+                    tracing::warn!(?blob_id, ?error, "Failed to parse directory");
+                }
+            }
+        }
+
+        let parsed = !children.is_empty();
+
+        for (blob_id, entry_type) in children {
+            self.add_blob(blob_id, entry_type);
+        }
+
+        parsed
+    }
+
+    fn add_blob(&mut self, blob_id: BlobId, entry_type: EntryType) {
+        if self.blobs.contains_key(&blob_id) {
+            return;
+        }
+
+        self.blobs.insert(
+            blob_id,
+            BlobState {
+                entry_type,
+                len: None,
+                head_scan: 0,
+                found: 0,
+                ambiguous: false,
+                parsed: false,
+                blocks: BTreeMap::new(),
+            },
+        );
+
+        let locator = Locator::head(blob_id);
+        self.wanted
+            .insert(locator.encode(self.branch.keys().read()), locator);
+    }
+
+    /// Links the matched blocks of the unambiguous blobs into the index of the local branch. The
+    /// current index is replaced only if every stored block was matched and every discovered blob
+    /// recovered, otherwise the blocks are linked into it and the rest of it is kept so that no
+    /// block it references is lost.
+    async fn commit(self) -> Result<RebuildIndexReport> {
+        let mut report = RebuildIndexReport {
+            unassigned_blocks: self.unassigned.len() as u64,
+            ..Default::default()
+        };
+
+        for state in self.blobs.values() {
+            if state.len.is_none() {
+                report.lost_blobs += 1;
+            } else if state.ambiguous {
+                report.ambiguous_blobs += 1;
+            } else if state.is_complete() {
+                report.recovered_blobs += 1;
+            } else {
+                report.incomplete_blobs += 1;
+            }
+        }
+
+        let links: Vec<_> = self
+            .found
+            .iter()
+            .filter(|(locator, _)| {
+                self.blobs
+                    .get(locator.blob_id())
+                    .map(|state| !state.ambiguous)
+                    .unwrap_or(false)
+            })
+            .filter_map(|(locator, versions)| match versions.as_slice() {
+                [block_id] => Some((*locator, *block_id)),
+                _ => None,
+            })
+            .collect();
+
+        report.recovered_blocks = links.len() as u64;
+
+        if links.is_empty() {
+            return Ok(report);
+        }
+
+        report.replaced = report.unassigned_blocks == 0
+            && report.lost_blobs == 0
+            && report.ambiguous_blobs == 0
+            && report.incomplete_blobs == 0;
+
+        let read_key = self.branch.keys().read();
+        let write_keys = self.branch.keys().write().ok_or(Error::PermissionDenied)?;
+
+        let mut changeset = Changeset::new();
+        changeset.reset(report.replaced);
+        changeset.force_bump(true);
+        changeset.bump(Bump::increment(*self.branch.id()));
+
+        for (locator, block_id) in links {
+            changeset.link_block(
+                locator.encode(read_key),
+                block_id,
+                SingleBlockPresence::Present,
+            );
+        }
+
+        let mut tx = self.branch.store().begin_write().await?;
+        changeset
+            .apply(&mut tx, self.branch.id(), write_keys)
+            .await?;
+
+        let event_tx = self.branch.notify();
+        tx.commit_and_then(move || event_tx.send()).await?;
+
+        Ok(report)
+    }
+}
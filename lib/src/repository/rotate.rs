@@ -0,0 +1,320 @@
+//! Key rotation: re-encrypting the whole content of a repository under freshly generated secrets.
+//!
+//! The repository id, the block ids and the index signatures are all derived from the secrets so
+//! rotating them means building a new repository. The content is copied entry by entry into a
+//! store created with new random write secrets, protected by the same kind of local secrets as the
+//! source. Entries already present in the destination with the same content (from a previous,
+//! interrupted rotation) are skipped which makes the rotation resumable. The same makes it cheap to
+//! repeat the copy until the source stops changing.
+
+use super::{Repository, RepositoryParams};
+use crate::{
+    access_control::{
+        Access, AccessMode, AccessSecrets, LocalSecret, SetLocalSecret, WriteSecrets,
+    },
+    db,
+    error::{Error, Result},
+    event::Event,
+    file::File,
+    joint_directory::JointEntryRef,
+    progress::Progress,
+    protocol::BLOCK_SIZE,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use futures_util::{stream, Stream, TryStreamExt};
+use metrics::Recorder;
+use std::{
+    collections::{HashSet, VecDeque},
+    io::SeekFrom,
+    vec,
+};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+pub(super) fn rotate<'a, R: Recorder>(
+    src: &'a Repository,
+    params: &'a RepositoryParams<R>,
+    local_read_secret: Option<SetLocalSecret>,
+    local_write_secret: Option<SetLocalSecret>,
+) -> impl Stream<Item = Result<Progress>> + 'a {
+    stream::once(async move {
+        let dst = open_or_create(params, local_read_secret, local_write_secret).await?;
+
+        // Subscribe before the first scan so no change made after it is missed.
+        let events = src.subscribe();
+        let entries = scan(src).await?;
+        let progress = Progress {
+            value: 0,
+            total: total_len(&entries),
+        };
+
+        let state = State {
+            dst,
+            events,
+            paths: paths(&entries),
+            entries: entries.into_iter(),
+            progress,
+        };
+
+        Ok(stream::try_unfold(state, move |mut state| async move {
+            loop {
+                if let Some(entry) = state.entries.next() {
+                    state.progress.value += match copy(src, &state.dst, entry).await {
+                        Ok(len) => len,
+                        // Removed from the source since the scan. The next pass will catch up.
+                        Err(Error::EntryNotFound) => 0,
+                        Err(error) => return Err(error),
+                    };
+
+                    return Ok(Some((state.progress, state)));
+                }
+
+                // All the scanned entries have been copied. Remove the ones that no longer exist
+                // in the source and if the source changed in the meantime, do another pass to pick
+                // up the changes (unchanged files are skipped).
+                prune(&state.dst, &state.paths).await?;
+
+                if !drain(&mut state.events) {
+                    state.dst.close().await?;
+                    return Ok(None);
+                }
+
+                let entries = scan(src).await?;
+                state.progress.total += total_len(&entries);
+                state.paths = paths(&entries);
+                state.entries = entries.into_iter();
+            }
+        }))
+    })
+    .try_flatten()
+}
+
+struct State {
+    dst: Repository,
+    events: broadcast::Receiver<Event>,
+    // Paths of all the entries of the current pass.
+    paths: HashSet<Utf8PathBuf>,
+    // Entries of the current pass not copied yet.
+    entries: vec::IntoIter<Entry>,
+    progress: Progress,
+}
+
+enum Entry {
+    Directory(Utf8PathBuf),
+    File(Utf8PathBuf, u64),
+    Link(Utf8PathBuf),
+}
+
+impl Entry {
+    fn path(&self) -> &Utf8Path {
+        match self {
+            Self::Directory(path) | Self::File(path, _) | Self::Link(path) => path,
+        }
+    }
+}
+
+fn total_len(entries: &[Entry]) -> u64 {
+    entries
+        .iter()
+        .map(|entry| match entry {
+            Entry::File(_, len) => *len,
+            Entry::Directory(_) | Entry::Link(_) => 0,
+        })
+        .sum()
+}
+
+fn paths(entries: &[Entry]) -> HashSet<Utf8PathBuf> {
+    entries
+        .iter()
+        .map(|entry| entry.path().to_owned())
+        .collect()
+}
+
+/// Returns whether any event has been received since the last call.
+fn drain(events: &mut broadcast::Receiver<Event>) -> bool {
+    let mut changed = false;
+
+    loop {
+        match events.try_recv() {
+            Ok(_) | Err(TryRecvError::Lagged(_)) => changed = true,
+            Err(TryRecvError::Empty | TryRecvError::Closed) => return changed,
+        }
+    }
+}
+
+/// Opens the destination repository left over from an interrupted rotation or creates a new one
+/// with random write secrets protected by the given local secrets.
+async fn open_or_create<R: Recorder>(
+    params: &RepositoryParams<R>,
+    local_read_secret: Option<SetLocalSecret>,
+    local_write_secret: Option<SetLocalSecret>,
+) -> Result<Repository> {
+    // Without a write secret `Access::new` would drop the write secrets and the copy would fail,
+    // so protect the write access with the read secret in that case.
+    let local_write_secret = local_write_secret.or_else(|| local_read_secret.clone());
+    let open_secret = local_write_secret.clone().map(LocalSecret::from);
+    let access = Access::new(
+        local_read_secret,
+        local_write_secret,
+        AccessSecrets::Write(WriteSecrets::random()),
+    );
+
+    match Repository::create(params, access).await {
+        Ok(repo) => Ok(repo),
+        Err(Error::Db(db::Error::Exists)) => {
+            let repo = Repository::open(params, open_secret, AccessMode::Write).await?;
+
+            if repo.access_mode() != AccessMode::Write {
+                repo.close().await?;
+                return Err(Error::PermissionDenied);
+            }
+
+            Ok(repo)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Lists all entries of `repo`, parents before their children.
+async fn scan(repo: &Repository) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut dirs = VecDeque::from([Utf8PathBuf::from("/")]);
+
+    while let Some(dir_path) = dirs.pop_front() {
+        let mut files = Vec::new();
+
+        for entry in repo.cd(&dir_path).await?.entries() {
+            let path = dir_path.join(entry.unique_name().as_ref());
+
+            match entry {
+                JointEntryRef::File(_) => files.push(path),
+                JointEntryRef::Directory(_) => {
+                    entries.push(Entry::Directory(path.clone()));
+                    dirs.push_back(path);
+                }
+                JointEntryRef::Link(_) => entries.push(Entry::Link(path)),
+            }
+        }
+
+        for path in files {
+            let len = repo.open_file(&path).await?.len();
+            entries.push(Entry::File(path, len));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Copies a single entry from `src` to `dst`. Returns the number of bytes copied. Files already
+/// present in `dst` with the same content have been copied by a previous rotation attempt and are
+/// skipped, any other existing file is overwritten.
+async fn copy(src: &Repository, dst: &Repository, entry: Entry) -> Result<u64> {
+    match entry {
+        Entry::Directory(path) => {
+            dst.create_directory(&path).await?;
+            Ok(0)
+        }
+        Entry::Link(path) => {
+            let target = src.read_link(&path).await?;
+
+            match dst.create_link(&path, &target).await {
+                Ok(()) => Ok(0),
+                Err(Error::EntryExists) => {
+                    // Copied by a previous attempt or pass but the source link might have been
+                    // retargeted since.
+                    if dst.read_link(&path).await? != target {
+                        dst.remove_entry(&path).await?;
+                        dst.create_link(&path, &target).await?;
+                    }
+
+                    Ok(0)
+                }
+                Err(error) => Err(error),
+            }
+        }
+        Entry::File(path, len) => {
+            let mut src_file = src.open_file(&path).await?;
+
+            match dst.open_file(&path).await {
+                Ok(mut dst_file) => {
+                    if same_content(&mut src_file, &mut dst_file).await? {
+                        return Ok(len);
+                    }
+
+                    src_file.seek(SeekFrom::Start(0));
+                }
+                Err(Error::EntryNotFound) => (),
+                Err(error) => return Err(error),
+            }
+
+            let mut dst_file = dst.create_file(&path).await?;
+            dst_file.truncate(0)?;
+
+            let mut buffer = vec![0; BLOCK_SIZE];
+
+            loop {
+                let n = src_file.read(&mut buffer).await?;
+                if n == 0 {
+                    break;
+                }
+
+                dst_file.write_all(&buffer[..n]).await?;
+            }
+
+            dst_file.flush().await?;
+
+            let metadata = src_file.metadata().await?;
+            if !metadata.is_empty() {
+                dst_file.set_metadata(metadata).await?;
+            }
+
+            Ok(len)
+        }
+    }
+}
+
+/// Removes the entries of `dst` whose paths are not in `paths` (removed from the source since they
+/// were copied).
+async fn prune(dst: &Repository, paths: &HashSet<Utf8PathBuf>) -> Result<()> {
+    for entry in scan(dst).await? {
+        let path = entry.path();
+
+        if paths.contains(path) {
+            continue;
+        }
+
+        match dst.remove_entry_recursively(path).await {
+            // The parent directory has already been removed.
+            Ok(()) | Err(Error::EntryNotFound) => (),
+            Err(error) => return Err(error),
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether the two files have the same content. Leaves both files at unspecified
+/// positions.
+async fn same_content(a: &mut File, b: &mut File) -> Result<bool> {
+    if a.len() != b.len() {
+        return Ok(false);
+    }
+
+    a.seek(SeekFrom::Start(0));
+    b.seek(SeekFrom::Start(0));
+
+    let mut buffer_a = vec![0; BLOCK_SIZE];
+    let mut buffer_b = vec![0; BLOCK_SIZE];
+
+    loop {
+        let n = a.read(&mut buffer_a).await?;
+        if n == 0 {
+            return Ok(true);
+        }
+
+        b.read_all(&mut buffer_b[..n]).await?;
+
+        if buffer_a[..n] != buffer_b[..n] {
+            return Ok(false);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Breakdown of the repository size into the logical (user visible) and physical (stored) parts.
+
+use super::Shared;
+use crate::{
+    blob::Blob,
+    branch::Branch,
+    directory::{Directory, DirectoryFallback},
+    error::{Error, Result},
+    joint_directory::{JointDirectory, JointEntryRef},
+    protocol::BLOCK_SIZE,
+    store::{self, ReadTransaction},
+};
+use futures_util::{future, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Breakdown of the repository size. Explains why a repository takes more storage than the sum of
+/// the sizes of its files (block padding, encryption nonces, the index, metadata, ...).
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SizeBreakdown {
+    /// Total length of all the files in the repository, as seen by the user. Files whose length
+    /// can't be determined because they are not downloaded yet are not included. Always zero in
+    /// blind replicas as they can't read the files.
+    pub logical_bytes: u64,
+    /// Size of the repository database (blocks, index and metadata).
+    pub physical_bytes: u64,
+    /// Size of a single block in bytes.
+    pub block_size: u64,
+    /// Number of blocks stored locally.
+    pub block_count: u64,
+    /// Difference between `physical_bytes` and `logical_bytes`.
+    pub overhead_bytes: u64,
+}
+
+/// Computes the size breakdown. All the numbers are read within a single read transaction so they
+/// are consistent with each other even when the repository is being concurrently modified.
+///
+/// Note: the file lengths are stored in the (encrypted) blob headers, not in the index, so
+/// computing `logical_bytes` requires reading the first block of every file. It can't be tracked
+/// as a running total updated on block insert/remove: a block doesn't know which files (if any) it
+/// belongs to, blocks are shared between files (copies, forks, versions in multiple branches) and
+/// the length of a file changes without any block being inserted or removed (e.g. truncating
+/// within the last block). `block_count` and `physical_bytes` are single aggregate queries.
+///
+/// Note: these numbers are not the ones the quota is checked against. `block_count` counts every
+/// block stored locally (the same count [`Repository::size`](super::Repository::size) is derived
+/// from) and `physical_bytes` is the size of the whole database file, while the quota counts only
+/// the blocks referenced from the latest snapshots (see
+/// [`Repository::quota_usage`](super::Repository::quota_usage)).
+pub(super) async fn compute(shared: &Shared) -> Result<SizeBreakdown> {
+    let mut tx = shared.vault.store().begin_read().await?;
+
+    let can_read = shared.credentials.read().unwrap().secrets.can_read();
+    let logical_bytes = if can_read {
+        logical_bytes(&mut tx, shared).await?
+    } else {
+        0
+    };
+
+    let block_count = tx.count_blocks().await?;
+    let physical_bytes = tx.database_size().await?;
+
+    Ok(SizeBreakdown {
+        logical_bytes,
+        physical_bytes,
+        block_size: BLOCK_SIZE as u64,
+        block_count,
+        overhead_bytes: physical_bytes.saturating_sub(logical_bytes),
+    })
+}
+
+/// Sums the lengths of all the files in the repository.
+async fn logical_bytes(tx: &mut ReadTransaction, shared: &Shared) -> Result<u64> {
+    let branches: Vec<Branch> = tx
+        .load_latest_approved_root_nodes()
+        .err_into()
+        .and_then(|node| future::ready(shared.get_branch(node.proof.writer_id)))
+        .try_collect()
+        .await?;
+
+    let mut roots = Vec::with_capacity(branches.len());
+
+    for branch in branches {
+        if let Some(dir) =
+            skip_missing(Directory::open_root_in(tx, branch, DirectoryFallback::Disabled).await)?
+        {
+            roots.push(dir);
+        }
+    }
+
+    let local_branch = shared.local_branch().ok();
+
+    files_len(
+        tx,
+        JointDirectory::new(local_branch.clone(), roots),
+        local_branch,
+    )
+    .await
+}
+
+/// Sums the lengths of all the files in the given directory and its subdirectories.
+async fn files_len(
+    tx: &mut ReadTransaction,
+    root: JointDirectory,
+    local_branch: Option<Branch>,
+) -> Result<u64> {
+    let mut total = 0;
+    let mut queue = VecDeque::from([root]);
+
+    while let Some(dir) = queue.pop_front() {
+        for entry in dir.entries() {
+            match entry {
+                JointEntryRef::File(entry) => {
+                    let file = entry.inner();
+
+                    if let Some(blob) =
+                        skip_missing(Blob::open(tx, file.branch().clone(), *file.blob_id()).await)?
+                    {
+                        total += blob.len();
+                    }
+                }
+                JointEntryRef::Link(_) => (),
+                JointEntryRef::Directory(entry) => {
+                    let mut versions = Vec::with_capacity(entry.versions().len());
+
+                    for version in entry.versions() {
+                        if let Some(dir) =
+                            skip_missing(version.open_in(tx, DirectoryFallback::Disabled).await)?
+                        {
+                            versions.push(dir);
+                        }
+                    }
+
+                    queue.push_back(JointDirectory::new(local_branch.clone(), versions));
+                }
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+fn skip_missing<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::Store(store::Error::BlockNotFound)) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
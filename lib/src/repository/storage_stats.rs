@@ -0,0 +1,37 @@
+//! Absolute numbers about the local storage of the repository.
+
+use super::Shared;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+
+/// Statistics of the local storage of a repository. Unlike `sync_progress` which is only a ratio,
+/// these are absolute numbers.
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StorageStats {
+    /// Number of blocks stored locally.
+    pub present_blocks: u64,
+    /// Number of blocks referenced in the index but not (yet) stored locally.
+    pub missing_blocks: u64,
+    /// Size of the locally stored blocks (content and nonces) in bytes.
+    pub present_bytes: u64,
+    /// Size of the rest of the database (the index and the metadata) in bytes.
+    pub index_bytes: u64,
+}
+
+/// Computes the storage stats within a single read transaction. Reads only the index and the
+/// blocks table, not the block contents.
+pub(super) async fn compute(shared: &Shared) -> Result<StorageStats> {
+    let mut tx = shared.vault.store().begin_read().await?;
+
+    let present_blocks = tx.count_blocks().await?;
+    let missing_blocks = tx.count_missing_block_ids().await?;
+    let present_bytes = tx.blocks_size().await?;
+    let index_bytes = tx.database_size().await?.saturating_sub(present_bytes);
+
+    Ok(StorageStats {
+        present_blocks,
+        missing_blocks,
+        present_bytes,
+        index_bytes,
+    })
+}
@@ -1,10 +1,15 @@
 use super::*;
 use crate::{
-    blob, db,
+    blob::{self, BlockIds},
+    db,
+    event::Payload,
     protocol::{BlockId, BLOCK_NONCE_SIZE, BLOCK_SIZE},
-    test_utils, LocalSecret, SetLocalSecret, WriteSecrets,
+    test_utils, FileMetadata, LocalSecret, SetLocalSecret, WriteSecrets, MAX_FILE_METADATA_SIZE,
 };
 use assert_matches::assert_matches;
+use metrics::{
+    Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+};
 use rand::Rng;
 use std::{future::Future, io::SeekFrom};
 use tempfile::TempDir;
@@ -200,6 +205,56 @@ async fn concurrent_read_and_create_dir() {
     open_dir.await.unwrap();
 }
 
+// Once `flush` returns, the write must be visible to any subsequent read on the same repository,
+// including through handles freshly opened in another task.
+#[tokio::test(flavor = "multi_thread")]
+async fn flushed_write_is_visible_immediately() {
+    let (_base_dir, repo) = setup().await;
+    let repo = Arc::new(repo);
+
+    repo.create_directory("dir").await.unwrap();
+
+    for i in 0..10 {
+        let name = format!("file-{i}.txt");
+        let path = format!("dir/{name}");
+        let content = random_bytes(2 * BLOCK_SIZE);
+
+        let mut file = repo.create_file(&path).await.unwrap();
+        file.write_all(&content).await.unwrap();
+        file.flush().await.unwrap();
+
+        let (names, read_content) = scoped_task::spawn({
+            let repo = repo.clone();
+            let path = path.clone();
+
+            async move {
+                let names: Vec<_> = repo
+                    .open_directory("dir")
+                    .await
+                    .unwrap()
+                    .entries()
+                    .map(|entry| entry.name().to_owned())
+                    .collect();
+
+                let read_content = repo
+                    .open_file(&path)
+                    .await
+                    .unwrap()
+                    .read_to_end()
+                    .await
+                    .unwrap();
+
+                (names, read_content)
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(names.contains(&name), "{name} not in {names:?}");
+        assert_eq!(read_content, content);
+    }
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn concurrent_write_and_read_file() {
     let (_base_dir, repo) = setup().await;
@@ -330,6 +385,58 @@ async fn move_file_onto_existing_file() {
     assert_eq!(file.read_to_end().await.unwrap(), b"src");
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn move_remote_file_into_subdirectory() {
+    let (_base_dir, repo) = setup().await;
+
+    let local_branch = repo.local_branch().unwrap();
+    let remote_id = PublicKey::random();
+
+    let remote_file = create_remote_file(&repo, remote_id, "src.txt", b"hello").await;
+    let remote_vv = remote_file.version_vector().await.unwrap();
+    drop(remote_file);
+
+    repo.create_directory("dir").await.unwrap();
+
+    repo.move_entry("/", "src.txt", "dir", "dst.txt")
+        .await
+        .unwrap();
+
+    assert_matches!(repo.open_file("src.txt").await, Err(Error::EntryNotFound));
+
+    let mut file = repo.open_file("dir/dst.txt").await.unwrap();
+    assert_eq!(file.branch().id(), local_branch.id());
+    assert_eq!(file.read_to_end().await.unwrap(), b"hello");
+
+    let dst_vv = file.version_vector().await.unwrap();
+    assert!(dst_vv > remote_vv);
+    assert!(dst_vv.get(local_branch.id()) > remote_vv.get(local_branch.id()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn move_file_onto_concurrent_remote_file() {
+    let (_base_dir, repo) = setup().await;
+
+    let remote_id = PublicKey::random();
+
+    repo.create_file("src.txt").await.unwrap();
+
+    let mut file = repo.create_file("dst.txt").await.unwrap();
+    file.write_all(b"local").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    create_remote_file(&repo, remote_id, "dst.txt", b"remote").await;
+
+    assert_matches!(
+        repo.move_entry("/", "src.txt", "/", "dst.txt").await,
+        Err(Error::EntryExists)
+    );
+
+    // Nothing has been moved.
+    assert_matches!(repo.open_file("src.txt").await, Ok(_));
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn move_file_onto_existing_directory() {
     let (_base_dir, repo) = setup().await;
@@ -1053,6 +1160,200 @@ async fn size() {
     );
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn storage_stats() {
+    let (_base_dir, repo) = setup().await;
+
+    let stats = repo.storage_stats().await.unwrap();
+    assert_eq!(stats.present_blocks, 0);
+    assert_eq!(stats.missing_blocks, 0);
+    assert_eq!(stats.present_bytes, 0);
+
+    let mut file = repo.create_file("test.txt").await.unwrap();
+    file.write_all(&random_bytes(BLOCK_SIZE)).await.unwrap();
+    file.flush().await.unwrap();
+
+    // 3 blocks: 2 for the file and 1 for the root dir
+    let stats = repo.storage_stats().await.unwrap();
+    assert_eq!(stats.present_blocks, 3);
+    assert_eq!(stats.missing_blocks, 0);
+    assert_eq!(
+        stats.present_bytes,
+        3 * (BLOCK_SIZE + BLOCK_NONCE_SIZE) as u64
+    );
+    assert!(stats.index_bytes > 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn local_write_exceeding_quota() {
+    let (_base_dir, repo) = setup().await;
+    repo.set_quota(Some(StorageSize::from_blocks(4)))
+        .await
+        .unwrap();
+
+    // 1 block for the root dir + 5 blocks for the file
+    let mut file = repo.create_file("large.dat").await.unwrap();
+    let content = random_bytes(4 * BLOCK_SIZE + 1);
+    let result = async {
+        file.write_all(&content).await?;
+        file.flush().await
+    }
+    .await;
+    assert_matches!(result, Err(Error::QuotaExceeded));
+    drop(file);
+
+    // Changes that don't grow the repository are always allowed.
+    repo.remove_entry("large.dat").await.unwrap();
+
+    // Writes within the quota are allowed.
+    let mut file = repo.create_file("small.dat").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
+
+    // 1 block for the root dir + 1 block for the file
+    assert_eq!(
+        repo.quota_usage().await.unwrap(),
+        StorageSize::from_blocks(2)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn verify_integrity() {
+    use sqlx::Row;
+
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("test.dat").await.unwrap();
+    file.write_all(&random_bytes(2 * BLOCK_SIZE)).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let reports: Vec<_> = repo.verify_integrity().try_collect().await.unwrap();
+    assert_eq!(reports, []);
+
+    // Corrupt one block and remove another one.
+    let mut tx = repo.db().begin_write().await.unwrap();
+    let block_ids: Vec<BlockId> = sqlx::query("SELECT id FROM blocks LIMIT 2")
+        .fetch_all(&mut tx)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.get(0))
+        .collect();
+    sqlx::query("UPDATE blocks SET content = ? WHERE id = ?")
+        .bind(vec![0u8; BLOCK_SIZE])
+        .bind(&block_ids[0])
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM blocks WHERE id = ?")
+        .bind(&block_ids[1])
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+
+    let reports: Vec<_> = repo.verify_integrity().try_collect().await.unwrap();
+    assert_eq!(reports.len(), 2);
+    assert!(reports.contains(&store::IntegrityReport::BlockCorrupted(block_ids[0])));
+    assert!(reports.contains(&store::IntegrityReport::BlockMissing(block_ids[1])));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rebuild_index() {
+    let (_base_dir, repo) = setup().await;
+
+    let content_a = random_bytes(2 * BLOCK_SIZE);
+    let content_b = b"hello world".to_vec();
+
+    repo.create_directory("dir").await.unwrap();
+
+    for (path, content) in [("dir/a.dat", &content_a), ("b.txt", &content_b)] {
+        let mut file = repo.create_file(path).await.unwrap();
+        file.write_all(content).await.unwrap();
+        file.flush().await.unwrap();
+    }
+
+    wipe_index(&repo).await;
+
+    let events: Vec<_> = repo.rebuild_index().try_collect().await.unwrap();
+    let report = assert_matches!(events.last(), Some(RebuildIndexEvent::Done(report)) => *report);
+
+    // root, dir, dir/a.dat, b.txt
+    assert_eq!(report.recovered_blobs, 4);
+    assert_eq!(report.incomplete_blobs, 0);
+    assert_eq!(report.lost_blobs, 0);
+    assert_eq!(report.ambiguous_blobs, 0);
+    assert_eq!(report.unassigned_blocks, 0);
+    assert!(report.replaced);
+
+    for (path, content) in [("dir/a.dat", &content_a), ("b.txt", &content_b)] {
+        let mut file = repo.open_file(path).await.unwrap();
+        assert_eq!(&file.read_to_end().await.unwrap(), content);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rebuild_index_keeps_unmatched_blocks() {
+    let (_base_dir, repo) = setup().await;
+
+    let content = b"hello world".to_vec();
+    let mut file = repo.create_file("a.txt").await.unwrap();
+    file.write_all(&content).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    // A block that doesn't belong to any blob. The index is kept intact so the other blocks are
+    // matched using the locators they are referenced at.
+    let mut tx = repo.db().begin_write().await.unwrap();
+    sqlx::query("INSERT INTO blocks (id, nonce, content) VALUES (?, ?, ?)")
+        .bind(random_bytes(32))
+        .bind(random_bytes(BLOCK_NONCE_SIZE))
+        .bind(random_bytes(BLOCK_SIZE))
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+
+    let events: Vec<_> = repo.rebuild_index().try_collect().await.unwrap();
+    let report = assert_matches!(events.last(), Some(RebuildIndexEvent::Done(report)) => *report);
+
+    assert_eq!(report.recovered_blobs, 2);
+    assert_eq!(report.unassigned_blocks, 1);
+    assert!(!report.replaced);
+
+    assert_eq!(read_file(&repo, "a.txt").await, content);
+}
+
+// Deletes the whole index but keeps the blocks.
+async fn wipe_index(repo: &Repository) {
+    use sqlx::Row;
+
+    let mut tx = repo.db().begin_write().await.unwrap();
+    let blocks: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)> =
+        sqlx::query("SELECT id, nonce, content FROM blocks")
+            .fetch_all(&mut tx)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect();
+    sqlx::query("DELETE FROM snapshot_root_nodes")
+        .execute(&mut tx)
+        .await
+        .unwrap();
+    for (id, nonce, content) in &blocks {
+        sqlx::query("INSERT INTO blocks (id, nonce, content) VALUES (?, ?, ?)")
+            .bind(id)
+            .bind(nonce)
+            .bind(content)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+    }
+    tx.commit().await.unwrap();
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn access_mode() {
     let secret1 = SetLocalSecret::random();
@@ -1198,103 +1499,1786 @@ async fn set_access_mode_is_idempotent() {
     assert_eq!(writer_id_0, writer_id_1);
 }
 
-// FIXME: This sometimes fails because of a bug in sqlx: https://github.com/launchbadge/sqlx/issues/3217
-#[ignore]
 #[tokio::test(flavor = "multi_thread")]
-async fn aux_db_files_are_deleted_on_close() {
+async fn lock_and_unlock() {
     test_utils::init_log();
 
-    let (temp_dir, repo) = setup().await;
+    let base_dir = TempDir::new().unwrap();
+    let params = RepositoryParams::new(base_dir.path().join("repo.db"));
+    let local_secret = SetLocalSecret::random();
 
-    repo.close().await.unwrap();
+    let repo = Repository::create(
+        &params,
+        Access::WriteLocked {
+            local_read_secret: local_secret.clone(),
+            local_write_secret: local_secret.clone(),
+            secrets: WriteSecrets::random(),
+        },
+    )
+    .await
+    .unwrap();
 
-    let mut read_dir = fs::read_dir(temp_dir.path()).await.unwrap();
-    let mut entries = Vec::new();
+    let mut file = repo.create_file("test.txt").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
 
-    while let Some(entry) = read_dir.next_entry().await.unwrap() {
-        entries.push(entry.path());
-    }
+    repo.lock().await.unwrap();
+    assert_eq!(repo.access_mode(), AccessMode::Blind);
 
-    assert_eq!(entries, [temp_dir.path().join(DEFAULT_REPO_NAME)]);
-}
+    // In-flight file handle is no longer usable.
+    file.seek(SeekFrom::Start(0));
+    assert_matches!(file.read_to_end().await, Err(Error::PermissionDenied));
+    assert_matches!(file.truncate(0), Err(Error::PermissionDenied));
+    assert_matches!(file.flush().await, Err(Error::PermissionDenied));
+    assert_matches!(
+        repo.open_file("test.txt").await,
+        Err(Error::PermissionDenied)
+    );
 
-#[tokio::test(flavor = "multi_thread")]
-async fn export() {
-    let (base_dir, src_repo) = setup().await;
+    // No decrypted content is kept around while locked.
+    assert_eq!(repo.shared.branch_shared.block_cache.len(), 0);
 
-    // Create some content
-    let src_content = random_bytes(1024);
+    assert_matches!(
+        repo.unlock(SetLocalSecret::random().into()).await,
+        Err(Error::WrongPassword)
+    );
+    assert_eq!(repo.access_mode(), AccessMode::Blind);
 
-    let mut file = src_repo.create_file("test.dat").await.unwrap();
-    file.write_all(&src_content).await.unwrap();
-    file.flush().await.unwrap();
-    drop(file);
+    repo.unlock(local_secret.into()).await.unwrap();
+    assert_eq!(repo.access_mode(), AccessMode::Write);
 
-    let dst_path = base_dir.path().join("export.db");
-    src_repo.export(&dst_path).await.unwrap();
+    file.seek(SeekFrom::Start(0));
+    assert_eq!(file.read_to_end().await.unwrap(), b"hello");
+}
 
-    // Verify the export has no aux files
-    let mut read_dir = fs::read_dir(base_dir.path()).await.unwrap();
-    let mut entries = Vec::new();
+#[tokio::test(flavor = "multi_thread")]
+async fn lock_reason() {
+    test_utils::init_log();
 
-    while let Some(path) = read_dir.next_entry().await.unwrap().map(|e| e.path()) {
-        if path.starts_with(&dst_path) {
-            entries.push(path);
-        }
-    }
+    let base_dir = TempDir::new().unwrap();
+    let params = RepositoryParams::new(base_dir.path().join("repo.db"));
+    let read_secret = SetLocalSecret::random();
+    let write_secret = SetLocalSecret::random();
+    let wrong_secret = SetLocalSecret::random();
 
-    assert_eq!(entries, [dst_path.clone()]);
+    let repo = Repository::create(
+        &params,
+        Access::WriteLocked {
+            local_read_secret: read_secret.clone(),
+            local_write_secret: write_secret.clone(),
+            secrets: WriteSecrets::random(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(repo.lock_reason().await.unwrap(), LockReason::Unlocked);
+    repo.close().await.unwrap();
 
-    // Open the exported repo
-    let dst_repo = Repository::open(&RepositoryParams::new(dst_path), None, AccessMode::Write)
+    // No password
+    let repo = Repository::open(&params, None, AccessMode::Write)
         .await
         .unwrap();
+    assert_eq!(repo.access_mode(), AccessMode::Blind);
+    assert_eq!(
+        repo.lock_reason().await.unwrap(),
+        LockReason::NeedsReadPassword
+    );
+    repo.close().await.unwrap();
 
-    // Verify the repo is read-only with no password
-    assert_eq!(dst_repo.access_mode(), AccessMode::Read);
-
-    // Verify we can read the content
-    let mut file = dst_repo.open_file("test.dat").await.unwrap();
-    let dst_content = file.read_to_end().await.unwrap();
-    assert_eq!(dst_content, src_content);
+    // Wrong password
+    assert_matches!(
+        Repository::open(
+            &params,
+            Some(wrong_secret.clone().into()),
+            AccessMode::Write,
+        )
+        .await,
+        Err(Error::WrongPassword)
+    );
 
-    // Verify we can lock the repo and then unlock it without password
-    dst_repo
-        .set_access_mode(AccessMode::Blind, None)
+    let repo = Repository::open(&params, None, AccessMode::Write)
         .await
         .unwrap();
-    dst_repo
-        .set_access_mode(AccessMode::Read, None)
+    assert_matches!(
+        repo.set_access_mode(AccessMode::Write, Some(wrong_secret.clone().into()))
+            .await,
+        Err(Error::WrongPassword)
+    );
+    assert_eq!(repo.access_mode(), AccessMode::Blind);
+    assert_eq!(repo.lock_reason().await.unwrap(), LockReason::WrongPassword);
+
+    // Read password
+    repo.set_access_mode(AccessMode::Write, Some(read_secret.clone().into()))
         .await
         .unwrap();
-    assert_eq!(dst_repo.access_mode(), AccessMode::Read);
-}
+    assert_eq!(repo.access_mode(), AccessMode::Read);
+    assert_eq!(
+        repo.lock_reason().await.unwrap(),
+        LockReason::NeedsWritePassword
+    );
 
-const DEFAULT_REPO_NAME: &str = "repo.db";
+    // Wrong password again
+    assert_matches!(
+        repo.set_access_mode(AccessMode::Write, Some(wrong_secret.into()))
+            .await,
+        Err(Error::WrongPassword)
+    );
+    assert_eq!(repo.access_mode(), AccessMode::Read);
+    assert_eq!(repo.lock_reason().await.unwrap(), LockReason::WrongPassword);
 
-async fn setup() -> (TempDir, Repository) {
-    test_utils::init_log();
+    // Write password
+    repo.set_access_mode(AccessMode::Write, Some(write_secret.into()))
+        .await
+        .unwrap();
+    assert_eq!(repo.access_mode(), AccessMode::Write);
+    assert_eq!(repo.lock_reason().await.unwrap(), LockReason::Unlocked);
+}
 
+#[tokio::test(flavor = "multi_thread")]
+async fn unlock_secrets_does_not_change_access_mode() {
     let base_dir = TempDir::new().unwrap();
+    let params = RepositoryParams::new(base_dir.path().join("repo.db"));
+    let read_secret = SetLocalSecret::random();
+    let write_secret = SetLocalSecret::random();
+
     let repo = Repository::create(
-        &RepositoryParams::new(base_dir.path().join(DEFAULT_REPO_NAME)),
-        Access::WriteUnlocked {
+        &params,
+        Access::WriteLocked {
+            local_read_secret: read_secret.clone(),
+            local_write_secret: write_secret.clone(),
             secrets: WriteSecrets::random(),
         },
     )
     .await
     .unwrap();
+    repo.close().await.unwrap();
 
-    (base_dir, repo)
-}
+    let repo = Repository::open(&params, Some(read_secret.clone().into()), AccessMode::Write)
+        .await
+        .unwrap();
+    assert_eq!(repo.access_mode(), AccessMode::Read);
 
-async fn read_file(repo: &Repository, path: impl AsRef<Utf8Path>) -> Vec<u8> {
-    let mut file = repo.open_file(path).await.unwrap();
-    file.read_to_end().await.unwrap()
+    for (secret, expected) in [
+        (SetLocalSecret::random(), AccessMode::Blind),
+        (read_secret, AccessMode::Read),
+        (write_secret, AccessMode::Write),
+    ] {
+        let secrets = repo.unlock_secrets(secret.into()).await.unwrap();
+        assert_eq!(secrets.access_mode(), expected);
+        assert_eq!(repo.access_mode(), AccessMode::Read);
+    }
 }
 
-#[instrument(skip(repo, content), fields(content.len = content.len()))]
-async fn create_remote_file(
+#[tokio::test(flavor = "multi_thread")]
+async fn export_and_import_access_secrets() {
+    let base_dir = TempDir::new().unwrap();
+    let read_secret = SetLocalSecret::random();
+    let write_secret = SetLocalSecret::random();
+    let secrets = WriteSecrets::random();
+
+    let repo = Repository::create(
+        &RepositoryParams::new(base_dir.path().join("a.db")),
+        Access::WriteLocked {
+            local_read_secret: read_secret.clone(),
+            local_write_secret: write_secret.clone(),
+            secrets: secrets.clone(),
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_matches!(
+        repo.export_access_secrets(SetLocalSecret::random().into())
+            .await,
+        Err(Error::WrongPassword)
+    );
+
+    let read_recovery = repo
+        .export_access_secrets(read_secret.into())
+        .await
+        .unwrap();
+    let write_recovery = repo
+        .export_access_secrets(write_secret.into())
+        .await
+        .unwrap();
+
+    // Replica on a new device which has only blind access.
+    let new_repo = Repository::create(
+        &RepositoryParams::new(base_dir.path().join("b.db")),
+        Access::Blind { id: secrets.id },
+    )
+    .await
+    .unwrap();
+    assert_eq!(new_repo.access_mode(), AccessMode::Blind);
+
+    new_repo
+        .import_access_secrets(&read_recovery)
+        .await
+        .unwrap();
+    assert_eq!(new_repo.access_mode(), AccessMode::Read);
+
+    new_repo
+        .import_access_secrets(&write_recovery)
+        .await
+        .unwrap();
+    assert_eq!(new_repo.access_mode(), AccessMode::Write);
+    assert_eq!(
+        new_repo.security_fingerprint(None),
+        repo.security_fingerprint(None)
+    );
+
+    // Importing lower access doesn't downgrade.
+    new_repo
+        .import_access_secrets(&read_recovery)
+        .await
+        .unwrap();
+    assert_eq!(new_repo.access_mode(), AccessMode::Write);
+
+    // Recovery string of a different repository
+    let other_recovery = AccessSecrets::random_write().to_recovery_string();
+    let blind_repo = Repository::create(
+        &RepositoryParams::new(base_dir.path().join("c.db")),
+        Access::Blind { id: secrets.id },
+    )
+    .await
+    .unwrap();
+    assert_matches!(
+        blind_repo.import_access_secrets(&other_recovery).await,
+        Err(Error::PermissionDenied)
+    );
+
+    assert_matches!(
+        blind_repo
+            .import_access_secrets("not a recovery string")
+            .await,
+        Err(Error::MalformedData)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn open_with_secrets() {
+    let base_dir = TempDir::new().unwrap();
+    let params = RepositoryParams::new(base_dir.path().join("repo.db"));
+    let read_secret = SetLocalSecret::random();
+    let write_secret = SetLocalSecret::random();
+    let wrong_secret = SetLocalSecret::random();
+
+    let repo = Repository::create(
+        &params,
+        Access::WriteLocked {
+            local_read_secret: read_secret.clone(),
+            local_write_secret: write_secret.clone(),
+            secrets: WriteSecrets::random(),
+        },
+    )
+    .await
+    .unwrap();
+    repo.close().await.unwrap();
+
+    assert_matches!(
+        Repository::open_with_secrets(&params, &[wrong_secret.clone().into()], AccessMode::Write)
+            .await,
+        Err(Error::WrongPassword)
+    );
+
+    for (secrets, expected_mode, expected_index) in [
+        (vec![], AccessMode::Blind, None),
+        (
+            vec![wrong_secret.clone(), read_secret.clone()],
+            AccessMode::Read,
+            Some(1),
+        ),
+        (
+            vec![read_secret.clone(), write_secret.clone()],
+            AccessMode::Write,
+            Some(1),
+        ),
+        (
+            vec![write_secret.clone(), read_secret.clone()],
+            AccessMode::Write,
+            Some(0),
+        ),
+    ] {
+        let secrets: Vec<LocalSecret> = secrets.into_iter().map(Into::into).collect();
+        let (repo, index) = Repository::open_with_secrets(&params, &secrets, AccessMode::Write)
+            .await
+            .unwrap();
+
+        assert_eq!(repo.access_mode(), expected_mode);
+        assert_eq!(index, expected_index);
+
+        repo.close().await.unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn verify_password_with_public_read_access() {
+    let base_dir = TempDir::new().unwrap();
+    let params = RepositoryParams::new(base_dir.path().join("repo.db"));
+    let write_secret = SetLocalSecret::random();
+
+    let repo = Repository::create(
+        &params,
+        Access::WriteLockedReadUnlocked {
+            local_write_secret: write_secret.clone(),
+            secrets: WriteSecrets::random(),
+        },
+    )
+    .await
+    .unwrap();
+    repo.close().await.unwrap();
+
+    let repo = Repository::open(&params, None, AccessMode::Write)
+        .await
+        .unwrap();
+    assert_eq!(repo.access_mode(), AccessMode::Read);
+
+    for (secret, expected) in [
+        (SetLocalSecret::random(), AccessMode::Blind),
+        (write_secret, AccessMode::Write),
+    ] {
+        assert_eq!(repo.verify_password(secret.into()).await.unwrap(), expected);
+        assert_eq!(repo.access_mode(), AccessMode::Read);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lock_reason_without_passwords() {
+    let (_base_dir, repo) = setup().await;
+
+    repo.set_access(
+        Some(AccessChange::Enable(None)),
+        Some(AccessChange::Disable),
+    )
+    .await
+    .unwrap();
+    repo.set_access_mode(AccessMode::Read, None).await.unwrap();
+
+    // Read access doesn't need a password. Write access is disabled but it's not possible to tell
+    // that apart from it being locked.
+    assert_eq!(
+        repo.lock_reason().await.unwrap(),
+        LockReason::NeedsWritePassword
+    );
+
+    // Blind only because we asked for it.
+    repo.set_access_mode(AccessMode::Blind, None).await.unwrap();
+    assert_eq!(repo.lock_reason().await.unwrap(), LockReason::Unlocked);
+}
+
+// FIXME: This sometimes fails because of a bug in sqlx: https://github.com/launchbadge/sqlx/issues/3217
+#[ignore]
+#[tokio::test(flavor = "multi_thread")]
+async fn aux_db_files_are_deleted_on_close() {
+    test_utils::init_log();
+
+    let (temp_dir, repo) = setup().await;
+
+    repo.close().await.unwrap();
+
+    let mut read_dir = fs::read_dir(temp_dir.path()).await.unwrap();
+    let mut entries = Vec::new();
+
+    while let Some(entry) = read_dir.next_entry().await.unwrap() {
+        entries.push(entry.path());
+    }
+
+    assert_eq!(entries, [temp_dir.path().join(DEFAULT_REPO_NAME)]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn export() {
+    let (base_dir, src_repo) = setup().await;
+
+    // Create some content
+    let src_content = random_bytes(1024);
+
+    let mut file = src_repo.create_file("test.dat").await.unwrap();
+    file.write_all(&src_content).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let dst_path = base_dir.path().join("export.db");
+    src_repo.export(&dst_path).await.unwrap();
+
+    // Verify the export has no aux files
+    let mut read_dir = fs::read_dir(base_dir.path()).await.unwrap();
+    let mut entries = Vec::new();
+
+    while let Some(path) = read_dir.next_entry().await.unwrap().map(|e| e.path()) {
+        if path.starts_with(&dst_path) {
+            entries.push(path);
+        }
+    }
+
+    assert_eq!(entries, [dst_path.clone()]);
+
+    // Open the exported repo
+    let dst_repo = Repository::open(&RepositoryParams::new(dst_path), None, AccessMode::Write)
+        .await
+        .unwrap();
+
+    // Verify the repo is read-only with no password
+    assert_eq!(dst_repo.access_mode(), AccessMode::Read);
+
+    // Verify we can read the content
+    let mut file = dst_repo.open_file("test.dat").await.unwrap();
+    let dst_content = file.read_to_end().await.unwrap();
+    assert_eq!(dst_content, src_content);
+
+    // Verify we can lock the repo and then unlock it without password
+    dst_repo
+        .set_access_mode(AccessMode::Blind, None)
+        .await
+        .unwrap();
+    dst_repo
+        .set_access_mode(AccessMode::Read, None)
+        .await
+        .unwrap();
+    assert_eq!(dst_repo.access_mode(), AccessMode::Read);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn export_and_import_archive() {
+    let (base_dir, src_repo) = setup().await;
+
+    let src_content = random_bytes(3 * BLOCK_SIZE);
+
+    let mut file = src_repo.create_file("test.dat").await.unwrap();
+    file.write_all(&src_content).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut archive = Vec::new();
+    src_repo.export_archive(&mut archive).await.unwrap();
+
+    let dst_repo = Repository::create(
+        &RepositoryParams::new(base_dir.path().join("dst.db")),
+        Access::WriteUnlocked {
+            secrets: src_repo.secrets().into_write_secrets().unwrap(),
+        },
+    )
+    .await
+    .unwrap();
+
+    dst_repo
+        .import_archive(&mut archive.as_slice())
+        .await
+        .unwrap();
+
+    wait_for(&dst_repo, || async {
+        let Ok(mut file) = dst_repo.open_file("test.dat").await else {
+            return false;
+        };
+
+        match file.read_to_end().await {
+            Ok(content) => {
+                assert_eq!(content, src_content);
+                true
+            }
+            Err(_) => false,
+        }
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn import_archive_rejects_tampered_block() {
+    let (base_dir, src_repo) = setup().await;
+
+    let mut file = src_repo.create_file("test.dat").await.unwrap();
+    file.write_all(&random_bytes(1024)).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut archive = Vec::new();
+    src_repo.export_archive(&mut archive).await.unwrap();
+
+    // Blocks are the last records before the end marker so flipping a byte near the end of the
+    // archive corrupts the content of the last block.
+    let index = archive.len() - 64;
+    archive[index] ^= 0xff;
+
+    let dst_repo = Repository::create(
+        &RepositoryParams::new(base_dir.path().join("dst.db")),
+        Access::WriteUnlocked {
+            secrets: src_repo.secrets().into_write_secrets().unwrap(),
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_matches!(
+        dst_repo.import_archive(&mut archive.as_slice()).await,
+        Err(Error::MalformedData)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn import_archive_rejects_unknown_version() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut archive = Vec::new();
+    repo.export_archive(&mut archive).await.unwrap();
+
+    // The format version follows the 8 magic bytes.
+    archive[8] = archive[8].wrapping_add(1);
+
+    assert_matches!(
+        repo.import_archive(&mut archive.as_slice()).await,
+        Err(Error::ArchiveVersionMismatch)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn import_archive_rejects_foreign_repository() {
+    let (base_dir, src_repo) = setup().await;
+
+    let mut archive = Vec::new();
+    src_repo.export_archive(&mut archive).await.unwrap();
+
+    let dst_repo = Repository::create(
+        &RepositoryParams::new(base_dir.path().join("dst.db")),
+        Access::WriteUnlocked {
+            secrets: WriteSecrets::random(),
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_matches!(
+        dst_repo.import_archive(&mut archive.as_slice()).await,
+        Err(Error::InvalidArgument)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn local_entries_are_available_offline() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("test.dat").await.unwrap();
+    file.write_all(&random_bytes(2 * BLOCK_SIZE)).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.create_directory("dir").await.unwrap();
+
+    assert!(repo.is_available_offline("/").await.unwrap());
+    assert!(repo.is_available_offline("test.dat").await.unwrap());
+    assert!(repo.is_available_offline("dir").await.unwrap());
+    assert_matches!(
+        repo.is_available_offline("missing.dat").await,
+        Err(Error::EntryNotFound)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn entry_with_missing_block_is_not_available_offline() {
+    let (_base_dir, repo) = setup().await;
+
+    for path in ["a.dat", "b.dat"] {
+        let mut file = repo.create_file(path).await.unwrap();
+        file.write_all(&random_bytes(2 * BLOCK_SIZE)).await.unwrap();
+        file.flush().await.unwrap();
+    }
+
+    // Remove the second block of "a.dat" (the first one contains the file header, without it the
+    // file couldn't be opened at all).
+    let blob_id = *repo.open_file("a.dat").await.unwrap().blob_id();
+    let block_ids: Vec<BlockId> = BlockIds::open(repo.local_branch().unwrap(), blob_id)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let mut tx = repo.shared.vault.store().begin_write().await.unwrap();
+    tx.remove_block(&block_ids[1]).await.unwrap();
+    tx.commit().await.unwrap();
+
+    assert!(!repo.is_available_offline("a.dat").await.unwrap());
+    assert!(repo.is_available_offline("b.dat").await.unwrap());
+    assert!(repo.is_available_offline("/").await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pinned_file_does_not_expire() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut pinned = repo.create_file("pinned.dat").await.unwrap();
+    pinned
+        .write_all(&random_bytes(2 * BLOCK_SIZE))
+        .await
+        .unwrap();
+    pinned.flush().await.unwrap();
+
+    let mut unpinned = repo.create_file("unpinned.dat").await.unwrap();
+    unpinned
+        .write_all(&random_bytes(2 * BLOCK_SIZE))
+        .await
+        .unwrap();
+    unpinned.flush().await.unwrap();
+
+    repo.create_directory("dir").await.unwrap();
+
+    repo.pin("pinned.dat").await.unwrap();
+    assert!(repo.is_pinned("pinned.dat").await.unwrap());
+    repo.unpin("pinned.dat").await.unwrap();
+    assert!(!repo.is_pinned("pinned.dat").await.unwrap());
+    repo.pin("pinned.dat").await.unwrap();
+
+    assert!(!repo.is_pinned("unpinned.dat").await.unwrap());
+    assert_matches!(repo.pin("dir").await, Err(Error::EntryIsDirectory));
+    assert_matches!(repo.pin("missing.dat").await, Err(Error::EntryNotFound));
+
+    repo.set_block_expiration(Some(Duration::from_millis(500)))
+        .await
+        .unwrap();
+
+    // Keep the files open so their blocks can be checked even after the blocks of the root
+    // directory expire.
+    timeout(Duration::from_secs(10), async {
+        while unpinned.is_available_offline().await.unwrap() {
+            time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(pinned.is_available_offline().await.unwrap());
+
+    // Garbage collection doesn't remove the pinned blocks either.
+    repo.gc().await.unwrap();
+
+    assert!(pinned.is_available_offline().await.unwrap());
+    assert!(!unpinned.is_available_offline().await.unwrap());
+
+    pinned.seek(SeekFrom::Start(0));
+    assert_eq!(pinned.read_to_end().await.unwrap().len(), 2 * BLOCK_SIZE);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pinned_file_does_not_expire_after_reopen() {
+    let (base_dir, repo) = setup().await;
+
+    let content = random_bytes(2 * BLOCK_SIZE);
+    let mut file = repo.create_file("pinned.dat").await.unwrap();
+    file.write_all(&content).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.pin("pinned.dat").await.unwrap();
+    repo.set_block_expiration(Some(Duration::from_millis(500)))
+        .await
+        .unwrap();
+
+    repo.close().await.unwrap();
+    drop(repo);
+
+    let repo = Repository::open(
+        &RepositoryParams::new(base_dir.path().join(DEFAULT_REPO_NAME)),
+        None,
+        AccessMode::Write,
+    )
+    .await
+    .unwrap();
+
+    // Keep the file open so its blocks can be checked even after the blocks of the root directory
+    // expire.
+    let mut file = repo.open_file("pinned.dat").await.unwrap();
+    time::sleep(Duration::from_millis(1500)).await;
+
+    assert!(file.is_available_offline().await.unwrap());
+    assert_eq!(file.read_to_end().await.unwrap(), content);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn local_file_range_is_available() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("test.dat").await.unwrap();
+    file.write_all(&random_bytes(3 * BLOCK_SIZE)).await.unwrap();
+    file.flush().await.unwrap();
+
+    assert!(file.is_range_available(0, 1024).await.unwrap());
+    assert!(file.is_range_available(0, file.len()).await.unwrap());
+    assert!(file
+        .is_range_available(BLOCK_SIZE as u64, BLOCK_SIZE as u64)
+        .await
+        .unwrap());
+    assert!(file.is_range_available(0, 0).await.unwrap());
+    // Past the end
+    assert!(file
+        .is_range_available(10 * BLOCK_SIZE as u64, BLOCK_SIZE as u64)
+        .await
+        .unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pool_warmup_persists() {
+    let (base_dir, repo) = setup().await;
+
+    assert_eq!(repo.pool_warmup().await.unwrap(), 0);
+
+    repo.set_pool_warmup(4).await.unwrap();
+    assert_eq!(repo.pool_warmup().await.unwrap(), 4);
+
+    repo.close().await.unwrap();
+    drop(repo);
+
+    let repo = Repository::open(
+        &RepositoryParams::new(base_dir.path().join(DEFAULT_REPO_NAME)),
+        None,
+        AccessMode::Write,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(repo.pool_warmup().await.unwrap(), 4);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_directory_created() {
+    let (_base_dir, repo) = setup().await;
+    repo.create_directory("dir").await.unwrap();
+
+    let mut events = pin!(repo.watch_directory("dir").await.unwrap());
+
+    repo.create_file("dir/test.txt").await.unwrap();
+
+    assert_eq!(
+        next_dir_event(&mut events).await,
+        Some(DirEvent::Created("test.txt".into()))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_directory_modified() {
+    let (_base_dir, repo) = setup().await;
+    repo.create_directory("dir").await.unwrap();
+    repo.create_file("dir/test.txt").await.unwrap();
+
+    let mut events = pin!(repo.watch_directory("dir").await.unwrap());
+
+    let mut file = repo.open_file("dir/test.txt").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
+
+    assert_eq!(
+        next_dir_event(&mut events).await,
+        Some(DirEvent::Modified("test.txt".into()))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_directory_removed() {
+    let (_base_dir, repo) = setup().await;
+    repo.create_directory("dir").await.unwrap();
+    repo.create_file("dir/test.txt").await.unwrap();
+
+    let mut events = pin!(repo.watch_directory("dir").await.unwrap());
+
+    repo.remove_entry("dir/test.txt").await.unwrap();
+
+    assert_eq!(
+        next_dir_event(&mut events).await,
+        Some(DirEvent::Removed("test.txt".into()))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_directory_directory_removed() {
+    let (_base_dir, repo) = setup().await;
+    repo.create_directory("dir").await.unwrap();
+
+    let mut events = pin!(repo.watch_directory("dir").await.unwrap());
+
+    repo.remove_entry("dir").await.unwrap();
+
+    assert_eq!(
+        next_dir_event(&mut events).await,
+        Some(DirEvent::DirectoryRemoved)
+    );
+    assert_eq!(next_dir_event(&mut events).await, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_directory_directory_moved() {
+    let (_base_dir, repo) = setup().await;
+    repo.create_directory("dir").await.unwrap();
+
+    let mut events = pin!(repo.watch_directory("dir").await.unwrap());
+
+    repo.move_entry("/", "dir", "/", "dir2").await.unwrap();
+
+    assert_eq!(
+        next_dir_event(&mut events).await,
+        Some(DirEvent::DirectoryRemoved)
+    );
+    assert_eq!(next_dir_event(&mut events).await, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribe_path_descendant_changed() {
+    let (_base_dir, repo) = setup().await;
+    repo.create_directory("dir").await.unwrap();
+    repo.create_directory("other").await.unwrap();
+
+    let mut events = pin!(repo.subscribe_path("dir").await.unwrap());
+
+    repo.create_file("other/test.txt").await.unwrap();
+    repo.create_file("dir/test.txt").await.unwrap();
+
+    let expected_vv = repo
+        .cd("/")
+        .await
+        .unwrap()
+        .lookup_unique("dir")
+        .unwrap()
+        .version_vector()
+        .into_owned();
+
+    // Intermediate versions might be reported too, but only as changes of the watched entry.
+    loop {
+        match next_path_event(&mut events).await {
+            Some(PathEvent::Changed(vv)) if vv == expected_vv => break,
+            Some(PathEvent::Changed(vv)) => assert!(vv < expected_vv),
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribe_path_file_removed() {
+    let (_base_dir, repo) = setup().await;
+    repo.create_file("test.txt").await.unwrap();
+
+    let mut events = pin!(repo.subscribe_path("test.txt").await.unwrap());
+
+    repo.remove_entry("test.txt").await.unwrap();
+
+    assert_eq!(next_path_event(&mut events).await, Some(PathEvent::Removed));
+    assert_eq!(next_path_event(&mut events).await, None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_missing_directory() {
+    let (_base_dir, repo) = setup().await;
+
+    assert_matches!(
+        repo.watch_directory("missing").await.map(|_| ()),
+        Err(Error::EntryNotFound)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn open_not_a_repository() {
+    test_utils::init_log();
+
+    let base_dir = TempDir::new().unwrap();
+
+    let open = |name: &str| {
+        let params = RepositoryParams::new(base_dir.path().join(name));
+        async move { Repository::open(&params, None, AccessMode::Write).await }
+    };
+
+    // Empty file
+    tokio::fs::write(base_dir.path().join("empty.db"), b"")
+        .await
+        .unwrap();
+    assert_matches!(open("empty.db").await, Err(Error::NotARepository));
+
+    // Truncated file
+    tokio::fs::write(base_dir.path().join("truncated.db"), b"SQLite format 3\0")
+        .await
+        .unwrap();
+    assert_matches!(open("truncated.db").await, Err(Error::NotARepository));
+
+    // Not a database
+    tokio::fs::write(base_dir.path().join("garbage.db"), random_bytes(4096))
+        .await
+        .unwrap();
+    assert_matches!(open("garbage.db").await, Err(Error::NotARepository));
+
+    // Database which is not a repository, with and without `user_version` set.
+    for (name, user_version) in [("foreign.db", 0), ("foreign-versioned.db", 3)] {
+        let path = base_dir.path().join(name);
+
+        let pool = sqlx::SqlitePool::connect_with(
+            sqlx::sqlite::SqliteConnectOptions::new()
+                .filename(&path)
+                .create_if_missing(true)
+                .journal_mode(sqlx::sqlite::SqliteJournalMode::Delete),
+        )
+        .await
+        .unwrap();
+        sqlx::query("CREATE TABLE foo (bar INTEGER)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(&format!("PRAGMA user_version = {user_version}"))
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let before = tokio::fs::read(&path).await.unwrap();
+
+        assert_matches!(open(name).await, Err(Error::NotARepository));
+
+        // The database has not been modified (not migrated, not switched to WAL) and no auxiliary
+        // files are left behind.
+        assert_eq!(tokio::fs::read(&path).await.unwrap(), before);
+        assert!(!base_dir.path().join(format!("{name}-wal")).exists());
+        assert!(!base_dir.path().join(format!("{name}-shm")).exists());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resolve_conflict() {
+    let (_base_dir, repo) = setup().await;
+
+    let local_branch = repo.local_branch().unwrap();
+    let remote_id = PublicKey::random();
+
+    let mut file = repo.create_file("test.txt").await.unwrap();
+    file.write_all(b"local").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    create_remote_file(&repo, remote_id, "test.txt", b"remote").await;
+
+    let conflicts = repo.conflicts().await.unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].path, "/test.txt");
+    assert_eq!(conflicts[0].versions.len(), 2);
+    assert!(conflicts[0]
+        .versions
+        .iter()
+        .any(|version| version.branch_id == *local_branch.id()));
+    assert!(conflicts[0]
+        .versions
+        .iter()
+        .any(|version| version.branch_id == remote_id));
+
+    repo.resolve_conflict("test.txt", b"merged").await.unwrap();
+
+    assert_eq!(repo.conflicts().await.unwrap(), []);
+    assert_eq!(read_file(&repo, "test.txt").await, b"merged");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn detect_conflict() {
+    let (_base_dir, repo) = setup().await;
+
+    let local_branch = repo.local_branch().unwrap();
+    let remote_id = PublicKey::random();
+
+    let mut file = repo.create_file("test.txt").await.unwrap();
+    file.write_all(b"local").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    assert_eq!(repo.detect_conflict("test.txt").await.unwrap(), None);
+
+    create_remote_file(&repo, remote_id, "test.txt", b"remote").await;
+
+    let conflict = repo.detect_conflict("test.txt").await.unwrap().unwrap();
+    assert_eq!(conflict.path, "/test.txt");
+    assert_eq!(conflict.versions.len(), 2);
+    assert!(conflict
+        .versions
+        .iter()
+        .any(|version| version.branch_id == *local_branch.id()));
+    assert!(conflict
+        .versions
+        .iter()
+        .any(|version| version.branch_id == remote_id));
+
+    repo.resolve_conflict("test.txt", b"merged").await.unwrap();
+    assert_eq!(repo.detect_conflict("test.txt").await.unwrap(), None);
+
+    assert_matches!(
+        repo.detect_conflict("missing.txt").await,
+        Err(Error::EntryNotFound)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rename_store() {
+    let (base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("test.txt").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let src = base_dir.path().join(DEFAULT_REPO_NAME);
+    let dst = base_dir.path().join("renamed").join(DEFAULT_REPO_NAME);
+
+    repo.rename_store(&dst).await.unwrap();
+
+    assert!(tokio::fs::metadata(&src).await.is_err());
+    assert!(tokio::fs::metadata(&dst).await.is_ok());
+
+    // The repository remains usable.
+    assert_eq!(read_file(&repo, "test.txt").await, b"hello");
+
+    let mut file = repo.create_file("other.txt").await.unwrap();
+    file.write_all(b"world").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    // Renaming to an existing file fails and keeps the repository at its current location.
+    tokio::fs::write(&src, b"").await.unwrap();
+    assert_matches!(repo.rename_store(&src).await, Err(Error::EntryExists));
+    assert_eq!(read_file(&repo, "other.txt").await, b"world");
+
+    repo.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribe_sync_progress() {
+    let (_base_dir, repo) = setup().await;
+    let mut progresses = pin!(repo.subscribe_sync_progress());
+
+    let progress = progresses.next().await.unwrap();
+    assert_eq!(progress, repo.sync_progress().await.unwrap());
+
+    let mut file = repo.create_file("test.txt").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let expected = repo.sync_progress().await.unwrap();
+    assert_ne!(expected, progress);
+
+    timeout(Duration::from_secs(10), async {
+        while progresses.next().await.unwrap() != expected {}
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn is_complete() {
+    let (_base_dir, repo) = setup().await;
+    assert!(repo.is_complete().await.unwrap());
+
+    let mut file = repo.create_file("test.txt").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    // All blocks are created locally so the repository stays complete.
+    assert!(repo.is_complete().await.unwrap());
+    timeout(Duration::from_secs(10), repo.on_complete())
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn is_complete_with_missing_block() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("test.dat").await.unwrap();
+    file.write_all(&random_bytes(2 * BLOCK_SIZE)).await.unwrap();
+    file.flush().await.unwrap();
+
+    let block_ids: Vec<BlockId> = BlockIds::open(repo.local_branch().unwrap(), *file.blob_id())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let mut tx = repo.shared.vault.store().begin_write().await.unwrap();
+    tx.remove_block(&block_ids[1]).await.unwrap();
+    tx.commit().await.unwrap();
+
+    assert!(!repo.is_complete().await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rotate_keys() {
+    let (base_dir, repo) = setup().await;
+
+    let content = random_bytes(2 * BLOCK_SIZE + 7);
+    let mut file = repo.create_file("dir/a.dat").await.unwrap();
+    file.write_all(&content).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.create_link("b", "dir/a.dat").await.unwrap();
+
+    let params = RepositoryParams::new(base_dir.path().join("rotated.db"));
+    let progresses: Vec<_> = repo
+        .rotate_keys(&params, None, None)
+        .try_collect()
+        .await
+        .unwrap();
+    // The total might be more than the content length if the repository changed during the
+    // rotation (e.g., due to a background job) and some of it had to be copied again.
+    let last = progresses.last().unwrap();
+    assert!(last.total >= content.len() as u64);
+    assert_eq!(last.value, last.total);
+
+    // Resuming a completed rotation copies nothing new.
+    let progresses: Vec<_> = repo
+        .rotate_keys(&params, None, None)
+        .try_collect()
+        .await
+        .unwrap();
+    let last = progresses.last().unwrap();
+    assert!(last.total >= content.len() as u64);
+    assert_eq!(last.value, last.total);
+
+    let rotated = Repository::open(&params, None, AccessMode::Write)
+        .await
+        .unwrap();
+    assert_eq!(rotated.access_mode(), AccessMode::Write);
+    assert_ne!(rotated.secrets().id(), repo.secrets().id());
+    assert_eq!(read_file(&rotated, "dir/a.dat").await, content);
+    assert_eq!(rotated.read_link("b").await.unwrap(), "dir/a.dat");
+
+    rotated.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rotate_keys_with_concurrent_changes() {
+    let (base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("dir/a.dat").await.unwrap();
+    file.write_all(b"a").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.create_link("b", "dir/a.dat").await.unwrap();
+
+    let params = RepositoryParams::new(base_dir.path().join("rotated.db"));
+    let mut stream = pin!(repo.rotate_keys(&params, None, None));
+
+    // Let it copy "dir" and "b" but not "dir/a.dat" yet.
+    stream.try_next().await.unwrap().unwrap();
+    stream.try_next().await.unwrap().unwrap();
+
+    // Change the repository in the middle of the rotation.
+    let mut file = repo.create_file("c.dat").await.unwrap();
+    file.write_all(b"c").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.remove_entry("b").await.unwrap();
+    repo.create_link("b", "c.dat").await.unwrap();
+    repo.remove_entry("dir/a.dat").await.unwrap();
+
+    while stream.try_next().await.unwrap().is_some() {}
+
+    let rotated = Repository::open(&params, None, AccessMode::Write)
+        .await
+        .unwrap();
+    assert_eq!(read_file(&rotated, "c.dat").await, b"c");
+    assert_eq!(rotated.read_link("b").await.unwrap(), "c.dat");
+    assert_matches!(
+        rotated.open_file("dir/a.dat").await,
+        Err(Error::EntryNotFound)
+    );
+
+    rotated.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rotate_keys_with_local_secret() {
+    let (base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("a.txt").await.unwrap();
+    file.write_all(b"old content").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let secret = SetLocalSecret::random();
+    let params = RepositoryParams::new(base_dir.path().join("rotated.db"));
+    repo.rotate_keys(&params, Some(secret.clone()), Some(secret.clone()))
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+
+    // Modify the file without changing its length and resume the rotation.
+    let mut file = repo.open_file("a.txt").await.unwrap();
+    file.write_all(b"new content").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.rotate_keys(&params, Some(secret.clone()), Some(secret.clone()))
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+
+    // The rotated repository is not accessible without the secret.
+    let rotated = Repository::open(&params, None, AccessMode::Write)
+        .await
+        .unwrap();
+    assert_eq!(rotated.access_mode(), AccessMode::Blind);
+    rotated.close().await.unwrap();
+
+    let rotated = Repository::open(&params, Some(secret.into()), AccessMode::Write)
+        .await
+        .unwrap();
+    assert_eq!(rotated.access_mode(), AccessMode::Write);
+    assert_eq!(read_file(&rotated, "a.txt").await, b"new content");
+
+    rotated.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn file_metadata() {
+    let (base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("image.png").await.unwrap();
+    file.write_all(b"not really a png").await.unwrap();
+    assert!(file.metadata().await.unwrap().is_empty());
+
+    let metadata = FileMetadata::from([("content-type".to_owned(), "image/png".to_owned())]);
+    file.set_metadata(metadata.clone()).await.unwrap();
+    drop(file);
+
+    let too_large = FileMetadata::from([("key".to_owned(), "x".repeat(MAX_FILE_METADATA_SIZE))]);
+    let mut file = repo.open_file("image.png").await.unwrap();
+    assert_matches!(
+        file.set_metadata(too_large).await,
+        Err(Error::InvalidArgument)
+    );
+    drop(file);
+
+    repo.close().await.unwrap();
+    drop(repo);
+
+    let repo = Repository::open(
+        &RepositoryParams::new(base_dir.path().join(DEFAULT_REPO_NAME)),
+        None,
+        AccessMode::Write,
+    )
+    .await
+    .unwrap();
+
+    let file = repo.open_file("image.png").await.unwrap();
+    assert_eq!(file.metadata().await.unwrap(), metadata);
+    drop(file);
+
+    assert_eq!(read_file(&repo, "image.png").await, b"not really a png");
+
+    let root = repo.cd("/").await.unwrap();
+    match root.lookup_unique("image.png").unwrap() {
+        JointEntryRef::File(entry) => assert_eq!(entry.metadata(), &metadata),
+        JointEntryRef::Directory(_) | JointEntryRef::Link(_) => unreachable!(),
+    }
+}
+
+#[test]
+fn create_on_custom_runtime() {
+    let custom_runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("custom")
+        .enable_all()
+        .build()
+        .unwrap();
+    let main_runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let base_dir = TempDir::new().unwrap();
+    let recorder = ThreadNameRecorder::default();
+
+    main_runtime.block_on(async {
+        let repo = Repository::create_on(
+            custom_runtime.handle(),
+            RepositoryParams::new(base_dir.path().join("repo.db")).with_recorder(recorder.clone()),
+            Access::WriteUnlocked {
+                secrets: WriteSecrets::random(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut file = repo.create_file("test.txt").await.unwrap();
+        file.write_all(b"hello").await.unwrap();
+        file.flush().await.unwrap();
+        drop(file);
+
+        assert_eq!(read_file(&repo, "test.txt").await, b"hello");
+
+        // Wait for the background worker to run some jobs.
+        timeout(Duration::from_secs(10), async {
+            while recorder.thread_names().is_empty() {
+                time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        repo.close().await.unwrap();
+    });
+
+    // The jobs ran on the custom runtime, not on the one the repository was created from.
+    let thread_names = recorder.thread_names();
+    assert!(!thread_names.is_empty());
+    assert!(thread_names.iter().all(|name| name == "custom"));
+}
+
+// Recorder that records the names of the threads the histograms are recorded on. The job monitors
+// record into histograms every time the background worker completes a job, so this tells where
+// the worker runs.
+#[derive(Clone, Default)]
+struct ThreadNameRecorder(Arc<BlockingMutex<Vec<String>>>);
+
+impl ThreadNameRecorder {
+    fn thread_names(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Recorder for ThreadNameRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, _: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::noop()
+    }
+
+    fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::noop()
+    }
+
+    fn register_histogram(&self, _: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(Arc::new(self.clone()))
+    }
+}
+
+impl HistogramFn for ThreadNameRecorder {
+    fn record(&self, _: f64) {
+        let name = std::thread::current().name().unwrap_or_default().to_owned();
+        self.0.lock().unwrap().push(name);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn size_breakdown() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("a.dat").await.unwrap();
+    file.write_all(&random_bytes(3 * BLOCK_SIZE / 2))
+        .await
+        .unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.create_directory("dir").await.unwrap();
+
+    let mut file = repo.create_file("dir/b.dat").await.unwrap();
+    file.write_all(&random_bytes(100)).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let size = repo.size_breakdown().await.unwrap();
+
+    assert_eq!(size.logical_bytes, 3 * BLOCK_SIZE as u64 / 2 + 100);
+    assert_eq!(size.block_size, BLOCK_SIZE as u64);
+    // At least root + dir + 2 blocks of a.dat + 1 block of b.dat. There might be more if some
+    // outdated blocks haven't been garbage collected yet.
+    assert!(size.block_count >= 5);
+    assert!(size.physical_bytes >= size.block_count * size.block_size);
+    assert_eq!(
+        size.overhead_bytes,
+        size.physical_bytes - size.logical_bytes
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn security_fingerprint_with_access_mode() {
+    let (_base_dir, repo) = setup().await;
+    let secrets = repo.secrets();
+
+    assert_eq!(
+        repo.security_fingerprint(None),
+        secrets.security_fingerprint()
+    );
+    assert_eq!(
+        repo.security_fingerprint(Some(AccessMode::Write)),
+        secrets.security_fingerprint()
+    );
+    assert_eq!(
+        repo.security_fingerprint(Some(AccessMode::Read)),
+        secrets.with_mode(AccessMode::Read).security_fingerprint()
+    );
+    assert_eq!(
+        repo.security_fingerprint(Some(AccessMode::Blind)),
+        secrets.with_mode(AccessMode::Blind).security_fingerprint()
+    );
+
+    // Higher mode than the current one is clamped to it.
+    repo.lock().await.unwrap();
+    assert_eq!(
+        repo.security_fingerprint(Some(AccessMode::Write)),
+        secrets.with_mode(AccessMode::Blind).security_fingerprint()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn size_breakdown_blind() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("a.dat").await.unwrap();
+    file.write_all(&random_bytes(3 * BLOCK_SIZE / 2))
+        .await
+        .unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.lock().await.unwrap();
+
+    // The files can't be read but the block level totals are still reported.
+    let size = repo.size_breakdown().await.unwrap();
+
+    assert_eq!(size.logical_bytes, 0);
+    assert_eq!(size.block_size, BLOCK_SIZE as u64);
+    // At least root + 2 blocks of a.dat.
+    assert!(size.block_count >= 3);
+    assert_eq!(size.overhead_bytes, size.physical_bytes);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn branches() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("a.dat").await.unwrap();
+    file.write_all(&random_bytes(2 * BLOCK_SIZE)).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let local_branch = repo.local_branch().unwrap();
+    let statuses = repo.branches().await.unwrap();
+
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0].branch_id, *local_branch.id());
+    assert_eq!(
+        statuses[0].version_vector,
+        local_branch.version_vector().await.unwrap()
+    );
+    assert!(statuses[0].is_complete);
+    assert_eq!(statuses[0].missing_blocks, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batch_commit() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("a.txt").await.unwrap();
+    file.write_all(b"old").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut batch = repo.batch().unwrap();
+
+    let file = repo.open_file("a.txt").await.unwrap();
+    let file = batch.add_file("a.txt", file).await.unwrap();
+    file.truncate(0).unwrap();
+    file.write_all(b"new").await.unwrap();
+    assert_matches!(file.flush().await, Err(Error::OperationNotSupported));
+
+    let file = batch.create_file("dir/sub/b.txt").await.unwrap();
+    file.write_all(b"created").await.unwrap();
+
+    let file = batch.create_file("dir/sub/c.txt").await.unwrap();
+    file.write_all(b"created too").await.unwrap();
+
+    // Nothing is visible before the commit, not even the parent directories.
+    assert_eq!(read_file(&repo, "a.txt").await, b"old");
+    assert_matches!(repo.open_directory("dir").await, Err(Error::EntryNotFound));
+
+    batch.commit().await.unwrap();
+
+    assert_eq!(read_file(&repo, "a.txt").await, b"new");
+    assert_eq!(read_file(&repo, "dir/sub/b.txt").await, b"created");
+    assert_eq!(read_file(&repo, "dir/sub/c.txt").await, b"created too");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batch_rollback() {
+    let (_base_dir, repo) = setup().await;
+
+    let mut file = repo.create_file("a.txt").await.unwrap();
+    file.write_all(b"old").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mut batch = repo.batch().unwrap();
+
+    let file = repo.open_file("a.txt").await.unwrap();
+    let file = batch.add_file("a.txt", file).await.unwrap();
+    file.truncate(0).unwrap();
+    file.write_all(b"new").await.unwrap();
+
+    batch.create_file("b.txt").await.unwrap();
+
+    // Create a conflicting file outside of the batch so the commit fails.
+    repo.create_file("b.txt")
+        .await
+        .unwrap()
+        .flush()
+        .await
+        .unwrap();
+
+    assert_matches!(batch.commit().await, Err(Error::EntryExists));
+
+    // None of the modifications have been applied.
+    assert_eq!(read_file(&repo, "a.txt").await, b"old");
+    assert_eq!(read_file(&repo, "b.txt").await, b"");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn find_duplicates() {
+    let (_base_dir, repo) = setup().await;
+
+    let content = random_bytes(2 * BLOCK_SIZE);
+    let mut other_content = content.clone();
+    other_content[0] = other_content[0].wrapping_add(1);
+
+    repo.create_directory("dir").await.unwrap();
+
+    for (path, content) in [
+        ("a.dat", &content[..]),
+        ("b.dat", &content[..]),
+        ("c.dat", &other_content[..]),
+        ("dir/d.dat", &content[..]),
+        ("e.dat", &[][..]),
+        ("f.dat", &[][..]),
+    ] {
+        let mut file = repo.create_file(path).await.unwrap();
+        file.write_all(content).await.unwrap();
+        file.flush().await.unwrap();
+    }
+
+    let mut groups = repo.find_duplicates().await.unwrap();
+    assert_eq!(groups.len(), 1);
+
+    let mut group: Vec<_> = groups.remove(0).into_iter().map(String::from).collect();
+    group.sort();
+    assert_eq!(group, ["/a.dat", "/b.dat", "/dir/d.dat"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn find_duplicates_with_progress() {
+    let (_base_dir, repo) = setup().await;
+
+    let content = random_bytes(BLOCK_SIZE);
+
+    for (path, content) in [
+        ("a.dat", &content[..]),
+        ("b.dat", &content[..]),
+        ("c.dat", &content[..10]),
+        ("d.dat", &content[..10]),
+        ("e.dat", &content[..20]),
+    ] {
+        let mut file = repo.create_file(path).await.unwrap();
+        file.write_all(content).await.unwrap();
+        file.flush().await.unwrap();
+    }
+
+    let events: Vec<_> = repo.duplicates_with_progress().try_collect().await.unwrap();
+
+    let progresses: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            DuplicatesSearchEvent::Progress(progress) => Some(*progress),
+            DuplicatesSearchEvent::Group(_) => None,
+        })
+        .collect();
+
+    // "e.dat" is the only file of its length so it's not hashed.
+    assert_eq!(
+        progresses,
+        [
+            Progress { value: 0, total: 4 },
+            Progress { value: 2, total: 4 },
+            Progress { value: 4, total: 4 },
+        ]
+    );
+
+    let groups = events
+        .iter()
+        .filter(|event| matches!(event, DuplicatesSearchEvent::Group(_)))
+        .count();
+    assert_eq!(groups, 2);
+}
+
+const DEFAULT_REPO_NAME: &str = "repo.db";
+
+#[tokio::test]
+async fn event_log() {
+    let (_base_dir, repo) = setup().await;
+    let branch_id = *repo.local_branch().unwrap().id();
+
+    // Disabled by default.
+    repo.create_file("a.txt")
+        .await
+        .unwrap()
+        .flush()
+        .await
+        .unwrap();
+    assert!(repo.event_log().is_empty());
+
+    repo.set_event_log_capacity(2);
+
+    for name in ["b.txt", "c.txt", "d.txt"] {
+        repo.create_file(name).await.unwrap().flush().await.unwrap();
+    }
+
+    // Only the most recent events are kept.
+    let log = repo.event_log();
+    assert_eq!(log.len(), 2);
+    assert!(log.iter().all(|event| matches!(
+        event.payload,
+        Payload::SnapshotApproved(id) if id == branch_id
+    ) || matches!(event.payload, Payload::MaintenanceCompleted)));
+
+    repo.set_event_log_capacity(0);
+    assert!(repo.event_log().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn follow_link() {
+    let (_base_dir, repo) = setup().await;
+
+    repo.create_directory("dir").await.unwrap();
+
+    let mut file = repo.create_file("dir/a.txt").await.unwrap();
+    file.write_all(b"hello").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    // Relative target
+    repo.create_link("dir/b.txt", "a.txt").await.unwrap();
+    // Absolute target pointing to another link
+    repo.create_link("c.txt", "/dir/b.txt").await.unwrap();
+    // Target with parent component
+    repo.create_link("dir/d", "../dir").await.unwrap();
+
+    assert_eq!(repo.read_link("dir/b.txt").await.unwrap(), "a.txt");
+    assert_eq!(repo.read_link("c.txt").await.unwrap(), "/dir/b.txt");
+    assert_matches!(repo.read_link("dir/a.txt").await, Err(Error::EntryIsFile));
+
+    assert_eq!(
+        repo.lookup_type("dir/b.txt").await.unwrap(),
+        EntryType::File
+    );
+    assert_eq!(repo.lookup_type("c.txt").await.unwrap(), EntryType::File);
+    assert_eq!(
+        repo.lookup_type("dir/d").await.unwrap(),
+        EntryType::Directory
+    );
+
+    assert_eq!(read_file(&repo, "dir/b.txt").await, b"hello");
+    assert_eq!(read_file(&repo, "c.txt").await, b"hello");
+
+    // Links to non-existing targets are allowed but can't be followed.
+    repo.create_link("e.txt", "missing.txt").await.unwrap();
+    assert_matches!(repo.lookup_type("e.txt").await, Err(Error::EntryNotFound));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn follow_cyclic_link() {
+    let (_base_dir, repo) = setup().await;
+
+    repo.create_link("a", "b").await.unwrap();
+    repo.create_link("b", "a").await.unwrap();
+
+    assert_matches!(repo.lookup_type("a").await, Err(Error::TooManyLinks));
+    assert_matches!(repo.open_file("a").await, Err(Error::TooManyLinks));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn copy_file() {
+    let (_base_dir, repo) = setup().await;
+    let branch = repo.local_branch().unwrap();
+
+    let content = random_bytes(2 * BLOCK_SIZE);
+
+    let mut file = repo.create_file("a.dat").await.unwrap();
+    file.write_all(&content).await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    repo.copy_entry("a.dat", "dir/b.dat").await.unwrap();
+    assert_eq!(read_file(&repo, "dir/b.dat").await, content);
+
+    // Both files share the same blocks.
+    let src_blob_id = *repo.open_file("a.dat").await.unwrap().blob_id();
+    let dst_blob_id = *repo.open_file("dir/b.dat").await.unwrap().blob_id();
+    assert_ne!(src_blob_id, dst_blob_id);
+
+    let src_block_ids: Vec<BlockId> = BlockIds::open(branch.clone(), src_blob_id)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let dst_block_ids: Vec<BlockId> = BlockIds::open(branch.clone(), dst_blob_id)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(src_block_ids, dst_block_ids);
+
+    assert_matches!(
+        repo.copy_entry("a.dat", "dir/b.dat").await,
+        Err(Error::EntryExists)
+    );
+
+    // Modifying the copy doesn't affect the original.
+    let mut file = repo.open_file("dir/b.dat").await.unwrap();
+    file.write_all(b"modified").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    assert_eq!(read_file(&repo, "a.dat").await, content);
+
+    // Removing the original keeps the shared blocks alive.
+    repo.remove_entry("a.dat").await.unwrap();
+    repo.gc().await.unwrap();
+
+    let mut expected = content;
+    expected[..8].copy_from_slice(b"modified");
+    assert_eq!(read_file(&repo, "dir/b.dat").await, expected);
+}
+
+async fn setup() -> (TempDir, Repository) {
+    test_utils::init_log();
+
+    let base_dir = TempDir::new().unwrap();
+    let repo = Repository::create(
+        &RepositoryParams::new(base_dir.path().join(DEFAULT_REPO_NAME)),
+        Access::WriteUnlocked {
+            secrets: WriteSecrets::random(),
+        },
+    )
+    .await
+    .unwrap();
+
+    (base_dir, repo)
+}
+
+async fn read_file(repo: &Repository, path: impl AsRef<Utf8Path>) -> Vec<u8> {
+    let mut file = repo.open_file(path).await.unwrap();
+    file.read_to_end().await.unwrap()
+}
+
+#[instrument(skip(repo, content), fields(content.len = content.len()))]
+async fn create_remote_file(
     repo: &Repository,
     remote_id: PublicKey,
     name: &str,
@@ -1320,6 +3304,26 @@ async fn create_file_in_directory(dir: &mut Directory, name: &str, content: &[u8
     file
 }
 
+async fn next_dir_event(
+    events: &mut (impl Stream<Item = Result<DirEvent>> + Unpin),
+) -> Option<DirEvent> {
+    timeout(Duration::from_secs(10), events.next())
+        .await
+        .unwrap()
+        .transpose()
+        .unwrap()
+}
+
+async fn next_path_event(
+    events: &mut (impl Stream<Item = Result<PathEvent>> + Unpin),
+) -> Option<PathEvent> {
+    timeout(Duration::from_secs(10), events.next())
+        .await
+        .unwrap()
+        .transpose()
+        .unwrap()
+}
+
 fn random_bytes(size: usize) -> Vec<u8> {
     let mut buffer = vec![0; size];
     rand::thread_rng().fill(&mut buffer[..]);
@@ -12,6 +12,7 @@ use crate::{
     event::EventSender,
     protocol::{RepositoryId, StorageSize},
     store::Store,
+    transfer_tracker::TransferTracker,
 };
 use sqlx::Row;
 use std::{sync::Arc, time::Duration};
@@ -23,6 +24,7 @@ pub(crate) struct Vault {
     store: Store,
     pub event_tx: EventSender,
     pub block_tracker: BlockTracker,
+    pub transfer_tracker: TransferTracker,
     pub monitor: Arc<RepositoryMonitor>,
 }
 
@@ -34,12 +36,14 @@ impl Vault {
         monitor: RepositoryMonitor,
     ) -> Self {
         let store = Store::new(pool);
+        let transfer_tracker = TransferTracker::new(event_tx.clone());
 
         Self {
             repository_id,
             store,
             event_tx,
             block_tracker: BlockTracker::new(),
+            transfer_tracker,
             monitor: Arc::new(monitor),
         }
     }
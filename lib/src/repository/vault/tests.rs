@@ -258,6 +258,46 @@ async fn prune_snapshots_keep_missing_and_insert_missing() {
     assert_eq!(count_snapshots(&index, &remote_id).await, 1);
 }
 
+#[tokio::test]
+async fn prune_snapshots_over_retention() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let (_base_dir, vault, secrets) = setup_with_rng(&mut rng).await;
+
+    let remote_id = PublicKey::generate(&mut rng);
+
+    // snapshot 1
+    let mut blocks = [rng.gen(), rng.gen()];
+    let snapshot = Snapshot::new(blocks.clone());
+    receive_snapshot(&vault, remote_id, &snapshot, &secrets.write_keys).await;
+
+    let block_0 = blocks[0].1.clone();
+    let block_1 = blocks[1].1.clone();
+
+    // snapshot 2 (update the first block)
+    blocks[0].1 = rng.gen();
+    let snapshot = Snapshot::new(blocks.clone());
+    receive_snapshot(&vault, remote_id, &snapshot, &secrets.write_keys).await;
+
+    // snapshot 3 (update the second block)
+    blocks[1].1 = rng.gen();
+    let snapshot = Snapshot::new(blocks);
+    receive_snapshot(&vault, remote_id, &snapshot, &secrets.write_keys).await;
+
+    // Receive only the original blocks. This makes every snapshot a fallback for the next one.
+    receive_block(&vault, &block_0).await;
+    receive_block(&vault, &block_1).await;
+
+    prune_snapshots(&vault, &remote_id).await;
+    assert_eq!(count_snapshots(&vault, &remote_id).await, 3);
+
+    prune_snapshots_over_retention(&vault, &remote_id, 2).await;
+    assert_eq!(count_snapshots(&vault, &remote_id).await, 2);
+
+    // The latest snapshot is always kept.
+    prune_snapshots_over_retention(&vault, &remote_id, 0).await;
+    assert_eq!(count_snapshots(&vault, &remote_id).await, 1);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn block_ids_local() {
     let (_base_dir, vault, secrets) = setup().await;
@@ -648,3 +688,19 @@ async fn prune_snapshots(vault: &Vault, writer_id: &PublicKey) {
         .await
         .unwrap();
 }
+
+async fn prune_snapshots_over_retention(vault: &Vault, writer_id: &PublicKey, count: u32) {
+    let root_node = vault
+        .store()
+        .acquire_read()
+        .await
+        .unwrap()
+        .load_latest_approved_root_node(writer_id, RootNodeFilter::Any)
+        .await
+        .unwrap();
+    vault
+        .store()
+        .remove_snapshots_over_retention(&root_node, count)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,240 @@
+//! Watching a directory for changes of its entries and a single entry for changes of itself or its
+//! descendants.
+
+use super::Repository;
+use crate::{
+    directory::EntryType,
+    error::{Error, Result},
+    event::Event,
+    path,
+    version_vector::VersionVector,
+};
+use camino::Utf8PathBuf;
+use futures_util::{stream, Stream};
+use std::collections::{BTreeMap, VecDeque};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Change of an entry in a watched directory.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum DirEvent {
+    /// Entry with the given name was created.
+    Created(String),
+    /// Entry with the given name was modified.
+    Modified(String),
+    /// Entry with the given name was removed.
+    Removed(String),
+    /// The watched directory itself was removed or moved away. No more events follow.
+    DirectoryRemoved,
+}
+
+/// Change of a watched entry.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum PathEvent {
+    /// The entry or, if it's a directory, any of its descendants changed. Carries the new version
+    /// vector of the entry, merged across all its versions.
+    Changed(VersionVector),
+    /// The entry was removed or moved away. No more events follow.
+    Removed,
+}
+
+/// Watches the directory at `path` and yields an event for every entry that was created, modified
+/// or removed in it. The events are computed by diffing the directory content across the
+/// repository change notifications, so multiple changes of the same entry that happen in quick
+/// succession might be reported only once. Changes in subdirectories are reported as
+/// modifications of the subdirectory entry.
+///
+/// The watch is path based - if the directory is removed or moved to a different path,
+/// `DirEvent::DirectoryRemoved` is yielded and the stream ends.
+pub(super) async fn watch(
+    repo: &Repository,
+    path: Utf8PathBuf,
+) -> Result<impl Stream<Item = Result<DirEvent>> + '_> {
+    // Subscribe before loading the initial snapshot so no change is missed.
+    let event_rx = repo.subscribe();
+
+    let mut watcher = Watcher {
+        repo,
+        event_rx,
+        path,
+        snapshot: Snapshot::new(),
+        pending: VecDeque::new(),
+        done: false,
+    };
+
+    watcher.snapshot = watcher.load().await?.ok_or(Error::EntryNotFound)?;
+
+    Ok(stream::try_unfold(watcher, |mut watcher| async move {
+        Ok(watcher.next().await?.map(|event| (event, watcher)))
+    }))
+}
+
+/// Entries of a directory, with the version vectors of all their versions merged.
+type Snapshot = BTreeMap<String, (EntryType, VersionVector)>;
+
+struct Watcher<'a> {
+    repo: &'a Repository,
+    event_rx: broadcast::Receiver<Event>,
+    path: Utf8PathBuf,
+    snapshot: Snapshot,
+    pending: VecDeque<DirEvent>,
+    done: bool,
+}
+
+impl Watcher<'_> {
+    async fn next(&mut self) -> Result<Option<DirEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+
+            if self.done {
+                return Ok(None);
+            }
+
+            match self.event_rx.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => (),
+                Err(RecvError::Closed) => return Ok(None),
+            }
+
+            let Some(new) = self.load().await? else {
+                self.pending.push_back(DirEvent::DirectoryRemoved);
+                self.done = true;
+                continue;
+            };
+
+            diff(&self.snapshot, &new, &mut self.pending);
+            self.snapshot = new;
+        }
+    }
+
+    /// Loads the current snapshot of the directory or `None` if it doesn't exist.
+    async fn load(&self) -> Result<Option<Snapshot>> {
+        let dir = match self.repo.cd(&self.path).await {
+            Ok(dir) => dir,
+            Err(Error::EntryNotFound) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let mut snapshot = Snapshot::new();
+
+        for entry in dir.entries() {
+            let (entry_type, vv) = snapshot
+                .entry(entry.name().to_owned())
+                .or_insert_with(|| (entry.entry_type(), VersionVector::new()));
+
+            // If there are versions of different types, the directory wins because that's what
+            // `cd` resolves the name to.
+            if entry.entry_type() == EntryType::Directory {
+                *entry_type = EntryType::Directory;
+            }
+
+            vv.merge(&entry.version_vector());
+        }
+
+        Ok(Some(snapshot))
+    }
+}
+
+/// Watches the entry at `path` and yields an event every time its version vector changes. Because
+/// modifying an entry bumps the version vectors of all its ancestor directories, this reports
+/// changes of the entry itself as well as of any of its descendants. As with [`watch`], multiple
+/// changes in quick succession might be reported only once.
+///
+/// The watch is path based - if the entry is removed or moved to a different path,
+/// `PathEvent::Removed` is yielded and the stream ends.
+pub(super) async fn watch_path(
+    repo: &Repository,
+    path: Utf8PathBuf,
+) -> Result<impl Stream<Item = Result<PathEvent>> + '_> {
+    // Subscribe before loading the initial version so no change is missed.
+    let event_rx = repo.subscribe();
+
+    let mut watcher = PathWatcher {
+        repo,
+        event_rx,
+        path,
+        version_vector: VersionVector::new(),
+        done: false,
+    };
+
+    watcher.version_vector = watcher.load().await?.ok_or(Error::EntryNotFound)?;
+
+    Ok(stream::try_unfold(watcher, |mut watcher| async move {
+        Ok(watcher.next().await?.map(|event| (event, watcher)))
+    }))
+}
+
+struct PathWatcher<'a> {
+    repo: &'a Repository,
+    event_rx: broadcast::Receiver<Event>,
+    path: Utf8PathBuf,
+    version_vector: VersionVector,
+    done: bool,
+}
+
+impl PathWatcher<'_> {
+    async fn next(&mut self) -> Result<Option<PathEvent>> {
+        while !self.done {
+            match self.event_rx.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => (),
+                Err(RecvError::Closed) => return Ok(None),
+            }
+
+            let Some(new) = self.load().await? else {
+                self.done = true;
+                return Ok(Some(PathEvent::Removed));
+            };
+
+            if new != self.version_vector {
+                self.version_vector = new.clone();
+                return Ok(Some(PathEvent::Changed(new)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Loads the current version vector of the entry or `None` if it doesn't exist.
+    async fn load(&self) -> Result<Option<VersionVector>> {
+        let Some((parent, name)) = path::decompose(&self.path) else {
+            return Ok(Some(self.repo.current_version().await?));
+        };
+
+        let dir = match self.repo.cd(parent).await {
+            Ok(dir) => dir,
+            Err(Error::EntryNotFound) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let mut vv: Option<VersionVector> = None;
+
+        for entry in dir.lookup(name) {
+            vv.get_or_insert_with(VersionVector::new)
+                .merge(&entry.version_vector());
+        }
+
+        Ok(vv)
+    }
+}
+
+fn diff(old: &Snapshot, new: &Snapshot, events: &mut VecDeque<DirEvent>) {
+    for (name, (old_type, old_vv)) in old {
+        match new.get(name) {
+            Some((new_type, _)) if new_type != old_type => {
+                events.push_back(DirEvent::Removed(name.clone()));
+                events.push_back(DirEvent::Created(name.clone()));
+            }
+            Some((_, new_vv)) if new_vv != old_vv => {
+                events.push_back(DirEvent::Modified(name.clone()));
+            }
+            Some(_) => (),
+            None => events.push_back(DirEvent::Removed(name.clone())),
+        }
+    }
+
+    for name in new.keys() {
+        if !old.contains_key(name) {
+            events.push_back(DirEvent::Created(name.clone()));
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use self::utils::{unlock, Command, Counter};
-use super::Shared;
+use super::{pin, Shared};
 use crate::{
     blob::{BlobId, BlockIds},
     branch::Branch,
@@ -58,7 +58,10 @@ pub(super) async fn run(shared: Arc<Shared>) {
                     })
                     | Err(Lagged) => Some(Command::Wait),
                     Ok(Event {
-                        payload: Payload::SnapshotRejected(_) | Payload::MaintenanceCompleted,
+                        payload:
+                            Payload::SnapshotRejected(_)
+                            | Payload::MaintenanceCompleted
+                            | Payload::TransferStateChanged,
                         ..
                     }) => None,
                 })
@@ -104,7 +107,10 @@ pub(super) async fn run(shared: Arc<Shared>) {
                     })
                     | Err(Lagged) => Some(Command::Wait),
                     Ok(Event {
-                        payload: Payload::SnapshotRejected(_) | Payload::MaintenanceCompleted,
+                        payload:
+                            Payload::SnapshotRejected(_)
+                            | Payload::MaintenanceCompleted
+                            | Payload::TransferStateChanged,
                         ..
                     }) => None,
                 })
@@ -154,7 +160,7 @@ async fn maintain(
             .vault
             .monitor
             .trash_job
-            .run(trash::run(shared, local_branch, unlock_tx))
+            .run(async { trash::run(shared, local_branch, unlock_tx).await.map(drop) })
             .await;
         success = success && job_success;
     }
@@ -164,6 +170,27 @@ async fn maintain(
     }
 }
 
+/// Prunes outdated branches and snapshots and removes unreachable blocks right away, independently
+/// of the background worker. Returns the number of removed blocks.
+pub(super) async fn collect_garbage(shared: &Shared) -> Result<u64> {
+    let local_branch = shared
+        .local_branch()
+        .ok()
+        .filter(|branch| branch.keys().write().is_some());
+
+    // Nobody waits for the unlock notifications here. Locked blobs are still skipped and collected
+    // later by the background worker.
+    let (unlock_tx, _) = unlock::channel();
+
+    prune::run(shared, &unlock_tx, &Counter::new()).await?;
+
+    if shared.credentials.read().unwrap().secrets.can_read() {
+        trash::run(shared, local_branch.as_ref(), &unlock_tx).await
+    } else {
+        Ok(0)
+    }
+}
+
 async fn scan(shared: &Shared, prune_counter: &Counter) {
     // Find missing blocks
     shared
@@ -225,7 +252,10 @@ mod scan {
             }
         }
 
-        traverse(shared, JointDirectory::new(None, versions)).await
+        traverse(shared, JointDirectory::new(None, versions)).await?;
+
+        // Pick up blocks of the pinned files that changed since the last scan.
+        pin::refresh(shared).await
     }
 
     #[async_recursion]
@@ -242,6 +272,8 @@ mod scan {
                     )
                     .await?;
                 }
+                // Links have no blocks of their own.
+                JointEntryRef::Link(_) => (),
                 JointEntryRef::Directory(entry) => {
                     for version in entry.versions() {
                         require_missing_blocks(shared, version.branch(), *version.blob_id())
@@ -318,7 +350,7 @@ mod scan {
 /// Merge remote branches into the local one.
 mod merge {
     use super::*;
-    use crate::store;
+    use crate::{repository::metadata, store};
 
     pub(super) async fn run(shared: &Shared, local_branch: &Branch) -> Result<()> {
         let branches: Vec<_> = shared.load_branches().await?;
@@ -342,8 +374,13 @@ mod merge {
             }
         }
 
+        let strategy = {
+            let mut conn = shared.vault.store().db().acquire().await?;
+            metadata::merge_strategy::get(&mut conn).await?
+        };
+
         match JointDirectory::new(Some(local_branch.clone()), roots)
-            .merge()
+            .merge_with(strategy)
             .await
         {
             Ok(_) | Err(Error::AmbiguousEntry) => Ok(()),
@@ -355,7 +392,7 @@ mod merge {
 /// Remove outdated branches and snapshots.
 mod prune {
     use super::*;
-    use crate::{protocol::NodeState, versioned::PreferBranch};
+    use crate::{protocol::NodeState, repository::metadata, versioned::PreferBranch};
     use futures_util::TryStreamExt;
 
     pub(super) async fn run(
@@ -431,6 +468,11 @@ mod prune {
             );
         }
 
+        let retention = {
+            let mut conn = shared.vault.store().db().acquire().await?;
+            metadata::snapshot_retention::get(&mut conn).await?
+        };
+
         // Remove outdated snapshots.
         for node in uptodate {
             shared
@@ -438,6 +480,14 @@ mod prune {
                 .store()
                 .remove_outdated_snapshots(&node)
                 .await?;
+
+            if retention > 0 {
+                shared
+                    .vault
+                    .store()
+                    .remove_snapshots_over_retention(&node, retention)
+                    .await?;
+            }
         }
 
         Ok(())
@@ -462,12 +512,13 @@ mod trash {
         shared: &Shared,
         local_branch: Option<&Branch>,
         unlock_tx: &unlock::Sender,
-    ) -> Result<()> {
+    ) -> Result<u64> {
         // Perform the scan in multiple passes, to avoid loading too many block ids into memory.
         const UNREACHABLE_BLOCKS_PAGE_SIZE: u32 = 1_000_000;
 
         let mut unreachable_block_ids_page =
             shared.vault.store().block_ids(UNREACHABLE_BLOCKS_PAGE_SIZE);
+        let mut removed_count = 0;
 
         loop {
             let mut unreachable_block_ids = unreachable_block_ids_page.next().await?;
@@ -491,10 +542,11 @@ mod trash {
                 traverse_root_in_local_branch(local_branch, &mut unreachable_block_ids).await?;
             }
 
-            remove_unreachable_blocks(shared, local_branch, unreachable_block_ids).await?;
+            removed_count +=
+                remove_unreachable_blocks(shared, local_branch, unreachable_block_ids).await?;
         }
 
-        Ok(())
+        Ok(removed_count)
     }
 
     async fn traverse_root_in_all_branches(
@@ -562,6 +614,7 @@ mod trash {
                         )
                         .await?;
                     }
+                    JointEntryRef::Link(_) => (),
                     JointEntryRef::Directory(entry) => {
                         for version in entry.versions() {
                             if Some(version.branch().id()) == skip_branch_id {
@@ -645,7 +698,7 @@ mod trash {
         shared: &Shared,
         local_branch: Option<&Branch>,
         unreachable_block_ids: BTreeSet<BlockId>,
-    ) -> Result<()> {
+    ) -> Result<u64> {
         // We need to delete the blocks and also mark them as missing (so they can be requested in
         // case they become needed again) in their corresponding leaf nodes and then update the
         // summaries of the corresponding ancestor nodes. This is a complex and potentially
@@ -700,7 +753,7 @@ mod trash {
             tracing::debug!("unreachable blocks removed: {}", total_count);
         }
 
-        Ok(())
+        Ok(total_count as u64)
     }
 
     async fn remove_local_nodes(
@@ -3,6 +3,7 @@ use crate::{
     db,
     protocol::{Block, BlockContent, BlockId, BlockNonce, BLOCK_SIZE},
 };
+use futures_util::TryStreamExt;
 use sqlx::Row;
 
 /// Reads a block from the store into a buffer.
@@ -95,6 +96,25 @@ pub(super) async fn count(conn: &mut db::Connection) -> Result<u64, Error> {
     ))
 }
 
+/// Returns the total size of the stored blocks (content and nonce) in bytes.
+pub(super) async fn total_size(conn: &mut db::Connection) -> Result<u64, Error> {
+    Ok(db::decode_u64(
+        sqlx::query("SELECT COALESCE(SUM(LENGTH(nonce) + LENGTH(content)), 0) FROM blocks")
+            .fetch_one(conn)
+            .await?
+            .get(0),
+    ))
+}
+
+/// Returns the ids of all the blocks in the store.
+pub(super) async fn load_all_ids(conn: &mut db::Connection) -> Result<Vec<BlockId>, Error> {
+    Ok(sqlx::query("SELECT id FROM blocks")
+        .fetch(conn)
+        .map_ok(|row| row.get(0))
+        .try_collect()
+        .await?)
+}
+
 /// Checks whether the block exists in the store.
 #[cfg(test)]
 pub(super) async fn exists(conn: &mut db::Connection, id: &BlockId) -> Result<bool, Error> {
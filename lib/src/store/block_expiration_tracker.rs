@@ -60,6 +60,7 @@ impl BlockExpirationTracker {
         expiration_time: Duration,
         block_download_tracker: BlockDownloadTracker,
         client_reload_index_tx: broadcast_hash_set::Sender<PublicKey>,
+        pinned_blocks: Arc<BlockingMutex<HashSet<BlockId>>>,
     ) -> Result<Self, Error> {
         let mut shared = Shared {
             blocks_by_id: Default::default(),
@@ -98,6 +99,7 @@ impl BlockExpirationTracker {
                     expiration_time_rx,
                     block_download_tracker,
                     client_reload_index_tx,
+                    pinned_blocks,
                 )
                 .await
                 {
@@ -284,6 +286,7 @@ async fn run_task(
     mut expiration_time_rx: watch::Receiver<Duration>,
     block_download_tracker: BlockDownloadTracker,
     client_reload_index_tx: broadcast_hash_set::Sender<PublicKey>,
+    pinned_blocks: Arc<BlockingMutex<HashSet<BlockId>>>,
 ) -> Result<(), Error> {
     loop {
         let expiration_time = *expiration_time_rx.borrow();
@@ -360,6 +363,16 @@ async fn run_task(
             }
         }
 
+        if pinned_blocks.lock().unwrap().contains(&block_id) {
+            // Pinned blocks never expire. Check again after another expiration period in case
+            // the block gets unpinned in the meantime.
+            shared
+                .lock()
+                .unwrap()
+                .insert_block(&block_id, SystemTime::now());
+            continue;
+        }
+
         let mut tx = pool.begin_write().await?;
 
         if !leaf_node::set_expired_if_present(&mut tx, &block_id).await? {
@@ -493,6 +506,7 @@ mod test {
             Duration::from_secs(1),
             BlockDownloadTracker::new(),
             broadcast_hash_set::channel().0,
+            Default::default(),
         )
         .await
         .unwrap();
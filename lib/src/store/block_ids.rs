@@ -1,6 +1,6 @@
 use super::error::Error;
 use crate::{
-    crypto::sign::PublicKey,
+    crypto::{sign::PublicKey, Hash},
     db,
     protocol::{BlockId, NodeState, SingleBlockPresence},
 };
@@ -67,6 +67,35 @@ impl BlockIdsPage {
     }
 }
 
+/// Returns the number of distinct missing block ids referenced from the snapshot with the given root
+/// hash. Only the part of the snapshot that's been downloaded so far is taken into account.
+pub(super) async fn count_missing_in(
+    conn: &mut db::Connection,
+    root_hash: &Hash,
+) -> Result<u64, Error> {
+    Ok(db::decode_u64(
+        sqlx::query(
+            "WITH RECURSIVE
+                 inner_nodes(hash) AS (
+                     SELECT hash FROM snapshot_inner_nodes WHERE parent = ?
+                     UNION ALL
+                     SELECT c.hash
+                         FROM snapshot_inner_nodes AS c
+                         INNER JOIN inner_nodes AS p ON p.hash = c.parent
+                 )
+             SELECT COUNT(DISTINCT block_id)
+                 FROM snapshot_leaf_nodes
+                 WHERE parent IN inner_nodes AND block_presence = ?
+             ",
+        )
+        .bind(root_hash)
+        .bind(SingleBlockPresence::Missing)
+        .fetch_one(conn)
+        .await?
+        .get(0),
+    ))
+}
+
 /// Yields all missing block ids referenced from the latest complete snapshot of the given branch.
 pub(super) fn missing_block_ids_in_branch<'a>(
     conn: &'a mut db::Connection,
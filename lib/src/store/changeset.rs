@@ -1,10 +1,11 @@
-use super::{block, error::Error, patch::Patch, WriteTransaction};
+use super::{block, error::Error, patch::Patch, quota, WriteTransaction};
 use crate::{
     crypto::{
         sign::{Keypair, PublicKey},
         Hash,
     },
-    protocol::{Block, BlockId, Bump, SingleBlockPresence},
+    protocol::{Block, BlockId, Bump, SingleBlockPresence, StorageSize},
+    repository,
 };
 
 /// Recorded changes to be applied to the store as a single unit.
@@ -15,6 +16,7 @@ pub(crate) struct Changeset {
     blocks: Vec<Block>,
     bump: Bump,
     bump_force: bool,
+    reset: bool,
 }
 
 impl Changeset {
@@ -24,14 +26,21 @@ impl Changeset {
 
     /// Applies this changeset to the transaction.
     /// Returns `true` if any change was performed, or `false` if the changeset is a no-op.
+    ///
+    /// Fails with `QuotaExceeded` if the repository has a quota set and applying the changeset
+    /// would grow the repository beyond it. Changes that don't grow the repository (e.g., removals)
+    /// are always allowed so it's possible to get back under the quota.
     pub async fn apply(
         self,
         tx: &mut WriteTransaction,
         branch_id: &PublicKey,
         write_keys: &Keypair,
     ) -> Result<bool, Error> {
-        let mut patch = Patch::new(tx, *branch_id).await?;
-        let mut changed = false;
+        let (mut patch, mut changed) = if self.reset {
+            (Patch::new_empty(tx, *branch_id).await?, true)
+        } else {
+            (Patch::new(tx, *branch_id).await?, false)
+        };
 
         for (encoded_locator, block_id, block_presence) in self.links {
             if patch
@@ -56,7 +65,45 @@ impl Changeset {
         }
 
         if changed {
-            patch.save(tx, self.bump, write_keys).await?;
+            if self.reset {
+                // The patch doesn't know how many blocks the discarded snapshot referenced so we
+                // have to measure the size before and after. Resets are rare so this is fine.
+                let quota = repository::quota::get(tx.db()).await?;
+                let old_size = if quota.is_some() {
+                    Some(quota::latest_size(tx.db()).await?)
+                } else {
+                    None
+                };
+
+                patch.save(tx, self.bump, write_keys).await?;
+
+                if let (Some(quota), Some(old_size)) = (quota, old_size) {
+                    let new_size = quota::latest_size(tx.db()).await?;
+
+                    if new_size > quota && new_size > old_size {
+                        return Err(Error::QuotaExceeded);
+                    }
+                }
+            } else {
+                // Only growing changes are checked and only those need to measure the current
+                // size. The delta is an upper bound: the inserted blocks might be already
+                // referenced from other branches.
+                let delta = patch.leaf_count_delta();
+
+                if delta > 0 {
+                    if let Some(quota) = repository::quota::get(tx.db()).await? {
+                        let old_size = quota::latest_size(tx.db()).await?;
+                        let new_size =
+                            old_size.saturating_add(StorageSize::from_blocks(delta as u64));
+
+                        if new_size > quota {
+                            return Err(Error::QuotaExceeded);
+                        }
+                    }
+                }
+
+                patch.save(tx, self.bump, write_keys).await?;
+            }
         }
 
         for block in self.blocks {
@@ -103,4 +150,11 @@ impl Changeset {
     pub fn force_bump(&mut self, force: bool) {
         self.bump_force = force;
     }
+
+    /// Set whether to discard the current content of the branch before applying the links and
+    /// unlinks, building the snapshot from scratch. The version vector is preserved. Default is
+    /// `false`.
+    pub fn reset(&mut self, reset: bool) {
+        self.reset = reset;
+    }
 }
@@ -21,7 +21,7 @@ use crate::{
     },
     repository, StorageSize,
 };
-use std::{mem, sync::Arc};
+use std::{mem, sync::Arc, time::SystemTime};
 
 /// Store operations for the client side of the sync protocol.
 pub(crate) struct ClientWriter {
@@ -87,6 +87,7 @@ impl ClientWriter {
         }
 
         let mut new_children = Vec::with_capacity(nodes.len());
+        let mut new_blocks_children = Vec::new();
         let nodes = nodes.into_inner();
 
         for (_, remote_node) in &nodes {
@@ -96,11 +97,12 @@ impl ClientWriter {
             }
 
             let local_node = inner_node::load(&mut self.db, &remote_node.hash).await?;
-            if local_node
-                .map(|local_node| local_node.summary.is_outdated(&remote_node.summary))
-                .unwrap_or(true)
-            {
-                new_children.push(*remote_node);
+            match local_node {
+                Some(local_node) if !local_node.summary.is_outdated(&remote_node.summary) => (),
+                Some(local_node) if local_node.summary.state != NodeState::Incomplete => {
+                    new_blocks_children.push(*remote_node)
+                }
+                Some(_) | None => new_children.push(*remote_node),
             }
         }
 
@@ -111,7 +113,10 @@ impl ClientWriter {
             self.summary_updates.push(parent_hash);
         }
 
-        Ok(InnerNodesStatus { new_children })
+        Ok(InnerNodesStatus {
+            new_children,
+            new_blocks_children,
+        })
     }
 
     pub async fn save_leaf_nodes(
@@ -215,6 +220,9 @@ impl ClientWriter {
             rejected_branches,
         } = self.finalize_snapshots().await?;
 
+        repository::branch_sync_times::update(&mut self.db, &approved_branches, SystemTime::now())
+            .await?;
+
         let approved_missing_blocks = self
             .load_approved_missing_blocks(&approved_branches)
             .await?;
@@ -371,8 +379,12 @@ impl ClientReader {
 
 #[derive(Default)]
 pub(crate) struct InnerNodesStatus {
-    /// Which of the received nodes should we request the children of.
+    /// Which of the received nodes should we request the children of because we don't have all
+    /// of them yet.
     pub new_children: Vec<InnerNode>,
+    /// Which of the received nodes should we request the children of because, although we already
+    /// have all of them, the peer has some blocks we don't.
+    pub new_blocks_children: Vec<InnerNode>,
 }
 
 #[derive(Default)]
@@ -437,6 +449,7 @@ mod tests {
     };
     use futures_util::{StreamExt, TryStreamExt};
     use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::time::Duration;
 
     mod future {
         pub use futures_util::future::join;
@@ -493,6 +506,39 @@ mod tests {
             .is_some());
     }
 
+    #[tokio::test]
+    async fn approved_branch_sync_time_is_recorded() {
+        let (_base_dir, pool) = db::create_temp().await.unwrap();
+        let store = Store::new(pool);
+        let remote_id = PublicKey::random();
+        let secrets = WriteSecrets::random();
+
+        let before = SystemTime::now();
+
+        let mut writer = store.begin_client_write().await.unwrap();
+        writer
+            .save_root_node(
+                Proof::new(
+                    remote_id,
+                    VersionVector::first(remote_id),
+                    *EMPTY_INNER_HASH,
+                    &secrets.write_keys,
+                ),
+                &MultiBlockPresence::None,
+            )
+            .await
+            .unwrap();
+        writer.commit().await.unwrap();
+
+        let mut conn = store.db().acquire().await.unwrap();
+        let times = repository::branch_sync_times::get(&mut conn).await.unwrap();
+
+        assert_eq!(times.len(), 1);
+        // Stored with millisecond precision.
+        assert!(times[&remote_id] + Duration::from_millis(1) >= before);
+        assert!(times[&remote_id] <= SystemTime::now());
+    }
+
     #[tokio::test]
     async fn save_duplicate_root_node() {
         let (_base_dir, pool) = db::create_temp().await.unwrap();
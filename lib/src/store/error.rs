@@ -16,4 +16,6 @@ pub enum Error {
     LocatorNotFound,
     #[error("block not found")]
     BlockNotFound,
+    #[error("storage quota exceeded")]
+    QuotaExceeded,
 }
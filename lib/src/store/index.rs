@@ -1,7 +1,14 @@
 //! Operations on the whole index (or its subset) as opposed to individual nodes.
 
 use super::{error::Error, inner_node, root_node};
-use crate::{collections::HashMap, crypto::Hash, db, future::TryStreamExt, protocol::NodeState};
+use crate::{
+    collections::HashMap,
+    crypto::Hash,
+    db,
+    future::TryStreamExt,
+    progress::Progress,
+    protocol::{NodeState, EMPTY_INNER_HASH, EMPTY_LEAF_HASH},
+};
 use sqlx::Row;
 
 /// Does a parent node (root or inner) with the given hash exist?
@@ -18,6 +25,36 @@ pub(super) async fn parent_exists(conn: &mut db::Connection, hash: &Hash) -> Res
     .get(0))
 }
 
+/// Progress of the index download: number of non-empty parent nodes (root or inner) whose children
+/// have been received / number of all non-empty parent nodes. Note the total grows as more inner
+/// nodes are received, so this can temporarily decrease.
+pub(super) async fn progress(conn: &mut db::Connection) -> Result<Progress, Error> {
+    let row = sqlx::query(
+        "WITH parents(hash) AS (
+             SELECT hash FROM snapshot_root_nodes
+             UNION
+             SELECT hash FROM snapshot_inner_nodes
+         )
+         SELECT
+             COUNT(*),
+             COALESCE(SUM(
+                 EXISTS(SELECT 0 FROM snapshot_inner_nodes WHERE parent = parents.hash) OR
+                 EXISTS(SELECT 0 FROM snapshot_leaf_nodes  WHERE parent = parents.hash)
+             ), 0)
+         FROM parents
+         WHERE hash <> ? AND hash <> ?",
+    )
+    .bind(&*EMPTY_INNER_HASH)
+    .bind(&*EMPTY_LEAF_HASH)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(Progress {
+        value: db::decode_u64(row.get(1)),
+        total: db::decode_u64(row.get(0)),
+    })
+}
+
 /// Update summary of the nodes with the specified hashes and all their ancestor nodes.
 /// Returns the affected snapshots and their states.
 pub(super) async fn update_summaries(
@@ -458,6 +495,54 @@ mod tests {
         pool.close().await.unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn progress_counts_received_children() {
+        let (_base_dir, pool) = setup().await;
+
+        let leaves: Vec<LeafNodes> = (0..2)
+            .map(|_| iter::once(LeafNode::missing(rand::random(), rand::random())).collect())
+            .collect();
+        let inners: InnerNodes = leaves
+            .iter()
+            .enumerate()
+            .map(|(bucket, nodes)| {
+                (
+                    bucket as u8,
+                    InnerNode::new(nodes.hash(), Summary::INCOMPLETE),
+                )
+            })
+            .collect();
+
+        let mut tx = pool.begin_write().await.unwrap();
+        assert_eq!(
+            progress(&mut tx).await.unwrap(),
+            Progress { value: 0, total: 0 }
+        );
+
+        inner_node::save_all(&mut tx, &inners, &rand::random())
+            .await
+            .unwrap();
+        assert_eq!(
+            progress(&mut tx).await.unwrap(),
+            Progress { value: 0, total: 2 }
+        );
+
+        for (index, nodes) in leaves.iter().enumerate() {
+            leaf_node::save_all(&mut tx, nodes, &nodes.hash())
+                .await
+                .unwrap();
+            assert_eq!(
+                progress(&mut tx).await.unwrap(),
+                Progress {
+                    value: index as u64 + 1,
+                    total: 2
+                }
+            );
+        }
+
+        tx.commit().await.unwrap();
+    }
+
     async fn setup() -> (TempDir, db::Pool) {
         db::create_temp().await.unwrap()
     }
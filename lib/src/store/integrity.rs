@@ -0,0 +1,161 @@
+//! Verification of the stored index nodes and blocks against their hashes.
+
+use super::{block, error::Error, inner_node, leaf_node, root_node, Store};
+use crate::{
+    collections::HashSet,
+    crypto::{Hash, Hashable},
+    protocol::{
+        BlockContent, BlockId, SingleBlockPresence, EMPTY_INNER_HASH, EMPTY_LEAF_HASH,
+        INNER_LAYER_COUNT,
+    },
+};
+use futures_util::{stream, Stream, TryStreamExt};
+use std::collections::VecDeque;
+
+/// Problem found by the repository integrity check.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum IntegrityReport {
+    /// The children of the index node with the given hash don't hash to it.
+    NodeHashMismatch(Hash),
+    /// The block is marked as present in the index but it's not in the store.
+    BlockMissing(BlockId),
+    /// The content of the block doesn't hash to its id.
+    BlockCorrupted(BlockId),
+}
+
+/// Walks the index of the latest approved snapshots of all branches, checking that the children of
+/// every node hash to the hash of the node and that every block marked as present is stored and
+/// its content hashes to its id. Yields the problems as they are found.
+///
+/// Each node is checked in its own read transaction so the check doesn't block writers and can
+/// run on a live repository. Nodes that are removed (e.g., by pruning outdated snapshots) while
+/// the check is running are skipped.
+pub(super) fn verify(store: &Store) -> impl Stream<Item = Result<IntegrityReport, Error>> + '_ {
+    let verifier = Verifier {
+        store,
+        started: false,
+        queue: VecDeque::new(),
+        visited: HashSet::default(),
+        reports: VecDeque::new(),
+    };
+
+    stream::try_unfold(verifier, |mut verifier| async move {
+        Ok(verifier.next().await?.map(|report| (report, verifier)))
+    })
+}
+
+struct Verifier<'a> {
+    store: &'a Store,
+    started: bool,
+    // Nodes yet to be checked, with their layers (root is layer 0).
+    queue: VecDeque<(Hash, usize)>,
+    // Nodes already checked. Nodes are shared among snapshots and branches so this avoids
+    // checking them more than once.
+    visited: HashSet<Hash>,
+    // Problems found but not yet yielded.
+    reports: VecDeque<IntegrityReport>,
+}
+
+impl Verifier<'_> {
+    async fn next(&mut self) -> Result<Option<IntegrityReport>, Error> {
+        loop {
+            if let Some(report) = self.reports.pop_front() {
+                return Ok(Some(report));
+            }
+
+            if !self.started {
+                self.started = true;
+                self.load_roots().await?;
+                continue;
+            }
+
+            let Some((hash, layer)) = self.queue.pop_front() else {
+                return Ok(None);
+            };
+
+            self.check_node(hash, layer).await?;
+        }
+    }
+
+    async fn load_roots(&mut self) -> Result<(), Error> {
+        let mut reader = self.store.acquire_read().await?;
+        let roots: Vec<_> = root_node::load_all_latest_approved(reader.db())
+            .try_collect()
+            .await?;
+
+        self.queue
+            .extend(roots.into_iter().map(|root| (root.proof.hash, 0)));
+
+        Ok(())
+    }
+
+    async fn check_node(&mut self, hash: Hash, layer: usize) -> Result<(), Error> {
+        if hash == *EMPTY_INNER_HASH || hash == *EMPTY_LEAF_HASH {
+            return Ok(());
+        }
+
+        if !self.visited.insert(hash) {
+            return Ok(());
+        }
+
+        let mut tx = self.store.begin_read().await?;
+
+        if layer < INNER_LAYER_COUNT {
+            let children = inner_node::load_children(tx.db(), &hash).await?;
+
+            // No children means they haven't been downloaded yet (or the node has been removed
+            // meanwhile). Either way there is nothing to check.
+            if children.is_empty() {
+                return Ok(());
+            }
+
+            if children.hash() != hash {
+                self.reports
+                    .push_back(IntegrityReport::NodeHashMismatch(hash));
+            }
+
+            self.queue
+                .extend(children.into_iter().map(|(_, node)| (node.hash, layer + 1)));
+        } else {
+            let children = leaf_node::load_children(tx.db(), &hash).await?;
+
+            if children.is_empty() {
+                return Ok(());
+            }
+
+            if children.hash() != hash {
+                self.reports
+                    .push_back(IntegrityReport::NodeHashMismatch(hash));
+            }
+
+            let mut content = BlockContent::new();
+
+            for node in &children {
+                match node.block_presence {
+                    SingleBlockPresence::Present => (),
+                    SingleBlockPresence::Missing | SingleBlockPresence::Expired => continue,
+                }
+
+                match block::read(tx.db(), &node.block_id, &mut content).await {
+                    Ok(nonce) => {
+                        if BlockId::new(&content, &nonce) != node.block_id {
+                            self.reports
+                                .push_back(IntegrityReport::BlockCorrupted(node.block_id));
+                        }
+                    }
+                    Err(Error::BlockNotFound) => {
+                        self.reports
+                            .push_back(IntegrityReport::BlockMissing(node.block_id));
+                    }
+                    Err(Error::MalformedData) => {
+                        self.reports
+                            .push_back(IntegrityReport::BlockCorrupted(node.block_id));
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -207,6 +207,21 @@ pub(super) async fn count_block_ids(conn: &mut db::Connection) -> Result<u64, Er
     ))
 }
 
+/// Returns the number of distinct block ids referenced in the index whose blocks are not stored
+/// locally.
+pub(super) async fn count_missing_block_ids(conn: &mut db::Connection) -> Result<u64, Error> {
+    Ok(db::decode_u64(
+        sqlx::query(
+            "SELECT COUNT(DISTINCT block_id)
+             FROM snapshot_leaf_nodes
+             WHERE NOT EXISTS (SELECT 0 FROM blocks WHERE id = block_id)",
+        )
+        .fetch_one(conn)
+        .await?
+        .get(0),
+    ))
+}
+
 #[cfg(test)]
 #[async_recursion]
 pub(super) async fn count_in(
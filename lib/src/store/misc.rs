@@ -47,8 +47,8 @@ pub(super) async fn check_integrity(conn: &mut db::Connection) -> Result<bool, E
     }
 
     // TODO: Check for root nodes with invalid signatures
-    // TODO: Check for child nodes with invalid hashes
-    // TODO: Check for blocks with invalid ids
+    // NOTE: Child nodes with invalid hashes and blocks with invalid ids are detected by
+    // `Store::verify_integrity`.
 
     Ok(true)
 }
@@ -60,3 +60,13 @@ pub(super) async fn export(conn: &mut db::Connection, dst: &Path) -> Result<(),
         .await?;
     Ok(())
 }
+
+/// Returns the size of the main database file in bytes (the WAL is not included).
+pub(super) async fn database_size(conn: &mut db::Connection) -> Result<u64, Error> {
+    Ok(db::decode_u64(
+        sqlx::query("SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()")
+            .fetch_one(conn)
+            .await?
+            .get(0),
+    ))
+}
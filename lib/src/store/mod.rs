@@ -7,6 +7,7 @@ mod client;
 mod error;
 mod index;
 mod inner_node;
+mod integrity;
 mod leaf_node;
 mod migrations;
 mod misc;
@@ -20,12 +21,14 @@ mod test_utils;
 mod tests;
 
 pub use error::Error;
+pub use integrity::IntegrityReport;
 pub use migrations::DATA_VERSION;
 
 pub(crate) use {
     block_ids::BlockIdsPage,
     changeset::Changeset,
     client::{ClientReader, ClientWriter},
+    root_node::RootNodeStatus,
 };
 
 #[cfg(test)]
@@ -37,6 +40,7 @@ use self::{
 };
 use crate::{
     block_tracker::BlockTracker as BlockDownloadTracker,
+    collections::HashSet,
     crypto::{
         sign::{Keypair, PublicKey},
         Hash,
@@ -45,11 +49,12 @@ use crate::{
     debug::DebugPrinter,
     progress::Progress,
     protocol::{
-        BlockContent, BlockId, BlockNonce, InnerNodes, LeafNodes, RootNode, RootNodeFilter,
-        SingleBlockPresence,
+        BlockContent, BlockId, BlockNonce, InnerNodes, LeafNodes, MultiBlockPresence, NodeState,
+        RootNode, RootNodeFilter, SingleBlockPresence, StorageSize,
     },
     sync::broadcast_hash_set,
 };
+use deadlock::BlockingMutex;
 use futures_util::{Stream, TryStreamExt};
 use std::{
     borrow::Cow,
@@ -69,6 +74,8 @@ pub(crate) struct Store {
     block_id_cache: BlockIdCache,
     pub client_reload_index_tx: broadcast_hash_set::Sender<PublicKey>,
     block_expiration_tracker: Arc<RwLock<Option<Arc<BlockExpirationTracker>>>>,
+    // Blocks exempt from expiration.
+    pinned_blocks: Arc<BlockingMutex<HashSet<BlockId>>>,
 }
 
 impl Store {
@@ -80,6 +87,7 @@ impl Store {
             block_id_cache: BlockIdCache::new(),
             client_reload_index_tx,
             block_expiration_tracker: Arc::new(RwLock::new(None)),
+            pinned_blocks: Arc::new(BlockingMutex::new(HashSet::default())),
         }
     }
 
@@ -97,6 +105,12 @@ impl Store {
         misc::check_integrity(self.acquire_read().await?.db()).await
     }
 
+    /// Verifies the stored index nodes and blocks against their hashes. See
+    /// [integrity::verify] for details.
+    pub fn verify_integrity(&self) -> impl Stream<Item = Result<IntegrityReport, Error>> + '_ {
+        integrity::verify(self)
+    }
+
     pub async fn set_block_expiration(
         &self,
         expiration_time: Option<Duration>,
@@ -122,6 +136,7 @@ impl Store {
             expiration_time,
             block_download_tracker,
             self.client_reload_index_tx.clone(),
+            self.pinned_blocks.clone(),
         )
         .await?;
 
@@ -138,6 +153,11 @@ impl Store {
             .map(|tracker| tracker.block_expiration())
     }
 
+    /// Replaces the set of blocks that never expire, regardless of the block expiration.
+    pub fn set_pinned_blocks(&self, block_ids: HashSet<BlockId>) {
+        *self.pinned_blocks.lock().unwrap() = block_ids;
+    }
+
     #[cfg(test)]
     pub async fn block_expiration_tracker(&self) -> Option<Arc<BlockExpirationTracker>> {
         self.block_expiration_tracker.read().await.as_ref().cloned()
@@ -235,6 +255,38 @@ impl Store {
         })
     }
 
+    /// Returns whether all the blocks referenced from the latest approved snapshots of all the
+    /// branches are present, that is, whether there is nothing left to download. Unlike comparing
+    /// the values returned from `sync_progress` this doesn't count blocks that are not referenced
+    /// from any approved snapshot.
+    pub async fn is_complete(&self) -> Result<bool, Error> {
+        let mut reader = self.acquire_read().await?;
+        let mut root_nodes = reader.load_latest_approved_root_nodes();
+
+        while let Some(root_node) = root_nodes.try_next().await? {
+            if root_node.summary.block_presence != MultiBlockPresence::Full {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Size counted against the storage quota, that is, the size of the blocks referenced from the
+    /// latest snapshots of all the branches (each block counted once, whether present or not).
+    pub async fn quota_usage(&self) -> Result<StorageSize, Error> {
+        let mut reader = self.acquire_read().await?;
+        quota::latest_size(reader.db()).await
+    }
+
+    /// Retrieve the index download progress of this repository (number of parent nodes whose
+    /// children have been received / number of all parent nodes). Unlike `sync_progress` this
+    /// advances even before any blocks are downloaded.
+    pub async fn index_progress(&self) -> Result<Progress, Error> {
+        let mut reader = self.acquire_read().await?;
+        index::progress(reader.db()).await
+    }
+
     /// Remove outdated older snapshots.
     ///
     /// This preserves older snapshots that can be used as fallback for the latest snapshot and only
@@ -295,6 +347,49 @@ impl Store {
         Ok(())
     }
 
+    /// Removes all approved snapshots in the branch of the given root node except the latest
+    /// `count` ones. Unlike `remove_outdated_snapshots` this removes also snapshots that could
+    /// still serve as fallback. Snapshots newer than the given root node (e.g., those still being
+    /// downloaded) are never removed and neither is the latest approved snapshot, so `count` is
+    /// effectively at least 1. Drafts (snapshots with the same version vector as their successor)
+    /// are kept together with their successor and don't count towards `count`.
+    pub async fn remove_snapshots_over_retention(
+        &self,
+        root_node: &RootNode,
+        count: u32,
+    ) -> Result<(), Error> {
+        let mut reader = self.acquire_read().await?;
+
+        let mut kept = u32::from(root_node.summary.state == NodeState::Approved);
+        let mut new = Cow::Borrowed(root_node);
+
+        while let Some(old) = reader.load_prev_approved_root_node(&new).await? {
+            if old.proof.version_vector != new.proof.version_vector {
+                if kept >= count.max(1) {
+                    let mut tx = self.begin_write().await?;
+                    root_node::remove_older(tx.db(), &old).await?;
+                    root_node::remove(tx.db(), &old).await?;
+                    tx.commit().await?;
+
+                    tracing::trace!(
+                        branch_id = ?old.proof.writer_id,
+                        hash = ?old.proof.hash,
+                        vv = ?old.proof.version_vector,
+                        "snapshots over retention removed"
+                    );
+
+                    break;
+                }
+
+                kept += 1;
+            }
+
+            new = Cow::Owned(old);
+        }
+
+        Ok(())
+    }
+
     /// Returns all block ids referenced from complete snapshots. The result is paginated (with
     /// `page_size` entries per page) to avoid loading too many items into memory.
     pub fn block_ids(&self, page_size: u32) -> BlockIdsPage {
@@ -365,6 +460,33 @@ impl Reader {
         leaf_node::count_block_ids(self.db()).await
     }
 
+    /// Returns the number of distinct block ids referenced in the index whose blocks are missing.
+    pub async fn count_missing_block_ids(&mut self) -> Result<u64, Error> {
+        leaf_node::count_missing_block_ids(self.db()).await
+    }
+
+    /// Returns the ids of all the stored blocks, regardless of whether they are referenced from
+    /// the index.
+    pub async fn load_block_ids(&mut self) -> Result<Vec<BlockId>, Error> {
+        block::load_all_ids(self.db()).await
+    }
+
+    /// Returns the total size of the stored blocks in bytes.
+    pub async fn blocks_size(&mut self) -> Result<u64, Error> {
+        block::total_size(self.db()).await
+    }
+
+    /// Returns the number of missing blocks referenced from the snapshot with the given root hash.
+    pub async fn count_missing_blocks_in(&mut self, root_hash: &Hash) -> Result<u64, Error> {
+        block_ids::count_missing_in(self.db(), root_hash).await
+    }
+
+    /// Returns the size of the database in bytes. This includes the blocks as well as the index
+    /// and the metadata.
+    pub async fn database_size(&mut self) -> Result<u64, Error> {
+        misc::database_size(self.db()).await
+    }
+
     #[cfg(test)]
     pub async fn count_leaf_nodes_in_branch(
         &mut self,
@@ -26,6 +26,8 @@ pub(super) struct Patch {
     root_summary: Summary,
     inners: BTreeMap<Key, InnerNodes>,
     leaves: BTreeMap<Key, LeafNodes>,
+    // Number of leaf nodes added minus the number of leaf nodes removed by this patch.
+    leaf_count_delta: i64,
 }
 
 impl Patch {
@@ -56,13 +58,35 @@ impl Patch {
             root_summary,
             inners: BTreeMap::new(),
             leaves: BTreeMap::new(),
+            leaf_count_delta: 0,
         })
     }
 
+    /// Creates a patch that starts from an empty tree instead of the current snapshot. Only the
+    /// version vector of the current snapshot is preserved.
+    pub async fn new_empty(tx: &mut ReadTransaction, branch_id: PublicKey) -> Result<Self, Error> {
+        let mut patch = Self::new(tx, branch_id).await?;
+        patch.root_hash = *EMPTY_INNER_HASH;
+        patch.root_summary = Summary {
+            state: NodeState::Approved,
+            block_presence: crate::protocol::MultiBlockPresence::Full,
+        };
+
+        Ok(patch)
+    }
+
     pub fn version_vector(&self) -> &VersionVector {
         &self.vv
     }
 
+    /// Number of leaf nodes (that is, block references) inserted into the branch minus the number
+    /// of the removed ones. Replacing the block of an existing leaf node doesn't count. Note that
+    /// for patches created with `new_empty` the leaf nodes of the original snapshot are not
+    /// counted as removed.
+    pub fn leaf_count_delta(&self) -> i64 {
+        self.leaf_count_delta
+    }
+
     pub async fn insert(
         &mut self,
         tx: &mut ReadTransaction,
@@ -71,8 +95,13 @@ impl Patch {
         block_presence: SingleBlockPresence,
     ) -> Result<bool, Error> {
         let nodes = self.fetch(tx, &encoded_locator).await?;
+        let is_new = nodes.get(&encoded_locator).is_none();
         let changed = nodes.insert(encoded_locator, block_id, block_presence);
 
+        if is_new {
+            self.leaf_count_delta += 1;
+        }
+
         Ok(changed)
     }
 
@@ -90,6 +119,10 @@ impl Patch {
             nodes.remove(encoded_locator)
         };
 
+        if old.is_some() {
+            self.leaf_count_delta -= 1;
+        }
+
         Ok(old.is_some())
     }
 
@@ -27,6 +27,25 @@ pub(super) async fn check(
     }
 }
 
+/// Total size of the blocks referenced from the latest snapshots of all branches.
+pub(super) async fn latest_size(conn: &mut db::Connection) -> Result<StorageSize, StoreError> {
+    let mut nodes = Vec::new();
+
+    root_node::load_all_latest_approved(conn)
+        .try_collect_into(&mut nodes)
+        .await?;
+
+    let nodes = versioned::keep_maximal(nodes, ());
+
+    let mut hashes: Vec<_> = nodes.into_iter().map(|node| node.proof.hash).collect();
+    hashes.sort();
+    hashes.dedup();
+
+    let block_count = count_referenced_blocks(conn, &hashes).await?;
+
+    Ok(StorageSize::from_blocks(block_count))
+}
+
 #[derive(Debug, Error)]
 pub(super) enum QuotaError {
     #[error("quota exceeded")]
@@ -18,8 +18,14 @@ use std::{cmp::Ordering, fmt, future};
 pub(crate) enum RootNodeStatus {
     /// The node represents a new snapshot - write it into the store and requests its children.
     NewSnapshot,
-    /// We already have the node but its block presence indicated the peer potentially has some
-    /// blocks we don't have. Don't write it into the store but do request its children.
+    /// We already have the node but not all its descendants and its block presence indicated the
+    /// peer potentially has some blocks we don't have. Don't write it into the store but do request
+    /// its children. Same as `NewBlocks` except that replicas which don't store blocks still need
+    /// to request the children in this case, to complete the index.
+    Incomplete,
+    /// We already have the node and all its descendants but its block presence indicated the peer
+    /// potentially has some blocks we don't have. Don't write it into the store but do request its
+    /// children.
     NewBlocks,
     /// The node is outdated - discard it.
     Outdated,
@@ -28,7 +34,7 @@ pub(crate) enum RootNodeStatus {
 impl RootNodeStatus {
     pub fn request_children(&self) -> bool {
         match self {
-            Self::NewSnapshot | Self::NewBlocks => true,
+            Self::NewSnapshot | Self::Incomplete | Self::NewBlocks => true,
             Self::Outdated => false,
         }
     }
@@ -36,7 +42,7 @@ impl RootNodeStatus {
     pub fn write(&self) -> bool {
         match self {
             Self::NewSnapshot => true,
-            Self::NewBlocks | Self::Outdated => false,
+            Self::Incomplete | Self::NewBlocks | Self::Outdated => false,
         }
     }
 }
@@ -45,6 +51,7 @@ impl fmt::Display for RootNodeStatus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::NewSnapshot => write!(f, "new snapshot"),
+            Self::Incomplete => write!(f, "incomplete"),
             Self::NewBlocks => write!(f, "new blocks"),
             Self::Outdated => write!(f, "outdated"),
         }
@@ -577,7 +584,13 @@ pub(super) async fn status(
                         .block_presence
                         .is_outdated(new_block_presence)
                     {
-                        status = RootNodeStatus::NewBlocks;
+                        // Tell apart nodes we don't have all the children of yet so that replicas
+                        // with block storage disabled request them too.
+                        status = if old_node.summary.state == NodeState::Incomplete {
+                            RootNodeStatus::Incomplete
+                        } else {
+                            RootNodeStatus::NewBlocks
+                        };
                     } else {
                         status = RootNodeStatus::Outdated;
                     }
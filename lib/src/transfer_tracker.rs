@@ -0,0 +1,109 @@
+//! Tracking of block transfers that are currently in flight.
+
+use crate::event::{EventSender, Payload};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Tracks whether any blocks are currently being transferred to or from remote replicas.
+///
+/// Every outstanding block request (sent by us) and every block response being served (to a
+/// remote peer) holds a `TransferGuard`. The repository is considered to be transferring as long as
+/// at least one such guard exists. `Payload::TransferStateChanged` is emitted every time this
+/// flips.
+#[derive(Clone)]
+pub(crate) struct TransferTracker {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    count: AtomicUsize,
+    event_tx: EventSender,
+}
+
+impl TransferTracker {
+    pub fn new(event_tx: EventSender) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                count: AtomicUsize::new(0),
+                event_tx,
+            }),
+        }
+    }
+
+    /// Marks the beginning of a transfer. The transfer ends when the returned guard is dropped.
+    pub fn begin(&self) -> TransferGuard {
+        if self.shared.count.fetch_add(1, Ordering::AcqRel) == 0 {
+            self.shared.event_tx.send(Payload::TransferStateChanged);
+        }
+
+        TransferGuard {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Is there at least one transfer in flight?
+    pub fn is_transferring(&self) -> bool {
+        self.shared.count.load(Ordering::Acquire) > 0
+    }
+}
+
+pub(crate) struct TransferGuard {
+    shared: Arc<Shared>,
+}
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        if self.shared.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.event_tx.send(Payload::TransferStateChanged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Event;
+    use assert_matches::assert_matches;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    #[test]
+    fn flips() {
+        let event_tx = EventSender::new(16);
+        let mut event_rx = event_tx.subscribe();
+        let tracker = TransferTracker::new(event_tx);
+
+        assert!(!tracker.is_transferring());
+
+        let guard_0 = tracker.begin();
+        assert!(tracker.is_transferring());
+        assert_matches!(
+            event_rx.try_recv(),
+            Ok(Event {
+                payload: Payload::TransferStateChanged,
+                ..
+            })
+        );
+
+        // No change notification while already transferring.
+        let guard_1 = tracker.begin();
+        assert!(tracker.is_transferring());
+        assert_matches!(event_rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(guard_0);
+        assert!(tracker.is_transferring());
+        assert_matches!(event_rx.try_recv(), Err(TryRecvError::Empty));
+
+        // Back to idle after the last transfer completes.
+        drop(guard_1);
+        assert!(!tracker.is_transferring());
+        assert_matches!(
+            event_rx.try_recv(),
+            Ok(Event {
+                payload: Payload::TransferStateChanged,
+                ..
+            })
+        );
+    }
+}
@@ -88,6 +88,11 @@ impl VersionVector {
     pub fn is_empty(&self) -> bool {
         self.0.values().all(|version| *version == 0)
     }
+
+    /// Sum of the versions of all the entries.
+    pub fn total(&self) -> u64 {
+        self.0.values().sum()
+    }
 }
 
 // Less clutter in the debug output this way (as opposed to deriving).
@@ -417,4 +422,18 @@ mod tests {
         assert_eq!(vv![].saturating_sub(&vv![id0 => 1]), vv![]);
         assert_eq!(vv![id0 => 1].saturating_sub(&vv![id0 => 2]), vv![]);
     }
+
+    #[test]
+    fn serialize_roundtrip() {
+        let id0 = PublicKey::random();
+        let id1 = PublicKey::random();
+
+        for orig in [vv![], vv![id0 => 1], vv![id0 => 3, id1 => 7]] {
+            let serialized = rmp_serde::to_vec(&orig).unwrap();
+            let deserialized: VersionVector = rmp_serde::from_slice(&serialized).unwrap();
+
+            assert_eq!(deserialized, orig);
+            assert_eq!(rmp_serde::to_vec(&deserialized).unwrap(), serialized);
+        }
+    }
 }
@@ -73,6 +73,7 @@ async fn save_directory(dir: JointDirectory) -> Directory {
                 let dir = entry.open().await.unwrap();
                 Entry::Directory(save_directory(dir).await)
             }
+            JointEntryRef::Link(_) => continue,
         };
 
         entries.insert(name, dump);
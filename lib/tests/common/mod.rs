@@ -615,6 +615,7 @@ pub(crate) async fn check_entry_exists(
     let result = match entry_type {
         EntryType::File => repo.open_file(path).await.map(|_| ()),
         EntryType::Directory => repo.open_directory(path).await.map(|_| ()),
+        EntryType::Link => repo.read_link(path).await.map(|_| ()),
     };
 
     match result {
@@ -299,6 +299,40 @@ fn change_access_mode() {
     });
 }
 
+#[test]
+fn explicit_gc() {
+    let mut env = Env::new();
+
+    env.actor("local", async move {
+        let repo = actor::create_repo(DEFAULT_REPO).await;
+
+        // Write, overwrite with less content and delete.
+        let mut file = repo.create_file("1.dat").await.unwrap();
+        write_to_file(&mut file, 3 * BLOCK_SIZE - BLOB_HEADER_SIZE).await;
+        file.flush().await.unwrap();
+
+        file.truncate(0).unwrap();
+        write_to_file(&mut file, BLOCK_SIZE - BLOB_HEADER_SIZE).await;
+        file.flush().await.unwrap();
+        drop(file);
+
+        let mut file = repo.create_file("2.dat").await.unwrap();
+        write_to_file(&mut file, 2 * BLOCK_SIZE - BLOB_HEADER_SIZE).await;
+        file.flush().await.unwrap();
+        drop(file);
+
+        repo.remove_entry("2.dat").await.unwrap();
+
+        repo.gc().await.unwrap();
+
+        // 1 block for '1.dat' + 1 block for the root directory
+        assert_eq!(repo.count_blocks().await.unwrap(), 2);
+
+        // Nothing left to collect.
+        assert_eq!(repo.gc().await.unwrap(), 0);
+    });
+}
+
 async fn write_to_file(file: &mut File, size: usize) {
     file.write_all(&common::random_bytes(size)).await.unwrap();
 }
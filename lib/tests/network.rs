@@ -7,9 +7,12 @@
 mod common;
 
 use self::common::{actor, Env, Proto, DEFAULT_REPO, TEST_TIMEOUT};
-use ouisync::{Network, PeerState};
-use std::sync::Arc;
-use tokio::{sync::Barrier, time};
+use ouisync::{Network, PeerEvent, PeerEventKind, PeerSource, PeerState, TransportPreference};
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
+use tokio::{
+    sync::{broadcast, Barrier},
+    time,
+};
 
 // This test requires QUIC which is not yet supported in simulation
 #[test]
@@ -236,6 +239,378 @@ fn add_peer_before_bind() {
     });
 }
 
+#[test]
+fn bind_multiple_addresses_per_protocol() {
+    let mut env = Env::new();
+    let proto = Proto::Tcp;
+    let barrier = Arc::new(Barrier::new(2));
+
+    env.actor("alice", {
+        let barrier = barrier.clone();
+
+        async move {
+            let network = actor::create_unbound_network();
+            network
+                .bind(&[
+                    proto.wrap((Ipv4Addr::UNSPECIFIED, 0)),
+                    proto.wrap((Ipv4Addr::UNSPECIFIED, 0)),
+                ])
+                .await;
+
+            let addrs = network.listener_local_addrs();
+            assert_eq!(addrs.len(), 2);
+            assert_ne!(addrs[0], addrs[1]);
+
+            // Advertise the second listener to make sure it accepts connections too.
+            actor::register_addr(addrs[1]);
+
+            expect_peer_active(&network, "bob").await;
+            barrier.wait().await;
+        }
+    });
+
+    env.actor("bob", {
+        async move {
+            let network = actor::create_network(proto).await;
+            network.add_user_provided_peer(&actor::lookup_addr("alice").await);
+
+            expect_peer_active(&network, "alice").await;
+            barrier.wait().await;
+        }
+    });
+}
+
+#[test]
+fn bind_and_wait() {
+    let mut env = Env::new();
+    let proto = Proto::Tcp;
+
+    env.actor("alice", async move {
+        let network_a = actor::create_unbound_network();
+        let addrs = network_a
+            .bind_and_wait(&[proto.wrap((Ipv4Addr::UNSPECIFIED, 0))])
+            .await
+            .unwrap();
+        assert_eq!(addrs.len(), 1);
+        assert_ne!(addrs[0].port(), 0);
+        assert_eq!(addrs, network_a.listener_local_addrs());
+
+        // The address is already taken.
+        let network_b = actor::create_unbound_network();
+        let error = network_b.bind_and_wait(&addrs).await.unwrap_err();
+        assert_eq!(error.addrs, addrs);
+        assert!(network_b.listener_local_addrs().is_empty());
+    });
+}
+
+#[test]
+fn peer_events() {
+    let mut env = Env::new();
+    let proto = Proto::Tcp;
+    let barrier = Arc::new(Barrier::new(2));
+
+    env.actor("alice", {
+        let barrier = barrier.clone();
+
+        async move {
+            let network = actor::create_network(proto).await;
+            let mut rx = network.on_peer_event();
+
+            let event = expect_peer_event(&mut rx, PeerEventKind::Connected).await;
+            assert_eq!(event.source, PeerSource::Listener);
+
+            barrier.wait().await;
+
+            let disconnected = expect_peer_event(&mut rx, PeerEventKind::Disconnected).await;
+            assert_eq!(disconnected.runtime_id, event.runtime_id);
+            assert_eq!(disconnected.addr, event.addr);
+        }
+    });
+
+    env.actor("bob", {
+        async move {
+            let network = actor::create_network(proto).await;
+            let mut rx = network.on_peer_event();
+
+            let peer_addr = actor::lookup_addr("alice").await;
+            network.add_user_provided_peer(&peer_addr);
+
+            let event = expect_peer_event(&mut rx, PeerEventKind::Connected).await;
+            assert_eq!(event.addr, peer_addr);
+            assert_eq!(event.source, PeerSource::UserProvided);
+
+            barrier.wait().await;
+            // Dropping the network disconnects alice.
+        }
+    });
+}
+
+#[test]
+fn blocked_peer() {
+    let mut env = Env::new();
+    let proto = Proto::Tcp;
+    let barrier = Arc::new(Barrier::new(2));
+
+    env.actor("alice", {
+        let barrier = barrier.clone();
+
+        async move {
+            let network = actor::create_network(proto).await;
+            let mut rx = network.on_peer_event();
+
+            let event = expect_peer_event(&mut rx, PeerEventKind::Connected).await;
+
+            network.block_runtime_id(event.runtime_id);
+            assert_eq!(network.blocked_runtime_ids(), [event.runtime_id]);
+
+            let disconnected = expect_peer_event(&mut rx, PeerEventKind::Disconnected).await;
+            assert_eq!(disconnected.runtime_id, event.runtime_id);
+
+            // Bob tries to reconnect but gets rejected.
+            let reconnected = time::timeout(
+                Duration::from_secs(2),
+                expect_peer_event(&mut rx, PeerEventKind::Connected),
+            )
+            .await;
+            assert!(reconnected.is_err());
+
+            barrier.wait().await;
+        }
+    });
+
+    env.actor("bob", {
+        async move {
+            let network = actor::create_network(proto).await;
+            let mut rx = network.on_peer_event();
+
+            let peer_addr = actor::lookup_addr("alice").await;
+            network.add_user_provided_peer(&peer_addr);
+
+            expect_peer_event(&mut rx, PeerEventKind::Connected).await;
+
+            barrier.wait().await;
+        }
+    });
+}
+
+// A stale self-address must not prevent connecting to a peer that later occupies that address.
+#[test]
+fn self_address_cleared_on_rebind() {
+    let mut env = Env::new();
+    let proto = Proto::Tcp;
+    let barrier = Arc::new(Barrier::new(2));
+
+    env.actor("alice", {
+        let barrier = barrier.clone();
+
+        async move {
+            let network = actor::create_network(proto).await;
+
+            // Connect to self so our address gets recorded as a self-address.
+            let self_addr = actor::lookup_addr("alice").await;
+            network.add_user_provided_peer(&self_addr);
+
+            time::timeout(*TEST_TIMEOUT, async {
+                while !network.self_addresses().contains(&self_addr) {
+                    time::sleep(Duration::from_millis(50)).await;
+                }
+            })
+            .await
+            .unwrap();
+
+            network.remove_user_provided_peer(&self_addr);
+
+            // Unbind so the address can be taken by someone else.
+            network.bind(&[]).await;
+            assert!(network.self_addresses().is_empty());
+            barrier.wait().await;
+
+            // Bob now occupies our old address.
+            barrier.wait().await;
+
+            network
+                .bind(&[proto.wrap((Ipv4Addr::UNSPECIFIED, 0))])
+                .await;
+            network.add_user_provided_peer(&actor::lookup_addr("bob").await);
+            expect_peer_active(&network, "bob").await;
+
+            barrier.wait().await;
+        }
+    });
+
+    env.actor("bob", {
+        async move {
+            let network = actor::create_unbound_network();
+            barrier.wait().await;
+
+            let alice_port = actor::lookup_addr("alice").await.port();
+            network
+                .bind(&[proto.wrap((Ipv4Addr::UNSPECIFIED, alice_port))])
+                .await;
+            actor::register_addr(proto.wrap((Ipv4Addr::UNSPECIFIED, alice_port)));
+            barrier.wait().await;
+
+            barrier.wait().await;
+        }
+    });
+}
+
+#[test]
+fn transport_preference() {
+    let mut env = Env::new();
+    let barrier = Arc::new(Barrier::new(2));
+
+    env.actor("alice", {
+        let barrier = barrier.clone();
+
+        async move {
+            // Listen on both TCP and QUIC on the same port number so bob can derive one address
+            // from the other.
+            let network = actor::create_network(Proto::Tcp).await;
+            let port = actor::lookup_addr("alice").await.port();
+            network
+                .bind(&[
+                    Proto::Tcp.wrap((Ipv4Addr::UNSPECIFIED, port)),
+                    Proto::Quic.wrap((Ipv4Addr::UNSPECIFIED, port)),
+                ])
+                .await;
+
+            barrier.wait().await;
+        }
+    });
+
+    env.actor("bob", {
+        async move {
+            let network = actor::create_unbound_network();
+            network
+                .bind(&[
+                    Proto::Tcp.wrap((Ipv4Addr::UNSPECIFIED, 0)),
+                    Proto::Quic.wrap((Ipv4Addr::UNSPECIFIED, 0)),
+                ])
+                .await;
+            network.set_transport_preference(TransportPreference::Quic);
+
+            let tcp_addr = actor::lookup_addr("alice").await;
+            let quic_addr = Proto::Quic.wrap(*tcp_addr.socket_addr());
+
+            network.add_user_provided_peer(&tcp_addr);
+            expect_peer_active(&network, "alice").await;
+
+            // The QUIC connection replaces the TCP one.
+            network.add_user_provided_peer(&quic_addr);
+
+            let is_active = |addr| {
+                network
+                    .peer_info(addr)
+                    .map(|info| matches!(info.state, PeerState::Active { .. }))
+                    .unwrap_or(false)
+            };
+
+            time::timeout(*TEST_TIMEOUT, async {
+                let mut rx = network.on_peer_set_change();
+
+                while !is_active(quic_addr) || is_active(tcp_addr) {
+                    rx.changed().await.unwrap();
+                }
+            })
+            .await
+            .unwrap();
+
+            // The TCP connection is not reestablished while the QUIC one is alive.
+            time::sleep(Duration::from_secs(1)).await;
+            assert!(is_active(quic_addr));
+            assert!(!is_active(tcp_addr));
+
+            barrier.wait().await;
+        }
+    });
+}
+
+#[test]
+fn transport_preference_opposite() {
+    let mut env = Env::new();
+    let barrier = Arc::new(Barrier::new(2));
+
+    env.actor("alice", {
+        let barrier = barrier.clone();
+
+        async move {
+            let network = actor::create_network(Proto::Tcp).await;
+            network.set_transport_preference(TransportPreference::Tcp);
+
+            let port = actor::lookup_addr("alice").await.port();
+            network
+                .bind(&[
+                    Proto::Tcp.wrap((Ipv4Addr::UNSPECIFIED, port)),
+                    Proto::Quic.wrap((Ipv4Addr::UNSPECIFIED, port)),
+                ])
+                .await;
+
+            barrier.wait().await;
+        }
+    });
+
+    env.actor("bob", {
+        async move {
+            let network = actor::create_unbound_network();
+            network
+                .bind(&[
+                    Proto::Tcp.wrap((Ipv4Addr::UNSPECIFIED, 0)),
+                    Proto::Quic.wrap((Ipv4Addr::UNSPECIFIED, 0)),
+                ])
+                .await;
+            network.set_transport_preference(TransportPreference::Quic);
+
+            let tcp_addr = actor::lookup_addr("alice").await;
+            let quic_addr = Proto::Quic.wrap(*tcp_addr.socket_addr());
+
+            network.add_user_provided_peer(&tcp_addr);
+            network.add_user_provided_peer(&quic_addr);
+
+            let is_active = |addr| {
+                network
+                    .peer_info(addr)
+                    .map(|info| matches!(info.state, PeerState::Active { .. }))
+                    .unwrap_or(false)
+            };
+
+            // Both peers agree on one of the transports (which one depends on their runtime ids)
+            // so exactly one connection remains.
+            time::timeout(*TEST_TIMEOUT, async {
+                let mut rx = network.on_peer_set_change();
+
+                while is_active(quic_addr) == is_active(tcp_addr) {
+                    rx.changed().await.unwrap();
+                }
+            })
+            .await
+            .unwrap();
+
+            let quic_active = is_active(quic_addr);
+
+            // The connections don't keep replacing each other.
+            let mut events = network.on_peer_event();
+            time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(is_active(quic_addr), quic_active);
+            assert_eq!(is_active(tcp_addr), !quic_active);
+
+            loop {
+                match events.try_recv() {
+                    Ok(PeerEvent {
+                        kind: PeerEventKind::Connected,
+                        ..
+                    }) => panic!("unexpected reconnect"),
+                    Ok(_) => continue,
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => panic!("too many events"),
+                    Err(_) => break,
+                }
+            }
+
+            barrier.wait().await;
+        }
+    });
+}
+
 async fn expect_peer_known(network: &Network, peer_name: &str) {
     expect_peer_state(network, peer_name, |_| true).await
 }
@@ -269,6 +644,23 @@ where
     .unwrap()
 }
 
+async fn expect_peer_event(
+    rx: &mut broadcast::Receiver<PeerEvent>,
+    expected_kind: PeerEventKind,
+) -> PeerEvent {
+    time::timeout(*TEST_TIMEOUT, async move {
+        loop {
+            let event = rx.recv().await.unwrap();
+
+            if event.kind == expected_kind {
+                break event;
+            }
+        }
+    })
+    .await
+    .unwrap()
+}
+
 async fn expect_knows_port(network: &Network, peer_port: u16) {
     let collector = network.peer_info_collector();
 
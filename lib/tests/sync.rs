@@ -289,6 +289,59 @@ fn relink_repository() {
     });
 }
 
+#[test]
+fn pause_and_resume_sync() {
+    let mut env = Env::new();
+
+    // Bidirectional side-channel
+    let (writer_tx, mut writer_rx) = mpsc::channel(1);
+    let (reader_tx, mut reader_rx) = mpsc::channel(1);
+
+    env.actor("writer", async move {
+        let (_network, repo, _reg) = actor::setup().await;
+
+        let mut file = repo.create_file("test.txt").await.unwrap();
+        file.write_all(b"first").await.unwrap();
+        file.flush().await.unwrap();
+
+        // Wait for the reader to see the file and then pause its sync
+        reader_rx.recv().await;
+
+        // Modify the file while reader is paused
+        file.truncate(0).unwrap();
+        file.write_all(b"second").await.unwrap();
+        file.flush().await.unwrap();
+
+        writer_tx.send(()).await.unwrap();
+
+        // Wait until reader is done
+        reader_rx.recv().await;
+    });
+
+    env.actor("reader", async move {
+        let (network, repo, reg) = actor::setup().await;
+
+        let peer_addr = actor::lookup_addr("writer").await;
+        network.add_user_provided_peer(&peer_addr);
+
+        common::expect_file_content(&repo, "test.txt", b"first").await;
+
+        reg.set_sync_enabled(false);
+        assert!(!reg.is_sync_enabled());
+        reader_tx.send(()).await.unwrap();
+
+        // Wait until writer is done updating the file
+        writer_rx.recv().await;
+
+        reg.set_sync_enabled(true);
+        assert!(reg.is_sync_enabled());
+
+        common::expect_file_content(&repo, "test.txt", b"second").await;
+
+        reader_tx.send(()).await.unwrap();
+    });
+}
+
 #[test]
 fn remove_remote_file() {
     let mut env = Env::new();
@@ -320,6 +373,54 @@ fn remove_remote_file() {
     });
 }
 
+// A file removed on one replica must not be resurrected by a replica that still had it once the
+// two keep syncing further changes.
+#[test]
+fn removed_file_stays_removed() {
+    let mut env = Env::new();
+    let barrier = Arc::new(Barrier::new(2));
+
+    env.actor("creator", {
+        let barrier = barrier.clone();
+
+        async move {
+            let (_network, repo, _reg) = actor::setup().await;
+
+            let mut file = repo.create_file("test.txt").await.unwrap();
+            file.write_all(b"hello").await.unwrap();
+            file.flush().await.unwrap();
+            drop(file);
+
+            common::expect_entry_not_found(&repo, "test.txt").await;
+
+            // Make another change after the removal has been merged.
+            let mut file = repo.create_file("other.txt").await.unwrap();
+            file.write_all(b"world").await.unwrap();
+            file.flush().await.unwrap();
+            drop(file);
+
+            barrier.wait().await;
+
+            common::expect_entry_not_found(&repo, "test.txt").await;
+        }
+    });
+
+    env.actor("remover", {
+        async move {
+            let (network, repo, _reg) = actor::setup().await;
+            network.add_user_provided_peer(&actor::lookup_addr("creator").await);
+
+            common::expect_file_content(&repo, "test.txt", b"hello").await;
+            repo.remove_entry("test.txt").await.unwrap();
+
+            common::expect_file_content(&repo, "other.txt", b"world").await;
+            common::expect_entry_not_found(&repo, "test.txt").await;
+
+            barrier.wait().await;
+        }
+    });
+}
+
 #[test]
 fn relay_write() {
     let file_size = LARGE_SIZE;
@@ -380,6 +481,65 @@ fn relay_case(proto: Proto, file_size: usize, relay_access_mode: AccessMode) {
     });
 }
 
+// Two full peers and one blind peer with block storage disabled ("coordinator"). The full peers
+// still converge (the blocks flow between them directly) while the coordinator stores no blocks.
+#[test]
+fn block_storage_disabled() {
+    let mut env = Env::new();
+    let (ready_tx, mut ready_rx) = mpsc::channel(1);
+    let (done_tx, _) = broadcast::channel(1);
+
+    let content = Arc::new(common::random_bytes(LARGE_SIZE));
+
+    env.actor("coordinator", {
+        let mut done_rx = done_tx.subscribe();
+
+        async move {
+            let network = actor::create_network(Proto::Tcp).await;
+            let repo = actor::create_repo_with_mode(DEFAULT_REPO, AccessMode::Blind).await;
+            let reg = network.register(repo.handle()).await;
+
+            reg.set_block_storage_enabled(false).await;
+            assert!(!reg.is_block_storage_enabled());
+
+            ready_tx.send(()).await.unwrap();
+            done_rx.recv().await.unwrap();
+
+            assert_eq!(repo.count_blocks().await.unwrap(), 0);
+        }
+    });
+
+    env.actor("writer", {
+        let content = content.clone();
+        let mut done_rx = done_tx.subscribe();
+
+        async move {
+            let (network, repo, _reg) = actor::setup().await;
+
+            ready_rx.recv().await.unwrap();
+            network.add_user_provided_peer(&actor::lookup_addr("coordinator").await);
+
+            let mut file = repo.create_file("test.dat").await.unwrap();
+            common::write_in_chunks(&mut file, &content, 4096).await;
+            file.flush().await.unwrap();
+
+            done_rx.recv().await.unwrap();
+        }
+    });
+
+    env.actor("reader", {
+        async move {
+            let (network, repo, _reg) = actor::setup().await;
+            network.add_user_provided_peer(&actor::lookup_addr("coordinator").await);
+            network.add_user_provided_peer(&actor::lookup_addr("writer").await);
+
+            common::expect_file_content(&repo, "test.dat", &content).await;
+
+            done_tx.send(()).unwrap();
+        }
+    });
+}
+
 // Test for an edge case where a sync happens while we are in the middle of writing a file.
 // This test makes sure that when the sync happens, the partially written file content is not
 // garbage collected prematurelly.
@@ -634,6 +794,116 @@ fn remote_rename_file() {
     });
 }
 
+#[test]
+fn remote_rename_file_preserves_history() {
+    let mut env = Env::new();
+    let (tx, mut rx) = mpsc::channel(1);
+
+    env.actor("writer", async move {
+        let (_network, repo, _reg) = actor::setup().await;
+
+        // Create the file and wait until reader has seen it
+        let mut file = repo.create_file("foo.txt").await.unwrap();
+        file.write_all(b"hello").await.unwrap();
+        file.flush().await.unwrap();
+        drop(file);
+        rx.recv().await;
+
+        // Rename it and wait until reader is done
+        repo.move_entry("/", "foo.txt", "/", "bar.txt")
+            .await
+            .unwrap();
+        rx.recv().await;
+    });
+
+    env.actor("reader", async move {
+        let (network, repo, _reg) = actor::setup().await;
+        network.add_user_provided_peer(&actor::lookup_addr("writer").await);
+
+        common::expect_file_content(&repo, "foo.txt", b"hello").await;
+        let old_vv = repo
+            .open_file("foo.txt")
+            .await
+            .unwrap()
+            .version_vector()
+            .await
+            .unwrap();
+        tx.send(()).await.unwrap();
+
+        common::expect_file_content(&repo, "bar.txt", b"hello").await;
+        common::expect_entry_not_found(&repo, "foo.txt").await;
+
+        // The renamed file descends from the original one (a newly created file would have an
+        // unrelated version vector) and it's not duplicated.
+        let new_vv = repo
+            .open_file("bar.txt")
+            .await
+            .unwrap()
+            .version_vector()
+            .await
+            .unwrap();
+        assert_eq!(new_vv.partial_cmp(&old_vv), Some(Ordering::Greater));
+        assert_eq!(repo.open_directory("/").await.unwrap().entries().count(), 1);
+
+        tx.send(()).await.unwrap();
+    });
+}
+
+#[test]
+fn remote_rename_file_without_history() {
+    let mut env = Env::new();
+    let (tx, mut rx) = mpsc::channel(1);
+
+    env.actor("writer", async move {
+        let (_network, repo, _reg) = actor::setup().await;
+
+        // Create the file and wait until reader has seen it
+        let mut file = repo.create_file("foo.txt").await.unwrap();
+        file.write_all(b"hello").await.unwrap();
+        file.flush().await.unwrap();
+        drop(file);
+        rx.recv().await;
+
+        // Rename it without preserving the history and wait until reader is done
+        repo.move_entry_with("/", "foo.txt", "/", "bar.txt", false)
+            .await
+            .unwrap();
+        rx.recv().await;
+    });
+
+    env.actor("reader", async move {
+        let (network, repo, _reg) = actor::setup().await;
+        network.add_user_provided_peer(&actor::lookup_addr("writer").await);
+
+        common::expect_file_content(&repo, "foo.txt", b"hello").await;
+        let old_vv = repo
+            .open_file("foo.txt")
+            .await
+            .unwrap()
+            .version_vector()
+            .await
+            .unwrap();
+        tx.send(()).await.unwrap();
+
+        common::expect_file_content(&repo, "bar.txt", b"hello").await;
+        common::expect_entry_not_found(&repo, "foo.txt").await;
+
+        // The renamed file starts a new history so it doesn't descend from the original one, but
+        // it's still not duplicated.
+        let new_vv = repo
+            .open_file("bar.txt")
+            .await
+            .unwrap()
+            .version_vector()
+            .await
+            .unwrap();
+        assert_ne!(new_vv.partial_cmp(&old_vv), Some(Ordering::Greater));
+        assert_eq!(repo.open_directory("/").await.unwrap().entries().count(), 1);
+
+        tx.send(()).await.unwrap();
+    });
+}
+
 #[test]
 fn remote_rename_empty_directory() {
     let mut env = Env::new();
@@ -1326,6 +1596,49 @@ fn file_progress() {
     });
 }
 
+#[test]
+fn on_complete_after_sync() {
+    let mut env = Env::new();
+    let (tx, mut rx) = mpsc::channel(1);
+
+    let content = common::random_bytes(LARGE_SIZE);
+
+    env.actor("writer", {
+        let content = content.clone();
+
+        async move {
+            let (_network, repo, _reg) = actor::setup().await;
+
+            let mut file = repo.create_file("test.dat").await.unwrap();
+            common::write_in_chunks(&mut file, &content, 4096).await;
+            file.flush().await.unwrap();
+
+            rx.recv().await.unwrap();
+        }
+    });
+
+    env.actor("reader", async move {
+        let (network, repo, _reg) = actor::setup().await;
+        network.add_user_provided_peer(&actor::lookup_addr("writer").await);
+
+        // The empty repository is complete, so wait for the file to appear first.
+        common::expect_entry_exists(&repo, "test.dat", EntryType::File).await;
+
+        time::timeout(Duration::from_secs(60), repo.on_complete())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(repo.is_complete().await.unwrap());
+
+        // All the blocks are present so the whole content can be read.
+        let mut file = repo.open_file("test.dat").await.unwrap();
+        let actual = common::read_in_chunks(&mut file, 4096).await.unwrap();
+        similar_asserts::assert_eq!(&actual, &content);
+
+        tx.send(()).await.unwrap();
+    });
+}
+
 #[instrument(skip(repo))]
 async fn expect_local_directory_exists(repo: &Repository, path: &str) {
     common::eventually(repo, || async {
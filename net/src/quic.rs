@@ -9,6 +9,7 @@ use std::{
         Arc,
     },
     task::{Context, Poll},
+    time::Duration,
 };
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
@@ -319,9 +320,89 @@ impl Drop for OwnedWriteHalf {
 }
 
 //------------------------------------------------------------------------------
+/// Congestion control algorithm used by the QUIC connections.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum CongestionKind {
+    /// CUBIC (RFC 8312). The default.
+    #[default]
+    Cubic,
+    /// NewReno (RFC 6582).
+    NewReno,
+    /// BBR. Can perform significantly better than the loss based algorithms on links with high
+    /// latency and/or random packet loss (e.g., satellite links).
+    Bbr,
+}
+
+/// Smallest MTU every QUIC path is required to support (RFC 9000, section 14).
+pub const MIN_INITIAL_MTU: u16 = 1200;
+
+/// Tuning parameters of the QUIC transport.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct QuicTuning {
+    /// Congestion control algorithm.
+    pub congestion: CongestionKind,
+    /// MTU to assume before the path MTU discovery completes. Must be at least
+    /// `MIN_INITIAL_MTU`.
+    pub initial_mtu: u16,
+    /// Time after which an inactive connection is closed. Must be longer than the keep-alive
+    /// interval, otherwise idle connections would be closed before the keep-alive is sent.
+    pub max_idle_timeout: Duration,
+}
+
+impl Default for QuicTuning {
+    fn default() -> Self {
+        Self {
+            congestion: CongestionKind::default(),
+            initial_mtu: MIN_INITIAL_MTU,
+            max_idle_timeout: 2 * KEEP_ALIVE_INTERVAL,
+        }
+    }
+}
+
+impl QuicTuning {
+    fn apply(&self, transport_config: &mut quinn::TransportConfig) {
+        use quinn::congestion::{BbrConfig, CubicConfig, NewRenoConfig};
+
+        match self.congestion {
+            CongestionKind::Cubic => {
+                transport_config.congestion_controller_factory(Arc::new(CubicConfig::default()))
+            }
+            CongestionKind::NewReno => {
+                transport_config.congestion_controller_factory(Arc::new(NewRenoConfig::default()))
+            }
+            CongestionKind::Bbr => {
+                transport_config.congestion_controller_factory(Arc::new(BbrConfig::default()))
+            }
+        };
+
+        transport_config
+            .initial_mtu(self.initial_mtu)
+            .max_idle_timeout(self.max_idle_timeout.try_into().ok());
+    }
+}
+
 pub async fn configure(bind_addr: SocketAddr) -> Result<(Connector, Acceptor, SideChannelMaker)> {
-    let server_config = make_server_config()?;
-    let custom_socket = CustomUdpSocket::bind(bind_addr).await?;
+    configure_with_tuning(bind_addr, &QuicTuning::default()).await
+}
+
+/// Like `configure` but with custom tuning of the QUIC transport.
+pub async fn configure_with_tuning(
+    bind_addr: SocketAddr,
+    tuning: &QuicTuning,
+) -> Result<(Connector, Acceptor, SideChannelMaker)> {
+    configure_on_device(bind_addr, tuning, None).await
+}
+
+/// Like `configure_with_tuning` but if `interface` is given, the underlying UDP socket is also
+/// bound to the network interface with that name so all the traffic of the endpoint (including
+/// the side channels) goes only through that interface.
+pub async fn configure_on_device(
+    bind_addr: SocketAddr,
+    tuning: &QuicTuning,
+    interface: Option<&str>,
+) -> Result<(Connector, Acceptor, SideChannelMaker)> {
+    let server_config = make_server_config(tuning)?;
+    let custom_socket = CustomUdpSocket::bind(bind_addr, interface).await?;
     let side_channel_maker = custom_socket.side_channel_maker();
 
     let mut endpoint = quinn::Endpoint::new_with_abstract_socket(
@@ -331,7 +412,7 @@ pub async fn configure(bind_addr: SocketAddr) -> Result<(Connector, Acceptor, Si
         Arc::new(quinn::TokioRuntime),
     )?;
 
-    endpoint.set_default_client_config(make_client_config());
+    endpoint.set_default_client_config(make_client_config(tuning));
 
     let local_addr = endpoint.local_addr()?;
 
@@ -385,7 +466,7 @@ impl rustls::client::ServerCertVerifier for SkipServerVerification {
     }
 }
 
-fn make_client_config() -> quinn::ClientConfig {
+fn make_client_config(tuning: &QuicTuning) -> quinn::ClientConfig {
     let crypto = rustls::ClientConfig::builder()
         .with_safe_defaults()
         .with_custom_certificate_verifier(Arc::new(SkipServerVerification {}))
@@ -401,14 +482,15 @@ fn make_client_config() -> quinn::ClientConfig {
         // to be on the client side with the reasoning that the server side has a better chance of
         // being behind a non restrictive NAT, and so that sending the packets from the client side
         // shall assist in hole punching.
-        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL))
-        .max_idle_timeout((2 * KEEP_ALIVE_INTERVAL).try_into().ok());
+        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+
+    tuning.apply(&mut transport_config);
 
     client_config.transport_config(Arc::new(transport_config));
     client_config
 }
 
-fn make_server_config() -> Result<quinn::ServerConfig> {
+fn make_server_config(tuning: &QuicTuning) -> Result<quinn::ServerConfig> {
     // Generate a self signed certificate.
     let cert = rcgen::generate_simple_self_signed(vec![CERT_DOMAIN.into()]).unwrap();
     let cert_der = cert.serialize_der().unwrap();
@@ -420,9 +502,9 @@ fn make_server_config() -> Result<quinn::ServerConfig> {
 
     let mut transport_config = quinn::TransportConfig::default();
 
-    transport_config
-        .max_concurrent_uni_streams(0_u8.into())
-        .max_idle_timeout((2 * KEEP_ALIVE_INTERVAL).try_into().ok());
+    transport_config.max_concurrent_uni_streams(0_u8.into());
+
+    tuning.apply(&mut transport_config);
 
     server_config.transport_config(Arc::new(transport_config));
 
@@ -457,8 +539,8 @@ struct CustomUdpSocket {
 }
 
 impl CustomUdpSocket {
-    async fn bind(addr: SocketAddr) -> io::Result<Self> {
-        let socket = crate::udp::UdpSocket::bind(addr).await?;
+    async fn bind(addr: SocketAddr, interface: Option<&str>) -> io::Result<Self> {
+        let socket = crate::udp::UdpSocket::bind_to_device(addr, interface).await?;
         let socket = socket.into_std()?;
 
         quinn::udp::UdpSocketState::configure((&socket).into())?;
@@ -669,6 +751,41 @@ mod tests {
         h2.await.unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn data_exchange_with_custom_tuning() {
+        let tuning = QuicTuning {
+            congestion: CongestionKind::Bbr,
+            initial_mtu: 1400,
+            max_idle_timeout: Duration::from_secs(60),
+        };
+
+        let (connector, mut acceptor, _) =
+            configure_with_tuning((Ipv4Addr::LOCALHOST, 0).into(), &tuning)
+                .await
+                .unwrap();
+
+        let addr = *acceptor.local_addr();
+
+        let message = vec![0xaa; 64 * 1024];
+        let expected = message.clone();
+
+        let h1 = task::spawn(async move {
+            let mut conn = acceptor.accept().await.unwrap().finish().await.unwrap();
+            let mut buf = Vec::new();
+            conn.read_to_end(&mut buf).await.unwrap();
+            assert_eq!(buf, expected);
+        });
+
+        let h2 = task::spawn(async move {
+            let mut conn = connector.connect(addr).await.unwrap();
+            conn.write_all(&message).await.unwrap();
+            conn.finish().await.unwrap();
+        });
+
+        h1.await.unwrap();
+        h2.await.unwrap();
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn side_channel() {
         let (_connector, mut acceptor, side_channel_maker) =
@@ -17,3 +17,22 @@ pub(crate) fn bind_with_fallback(socket: &Socket, mut addr: SocketAddr) -> io::R
         }
     }
 }
+
+/// Binds the socket to the network interface with the given name (`SO_BINDTODEVICE`) so it sends
+/// and receives only through that interface. Supported only on Linux and Android, fails with
+/// `Unsupported` elsewhere.
+pub(crate) fn bind_device(socket: &Socket, interface: &str) -> io::Result<()> {
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    {
+        socket.bind_device(Some(interface.as_bytes()))
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "linux")))]
+    {
+        let _ = (socket, interface);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "binding to a network interface is not supported on this platform",
+        ))
+    }
+}
@@ -21,22 +21,7 @@ mod implementation {
     impl TcpListener {
         /// Binds TCP socket to the given address. If the port is taken, uses a random one,
         pub async fn bind(addr: impl Into<SocketAddr>) -> io::Result<Self> {
-            let addr = addr.into();
-
-            let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
-            socket.set_nonblocking(true)?;
-            // Ignore errors - reuse address is nice to have but not required.
-            socket.set_reuse_address(true).ok();
-            set_keep_alive(&socket)?;
-            socket::bind_with_fallback(&socket, addr)?;
-
-            // Marks the socket as ready for accepting incoming connections. This needs to be set
-            // for TCP listeners otherwise we get "Invalid argument" error when calling `accept`.
-            //
-            // See https://stackoverflow.com/a/10002936/170073 for explanation of the parameter.
-            socket.listen(128)?;
-
-            Ok(Self(tokio::net::TcpListener::from_std(socket.into())?))
+            bind_to_device(addr.into(), None).await
         }
 
         pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
@@ -56,15 +41,7 @@ mod implementation {
 
     impl TcpStream {
         pub async fn connect(addr: SocketAddr) -> io::Result<Self> {
-            let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
-            socket.set_nonblocking(true)?;
-            set_keep_alive(&socket)?;
-
-            Ok(Self(
-                tokio::net::TcpSocket::from_std_stream(socket.into())
-                    .connect(addr)
-                    .await?,
-            ))
+            connect_via_device(addr, None).await
         }
 
         pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
@@ -72,6 +49,56 @@ mod implementation {
         }
     }
 
+    /// Like `TcpListener::bind` but if `interface` is given, also binds the listener to the network
+    /// interface with that name. See `socket::bind_device` for the platform support.
+    pub async fn bind_to_device(
+        addr: SocketAddr,
+        interface: Option<&str>,
+    ) -> io::Result<TcpListener> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+        socket.set_nonblocking(true)?;
+        // Ignore errors - reuse address is nice to have but not required.
+        socket.set_reuse_address(true).ok();
+        set_keep_alive(&socket)?;
+
+        if let Some(interface) = interface {
+            socket::bind_device(&socket, interface)?;
+        }
+
+        socket::bind_with_fallback(&socket, addr)?;
+
+        // Marks the socket as ready for accepting incoming connections. This needs to be set
+        // for TCP listeners otherwise we get "Invalid argument" error when calling `accept`.
+        //
+        // See https://stackoverflow.com/a/10002936/170073 for explanation of the parameter.
+        socket.listen(128)?;
+
+        Ok(TcpListener(tokio::net::TcpListener::from_std(
+            socket.into(),
+        )?))
+    }
+
+    /// Like `TcpStream::connect` but if `interface` is given, the connection goes only through the
+    /// network interface with that name. See `socket::bind_device` for the platform support.
+    pub async fn connect_via_device(
+        addr: SocketAddr,
+        interface: Option<&str>,
+    ) -> io::Result<TcpStream> {
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+        socket.set_nonblocking(true)?;
+        set_keep_alive(&socket)?;
+
+        if let Some(interface) = interface {
+            socket::bind_device(&socket, interface)?;
+        }
+
+        Ok(TcpStream(
+            tokio::net::TcpSocket::from_std_stream(socket.into())
+                .connect(addr)
+                .await?,
+        ))
+    }
+
     fn set_keep_alive(socket: &Socket) -> io::Result<()> {
         let options = TcpKeepalive::new()
             .with_time(KEEP_ALIVE_INTERVAL)
@@ -137,4 +164,22 @@ mod implementation {
         tcp::{OwnedReadHalf, OwnedWriteHalf},
         TcpListener, TcpStream,
     };
+
+    use std::{io, net::SocketAddr};
+
+    // Simulated sockets are not bound to any real interface so `interface` is ignored.
+
+    pub async fn bind_to_device(
+        addr: SocketAddr,
+        _interface: Option<&str>,
+    ) -> io::Result<TcpListener> {
+        TcpListener::bind(addr).await
+    }
+
+    pub async fn connect_via_device(
+        addr: SocketAddr,
+        _interface: Option<&str>,
+    ) -> io::Result<TcpStream> {
+        TcpStream::connect(addr).await
+    }
 }
@@ -38,16 +38,45 @@ mod implementation {
     impl UdpSocket {
         /// Binds UDP socket to the given address. If the port is taken, uses a random one,
         pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+            Self::bind_to_device(addr, None).await
+        }
+
+        /// Like `bind` but if `interface` is given, also binds the socket to the network interface
+        /// with that name. See `socket::bind_device` for the platform support.
+        pub async fn bind_to_device(addr: SocketAddr, interface: Option<&str>) -> io::Result<Self> {
             let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, None)?;
             socket.set_nonblocking(true)?;
             // Ignore errors - reuse address is nice to have but not required.
             socket.set_reuse_address(true).ok();
+
+            if let Some(interface) = interface {
+                socket::bind_device(&socket, interface)?;
+            }
+
             socket::bind_with_fallback(&socket, addr)?;
 
             Ok(Self(tokio::net::UdpSocket::from_std(socket.into())?))
         }
 
         pub async fn bind_multicast(interface: Ipv4Addr) -> io::Result<Self> {
+            Self::bind_multicast_impl(interface, None).await
+        }
+
+        /// Like `bind_multicast` but the multicast packets are sent only through `interface`
+        /// (instead of the system default multicast interface) and, if `device` is given, the
+        /// socket is also bound to the network interface with that name. See
+        /// `socket::bind_device` for the platform support.
+        pub async fn bind_multicast_restricted(
+            interface: Ipv4Addr,
+            device: Option<&str>,
+        ) -> io::Result<Self> {
+            Self::bind_multicast_impl(interface, Some(device)).await
+        }
+
+        async fn bind_multicast_impl(
+            interface: Ipv4Addr,
+            restrict: Option<Option<&str>>,
+        ) -> io::Result<Self> {
             let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, MULTICAST_PORT));
 
             let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, None)?;
@@ -56,6 +85,14 @@ mod implementation {
             #[cfg(not(windows))]
             socket.set_reuse_port(true)?;
 
+            if let Some(device) = restrict {
+                socket.set_multicast_if_v4(&interface)?;
+
+                if let Some(device) = device {
+                    socket::bind_device(&socket, device)?;
+                }
+            }
+
             // Receive broadcasts from other apps on this device
             socket.set_multicast_loop_v4(true)?;
 
@@ -101,10 +138,24 @@ mod implementation {
             unimplemented!("simulated udp sockets not supported")
         }
 
+        pub async fn bind_to_device(
+            _addr: SocketAddr,
+            _interface: Option<&str>,
+        ) -> io::Result<Self> {
+            unimplemented!("simulated udp sockets not supported")
+        }
+
         pub async fn bind_multicast(_interface: Ipv4Addr) -> io::Result<Self> {
             unimplemented!("simulated udp sockets not supported")
         }
 
+        pub async fn bind_multicast_restricted(
+            _interface: Ipv4Addr,
+            _device: Option<&str>,
+        ) -> io::Result<Self> {
+            unimplemented!("simulated udp sockets not supported")
+        }
+
         pub fn into_std(self) -> io::Result<std::net::UdpSocket> {
             unimplemented!()
         }
@@ -14,8 +14,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "ffi/src/lib.rs",
         "lib/src/access_control/access_mode.rs",
         "lib/src/directory/entry_type.rs",
+        "lib/src/joint_directory/merge_strategy.rs",
         "lib/src/network/peer_source.rs",
         "lib/src/network/peer_state.rs",
+        "lib/src/network/transport_preference.rs",
+        "lib/src/repository/lock_reason.rs",
     ];
     let mut source = Source::new();
 
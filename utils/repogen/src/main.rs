@@ -50,7 +50,10 @@ fn run(mode: AccessMode, input: Option<&str>) {
         ShareToken::from(AccessSecrets::random_write())
     };
 
-    println!("{}", ShareToken::from(token.into_secrets().with_mode(mode)));
+    println!(
+        "{}",
+        ShareToken::from(token.into_secrets().unwrap().with_mode(mode))
+    );
 }
 
 fn show_token_information(token: &ShareToken) {
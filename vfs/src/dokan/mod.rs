@@ -1,7 +1,7 @@
 pub(crate) mod multi_repo_mount;
 pub(crate) mod single_repo_mount;
 
-use camino::Utf8PathBuf;
+use camino::{Utf8Component, Utf8PathBuf};
 use deadlock::{AsyncMutex, AsyncMutexGuard};
 use dokan::{
     CreateFileInfo, DiskSpaceInfo, FileInfo, FileSystemHandler, FileTimeOperation, FillDataError,
@@ -35,6 +35,9 @@ pub const MAX_COMPONENT_LENGTH: u32 = 255;
 struct VirtualFilesystem {
     rt: tokio::runtime::Handle,
     repo: Arc<Repository>,
+    // Path (relative to the repository root) of the directory exposed as the root of the
+    // filesystem. Empty if the whole repository is mounted.
+    root: Utf8PathBuf,
     handles: Arc<AsyncMutex<Handles>>,
     entry_id_generator: Arc<EntryIdGenerator>,
 }
@@ -44,15 +47,34 @@ impl VirtualFilesystem {
         rt: tokio::runtime::Handle,
         entry_id_generator: Arc<EntryIdGenerator>,
         repo: Arc<Repository>,
+        root: Utf8PathBuf,
     ) -> Self {
         Self {
             rt,
             repo,
+            root,
             handles: Arc::new(AsyncMutex::new(Default::default())),
             entry_id_generator,
         }
     }
 
+    // Converts the path received from dokan into a path relative to the repository root.
+    fn repo_path(&self, path_cstr: &U16CStr) -> OperationResult<Utf8PathBuf> {
+        let path = to_path(path_cstr)?;
+
+        if self.root.as_str().is_empty() {
+            return Ok(path);
+        }
+
+        let mut full = self.root.clone();
+        full.extend(
+            path.components()
+                .filter(|component| !matches!(component, Utf8Component::RootDir)),
+        );
+
+        Ok(full)
+    }
+
     async fn get_or_set_shared(
         &self,
         path: Utf8PathBuf,
@@ -157,6 +179,8 @@ impl VirtualFilesystem {
                 JointEntryRef::Directory(_) => {
                     Entry::new_dir(self.repo.clone(), path.clone(), shared).await?
                 }
+                // Links are not supported on Windows yet.
+                JointEntryRef::Link(_) => return Err(E::EntryIsLink.into()),
             }
         } else {
             match existing_entry {
@@ -170,6 +194,8 @@ impl VirtualFilesystem {
                 JointEntryRef::Directory(_) => {
                     Entry::new_dir(self.repo.clone(), path.clone(), shared).await?
                 }
+                // Links are not supported on Windows yet.
+                JointEntryRef::Link(_) => return Err(E::EntryIsLink.into()),
             }
         };
 
@@ -288,6 +314,8 @@ impl VirtualFilesystem {
                     // TODO: Count block sizes
                     (winnt::FILE_ATTRIBUTE_DIRECTORY, 0)
                 }
+                // Links are not supported on Windows yet.
+                JointEntryRef::Link(_) => continue,
             };
 
             if let Some(pattern) = pattern {
@@ -338,7 +366,7 @@ impl VirtualFilesystem {
             file_attributes
         );
 
-        let path = to_path(file_name)?;
+        let path = self.repo_path(file_name)?;
 
         let (entry, is_new, id) = self
             .create_entry(
@@ -782,7 +810,7 @@ impl VirtualFilesystem {
     ) -> Result<(), Error> {
         tracing::trace!("enter");
         let dir_entry = context.entry.as_directory()?;
-        let path = to_path(file_name)?;
+        let path = self.repo_path(file_name)?;
         let mut shared = dir_entry.shared.write().await;
 
         let dir = self.repo.cd(&path).await?;
@@ -819,8 +847,8 @@ impl VirtualFilesystem {
     ) -> Result<(), Error> {
         tracing::trace!("enter");
 
-        let src_path = to_path(file_name)?;
-        let dst_path = to_path(new_file_name)?;
+        let src_path = self.repo_path(file_name)?;
+        let dst_path = self.repo_path(new_file_name)?;
 
         if src_path == dst_path {
             return Ok(());
@@ -1143,13 +1171,21 @@ impl From<Error> for i32 {
                     // These two are as they were used in the memfs dokan example.
                     E::EntryIsFile => STATUS_INVALID_DEVICE_REQUEST,
                     E::EntryIsDirectory => STATUS_INVALID_DEVICE_REQUEST,
+                    E::EntryIsLink | E::TooManyLinks => STATUS_INVALID_DEVICE_REQUEST,
                     E::NonUtf8FileName => STATUS_OBJECT_NAME_INVALID,
-                    E::InvalidArgument | E::OffsetOutOfRange => STATUS_INVALID_PARAMETER,
+                    E::InvalidArgument | E::OffsetOutOfRange | E::TokenExpired => {
+                        STATUS_INVALID_PARAMETER
+                    }
                     E::DirectoryNotEmpty => STATUS_DIRECTORY_NOT_EMPTY,
                     E::OperationNotSupported => STATUS_NOT_IMPLEMENTED,
                     E::Writer(_) => STATUS_IO_DEVICE_ERROR,
+                    E::Reader(_) => STATUS_IO_DEVICE_ERROR,
                     E::StorageVersionMismatch => STATUS_IO_DEVICE_ERROR,
-                    E::Locked => STATUS_LOCK_NOT_GRANTED,
+                    E::ArchiveVersionMismatch => STATUS_IO_DEVICE_ERROR,
+                    E::NotARepository => STATUS_IO_DEVICE_ERROR,
+                    E::Locked | E::StoreLocked(_) => STATUS_LOCK_NOT_GRANTED,
+                    E::QuotaExceeded => STATUS_DISK_FULL,
+                    E::WrongPassword => STATUS_WRONG_PASSWORD,
                 }
             }
         }
@@ -1,5 +1,6 @@
 use super::{EntryHandle, EntryIdGenerator, VirtualFilesystem};
 use crate::{MountError, MultiRepoMount};
+use camino::Utf8PathBuf;
 use deadlock::BlockingRwLock;
 use dokan::{
     init, shutdown, unmount, CreateFileInfo, DiskSpaceInfo, FileInfo, FileSystemHandler,
@@ -179,6 +180,7 @@ impl MultiRepoMount for MultiRepoVFS {
                     self.runtime_handle.clone(),
                     self.entry_id_generator.clone(),
                     repo,
+                    Utf8PathBuf::new(),
                 ));
                 name_to_repo_entry.insert(repo);
                 path_to_name_entry.insert(name);
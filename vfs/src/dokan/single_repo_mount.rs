@@ -1,8 +1,9 @@
 use super::{EntryHandle, EntryIdGenerator, VirtualFilesystem};
+use camino::Utf8PathBuf;
 use dokan::{
     init, shutdown, unmount, CreateFileInfo, DiskSpaceInfo, FileInfo, FileSystemHandler,
-    FileSystemMounter, FileTimeOperation, FillDataResult, FindData, MountOptions, OperationInfo,
-    OperationResult, VolumeInfo, IO_SECURITY_CONTEXT,
+    FileSystemMounter, FileTimeOperation, FillDataResult, FindData, MountFlags, MountOptions,
+    OperationInfo, OperationResult, VolumeInfo, IO_SECURITY_CONTEXT,
 };
 use ouisync_lib::Repository;
 use std::io;
@@ -275,15 +276,72 @@ pub fn mount(
     mount_with_span(runtime_handle, repository, mount_point, None)
 }
 
+/// Like [`mount`] but the filesystem is mounted write-protected.
+pub fn mount_readonly(
+    runtime_handle: tokio::runtime::Handle,
+    repository: Arc<Repository>,
+    mount_point: impl AsRef<Path>,
+) -> Result<MountGuard, io::Error> {
+    mount_with_flags(
+        runtime_handle,
+        repository,
+        Utf8PathBuf::new(),
+        mount_point,
+        super::default_mount_flags() | MountFlags::WRITE_PROTECT,
+        None,
+    )
+}
+
+/// Like [`mount`] but only the given subdirectory of the repository is mounted. The subdirectory
+/// becomes the root of the filesystem and the rest of the repository is not accessible through it.
+/// Fails with `InvalidInput` if `subdir` contains `..` components and with `NotFound` (wrapping
+/// `Error::EntryNotFound`) if it doesn't exist.
+pub fn mount_subdir(
+    runtime_handle: tokio::runtime::Handle,
+    repository: Arc<Repository>,
+    subdir: Utf8PathBuf,
+    mount_point: impl AsRef<Path>,
+) -> Result<MountGuard, io::Error> {
+    let root = crate::normalize_subdir(&subdir)?;
+    crate::check_subdir(&runtime_handle, &repository, &root)?;
+
+    mount_with_flags(
+        runtime_handle,
+        repository,
+        root,
+        mount_point,
+        super::default_mount_flags(),
+        None,
+    )
+}
+
 pub fn mount_with_span(
     runtime_handle: tokio::runtime::Handle,
     repository: Arc<Repository>,
     mount_point: impl AsRef<Path>,
     span: Option<tracing::Span>,
+) -> Result<MountGuard, io::Error> {
+    mount_with_flags(
+        runtime_handle,
+        repository,
+        Utf8PathBuf::new(),
+        mount_point,
+        super::default_mount_flags(),
+        span,
+    )
+}
+
+fn mount_with_flags(
+    runtime_handle: tokio::runtime::Handle,
+    repository: Arc<Repository>,
+    root: Utf8PathBuf,
+    mount_point: impl AsRef<Path>,
+    flags: MountFlags,
+    span: Option<tracing::Span>,
 ) -> Result<MountGuard, io::Error> {
     let options = MountOptions {
         single_thread: false,
-        flags: super::default_mount_flags(),
+        flags,
         ..Default::default()
     };
 
@@ -309,6 +367,7 @@ pub fn mount_with_span(
                 runtime_handle,
                 Arc::new(EntryIdGenerator::new()),
                 repository,
+                root,
             ),
             span,
         };
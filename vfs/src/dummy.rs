@@ -1,6 +1,7 @@
 //! Dummy implementation that does nothing. Used on OSes that don't support mounting.
 
 use crate::{MountError, MultiRepoMount};
+use camino::Utf8PathBuf;
 use ouisync_lib::Repository;
 use std::{
     future::{self, Future},
@@ -42,3 +43,20 @@ pub fn mount(
 ) -> Result<MountGuard, io::Error> {
     Err(io::ErrorKind::Unsupported.into())
 }
+
+pub fn mount_readonly(
+    _runtime_handle: tokio::runtime::Handle,
+    _repository: Arc<Repository>,
+    _mount_point: impl AsRef<Path>,
+) -> Result<MountGuard, io::Error> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+pub fn mount_subdir(
+    _runtime_handle: tokio::runtime::Handle,
+    _repository: Arc<Repository>,
+    _subdir: Utf8PathBuf,
+    _mount_point: impl AsRef<Path>,
+) -> Result<MountGuard, io::Error> {
+    Err(io::ErrorKind::Unsupported.into())
+}
@@ -1,4 +1,4 @@
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use fuser::FUSE_ROOT_ID;
 use ouisync_lib::{crypto::sign::PublicKey, Error, Result};
 use slab::Slab;
@@ -14,10 +14,13 @@ pub type Inode = u64;
 pub struct InodeMap {
     forward: Slab<InodeData>,
     reverse: HashMap<Key, Inode>,
+    // Path of the root inode relative to the repository root. Empty if the whole repository is
+    // mounted.
+    root: Utf8PathBuf,
 }
 
 impl InodeMap {
-    pub fn new() -> Self {
+    pub fn new(root: Utf8PathBuf) -> Self {
         // Create inode for the root directory
         let mut forward = Slab::with_capacity(1);
 
@@ -36,6 +39,7 @@ impl InodeMap {
         Self {
             forward,
             reverse: HashMap::new(),
+            root,
         }
     }
 
@@ -131,8 +135,8 @@ impl InodeMap {
             .expect("inode not found")
     }
 
-    // Returns an object that displays the absolute (from the repository root) path of a given
-    // inode. If `last` is `Some`, it is appended as the final component of the path. This is
+    // Returns an object that displays the absolute (from the repository root, not the mount root)
+    // path of a given inode. If `last` is `Some`, it is appended as the final component of the path. This is
     // useful for printing paths of non-existing entries.
     //
     // # Panics
@@ -143,12 +147,12 @@ impl InodeMap {
         inode: Inode,
         last: Option<&'a str>,
     ) -> impl fmt::Display + 'a {
-        PathDisplay(&self.forward, inode, last)
+        PathDisplay(&self.forward, &self.root, inode, last)
     }
 
     fn calculate_path(&self, inode_data: &InodeData) -> Utf8PathBuf {
         if inode_data.parent == 0 {
-            return Utf8PathBuf::new();
+            return self.root.clone();
         }
 
         self.calculate_path(self.get(inode_data.parent).data)
@@ -164,6 +168,7 @@ pub enum Representation {
     // corresponding to the path is requested, it'll be determined dynamically.
     Directory,
     File(PublicKey),
+    Link,
 }
 
 impl Representation {
@@ -171,6 +176,7 @@ impl Representation {
         match self {
             Self::Directory => Err(Error::EntryIsDirectory),
             Self::File(branch_id) => Ok(branch_id),
+            Self::Link => Err(Error::EntryIsLink),
         }
     }
 }
@@ -225,20 +231,24 @@ fn inode_to_index(inode: Inode) -> usize {
 }
 
 // Helper to display the full path of an inode. See `InodeMap::path_display` for more info.
-struct PathDisplay<'a>(&'a Slab<InodeData>, Inode, Option<&'a str>);
+struct PathDisplay<'a>(&'a Slab<InodeData>, &'a Utf8Path, Inode, Option<&'a str>);
 
 impl fmt::Display for PathDisplay<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt_inode_path(f, self.0, self.1)?;
+        let Self(map, root, inode, last) = *self;
 
-        if let Some(last) = self.2 {
-            if self.1 > FUSE_ROOT_ID {
-                write!(f, "/")?;
-            }
+        if !root.as_str().is_empty() {
+            write!(f, "/{root}")?;
+        }
 
-            write!(f, "{last}")
-        } else {
-            Ok(())
+        if inode > FUSE_ROOT_ID {
+            fmt_inode_path(f, map, inode)?;
+        }
+
+        match last {
+            Some(last) => write!(f, "/{last}"),
+            None if inode == FUSE_ROOT_ID && root.as_str().is_empty() => write!(f, "/"),
+            None => Ok(()),
         }
     }
 }
@@ -12,6 +12,7 @@ use self::{
     inode::{Inode, InodeMap, InodeView, Representation},
     utils::{FormatOptionScope, MaybeOwnedMut},
 };
+use camino::Utf8PathBuf;
 use fuser::{
     BackgroundSession, FileAttr, FileType, KernelConfig, MountOption, ReplyAttr, ReplyCreate,
     ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow,
@@ -47,10 +48,64 @@ pub fn mount(
     repository: Arc<Repository>,
     mount_point: impl AsRef<Path>,
 ) -> Result<MountGuard, io::Error> {
-    let session = fuser::spawn_mount2(
-        VirtualFilesystem::new(runtime_handle, repository),
+    mount_with_options(
+        runtime_handle,
+        repository,
+        Utf8PathBuf::new(),
         mount_point,
         &[MountOption::FSName(FS_NAME.into())],
+    )
+}
+
+/// Like [`mount`] but the filesystem is mounted read-only. Any attempt to modify it fails with
+/// `EROFS`, regardless of the access mode of the repository.
+pub fn mount_readonly(
+    runtime_handle: tokio::runtime::Handle,
+    repository: Arc<Repository>,
+    mount_point: impl AsRef<Path>,
+) -> Result<MountGuard, io::Error> {
+    mount_with_options(
+        runtime_handle,
+        repository,
+        Utf8PathBuf::new(),
+        mount_point,
+        &[MountOption::FSName(FS_NAME.into()), MountOption::RO],
+    )
+}
+
+/// Like [`mount`] but only the given subdirectory of the repository is mounted. The subdirectory
+/// becomes the root of the filesystem and the rest of the repository is not accessible through it.
+/// Fails with `InvalidInput` if `subdir` contains `..` components and with `NotFound` (wrapping
+/// `Error::EntryNotFound`) if it doesn't exist.
+pub fn mount_subdir(
+    runtime_handle: tokio::runtime::Handle,
+    repository: Arc<Repository>,
+    subdir: Utf8PathBuf,
+    mount_point: impl AsRef<Path>,
+) -> Result<MountGuard, io::Error> {
+    let root = crate::normalize_subdir(&subdir)?;
+    crate::check_subdir(&runtime_handle, &repository, &root)?;
+
+    mount_with_options(
+        runtime_handle,
+        repository,
+        root,
+        mount_point,
+        &[MountOption::FSName(FS_NAME.into())],
+    )
+}
+
+fn mount_with_options(
+    runtime_handle: tokio::runtime::Handle,
+    repository: Arc<Repository>,
+    root: Utf8PathBuf,
+    mount_point: impl AsRef<Path>,
+    options: &[MountOption],
+) -> Result<MountGuard, io::Error> {
+    let session = fuser::spawn_mount2(
+        VirtualFilesystem::new(runtime_handle, repository, root),
+        mount_point,
+        options,
     )?;
     Ok(MountGuard(Some(session)))
 }
@@ -121,12 +176,18 @@ struct VirtualFilesystem {
 }
 
 impl VirtualFilesystem {
-    fn new(runtime_handle: tokio::runtime::Handle, repository: Arc<Repository>) -> Self {
+    // `root` is the path (relative to the repository root) of the directory to be exposed as the
+    // root of the filesystem.
+    fn new(
+        runtime_handle: tokio::runtime::Handle,
+        repository: Arc<Repository>,
+        root: Utf8PathBuf,
+    ) -> Self {
         Self {
             rt: runtime_handle,
             inner: Inner {
                 repository,
-                inodes: InodeMap::new(),
+                inodes: InodeMap::new(root),
                 entries: EntryMap::default(),
             },
         }
@@ -164,6 +225,11 @@ impl fuser::Filesystem for VirtualFilesystem {
         reply.attr(&TTL, &attr)
     }
 
+    fn readlink(&mut self, _req: &Request, inode: Inode, reply: ReplyData) {
+        let data = try_request!(self.rt.block_on(self.inner.readlink(inode)), reply);
+        reply.data(&data);
+    }
+
     fn setattr(
         &mut self,
         _req: &Request,
@@ -435,6 +501,7 @@ impl Inner {
             JointEntryRef::Directory(entry) => {
                 (entry.open().await?.len(), Representation::Directory)
             }
+            JointEntryRef::Link(entry) => (entry.target().len() as u64, Representation::Link),
         };
 
         let inode = self.inodes.lookup(parent, entry.name(), name, repr);
@@ -453,7 +520,25 @@ impl Inner {
     async fn getattr(&mut self, inode: Inode) -> Result<FileAttr> {
         self.record_path(inode, None);
 
-        let entry = self.open_entry_by_inode(self.inodes.get(inode)).await?;
+        let inode_view = self.inodes.get(inode);
+
+        if let Representation::Link = inode_view.representation() {
+            let target = self
+                .repository
+                .read_link(inode_view.calculate_path())
+                .await?;
+
+            // TODO: uid, gid
+            return Ok(make_file_attr(
+                inode,
+                EntryType::Link,
+                target.len() as u64,
+                0,
+                0,
+            ));
+        }
+
+        let entry = self.open_entry_by_inode(inode_view).await?;
 
         // TODO: uid, gid
         Ok(make_file_attr_for_entry(&entry, inode, 0, 0).await)
@@ -548,6 +633,16 @@ impl Inner {
         ))
     }
 
+    #[instrument(skip(self, inode), fields(path), err(Debug))]
+    async fn readlink(&mut self, inode: Inode) -> Result<Vec<u8>> {
+        self.record_path(inode, None);
+
+        let path = self.inodes.get(inode).calculate_path();
+        let target = self.repository.read_link(path).await?;
+
+        Ok(target.into_bytes())
+    }
+
     #[instrument(skip(self, inode, flags), fields(path, ?flags), err(Debug))]
     async fn opendir(&mut self, inode: Inode, flags: OpenFlags) -> Result<FileHandle> {
         self.record_path(inode, None);
@@ -913,6 +1008,7 @@ impl Inner {
                 let file = self.repository.open_file_version(path, branch_id).await?;
                 Ok(JointEntry::File(file))
             }
+            Representation::Link => Err(Error::EntryIsLink),
         }
     }
 
@@ -951,6 +1047,7 @@ fn make_file_attr(inode: Inode, entry_type: EntryType, len: u64, uid: u32, gid:
         perm: match entry_type {
             EntryType::File => 0o444,      // TODO
             EntryType::Directory => 0o555, // TODO
+            EntryType::Link => 0o777,
         },
         nlink: 1,
         uid,
@@ -969,17 +1066,23 @@ fn to_error_code(error: &Error) -> libc::c_int {
         | Error::MalformedData
         | Error::MalformedDirectory
         | Error::Writer(_)
-        | Error::StorageVersionMismatch => libc::EIO,
+        | Error::Reader(_)
+        | Error::StorageVersionMismatch
+        | Error::ArchiveVersionMismatch
+        | Error::NotARepository => libc::EIO,
         Error::EntryNotFound | Error::AmbiguousEntry => libc::ENOENT,
         Error::EntryExists => libc::EEXIST,
         Error::EntryIsFile => libc::ENOTDIR,
         Error::EntryIsDirectory => libc::EISDIR,
-        Error::NonUtf8FileName | Error::InvalidArgument => libc::EINVAL,
+        Error::EntryIsLink => libc::EINVAL,
+        Error::TooManyLinks => libc::ELOOP,
+        Error::NonUtf8FileName | Error::InvalidArgument | Error::TokenExpired => libc::EINVAL,
         Error::OffsetOutOfRange => libc::EINVAL,
-        Error::PermissionDenied => libc::EACCES,
+        Error::PermissionDenied | Error::WrongPassword => libc::EACCES,
         Error::DirectoryNotEmpty => libc::ENOTEMPTY,
         Error::OperationNotSupported => libc::ENOTSUP,
-        Error::Locked => libc::EBUSY,
+        Error::Locked | Error::StoreLocked(_) => libc::EBUSY,
+        Error::QuotaExceeded => libc::EDQUOT,
     }
 }
 
@@ -987,5 +1090,6 @@ fn to_file_type(entry_type: EntryType) -> FileType {
     match entry_type {
         EntryType::File => FileType::RegularFile,
         EntryType::Directory => FileType::Directory,
+        EntryType::Link => FileType::Symlink,
     }
 }
@@ -3,7 +3,7 @@
 mod fuse;
 
 #[cfg(target_os = "linux")]
-pub use fuse::{mount, MountGuard, MultiRepoVFS};
+pub use fuse::{mount, mount_readonly, mount_subdir, MountGuard, MultiRepoVFS};
 
 // --- Windows ---------------------------------------------------------------------
 #[cfg(target_os = "windows")]
@@ -12,7 +12,7 @@ mod dokan;
 #[cfg(target_os = "windows")]
 pub use crate::dokan::{
     multi_repo_mount::MultiRepoVFS,
-    single_repo_mount::{mount, MountGuard},
+    single_repo_mount::{mount, mount_readonly, mount_subdir, MountGuard},
 };
 
 // --- Dummy -----------------------------------------------------------------------
@@ -20,20 +20,22 @@ pub use crate::dokan::{
 mod dummy;
 
 #[cfg(not(any(target_os = "linux", target_os = "windows")))]
-pub use dummy::{mount, MountGuard, MultiRepoVFS};
+pub use dummy::{mount, mount_readonly, mount_subdir, MountGuard, MultiRepoVFS};
 
 // ---------------------------------------------------------------------------------
 
 #[cfg(test)]
 mod tests;
 
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use ouisync_lib::Repository;
 use std::{
     future::Future,
-    io,
+    io, panic,
     path::{Path, PathBuf},
     pin::Pin,
     sync::Arc,
+    thread,
 };
 use thiserror::Error;
 
@@ -60,3 +62,56 @@ pub enum MountError {
     #[error("Backend error")]
     Backend(#[source] Box<dyn std::error::Error + Send + 'static>),
 }
+
+/// Normalizes the path of the subdirectory to be mounted as the root of the filesystem. Fails with
+/// `InvalidInput` if the path contains components that would make it escape the repository root.
+#[cfg_attr(not(any(target_os = "linux", target_os = "windows")), allow(dead_code))]
+fn normalize_subdir(subdir: &Utf8Path) -> Result<Utf8PathBuf, io::Error> {
+    let mut normalized = Utf8PathBuf::new();
+
+    for component in subdir.components() {
+        match component {
+            Utf8Component::RootDir | Utf8Component::CurDir => (),
+            Utf8Component::Normal(name) => normalized.push(name),
+            Utf8Component::ParentDir | Utf8Component::Prefix(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid subdirectory {subdir:?}"),
+                ))
+            }
+        }
+    }
+
+    Ok(normalized)
+}
+
+/// Checks that the (normalized) subdirectory `root` exists in the repository and is a directory,
+/// so mounting a missing one fails up front instead of producing a filesystem whose every
+/// operation fails. Fails with `NotFound` wrapping `Error::EntryNotFound` if it doesn't exist.
+#[cfg_attr(not(any(target_os = "linux", target_os = "windows")), allow(dead_code))]
+fn check_subdir(
+    runtime_handle: &tokio::runtime::Handle,
+    repository: &Repository,
+    root: &Utf8Path,
+) -> Result<(), io::Error> {
+    // Run on a separate thread because `block_on` panics when called from within the runtime,
+    // which is where the mount functions are typically called from.
+    let result = thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                runtime_handle
+                    .block_on(repository.open_directory(root))
+                    .map(|_| ())
+            })
+            .join()
+    })
+    .unwrap_or_else(|payload| panic::resume_unwind(payload));
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(error @ ouisync_lib::Error::EntryNotFound) => {
+            Err(io::Error::new(io::ErrorKind::NotFound, error))
+        }
+        Err(error) => Err(io::Error::new(io::ErrorKind::Other, error)),
+    }
+}
@@ -279,6 +279,77 @@ async fn append_to_file(setup: Setup) {
 
 // -----------------------------------------------------------------------------
 
+#[tokio::test(flavor = "multi_thread")]
+async fn overwrite_across_block_boundary_single() {
+    let setup = Setup::new_single("").await;
+    overwrite_across_block_boundary(setup).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn overwrite_across_block_boundary_multi() {
+    let setup = Setup::new_multi("").await;
+    overwrite_across_block_boundary(setup).await
+}
+
+// ----------------------------------
+
+async fn overwrite_across_block_boundary(setup: Setup) {
+    let path = setup.mount_dir_path().join("file.txt");
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut expected: Vec<u8> = (&mut rng).sample_iter(Standard).take(100_000).collect();
+    let patch: Vec<u8> = (&mut rng).sample_iter(Standard).take(1000).collect();
+
+    fs::write(&path, &expected).await.unwrap();
+
+    // Write at an offset which is not block-aligned and which makes the write span two blocks.
+    let offset = 65_000;
+
+    let mut file = OpenOptions::new().write(true).open(&path).await.unwrap();
+    file.seek(SeekFrom::Start(offset as u64)).await.unwrap();
+    file.write_all(&patch).await.unwrap();
+    file.sync_all().await.unwrap();
+    drop(file);
+
+    expected[offset..offset + patch.len()].copy_from_slice(&patch);
+
+    let actual = fs::read(path).await.unwrap();
+    assert_eq!(actual.len(), expected.len());
+    assert!(actual == expected);
+}
+
+// -----------------------------------------------------------------------------
+
+#[tokio::test(flavor = "multi_thread")]
+async fn truncate_file_single() {
+    let setup = Setup::new_single("").await;
+    truncate_file(setup).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn truncate_file_multi() {
+    let setup = Setup::new_multi("").await;
+    truncate_file(setup).await
+}
+
+// ----------------------------------
+
+async fn truncate_file(setup: Setup) {
+    let path = setup.mount_dir_path().join("file.txt");
+
+    fs::write(&path, b"foobar").await.unwrap();
+
+    let file = OpenOptions::new().write(true).open(&path).await.unwrap();
+    file.set_len(3).await.unwrap();
+    file.sync_all().await.unwrap();
+    drop(file);
+
+    assert_eq!(fs::read(&path).await.unwrap(), b"foo");
+    assert_eq!(fs::metadata(&path).await.unwrap().len(), 3);
+}
+
+// -----------------------------------------------------------------------------
+
 #[proptest]
 fn seek_and_read_single(
     #[strategy(0usize..64 * 1024)] len: usize,
@@ -358,6 +429,106 @@ async fn move_file(setup: Setup) {
 
 // -----------------------------------------------------------------------------
 
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "multi_thread")]
+async fn read_only_mount() {
+    init_log();
+
+    let base_dir = TempDir::new().unwrap();
+    let repo = Setup::create_repo(&base_dir.path().join("repo.db"), tracing::Span::none()).await;
+
+    let mut file = repo.create_file("test.txt").await.unwrap();
+    file.write_all(b"blah").await.unwrap();
+    file.flush().await.unwrap();
+    drop(file);
+
+    let mount_dir = base_dir.path().join("mnt");
+    fs::create_dir(&mount_dir).await.unwrap();
+
+    let _guard =
+        super::mount_readonly(tokio::runtime::Handle::current(), repo, &mount_dir).unwrap();
+
+    assert_eq!(fs::read(mount_dir.join("test.txt")).await.unwrap(), b"blah");
+
+    let error = fs::write(mount_dir.join("test.txt"), b"bleh")
+        .await
+        .unwrap_err();
+    assert_eq!(error.raw_os_error(), Some(libc::EROFS));
+
+    let error = fs::create_dir(mount_dir.join("dir")).await.unwrap_err();
+    assert_eq!(error.raw_os_error(), Some(libc::EROFS));
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test(flavor = "multi_thread")]
+async fn subdir_mount() {
+    init_log();
+
+    let base_dir = TempDir::new().unwrap();
+    let repo = Setup::create_repo(&base_dir.path().join("repo.db"), tracing::Span::none()).await;
+
+    repo.create_directory("shared").await.unwrap();
+
+    for (path, content) in [("shared/a.txt", b"shared"), ("private.txt", b"secret")] {
+        let mut file = repo.create_file(path).await.unwrap();
+        file.write_all(content).await.unwrap();
+        file.flush().await.unwrap();
+    }
+
+    let mount_dir = base_dir.path().join("mnt");
+    fs::create_dir(&mount_dir).await.unwrap();
+
+    assert_eq!(
+        super::mount_subdir(
+            tokio::runtime::Handle::current(),
+            repo.clone(),
+            "shared/..".into(),
+            &mount_dir,
+        )
+        .err()
+        .map(|error| error.kind()),
+        Some(ErrorKind::InvalidInput)
+    );
+
+    let error = super::mount_subdir(
+        tokio::runtime::Handle::current(),
+        repo.clone(),
+        "missing".into(),
+        &mount_dir,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+    assert!(matches!(
+        error
+            .get_ref()
+            .and_then(|error| error.downcast_ref::<ouisync_lib::Error>()),
+        Some(ouisync_lib::Error::EntryNotFound)
+    ));
+
+    let _guard = super::mount_subdir(
+        tokio::runtime::Handle::current(),
+        repo.clone(),
+        "shared".into(),
+        &mount_dir,
+    )
+    .unwrap();
+
+    let entries = read_dir(&mount_dir).await;
+    assert_eq!(entries.len(), 1);
+    assert!(entries.contains_key(OsStr::new("a.txt")));
+
+    assert_eq!(fs::read(mount_dir.join("a.txt")).await.unwrap(), b"shared");
+
+    // Writes go into the subdirectory.
+    fs::write(mount_dir.join("b.txt"), b"new").await.unwrap();
+
+    let mut file = repo.open_file("shared/b.txt").await.unwrap();
+    assert_eq!(file.read_to_end().await.unwrap(), b"new");
+}
+
+// -----------------------------------------------------------------------------
+
 // proptest doesn't work with the `#[tokio::test]` macro yet
 // (see https://github.com/AltSysrq/proptest/issues/179). As a workaround, create the runtime
 // manually.